@@ -17,6 +17,7 @@ struct OneDocFuzzer {
 impl OneDocFuzzer {
     pub fn new(site_num: usize) -> Self {
         let doc = LoroDoc::new();
+        doc.set_random_seed(Some(0));
         doc.set_detached_editing(true);
         Self {
             doc,
@@ -20,6 +20,12 @@ pub enum LoroError {
     DecodeDataCorruptionError,
     #[error("Decode error: Checksum mismatch. The data is corrupted.")]
     DecodeChecksumMismatchError,
+    #[error("Decode error: The {limit} is {actual}, which exceeds the limit {max}.")]
+    DecodeLimitExceeded {
+        limit: DecodeLimit,
+        max: usize,
+        actual: usize,
+    },
     #[error("Decode error: Encoding version \"{0}\" is incompatible. Loro's encoding is backward compatible but not forward compatible. Please upgrade the version of Loro to support this version of the exported data.")]
     IncompatibleFutureEncodingError(usize),
     #[error("Js error ({0})")]
@@ -54,6 +60,25 @@ pub enum LoroError {
     AutoCommitNotStarted,
     #[error("Style configuration missing for \"({0:?})\". Please provide the style configuration using `configTextStyle` on your Loro doc.")]
     StyleConfigMissing(InternalString),
+    #[error("The value of the style \"{key}\" should be {expected}")]
+    InvalidStyleValue {
+        key: InternalString,
+        expected: &'static str,
+    },
+    #[error("The counter would overflow its representable range")]
+    CounterOverflow,
+    #[error(
+        "The doc only accepts the ops of its single writer {writer}, but got the ops of {peer}"
+    )]
+    SingleWriterViolation { writer: PeerID, peer: PeerID },
+    #[error("The state of the container {0} doesn't match its checksum in the snapshot")]
+    ContainerChecksumMismatch(Box<ContainerID>),
+    #[error("Decode error: The blob is compressed with the dictionary {0:#010x}, which is not registered in the doc")]
+    CompressionDictionaryNotFound(u32),
+    #[error("The doc is frozen and can't be edited anymore: {0}")]
+    DocFrozen(Box<str>),
+    #[error("The value type {0} can't be decoded by the older versions of Loro. Enable it with `LoroDoc::set_extended_value_types` if all the peers support it")]
+    ExtendedValueTypeDisabled(Box<str>),
     #[error("Unknown Error ({0})")]
     Unknown(Box<str>),
     #[error("The given ID ({0}) is not contained by the doc")]
@@ -96,6 +121,35 @@ pub enum LoroError {
     InvalidPeerID,
     #[error("The containers {containers:?} are not found in the doc")]
     ContainersNotFound { containers: Box<Vec<ContainerID>> },
+    #[error("Import Failed: The types of the containers {containers:?} conflict with the containers created with the same ids")]
+    ContainerTypeConflict { containers: Box<Vec<ContainerID>> },
+}
+
+/// The decode limits that can be exceeded by an imported blob
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DecodeLimit {
+    /// The number of the changes in a blob
+    Changes,
+    /// The number of the ops, counted in atoms, in a change
+    OpsPerChange,
+    /// The length in bytes of a string value or a text insertion
+    StringLength,
+    /// The length of a binary value
+    BinaryLength,
+    /// The number of the elements deleted by a delete op
+    DeleteSpan,
+}
+
+impl std::fmt::Display for DecodeLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Changes => "number of changes",
+            Self::OpsPerChange => "number of ops in a change",
+            Self::StringLength => "length of a string",
+            Self::BinaryLength => "length of a binary",
+            Self::DeleteSpan => "length of a deletion",
+        })
+    }
 }
 
 #[derive(Error, Debug, PartialEq)]
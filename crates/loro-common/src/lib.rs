@@ -13,13 +13,14 @@ mod macros;
 mod span;
 mod value;
 
-pub use error::{LoroEncodeError, LoroError, LoroResult, LoroTreeError};
+pub use error::{DecodeLimit, LoroEncodeError, LoroError, LoroResult, LoroTreeError};
 #[doc(hidden)]
 pub use fxhash::FxHashMap;
 pub use internal_string::InternalString;
 pub use span::*;
 pub use value::{
-    to_value, LoroBinaryValue, LoroListValue, LoroMapValue, LoroStringValue, LoroValue,
+    to_value, LoroBinaryValue, LoroDecimal, LoroListValue, LoroMapValue, LoroStringValue,
+    LoroValue, LORO_CONTAINER_REF_TAG, LORO_DECIMAL_TAG, LORO_ESCAPED_MAP_TAG, LORO_TIMESTAMP_TAG,
 };
 
 /// Unique id for each peer. It's a random u64 by default.
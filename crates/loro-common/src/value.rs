@@ -3,7 +3,7 @@ use std::{collections::HashMap, hash::Hash, ops::Index, sync::Arc};
 use arbitrary::Arbitrary;
 use enum_as_inner::EnumAsInner;
 use fxhash::FxHashMap;
-use serde::{de::VariantAccess, Deserialize, Serialize};
+use serde::{de::VariantAccess, ser::SerializeMap, Deserialize, Serialize};
 
 use crate::ContainerID;
 
@@ -24,6 +24,30 @@ pub enum LoroValue {
     // PERF We can use InternalString as key
     Map(LoroMapValue),
     Container(ContainerID),
+    /// A non-owning reference to a container.
+    ///
+    /// Unlike [`LoroValue::Container`], it doesn't make the target a child of the container
+    /// holding the value, so it doesn't affect the hierarchy or the deletion of the target.
+    /// The target may not exist or may have been deleted, i.e. the reference can be dangling.
+    ///
+    /// The older versions of Loro can't decode it, so a doc only writes it after opting in
+    /// with `LoroDoc::set_extended_value_types`. In JSON and JS it's represented by the
+    /// object `{"🦜ref": "<container id>"}`, see [`LORO_CONTAINER_REF_TAG`], which is only
+    /// parsed back by [`LoroValue::parse_tags`].
+    ContainerRef(ContainerID),
+    /// A point in time, in milliseconds since the Unix epoch
+    ///
+    /// Like [`LoroValue::ContainerRef`], it's only written after opting in with
+    /// `LoroDoc::set_extended_value_types`. It's a `Date` in JS, and the object
+    /// `{"🦜ts": <milliseconds>}` in JSON, see [`LORO_TIMESTAMP_TAG`]. A JS `Date` is
+    /// converted to that object too, so it only becomes a timestamp in a doc that opted in.
+    Timestamp(i64),
+    /// An arbitrary-precision decimal number
+    ///
+    /// Like [`LoroValue::ContainerRef`], it's only written after opting in with
+    /// `LoroDoc::set_extended_value_types`. It's the object `{"🦜dec": "<decimal>"}` in
+    /// JSON and JS, see [`LORO_DECIMAL_TAG`].
+    Decimal(LoroDecimal),
 }
 
 #[derive(Default, Debug, PartialEq, Clone, Arbitrary)]
@@ -35,6 +59,89 @@ pub struct LoroListValue(Arc<Vec<LoroValue>>);
 #[derive(Default, Debug, PartialEq, Clone, Arbitrary)]
 pub struct LoroMapValue(Arc<FxHashMap<String, LoroValue>>);
 
+/// An arbitrary-precision decimal number, e.g. `-12.05`.
+///
+/// It's stored in the canonical form, which has no leading zeros in the integer part, no
+/// trailing zeros in the fraction and no negative zero, so the equal numbers are represented
+/// the same way. It's cheap to clone.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct LoroDecimal(Arc<str>);
+
+impl LoroDecimal {
+    /// The decimal `mantissa * 10^(-scale)`
+    pub fn new(mantissa: i128, scale: u32) -> Self {
+        let digits = mantissa.unsigned_abs().to_string();
+        let scale = scale as usize;
+        let s = if digits.len() > scale {
+            let (int, frac) = digits.split_at(digits.len() - scale);
+            format!("{int}.{frac}")
+        } else {
+            format!("0.{}{digits}", "0".repeat(scale - digits.len()))
+        };
+        let s = if mantissa < 0 { format!("-{s}") } else { s };
+        Self::parse(&s).unwrap()
+    }
+
+    /// Parse a decimal in the form of `[+-]digits[.digits]`
+    pub fn parse(s: &str) -> Option<Self> {
+        let (negative, s) = match s.as_bytes().first()? {
+            b'-' => (true, &s[1..]),
+            b'+' => (false, &s[1..]),
+            _ => (false, s),
+        };
+        let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+        if (int.is_empty() && frac.is_empty())
+            || !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+
+        let int = int.trim_start_matches('0');
+        let frac = frac.trim_end_matches('0');
+        let mut ans = String::with_capacity(int.len() + frac.len() + 3);
+        if negative && !(int.is_empty() && frac.is_empty()) {
+            ans.push('-');
+        }
+        ans.push_str(if int.is_empty() { "0" } else { int });
+        if !frac.is_empty() {
+            ans.push('.');
+            ans.push_str(frac);
+        }
+
+        Some(Self(ans.into()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The nearest `f64`
+    pub fn to_f64(&self) -> f64 {
+        self.0.parse().unwrap()
+    }
+}
+
+impl std::fmt::Display for LoroDecimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::str::FromStr for LoroDecimal {
+    type Err = crate::LoroError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+            .ok_or_else(|| crate::LoroError::ArgErr(format!("Invalid decimal: {s}").into()))
+    }
+}
+
+impl From<LoroDecimal> for LoroValue {
+    fn from(value: LoroDecimal) -> Self {
+        Self::Decimal(value)
+    }
+}
+
 impl From<Vec<u8>> for LoroBinaryValue {
     fn from(value: Vec<u8>) -> Self {
         Self(Arc::new(value))
@@ -109,12 +216,22 @@ impl LoroBinaryValue {
     pub fn make_mut(&mut self) -> &mut Vec<u8> {
         Arc::make_mut(&mut self.0)
     }
+
+    /// The number of values sharing the allocation of this binary
+    pub fn ref_count(&self) -> usize {
+        Arc::strong_count(&self.0)
+    }
 }
 
 impl LoroStringValue {
     pub fn make_mut(&mut self) -> &mut String {
         Arc::make_mut(&mut self.0)
     }
+
+    /// The number of values sharing the allocation of this string
+    pub fn ref_count(&self) -> usize {
+        Arc::strong_count(&self.0)
+    }
 }
 
 impl LoroListValue {
@@ -220,6 +337,85 @@ impl<'a> arbitrary::Arbitrary<'a> for LoroValue {
 }
 
 impl LoroValue {
+    /// Parse the values that are represented by single-entry tagged objects in JSON and JS,
+    /// e.g. `{"🦜ref": "cid:root-a:Map"}` for a [`LoroValue::ContainerRef`],
+    /// `{"🦜ts": 1700000000000}` for a [`LoroValue::Timestamp`] and `{"🦜dec": "12.05"}`
+    /// for a [`LoroValue::Decimal`].
+    ///
+    /// Strings are never parsed as such values, so the user strings keep their type on a
+    /// JSON round trip. See [`LoroValue::parse_tags`] for the nested values.
+    pub fn try_from_tagged_map(map: &FxHashMap<String, LoroValue>) -> Option<Self> {
+        if map.len() != 1 {
+            return None;
+        }
+
+        let (tag, value) = map.iter().next().unwrap();
+        match (tag.as_str(), value) {
+            (LORO_CONTAINER_REF_TAG, Self::String(id)) => ContainerID::try_from(id.as_str())
+                .ok()
+                .map(Self::ContainerRef),
+            (LORO_TIMESTAMP_TAG, Self::I64(t)) => Some(Self::Timestamp(*t)),
+            (LORO_DECIMAL_TAG, Self::String(d)) => LoroDecimal::parse(d).map(Self::Decimal),
+            _ => None,
+        }
+    }
+
+    /// Parse the tagged objects in the value and its nested values, see
+    /// [`LoroValue::try_from_tagged_map`], and unwrap the maps escaped with
+    /// [`LORO_ESCAPED_MAP_TAG`].
+    ///
+    /// The conversions from JSON, serde and JS never parse the tags, so a user map that looks
+    /// like a tagged object keeps its type. The callers that expect the extended value types
+    /// opt in by calling this, e.g. the import of JSON updates and the local ops of a doc
+    /// with `LoroDoc::set_extended_value_types` enabled.
+    pub fn parse_tags(self) -> Self {
+        match self {
+            Self::List(list) if list.iter().any(Self::has_tagged_map) => {
+                Self::List(list.unwrap().into_iter().map(Self::parse_tags).collect())
+            }
+            Self::Map(map) if Self::has_tagged_map_in(&map) => {
+                if let Some(v) = Self::try_from_tagged_map(&map) {
+                    return v;
+                }
+
+                let escaped = match map.get(LORO_ESCAPED_MAP_TAG) {
+                    Some(Self::Map(inner)) if map.len() == 1 => Some(inner.clone()),
+                    _ => None,
+                };
+                let map = escaped.unwrap_or(map).unwrap();
+                Self::Map(map.into_iter().map(|(k, v)| (k, v.parse_tags())).collect())
+            }
+            v => v,
+        }
+    }
+
+    /// Whether the map has a single key, which is one of the tags, so that it's escaped with
+    /// [`LORO_ESCAPED_MAP_TAG`] in JSON and JS
+    pub fn is_tagged_map(map: &FxHashMap<String, LoroValue>) -> bool {
+        map.len() == 1
+            && map.keys().all(|k| {
+                [
+                    LORO_CONTAINER_REF_TAG,
+                    LORO_TIMESTAMP_TAG,
+                    LORO_DECIMAL_TAG,
+                    LORO_ESCAPED_MAP_TAG,
+                ]
+                .contains(&k.as_str())
+            })
+    }
+
+    fn has_tagged_map(&self) -> bool {
+        match self {
+            Self::List(list) => list.iter().any(Self::has_tagged_map),
+            Self::Map(map) => Self::has_tagged_map_in(map),
+            _ => false,
+        }
+    }
+
+    fn has_tagged_map_in(map: &FxHashMap<String, LoroValue>) -> bool {
+        Self::is_tagged_map(map) || map.values().any(Self::has_tagged_map)
+    }
+
     pub fn get_by_key(&self, key: &str) -> Option<&Self> {
         match self {
             Self::Map(map) => map.get(key),
@@ -439,6 +635,15 @@ impl Hash for LoroValue {
             Self::Container(v) => {
                 v.hash(state);
             }
+            Self::ContainerRef(v) => {
+                v.hash(state);
+            }
+            Self::Timestamp(v) => {
+                state.write_i64(*v);
+            }
+            Self::Decimal(v) => {
+                v.hash(state);
+            }
         }
     }
 }
@@ -550,7 +755,7 @@ impl From<ContainerID> for LoroValue {
 #[cfg(feature = "wasm")]
 pub mod wasm {
     use fxhash::FxHashMap;
-    use js_sys::{Array, Object, Uint8Array};
+    use js_sys::{Array, Date, Object, Uint8Array};
     use wasm_bindgen::{JsCast, JsValue, __rt::IntoJsResult};
 
     use crate::{ContainerID, LoroError, LoroValue};
@@ -580,15 +785,48 @@ pub mod wasm {
             }
             LoroValue::Map(m) => {
                 let m = m.unwrap();
+                let escaped = LoroValue::is_tagged_map(&m);
                 let map = Object::new();
                 for (k, v) in m.into_iter() {
                     let str: &str = &k;
                     js_sys::Reflect::set(&map, &JsValue::from_str(str), &convert(v)).unwrap();
                 }
 
+                if escaped {
+                    let wrapper = Object::new();
+                    js_sys::Reflect::set(
+                        &wrapper,
+                        &JsValue::from_str(super::LORO_ESCAPED_MAP_TAG),
+                        &map,
+                    )
+                    .unwrap();
+                    return wrapper.into_js_result().unwrap();
+                }
+
                 map.into_js_result().unwrap()
             }
             LoroValue::Container(container_id) => JsValue::from(&container_id),
+            LoroValue::ContainerRef(container_id) => {
+                let map = Object::new();
+                js_sys::Reflect::set(
+                    &map,
+                    &JsValue::from_str(crate::LORO_CONTAINER_REF_TAG),
+                    &JsValue::from(&container_id),
+                )
+                .unwrap();
+                map.into_js_result().unwrap()
+            }
+            LoroValue::Timestamp(t) => Date::new(&JsValue::from_f64(t as f64)).into(),
+            LoroValue::Decimal(d) => {
+                let map = Object::new();
+                js_sys::Reflect::set(
+                    &map,
+                    &JsValue::from_str(super::LORO_DECIMAL_TAG),
+                    &JsValue::from_str(d.as_str()),
+                )
+                .unwrap();
+                map.into_js_result().unwrap()
+            }
         }
     }
 
@@ -613,6 +851,14 @@ pub mod wasm {
                 }
             } else if js_value.is_string() {
                 Self::String(js_value.as_string().unwrap().into())
+            } else if js_value.is_instance_of::<Date>() {
+                // Parsed as a timestamp by the docs with the extended value types enabled
+                let time = js_value.unchecked_into::<Date>().get_time() as i64;
+                Self::Map(
+                    [(super::LORO_TIMESTAMP_TAG.to_string(), Self::I64(time))]
+                        .into_iter()
+                        .collect(),
+                )
             } else if js_value.has_type::<Array>() {
                 let array = js_value.unchecked_into::<Array>();
                 let mut list = Vec::new();
@@ -674,6 +920,19 @@ pub mod wasm {
 }
 
 const LORO_CONTAINER_ID_PREFIX: &str = "🦜:";
+/// The key of the single-entry object representing a [`LoroValue::ContainerRef`] in JSON
+/// and JS, whose value is the id of the target, e.g. `{"🦜ref": "cid:root-a:Map"}`
+pub const LORO_CONTAINER_REF_TAG: &str = "🦜ref";
+/// The key of the single-entry object representing a [`LoroValue::Timestamp`] in JSON,
+/// whose value is the number of milliseconds, e.g. `{"🦜ts": 1700000000000}`
+pub const LORO_TIMESTAMP_TAG: &str = "🦜ts";
+/// The key of the single-entry object representing a [`LoroValue::Decimal`] in JSON and JS,
+/// whose value is the canonical string of the decimal, e.g. `{"🦜dec": "12.05"}`
+pub const LORO_DECIMAL_TAG: &str = "🦜dec";
+/// The key of the single-entry object wrapping a user map that would otherwise be read as a
+/// tagged object, e.g. `{"🦜map": {"🦜ts": 5}}` for the map `{"🦜ts": 5}`. It's added when a
+/// value is converted to JSON or JS and removed by [`LoroValue::parse_tags`].
+pub const LORO_ESCAPED_MAP_TAG: &str = "🦜map";
 
 impl Serialize for LoroValue {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -690,10 +949,30 @@ impl Serialize for LoroValue {
                 Self::String(s) => serializer.serialize_str(s),
                 Self::Binary(b) => serializer.collect_seq(b.iter()),
                 Self::List(l) => serializer.collect_seq(l.iter()),
-                Self::Map(m) => serializer.collect_map(m.iter()),
+                Self::Map(m) if Self::is_tagged_map(m) => {
+                    let mut map = serializer.serialize_map(Some(1))?;
+                    map.serialize_entry(LORO_ESCAPED_MAP_TAG, m)?;
+                    map.end()
+                }
+                Self::Map(m) => m.serialize(serializer),
                 Self::Container(id) => {
                     serializer.serialize_str(&format!("{}{}", LORO_CONTAINER_ID_PREFIX, id))
                 }
+                Self::ContainerRef(id) => {
+                    let mut map = serializer.serialize_map(Some(1))?;
+                    map.serialize_entry(LORO_CONTAINER_REF_TAG, &id.to_string())?;
+                    map.end()
+                }
+                Self::Timestamp(t) => {
+                    let mut map = serializer.serialize_map(Some(1))?;
+                    map.serialize_entry(LORO_TIMESTAMP_TAG, t)?;
+                    map.end()
+                }
+                Self::Decimal(d) => {
+                    let mut map = serializer.serialize_map(Some(1))?;
+                    map.serialize_entry(LORO_DECIMAL_TAG, d.as_str())?;
+                    map.end()
+                }
             }
         } else {
             // binary type
@@ -709,18 +988,40 @@ impl Serialize for LoroValue {
                 }
 
                 Self::List(l) => serializer.serialize_newtype_variant("LoroValue", 5, "List", &**l),
-                Self::Map(m) => serializer.serialize_newtype_variant("LoroValue", 6, "Map", &**m),
+                Self::Map(m) => serializer.serialize_newtype_variant("LoroValue", 6, "Map", m),
                 Self::Container(id) => {
                     serializer.serialize_newtype_variant("LoroValue", 7, "Container", id)
                 }
                 Self::Binary(b) => {
                     serializer.serialize_newtype_variant("LoroValue", 8, "Binary", &**b)
                 }
+                Self::ContainerRef(id) => {
+                    serializer.serialize_newtype_variant("LoroValue", 9, "ContainerRef", id)
+                }
+                Self::Timestamp(t) => {
+                    serializer.serialize_newtype_variant("LoroValue", 10, "Timestamp", t)
+                }
+                Self::Decimal(d) => {
+                    serializer.serialize_newtype_variant("LoroValue", 11, "Decimal", d.as_str())
+                }
             }
         }
     }
 }
 
+/// The entries are serialized in the order of their keys, so that the equal maps are always
+/// encoded to the same bytes, whatever the order of the underlying hash map is.
+impl Serialize for LoroMapValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut entries: Vec<_> = self.0.iter().collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        serializer.collect_map(entries)
+    }
+}
+
 impl<'de> Deserialize<'de> for LoroValue {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -741,6 +1042,9 @@ impl<'de> Deserialize<'de> for LoroValue {
                     "Map",
                     "Container",
                     "Binary",
+                    "ContainerRef",
+                    "Timestamp",
+                    "Decimal",
                 ],
                 LoroValueEnumVisitor,
             )
@@ -870,6 +1174,9 @@ enum LoroValueFields {
     Map,
     Container,
     Binary,
+    ContainerRef,
+    Timestamp,
+    Decimal,
 }
 
 struct LoroValueEnumVisitor;
@@ -904,6 +1211,14 @@ impl<'de> serde::de::Visitor<'de> for LoroValueEnumVisitor {
             (LoroValueFields::Binary, v) => v
                 .newtype_variant()
                 .map(|x: Vec<u8>| LoroValue::Binary(x.into())),
+            (LoroValueFields::ContainerRef, v) => v.newtype_variant().map(LoroValue::ContainerRef),
+            (LoroValueFields::Timestamp, v) => v.newtype_variant().map(LoroValue::Timestamp),
+            (LoroValueFields::Decimal, v) => {
+                let s: String = v.newtype_variant()?;
+                LoroDecimal::parse(&s)
+                    .map(LoroValue::Decimal)
+                    .ok_or_else(|| serde::de::Error::custom("Invalid decimal"))
+            }
         }
     }
 }
@@ -914,6 +1229,7 @@ pub fn to_value<T: Into<LoroValue>>(value: T) -> LoroValue {
 
 #[cfg(feature = "serde_json")]
 mod serde_json_impl {
+    use fxhash::FxHashMap;
     use serde_json::{Number, Value};
 
     use super::LoroValue;
@@ -933,13 +1249,18 @@ mod serde_json_impl {
                 Value::String(s) => Self::String(s.into()),
                 Value::Array(arr) => Self::List(arr.into_iter().map(Self::from).collect()),
                 Value::Object(obj) => {
-                    Self::Map(obj.into_iter().map(|(k, v)| (k, Self::from(v))).collect())
+                    let map: FxHashMap<String, Self> =
+                        obj.into_iter().map(|(k, v)| (k, Self::from(v))).collect();
+                    Self::Map(map.into())
                 }
             }
         }
     }
 
-    use super::LORO_CONTAINER_ID_PREFIX;
+    use super::{
+        LORO_CONTAINER_ID_PREFIX, LORO_CONTAINER_REF_TAG, LORO_DECIMAL_TAG, LORO_ESCAPED_MAP_TAG,
+        LORO_TIMESTAMP_TAG,
+    };
     impl From<LoroValue> for Value {
         fn from(value: LoroValue) -> Self {
             match value {
@@ -949,14 +1270,46 @@ mod serde_json_impl {
                 LoroValue::I64(i) => Self::Number(Number::from(i)),
                 LoroValue::String(s) => Self::String(s.to_string()),
                 LoroValue::List(l) => Self::Array(l.iter().cloned().map(Self::from).collect()),
-                LoroValue::Map(m) => Self::Object(
-                    m.iter()
-                        .map(|(k, v)| (k.clone(), Self::from(v.clone())))
-                        .collect(),
-                ),
+                LoroValue::Map(m) => {
+                    let object = Self::Object(
+                        m.iter()
+                            .map(|(k, v)| (k.clone(), Self::from(v.clone())))
+                            .collect(),
+                    );
+                    if LoroValue::is_tagged_map(&m) {
+                        Self::Object(
+                            [(LORO_ESCAPED_MAP_TAG.to_string(), object)]
+                                .into_iter()
+                                .collect(),
+                        )
+                    } else {
+                        object
+                    }
+                }
                 LoroValue::Container(id) => {
                     Self::String(format!("{}{}", LORO_CONTAINER_ID_PREFIX, id))
                 }
+                LoroValue::ContainerRef(id) => Self::Object(
+                    [(
+                        LORO_CONTAINER_REF_TAG.to_string(),
+                        Self::String(id.to_string()),
+                    )]
+                    .into_iter()
+                    .collect(),
+                ),
+                LoroValue::Timestamp(t) => Self::Object(
+                    [(
+                        LORO_TIMESTAMP_TAG.to_string(),
+                        Self::Number(Number::from(t)),
+                    )]
+                    .into_iter()
+                    .collect(),
+                ),
+                LoroValue::Decimal(d) => Self::Object(
+                    [(LORO_DECIMAL_TAG.to_string(), Self::String(d.to_string()))]
+                        .into_iter()
+                        .collect(),
+                ),
                 LoroValue::Binary(b) => Self::Array(b.iter().copied().map(Self::from).collect()),
             }
         }
@@ -3,7 +3,7 @@ use std::{fmt::Display, sync::Arc};
 use loro::TextDelta as InternalTextDelta;
 use loro::{cursor::Side, ContainerTrait, LoroResult, PeerID, UpdateOptions, UpdateTimeoutError};
 
-use crate::{ContainerID, LoroDoc, LoroValue, LoroValueLike, TextDelta};
+use crate::{ContainerID, LoroDoc, LoroValue, LoroValueLike, ParagraphId, TextDelta};
 
 use super::Cursor;
 
@@ -108,6 +108,15 @@ impl LoroText {
         self.inner.len_utf16() as u32
     }
 
+    /// Get the ids of the paragraphs in document order.
+    ///
+    /// Returns `None` if the text is detached.
+    pub fn paragraph_ids(&self) -> Option<Vec<ParagraphId>> {
+        self.inner
+            .paragraph_ids()
+            .map(|ids| ids.into_iter().map(|id| id.into()).collect())
+    }
+
     /// Update the current text based on the provided text.
     pub fn update(&self, text: &str, options: UpdateOptions) -> Result<(), UpdateTimeoutError> {
         self.inner.update(text, options)
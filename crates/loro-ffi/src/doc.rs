@@ -9,7 +9,8 @@ use std::{
 use loro::{
     cursor::CannotFindRelativePosition, ChangeTravelError, CounterSpan, DocAnalysis,
     FrontiersNotIncluded, IdSpan, JsonPathError, JsonSchema, Lamport, LoroDoc as InnerLoroDoc,
-    LoroEncodeError, LoroError, LoroResult, PeerID, StyleConfig, Timestamp, VersionRange, ID,
+    LoroEncodeError, LoroError, LoroResult, PeerID, SnapshotImportMode, StyleConfig, Timestamp,
+    VersionRange, ID,
 };
 
 use crate::{
@@ -88,6 +89,33 @@ impl LoroDoc {
         self.doc.set_record_timestamp(record);
     }
 
+    /// Set whether the text events report the ids of the changed paragraphs.
+    #[inline]
+    pub fn set_text_paragraph_ids(&self, enabled: bool) {
+        self.doc.set_text_paragraph_ids(enabled);
+    }
+
+    /// Set whether the local ops can write the values that the older versions of Loro
+    /// can't decode.
+    #[inline]
+    pub fn set_extended_value_types(&self, enabled: bool) {
+        self.doc.set_extended_value_types(enabled);
+    }
+
+    /// Set whether the exported snapshots store the checksums of the container states.
+    #[inline]
+    pub fn set_snapshot_checksums(&self, enabled: bool) {
+        self.doc.set_snapshot_checksums(enabled);
+    }
+
+    /// Make the internal randomness of the doc deterministic by deriving it from `seed`.
+    ///
+    /// It covers the peer ids generated by the doc afterwards and the jitter of the
+    /// fractional indexes of trees.
+    pub fn set_random_seed(&self, seed: Option<u64>) {
+        self.doc.set_random_seed(seed);
+    }
+
     /// Set the interval of mergeable changes, **in seconds**.
     ///
     /// If two continuous local changes are within the interval, they will be merged into one change.
@@ -973,6 +1001,9 @@ pub struct ContainerPath {
 pub struct ImportStatus {
     pub success: HashMap<u64, CounterSpan>,
     pub pending: Option<HashMap<u64, CounterSpan>>,
+    pub stats: ImportStats,
+    /// How the snapshot was applied, `None` if the imported blob is not a snapshot
+    pub snapshot: Option<SnapshotImportMode>,
 }
 
 impl From<loro::ImportStatus> for ImportStatus {
@@ -981,10 +1012,52 @@ impl From<loro::ImportStatus> for ImportStatus {
         Self {
             success: vr_to_map(a),
             pending: value.pending.as_ref().map(vr_to_map),
+            stats: value.stats.into(),
+            snapshot: value.snapshot,
+        }
+    }
+}
+
+pub struct ImportStats {
+    /// The number of changes in the imported blob
+    pub changes: u32,
+    /// The number of ops in the imported blob
+    pub ops: u32,
+    /// The peers that authored the changes in the imported blob
+    pub peers: Vec<u64>,
+    /// The age of the imported ops in seconds
+    pub op_age: Option<OpAgeStats>,
+    /// The number of missing ops on each peer that the pending changes are waiting for
+    pub causal_gaps: HashMap<u64, i32>,
+}
+
+impl From<loro::ImportStats> for ImportStats {
+    fn from(value: loro::ImportStats) -> Self {
+        Self {
+            changes: value.changes as u32,
+            ops: value.ops as u32,
+            peers: value.peers.into_iter().collect(),
+            op_age: value.op_age.map(|x| OpAgeStats {
+                min: x.min,
+                max: x.max,
+                sum: x.sum,
+                ops: x.ops as u32,
+                buckets: x.buckets.iter().map(|&b| b as u32).collect(),
+            }),
+            causal_gaps: value.causal_gaps.into_iter().collect(),
         }
     }
 }
 
+pub struct OpAgeStats {
+    pub min: i64,
+    pub max: i64,
+    pub sum: i64,
+    pub ops: u32,
+    /// The number of ops in each bucket, whose upper bounds are 1s, 10s, 1min, 1h and 1d
+    pub buckets: Vec<u32>,
+}
+
 fn vr_to_map(a: &VersionRange) -> HashMap<u64, CounterSpan> {
     a.iter()
         .map(|x| {
@@ -4,7 +4,7 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use loro::{EventTriggerKind, FractionalIndex, TreeID};
+use loro::{EventTriggerKind, FractionalIndex, TreeID, ID};
 
 use crate::{
     convert_trait_to_v_or_container, ContainerID, LoroValue, TreeParentId, ValueOrContainer,
@@ -51,6 +51,26 @@ pub struct ContainerDiff {
     pub is_unknown: bool,
     /// The diff
     pub diff: Diff,
+    /// The paragraphs of the text that are changed by the diff, in document order.
+    pub paragraph_changed: Vec<ParagraphId>,
+}
+
+/// The stable id of a paragraph in a text container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParagraphId {
+    /// The first paragraph, which has no newline before it
+    First,
+    /// The paragraph that starts after the newline with this id
+    Newline { id: ID },
+}
+
+impl From<loro::ParagraphId> for ParagraphId {
+    fn from(value: loro::ParagraphId) -> Self {
+        match value {
+            loro::ParagraphId::First => Self::First,
+            loro::ParagraphId::Newline(id) => Self::Newline { id },
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -280,6 +300,7 @@ impl<'a> From<&loro::event::ContainerDiff<'a>> for ContainerDiff {
                 .collect(),
             is_unknown: value.is_unknown,
             diff: (&value.diff).into(),
+            paragraph_changed: value.paragraph_changed.iter().map(|&p| p.into()).collect(),
         }
     }
 }
@@ -6,7 +6,8 @@ pub use loro::{
     CounterSpan, EventTriggerKind, ExpandType, FractionalIndex, IdLp, IdSpan, JsonChange,
     JsonFutureOp, JsonFutureOpWrapper, JsonListOp, JsonMapOp, JsonMovableListOp, JsonOp,
     JsonOpContent, JsonPathError, JsonSchema, JsonTextOp, JsonTreeOp, Lamport, LoroEncodeError,
-    LoroError, PeerID, StyleConfig, TreeID, UpdateOptions, UpdateTimeoutError, ID, LORO_VERSION,
+    LoroError, PeerID, SnapshotImportMode, StyleConfig, TreeID, UpdateOptions, UpdateTimeoutError,
+    ID, LORO_VERSION,
 };
 pub use std::cmp::Ordering;
 use std::sync::Arc;
@@ -14,8 +15,8 @@ pub use value::{ContainerID, ContainerType, LoroValue, LoroValueLike};
 mod doc;
 pub use doc::{
     decode_import_blob_meta, ChangeAncestorsTraveler, ChangeMeta, CommitOptions, ContainerPath,
-    FrontiersOrID, ImportBlobMetadata, ImportStatus, JsonSchemaLike, LocalUpdateCallback, LoroDoc,
-    PosQueryResult, Subscription, Unsubscriber,
+    FrontiersOrID, ImportBlobMetadata, ImportStats, ImportStatus, JsonSchemaLike,
+    LocalUpdateCallback, LoroDoc, OpAgeStats, PosQueryResult, Subscription, Unsubscriber,
 };
 mod container;
 pub use container::{
@@ -25,7 +26,7 @@ pub use container::{
 mod event;
 pub use event::{
     ContainerDiff, ContainerIDAndDiff, Diff, DiffBatch, DiffEvent, Index, ListDiffItem, MapDelta,
-    PathItem, Subscriber, TextDelta, TreeDiff, TreeDiffItem, TreeExternalDiff,
+    ParagraphId, PathItem, Subscriber, TextDelta, TreeDiff, TreeDiffItem, TreeExternalDiff,
 };
 mod undo;
 pub use undo::{AbsolutePosition, CursorWithPos, OnPop, OnPush, UndoItemMeta, UndoManager};
@@ -33,14 +33,41 @@ pub enum ContainerID {
 #[derive(Debug, Clone)]
 pub enum LoroValue {
     Null,
-    Bool { value: bool },
-    Double { value: f64 },
-    I64 { value: i64 },
-    Binary { value: Vec<u8> },
-    String { value: String },
-    List { value: Vec<LoroValue> },
-    Map { value: HashMap<String, LoroValue> },
-    Container { value: ContainerID },
+    Bool {
+        value: bool,
+    },
+    Double {
+        value: f64,
+    },
+    I64 {
+        value: i64,
+    },
+    Binary {
+        value: Vec<u8>,
+    },
+    String {
+        value: String,
+    },
+    List {
+        value: Vec<LoroValue>,
+    },
+    Map {
+        value: HashMap<String, LoroValue>,
+    },
+    Container {
+        value: ContainerID,
+    },
+    ContainerRef {
+        value: ContainerID,
+    },
+    /// Milliseconds since the Unix epoch
+    Timestamp {
+        value: i64,
+    },
+    /// A decimal in the form of `[-]digits[.digits]`
+    Decimal {
+        value: String,
+    },
 }
 
 impl From<LoroValue> for loro::LoroValue {
@@ -57,10 +84,21 @@ impl From<LoroValue> for loro::LoroValue {
                 Self::Map(value.into_iter().map(|(k, v)| (k, v.into())).collect())
             }
             LoroValue::Container { value } => Self::Container(value.into()),
+            LoroValue::ContainerRef { value } => Self::ContainerRef(value.into()),
+            LoroValue::Timestamp { value } => Self::Timestamp(value),
+            LoroValue::Decimal { value } => decimal_from_string(&value),
         }
     }
 }
 
+/// The invalid decimals are kept as strings, so that no data is lost
+fn decimal_from_string(value: &str) -> loro::LoroValue {
+    match loro::LoroDecimal::parse(value) {
+        Some(d) => loro::LoroValue::Decimal(d),
+        None => loro::LoroValue::String(value.into()),
+    }
+}
+
 impl From<&LoroValue> for loro::LoroValue {
     fn from(value: &LoroValue) -> Self {
         match value {
@@ -75,6 +113,9 @@ impl From<&LoroValue> for loro::LoroValue {
                 Self::Map(value.iter().map(|(k, v)| (k.clone(), v.into())).collect())
             }
             LoroValue::Container { value } => Self::Container(value.into()),
+            LoroValue::ContainerRef { value } => Self::ContainerRef(value.into()),
+            LoroValue::Timestamp { value } => Self::Timestamp(*value),
+            LoroValue::Decimal { value } => decimal_from_string(value),
         }
     }
 }
@@ -105,6 +146,13 @@ impl From<loro::LoroValue> for LoroValue {
             loro::LoroValue::Container(value) => Self::Container {
                 value: value.into(),
             },
+            loro::LoroValue::ContainerRef(value) => Self::ContainerRef {
+                value: value.into(),
+            },
+            loro::LoroValue::Timestamp(value) => Self::Timestamp { value },
+            loro::LoroValue::Decimal(value) => Self::Decimal {
+                value: value.to_string(),
+            },
         }
     }
 }
@@ -0,0 +1,159 @@
+//! The log of the ops that had no effect when they were applied.
+//!
+//! It's disabled by default. Once enabled by [`LoroDoc::set_record_apply_log`], the imports
+//! and the local edits record why an op didn't show up in the state, which can be read by
+//! [`LoroDoc::take_apply_log`].
+use std::sync::{Arc, Mutex};
+
+use loro_common::{ContainerID, IdLp, IdSpan, InternalString, ID};
+use rle::HasLength;
+
+use crate::{
+    change::Change,
+    op::InnerContent,
+    oplog::OpLog,
+    state::{DocState, State},
+    version::VersionRange,
+    LoroDoc, VersionVector,
+};
+
+/// An op that had no effect when it was applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IgnoredOp {
+    /// The imported ops were already in the doc
+    Duplicate { span: IdSpan },
+    /// The imported ops are pending because the ops they depend on are missing.
+    /// They will be applied once the missing ops are imported.
+    Pending { span: IdSpan },
+    /// The imported map op lost to the op `winner` on the same key, which has
+    /// a greater lamport (or the same lamport and a greater peer id)
+    OverriddenMapValue {
+        id: ID,
+        container: ContainerID,
+        key: InternalString,
+        winner: IdLp,
+    },
+    /// `mark` or `unmark` created no op, because the range already had the same value
+    /// of the style
+    StyleNoop {
+        container: ContainerID,
+        key: InternalString,
+        start: usize,
+        end: usize,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ApplyLog(Arc<Mutex<Option<Vec<IgnoredOp>>>>);
+
+impl ApplyLog {
+    /// A log with the same enabled state but no entries
+    pub(crate) fn fork(&self) -> Self {
+        let log = Self::default();
+        log.set_enabled(self.is_enabled());
+        log
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.0.lock().unwrap().is_some()
+    }
+
+    pub(crate) fn set_enabled(&self, enabled: bool) {
+        let mut log = self.0.lock().unwrap();
+        match (enabled, log.is_some()) {
+            (true, false) => *log = Some(Vec::new()),
+            (false, true) => *log = None,
+            _ => {}
+        }
+    }
+
+    pub(crate) fn record(&self, entry: impl FnOnce() -> IgnoredOp) {
+        if let Some(log) = self.0.lock().unwrap().as_mut() {
+            log.push(entry());
+        }
+    }
+
+    fn take(&self) -> Vec<IgnoredOp> {
+        self.0
+            .lock()
+            .unwrap()
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Record the parts of the decoded changes that are already included by `vv`
+    pub(crate) fn record_duplicates(&self, changes: &[Change], vv: &VersionVector) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        for change in changes {
+            let end = vv.get(&change.id.peer).copied().unwrap_or(0);
+            if change.id.counter < end {
+                let end = end.min(change.id.counter + change.atom_len() as i32);
+                self.record(|| IgnoredOp::Duplicate {
+                    span: IdSpan::new(change.id.peer, change.id.counter, end),
+                });
+            }
+        }
+    }
+
+    pub(crate) fn record_pending(&self, pending: &VersionRange) {
+        for (peer, (start, end)) in pending.iter() {
+            self.record(|| IgnoredOp::Pending {
+                span: IdSpan::new(*peer, *start, *end),
+            });
+        }
+    }
+
+    /// Record the imported map ops that are not the current values of their keys
+    pub(crate) fn record_overridden_map_ops(
+        &self,
+        oplog: &OpLog,
+        state: &mut DocState,
+        imported: &VersionRange,
+    ) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        for (peer, (start, end)) in imported.iter() {
+            for op in oplog.iter_ops(IdSpan::new(*peer, *start, *end)) {
+                let InnerContent::Map(set) = &op.raw_op().content else {
+                    continue;
+                };
+
+                let idx = op.container();
+                let winner = state.with_state_mut(idx, |s| match s {
+                    State::MapState(m) => m.get_winner(&set.key),
+                    _ => None,
+                });
+                if let Some(winner) = winner.filter(|w| *w != op.idlp()) {
+                    self.record(|| IgnoredOp::OverriddenMapValue {
+                        id: op.id(),
+                        container: state.arena.idx_to_id(idx).unwrap(),
+                        key: set.key.clone(),
+                        winner,
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl LoroDoc {
+    /// Enable or disable recording the ops that had no effect when they were applied.
+    ///
+    /// Disabling it drops the recorded entries.
+    pub fn set_record_apply_log(&self, enabled: bool) {
+        self.config.apply_log.set_enabled(enabled);
+    }
+
+    /// Take the ops that had no effect since the last call, see [`IgnoredOp`].
+    ///
+    /// It's always empty unless [`LoroDoc::set_record_apply_log`] is enabled.
+    pub fn take_apply_log(&self) -> Vec<IgnoredOp> {
+        self.config.apply_log.take()
+    }
+}
@@ -1,4 +1,5 @@
 mod str_arena;
+mod value_pool;
 
 use std::{
     num::NonZeroU16,
@@ -18,12 +19,14 @@ use crate::{
         map::MapSet,
         ContainerID,
     },
+    delta::MapValue,
+    event::InternalDiff,
     id::Counter,
     op::{InnerContent, ListSlice, Op, RawOp, RawOpContent, SliceRange},
     LoroValue,
 };
 
-use self::str_arena::StrArena;
+use self::{str_arena::StrArena, value_pool::ValuePool};
 
 #[derive(Default, Debug)]
 struct InnerSharedArena {
@@ -38,6 +41,8 @@ struct InnerSharedArena {
     values: Mutex<Vec<LoroValue>>,
     root_c_idx: Mutex<Vec<ContainerIdx>>,
     str: Arc<Mutex<StrArena>>,
+    /// It's None if value interning is disabled
+    value_pool: Mutex<Option<ValuePool>>,
 }
 
 /// This is shared between [OpLog] and [AppState].
@@ -123,6 +128,7 @@ impl SharedArena {
                 values: Mutex::new(self.inner.values.lock().unwrap().clone()),
                 root_c_idx: Mutex::new(self.inner.root_c_idx.lock().unwrap().clone()),
                 str: self.inner.str.clone(),
+                value_pool: Mutex::new(self.inner.value_pool.lock().unwrap().clone()),
             }),
         }
     }
@@ -211,16 +217,127 @@ impl SharedArena {
         self.inner.str.lock().unwrap().len_utf16()
     }
 
+    /// The number of bytes of the text in the arena
+    #[inline]
+    pub fn str_len_bytes(&self) -> usize {
+        self.inner.str.lock().unwrap().len_bytes()
+    }
+
+    /// The number of values in the arena
+    #[inline]
+    pub fn values_len(&self) -> usize {
+        self.inner.values.lock().unwrap().len()
+    }
+
     #[inline]
     pub fn alloc_value(&self, value: LoroValue) -> usize {
+        let value = self.intern_value(value);
         let mut values_lock = self.inner.values.lock().unwrap();
         _alloc_value(&mut values_lock, value)
     }
 
     #[inline]
     pub fn alloc_values(&self, values: impl Iterator<Item = LoroValue>) -> std::ops::Range<usize> {
+        let mut pool = self.inner.value_pool.lock().unwrap();
         let mut values_lock = self.inner.values.lock().unwrap();
-        _alloc_values(&mut values_lock, values)
+        match pool.as_mut() {
+            Some(pool) => _alloc_values(&mut values_lock, values.map(|v| pool.intern(v))),
+            None => _alloc_values(&mut values_lock, values),
+        }
+    }
+
+    /// Enable or disable the value interning.
+    ///
+    /// Disabling it drops the pool, but the values that are already interned keep
+    /// sharing their allocations.
+    pub fn set_value_interning(&self, enabled: bool) {
+        let mut pool = self.inner.value_pool.lock().unwrap();
+        match (enabled, pool.is_some()) {
+            (true, false) => *pool = Some(ValuePool::default()),
+            (false, true) => *pool = None,
+            _ => {}
+        }
+    }
+
+    pub fn is_value_interning_enabled(&self) -> bool {
+        self.inner.value_pool.lock().unwrap().is_some()
+    }
+
+    /// The number of distinct values in the intern pool
+    pub fn interned_values_len(&self) -> usize {
+        self.inner
+            .value_pool
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|x| x.len())
+            .unwrap_or(0)
+    }
+
+    /// Return the pooled value that is identical to the given value if value interning is enabled.
+    #[inline]
+    pub fn intern_value(&self, value: LoroValue) -> LoroValue {
+        match self.inner.value_pool.lock().unwrap().as_mut() {
+            Some(pool) => pool.intern(value),
+            None => value,
+        }
+    }
+
+    /// Intern the values carried by a local op before it's applied to the state and the oplog.
+    pub(crate) fn intern_raw_op_content(&self, content: &mut RawOpContent) {
+        let mut pool = self.inner.value_pool.lock().unwrap();
+        let Some(pool) = pool.as_mut() else {
+            return;
+        };
+
+        match content {
+            RawOpContent::Map(MapSet {
+                value: Some(value), ..
+            })
+            | RawOpContent::List(ListOp::Set { value, .. }) => {
+                *value = pool.intern(std::mem::take(value));
+            }
+            RawOpContent::List(ListOp::Insert {
+                slice: ListSlice::RawData(values),
+                ..
+            }) => {
+                for value in values.to_mut().iter_mut() {
+                    *value = pool.intern(std::mem::take(value));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Intern the values carried by a remote diff before it's applied to the state.
+    ///
+    /// The values of list diffs are allocated in the arena, so they are already interned.
+    pub(crate) fn intern_internal_diff(&self, diff: &mut InternalDiff) {
+        let mut pool = self.inner.value_pool.lock().unwrap();
+        let Some(pool) = pool.as_mut() else {
+            return;
+        };
+
+        match diff {
+            InternalDiff::Map(map) => {
+                for value in map.updated.values_mut() {
+                    if let Some(MapValue {
+                        value: Some(value), ..
+                    }) = value
+                    {
+                        *value = pool.intern(std::mem::take(value));
+                    }
+                }
+            }
+            InternalDiff::MovableList(list) => {
+                for elem in list.elements.values_mut() {
+                    if elem.value_updated {
+                        elem.value = pool.intern(std::mem::take(&mut elem.value));
+                    }
+                }
+            }
+            _ => {}
+        }
     }
 
     #[inline]
@@ -0,0 +1,109 @@
+use fxhash::FxHashSet;
+use loro_common::LoroValue;
+
+/// Strings or binaries longer than this are unlikely to be repeated, so they
+/// are not worth the cost of hashing and keeping them alive in the pool.
+const MAX_INTERNED_LEN: usize = 256;
+
+/// The pool is never swept before it holds this many values.
+const MIN_EVICTION_LEN: usize = 1024;
+
+/// A pool that makes identical scalar values share the same allocation.
+///
+/// [LoroValue::String] and [LoroValue::Binary] are reference-counted, so interning them
+/// lets a log-like list with many repeated enum-like strings keep a single copy of
+/// each distinct string in the state, the arena and the history.
+///
+/// Other scalar values are stored inline, so there is nothing to share.
+///
+/// The values that are only referenced by the pool, e.g. because they were deleted and
+/// their history was dropped, are evicted when the pool doubles in size since the last
+/// sweep. So the pool stays proportional to the values that are alive in the doc, and the
+/// sweeps are amortized O(1) per interned value.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ValuePool {
+    values: FxHashSet<LoroValue>,
+    /// The pool is swept when its length reaches this
+    evict_at: usize,
+}
+
+impl ValuePool {
+    pub fn intern(&mut self, value: LoroValue) -> LoroValue {
+        if !Self::should_intern(&value) {
+            return value;
+        }
+
+        if let Some(v) = self.values.get(&value) {
+            return v.clone();
+        }
+
+        self.values.insert(value.clone());
+        if self.values.len() >= self.evict_at.max(MIN_EVICTION_LEN) {
+            self.evict_unused();
+            self.evict_at = self.values.len() * 2;
+        }
+
+        value
+    }
+
+    /// Drop the values that are not referenced outside of the pool
+    fn evict_unused(&mut self) {
+        self.values.retain(|v| match v {
+            LoroValue::String(s) => s.ref_count() > 1,
+            LoroValue::Binary(b) => b.ref_count() > 1,
+            _ => false,
+        });
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn should_intern(value: &LoroValue) -> bool {
+        match value {
+            LoroValue::String(s) => s.len() <= MAX_INTERNED_LEN,
+            LoroValue::Binary(b) => b.len() <= MAX_INTERNED_LEN,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn identical_strings_share_allocation() {
+        let mut pool = ValuePool::default();
+        let a = pool.intern(LoroValue::from("info"));
+        let b = pool.intern(LoroValue::from("info"));
+        let c = pool.intern(LoroValue::from("warn"));
+        assert_eq!(
+            a.as_string().unwrap().as_ptr(),
+            b.as_string().unwrap().as_ptr()
+        );
+        assert_ne!(
+            a.as_string().unwrap().as_ptr(),
+            c.as_string().unwrap().as_ptr()
+        );
+        pool.intern(LoroValue::I64(1));
+        pool.intern(LoroValue::from("x".repeat(MAX_INTERNED_LEN + 1)));
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn unused_values_are_evicted() {
+        let mut pool = ValuePool::default();
+        let kept = pool.intern(LoroValue::from("kept"));
+        for i in 0..MIN_EVICTION_LEN * 4 {
+            pool.intern(LoroValue::from(i.to_string()));
+        }
+        assert!(pool.len() < MIN_EVICTION_LEN * 2);
+        let again = pool.intern(LoroValue::from("kept"));
+        assert_eq!(
+            kept.as_string().unwrap().as_ptr(),
+            again.as_string().unwrap().as_ptr()
+        );
+    }
+}
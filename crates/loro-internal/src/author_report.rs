@@ -0,0 +1,202 @@
+//! Collecting and redacting the ops of a given author.
+//!
+//! A data-subject request asks for everything a person wrote, or for its removal. Loro
+//! attributes every op to the peer that created it, so [`LoroDoc::export_ops_by_peer`] lists
+//! the ops of the given peers with readable descriptions, and [`LoroDoc::redact_peer`]
+//! rebuilds the doc with their content replaced by placeholders, using
+//! [`json::redact`](crate::json::redact).
+use std::{ops::RangeBounds, sync::Arc};
+
+use loro_common::{
+    ContainerID, Counter, IdSpan, Lamport, LoroError, LoroResult, PeerID, Timestamp, ID,
+};
+use rle::HasLength;
+use serde_json::json;
+
+use crate::{op_explanation::describe, version::VersionRange, LoroDoc};
+
+/// An op listed by [`LoroDoc::export_ops_by_peer`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthoredOp {
+    /// The id of the first atom of the op
+    pub id: ID,
+    pub lamport: Lamport,
+    /// The number of atoms of the op, e.g. the number of inserted characters
+    pub len: usize,
+    /// The timestamp of the change that contains the op, in seconds
+    pub timestamp: Timestamp,
+    /// The commit message of the change that contains the op
+    pub message: Option<Arc<str>>,
+    pub container: ContainerID,
+    /// A readable description of the op, e.g. `insert "abc" at 3`
+    pub content: String,
+}
+
+/// The ops of some peers, ordered by peer and counter
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuthorReport {
+    pub ops: Vec<AuthoredOp>,
+}
+
+impl AuthorReport {
+    pub fn to_json(&self) -> String {
+        let ops: Vec<_> = self
+            .ops
+            .iter()
+            .map(|op| {
+                json!({
+                    "id": op.id.to_string(),
+                    "lamport": op.lamport,
+                    "len": op.len,
+                    "timestamp": op.timestamp,
+                    "message": op.message.as_deref(),
+                    "container": op.container.to_string(),
+                    "content": op.content,
+                })
+            })
+            .collect();
+        serde_json::to_string_pretty(&json!({ "ops": ops })).unwrap()
+    }
+}
+
+impl LoroDoc {
+    /// List the ops created by `peers` in the changes whose timestamps are in `range`.
+    ///
+    /// The pending ops are committed first. The ops before the shallow root of a shallow doc
+    /// are not listed, as their content is not in the doc. If the doc doesn't record
+    /// timestamps, all the changes have the timestamp 0.
+    pub fn export_ops_by_peer(
+        &self,
+        peers: &[PeerID],
+        range: impl RangeBounds<Timestamp>,
+    ) -> AuthorReport {
+        self.commit_then_renew();
+        let oplog = self.oplog().lock().unwrap();
+        let mut peers = peers.to_vec();
+        peers.sort_unstable();
+        peers.dedup();
+        let mut ops = Vec::new();
+        for peer in peers {
+            let start = oplog.shallow_since_vv().get(&peer).copied().unwrap_or(0);
+            let end = oplog.vv().get(&peer).copied().unwrap_or(0);
+            if start >= end {
+                continue;
+            }
+
+            for change in oplog
+                .change_store()
+                .iter_changes(IdSpan::new(peer, start, end))
+            {
+                if !range.contains(&change.timestamp) {
+                    continue;
+                }
+
+                for op in change.ops().iter() {
+                    if op.counter + (op.atom_len() as Counter) <= start {
+                        continue;
+                    }
+
+                    ops.push(AuthoredOp {
+                        id: ID::new(peer, op.counter),
+                        lamport: change.lamport + (op.counter - change.id.counter) as Lamport,
+                        len: op.atom_len(),
+                        timestamp: change.timestamp,
+                        message: change.commit_msg.clone(),
+                        container: oplog.arena.idx_to_id(op.container).unwrap(),
+                        content: describe(op, &oplog.arena),
+                    });
+                }
+            }
+        }
+
+        AuthorReport { ops }
+    }
+
+    /// Create a copy of the doc whose ops created by `peer` have their content replaced by
+    /// placeholders, and whose changes by `peer` have no commit messages.
+    ///
+    /// The structure of the history is kept, so the copy can be synced with the other
+    /// replicas that are redacted too. The other replicas still have the content until they
+    /// switch to the copy. The new doc has the same configuration and a random peer id.
+    ///
+    /// It returns an error for a shallow doc, whose history before the shallow root cannot
+    /// be rebuilt.
+    pub fn redact_peer(&self, peer: PeerID) -> LoroResult<LoroDoc> {
+        if self.is_shallow() {
+            return Err(LoroError::ArgErr(
+                "Cannot redact the history of a shallow doc".into(),
+            ));
+        }
+
+        let vv = self.oplog_vv();
+        let mut json = self.export_json_updates(&Default::default(), &vv, false);
+        let mut range = VersionRange::new();
+        range.insert(peer, 0, vv.get(&peer).copied().unwrap_or(0));
+        crate::json::redact(&mut json, range)
+            .map_err(|e| LoroError::ArgErr(e.to_string().into_boxed_str()))?;
+        for change in json.changes.iter_mut() {
+            if change.id.peer == peer {
+                change.msg = None;
+            }
+        }
+
+        let doc = LoroDoc::new();
+        doc.set_config(&self.config);
+        doc.derive_randomness_from(&self.config);
+        doc.import_json_updates(json)?;
+        if self.auto_commit.load(std::sync::atomic::Ordering::Relaxed) {
+            doc.start_auto_commit();
+        }
+
+        Ok(doc)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoding::ExportMode;
+
+    #[test]
+    fn export_and_redact_ops_of_a_peer() {
+        let a = LoroDoc::new_auto_commit();
+        a.set_peer_id(1).unwrap();
+        a.get_text("text").insert(0, "public").unwrap();
+        a.commit_then_renew();
+        let b = LoroDoc::new_auto_commit();
+        b.set_peer_id(2).unwrap();
+        b.import(&a.export(ExportMode::all_updates()).unwrap())
+            .unwrap();
+        b.get_text("text").insert(0, "secret ").unwrap();
+        b.get_map("map").insert("email", "a@b.c").unwrap();
+        b.set_next_commit_message("by Bob");
+        b.commit_then_renew();
+        a.import(&b.export(ExportMode::all_updates()).unwrap())
+            .unwrap();
+
+        let report = a.export_ops_by_peer(&[2, 3], ..);
+        assert_eq!(report.ops.len(), 2);
+        assert_eq!(report.ops[0].id, ID::new(2, 0));
+        assert_eq!(report.ops[0].content, "insert \"secret \" at 0");
+        assert_eq!(report.ops[0].len, 7);
+        assert_eq!(report.ops[1].message.as_deref(), Some("by Bob"));
+        assert_eq!(report.ops[1].container, a.get_map("map").id());
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["ops"][1]["id"], "7@2");
+        assert!(a.export_ops_by_peer(&[2], 1..).ops.is_empty());
+
+        let redacted = a.redact_peer(2).unwrap();
+        let text = redacted.get_text("text").to_string();
+        assert_eq!(text.chars().count(), "secret public".len());
+        assert!(text.ends_with("public"));
+        assert!(!text.contains("secret"));
+        assert_eq!(
+            redacted.get_map("map").get("email"),
+            Some(loro_common::LoroValue::Null)
+        );
+        assert!(redacted.export_ops_by_peer(&[2], ..).ops[1]
+            .message
+            .is_none());
+        assert_eq!(redacted.oplog_vv(), a.oplog_vv());
+    }
+}
@@ -206,6 +206,11 @@ pub struct EphemeralStore {
     local_subs: SubscriberSetWithQueue<(), LocalEphemeralCallback, Vec<u8>>,
     subscribers: SubscriberSetWithQueue<(), EphemeralSubscriber, EphemeralStoreEvent>,
     timeout: i64,
+    /// The min interval between two local updates, in milliseconds
+    local_update_throttle: i64,
+    last_local_update: i64,
+    /// The keys updated locally but not yet emitted to `local_subs`
+    pending_local_keys: Vec<String>,
 }
 
 impl std::fmt::Debug for EphemeralStore {
@@ -239,6 +244,9 @@ impl EphemeralStore {
             states: FxHashMap::default(),
             local_subs: SubscriberSetWithQueue::new(),
             subscribers: SubscriberSetWithQueue::new(),
+            local_update_throttle: 0,
+            last_local_update: 0,
+            pending_local_keys: Vec::new(),
         }
     }
 
@@ -260,6 +268,27 @@ impl EphemeralStore {
         postcard::to_allocvec(&peers_info).unwrap()
     }
 
+    /// Encode the states of the given keys into one update.
+    pub fn encode_keys<'a>(&self, keys: impl IntoIterator<Item = &'a str>) -> Vec<u8> {
+        let mut peers_info = Vec::new();
+        let now = get_sys_timestamp() as Timestamp;
+        for key in keys {
+            if let Some(peer_state) = self.states.get(key) {
+                if now - peer_state.timestamp > self.timeout {
+                    continue;
+                }
+                let encoded_peer_info = EncodedState {
+                    key,
+                    value: peer_state.state.clone(),
+                    timestamp: peer_state.timestamp,
+                };
+                peers_info.push(encoded_peer_info);
+            }
+        }
+
+        postcard::to_allocvec(&peers_info).unwrap()
+    }
+
     pub fn encode_all(&self) -> Vec<u8> {
         let mut peers_info = Vec::new();
         let now = get_sys_timestamp() as Timestamp;
@@ -377,6 +406,38 @@ impl EphemeralStore {
             .map(|s| s.as_str())
     }
 
+    /// Set the min interval between two local updates, in milliseconds. Default is 0.
+    ///
+    /// Rapid local changes (e.g. cursor moves) within the interval are coalesced: the changed
+    /// keys are kept pending and sent as one update to the local update subscribers when a
+    /// change is made after the interval has passed, or when [`EphemeralStore::flush_local_updates`]
+    /// is called. The caller is expected to schedule a flush to send the trailing update.
+    pub fn set_local_update_throttle(&mut self, throttle: i64) {
+        self.local_update_throttle = throttle;
+        if throttle <= 0 {
+            self.flush_local_updates();
+        }
+    }
+
+    /// Whether there are local changes that haven't been sent to the local update subscribers.
+    pub fn has_pending_local_updates(&self) -> bool {
+        !self.pending_local_keys.is_empty()
+    }
+
+    /// Send the pending local changes to the local update subscribers as one update.
+    pub fn flush_local_updates(&mut self) {
+        if self.pending_local_keys.is_empty() {
+            return;
+        }
+
+        let keys = std::mem::take(&mut self.pending_local_keys);
+        self.last_local_update = get_sys_timestamp() as Timestamp;
+        if !self.local_subs.inner().is_empty() {
+            self.local_subs
+                .emit(&(), self.encode_keys(keys.iter().map(|x| x.as_str())));
+        }
+    }
+
     pub fn subscribe_local_updates(&self, callback: LocalEphemeralCallback) -> Subscription {
         let (sub, activate) = self.local_subs.inner().insert((), callback);
         activate();
@@ -391,15 +452,25 @@ impl EphemeralStore {
 
     fn _set_local_state(&mut self, key: &str, value: Option<LoroValue>) {
         let is_delete = value.is_none();
+        let now = get_sys_timestamp() as Timestamp;
         let old = self.states.insert(
             key.to_string(),
             State {
                 state: value,
-                timestamp: get_sys_timestamp() as Timestamp,
+                timestamp: now,
             },
         );
-        if !self.local_subs.inner().is_empty() {
-            self.local_subs.emit(&(), self.encode(key));
+        if self.local_update_throttle <= 0 {
+            if !self.local_subs.inner().is_empty() {
+                self.local_subs.emit(&(), self.encode(key));
+            }
+        } else {
+            if !self.pending_local_keys.iter().any(|k| k == key) {
+                self.pending_local_keys.push(key.to_string());
+            }
+            if now - self.last_local_update >= self.local_update_throttle {
+                self.flush_local_updates();
+            }
         }
         if !self.subscribers.inner().is_empty() {
             if old.is_some() {
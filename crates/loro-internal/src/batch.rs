@@ -0,0 +1,125 @@
+//! Batch the local changes into one commit.
+//!
+//! [`LoroDoc::batch`] defers the auto commits until the closure returns, so the changes
+//! made by any number of handler calls are committed as one change and emitted as one
+//! event batch. When the closure returns an error, the uncommitted changes are rolled back
+//! by restoring the states of the changed containers, which are recorded before their
+//! first change in the batch.
+//!
+//! The containers created inside a rolled-back batch lose their states, but they stay
+//! registered in the arena of the doc, like the ones created by an aborted transaction. So
+//! [`LoroDoc::has_container`] still returns `true` for them, while they are empty and not
+//! reachable from the doc value.
+use std::sync::atomic::Ordering;
+
+use loro_common::LoroResult;
+
+use crate::LoroDoc;
+
+/// Stop the backup and reset the batching flag even if the closure panics
+struct BatchingGuard<'a>(&'a LoroDoc);
+
+impl Drop for BatchingGuard<'_> {
+    fn drop(&mut self) {
+        // Otherwise every later local change would keep forking the container states
+        if let Ok(mut state) = self.0.state.lock() {
+            state.stop_txn_backup();
+        }
+        self.0.batching.store(false, Ordering::Release);
+    }
+}
+
+impl LoroDoc {
+    /// Run `f` as one batch of local changes.
+    ///
+    /// The changes made inside `f` are committed as one change when `f` returns `Ok`, so the
+    /// subscribers receive one event batch no matter how many handler calls or commits
+    /// happen inside it. The pending changes before the batch are committed first.
+    ///
+    /// If `f` returns an error, the uncommitted changes made inside it are rolled back and
+    /// nothing is committed.
+    ///
+    /// The methods that switch the version of the doc, e.g. `import`, `export` and
+    /// `checkout`, still commit the pending changes when called inside the batch, and the
+    /// changes committed by them cannot be rolled back. Nested batches are merged into the
+    /// outermost one.
+    pub fn batch<R>(&self, f: impl FnOnce() -> LoroResult<R>) -> LoroResult<R> {
+        if self.batching.load(Ordering::Acquire) {
+            return f();
+        }
+
+        self.commit_then_renew();
+        self.batching.store(true, Ordering::Release);
+        let guard = BatchingGuard(self);
+        self.state.lock().unwrap().start_txn_backup();
+        let ans = f();
+        if ans.is_err() {
+            self.rollback_batch();
+        }
+
+        drop(guard);
+        if ans.is_ok() {
+            self.commit_then_renew();
+        }
+
+        ans
+    }
+
+    fn rollback_batch(&self) {
+        let mut txn = self.txn.lock().unwrap();
+        if let Some(txn) = txn.take() {
+            txn.abort();
+        }
+
+        self.state.lock().unwrap().rollback_txn_backup();
+        drop(txn);
+        self.renew_txn_if_auto_commit(None);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use loro_common::LoroError;
+
+    use super::*;
+    use crate::{handler::TextHandler, HandlerTrait};
+
+    #[test]
+    fn panic_in_batch_stops_the_backup() {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        let ans = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            doc.batch(|| -> LoroResult<()> {
+                text.insert(0, "a")?;
+                panic!("panic in batch");
+            })
+        }));
+        assert!(ans.is_err());
+        assert!(!doc.batching.load(Ordering::Acquire));
+        assert!(!doc.state.lock().unwrap().is_txn_backup_enabled());
+
+        text.insert(1, "b").unwrap();
+        doc.commit_then_renew();
+        assert_eq!(text.to_string(), "ab");
+    }
+
+    #[test]
+    fn rolled_back_child_container_stays_registered() {
+        let doc = LoroDoc::new_auto_commit();
+        let map = doc.get_map("map");
+        let mut child_id = None;
+        let ans: LoroResult<()> = doc.batch(|| {
+            let child = map.insert_container("child", TextHandler::new_detached())?;
+            child.insert(0, "x")?;
+            child_id = Some(child.id());
+            Err(LoroError::Unknown("rollback".into()))
+        });
+        assert!(ans.is_err());
+        assert!(!doc.state.lock().unwrap().is_txn_backup_enabled());
+        assert_eq!(map.get("child"), None);
+
+        let child_id = child_id.unwrap();
+        assert!(doc.has_container(&child_id));
+        assert_eq!(doc.get_text(child_id).to_string(), "");
+    }
+}
@@ -0,0 +1,608 @@
+//! Keeping the history of a doc in an external store.
+//!
+//! The history is encoded as blocks of changes, each of them keyed by the id of its first
+//! change. By default the encoded blocks stay in memory, so a doc with a long history holds
+//! all of it even if only the recent changes are ever read. A [`BlockStore`] keeps them
+//! elsewhere, e.g. in files, SQLite or IndexedDB. A doc created by
+//! [`LoroDoc::from_block_store`] loads the blocks from the store when they are accessed, and
+//! [`LoroDoc::flush_block_store`] writes the new blocks into it and drops the loaded ones
+//! from memory.
+//!
+//! The store also keeps a few entries of metadata, the versions of the history in it. The
+//! state of the doc is not stored, it's computed from the history when the doc is created.
+//! [`MemBlockStore`] keeps the blocks in memory, and [`FileBlockStore`] is a reference
+//! implementation that keeps each of them in a file.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    ops::Bound,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use bytes::Bytes;
+use loro_common::{LoroError, LoroResult, ID};
+use loro_kv_store::mem_store::MemKvConfig;
+
+use crate::{
+    kv_store::{KvStore, MemKvStore},
+    oplog::OpLog,
+    LoroDoc,
+};
+
+/// The length of the keys of the blocks in the change store, the bytes of an [`ID`]
+const BLOCK_KEY_LEN: usize = 12;
+
+/// A store of the encoded change blocks of a doc, keyed by the id of the first change of
+/// each block, and of a few entries of metadata, whose keys are shorter than the ids.
+///
+/// The methods are infallible like the ones of [`KvStore`]. An implementation that can fail
+/// should panic, because the doc can't go on with a part of its history.
+pub trait BlockStore: std::fmt::Debug + Send + Sync {
+    /// The block that starts at `id`
+    fn get_block(&self, id: ID) -> Option<Bytes>;
+    /// Insert or replace the block that starts at `id`
+    fn put_block(&mut self, id: ID, block: Bytes);
+    fn remove_block(&mut self, id: ID) -> Option<Bytes>;
+    /// The blocks whose start ids are in the range, in the order of the ids. The start of
+    /// the range is never after its end.
+    fn iter_blocks(
+        &self,
+        start: Bound<ID>,
+        end: Bound<ID>,
+    ) -> Box<dyn DoubleEndedIterator<Item = (ID, Bytes)> + '_>;
+    fn get_meta(&self, key: &[u8]) -> Option<Bytes>;
+    /// Insert or replace the metadata entry, or remove it if `value` is `None`
+    fn set_meta(&mut self, key: &[u8], value: Option<Bytes>);
+    fn meta_keys(&self) -> Vec<Bytes>;
+}
+
+/// A [`BlockStore`] in memory
+#[derive(Debug, Clone, Default)]
+pub struct MemBlockStore {
+    blocks: BTreeMap<ID, Bytes>,
+    meta: BTreeMap<Bytes, Bytes>,
+}
+
+impl MemBlockStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl BlockStore for MemBlockStore {
+    fn get_block(&self, id: ID) -> Option<Bytes> {
+        self.blocks.get(&id).cloned()
+    }
+
+    fn put_block(&mut self, id: ID, block: Bytes) {
+        self.blocks.insert(id, block);
+    }
+
+    fn remove_block(&mut self, id: ID) -> Option<Bytes> {
+        self.blocks.remove(&id)
+    }
+
+    fn iter_blocks(
+        &self,
+        start: Bound<ID>,
+        end: Bound<ID>,
+    ) -> Box<dyn DoubleEndedIterator<Item = (ID, Bytes)> + '_> {
+        Box::new(
+            self.blocks
+                .range((start, end))
+                .map(|(id, block)| (*id, block.clone())),
+        )
+    }
+
+    fn get_meta(&self, key: &[u8]) -> Option<Bytes> {
+        self.meta.get(key).cloned()
+    }
+
+    fn set_meta(&mut self, key: &[u8], value: Option<Bytes>) {
+        match value {
+            Some(value) => {
+                self.meta.insert(Bytes::copy_from_slice(key), value);
+            }
+            None => {
+                self.meta.remove(key);
+            }
+        }
+    }
+
+    fn meta_keys(&self) -> Vec<Bytes> {
+        self.meta.keys().cloned().collect()
+    }
+}
+
+const BLOCK_FILE_PREFIX: &str = "block-";
+const META_FILE_PREFIX: &str = "meta-";
+
+/// A [`BlockStore`] that keeps each block and each metadata entry in a file of a directory,
+/// named after the hex of its key.
+///
+/// The ids of the blocks are listed when the store is opened, and the blocks are read when
+/// they are accessed. A file is written to a temporary file that is renamed over it, so a
+/// crash doesn't leave a partial block. It panics if a file can't be read or written.
+#[derive(Debug)]
+pub struct FileBlockStore {
+    dir: PathBuf,
+    ids: BTreeSet<ID>,
+    meta: BTreeMap<Bytes, Bytes>,
+}
+
+impl FileBlockStore {
+    /// Open the store in `dir`, which is created if it doesn't exist
+    pub fn open(dir: impl AsRef<Path>) -> LoroResult<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let err = |e: io::Error| {
+            LoroError::ArgErr(format!("Cannot open {}: {}", dir.display(), e).into_boxed_str())
+        };
+        fs::create_dir_all(&dir).map_err(err)?;
+        let mut ids = BTreeSet::new();
+        let mut meta = BTreeMap::new();
+        for entry in fs::read_dir(&dir).map_err(err)? {
+            let entry = entry.map_err(err)?;
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+
+            if let Some(key) = name.strip_prefix(BLOCK_FILE_PREFIX).and_then(decode_hex) {
+                if key.len() == BLOCK_KEY_LEN {
+                    ids.insert(ID::from_bytes(&key));
+                }
+            } else if let Some(key) = name.strip_prefix(META_FILE_PREFIX).and_then(decode_hex) {
+                let value = fs::read(entry.path()).map_err(err)?;
+                meta.insert(Bytes::from(key), Bytes::from(value));
+            }
+        }
+
+        Ok(Self { dir, ids, meta })
+    }
+
+    fn block_path(&self, id: ID) -> PathBuf {
+        self.dir.join(format!(
+            "{}{}",
+            BLOCK_FILE_PREFIX,
+            encode_hex(&id.to_bytes())
+        ))
+    }
+
+    fn meta_path(&self, key: &[u8]) -> PathBuf {
+        self.dir
+            .join(format!("{}{}", META_FILE_PREFIX, encode_hex(key)))
+    }
+
+    fn read_block(&self, id: ID) -> Bytes {
+        let path = self.block_path(id);
+        fs::read(&path)
+            .unwrap_or_else(|e| panic!("Cannot read {}: {}", path.display(), e))
+            .into()
+    }
+}
+
+impl BlockStore for FileBlockStore {
+    fn get_block(&self, id: ID) -> Option<Bytes> {
+        self.ids.contains(&id).then(|| self.read_block(id))
+    }
+
+    fn put_block(&mut self, id: ID, block: Bytes) {
+        write_file(&self.block_path(id), &block);
+        self.ids.insert(id);
+    }
+
+    fn remove_block(&mut self, id: ID) -> Option<Bytes> {
+        let block = self.get_block(id)?;
+        remove_file(&self.block_path(id));
+        self.ids.remove(&id);
+        Some(block)
+    }
+
+    fn iter_blocks(
+        &self,
+        start: Bound<ID>,
+        end: Bound<ID>,
+    ) -> Box<dyn DoubleEndedIterator<Item = (ID, Bytes)> + '_> {
+        Box::new(
+            self.ids
+                .range((start, end))
+                .map(|id| (*id, self.read_block(*id))),
+        )
+    }
+
+    fn get_meta(&self, key: &[u8]) -> Option<Bytes> {
+        self.meta.get(key).cloned()
+    }
+
+    fn set_meta(&mut self, key: &[u8], value: Option<Bytes>) {
+        match value {
+            Some(value) => {
+                write_file(&self.meta_path(key), &value);
+                self.meta.insert(Bytes::copy_from_slice(key), value);
+            }
+            None => {
+                if self.meta.remove(key).is_some() {
+                    remove_file(&self.meta_path(key));
+                }
+            }
+        }
+    }
+
+    fn meta_keys(&self) -> Vec<Bytes> {
+        self.meta.keys().cloned().collect()
+    }
+}
+
+fn write_file(path: &Path, bytes: &[u8]) {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, bytes)
+        .and_then(|_| fs::rename(&tmp, path))
+        .unwrap_or_else(|e| panic!("Cannot write {}: {}", path.display(), e));
+}
+
+fn remove_file(path: &Path) {
+    fs::remove_file(path).unwrap_or_else(|e| panic!("Cannot remove {}: {}", path.display(), e));
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{:02x}", b);
+        s
+    })
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// The [`KvStore`] of the change store of a doc created by [`LoroDoc::from_block_store`].
+///
+/// The keys of the ids' length are the blocks, and the others are the metadata.
+#[derive(Debug)]
+struct BlockKvStore(Box<dyn BlockStore>);
+
+impl BlockKvStore {
+    fn blocks(
+        &self,
+        start: Bound<ID>,
+        end: Bound<ID>,
+    ) -> Box<dyn DoubleEndedIterator<Item = (Bytes, Bytes)> + '_> {
+        if is_empty_range(&start, &end) {
+            return Box::new(std::iter::empty());
+        }
+
+        Box::new(
+            self.0
+                .iter_blocks(start, end)
+                .map(|(id, block)| (Bytes::copy_from_slice(&id.to_bytes()), block)),
+        )
+    }
+
+    fn to_mem_kv(&self) -> MemKvStore {
+        let mut kv = MemKvStore::new(MemKvConfig::default());
+        for (key, value) in self.scan(Bound::Unbounded, Bound::Unbounded) {
+            kv.set(&key, value);
+        }
+
+        kv
+    }
+}
+
+/// The bound of the ids whose bytes are in the bound of the byte keys
+fn id_bound(bound: Bound<&[u8]>, is_start: bool) -> Bound<ID> {
+    let key = match bound {
+        Bound::Included(key) | Bound::Excluded(key) => key,
+        Bound::Unbounded => return Bound::Unbounded,
+    };
+
+    match key.len().cmp(&BLOCK_KEY_LEN) {
+        std::cmp::Ordering::Equal => bound.map(ID::from_bytes),
+        // The ids starting with a shorter key are after it, so the key is between the ids
+        // before the padded key and the ones from it
+        std::cmp::Ordering::Less => {
+            let mut padded = [0; BLOCK_KEY_LEN];
+            padded[..key.len()].copy_from_slice(key);
+            let id = ID::from_bytes(&padded);
+            if is_start {
+                Bound::Included(id)
+            } else {
+                Bound::Excluded(id)
+            }
+        }
+        // A longer key is between its prefix and the ids after it
+        std::cmp::Ordering::Greater => {
+            let id = ID::from_bytes(&key[..BLOCK_KEY_LEN]);
+            if is_start {
+                Bound::Excluded(id)
+            } else {
+                Bound::Included(id)
+            }
+        }
+    }
+}
+
+fn is_empty_range(start: &Bound<ID>, end: &Bound<ID>) -> bool {
+    match (start, end) {
+        (Bound::Included(s), Bound::Included(e)) => s > e,
+        (Bound::Included(s) | Bound::Excluded(s), Bound::Excluded(e))
+        | (Bound::Excluded(s), Bound::Included(e)) => s >= e,
+        _ => false,
+    }
+}
+
+fn in_range(key: &[u8], start: Bound<&[u8]>, end: Bound<&[u8]>) -> bool {
+    let after_start = match start {
+        Bound::Included(s) => key >= s,
+        Bound::Excluded(s) => key > s,
+        Bound::Unbounded => true,
+    };
+    let before_end = match end {
+        Bound::Included(e) => key <= e,
+        Bound::Excluded(e) => key < e,
+        Bound::Unbounded => true,
+    };
+    after_start && before_end
+}
+
+impl KvStore for BlockKvStore {
+    fn get(&self, key: &[u8]) -> Option<Bytes> {
+        if key.len() == BLOCK_KEY_LEN {
+            self.0.get_block(ID::from_bytes(key))
+        } else {
+            self.0.get_meta(key)
+        }
+    }
+
+    fn set(&mut self, key: &[u8], value: Bytes) {
+        if key.len() == BLOCK_KEY_LEN {
+            self.0.put_block(ID::from_bytes(key), value);
+        } else {
+            self.0.set_meta(key, Some(value));
+        }
+    }
+
+    fn compare_and_swap(&mut self, key: &[u8], old: Option<Bytes>, new: Bytes) -> bool {
+        if self.get(key) != old {
+            return false;
+        }
+
+        self.set(key, new);
+        true
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<Bytes> {
+        if key.len() == BLOCK_KEY_LEN {
+            self.0.remove_block(ID::from_bytes(key))
+        } else {
+            let ans = self.0.get_meta(key);
+            self.0.set_meta(key, None);
+            ans
+        }
+    }
+
+    fn contains_key(&self, key: &[u8]) -> bool {
+        self.get(key).is_some()
+    }
+
+    fn scan(
+        &self,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+    ) -> Box<dyn DoubleEndedIterator<Item = (Bytes, Bytes)> + '_> {
+        let mut meta_keys: Vec<Bytes> = self
+            .0
+            .meta_keys()
+            .into_iter()
+            .filter(|key| in_range(key, start, end))
+            .collect();
+        meta_keys.sort_unstable();
+        // The blocks between the metadata entries are only read when they are reached
+        let mut iter: Box<dyn DoubleEndedIterator<Item = (Bytes, Bytes)> + '_> =
+            Box::new(std::iter::empty());
+        let mut block_start = id_bound(start, true);
+        for key in meta_keys {
+            let block_end = id_bound(Bound::Excluded(&key), false);
+            let next_block_start = id_bound(Bound::Excluded(&key), true);
+            let entry = self.0.get_meta(&key).map(|value| (key, value));
+            iter = Box::new(iter.chain(self.blocks(block_start, block_end)).chain(entry));
+            block_start = next_block_start;
+        }
+
+        Box::new(iter.chain(self.blocks(block_start, id_bound(end, false))))
+    }
+
+    fn len(&self) -> usize {
+        self.0.meta_keys().len()
+            + self
+                .0
+                .iter_blocks(Bound::Unbounded, Bound::Unbounded)
+                .count()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.meta_keys().is_empty()
+            && self
+                .0
+                .iter_blocks(Bound::Unbounded, Bound::Unbounded)
+                .next()
+                .is_none()
+    }
+
+    fn size(&self) -> usize {
+        self.scan(Bound::Unbounded, Bound::Unbounded)
+            .map(|(k, v)| k.len() + v.len())
+            .sum()
+    }
+
+    fn export_all(&mut self) -> Bytes {
+        self.to_mem_kv().export_all()
+    }
+
+    fn import_all(&mut self, bytes: Bytes) -> Result<(), String> {
+        let mut kv = MemKvStore::new(MemKvConfig::default());
+        kv.import_all(bytes)?;
+        for (key, value) in kv.scan(Bound::Unbounded, Bound::Unbounded) {
+            self.set(&key, value);
+        }
+
+        Ok(())
+    }
+
+    /// The forks keep their history in memory
+    fn clone_store(&self) -> Arc<Mutex<dyn KvStore>> {
+        Arc::new(Mutex::new(self.to_mem_kv()))
+    }
+}
+
+impl LoroDoc {
+    /// Create a doc whose history is kept in `store`, see the [module docs](self).
+    ///
+    /// The history already in the store is loaded lazily, and the state is computed from it.
+    /// It returns an error if the store has a shallow history, because the state at its
+    /// shallow root is not stored.
+    pub fn from_block_store(store: impl BlockStore + 'static) -> LoroResult<Self> {
+        let kv = BlockKvStore(Box::new(store));
+        let is_empty = kv.is_empty();
+        let doc = Self::new_with_oplog(OpLog::new_with_kv(Some(Arc::new(Mutex::new(kv)))));
+        if is_empty {
+            return Ok(doc);
+        }
+
+        let mut oplog = doc.oplog.lock().unwrap();
+        let info = oplog.change_store().load_version()?;
+        if info.start_version.is_some() {
+            return Err(LoroError::ArgErr(
+                "The block store has a shallow history, which can't be loaded without its state"
+                    .into(),
+            ));
+        }
+
+        oplog.dag.set_version_by_fast_snapshot_import(info);
+        oplog.reset_container_history_sizes();
+        oplog.reset_frozen();
+        drop(oplog);
+        doc.set_detached(true);
+        doc._checkout_to_latest_without_commit(false);
+        Ok(doc)
+    }
+
+    /// Write the new changes into the block store, and drop the blocks loaded from it from
+    /// memory. The pending txn is committed first.
+    ///
+    /// The doc that is not created by [`LoroDoc::from_block_store`] keeps the blocks in its
+    /// in-memory store.
+    pub fn flush_block_store(&self) {
+        self.commit_then_renew();
+        let mut oplog = self.oplog.lock().unwrap();
+        oplog.compact_change_store();
+        oplog.change_store().evict_flushed_blocks();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::loro::ExportMode;
+
+    #[test]
+    fn scan_block_kv_store_in_order() {
+        let mut kv = BlockKvStore(Box::new(MemBlockStore::new()));
+        let keys: Vec<Vec<u8>> = vec![
+            ID::new(0x6300, 1).to_bytes().to_vec(),
+            ID::new(u64::from_be_bytes(*b"cv\0\0\0\0\0\0"), 0)
+                .to_bytes()
+                .to_vec(),
+            ID::new(u64::MAX, 3).to_bytes().to_vec(),
+            b"cv".to_vec(),
+            b"fr".to_vec(),
+        ];
+        let mut entries = BTreeMap::new();
+        for (i, key) in keys.iter().enumerate() {
+            kv.set(key, Bytes::from(vec![i as u8]));
+            entries.insert(Bytes::from(key.clone()), Bytes::from(vec![i as u8]));
+        }
+
+        let bounds = [
+            Bound::Unbounded,
+            Bound::Included(&b"cv"[..]),
+            Bound::Excluded(&b"cv"[..]),
+            Bound::Included(&keys[1][..]),
+            Bound::Excluded(&b"cv\0"[..]),
+        ];
+        for start in bounds {
+            for end in bounds {
+                let expected: Vec<_> = entries
+                    .iter()
+                    .filter(|(k, _)| in_range(k, start, end))
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect();
+                let ans: Vec<_> = kv.scan(start, end).collect();
+                assert_eq!(ans, expected, "{start:?}..{end:?}");
+                let mut rev: Vec<_> = kv.scan(start, end).rev().collect();
+                rev.reverse();
+                assert_eq!(rev, expected);
+            }
+        }
+
+        assert_eq!(kv.len(), keys.len());
+        assert_eq!(kv.remove(b"cv"), Some(Bytes::from(vec![3])));
+        assert_eq!(kv.remove(&keys[0]), Some(Bytes::from(vec![0])));
+        assert_eq!(kv.len(), keys.len() - 2);
+    }
+
+    #[test]
+    fn keep_history_in_file_block_store() {
+        let dir = std::env::temp_dir().join(format!("loro-block-store-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let doc = LoroDoc::from_block_store(FileBlockStore::open(&dir).unwrap()).unwrap();
+        doc.start_auto_commit();
+        doc.set_peer_id(1).unwrap();
+        let text = doc.get_text("text");
+        for i in 0..100 {
+            text.insert(i, "a").unwrap();
+            doc.commit_then_renew();
+        }
+        doc.get_map("map").insert("key", "value").unwrap();
+        doc.flush_block_store();
+        let snapshot = doc.export(ExportMode::Snapshot).unwrap();
+        let vv = doc.oplog_vv();
+        drop(doc);
+
+        let doc = LoroDoc::from_block_store(FileBlockStore::open(&dir).unwrap()).unwrap();
+        doc.start_auto_commit();
+        doc.set_peer_id(2).unwrap();
+        assert_eq!(doc.oplog_vv(), vv);
+        assert_eq!(doc.get_text("text").to_string(), "a".repeat(100));
+        assert_eq!(doc.get_map("map").get("key"), Some("value".into()));
+        doc.checkout(&crate::version::Frontiers::from_id(ID::new(1, 9)))
+            .unwrap();
+        assert_eq!(doc.get_text("text").to_string(), "a".repeat(10));
+        doc.checkout_to_latest();
+
+        doc.get_text("text").insert(0, "b").unwrap();
+        doc.flush_block_store();
+        drop(doc);
+        let doc = LoroDoc::from_block_store(FileBlockStore::open(&dir).unwrap()).unwrap();
+        assert_eq!(doc.get_text("text").len_unicode(), 101);
+
+        // The forks and the imports of the snapshots keep the history in memory
+        let new_doc = LoroDoc::new_auto_commit();
+        new_doc.import(&snapshot).unwrap();
+        new_doc
+            .import(&doc.export(ExportMode::updates(&vv)).unwrap())
+            .unwrap();
+        assert_eq!(new_doc.get_deep_value(), doc.get_deep_value());
+        assert_eq!(doc.fork().get_deep_value(), doc.get_deep_value());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,317 @@
+//! Splitting snapshots into content-defined chunks.
+//!
+//! Changing a single op can shift the rest of the snapshot bytes, so the storages that
+//! deduplicate fixed-size blocks store the whole snapshot again. [`ChunkedSnapshot`] cuts the
+//! snapshot where a rolling hash of the last bytes matches a pattern, so the boundaries only
+//! depend on the content around them. After an edit, the boundaries resynchronize a few chunks
+//! later, and the unchanged chunks keep their hashes. The storage keeps the chunks by hash,
+//! and the index lists them in order, so only the index and the changed chunks are uploaded.
+//!
+//! The gear table and the chunk sizes are part of the format: changing them would move every
+//! boundary and defeat the deduplication across versions.
+use bytes::Bytes;
+use loro_common::{LoroEncodeError, LoroError, LoroResult};
+
+use crate::{encoding::ImportStatus, loro::ExportMode, LoroDoc};
+
+/// The MD5 digest of the bytes of a chunk
+pub type ChunkHash = [u8; 16];
+
+const INDEX_MAGIC: &[u8] = b"LCDC";
+const MIN_CHUNK_SIZE: usize = 2 * 1024;
+const MAX_CHUNK_SIZE: usize = 64 * 1024;
+/// A boundary is cut when the top 13 bits of the hash are zero, so the chunks are about
+/// `MIN_CHUNK_SIZE + 8 KiB` long
+const BOUNDARY_MASK: u64 = !(u64::MAX >> 13);
+
+/// The random values of the gear hash, generated by splitmix64
+const GEAR: [u64; 256] = {
+    let mut table = [0; 256];
+    let mut seed: u64 = 0;
+    let mut i = 0;
+    while i < 256 {
+        seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotChunk {
+    pub hash: ChunkHash,
+    pub bytes: Bytes,
+}
+
+/// A snapshot split into content-defined chunks, see [`LoroDoc::export_chunked_snapshot`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkedSnapshot {
+    /// The chunks in their order in the snapshot
+    pub chunks: Vec<SnapshotChunk>,
+}
+
+impl ChunkedSnapshot {
+    /// Split the bytes of a snapshot, or of any other blob, into content-defined chunks
+    pub fn split(bytes: &[u8]) -> Self {
+        let bytes = Bytes::copy_from_slice(bytes);
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        for end in chunk_ends(&bytes) {
+            let chunk = bytes.slice(start..end);
+            chunks.push(SnapshotChunk {
+                hash: md5::compute(&chunk).0,
+                bytes: chunk,
+            });
+            start = end;
+        }
+
+        Self { chunks }
+    }
+
+    /// Encode the index, which lists the hashes and the lengths of the chunks in order
+    pub fn index(&self) -> Vec<u8> {
+        let mut ans = INDEX_MAGIC.to_vec();
+        leb128::write::unsigned(&mut ans, self.chunks.len() as u64).unwrap();
+        for chunk in self.chunks.iter() {
+            ans.extend_from_slice(&chunk.hash);
+            leb128::write::unsigned(&mut ans, chunk.bytes.len() as u64).unwrap();
+        }
+
+        ans
+    }
+
+    /// Decode the hashes and the lengths of the chunks listed by an index.
+    ///
+    /// The index may come from an untrusted storage, so the chunks longer than the chunker
+    /// ever cuts are rejected.
+    pub fn decode_index(index: &[u8]) -> LoroResult<Vec<(ChunkHash, usize)>> {
+        let err = || LoroError::DecodeError("Invalid chunked snapshot index".into());
+        let Some(mut r) = index.strip_prefix(INDEX_MAGIC) else {
+            return Err(err());
+        };
+        let len = leb128::read::unsigned(&mut r).map_err(|_| err())? as usize;
+        // Each entry takes at least 17 bytes, so the capacity is bounded by the index size
+        let mut ans = Vec::with_capacity(len.min(r.len() / 17));
+        let mut total: usize = 0;
+        for _ in 0..len {
+            if r.len() < 16 {
+                return Err(err());
+            }
+
+            let hash: ChunkHash = r[..16].try_into().unwrap();
+            r = &r[16..];
+            let size = leb128::read::unsigned(&mut r).map_err(|_| err())?;
+            if size > MAX_CHUNK_SIZE as u64 {
+                return Err(err());
+            }
+
+            let size = size as usize;
+            total = total.checked_add(size).ok_or_else(err)?;
+            ans.push((hash, size));
+        }
+
+        Ok(ans)
+    }
+
+    /// Join the chunks listed by `index`, which are looked up by `get_chunk`.
+    ///
+    /// It returns an error if a chunk is missing or doesn't match its hash.
+    pub fn assemble<C: AsRef<[u8]>>(
+        index: &[u8],
+        mut get_chunk: impl FnMut(&ChunkHash) -> Option<C>,
+    ) -> LoroResult<Vec<u8>> {
+        let entries = Self::decode_index(index)?;
+        // Not allocated from the lengths in the index, which are only checked against the chunks
+        let mut ans = Vec::new();
+        for (hash, len) in entries {
+            let Some(chunk) = get_chunk(&hash) else {
+                return Err(LoroError::NotFoundError(
+                    format!("The chunk {}", hex_string(&hash)).into_boxed_str(),
+                ));
+            };
+            let chunk = chunk.as_ref();
+            if chunk.len() != len || md5::compute(chunk).0 != hash {
+                return Err(LoroError::DecodeChecksumMismatchError);
+            }
+
+            ans.extend_from_slice(chunk);
+        }
+
+        Ok(ans)
+    }
+}
+
+impl LoroDoc {
+    /// Export a snapshot split into content-defined chunks.
+    ///
+    /// The snapshot is the same as the one of [`ExportMode::Snapshot`]. The chunks that are not
+    /// affected by the changes since a previous export keep their hashes, so a storage that
+    /// keeps the chunks by hash only needs the new ones and the index.
+    pub fn export_chunked_snapshot(&self) -> Result<ChunkedSnapshot, LoroEncodeError> {
+        Ok(ChunkedSnapshot::split(&self.export(ExportMode::Snapshot)?))
+    }
+
+    /// Import the snapshot whose chunks are listed by `index`, see
+    /// [`ChunkedSnapshot::assemble`].
+    pub fn import_chunked_snapshot<C: AsRef<[u8]>>(
+        &self,
+        index: &[u8],
+        get_chunk: impl FnMut(&ChunkHash) -> Option<C>,
+    ) -> LoroResult<ImportStatus> {
+        let bytes = ChunkedSnapshot::assemble(index, get_chunk)?;
+        self.import(&bytes)
+    }
+}
+
+/// The ends of the chunks of `data`, cut by a gear hash
+fn chunk_ends(data: &[u8]) -> Vec<usize> {
+    let mut ans = Vec::new();
+    let mut start = 0;
+    let mut hash: u64 = 0;
+    for (i, &b) in data.iter().enumerate() {
+        hash = (hash << 1).wrapping_add(GEAR[b as usize]);
+        let len = i + 1 - start;
+        if (len >= MIN_CHUNK_SIZE && hash & BOUNDARY_MASK == 0) || len >= MAX_CHUNK_SIZE {
+            ans.push(i + 1);
+            start = i + 1;
+            hash = 0;
+        }
+    }
+
+    if start < data.len() {
+        ans.push(data.len());
+    }
+
+    ans
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fxhash::{FxHashMap, FxHashSet};
+
+    fn random_bytes(len: usize, mut seed: u64) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                seed as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn boundaries_resynchronize_after_an_edit() {
+        let data = random_bytes(1 << 20, 42);
+        let edited = [&data[..500_000], b"inserted", &data[500_000..]].concat();
+
+        let a = ChunkedSnapshot::split(&data);
+        let b = ChunkedSnapshot::split(&edited);
+        assert!(a.chunks.len() > 50);
+        assert!(a.chunks[..a.chunks.len() - 1]
+            .iter()
+            .all(|c| (MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE).contains(&c.bytes.len())));
+        let hashes: FxHashSet<_> = a.chunks.iter().map(|c| c.hash).collect();
+        let changed = b
+            .chunks
+            .iter()
+            .filter(|c| !hashes.contains(&c.hash))
+            .count();
+        assert!(changed <= 3, "{changed} chunks changed");
+    }
+
+    #[test]
+    fn export_and_import_chunks() {
+        let doc = LoroDoc::new_auto_commit();
+        let words = random_bytes(100_000, 7)
+            .into_iter()
+            .map(|b| (b'a' + b % 26) as char)
+            .collect::<String>();
+        doc.get_text("text").insert(0, &words).unwrap();
+        doc.commit_then_renew();
+
+        let chunked = doc.export_chunked_snapshot().unwrap();
+        assert!(chunked.chunks.len() > 1);
+        let store: FxHashMap<ChunkHash, Bytes> = chunked
+            .chunks
+            .iter()
+            .map(|c| (c.hash, c.bytes.clone()))
+            .collect();
+        let index = chunked.index();
+        assert_eq!(
+            ChunkedSnapshot::decode_index(&index).unwrap().len(),
+            chunked.chunks.len()
+        );
+
+        let new_doc = LoroDoc::new_auto_commit();
+        new_doc
+            .import_chunked_snapshot(&index, |hash| store.get(hash).cloned())
+            .unwrap();
+        assert_eq!(new_doc.get_text("text").to_string(), words);
+
+        let missing = chunked.chunks[0].hash;
+        assert!(matches!(
+            ChunkedSnapshot::assemble(&index, |hash| (hash != &missing)
+                .then(|| store[hash].clone())),
+            Err(LoroError::NotFoundError(_))
+        ));
+        assert_eq!(
+            ChunkedSnapshot::assemble(&index, |_| Some(b"x")),
+            Err(LoroError::DecodeChecksumMismatchError)
+        );
+        assert!(ChunkedSnapshot::decode_index(b"junk").is_err());
+    }
+
+    #[test]
+    fn reject_oversized_chunks_in_index() {
+        let mut index = INDEX_MAGIC.to_vec();
+        leb128::write::unsigned(&mut index, 2).unwrap();
+        for _ in 0..2 {
+            index.extend_from_slice(&[0; 16]);
+            leb128::write::unsigned(&mut index, u64::MAX).unwrap();
+        }
+
+        assert!(ChunkedSnapshot::decode_index(&index).is_err());
+        assert!(ChunkedSnapshot::assemble(&index, |_| Some(b"x")).is_err());
+    }
+
+    #[test]
+    fn edited_doc_reuses_most_chunks() {
+        let doc = LoroDoc::new_auto_commit();
+        doc.set_peer_id(1).unwrap();
+        for i in 0..200 {
+            let words = random_bytes(2_000, i + 1)
+                .into_iter()
+                .map(|b| (b'a' + b % 26) as char)
+                .collect::<String>();
+            doc.get_text(format!("text{i}")).insert(0, &words).unwrap();
+            doc.commit_then_renew();
+        }
+
+        let before = doc.export_chunked_snapshot().unwrap();
+        doc.get_text("text100").insert(1_000, "edit").unwrap();
+        doc.commit_then_renew();
+        let after = doc.export_chunked_snapshot().unwrap();
+
+        assert!(after.chunks.len() > 20);
+        let hashes: FxHashSet<_> = before.chunks.iter().map(|c| c.hash).collect();
+        let reused = after
+            .chunks
+            .iter()
+            .filter(|c| hashes.contains(&c.hash))
+            .count();
+        assert!(
+            reused * 10 >= after.chunks.len() * 8,
+            "only {reused} of {} chunks are reused",
+            after.chunks.len()
+        );
+    }
+}
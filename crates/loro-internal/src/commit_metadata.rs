@@ -0,0 +1,111 @@
+//! Key-value metadata attached to the commits.
+//!
+//! History UIs often need to know who made a commit and why, e.g. an `author` or a `ticket`
+//! entry. The metadata is stored at the end of the commit message, in a line made of
+//! [`COMMIT_METADATA_PREFIX`] and a JSON object. So it's persisted by every encoding, kept in the
+//! `msg` of the JSON updates, and read as a part of the message by the older versions. Like
+//! the commit messages, the consecutive changes with different metadata are never merged.
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{change_meta::ChangeMeta, txn::Transaction, LoroDoc};
+
+/// The prefix of the line that stores the metadata at the end of a commit message
+pub const COMMIT_METADATA_PREFIX: &str = "loro:metadata ";
+
+/// The key-value metadata of a commit
+pub type CommitMetadata = BTreeMap<String, String>;
+
+/// Split a commit message into the message text and the metadata
+pub fn parse_commit_message(msg: &str) -> (&str, CommitMetadata) {
+    let (text, last_line) = match msg.rsplit_once('\n') {
+        Some((text, last_line)) => (text, last_line),
+        None => ("", msg),
+    };
+    match last_line
+        .strip_prefix(COMMIT_METADATA_PREFIX)
+        .and_then(|json| serde_json::from_str(json).ok())
+    {
+        Some(metadata) => (text, metadata),
+        None => (msg, CommitMetadata::new()),
+    }
+}
+
+/// The commit message that stores both `text` and `metadata`
+pub(crate) fn compose_commit_message(text: &str, metadata: &CommitMetadata) -> Option<Arc<str>> {
+    if metadata.is_empty() {
+        return (!text.is_empty()).then(|| text.into());
+    }
+
+    // The JSON string is a single line, because the line breaks in it are escaped
+    let json = serde_json::to_string(metadata).unwrap();
+    Some(if text.is_empty() {
+        format!("{COMMIT_METADATA_PREFIX}{json}").into()
+    } else {
+        format!("{text}\n{COMMIT_METADATA_PREFIX}{json}").into()
+    })
+}
+
+impl Transaction {
+    /// Set the commit message, keeping the metadata set by [`Transaction::set_metadata`]
+    pub fn set_message(&mut self, text: &str) {
+        let (_, metadata) = parse_commit_message(self.msg().as_deref().unwrap_or_default());
+        let msg = compose_commit_message(text, &metadata);
+        self.set_msg(msg);
+    }
+
+    /// Set an entry of the metadata of the commit
+    pub fn set_metadata(&mut self, key: &str, value: &str) {
+        let (text, mut metadata) = parse_commit_message(self.msg().as_deref().unwrap_or_default());
+        metadata.insert(key.to_string(), value.to_string());
+        let msg = compose_commit_message(text, &metadata);
+        self.set_msg(msg);
+    }
+}
+
+impl LoroDoc {
+    /// Set an entry of the metadata of the next commit. It's kept when the commit message is
+    /// set by [`LoroDoc::set_next_commit_message`].
+    pub fn set_next_commit_metadata(&self, key: &str, value: &str) {
+        let mut txn = self.txn.lock().unwrap();
+        if let Some(txn) = txn.as_mut() {
+            txn.set_metadata(key, value);
+        }
+    }
+}
+
+impl ChangeMeta {
+    /// The commit message without the metadata
+    pub fn message_text(&self) -> &str {
+        parse_commit_message(self.message()).0
+    }
+
+    /// The metadata set by [`LoroDoc::set_next_commit_metadata`]
+    pub fn metadata(&self) -> CommitMetadata {
+        parse_commit_message(self.message()).1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_and_compose() {
+        let mut metadata = CommitMetadata::new();
+        assert_eq!(compose_commit_message("", &metadata), None);
+        assert_eq!(
+            parse_commit_message("fix\nbug"),
+            ("fix\nbug", metadata.clone())
+        );
+
+        metadata.insert("author".into(), "alice\nbob".into());
+        let msg = compose_commit_message("fix\nbug", &metadata).unwrap();
+        assert_eq!(parse_commit_message(&msg), ("fix\nbug", metadata.clone()));
+        let msg = compose_commit_message("", &metadata).unwrap();
+        assert_eq!(parse_commit_message(&msg), ("", metadata));
+
+        // Not a valid JSON object
+        let msg = format!("fix\n{COMMIT_METADATA_PREFIX}{{");
+        assert_eq!(parse_commit_message(&msg).0, msg);
+    }
+}
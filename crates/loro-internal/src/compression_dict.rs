@@ -0,0 +1,292 @@
+//! Compressing small updates with a shared dictionary.
+//!
+//! An update of a single keystroke is a few dozen bytes, and most of them are the same in
+//! every update: the header, the peer ids, the container ids and the keys. A general compressor
+//! has nothing to match them against in such a short input. A [`CompressionDictionary`] trained
+//! on typical updates gives LZ4 that context, so it's deployed on both ends, per doc or for
+//! all the docs of a service.
+//!
+//! A compressed blob starts with its own magic bytes and the id of the dictionary, which is the
+//! hash of its bytes, so the peers agree on the ids without coordination. The receivers
+//! advertise the ids of their dictionaries, and [`LoroDoc::export_compressed`] only uses a
+//! dictionary the receiver has, falling back to the plain blob otherwise. A doc that imports a
+//! blob compressed with an unknown dictionary fails with
+//! [`LoroError::CompressionDictionaryNotFound`] without changing the doc.
+use std::{borrow::Cow, cmp::Reverse, collections::BinaryHeap};
+
+use bytes::Bytes;
+use fxhash::{FxHashMap, FxHashSet};
+use loro_common::{LoroEncodeError, LoroError, LoroResult};
+
+use crate::{loro::ExportMode, LoroDoc};
+
+const MAGIC_BYTES: [u8; 4] = *b"lozd";
+const HEADER_SIZE: usize = 8;
+const ID_SEED: u32 = u32::from_le_bytes(*b"LOZD");
+/// LZ4 can only refer to the last 64 KiB of a dictionary
+pub const MAX_DICTIONARY_SIZE: usize = 64 * 1024;
+/// The windows whose frequencies are counted by the trainer
+const TRAIN_WINDOW: usize = 8;
+/// The trainer picks the dictionary content in segments of this size
+const TRAIN_SEGMENT: usize = 64;
+/// LZ4 can't expand a byte to more than 255 bytes, so the larger sizes are corrupted
+const MAX_EXPANSION: usize = 255;
+
+/// The bytes that LZ4 can refer to while compressing an update, see the [module
+/// docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressionDictionary {
+    id: u32,
+    bytes: Bytes,
+}
+
+impl CompressionDictionary {
+    pub fn new(bytes: impl Into<Bytes>) -> Self {
+        let bytes = bytes.into();
+        Self {
+            id: xxhash_rust::xxh32::xxh32(&bytes, ID_SEED),
+            bytes,
+        }
+    }
+
+    /// Build a dictionary of at most `max_size` bytes from the samples of the updates it will
+    /// compress, e.g. the updates exported by a session of typical edits.
+    ///
+    /// It picks the segments of the samples whose content recurs across the most samples.
+    /// The content that only appears in a single sample is never picked.
+    pub fn train<S: AsRef<[u8]>>(samples: &[S], max_size: usize) -> Self {
+        let max_size = max_size.min(MAX_DICTIONARY_SIZE);
+        // The number of samples that contain each window
+        let mut freq: FxHashMap<&[u8], u32> = FxHashMap::default();
+        for sample in samples {
+            let mut seen = FxHashSet::default();
+            for w in sample.as_ref().windows(TRAIN_WINDOW) {
+                if seen.insert(w) {
+                    *freq.entry(w).or_default() += 1;
+                }
+            }
+        }
+
+        let segment = |(i, start): (usize, usize)| {
+            let sample = samples[i].as_ref();
+            &sample[start..(start + TRAIN_SEGMENT).min(sample.len())]
+        };
+        let score = |seg: &[u8], covered: &FxHashSet<&[u8]>| -> u32 {
+            seg.windows(TRAIN_WINDOW)
+                .filter(|w| !covered.contains(w))
+                .map(|w| freq[w] - 1)
+                .sum()
+        };
+
+        let mut covered = FxHashSet::default();
+        let mut heap = BinaryHeap::new();
+        for (i, sample) in samples.iter().enumerate() {
+            for start in (0..sample.as_ref().len()).step_by(TRAIN_SEGMENT) {
+                let s = score(segment((i, start)), &covered);
+                if s > 0 {
+                    heap.push((s, Reverse((i, start))));
+                }
+            }
+        }
+
+        // The scores only drop as the dictionary covers more windows, so a segment whose
+        // updated score is still the greatest is the best one
+        let mut picked = Vec::new();
+        let mut size = 0;
+        while let Some((stale, Reverse(pos))) = heap.pop() {
+            let seg = segment(pos);
+            let s = score(seg, &covered);
+            if s < stale {
+                if s > 0 {
+                    heap.push((s, Reverse(pos)));
+                }
+                continue;
+            }
+
+            if size + seg.len() > max_size {
+                continue;
+            }
+
+            size += seg.len();
+            covered.extend(seg.windows(TRAIN_WINDOW));
+            picked.push(seg);
+        }
+
+        Self::new(picked.concat())
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn bytes(&self) -> &Bytes {
+        &self.bytes
+    }
+
+    /// Compress an exported blob
+    pub fn compress(&self, blob: &[u8]) -> Vec<u8> {
+        let mut ans = Vec::with_capacity(HEADER_SIZE + blob.len());
+        ans.extend_from_slice(&MAGIC_BYTES);
+        ans.extend_from_slice(&self.id.to_le_bytes());
+        ans.extend(lz4_flex::block::compress_prepend_size_with_dict(
+            blob,
+            &self.bytes,
+        ));
+        ans
+    }
+
+    /// Decompress a blob compressed with this dictionary
+    pub fn decompress(&self, bytes: &[u8]) -> LoroResult<Vec<u8>> {
+        match compressed_blob_dictionary_id(bytes) {
+            Some(id) if id == self.id => {}
+            Some(id) => return Err(LoroError::CompressionDictionaryNotFound(id)),
+            None => {
+                return Err(LoroError::DecodeError(
+                    "The blob is not compressed with a dictionary".into(),
+                ))
+            }
+        }
+
+        let body = &bytes[HEADER_SIZE..];
+        let err = || LoroError::DecodeError("Invalid compressed blob".into());
+        let Some(size) = body.get(..4) else {
+            return Err(err());
+        };
+        let size = u32::from_le_bytes(size.try_into().unwrap()) as usize;
+        if size > body.len().saturating_mul(MAX_EXPANSION) {
+            return Err(err());
+        }
+
+        lz4_flex::block::decompress_size_prepended_with_dict(body, &self.bytes).map_err(|_| err())
+    }
+}
+
+/// The id of the dictionary that `bytes` is compressed with, or `None` if it's not a
+/// compressed blob
+pub fn compressed_blob_dictionary_id(bytes: &[u8]) -> Option<u32> {
+    if bytes.len() < HEADER_SIZE || bytes[..4] != MAGIC_BYTES {
+        return None;
+    }
+
+    Some(u32::from_le_bytes(
+        bytes[4..HEADER_SIZE].try_into().unwrap(),
+    ))
+}
+
+impl LoroDoc {
+    /// Register a dictionary that the compressed blobs can be imported and exported with.
+    ///
+    /// The dictionaries are kept in the order they were registered, and a dictionary with the
+    /// same id replaces the old one.
+    pub fn add_compression_dictionary(&self, dict: CompressionDictionary) {
+        let mut dicts = self.config.compression_dictionaries.write().unwrap();
+        dicts.retain(|d| d.id != dict.id);
+        dicts.push(dict);
+    }
+
+    /// The ids of the registered dictionaries, to be advertised to the peers
+    pub fn compression_dictionary_ids(&self) -> Vec<u32> {
+        self.config
+            .compression_dictionaries()
+            .iter()
+            .map(|d| d.id)
+            .collect()
+    }
+
+    /// Export in the given mode, compressed with the last registered dictionary whose id is in
+    /// `peer_dictionary_ids`. If the peer has none of them, the blob is not compressed.
+    pub fn export_compressed(
+        &self,
+        mode: ExportMode,
+        peer_dictionary_ids: &[u32],
+    ) -> Result<Vec<u8>, LoroEncodeError> {
+        let blob = self.export(mode)?;
+        let dicts = self.config.compression_dictionaries();
+        match dicts
+            .iter()
+            .rev()
+            .find(|d| peer_dictionary_ids.contains(&d.id))
+        {
+            Some(dict) => Ok(dict.compress(&blob)),
+            None => Ok(blob),
+        }
+    }
+
+    /// Decompress `bytes` with the registered dictionary if it's a compressed blob
+    pub(crate) fn decompress_blob<'a>(&self, bytes: &'a [u8]) -> LoroResult<Cow<'a, [u8]>> {
+        let Some(id) = compressed_blob_dictionary_id(bytes) else {
+            return Ok(Cow::Borrowed(bytes));
+        };
+
+        let dicts = self.config.compression_dictionaries();
+        let Some(dict) = dicts.iter().find(|d| d.id == id) else {
+            return Err(LoroError::CompressionDictionaryNotFound(id));
+        };
+
+        dict.decompress(bytes).map(Cow::Owned)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn keystrokes(peer: u64, n: usize) -> Vec<Vec<u8>> {
+        let doc = LoroDoc::new_auto_commit();
+        doc.set_peer_id(peer).unwrap();
+        let text = doc.get_text("text");
+        let mut updates = Vec::new();
+        for i in 0..n {
+            let vv = doc.oplog_vv();
+            text.insert(i, "a").unwrap();
+            doc.commit_then_renew();
+            updates.push(doc.export(ExportMode::updates(&vv)).unwrap());
+        }
+
+        updates
+    }
+
+    #[test]
+    fn compress_updates_with_dictionary() {
+        let dict = CompressionDictionary::train(&keystrokes(1, 100), 4096);
+        assert!(!dict.bytes().is_empty());
+        assert!(dict.bytes().len() <= 4096);
+        assert_eq!(dict, CompressionDictionary::new(dict.bytes().clone()));
+
+        let updates = keystrokes(2, 20);
+        let plain: usize = updates.iter().map(|u| u.len()).sum();
+        let compressed: usize = updates.iter().map(|u| dict.compress(u).len()).sum();
+        assert!(compressed < plain, "{compressed} >= {plain}");
+
+        let a = LoroDoc::new_auto_commit();
+        a.set_peer_id(3).unwrap();
+        a.add_compression_dictionary(dict.clone());
+        a.get_text("text").insert(0, "hello").unwrap();
+        a.commit_then_renew();
+
+        let b = LoroDoc::new_auto_commit();
+        // The peer without the dictionary gets the plain blob
+        let blob = a
+            .export_compressed(ExportMode::all_updates(), &b.compression_dictionary_ids())
+            .unwrap();
+        assert_eq!(compressed_blob_dictionary_id(&blob), None);
+        let blob = a
+            .export_compressed(ExportMode::all_updates(), &[dict.id()])
+            .unwrap();
+        assert_eq!(compressed_blob_dictionary_id(&blob), Some(dict.id()));
+        assert_eq!(
+            b.import(&blob),
+            Err(LoroError::CompressionDictionaryNotFound(dict.id()))
+        );
+        assert!(b.oplog_vv().is_empty());
+
+        b.add_compression_dictionary(dict.clone());
+        assert_eq!(b.compression_dictionary_ids(), vec![dict.id()]);
+        b.import(&blob).unwrap();
+        assert_eq!(b.get_text("text").to_string(), "hello");
+
+        let mut corrupted = blob.clone();
+        corrupted[HEADER_SIZE..HEADER_SIZE + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(dict.decompress(&corrupted).is_err());
+    }
+}
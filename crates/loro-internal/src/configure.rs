@@ -1,5 +1,19 @@
-pub use crate::container::richtext::config::{StyleConfig, StyleConfigMap};
-use crate::LoroDoc;
+pub use crate::container::richtext::config::{
+    InvalidStyle, InvalidStyleReason, StyleConfig, StyleConfigMap, StyleImportPolicy,
+    StyleValueType,
+};
+use crate::{
+    apply_log::ApplyLog,
+    compression_dict::CompressionDictionary,
+    container::list::list_op::InnerListOp,
+    container_template::ContainerTemplate,
+    map_conflict::MapConflictResolver,
+    op::{InnerContent, Op},
+    LoroDoc,
+};
+use fxhash::FxHashMap;
+use loro_common::{DecodeLimit, InternalString, LoroError, LoroResult, ID};
+use rle::{HasLength, Mergable, RleVec};
 
 #[derive(Clone, Debug)]
 pub struct Configure {
@@ -7,6 +21,261 @@ pub struct Configure {
     record_timestamp: Arc<AtomicBool>,
     pub(crate) merge_interval_in_s: Arc<AtomicI64>,
     pub(crate) editable_detached_mode: Arc<AtomicBool>,
+    /// The max number of consecutive imports whose events are composed into one event.
+    pub(crate) import_event_coalescing_window: Arc<AtomicUsize>,
+    container_type_conflict_policy: Arc<AtomicU8>,
+    text_paragraph_ids: Arc<AtomicBool>,
+    single_writer: Arc<AtomicBool>,
+    /// Whether the local ops can write the values that the older versions can't decode
+    extended_value_types: Arc<AtomicBool>,
+    /// Whether the fast snapshots store the checksums of the container states
+    snapshot_checksums: Arc<AtomicBool>,
+    /// Whether the internal randomness is derived from `random_seed`
+    seeded_random: Arc<AtomicBool>,
+    random_seed: Arc<AtomicU64>,
+    /// The number of random values drawn from `random_seed`
+    random_counter: Arc<AtomicU64>,
+    pub(crate) apply_log: ApplyLog,
+    pub(crate) counter_config: Arc<RwLock<Option<CounterConfig>>>,
+    /// The templates registered by [`LoroDoc::register_template`]
+    pub(crate) templates: Arc<RwLock<FxHashMap<InternalString, Arc<ContainerTemplate>>>>,
+    pub(crate) decode_limits: Arc<RwLock<DecodeLimits>>,
+    pub(crate) delete_merge: Arc<RwLock<DeleteMergeConfig>>,
+    /// The rule set by [`LoroDoc::set_map_conflict_resolver`]
+    pub(crate) map_conflict_resolver: Arc<RwLock<Option<MapConflictResolver>>>,
+    /// The dictionaries registered by [`LoroDoc::add_compression_dictionary`]
+    pub(crate) compression_dictionaries: Arc<RwLock<Vec<CompressionDictionary>>>,
+}
+
+/// How to handle the imported ops that target a container whose type conflicts with a
+/// container known by the doc, i.e. they share the same creation id but have different types.
+///
+/// It can only be caused by corrupted or malicious data. It's checked for the imported
+/// updates, the JSON updates and the states of the imported snapshots.
+///
+/// There's no policy to coerce the ops to the type of the known container, because the ops
+/// of one type can't be applied to a container of another type, e.g. a text insert to a map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContainerTypeConflictPolicy {
+    /// Fail the import without importing any of the changes
+    #[default]
+    Reject,
+    /// Import the changes, but detach the conflicting containers so that they can never be
+    /// reached from the root containers. The conflicts can be inspected with
+    /// [`LoroDoc::container_type_conflicts`].
+    Quarantine,
+}
+
+/// How the counters of a doc accumulate their increments.
+///
+/// Floating point additions are not associative, so the replicas that apply fractional
+/// increments in different orders may disagree in the last digits. With a `precision`, each
+/// increment is rounded to that many decimal places and the value is accumulated as an integer
+/// number of the smallest units, which is exact and doesn't depend on the order.
+///
+/// All the replicas should use the same config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CounterConfig {
+    /// The number of decimal places. `None` keeps the plain floating point arithmetic.
+    pub precision: Option<u8>,
+    pub overflow: CounterOverflowPolicy,
+}
+
+/// What to do when an increment takes a counter out of its representable range.
+///
+/// With a precision, the range is `±(2^53 - 1)` smallest units, in which every value is exact.
+/// Otherwise it's the range of the finite `f64`s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CounterOverflowPolicy {
+    /// Clamp the value to the range
+    #[default]
+    Saturate,
+    /// Fail the local increments that overflow with [`loro_common::LoroError::CounterOverflow`].
+    /// The imported increments cannot be rejected, so they are still saturated.
+    Reject,
+}
+
+/// The limits checked when the updates and the snapshots are decoded.
+///
+/// A server that imports blobs from untrusted peers can bound the memory spent on a single
+/// import with them. The sizes are checked before the data they describe is allocated, and an
+/// import that exceeds a limit fails with [`loro_common::LoroError::DecodeLimitExceeded`]
+/// without changing the doc. In a snapshot, the limits are checked on its history.
+///
+/// All the limits are unlimited by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// The max number of the changes in an imported blob
+    pub max_changes: usize,
+    /// The max number of the ops in a change, counted in atoms like [`LoroDoc::len_ops`]
+    pub max_ops_per_change: usize,
+    /// The max length in bytes of a string value or a text insertion
+    pub max_str_len: usize,
+    /// The max length of a binary value
+    pub max_binary_len: usize,
+    /// The max number of the elements deleted by a single delete op
+    pub max_delete_span: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self::UNLIMITED
+    }
+}
+
+impl DecodeLimits {
+    pub const UNLIMITED: Self = Self {
+        max_changes: usize::MAX,
+        max_ops_per_change: usize::MAX,
+        max_str_len: usize::MAX,
+        max_binary_len: usize::MAX,
+        max_delete_span: usize::MAX,
+    };
+
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::UNLIMITED
+    }
+
+    fn max(&self, limit: DecodeLimit) -> usize {
+        match limit {
+            DecodeLimit::Changes => self.max_changes,
+            DecodeLimit::OpsPerChange => self.max_ops_per_change,
+            DecodeLimit::StringLength => self.max_str_len,
+            DecodeLimit::BinaryLength => self.max_binary_len,
+            DecodeLimit::DeleteSpan => self.max_delete_span,
+        }
+    }
+
+    /// Check that `actual` is within the `limit`
+    pub(crate) fn check(&self, limit: DecodeLimit, actual: usize) -> LoroResult<()> {
+        let max = self.max(limit);
+        if actual > max {
+            return Err(LoroError::DecodeLimitExceeded { limit, max, actual });
+        }
+
+        Ok(())
+    }
+}
+
+/// How the consecutive deletions in a list, a movable list or a text are merged into one op.
+///
+/// By default, the deletions made in a row, e.g. by holding backspace, are stored as a single
+/// op. Undo and analytics may want them apart. The ops are only merged within the changes of
+/// the same peer, so the config only affects the local storage and never the merged state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeleteMergeConfig {
+    /// Merge the deletions moving toward the start, like backspace
+    pub backward: bool,
+    /// Merge the deletions at the same position, like the delete key
+    pub forward: bool,
+    /// The max number of the elements deleted by a merged op
+    pub max_len: usize,
+}
+
+impl Default for DeleteMergeConfig {
+    fn default() -> Self {
+        Self::MERGE_ALL
+    }
+}
+
+impl DeleteMergeConfig {
+    pub const MERGE_ALL: Self = Self {
+        backward: true,
+        forward: true,
+        max_len: usize::MAX,
+    };
+
+    /// Keep every deletion as its own op, preserving the granularity of the keystrokes
+    pub const KEYSTROKE: Self = Self {
+        backward: false,
+        forward: false,
+        max_len: 1,
+    };
+
+    /// Whether `op` may be merged into `last`. Only the deletions are restricted.
+    pub(crate) fn can_merge(&self, last: &Op, op: &Op) -> bool {
+        if *self == Self::MERGE_ALL {
+            return true;
+        }
+
+        let (
+            InnerContent::List(InnerListOp::Delete(a)),
+            InnerContent::List(InnerListOp::Delete(b)),
+        ) = (&last.content, &op.content)
+        else {
+            return true;
+        };
+        if !a.is_mergable(b, &()) {
+            return true;
+        }
+
+        let mut merged = *a;
+        merged.merge(b, &());
+        let allowed = if merged.span.direction() > 0 {
+            self.forward
+        } else {
+            self.backward
+        };
+        allowed && merged.atom_len() <= self.max_len
+    }
+
+    /// Push `op` into `ops`, merging it into the last op when it's allowed.
+    /// Returns whether it's merged.
+    pub(crate) fn push_op(&self, ops: &mut RleVec<[Op; 1]>, op: Op) -> bool {
+        match ops.vec().last() {
+            Some(last) if !self.can_merge(last, &op) => {
+                ops.vec_mut().push(op);
+                false
+            }
+            _ => ops.push(op),
+        }
+    }
+}
+
+impl CounterConfig {
+    const MAX_UNITS: i128 = (1 << 53) - 1;
+
+    pub fn new(precision: Option<u8>, overflow: CounterOverflowPolicy) -> Self {
+        Self {
+            precision,
+            overflow,
+        }
+    }
+
+    fn scale(precision: u8) -> f64 {
+        10f64.powi(precision as i32)
+    }
+
+    /// Round `n` to the precision
+    pub fn round(&self, n: f64) -> f64 {
+        match self.precision {
+            Some(p) => {
+                let scale = Self::scale(p);
+                (n * scale).round() / scale
+            }
+            None => n,
+        }
+    }
+
+    /// Add `n` to `value`, returning the sum clamped to the range and whether it overflowed
+    pub(crate) fn add(&self, value: f64, n: f64) -> (f64, bool) {
+        match self.precision {
+            Some(p) => {
+                let scale = Self::scale(p);
+                let units = (value * scale).round() as i128 + (n * scale).round() as i128;
+                let clamped = units.clamp(-Self::MAX_UNITS, Self::MAX_UNITS);
+                (clamped as f64 / scale, clamped != units)
+            }
+            None => {
+                let sum = value + n;
+                if sum.is_finite() || !value.is_finite() || !n.is_finite() {
+                    (sum, false)
+                } else {
+                    (f64::MAX.copysign(sum), true)
+                }
+            }
+        }
+    }
 }
 
 impl LoroDoc {
@@ -15,6 +284,32 @@ impl LoroDoc {
         self.set_record_timestamp(config.record_timestamp());
         self.set_change_merge_interval(config.merge_interval());
         self.set_detached_editing(config.detached_editing());
+        self.set_import_event_coalescing_window(config.import_event_coalescing_window());
+        self.set_container_type_conflict_policy(config.container_type_conflict_policy());
+        self.set_text_paragraph_ids(config.text_paragraph_ids());
+        self.config.set_single_writer(config.single_writer());
+        self.set_extended_value_types(config.extended_value_types());
+        self.set_snapshot_checksums(config.snapshot_checksums());
+        self.set_record_apply_log(config.apply_log.is_enabled());
+        self.config_counter(*config.counter_config.read().unwrap());
+        *self.config.templates.write().unwrap() = config.templates.read().unwrap().clone();
+        self.set_decode_limits(config.decode_limits());
+        self.set_delete_merge_config(config.delete_merge());
+        self.set_map_conflict_resolver(config.map_conflict_resolver());
+        *self.config.compression_dictionaries.write().unwrap() = config.compression_dictionaries();
+        self.set_random_seed(config.random_seed());
+    }
+
+    /// Set up a doc created from the doc with the `source` config, e.g. by forking it, after
+    /// its config has been copied.
+    ///
+    /// If the source is seeded, the seed and the peer id of the new doc are drawn from the
+    /// source, so that the random sequences of the docs are deterministic but distinct.
+    pub(crate) fn derive_randomness_from(&self, source: &Configure) {
+        if source.random_seed().is_some() {
+            self.set_random_seed(Some(source.next_random_u64()));
+            self.renew_peer_id();
+        }
     }
 }
 
@@ -25,6 +320,22 @@ impl Default for Configure {
             record_timestamp: Arc::new(AtomicBool::new(false)),
             editable_detached_mode: Arc::new(AtomicBool::new(false)),
             merge_interval_in_s: Arc::new(AtomicI64::new(1000)),
+            import_event_coalescing_window: Arc::new(AtomicUsize::new(1)),
+            container_type_conflict_policy: Arc::new(AtomicU8::new(0)),
+            text_paragraph_ids: Arc::new(AtomicBool::new(false)),
+            single_writer: Arc::new(AtomicBool::new(false)),
+            extended_value_types: Arc::new(AtomicBool::new(false)),
+            snapshot_checksums: Arc::new(AtomicBool::new(false)),
+            seeded_random: Arc::new(AtomicBool::new(false)),
+            random_seed: Arc::new(AtomicU64::new(0)),
+            random_counter: Arc::new(AtomicU64::new(0)),
+            apply_log: ApplyLog::default(),
+            counter_config: Arc::new(RwLock::new(None)),
+            templates: Default::default(),
+            decode_limits: Default::default(),
+            delete_merge: Default::default(),
+            map_conflict_resolver: Default::default(),
+            compression_dictionaries: Default::default(),
         }
     }
 }
@@ -47,6 +358,48 @@ impl Configure {
                 self.editable_detached_mode
                     .load(std::sync::atomic::Ordering::Relaxed),
             )),
+            import_event_coalescing_window: Arc::new(AtomicUsize::new(
+                self.import_event_coalescing_window
+                    .load(std::sync::atomic::Ordering::Relaxed),
+            )),
+            container_type_conflict_policy: Arc::new(AtomicU8::new(
+                self.container_type_conflict_policy
+                    .load(std::sync::atomic::Ordering::Relaxed),
+            )),
+            text_paragraph_ids: Arc::new(AtomicBool::new(
+                self.text_paragraph_ids
+                    .load(std::sync::atomic::Ordering::Relaxed),
+            )),
+            single_writer: Arc::new(AtomicBool::new(
+                self.single_writer
+                    .load(std::sync::atomic::Ordering::Relaxed),
+            )),
+            extended_value_types: Arc::new(AtomicBool::new(
+                self.extended_value_types
+                    .load(std::sync::atomic::Ordering::Relaxed),
+            )),
+            snapshot_checksums: Arc::new(AtomicBool::new(
+                self.snapshot_checksums
+                    .load(std::sync::atomic::Ordering::Relaxed),
+            )),
+            seeded_random: Arc::new(AtomicBool::new(
+                self.seeded_random
+                    .load(std::sync::atomic::Ordering::Relaxed),
+            )),
+            random_seed: Arc::new(AtomicU64::new(
+                self.random_seed.load(std::sync::atomic::Ordering::Relaxed),
+            )),
+            random_counter: Arc::new(AtomicU64::new(
+                self.random_counter
+                    .load(std::sync::atomic::Ordering::Relaxed),
+            )),
+            apply_log: self.apply_log.fork(),
+            counter_config: Arc::new(RwLock::new(*self.counter_config.read().unwrap())),
+            templates: Arc::new(RwLock::new(self.templates.read().unwrap().clone())),
+            decode_limits: Arc::new(RwLock::new(self.decode_limits())),
+            delete_merge: Arc::new(RwLock::new(self.delete_merge())),
+            map_conflict_resolver: Arc::new(RwLock::new(self.map_conflict_resolver())),
+            compression_dictionaries: Arc::new(RwLock::new(self.compression_dictionaries())),
         }
     }
 
@@ -54,6 +407,26 @@ impl Configure {
         &self.text_style_config
     }
 
+    pub fn counter_config(&self) -> Option<CounterConfig> {
+        *self.counter_config.read().unwrap()
+    }
+
+    pub fn decode_limits(&self) -> DecodeLimits {
+        *self.decode_limits.read().unwrap()
+    }
+
+    pub fn delete_merge(&self) -> DeleteMergeConfig {
+        *self.delete_merge.read().unwrap()
+    }
+
+    pub fn map_conflict_resolver(&self) -> Option<MapConflictResolver> {
+        self.map_conflict_resolver.read().unwrap().clone()
+    }
+
+    pub fn compression_dictionaries(&self) -> Vec<CompressionDictionary> {
+        self.compression_dictionaries.read().unwrap().clone()
+    }
+
     pub fn record_timestamp(&self) -> bool {
         self.record_timestamp
             .load(std::sync::atomic::Ordering::Relaxed)
@@ -83,15 +456,137 @@ impl Configure {
         self.merge_interval_in_s
             .store(interval, std::sync::atomic::Ordering::Relaxed);
     }
+
+    pub fn import_event_coalescing_window(&self) -> usize {
+        self.import_event_coalescing_window
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    pub fn set_import_event_coalescing_window(&self, window: usize) {
+        self.import_event_coalescing_window
+            .store(window, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn container_type_conflict_policy(&self) -> ContainerTypeConflictPolicy {
+        match self
+            .container_type_conflict_policy
+            .load(std::sync::atomic::Ordering::Relaxed)
+        {
+            0 => ContainerTypeConflictPolicy::Reject,
+            _ => ContainerTypeConflictPolicy::Quarantine,
+        }
+    }
+
+    pub fn set_container_type_conflict_policy(&self, policy: ContainerTypeConflictPolicy) {
+        let v = match policy {
+            ContainerTypeConflictPolicy::Reject => 0,
+            ContainerTypeConflictPolicy::Quarantine => 1,
+        };
+        self.container_type_conflict_policy
+            .store(v, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn text_paragraph_ids(&self) -> bool {
+        self.text_paragraph_ids
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    pub fn set_text_paragraph_ids(&self, enabled: bool) {
+        self.text_paragraph_ids
+            .store(enabled, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn single_writer(&self) -> bool {
+        self.single_writer
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_single_writer(&self, enabled: bool) {
+        self.single_writer
+            .store(enabled, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn extended_value_types(&self) -> bool {
+        self.extended_value_types
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    pub fn set_extended_value_types(&self, enabled: bool) {
+        self.extended_value_types
+            .store(enabled, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn snapshot_checksums(&self) -> bool {
+        self.snapshot_checksums
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    pub fn set_snapshot_checksums(&self, enabled: bool) {
+        self.snapshot_checksums
+            .store(enabled, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn random_seed(&self) -> Option<u64> {
+        if self
+            .seeded_random
+            .load(std::sync::atomic::Ordering::Relaxed)
+        {
+            Some(self.random_seed.load(std::sync::atomic::Ordering::Relaxed))
+        } else {
+            None
+        }
+    }
+
+    /// Derive the internal randomness from `seed`, or from the system's secure random
+    /// generator if it's `None`. It restarts the random sequence of the seed.
+    pub fn set_random_seed(&self, seed: Option<u64>) {
+        self.random_seed
+            .store(seed.unwrap_or(0), std::sync::atomic::Ordering::Relaxed);
+        self.random_counter
+            .store(0, std::sync::atomic::Ordering::Relaxed);
+        self.seeded_random
+            .store(seed.is_some(), std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Draw a random value, e.g. for a new peer id.
+    ///
+    /// It's deterministic if the random seed is set.
+    pub(crate) fn next_random_u64(&self) -> u64 {
+        match self.random_seed() {
+            Some(seed) => {
+                let n = self
+                    .random_counter
+                    .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                splitmix64(seed ^ splitmix64(n))
+            }
+            None => DefaultRandom.next_u64(),
+        }
+    }
+
+    /// The seed of the fractional index jitter of the op `id`.
+    ///
+    /// It only depends on the random seed and the op id, rather than on a stateful generator,
+    /// so replaying the same ops always generates the same positions.
+    pub(crate) fn jitter_seed(&self, id: ID) -> u64 {
+        let seed = self.random_seed().unwrap_or(0);
+        splitmix64(seed ^ splitmix64(id.peer ^ splitmix64(id.counter as u64)))
+    }
+}
+
+/// The finalizer of [SplitMix64](https://prng.di.unimi.it/splitmix64.c), which maps
+/// each input to a well mixed output.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
 }
 
 #[derive(Debug)]
 pub struct DefaultRandom;
 
-#[cfg(test)]
-use std::sync::atomic::AtomicU64;
 use std::sync::{
-    atomic::{AtomicBool, AtomicI64},
+    atomic::{AtomicBool, AtomicI64, AtomicU64, AtomicU8, AtomicUsize},
     Arc, RwLock,
 };
 #[cfg(test)]
@@ -137,6 +137,12 @@ impl Ord for StyleOp {
 /// - 0              (6th bit)
 /// - 0              (7th bit)
 /// - 0              (8th bit):
+///
+/// The spare bits are not used to store common styles (e.g. bold or italic) inline on the
+/// text spans. A style must stay an anchor pair with its own op: the winner of concurrent
+/// `mark`/`unmark` calls is decided by the lamport of the style ops, and the expand
+/// behavior depends on where the anchors sit among concurrent insertions. A per-span flag
+/// has neither, and the peers on the current encoding could not decode it.
 #[derive(Default, Clone, Copy, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct TextStyleInfoFlag {
     data: u8,
@@ -1,12 +1,15 @@
 use fxhash::FxHashMap;
-use loro_common::InternalString;
+use loro_common::{InternalString, LoroError, LoroResult, LoroValue, ID};
 
-use super::{ExpandType, TextStyleInfoFlag};
+use super::{ExpandType, StyleOp, TextStyleInfoFlag};
 
 #[derive(Debug, Default, Clone)]
 pub struct StyleConfigMap {
     pub(crate) map: FxHashMap<InternalString, StyleConfig>,
     pub(crate) default_style: Option<StyleConfig>,
+    pub(crate) import_policy: StyleImportPolicy,
+    /// The imported styles flagged by [StyleImportPolicy::Flag]
+    pub(crate) flagged: Vec<InvalidStyle>,
 }
 
 impl StyleConfigMap {
@@ -14,6 +17,8 @@ impl StyleConfigMap {
         Self {
             map: FxHashMap::default(),
             default_style: None,
+            import_policy: StyleImportPolicy::Keep,
+            flagged: Vec::new(),
         }
     }
 
@@ -29,6 +34,64 @@ impl StyleConfigMap {
         self.map.get(key).copied().or(self.default_style)
     }
 
+    /// Set how to handle the imported styles that don't match the configuration
+    pub fn set_import_policy(&mut self, policy: StyleImportPolicy) {
+        self.import_policy = policy;
+    }
+
+    pub fn import_policy(&self) -> StyleImportPolicy {
+        self.import_policy
+    }
+
+    /// Check that the key is configured and that the value has the configured type.
+    ///
+    /// `Null` is always accepted, because it's the value used by `unmark`.
+    pub fn validate(&self, key: &InternalString, value: &LoroValue) -> LoroResult<()> {
+        let config = self
+            .get_config(key)
+            .ok_or_else(|| LoroError::StyleConfigMissing(key.clone()))?;
+        match config.value_type {
+            Some(t) if !matches!(value, LoroValue::Null) && !t.matches(value) => {
+                Err(LoroError::InvalidStyleValue {
+                    key: key.clone(),
+                    expected: t.as_str(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check an imported style against the configuration, including its expand rule
+    pub(crate) fn check_imported(&self, style: &StyleOp) -> Result<(), InvalidStyleReason> {
+        let Some(config) = self.get_config(&style.key) else {
+            return Err(InvalidStyleReason::UnknownKey);
+        };
+
+        if let Some(t) = config.value_type {
+            if !matches!(style.value, LoroValue::Null) && !t.matches(&style.value) {
+                return Err(InvalidStyleReason::ValueType(t));
+            }
+        }
+
+        let expected = if matches!(style.value, LoroValue::Null) {
+            TextStyleInfoFlag::new(config.expand.reverse())
+        } else {
+            TextStyleInfoFlag::new(config.expand)
+        };
+        if style.info != expected {
+            return Err(InvalidStyleReason::Expand(config.expand));
+        }
+
+        Ok(())
+    }
+
+    fn get_config(&self, key: &InternalString) -> Option<StyleConfig> {
+        match key.find(':') {
+            Some(index) => self.get(&key[..index].into()),
+            None => self.get(key),
+        }
+    }
+
     pub fn get_style_flag(&self, key: &InternalString) -> Option<TextStyleInfoFlag> {
         self._get_style_flag(key, false)
     }
@@ -50,15 +113,13 @@ impl StyleConfigMap {
     }
 
     pub fn default_rich_text_config() -> Self {
-        let mut map = Self {
-            map: FxHashMap::default(),
-            default_style: None,
-        };
+        let mut map = Self::new();
 
         map.map.insert(
             "bold".into(),
             StyleConfig {
                 expand: ExpandType::After,
+                value_type: None,
             },
         );
 
@@ -66,6 +127,7 @@ impl StyleConfigMap {
             "italic".into(),
             StyleConfig {
                 expand: ExpandType::After,
+                value_type: None,
             },
         );
 
@@ -73,6 +135,7 @@ impl StyleConfigMap {
             "underline".into(),
             StyleConfig {
                 expand: ExpandType::After,
+                value_type: None,
             },
         );
 
@@ -80,6 +143,7 @@ impl StyleConfigMap {
             "link".into(),
             StyleConfig {
                 expand: ExpandType::None,
+                value_type: None,
             },
         );
 
@@ -87,6 +151,7 @@ impl StyleConfigMap {
             "highlight".into(),
             StyleConfig {
                 expand: ExpandType::None,
+                value_type: None,
             },
         );
 
@@ -94,6 +159,7 @@ impl StyleConfigMap {
             "comment".into(),
             StyleConfig {
                 expand: ExpandType::None,
+                value_type: None,
             },
         );
 
@@ -101,6 +167,16 @@ impl StyleConfigMap {
             "code".into(),
             StyleConfig {
                 expand: ExpandType::None,
+                value_type: None,
+            },
+        );
+
+        // The annotation text of `TextHandler::set_ruby`
+        map.map.insert(
+            "ruby".into(),
+            StyleConfig {
+                expand: ExpandType::None,
+                value_type: Some(StyleValueType::String),
             },
         );
 
@@ -111,12 +187,15 @@ impl StyleConfigMap {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct StyleConfig {
     pub expand: ExpandType,
+    /// The type of the values of the style. Any value is accepted if it's `None`.
+    pub value_type: Option<StyleValueType>,
 }
 
 impl StyleConfig {
     pub fn new() -> Self {
         Self {
             expand: ExpandType::None,
+            value_type: None,
         }
     }
 
@@ -124,6 +203,85 @@ impl StyleConfig {
         self.expand = expand;
         self
     }
+
+    pub fn value_type(mut self, value_type: StyleValueType) -> Self {
+        self.value_type = Some(value_type);
+        self
+    }
+}
+
+/// The type of the values that a style accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StyleValueType {
+    Bool,
+    /// A double or an i64
+    Number,
+    String,
+    List,
+    Map,
+}
+
+impl StyleValueType {
+    pub fn matches(self, value: &LoroValue) -> bool {
+        matches!(
+            (self, value),
+            (Self::Bool, LoroValue::Bool(_))
+                | (
+                    Self::Number,
+                    LoroValue::Double(_) | LoroValue::I64(_) | LoroValue::Decimal(_)
+                )
+                | (Self::String, LoroValue::String(_))
+                | (Self::List, LoroValue::List(_))
+                | (Self::Map, LoroValue::Map(_))
+        )
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Bool => "a bool",
+            Self::Number => "a number",
+            Self::String => "a string",
+            Self::List => "a list",
+            Self::Map => "a map",
+        }
+    }
+}
+
+/// How to handle the imported styles that don't match the style configuration of the doc,
+/// e.g. the ones created by peers with another configuration.
+///
+/// The styles are checked when they are applied to the state by importing updates or by
+/// checking out.
+/// They are always kept in the history, so the policy only affects the state of this doc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StyleImportPolicy {
+    /// Apply the styles as they are
+    #[default]
+    Keep,
+    /// Don't apply the styles to the text
+    Drop,
+    /// Apply the styles, and record them to be taken by [`crate::LoroDoc::take_flagged_styles`]
+    Flag,
+}
+
+/// An imported style that doesn't match the style configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidStyle {
+    /// The id of the op that created the style
+    pub id: ID,
+    pub key: InternalString,
+    pub value: LoroValue,
+    pub reason: InvalidStyleReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidStyleReason {
+    /// The key isn't configured and there is no default style
+    UnknownKey,
+    /// The value doesn't have the configured type
+    ValueType(StyleValueType),
+    /// The style expands differently from the configured rule
+    Expand(ExpandType),
 }
 
 impl Default for StyleConfig {
@@ -1861,6 +1861,18 @@ impl RichtextState {
         &self,
         pos: usize,
         len: usize,
+    ) -> LoroResult<String> {
+        self.get_text_slice(pos, len, PosType::Event)
+    }
+
+    /// Get the text in `pos..pos + len`, where both are measured in `pos_type`.
+    ///
+    /// It fails if the range boundaries are in the middle of a code point.
+    pub(crate) fn get_text_slice(
+        &self,
+        pos: usize,
+        len: usize,
+        pos_type: PosType,
     ) -> LoroResult<String> {
         self.check_cache();
         let result = {
@@ -1872,22 +1884,31 @@ impl RichtextState {
                 return Ok(String::new());
             }
 
-            if pos + len > self.len_event() {
+            if pos + len > self.len(pos_type) {
                 return Err(LoroError::OutOfBound {
                     pos: pos + len,
-                    len: self.len_event(),
+                    len: self.len(pos_type),
                     info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
                 });
             }
 
             let mut ans = String::new();
             let (start, end) = (
-                self.tree.query::<EventIndexQuery>(&pos).unwrap().cursor,
-                self.tree
-                    .query::<EventIndexQuery>(&(pos + len))
-                    .unwrap()
-                    .cursor,
+                self.query_cursor(pos, pos_type),
+                self.query_cursor(pos + len, pos_type),
             );
+            // The utf-8 and utf-16 queries round the positions inside a code point down
+            for (index, cursor) in [(pos, start), (pos + len, end)] {
+                if matches!(pos_type, PosType::Bytes | PosType::Utf16)
+                    && self.get_index_from_cursor(cursor, pos_type) != Some(index)
+                {
+                    return Err(if pos_type == PosType::Bytes {
+                        LoroError::UTF8InUnicodeCodePoint { pos: index }
+                    } else {
+                        LoroError::UTF16InUnicodeCodePoint { pos: index }
+                    });
+                }
+            }
 
             for span in self.tree.iter_range(start..end) {
                 let start = span.start.unwrap_or(0);
@@ -1912,6 +1933,16 @@ impl RichtextState {
         result
     }
 
+    fn query_cursor(&self, pos: usize, pos_type: PosType) -> Cursor {
+        match pos_type {
+            PosType::Bytes => self.tree.query::<ByteQuery>(&pos).unwrap().cursor,
+            PosType::Unicode => self.tree.query::<UnicodeQuery>(&pos).unwrap().cursor,
+            PosType::Utf16 => self.tree.query::<Utf16Query>(&pos).unwrap().cursor,
+            PosType::Entity => self.tree.query::<EntityQuery>(&pos).unwrap().cursor,
+            PosType::Event => self.tree.query::<EventIndexQuery>(&pos).unwrap().cursor,
+        }
+    }
+
     // PERF: can be splitted into two methods. One is without cursor_to_event_index
     // PERF: can be speed up a lot by detecting whether the range is in a single leaf first
     /// This is used to accept changes from DiffCalculator
@@ -0,0 +1,204 @@
+//! Verifying the states of containers against the checksums stored in snapshots.
+//!
+//! A snapshot decodes the container states lazily, so the corrupted bytes of a container are
+//! only noticed when it's read, if at all. When [`LoroDoc::set_snapshot_checksums`] is
+//! enabled, a fast snapshot stores the checksum of the state of every alive container in an
+//! optional trailing section, which the older versions skip. It's off by default, because
+//! computing the checksums decodes every container state on export.
+//! [`LoroDoc::verify_container`] checks one container against it, and
+//! [`LoroDoc::import_with_verification`] checks the given containers right after importing a
+//! snapshot, without decoding the other ones.
+use bytes::Bytes;
+use fxhash::FxHashMap;
+use loro_common::{ContainerID, LoroError, LoroResult};
+
+use crate::{
+    arena::SharedArena,
+    convergence::hash_value,
+    encoding::ImportStatus,
+    state::{ContainerState, DocState, State},
+    version::Frontiers,
+    LoroDoc,
+};
+
+/// The checksums loaded from a snapshot, with the version they describe
+#[derive(Debug, Clone)]
+pub(crate) struct SnapshotChecksums {
+    frontiers: Frontiers,
+    checksums: FxHashMap<ContainerID, [u8; 16]>,
+}
+
+impl LoroDoc {
+    /// Check the current state of the container `id` against its checksum in the snapshot
+    /// imported by the doc.
+    ///
+    /// It returns an error if the doc wasn't loaded from a snapshot that has the checksum of the
+    /// container, e.g. a snapshot exported by an older version or a shallow snapshot, or if the
+    /// state has changed since the snapshot was imported.
+    pub fn verify_container(&self, id: &ContainerID) -> LoroResult<bool> {
+        self.commit_then_renew();
+        let checksums = self.container_checksums.lock().unwrap();
+        let Some(checksums) = checksums.as_ref() else {
+            return Err(LoroError::NotFoundError(
+                "The doc has no container checksums".into(),
+            ));
+        };
+        let Some(expected) = checksums.checksums.get(id) else {
+            return Err(LoroError::NotFoundError(
+                format!("The checksum of the container {id}").into_boxed_str(),
+            ));
+        };
+        if self.state_frontiers() != checksums.frontiers {
+            return Err(LoroError::ArgErr(
+                "The state has changed since the snapshot was imported".into(),
+            ));
+        }
+
+        let mut state = self.app_state().lock().unwrap();
+        Ok(container_checksum(&mut state, &self.arena, id) == *expected)
+    }
+
+    /// Import `bytes` like [`LoroDoc::import`], then verify the `containers` with
+    /// [`LoroDoc::verify_container`].
+    ///
+    /// It returns [`LoroError::ContainerChecksumMismatch`] for the first container whose state
+    /// doesn't match its checksum. The imported data is kept in that case, so the doc should be
+    /// discarded.
+    pub fn import_with_verification(
+        &self,
+        bytes: &[u8],
+        containers: &[ContainerID],
+    ) -> LoroResult<ImportStatus> {
+        let status = self.import(bytes)?;
+        for id in containers {
+            if !self.verify_container(id)? {
+                return Err(LoroError::ContainerChecksumMismatch(Box::new(id.clone())));
+            }
+        }
+
+        Ok(status)
+    }
+
+    pub(crate) fn set_container_checksums(&self, checksums: Option<SnapshotChecksums>) {
+        *self.container_checksums.lock().unwrap() = checksums;
+    }
+}
+
+/// The MD5 checksum of the state of the container `id`, including the styles of a text
+fn container_checksum(state: &mut DocState, arena: &SharedArena, id: &ContainerID) -> [u8; 16] {
+    let idx = arena.register_container(id);
+    let value = state.with_state_mut(idx, |state| match state {
+        State::RichtextState(s) => s.get_richtext_value(),
+        _ => state.get_value(),
+    });
+    let mut ctx = md5::Context::new();
+    hash_value(&mut ctx, &value);
+    ctx.compute().0
+}
+
+/// Encode the checksums of the containers `ids` as the sorted list of their ids and checksums
+pub(crate) fn encode_container_checksums(
+    state: &mut DocState,
+    arena: &SharedArena,
+    ids: impl IntoIterator<Item = ContainerID>,
+) -> Bytes {
+    let mut ids: Vec<(String, ContainerID)> =
+        ids.into_iter().map(|id| (id.to_string(), id)).collect();
+    ids.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    let mut bytes = Vec::new();
+    leb128::write::unsigned(&mut bytes, ids.len() as u64).unwrap();
+    for (name, id) in ids {
+        leb128::write::unsigned(&mut bytes, name.len() as u64).unwrap();
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(&container_checksum(state, arena, &id));
+    }
+
+    bytes.into()
+}
+
+pub(crate) fn decode_container_checksums(
+    mut bytes: &[u8],
+    frontiers: Frontiers,
+) -> LoroResult<SnapshotChecksums> {
+    let err = || LoroError::DecodeError("Invalid container checksums".into());
+    let len = leb128::read::unsigned(&mut bytes).map_err(|_| err())?;
+    let mut checksums = FxHashMap::default();
+    for _ in 0..len {
+        let id_len = leb128::read::unsigned(&mut bytes).map_err(|_| err())? as usize;
+        if bytes.len() < id_len + 16 {
+            return Err(err());
+        }
+
+        let id = std::str::from_utf8(&bytes[..id_len]).map_err(|_| err())?;
+        let id = ContainerID::try_from(id).map_err(|_| err())?;
+        let checksum: [u8; 16] = bytes[id_len..id_len + 16].try_into().unwrap();
+        checksums.insert(id, checksum);
+        bytes = &bytes[id_len + 16..];
+    }
+
+    Ok(SnapshotChecksums {
+        frontiers,
+        checksums,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{encoding::ExportMode, value::ToJson};
+
+    #[test]
+    fn verify_containers_of_snapshot() {
+        let doc = LoroDoc::new_auto_commit();
+        doc.set_snapshot_checksums(true);
+        let text = doc.get_text("text");
+        text.insert(0, "hello").unwrap();
+        let map = doc.get_map("map");
+        map.insert("a", 1).unwrap();
+        let list = map
+            .insert_container("list", crate::ListHandler::new_detached())
+            .unwrap();
+        list.push(2).unwrap();
+        doc.commit_then_renew();
+        let bytes = doc.export(ExportMode::Snapshot).unwrap();
+
+        let new_doc = LoroDoc::new_auto_commit();
+        new_doc
+            .import_with_verification(&bytes, &[text.id(), list.id()])
+            .unwrap();
+        assert!(new_doc.verify_container(&map.id()).unwrap());
+        assert_eq!(
+            new_doc.get_deep_value().to_json_value(),
+            doc.get_deep_value().to_json_value()
+        );
+
+        new_doc.get_text("text").insert(0, "x").unwrap();
+        assert!(new_doc.verify_container(&text.id()).is_err());
+
+        // The checksums are opt-in
+        doc.set_snapshot_checksums(false);
+        let no_checksums = LoroDoc::new_auto_commit();
+        no_checksums
+            .import(&doc.export(ExportMode::Snapshot).unwrap())
+            .unwrap();
+        assert!(no_checksums.verify_container(&text.id()).is_err());
+
+        // The updates have no checksums
+        let updates = LoroDoc::new_auto_commit();
+        updates
+            .import(&doc.export(ExportMode::all_updates()).unwrap())
+            .unwrap();
+        assert!(updates.verify_container(&text.id()).is_err());
+
+        let bytes = encode_container_checksums(
+            &mut doc.app_state().lock().unwrap(),
+            &doc.arena,
+            [text.id()],
+        );
+        let mut checksums = decode_container_checksums(&bytes, doc.state_frontiers()).unwrap();
+        assert_eq!(checksums.checksums.len(), 1);
+        checksums.checksums.insert(text.id(), [0; 16]);
+        doc.set_container_checksums(Some(checksums));
+        assert!(!doc.verify_container(&text.id()).unwrap());
+    }
+}
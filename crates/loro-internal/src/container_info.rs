@@ -0,0 +1,176 @@
+use loro_common::{ContainerID, ContainerType, LoroValue, ID};
+
+use crate::{
+    change::Timestamp, container::idx::ContainerIdx, event::Index, handler::Handler,
+    state::DocState, LoroDoc,
+};
+
+/// The metadata of a container in the document, returned by [`LoroDoc::list_containers`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerInfo {
+    pub id: ContainerID,
+    pub container_type: ContainerType,
+    /// The container that this container was created in. It's `None` for root containers.
+    pub parent: Option<ContainerID>,
+    /// The path from the root to the container in the current state.
+    ///
+    /// It's `None` if the container is deleted.
+    pub path: Option<Vec<(ContainerID, Index)>>,
+    /// The id of the op that created the container. It's `None` for root containers.
+    pub created_by: Option<ID>,
+    /// The timestamp of the change that created the container.
+    ///
+    /// It's `None` for root containers, or when the change is not in the history, e.g. it's
+    /// still in the pending transaction or it's before the start of a shallow doc.
+    pub created_at: Option<Timestamp>,
+    /// Whether the container is reachable from a root container in the current state
+    pub alive: bool,
+}
+
+/// A [`LoroValue::ContainerRef`] whose target doesn't exist or has been deleted,
+/// returned by [`LoroDoc::dangling_container_refs`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DanglingContainerRef {
+    /// The container whose value holds the reference
+    pub container: ContainerID,
+    /// The referenced container
+    pub target: ContainerID,
+}
+
+/// Filter the containers returned by [`LoroDoc::list_containers`].
+///
+/// The default filter accepts every container.
+#[derive(Debug, Clone, Default)]
+pub struct ContainerFilter {
+    /// Only include the containers of this type
+    pub container_type: Option<ContainerType>,
+    /// Skip the deleted containers
+    pub alive_only: bool,
+}
+
+impl LoroDoc {
+    /// List all the containers known by the document with their metadata,
+    /// including the deleted ones that are still referenced by the history.
+    ///
+    /// The containers are read from the arena, which registers every container
+    /// when its first op is created or imported, so it doesn't need to traverse
+    /// the document value.
+    pub fn list_containers(&self, filter: &ContainerFilter) -> Vec<ContainerInfo> {
+        let ids = self.arena.export_containers();
+        let parents = self.arena.export_parents();
+        let oplog = self.oplog.lock().unwrap();
+        let mut state = self.state.lock().unwrap();
+        let mut ans = Vec::new();
+        for (i, (id, parent)) in ids.into_iter().zip(parents).enumerate() {
+            let container_type = id.container_type();
+            if filter.container_type.is_some_and(|t| t != container_type) {
+                continue;
+            }
+
+            let idx = ContainerIdx::from_index_and_type(i as u32, container_type);
+            // A child container whose parent is unknown can't be reached from the roots
+            let alive = if id.is_root() || parent.is_some() {
+                !state.is_deleted(idx)
+            } else {
+                false
+            };
+            if filter.alive_only && !alive {
+                continue;
+            }
+
+            let created_by = match &id {
+                ContainerID::Root { .. } => None,
+                ContainerID::Normal { peer, counter, .. } => Some(ID::new(*peer, *counter)),
+            };
+            let created_at = created_by.and_then(|id| oplog.get_change_at(id).map(|c| c.timestamp));
+            ans.push(ContainerInfo {
+                parent: parent.map(|p| self.arena.idx_to_id(p).unwrap()),
+                path: if alive { state.get_path(idx) } else { None },
+                id,
+                container_type,
+                created_by,
+                created_at,
+                alive,
+            });
+        }
+
+        ans
+    }
+}
+
+impl LoroDoc {
+    /// Get the handler of the container referenced by a [`LoroValue::ContainerRef`].
+    ///
+    /// Returns `None` if the reference is dangling, i.e. the target doesn't exist
+    /// or has been deleted.
+    pub fn resolve_container_ref(&self, target: &ContainerID) -> Option<Handler> {
+        let alive = is_alive(&mut self.state.lock().unwrap(), target);
+        if alive {
+            Some(Handler::new_attached(target.clone(), self.clone()))
+        } else {
+            None
+        }
+    }
+
+    /// Find all the [`LoroValue::ContainerRef`] values held by the alive containers
+    /// whose targets don't exist or have been deleted.
+    pub fn dangling_container_refs(&self) -> Vec<DanglingContainerRef> {
+        let mut state = self.state.lock().unwrap();
+        let mut ans = Vec::new();
+        for (i, id) in self.arena.export_containers().into_iter().enumerate() {
+            let container_type = id.container_type();
+            if !matches!(
+                container_type,
+                ContainerType::Map | ContainerType::List | ContainerType::MovableList
+            ) {
+                continue;
+            }
+
+            let idx = ContainerIdx::from_index_and_type(i as u32, container_type);
+            if state.is_deleted(idx) {
+                continue;
+            }
+
+            let mut targets = Vec::new();
+            collect_container_refs(&state.get_value_by_idx(idx), &mut targets);
+            for target in targets {
+                if !is_alive(&mut state, &target) {
+                    ans.push(DanglingContainerRef {
+                        container: id.clone(),
+                        target,
+                    });
+                }
+            }
+        }
+
+        ans
+    }
+}
+
+fn is_alive(state: &mut DocState, id: &ContainerID) -> bool {
+    if id.is_root() {
+        return true;
+    }
+
+    match state.arena.id_to_idx(id) {
+        Some(idx) => !state.is_deleted(idx),
+        None => false,
+    }
+}
+
+fn collect_container_refs(value: &LoroValue, ans: &mut Vec<ContainerID>) {
+    match value {
+        LoroValue::ContainerRef(id) => ans.push(id.clone()),
+        LoroValue::List(list) => {
+            for v in list.iter() {
+                collect_container_refs(v, ans);
+            }
+        }
+        LoroValue::Map(map) => {
+            for v in map.values() {
+                collect_container_refs(v, ans);
+            }
+        }
+        _ => {}
+    }
+}
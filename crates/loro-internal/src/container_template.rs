@@ -0,0 +1,284 @@
+//! Templates of container structures that can be instantiated repeatedly.
+//!
+//! Apps often create the same structure many times, e.g. a "task card" made of a map with a
+//! title text, a list of tags and a map of properties. A [`ContainerTemplate`] describes such
+//! a structure once. After it's registered with [`LoroDoc::register_template`],
+//! [`LoroDoc::instantiate_template`] creates the whole structure, with fresh container ids,
+//! in a single commit.
+use std::sync::Arc;
+
+use fxhash::FxHashMap;
+use loro_common::{ContainerID, InternalString, LoroError, LoroResult, LoroValue};
+
+use crate::{
+    handler::{Handler, ValueOrHandler},
+    HandlerTrait, ListHandler, LoroDoc, MapHandler, MovableListHandler, TextHandler,
+};
+
+/// A subtree of containers with their initial values.
+///
+/// The styles of the texts are not kept. Trees cannot be templated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContainerTemplate {
+    /// A plain value. It can only be a child of a container, and it cannot be a container id.
+    Value(LoroValue),
+    Text(String),
+    List(Vec<ContainerTemplate>),
+    MovableList(Vec<ContainerTemplate>),
+    Map(FxHashMap<String, ContainerTemplate>),
+    #[cfg(feature = "counter")]
+    Counter(f64),
+}
+
+impl ContainerTemplate {
+    /// Capture the current content of a container as a template
+    pub fn from_handler(handler: &Handler) -> LoroResult<Self> {
+        Ok(match handler {
+            Handler::Text(t) => Self::Text(t.to_string()),
+            Handler::List(l) => {
+                let mut children = Vec::with_capacity(l.len());
+                let mut err = None;
+                l.for_each(|v| match Self::from_value_or_handler(v) {
+                    Ok(c) => children.push(c),
+                    Err(e) => err = Some(e),
+                });
+                if let Some(e) = err {
+                    return Err(e);
+                }
+
+                Self::List(children)
+            }
+            Handler::MovableList(l) => {
+                let mut children = Vec::with_capacity(l.len());
+                let mut err = None;
+                l.for_each(|v| match Self::from_value_or_handler(v) {
+                    Ok(c) => children.push(c),
+                    Err(e) => err = Some(e),
+                });
+                if let Some(e) = err {
+                    return Err(e);
+                }
+
+                Self::MovableList(children)
+            }
+            Handler::Map(m) => {
+                let mut children = FxHashMap::default();
+                let mut err = None;
+                m.for_each(|k, v| match Self::from_value_or_handler(v) {
+                    Ok(c) => {
+                        children.insert(k.to_string(), c);
+                    }
+                    Err(e) => err = Some(e),
+                });
+                if let Some(e) = err {
+                    return Err(e);
+                }
+
+                Self::Map(children)
+            }
+            #[cfg(feature = "counter")]
+            Handler::Counter(c) => Self::Counter(*c.get_value().as_double().unwrap()),
+            Handler::Tree(_) | Handler::Unknown(_) => {
+                return Err(LoroError::ArgErr(
+                    format!("Cannot make a template of the {} container", handler.kind())
+                        .into_boxed_str(),
+                ))
+            }
+        })
+    }
+
+    fn from_value_or_handler(v: ValueOrHandler) -> LoroResult<Self> {
+        match v {
+            ValueOrHandler::Value(v) => Ok(Self::Value(v)),
+            ValueOrHandler::Handler(h) => Self::from_handler(&h),
+        }
+    }
+
+    fn check(&self, is_root: bool) -> LoroResult<()> {
+        match self {
+            Self::Value(LoroValue::Container(_)) => Err(LoroError::ArgErr(
+                "A template cannot refer to an existing container".into(),
+            )),
+            Self::Value(_) if is_root => Err(LoroError::ArgErr(
+                "The root of a template must be a container".into(),
+            )),
+            Self::List(children) | Self::MovableList(children) => {
+                children.iter().try_for_each(|c| c.check(false))
+            }
+            Self::Map(children) => children.values().try_for_each(|c| c.check(false)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Build the detached containers, which are copied when they are attached
+    fn to_detached(&self) -> LoroResult<ValueOrHandler> {
+        Ok(match self {
+            Self::Value(v) => ValueOrHandler::Value(v.clone()),
+            Self::Text(s) => {
+                let text = TextHandler::new_detached();
+                text.insert(0, s)?;
+                ValueOrHandler::Handler(Handler::Text(text))
+            }
+            Self::List(children) => {
+                let list = ListHandler::new_detached();
+                for (i, c) in children.iter().enumerate() {
+                    match c.to_detached()? {
+                        ValueOrHandler::Value(v) => list.insert(i, v)?,
+                        ValueOrHandler::Handler(h) => {
+                            list.insert_container(i, h)?;
+                        }
+                    }
+                }
+
+                ValueOrHandler::Handler(Handler::List(list))
+            }
+            Self::MovableList(children) => {
+                let list = MovableListHandler::new_detached();
+                for (i, c) in children.iter().enumerate() {
+                    match c.to_detached()? {
+                        ValueOrHandler::Value(v) => list.insert(i, v)?,
+                        ValueOrHandler::Handler(h) => {
+                            list.insert_container(i, h)?;
+                        }
+                    }
+                }
+
+                ValueOrHandler::Handler(Handler::MovableList(list))
+            }
+            Self::Map(children) => {
+                let map = MapHandler::new_detached();
+                for (k, c) in children.iter() {
+                    match c.to_detached()? {
+                        ValueOrHandler::Value(v) => map.insert(k, v)?,
+                        ValueOrHandler::Handler(h) => {
+                            map.insert_container(k, h)?;
+                        }
+                    }
+                }
+
+                ValueOrHandler::Handler(Handler::Map(map))
+            }
+            #[cfg(feature = "counter")]
+            Self::Counter(n) => {
+                let counter = crate::handler::counter::CounterHandler::new_detached();
+                counter.increment(*n)?;
+                ValueOrHandler::Handler(Handler::Counter(counter))
+            }
+        })
+    }
+}
+
+impl LoroDoc {
+    /// Register a template under `name`, replacing the template registered before.
+    pub fn register_template(&self, name: &str, template: ContainerTemplate) -> LoroResult<()> {
+        template.check(true)?;
+        self.config
+            .templates
+            .write()
+            .unwrap()
+            .insert(name.into(), Arc::new(template));
+        Ok(())
+    }
+
+    /// Remove the template registered under `name`. Returns whether it existed.
+    pub fn unregister_template(&self, name: &str) -> bool {
+        self.config
+            .templates
+            .write()
+            .unwrap()
+            .remove(&InternalString::from(name))
+            .is_some()
+    }
+
+    /// Create the structure of the template `name`, and insert it into the list or the movable
+    /// list `parent` at `index`. Returns the handler of the created container.
+    ///
+    /// The containers get fresh ids, and the whole structure is created in a single commit.
+    pub fn instantiate_template(
+        &self,
+        name: &str,
+        parent: &ContainerID,
+        index: usize,
+    ) -> LoroResult<Handler> {
+        let template = self
+            .config
+            .templates
+            .read()
+            .unwrap()
+            .get(&InternalString::from(name))
+            .cloned()
+            .ok_or_else(|| LoroError::NotFoundError(format!("Template {name}").into_boxed_str()))?;
+        let ValueOrHandler::Handler(child) = template.to_detached()? else {
+            unreachable!("the root of a template is checked to be a container")
+        };
+        let parent =
+            self.get_handler(parent.clone())
+                .ok_or_else(|| LoroError::ContainersNotFound {
+                    containers: Box::new(vec![parent.clone()]),
+                })?;
+
+        // Flush the pending ops, so the commit only contains the template
+        self.commit_then_renew();
+        let ans = match parent {
+            Handler::List(l) => l.insert_container(index, child),
+            Handler::MovableList(l) => l.insert_container(index, child),
+            _ => Err(LoroError::ArgErr(
+                format!(
+                    "A template can only be instantiated in a list, but the parent is a {}",
+                    parent.kind()
+                )
+                .into_boxed_str(),
+            )),
+        };
+        self.commit_then_renew();
+        ans
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn instantiate_with_fresh_ids() {
+        let doc = LoroDoc::new_auto_commit();
+        let source = MapHandler::new_detached();
+        source.insert("done", false).unwrap();
+        source
+            .insert_container("title", TextHandler::new_detached())
+            .unwrap()
+            .insert(0, "Untitled")
+            .unwrap();
+        source
+            .insert_container("tags", ListHandler::new_detached())
+            .unwrap()
+            .push("todo")
+            .unwrap();
+        let template = ContainerTemplate::from_handler(&Handler::Map(source)).unwrap();
+        doc.register_template("card", template).unwrap();
+
+        let cards = doc.get_list("cards");
+        let a = doc.instantiate_template("card", &cards.id(), 0).unwrap();
+        let b = doc.instantiate_template("card", &cards.id(), 0).unwrap();
+        assert_ne!(a.id(), b.id());
+        assert_eq!(a.get_deep_value(), b.get_deep_value());
+        assert_eq!(doc.len_changes(), 2);
+        let a = a.into_map().unwrap();
+        a.get_child_handler("title")
+            .unwrap()
+            .as_text()
+            .unwrap()
+            .insert(0, "A ")
+            .unwrap();
+        assert_ne!(a.get_deep_value(), b.get_deep_value());
+
+        assert!(doc.instantiate_template("missing", &cards.id(), 0).is_err());
+        assert!(doc
+            .instantiate_template("card", &doc.get_map("map").id(), 0)
+            .is_err());
+        assert!(doc
+            .register_template("value", ContainerTemplate::Value(1.into()))
+            .is_err());
+        assert!(doc.unregister_template("card"));
+    }
+}
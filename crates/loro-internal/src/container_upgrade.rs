@@ -0,0 +1,277 @@
+//! Upgrade a container to a richer type in place, e.g. a List to a MovableList.
+use fxhash::FxHashMap;
+use loro_common::{ContainerID, ContainerType, LoroError, LoroResult};
+
+use crate::{
+    handler::{Handler, MovableListHandler, TreeHandler, ValueOrHandler},
+    HandlerTrait, LoroDoc, TreeParentId,
+};
+
+/// The commit message prefix of the changes made by [`LoroDoc::upgrade_container`].
+///
+/// The rest of the message is the JSON array of the `[old, new]` pairs of the container ids.
+pub const UPGRADE_COMMIT_MSG_PREFIX: &str = "loro:upgrade ";
+
+impl LoroDoc {
+    /// Upgrade the container to a richer type in place and return the id of the new container.
+    ///
+    /// Only `List -> MovableList` is supported. The new container takes the place of the old
+    /// one in its parent, or takes its name if it's a root container. The elements are moved
+    /// into it in order, the child containers are copied, and the old container is emptied.
+    ///
+    /// It's committed as one change whose commit message records the ids of the replaced
+    /// containers (see [`UPGRADE_COMMIT_MSG_PREFIX`]), so that the links to the old
+    /// containers can be followed by [`LoroDoc::get_upgraded_container`]. The peers that
+    /// don't know about upgrades see an ordinary change that replaces the list.
+    ///
+    /// The elements inserted into the old container concurrently are not moved. Upgrading
+    /// the old container again moves them to the end of the new container.
+    pub fn upgrade_container(
+        &self,
+        id: &ContainerID,
+        to: ContainerType,
+    ) -> LoroResult<ContainerID> {
+        if id.container_type() != ContainerType::List || to != ContainerType::MovableList {
+            return Err(LoroError::ArgErr(
+                format!("Cannot upgrade {} to {}", id, to).into_boxed_str(),
+            ));
+        }
+
+        if !self.can_edit() {
+            return Err(LoroError::EditWhenDetached);
+        }
+
+        if !self.has_container(id) {
+            return Err(LoroError::NotFoundError(id.to_string().into_boxed_str()));
+        }
+
+        let upgraded = self.get_upgraded_container(id);
+        self.batch(|| {
+            let old = self.get_list(id);
+            let mut copied = Vec::new();
+            let new = match upgraded {
+                Some(new) => self.get_movable_list(new),
+                None => {
+                    let new = self.replace_in_parent(id)?;
+                    copied.push((id.clone(), new.id()));
+                    new
+                }
+            };
+
+            let mut elements = Vec::with_capacity(old.len());
+            old.for_each(|v| elements.push(v));
+            for v in elements {
+                match v {
+                    ValueOrHandler::Value(v) => new.push(v)?,
+                    ValueOrHandler::Handler(h) => {
+                        let child = new.push_container(Handler::new_unattached(h.c_type()))?;
+                        copy_content(&h, &child, &mut copied)?;
+                    }
+                }
+            }
+
+            old.delete(0, old.len())?;
+            if !copied.is_empty() {
+                let pairs: Vec<(String, String)> = copied
+                    .iter()
+                    .map(|(old, new)| (old.to_string(), new.to_string()))
+                    .collect();
+                self.set_next_commit_message(&format!(
+                    "{}{}",
+                    UPGRADE_COMMIT_MSG_PREFIX,
+                    serde_json::to_string(&pairs).unwrap()
+                ));
+            }
+
+            Ok(new.id())
+        })
+    }
+
+    /// Get the container that replaced the given one by [`LoroDoc::upgrade_container`],
+    /// following the later upgrades.
+    ///
+    /// It also resolves the child containers that were copied by the upgrade.
+    /// Returns `None` if the container was not upgraded.
+    pub fn get_upgraded_container(&self, id: &ContainerID) -> Option<ContainerID> {
+        let upgrades = self.container_upgrades();
+        let mut ans = upgrades.get(id)?;
+        // Bounded in case of the malformed commit messages
+        for _ in 0..upgrades.len() {
+            match upgrades.get(ans) {
+                Some(next) => ans = next,
+                None => break,
+            }
+        }
+
+        Some(ans.clone())
+    }
+
+    fn container_upgrades(&self) -> FxHashMap<ContainerID, ContainerID> {
+        let oplog = self.oplog.lock().unwrap();
+        let mut ans = FxHashMap::default();
+        oplog.change_store().visit_all_changes(&mut |c| {
+            let Some(pairs) = c
+                .message()
+                .and_then(|msg| msg.strip_prefix(UPGRADE_COMMIT_MSG_PREFIX))
+            else {
+                return;
+            };
+
+            let Ok(pairs) = serde_json::from_str::<Vec<(String, String)>>(pairs) else {
+                return;
+            };
+
+            for (old, new) in pairs {
+                if let (Ok(old), Ok(new)) = (
+                    ContainerID::try_from(old.as_str()),
+                    ContainerID::try_from(new.as_str()),
+                ) {
+                    ans.insert(old, new);
+                }
+            }
+        });
+
+        ans
+    }
+
+    /// Create an empty movable list in the place of the container in its parent
+    fn replace_in_parent(&self, id: &ContainerID) -> LoroResult<MovableListHandler> {
+        if let ContainerID::Root { name, .. } = id {
+            return Ok(
+                self.get_movable_list(ContainerID::new_root(name, ContainerType::MovableList))
+            );
+        }
+
+        let not_attached =
+            || LoroError::ArgErr(format!("{} is not attached to the doc", id).into_boxed_str());
+        let parent = self
+            .arena
+            .id_to_idx(id)
+            .and_then(|idx| self.arena.get_parent(idx))
+            .and_then(|parent| self.arena.idx_to_id(parent))
+            .and_then(|parent| self.get_handler(parent))
+            .ok_or_else(not_attached)?;
+        let is_old = |v: Option<ValueOrHandler>| matches!(v, Some(ValueOrHandler::Handler(h)) if &h.id() == id);
+        let child = MovableListHandler::new_detached();
+        match parent {
+            Handler::Map(map) => {
+                let mut key = None;
+                map.for_each(|k, v| {
+                    if is_old(Some(v)) {
+                        key = Some(k.to_string());
+                    }
+                });
+                map.insert_container(&key.ok_or_else(not_attached)?, child)
+            }
+            Handler::List(list) => {
+                let pos = (0..list.len())
+                    .find(|&i| is_old(list.get_(i)))
+                    .ok_or_else(not_attached)?;
+                list.delete(pos, 1)?;
+                list.insert_container(pos, child)
+            }
+            Handler::MovableList(list) => {
+                let pos = (0..list.len())
+                    .find(|&i| is_old(list.get_(i)))
+                    .ok_or_else(not_attached)?;
+                list.set_container(pos, child)
+            }
+            _ => Err(LoroError::ArgErr(
+                format!("Cannot upgrade {} inside a {}", id, parent.c_type()).into_boxed_str(),
+            )),
+        }
+    }
+}
+
+/// Copy the content of `src` into the empty container `dst` of the same type,
+/// recording the ids of the copied child containers.
+fn copy_content(
+    src: &Handler,
+    dst: &Handler,
+    copied: &mut Vec<(ContainerID, ContainerID)>,
+) -> LoroResult<()> {
+    copied.push((src.id(), dst.id()));
+    match (src, dst) {
+        (Handler::Map(src), Handler::Map(dst)) => {
+            let mut entries = Vec::new();
+            src.for_each(|k, v| entries.push((k.to_string(), v)));
+            for (k, v) in entries {
+                match v {
+                    ValueOrHandler::Value(v) => dst.insert(&k, v)?,
+                    ValueOrHandler::Handler(h) => {
+                        let child =
+                            dst.insert_container(&k, Handler::new_unattached(h.c_type()))?;
+                        copy_content(&h, &child, copied)?;
+                    }
+                }
+            }
+        }
+        (Handler::List(src), Handler::List(dst)) => {
+            let mut elements = Vec::with_capacity(src.len());
+            src.for_each(|v| elements.push(v));
+            for v in elements {
+                match v {
+                    ValueOrHandler::Value(v) => dst.push(v)?,
+                    ValueOrHandler::Handler(h) => {
+                        let child = dst.push_container(Handler::new_unattached(h.c_type()))?;
+                        copy_content(&h, &child, copied)?;
+                    }
+                }
+            }
+        }
+        (Handler::MovableList(src), Handler::MovableList(dst)) => {
+            let mut elements = Vec::with_capacity(src.len());
+            src.for_each(|v| elements.push(v));
+            for v in elements {
+                match v {
+                    ValueOrHandler::Value(v) => dst.push(v)?,
+                    ValueOrHandler::Handler(h) => {
+                        let child = dst.push_container(Handler::new_unattached(h.c_type()))?;
+                        copy_content(&h, &child, copied)?;
+                    }
+                }
+            }
+        }
+        (Handler::Text(src), Handler::Text(dst)) => dst.apply_delta(&src.get_delta())?,
+        (Handler::Tree(src), Handler::Tree(dst)) => {
+            copy_tree_children(src, dst, TreeParentId::Root, TreeParentId::Root, copied)?
+        }
+        #[cfg(feature = "counter")]
+        (Handler::Counter(src), Handler::Counter(dst)) => {
+            dst.increment(*src.get_value().as_double().unwrap())?
+        }
+        _ => {
+            return Err(LoroError::ArgErr(
+                format!("Cannot copy {}", src.id()).into_boxed_str(),
+            ))
+        }
+    }
+
+    Ok(())
+}
+
+fn copy_tree_children(
+    src: &TreeHandler,
+    dst: &TreeHandler,
+    src_parent: TreeParentId,
+    dst_parent: TreeParentId,
+    copied: &mut Vec<(ContainerID, ContainerID)>,
+) -> LoroResult<()> {
+    for node in src.children(&src_parent).unwrap_or_default() {
+        let new_node = dst.create(dst_parent)?;
+        copy_content(
+            &Handler::Map(src.get_meta(node)?),
+            &Handler::Map(dst.get_meta(new_node)?),
+            copied,
+        )?;
+        copy_tree_children(
+            src,
+            dst,
+            TreeParentId::Node(node),
+            TreeParentId::Node(new_node),
+            copied,
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,218 @@
+//! Digests of the doc states, for replicas to check that they converged after syncing.
+//!
+//! A [`ConvergenceDigest`] is a hash of the canonical form of the state at a version. Two
+//! replicas that have the same version must have the same digest, so a mismatch reveals a
+//! divergence bug. [`LoroDoc::verify_convergence`] captures a [`DivergenceBundle`] with the
+//! data needed to reproduce it when that happens.
+use loro_common::{LoroError, LoroResult, LoroValue};
+use serde::{Deserialize, Serialize};
+
+use crate::{loro::ExportMode, version::Frontiers, LoroDoc, VersionVector};
+
+/// The digest of the state of a doc at a version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvergenceDigest {
+    pub frontiers: Frontiers,
+    /// The MD5 hash of the canonical form of the state
+    pub hash: [u8; 16],
+}
+
+/// The diagnostic data captured when a replica's state doesn't match a [`ConvergenceDigest`].
+#[derive(Debug, Clone)]
+pub struct DivergenceBundle {
+    pub frontiers: Frontiers,
+    /// The hash in the digest from the other replica
+    pub expected: [u8; 16],
+    /// The hash computed on this replica
+    pub actual: [u8; 16],
+    /// The state of this replica at `frontiers`
+    pub value: LoroValue,
+    pub oplog_vv: VersionVector,
+    /// All the updates of this replica, to replay the divergence
+    pub updates: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncodedDigest<'a> {
+    #[serde(borrow)]
+    frontiers: &'a [u8],
+    hash: [u8; 16],
+}
+
+impl ConvergenceDigest {
+    pub fn encode(&self) -> Vec<u8> {
+        postcard::to_allocvec(&EncodedDigest {
+            frontiers: &self.frontiers.encode(),
+            hash: self.hash,
+        })
+        .unwrap()
+    }
+
+    pub fn decode(bytes: &[u8]) -> LoroResult<Self> {
+        let encoded: EncodedDigest = postcard::from_bytes(bytes)
+            .map_err(|_| LoroError::DecodeError("Invalid convergence digest".into()))?;
+        Ok(Self {
+            frontiers: Frontiers::decode(encoded.frontiers)?,
+            hash: encoded.hash,
+        })
+    }
+}
+
+impl LoroDoc {
+    /// Compute the digest of the state at `frontiers`.
+    ///
+    /// The digest only depends on the state, so it's the same on every replica that has the
+    /// version, no matter how the history was received. Empty root containers are ignored,
+    /// since a root container exists on a replica as soon as it's accessed.
+    pub fn convergence_digest(&self, frontiers: &Frontiers) -> LoroResult<ConvergenceDigest> {
+        let value = self.get_value_at(frontiers)?;
+        Ok(ConvergenceDigest {
+            frontiers: frontiers.clone(),
+            hash: hash_state(&value),
+        })
+    }
+
+    /// Check the state of this doc against the digest computed by another replica.
+    ///
+    /// Returns `None` if the states match, or the diagnostic bundle if they diverged.
+    /// It fails if this doc doesn't have the version of the digest.
+    pub fn verify_convergence(
+        &self,
+        digest: &ConvergenceDigest,
+    ) -> LoroResult<Option<DivergenceBundle>> {
+        let value = self.get_value_at(&digest.frontiers)?;
+        let actual = hash_state(&value);
+        if actual == digest.hash {
+            return Ok(None);
+        }
+
+        Ok(Some(DivergenceBundle {
+            frontiers: digest.frontiers.clone(),
+            expected: digest.hash,
+            actual,
+            value,
+            oplog_vv: self.oplog_vv(),
+            updates: self.export(ExportMode::all_updates()).unwrap(),
+        }))
+    }
+
+    fn get_value_at(&self, frontiers: &Frontiers) -> LoroResult<LoroValue> {
+        self.commit_then_renew();
+        let vv = self.oplog_vv();
+        if let Some(id) = frontiers.iter().find(|id| !vv.includes_id(*id)) {
+            return Err(LoroError::FrontiersNotFound(id));
+        }
+
+        if !self.is_detached() && frontiers == &self.oplog_frontiers() {
+            return Ok(self.get_deep_value());
+        }
+
+        Ok(self.fork_at(frontiers).get_deep_value())
+    }
+}
+
+fn hash_state(value: &LoroValue) -> [u8; 16] {
+    let mut ctx = md5::Context::new();
+    let LoroValue::Map(roots) = value else {
+        unreachable!()
+    };
+
+    let mut names: Vec<_> = roots
+        .iter()
+        .filter(|(_, v)| !is_empty_root(v))
+        .map(|(k, _)| k)
+        .collect();
+    names.sort_unstable();
+    for name in names {
+        hash_str(&mut ctx, name);
+        hash_value(&mut ctx, &roots[name]);
+    }
+
+    ctx.compute().0
+}
+
+fn is_empty_root(value: &LoroValue) -> bool {
+    match value {
+        LoroValue::List(list) => list.is_empty(),
+        LoroValue::Map(map) => map.is_empty(),
+        LoroValue::String(s) => s.is_empty(),
+        LoroValue::Double(d) => *d == 0.,
+        _ => false,
+    }
+}
+
+fn hash_str(ctx: &mut md5::Context, s: &str) {
+    ctx.consume((s.len() as u64).to_le_bytes());
+    ctx.consume(s.as_bytes());
+}
+
+pub(crate) fn hash_value(ctx: &mut md5::Context, value: &LoroValue) {
+    match value {
+        LoroValue::Null => ctx.consume([0]),
+        LoroValue::Bool(b) => ctx.consume([1, *b as u8]),
+        LoroValue::Double(d) => {
+            ctx.consume([2]);
+            ctx.consume(d.to_bits().to_le_bytes());
+        }
+        LoroValue::I64(i) => {
+            ctx.consume([3]);
+            ctx.consume(i.to_le_bytes());
+        }
+        LoroValue::Binary(b) => {
+            ctx.consume([4]);
+            ctx.consume((b.len() as u64).to_le_bytes());
+            ctx.consume(&b[..]);
+        }
+        LoroValue::String(s) => {
+            ctx.consume([5]);
+            hash_str(ctx, s);
+        }
+        LoroValue::List(list) => {
+            ctx.consume([6]);
+            ctx.consume((list.len() as u64).to_le_bytes());
+            for v in list.iter() {
+                hash_value(ctx, v);
+            }
+        }
+        LoroValue::Map(map) => {
+            ctx.consume([7]);
+            ctx.consume((map.len() as u64).to_le_bytes());
+            let mut keys: Vec<_> = map.keys().collect();
+            keys.sort_unstable();
+            for k in keys {
+                hash_str(ctx, k);
+                hash_value(ctx, &map[k]);
+            }
+        }
+        LoroValue::Container(id) => {
+            ctx.consume([8]);
+            hash_str(ctx, &id.to_string());
+        }
+        LoroValue::ContainerRef(id) => {
+            ctx.consume([9]);
+            hash_str(ctx, &id.to_string());
+        }
+        LoroValue::Timestamp(t) => {
+            ctx.consume([10]);
+            ctx.consume(t.to_le_bytes());
+        }
+        LoroValue::Decimal(d) => {
+            ctx.consume([11]);
+            hash_str(ctx, d.as_str());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hash_ignores_map_order_and_empty_roots() {
+        let a = crate::loro_value!({"map": {"a": 1, "b": [true, "x"]}, "list": []});
+        let b = crate::loro_value!({"map": {"b": [true, "x"], "a": 1}});
+        assert_eq!(hash_state(&a), hash_state(&b));
+        let c = crate::loro_value!({"map": {"b": [true, "x"], "a": 2}});
+        assert_ne!(hash_state(&a), hash_state(&c));
+    }
+}
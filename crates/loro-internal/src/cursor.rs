@@ -72,6 +72,37 @@ impl Cursor {
         }
     }
 
+    /// Create the cursor of `pos` in a sequence container of length `len`, where `id` is the
+    /// id of the element at `pos`.
+    ///
+    /// A cursor in an empty container or at the end of it is not anchored to any element.
+    pub(crate) fn anchored(
+        container: ContainerID,
+        id: Option<ID>,
+        len: usize,
+        pos: usize,
+        side: Side,
+    ) -> Option<Self> {
+        if len == 0 {
+            return Some(Self::new(
+                None,
+                container,
+                if side == Side::Middle {
+                    Side::Left
+                } else {
+                    side
+                },
+                0,
+            ));
+        }
+
+        if len <= pos {
+            return Some(Self::new(None, container, Side::Right, len));
+        }
+
+        Some(Self::new(Some(id?), container, side, pos))
+    }
+
     pub fn encode(&self) -> Vec<u8> {
         postcard::to_allocvec(self).unwrap()
     }
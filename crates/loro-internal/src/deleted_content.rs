@@ -0,0 +1,166 @@
+//! Look up the content removed between two versions, e.g. to show the deletions or to
+//! restore them from a trash view.
+use loro_common::{
+    ContainerID, IdSpan, Lamport, LoroError, LoroResult, LoroValue, PeerID, Timestamp,
+};
+use rle::HasLength;
+
+use crate::{
+    container::list::list_op::InnerListOp,
+    handler::{HandlerTrait, ListHandler, TextHandler},
+    op::InnerContent,
+    oplog::OpLog,
+    span::HasLamport,
+    version::Frontiers,
+};
+
+/// A span of text removed by one delete op.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletedSpan {
+    pub text: String,
+    pub deleted_by: PeerID,
+    /// The timestamp of the change that deleted the text.
+    /// It's 0 unless the timestamps are recorded.
+    pub at: Timestamp,
+}
+
+/// The list elements removed by one delete op.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeletedListSpan {
+    /// The removed values. The child containers are [`LoroValue::Container`].
+    pub values: Vec<LoroValue>,
+    pub deleted_by: PeerID,
+    /// The timestamp of the change that deleted the elements.
+    /// It's 0 unless the timestamps are recorded.
+    pub at: Timestamp,
+}
+
+/// A delete op on the container, with the ids of the elements it removed
+struct Deletion {
+    lamport: Lamport,
+    peer: PeerID,
+    at: Timestamp,
+    target: IdSpan,
+}
+
+impl TextHandler {
+    /// Get the text removed by the changes that are in `to` but not in `from`, in causal order.
+    ///
+    /// The text that is concurrently deleted by several peers shows up once per deletion.
+    /// The text inserted before the shallow root of a shallow doc can't be recovered.
+    pub fn deleted_content_between(
+        &self,
+        from: &Frontiers,
+        to: &Frontiers,
+    ) -> LoroResult<Vec<DeletedSpan>> {
+        let doc = self.doc().ok_or(LoroError::MisuseDetachedContainer {
+            method: "deleted_content_between",
+        })?;
+        let oplog = doc.oplog().lock().unwrap();
+        let deletions = deletions_between(&oplog, &self.id(), from, to)?;
+        Ok(deletions
+            .into_iter()
+            .map(|d| {
+                let mut text = String::new();
+                for op in oplog.iter_ops(d.target) {
+                    if let InnerContent::List(InnerListOp::InsertText { slice, .. }) =
+                        &op.op().content
+                    {
+                        text.push_str(std::str::from_utf8(slice.as_bytes()).unwrap());
+                    }
+                }
+
+                DeletedSpan {
+                    text,
+                    deleted_by: d.peer,
+                    at: d.at,
+                }
+            })
+            .collect())
+    }
+}
+
+impl ListHandler {
+    /// Get the values removed by the changes that are in `to` but not in `from`, in causal order.
+    ///
+    /// The elements that are concurrently deleted by several peers show up once per deletion.
+    /// The elements inserted before the shallow root of a shallow doc can't be recovered.
+    pub fn deleted_content_between(
+        &self,
+        from: &Frontiers,
+        to: &Frontiers,
+    ) -> LoroResult<Vec<DeletedListSpan>> {
+        let doc = self.doc().ok_or(LoroError::MisuseDetachedContainer {
+            method: "deleted_content_between",
+        })?;
+        let oplog = doc.oplog().lock().unwrap();
+        let deletions = deletions_between(&oplog, &self.id(), from, to)?;
+        Ok(deletions
+            .into_iter()
+            .map(|d| {
+                let mut values = Vec::with_capacity(d.target.atom_len());
+                for op in oplog.iter_ops(d.target) {
+                    if let InnerContent::List(InnerListOp::Insert { slice, .. }) = &op.op().content
+                    {
+                        values.extend(oplog.arena.get_values(slice.to_range()));
+                    }
+                }
+
+                DeletedListSpan {
+                    values,
+                    deleted_by: d.peer,
+                    at: d.at,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Collect the delete ops on the container in the changes that are in `to` but not in `from`
+fn deletions_between(
+    oplog: &OpLog,
+    container: &ContainerID,
+    from: &Frontiers,
+    to: &Frontiers,
+) -> LoroResult<Vec<Deletion>> {
+    let to_vv = |f: &Frontiers| {
+        oplog.dag().frontiers_to_vv(f).ok_or_else(|| {
+            match f.iter().find(|id| !oplog.vv().includes_id(*id)) {
+                Some(id) => LoroError::FrontiersNotFound(id),
+                None => {
+                    LoroError::ArgErr(format!("Cannot find the version {:?}", f).into_boxed_str())
+                }
+            }
+        })
+    };
+    let from_vv = to_vv(from)?;
+    let to_vv = to_vv(to)?;
+    let Some(idx) = oplog.arena.id_to_idx(container) else {
+        return Ok(Vec::new());
+    };
+
+    let mut ans = Vec::new();
+    for span in to_vv.sub_iter(&from_vv) {
+        for op in oplog.iter_ops(span) {
+            if op.container() != idx {
+                continue;
+            }
+
+            if let InnerContent::List(InnerListOp::Delete(del)) = &op.op().content {
+                ans.push(Deletion {
+                    lamport: op.lamport(),
+                    peer: op.peer,
+                    at: op.timestamp,
+                    target: IdSpan::new(
+                        del.id_start.peer,
+                        del.id_start.counter,
+                        del.id_start.counter + del.atom_len() as i32,
+                    ),
+                });
+            }
+        }
+    }
+
+    ans.sort_by_key(|d| (d.lamport, d.peer));
+    Ok(ans)
+}
@@ -40,6 +40,22 @@ impl Default for UpdateOptions {
     }
 }
 
+/// The unit of the diff computed by [`TextHandler::update_with_mode`](crate::TextHandler::update_with_mode)
+///
+/// A coarser unit is faster, and an edit inside a unit replaces the whole unit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiffMode {
+    /// Unicode scalar values
+    #[default]
+    Char,
+    /// The words, the runs of whitespace and the punctuation marks, by UAX #29
+    Word,
+    /// The sentences, by UAX #29
+    Sentence,
+    /// The lines, including their trailing `\n`
+    Line,
+}
+
 #[derive(thiserror::Error, Debug, PartialEq)]
 pub enum UpdateTimeoutError {
     #[error("Timeout")]
@@ -109,8 +125,7 @@ impl<D: DiffHandler> OperateProxy<D> {
         self.handler.insert(old_index, new_index, new_len);
     }
 
-    #[allow(unused)]
-    fn unwrap(self) -> D {
+    pub fn unwrap(self) -> D {
         self.handler
     }
 }
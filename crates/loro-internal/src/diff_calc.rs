@@ -34,6 +34,7 @@ use crate::{
         Delta, DeltaItem, DeltaValue, ElementDelta, MapDelta, MapValue, MovableListInnerDelta,
     },
     event::{DiffVariant, InternalDiff},
+    map_conflict::resolve_map_key,
     op::{InnerContent, RichOp, SliceRange, SliceWithId},
     span::{HasId, HasLamport},
     version::Frontiers,
@@ -488,6 +489,7 @@ impl DiffCalculatorTrait for MapDiffCalculator {
         DiffCalcVersionInfo { from_vv, to_vv, .. }: DiffCalcVersionInfo,
         mut on_new_container: impl FnMut(&ContainerID),
     ) -> (InternalDiff, DiffMode) {
+        let resolver = oplog.configure.map_conflict_resolver();
         match self.current_mode {
             DiffMode::Checkout | DiffMode::Import => oplog.with_history_cache(|h| {
                 let checkout_index = &h.get_checkout_index().map;
@@ -521,6 +523,35 @@ impl DiffCalculatorTrait for MapDiffCalculator {
                     changed.push((k, Some(peek_to)));
                 }
 
+                if let Some(resolver) = resolver {
+                    let keys: FxHashSet<InternalString> = from_map
+                        .into_keys()
+                        .chain(changed.drain(..).map(|(k, _)| k))
+                        .collect();
+                    let mut updated = FxHashMap::default();
+                    for key in keys {
+                        let from =
+                            resolve_map_key(h, oplog, self.container_idx, &key, from_vv, &resolver);
+                        let to =
+                            resolve_map_key(h, oplog, self.container_idx, &key, to_vv, &resolver);
+                        if from.as_ref().map(|v| &v.value) == to.as_ref().map(|v| &v.value) {
+                            continue;
+                        }
+
+                        if let Some(MapValue {
+                            value: Some(LoroValue::Container(c)),
+                            ..
+                        }) = &to
+                        {
+                            on_new_container(c);
+                        }
+
+                        updated.insert(key, to);
+                    }
+
+                    return (InternalDiff::Map(MapDelta { updated }), DiffMode::Checkout);
+                }
+
                 let mut updated =
                     FxHashMap::with_capacity_and_hasher(changed.len(), Default::default());
                 for (key, value) in changed {
@@ -543,7 +574,23 @@ impl DiffCalculatorTrait for MapDiffCalculator {
                 (InternalDiff::Map(MapDelta { updated }), DiffMode::Checkout)
             }),
             DiffMode::ImportGreaterUpdates | DiffMode::Linear => {
-                let changed = std::mem::take(&mut self.changed);
+                let mut changed = std::mem::take(&mut self.changed);
+                if let Some(resolver) = resolver {
+                    // The new ops may be concurrent with each other
+                    oplog.with_history_cache(|h| {
+                        for (key, value) in changed.iter_mut() {
+                            *value = resolve_map_key(
+                                h,
+                                oplog,
+                                self.container_idx,
+                                key,
+                                to_vv,
+                                &resolver,
+                            );
+                        }
+                    });
+                }
+
                 let mode = self.current_mode;
                 // Reset this field to avoid we use `has_all` to cache the diff calc and use it next round
                 // (In the next round we need to use the checkout mode)
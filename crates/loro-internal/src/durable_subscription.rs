@@ -0,0 +1,236 @@
+//! Subscriptions whose events can be replayed after the consumer crashes.
+//!
+//! A mirror of the doc in a database has to apply every event exactly once, or at least once
+//! if applying it is idempotent. A [`DurableSubscription`] numbers the event batches it
+//! delivers, and keeps them until the consumer acknowledges them. After a crash, the consumer
+//! resumes from the last sequence number it persisted, and the batches after it are delivered
+//! again, without diffing the state.
+//!
+//! The number of the retained batches is bounded. If a consumer falls behind further than the
+//! bound, the oldest batches are evicted, and resuming before them fails with
+//! [`EventsEvicted`]. The consumer has to rebuild its mirror from the state then.
+//!
+//! The retained batches and the sequence numbers only live in the memory of the process that
+//! holds the doc. They cover the failures of the consumer while the doc stays loaded, e.g. a
+//! failed write to the database, but not a crash of that process: the sequence numbers of a
+//! new subscription start from 1 again. To recover from it, the consumer persists the version
+//! of the doc it has mirrored, and catches up with the diff from that version, see
+//! `LoroDoc::diff`. The batches are replayed as they were delivered, they are not composed
+//! into one diff.
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    event::{ContainerDiff, DiffEvent, DocDiff},
+    LoroDoc, Subscription,
+};
+
+/// The callback of a [`DurableSubscription`]. It receives the sequence number of the batch and
+/// the event.
+pub type DurableSubscriber = Arc<dyn (for<'a> Fn(u64, DiffEvent<'a>)) + Send + Sync>;
+
+/// The batches after the requested sequence number were evicted before being acknowledged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("The event batches from {requested} to {first_retained} (exclusive) were evicted")]
+pub struct EventsEvicted {
+    /// The first batch the consumer asked for
+    pub requested: u64,
+    /// The first batch that is still retained
+    pub first_retained: u64,
+}
+
+#[derive(Debug)]
+struct DurableState {
+    /// The sequence number of the last delivered batch
+    last_seq: u64,
+    acked: u64,
+    /// The sequence number of the last batch evicted without being acknowledged
+    evicted: u64,
+    max_retained: usize,
+    /// The batches that are not acknowledged, ordered by the sequence number
+    retained: VecDeque<(u64, Arc<DocDiff>)>,
+}
+
+impl DurableState {
+    fn push(&mut self, diff: Arc<DocDiff>) -> u64 {
+        self.last_seq += 1;
+        self.retained.push_back((self.last_seq, diff));
+        while self.retained.len() > self.max_retained {
+            let (seq, _) = self.retained.pop_front().unwrap();
+            self.evicted = seq;
+        }
+
+        self.last_seq
+    }
+
+    fn ack(&mut self, seq: u64) {
+        let seq = seq.min(self.last_seq);
+        self.acked = self.acked.max(seq);
+        while self.retained.front().is_some_and(|(s, _)| *s <= seq) {
+            self.retained.pop_front();
+        }
+    }
+}
+
+/// A root subscription whose event batches have sequence numbers and can be replayed
+/// until they are acknowledged.
+///
+/// The events stop when it's dropped.
+pub struct DurableSubscription {
+    state: Arc<Mutex<DurableState>>,
+    callback: DurableSubscriber,
+    _sub: Subscription,
+}
+
+impl std::fmt::Debug for DurableSubscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DurableSubscription")
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl DurableSubscription {
+    /// Acknowledge the batches up to `seq`, so they are no longer retained.
+    pub fn ack(&self, seq: u64) {
+        self.state.lock().unwrap().ack(seq);
+    }
+
+    /// Acknowledge the batches up to `seq`, and deliver the retained batches after it to the
+    /// callback again, in order. Returns the number of the replayed batches.
+    pub fn resume(&self, seq: u64) -> Result<usize, EventsEvicted> {
+        let batches: Vec<(u64, Arc<DocDiff>)> = {
+            let mut state = self.state.lock().unwrap();
+            if state.evicted > seq {
+                return Err(EventsEvicted {
+                    requested: seq + 1,
+                    first_retained: state.evicted + 1,
+                });
+            }
+
+            state.ack(seq);
+            state.retained.iter().cloned().collect()
+        };
+
+        for (seq, diff) in batches.iter() {
+            let events: Vec<&ContainerDiff> = diff.diff.iter().collect();
+            (self.callback)(
+                *seq,
+                DiffEvent {
+                    current_target: None,
+                    events: &events,
+                    event_meta: diff,
+                },
+            );
+        }
+
+        Ok(batches.len())
+    }
+
+    /// The sequence number of the last delivered batch, or 0 if there is none
+    pub fn last_seq(&self) -> u64 {
+        self.state.lock().unwrap().last_seq
+    }
+
+    /// The greatest acknowledged sequence number
+    pub fn acked_seq(&self) -> u64 {
+        self.state.lock().unwrap().acked
+    }
+
+    /// The number of the batches waiting for the acknowledgment
+    pub fn retained_len(&self) -> usize {
+        self.state.lock().unwrap().retained.len()
+    }
+}
+
+impl LoroDoc {
+    /// Subscribe to all the events of the doc like [`LoroDoc::subscribe_root`], but number
+    /// the batches and keep up to `max_retained` of them until they are acknowledged.
+    ///
+    /// The sequence numbers start from 1 for every subscription, and the retained batches are
+    /// only kept in memory, see the [module docs](self).
+    pub fn subscribe_durable(
+        &self,
+        max_retained: usize,
+        callback: DurableSubscriber,
+    ) -> DurableSubscription {
+        let state = Arc::new(Mutex::new(DurableState {
+            last_seq: 0,
+            acked: 0,
+            evicted: 0,
+            max_retained,
+            retained: VecDeque::new(),
+        }));
+        let sub = {
+            let state = state.clone();
+            let callback = callback.clone();
+            self.subscribe_root(Arc::new(move |e| {
+                let diff = Arc::new(DocDiff {
+                    from: e.event_meta.from.clone(),
+                    to: e.event_meta.to.clone(),
+                    origin: e.event_meta.origin.clone(),
+                    by: e.event_meta.by,
+                    diff: e.events.iter().map(|d| (*d).clone()).collect(),
+                });
+                let seq = state.lock().unwrap().push(diff);
+                callback(seq, e);
+            }))
+        };
+
+        DurableSubscription {
+            state,
+            callback,
+            _sub: sub,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::HandlerTrait;
+    use loro_common::LoroValue;
+
+    #[test]
+    fn replay_unacknowledged_batches() {
+        let doc = LoroDoc::new_auto_commit();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sub = {
+            let received = received.clone();
+            doc.subscribe_durable(
+                2,
+                Arc::new(move |seq, e| {
+                    assert_eq!(e.events.len(), 1);
+                    received.lock().unwrap().push(seq);
+                }),
+            )
+        };
+
+        let text = doc.get_text("text");
+        for s in ["a", "b", "c"] {
+            text.insert(0, s).unwrap();
+            doc.commit_then_renew();
+        }
+
+        assert_eq!(sub.last_seq(), 3);
+        assert_eq!(sub.retained_len(), 2);
+        assert_eq!(
+            sub.resume(0),
+            Err(EventsEvicted {
+                requested: 1,
+                first_retained: 2
+            })
+        );
+
+        received.lock().unwrap().clear();
+        assert_eq!(sub.resume(2), Ok(1));
+        assert_eq!(*received.lock().unwrap(), vec![3]);
+        assert_eq!(sub.acked_seq(), 2);
+        sub.ack(3);
+        assert_eq!(sub.retained_len(), 0);
+        assert_eq!(sub.resume(3), Ok(0));
+        assert_eq!(text.get_value(), LoroValue::from("cba"));
+    }
+}
@@ -1,21 +1,33 @@
 pub(crate) mod arena;
+pub mod columnar;
 pub(crate) mod fast_snapshot;
+mod import_stats;
 pub(crate) mod json_schema;
 mod outdated_encode_reordered;
+mod redact;
 mod shallow_snapshot;
 pub(crate) mod value;
 pub(crate) mod value_register;
+pub(crate) use import_stats::calc_causal_gaps;
+pub use import_stats::{ImportStats, OpAgeStats, OP_AGE_BUCKET_BOUNDS};
 pub(crate) use outdated_encode_reordered::{
     decode_op, encode_op, get_op_prop, EncodedDeleteStartId, IterableEncodedDeleteStartId,
 };
 use outdated_encode_reordered::{import_changes_to_oplog, ImportChangesResult};
+pub use redact::strip_update_payloads;
 pub(crate) use value::OwnedValue;
 
+use crate::arena::SharedArena;
+use crate::change::Change;
+use crate::import_pipeline::ImportPipeline;
 use crate::op::OpWithId;
-use crate::version::{Frontiers, VersionRange};
+use crate::version::{Frontiers, ImVersionVector, VersionRange};
 use crate::LoroDoc;
 use crate::{oplog::OpLog, LoroError, VersionVector};
-use loro_common::{HasIdSpan, IdLpSpan, IdSpan, LoroEncodeError, LoroResult, PeerID, ID};
+use fxhash::FxHashSet;
+use loro_common::{
+    ContainerID, HasIdSpan, IdLpSpan, IdSpan, LoroEncodeError, LoroResult, PeerID, ID,
+};
 use num_traits::{FromPrimitive, ToPrimitive};
 use rle::{HasLength, Sliceable};
 use std::borrow::Cow;
@@ -56,8 +68,24 @@ pub enum ExportMode<'a> {
     Snapshot,
     /// It contains the history since the `from` version vector.
     Updates { from: Cow<'a, VersionVector> },
+    /// It contains the history since the `from` version vector, where the consecutive
+    /// changes from the same peer whose timestamps are within `merge_interval` seconds
+    /// are squashed into one change.
+    ///
+    /// It's useful for the receivers that don't need the keystroke-granular history,
+    /// e.g. cold storage. The ops and their IDs and lamports are unchanged, but the
+    /// timestamps of the squashed changes are replaced by the timestamp of the first one.
+    /// A change that depends on other peers' changes is never squashed into the change
+    /// before it, so the causal dependencies of every op are preserved.
+    UpdatesSquashed {
+        from: Cow<'a, VersionVector>,
+        merge_interval: i64,
+    },
     /// This mode exports the history in the specified range.
     UpdatesInRange { spans: Cow<'a, [IdSpan]> },
+    /// The same as [`ExportMode::Updates`], but `from` is an [`ImVersionVector`], e.g. the
+    /// one returned by [`LoroDoc::oplog_im_vv`], which is cheap to keep and share.
+    UpdatesIm { from: Cow<'a, ImVersionVector> },
     /// The shallow snapshot only contains the history since the target frontiers
     ShallowSnapshot(Cow<'a, Frontiers>),
     /// The state only snapshot exports the state of the target version
@@ -91,6 +119,22 @@ impl<'a> ExportMode<'a> {
         }
     }
 
+    /// It contains the history since the `from` version vector.
+    pub fn updates_im(from: &'a ImVersionVector) -> Self {
+        ExportMode::UpdatesIm {
+            from: Cow::Borrowed(from),
+        }
+    }
+
+    /// It contains the history since the `from` version vector, where the consecutive
+    /// changes from the same peer within `merge_interval` seconds are squashed.
+    pub fn updates_squashed(from: &'a VersionVector, merge_interval: i64) -> Self {
+        ExportMode::UpdatesSquashed {
+            from: Cow::Borrowed(from),
+            merge_interval,
+        }
+    }
+
     /// It contains all the history of the document.
     pub fn all_updates() -> Self {
         ExportMode::Updates {
@@ -226,6 +270,23 @@ impl TryFrom<[u8; 2]> for EncodeMode {
 pub struct ImportStatus {
     pub success: VersionRange,
     pub pending: Option<VersionRange>,
+    /// The statistics of the imported changes.
+    ///
+    /// It's empty when a snapshot is imported into an empty doc.
+    pub stats: ImportStats,
+    /// How the snapshot was applied, `None` if the imported blob is not a snapshot
+    pub snapshot: Option<SnapshotImportMode>,
+}
+
+/// How an imported snapshot was applied to the doc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotImportMode {
+    /// The doc was empty, so it was initialized with the state in the snapshot.
+    Reset,
+    /// The doc already had history, so the changes in the snapshot were imported as updates
+    /// and merged with the existing ones. `success` only covers the changes that the doc
+    /// didn't have, and it's empty if the doc already included the snapshot.
+    Merged,
 }
 
 /// The encoder used to encode the container states.
@@ -302,9 +363,12 @@ pub(crate) fn encode_oplog(oplog: &OpLog, vv: &VersionVector, mode: EncodeMode)
     encode_header_and_body(mode, body)
 }
 
+/// Decode the changes of `parsed` and import them, skipping the ones rejected by the filters
+/// of `pipeline`
 pub(crate) fn decode_oplog(
     oplog: &mut OpLog,
     parsed: ParsedHeaderAndBody,
+    pipeline: Option<&ImportPipeline>,
 ) -> Result<ImportStatus, LoroError> {
     let ParsedHeaderAndBody { mode, body, .. } = parsed;
     let changes = match mode {
@@ -315,6 +379,18 @@ pub(crate) fn decode_oplog(
         EncodeMode::FastUpdates => fast_snapshot::decode_updates(oplog, body.to_vec().into()),
         EncodeMode::Auto => unreachable!(),
     }?;
+    let changes = match pipeline {
+        Some(pipeline) if pipeline.has_filters() => {
+            pipeline.filter_changes(changes, &oplog.arena)?
+        }
+        _ => changes,
+    };
+    oplog.check_container_type_conflicts(&changes)?;
+    let mut stats = ImportStats::from_changes(&changes);
+    oplog
+        .configure
+        .apply_log
+        .record_duplicates(&changes, oplog.vv());
     let ImportChangesResult {
         mut imported,
         latest_ids,
@@ -326,15 +402,19 @@ pub(crate) fn decode_oplog(
     pending_changes.iter().for_each(|c| {
         pending.extends_to_include_id_span(c.id_span());
     });
+    oplog.configure.apply_log.record_pending(&pending);
     // TODO: PERF: should we use hashmap to filter latest_ids with the same peer first?
     oplog.try_apply_pending(latest_ids, Some(&mut imported));
     oplog.import_unknown_lamport_pending_changes(pending_changes)?;
     if !changes_that_have_deps_before_shallow_root.is_empty() {
         return Err(LoroError::ImportUpdatesThatDependsOnOutdatedVersion);
     }
+    stats.causal_gaps = oplog.pending_causal_gaps();
     Ok(ImportStatus {
         success: imported,
         pending: (!pending.is_empty()).then_some(pending),
+        stats,
+        snapshot: mode.is_snapshot().then_some(SnapshotImportMode::Merged),
     })
 }
 
@@ -451,6 +531,52 @@ pub(crate) fn export_fast_updates(doc: &LoroDoc, vv: &VersionVector) -> Vec<u8>
     .unwrap()
 }
 
+/// Write the updates since `vv` in the same format as [export_fast_updates], one block at
+/// a time.
+///
+/// The checksum in the header covers the whole body, so the blocks are encoded twice: once to
+/// compute the checksum and once to write them. It trades time for memory usage bounded by
+/// the size of a block.
+pub(crate) fn export_fast_updates_to<W: std::io::Write>(
+    doc: &LoroDoc,
+    vv: &VersionVector,
+    w: &mut W,
+) -> std::io::Result<()> {
+    let oplog = doc.oplog().lock().unwrap();
+    let mode = EncodeMode::FastUpdates.to_bytes();
+    let mut hasher = xxhash_rust::xxh32::Xxh32::new(XXH_SEED);
+    hasher.update(&mode);
+    let mut len_bytes = Vec::new();
+    oplog.for_each_block_from(vv, &mut |block| {
+        len_bytes.clear();
+        leb128::write::unsigned(&mut len_bytes, block.len() as u64)?;
+        hasher.update(&len_bytes);
+        hasher.update(block);
+        Ok(())
+    })?;
+
+    w.write_all(&MAGIC_BYTES)?;
+    w.write_all(&[0; 12])?;
+    w.write_all(&hasher.digest().to_le_bytes())?;
+    w.write_all(&mode)?;
+    oplog.for_each_block_from(vv, &mut |block| {
+        leb128::write::unsigned(&mut *w, block.len() as u64)?;
+        w.write_all(block)
+    })
+}
+
+pub(crate) fn export_fast_squashed_updates(
+    doc: &LoroDoc,
+    vv: &VersionVector,
+    merge_interval: i64,
+) -> Vec<u8> {
+    encode_with(EncodeMode::FastUpdates, &mut |ans| {
+        fast_snapshot::encode_squashed_updates(doc, vv, merge_interval, ans);
+        Ok(())
+    })
+    .unwrap()
+}
+
 pub(crate) fn export_fast_updates_in_range(oplog: &OpLog, spans: &[IdSpan]) -> Vec<u8> {
     encode_with(EncodeMode::FastUpdates, &mut |ans| {
         fast_snapshot::encode_updates_in_range(oplog, spans, ans);
@@ -524,6 +650,8 @@ pub(crate) fn decode_snapshot(
     Ok(ImportStatus {
         success: VersionRange::from_vv(&doc.oplog_vv()),
         pending: None,
+        stats: Default::default(),
+        snapshot: Some(SnapshotImportMode::Reset),
     })
 }
 
@@ -576,6 +704,42 @@ pub struct ImportBlobMetadata {
     pub end_timestamp: i64,
     pub change_num: u32,
     pub mode: EncodedBlobMode,
+    /// The root containers that the ops of the blob modify, in the order of their first ops.
+    pub root_containers: Vec<ContainerID>,
+    /// The child containers created by the ops of the blob, as `(child, parent)` pairs in the
+    /// order of the ops. For a tree, the metadata map of every node that the blob creates,
+    /// moves or deletes is included.
+    ///
+    /// A relay can check them against the subtrees that the writer is allowed to edit, without
+    /// importing the blob.
+    pub created_containers: Vec<(ContainerID, ContainerID)>,
+}
+
+/// Collect [`ImportBlobMetadata::root_containers`] and [`ImportBlobMetadata::created_containers`]
+/// from the changes visited by `visit`
+pub(crate) fn collect_blob_containers(
+    arena: &SharedArena,
+    visit: impl FnOnce(&mut dyn FnMut(&Change)),
+) -> (Vec<ContainerID>, Vec<(ContainerID, ContainerID)>) {
+    let mut roots = Vec::new();
+    let mut created = Vec::new();
+    let mut seen = FxHashSet::default();
+    visit(&mut |change| {
+        for op in change.ops.iter() {
+            let parent = arena.idx_to_id(op.container).unwrap();
+            if matches!(parent, ContainerID::Root { .. }) && seen.insert(parent.clone()) {
+                roots.push(parent.clone());
+            }
+
+            op.content.visit_created_children(arena, &mut |child| {
+                if seen.insert(child.clone()) {
+                    created.push((child.clone(), parent.clone()));
+                }
+            });
+        }
+    });
+
+    (roots, created)
 }
 
 impl LoroDoc {
@@ -194,8 +194,11 @@ impl EncodedArenas<'_> {
     }
 }
 
-#[derive(Serialize, Deserialize)]
-pub(super) struct PeerIdArena {
+/// A table of peer ids, so that the other columns can refer to the peers by their indexes.
+///
+/// It's encoded as the LEB128 length followed by the big-endian bytes of each peer.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerIdArena {
     pub(super) peer_ids: Vec<u64>,
 }
 
@@ -208,7 +211,15 @@ impl Deref for PeerIdArena {
 }
 
 impl PeerIdArena {
-    fn encode(&self) -> Vec<u8> {
+    pub fn new(peer_ids: Vec<PeerID>) -> Self {
+        Self { peer_ids }
+    }
+
+    pub fn into_vec(self) -> Vec<PeerID> {
+        self.peer_ids
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
         let mut ans = Vec::with_capacity(self.peer_ids.len() * 8);
         leb128::write::unsigned(&mut ans, self.peer_ids.len() as u64).unwrap();
         for &peer_id in &self.peer_ids {
@@ -217,7 +228,7 @@ impl PeerIdArena {
         ans
     }
 
-    fn decode(peer_id_arena: &[u8]) -> LoroResult<Self> {
+    pub fn decode(peer_id_arena: &[u8]) -> LoroResult<Self> {
         let mut reader = peer_id_arena;
         let len = leb128::read::unsigned(&mut reader)
             .map_err(|_| LoroError::DecodeDataCorruptionError)?;
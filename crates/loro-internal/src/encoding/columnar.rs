@@ -0,0 +1,118 @@
+//! The columnar encoding primitives used by Loro's binary formats.
+//!
+//! They are exposed so that the services working alongside Loro can encode their
+//! data in the same compact style:
+//!
+//! - [`ValueRegister`] deduplicates values so that the columns can refer to them by index
+//! - [`PeerIdArena`] encodes a table of peer ids
+//! - [`encode_delta_rle`] and [`decode_delta_rle`] encode a column of integers with
+//!   the delta + run-length strategy, which suits counters and timestamps
+//! - [`to_versioned_vec`] and [`from_versioned_bytes`] prefix the payload with a
+//!   format version, so that the format can evolve
+//!
+//! The `#[columnar]` derive macro and the raw encoders of [`serde_columnar`] are
+//! re-exported as well. The code generated by the macro refers to `serde_columnar`
+//! directly, so the crate using it should depend on the same version of `serde_columnar`.
+//!
+//! # Example
+//!
+//! ```
+//! use loro_internal::encoding::columnar::{
+//!     decode_delta_rle, encode_delta_rle, from_versioned_bytes, to_versioned_vec,
+//!     PeerIdArena, ValueRegister,
+//! };
+//!
+//! let mut peers = ValueRegister::new();
+//! let seen: Vec<usize> = [7u64, 3, 7, 7].iter().map(|p| peers.register(p)).collect();
+//! assert_eq!(seen, vec![0, 1, 0, 0]);
+//! let table = PeerIdArena::new(peers.unwrap_vec()).encode();
+//! assert_eq!(PeerIdArena::decode(&table).unwrap().into_vec(), vec![7, 3]);
+//!
+//! let timestamps = encode_delta_rle([100, 101, 102, 103, 104]);
+//! assert_eq!(decode_delta_rle(&timestamps).unwrap(), vec![100, 101, 102, 103, 104]);
+//!
+//! let bytes = to_versioned_vec(1, &(seen, timestamps)).unwrap();
+//! let (version, (seen, _)): (u8, (Vec<usize>, Vec<u8>)) =
+//!     from_versioned_bytes(&bytes).unwrap();
+//! assert_eq!(version, 1);
+//! assert_eq!(seen, vec![0, 1, 0, 0]);
+//! ```
+use loro_common::{LoroError, LoroResult};
+use serde::{Deserialize, Serialize};
+
+pub use super::arena::PeerIdArena;
+pub use super::value_register::ValueRegister;
+pub use serde_columnar::{
+    self, columnar, from_bytes, iter_from_bytes, to_vec, AnyRleDecoder, AnyRleEncoder,
+    BoolRleDecoder, BoolRleEncoder, ColumnarError, DeltaOfDeltaDecoder, DeltaOfDeltaEncoder,
+};
+
+#[columnar(vec, ser, de, iterable)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DeltaRleItem {
+    #[columnar(strategy = "DeltaRle")]
+    value: i64,
+}
+
+#[columnar(ser, de)]
+struct DeltaRleColumn {
+    #[columnar(class = "vec", iter = "DeltaRleItem")]
+    items: Vec<DeltaRleItem>,
+}
+
+/// Encode a column of integers with the delta + run-length strategy.
+///
+/// It's compact when the adjacent values have the same difference, e.g. counters.
+pub fn encode_delta_rle(values: impl IntoIterator<Item = i64>) -> Vec<u8> {
+    let column = DeltaRleColumn {
+        items: values
+            .into_iter()
+            .map(|value| DeltaRleItem { value })
+            .collect(),
+    };
+    serde_columnar::to_vec(&column).unwrap()
+}
+
+/// Decode the column encoded by [`encode_delta_rle`].
+pub fn decode_delta_rle(bytes: &[u8]) -> LoroResult<Vec<i64>> {
+    let column: DeltaRleColumn = serde_columnar::from_bytes(bytes)?;
+    Ok(column.items.into_iter().map(|x| x.value).collect())
+}
+
+/// Encode the value with [`serde_columnar`] after a leading version byte.
+pub fn to_versioned_vec<T: Serialize>(version: u8, value: &T) -> LoroResult<Vec<u8>> {
+    let mut ans = vec![version];
+    ans.extend(serde_columnar::to_vec(value)?);
+    Ok(ans)
+}
+
+/// Decode the bytes encoded by [`to_versioned_vec`], returning the version and the value.
+pub fn from_versioned_bytes<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> LoroResult<(u8, T)> {
+    let (&version, rest) = bytes
+        .split_first()
+        .ok_or(LoroError::DecodeDataCorruptionError)?;
+    Ok((version, serde_columnar::from_bytes(rest)?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn delta_rle_roundtrip() {
+        let values: Vec<i64> = (0..1000).map(|i| 1_700_000_000 + i * 3).collect();
+        let bytes = encode_delta_rle(values.iter().copied());
+        assert!(bytes.len() < 32);
+        assert_eq!(decode_delta_rle(&bytes).unwrap(), values);
+        assert_eq!(decode_delta_rle(&encode_delta_rle([])).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn versioned_roundtrip() {
+        let bytes = to_versioned_vec(3, &vec![1u32, 2, 3]).unwrap();
+        let (version, value): (u8, Vec<u32>) = from_versioned_bytes(&bytes).unwrap();
+        assert_eq!(version, 3);
+        assert_eq!(value, vec![1, 2, 3]);
+        assert!(from_versioned_bytes::<Vec<u32>>(&[]).is_err());
+    }
+}
@@ -8,26 +8,37 @@
 //! - state bytes
 //! - u32 in little endian for len of bytes for gc
 //! - gc bytes
+//! - (optional) u32 in little endian for len of bytes for container checksums
+//! - (optional) container checksums bytes
 //!
 //! All of `oplog bytes`, `state bytes` and `gc bytes` are encoded KV store bytes.
+//! The container checksums are only written for the non-shallow snapshots when
+//! [`crate::LoroDoc::set_snapshot_checksums`] is enabled, see [`crate::container_checksum`].
+//! The older decoders ignore them.
 //!
 //!
 //!
 use std::io::{Read, Write};
 
 use crate::{
-    change::Change, encoding::shallow_snapshot, oplog::ChangeStore, LoroDoc, OpLog, VersionVector,
+    change::Change,
+    container_checksum::{decode_container_checksums, encode_container_checksums},
+    encoding::shallow_snapshot,
+    oplog::ChangeStore,
+    LoroDoc, OpLog, VersionVector,
 };
 use bytes::{Buf, Bytes};
-use loro_common::{HasCounterSpan, IdSpan, LoroError, LoroResult};
+use loro_common::{DecodeLimit, HasCounterSpan, IdSpan, LoroError, LoroResult};
 use tracing::trace;
 
-use super::{EncodedBlobMode, ImportBlobMetadata, ParsedHeaderAndBody};
+use super::{collect_blob_containers, EncodedBlobMode, ImportBlobMetadata, ParsedHeaderAndBody};
 pub(crate) const EMPTY_MARK: &[u8] = b"E";
 pub(crate) struct Snapshot {
     pub oplog_bytes: Bytes,
     pub state_bytes: Option<Bytes>,
     pub shallow_root_state_bytes: Bytes,
+    /// Empty if the snapshot has no container checksums
+    pub container_checksums: Bytes,
 }
 
 pub(super) fn _encode_snapshot<W: Write>(s: Snapshot, w: &mut W) {
@@ -43,6 +54,11 @@ pub(super) fn _encode_snapshot<W: Write>(s: Snapshot, w: &mut W) {
     w.write_all(&(s.shallow_root_state_bytes.len() as u32).to_le_bytes())
         .unwrap();
     w.write_all(&s.shallow_root_state_bytes).unwrap();
+    if !s.container_checksums.is_empty() {
+        w.write_all(&(s.container_checksums.len() as u32).to_le_bytes())
+            .unwrap();
+        w.write_all(&s.container_checksums).unwrap();
+    }
 }
 
 pub(super) fn _decode_snapshot_bytes(bytes: Bytes) -> LoroResult<Snapshot> {
@@ -58,10 +74,20 @@ pub(super) fn _decode_snapshot_bytes(bytes: Bytes) -> LoroResult<Snapshot> {
     };
     let shallow_bytes_len = read_u32_le(&mut r) as usize;
     let shallow_root_state_bytes = r.get_mut().copy_to_bytes(shallow_bytes_len);
+    let container_checksums = if r.get_ref().remaining() >= 4 {
+        let len = read_u32_le(&mut r) as usize;
+        if r.get_ref().remaining() < len {
+            return Err(LoroError::DecodeError("Invalid container checksums".into()));
+        }
+        r.get_mut().copy_to_bytes(len)
+    } else {
+        Bytes::new()
+    };
     Ok(Snapshot {
         oplog_bytes,
         state_bytes,
         shallow_root_state_bytes,
+        container_checksums,
     })
 }
 
@@ -98,6 +124,7 @@ pub(crate) fn decode_snapshot_inner(snapshot: Snapshot, doc: &LoroDoc) -> Result
         oplog_bytes,
         state_bytes,
         shallow_root_state_bytes,
+        container_checksums,
     } = snapshot;
     ensure_cov::notify_cov("loro_internal::import::fast_snapshot::decode_snapshot");
     let mut oplog = doc.oplog().lock().map_err(|_| {
@@ -123,6 +150,11 @@ pub(crate) fn decode_snapshot_inner(snapshot: Snapshot, doc: &LoroDoc) -> Result
 
     assert!(state.frontiers.is_empty());
     assert!(oplog.frontiers().is_empty());
+    let limits = oplog.configure.decode_limits();
+    if !limits.is_unlimited() {
+        ChangeStore::check_snapshot_limits(oplog_bytes.clone(), &limits)?;
+    }
+
     oplog.decode_change_store(oplog_bytes)?;
     let need_calc = state_bytes.is_none();
     let state_frontiers;
@@ -156,12 +188,25 @@ pub(crate) fn decode_snapshot_inner(snapshot: Snapshot, doc: &LoroDoc) -> Result
         }
     }
 
+    if !need_calc {
+        oplog.check_snapshot_container_type_conflicts()?;
+    }
+
     // FIXME: we may need to extract the unknown containers here?
     // Or we should lazy load it when the time comes?
 
+    let checksums = if container_checksums.is_empty() || need_calc {
+        None
+    } else {
+        Some(decode_container_checksums(
+            &container_checksums,
+            state_frontiers.clone(),
+        )?)
+    };
     state.init_with_states_and_version(state_frontiers, &oplog, vec![], false);
     drop(state);
     drop(oplog);
+    doc.set_container_checksums(checksums);
     if need_calc {
         doc.set_detached(true);
         doc._checkout_to_latest_without_commit(false);
@@ -175,6 +220,8 @@ impl OpLog {
     pub(super) fn decode_change_store(&mut self, bytes: bytes::Bytes) -> LoroResult<()> {
         let v = self.change_store().import_all(bytes)?;
         self.dag.set_version_by_fast_snapshot_import(v);
+        self.reset_container_history_sizes();
+        self.reset_frozen();
         Ok(())
     }
 }
@@ -216,12 +263,18 @@ pub(crate) fn encode_snapshot_inner(doc: &LoroDoc) -> Snapshot {
             .unwrap();
         state = doc.app_state().lock().unwrap();
     }
-    state.ensure_all_alive_containers();
+    let alive_containers = state.ensure_all_alive_containers();
+    let container_checksums = if doc.config.snapshot_checksums() {
+        encode_container_checksums(&mut state, &doc.arena, alive_containers)
+    } else {
+        Bytes::new()
+    };
     let state_bytes = state.store.encode();
     let snapshot = Snapshot {
         oplog_bytes,
         state_bytes: Some(state_bytes),
         shallow_root_state_bytes: Bytes::new(),
+        container_checksums,
     };
     if was_detached {
         drop(state);
@@ -236,6 +289,11 @@ pub(crate) fn encode_snapshot_inner(doc: &LoroDoc) -> Snapshot {
 pub(crate) fn decode_oplog(oplog: &mut OpLog, bytes: &[u8]) -> Result<Vec<Change>, LoroError> {
     let oplog_len = u32::from_le_bytes(bytes[0..4].try_into().unwrap());
     let oplog_bytes = &bytes[4..4 + oplog_len as usize];
+    let limits = oplog.configure.decode_limits();
+    if !limits.is_unlimited() {
+        ChangeStore::check_snapshot_limits(oplog_bytes.to_vec().into(), &limits)?;
+    }
+
     let mut changes = ChangeStore::decode_snapshot_for_updates(
         oplog_bytes.to_vec().into(),
         &oplog.arena,
@@ -250,6 +308,16 @@ pub(crate) fn encode_updates<W: std::io::Write>(doc: &LoroDoc, vv: &VersionVecto
     oplog.export_blocks_from(vv, w);
 }
 
+pub(crate) fn encode_squashed_updates<W: std::io::Write>(
+    doc: &LoroDoc,
+    vv: &VersionVector,
+    merge_interval: i64,
+    w: &mut W,
+) {
+    let oplog = doc.oplog().lock().unwrap();
+    oplog.export_squashed_blocks_from(vv, merge_interval, w);
+}
+
 pub(crate) fn encode_updates_in_range<W: std::io::Write>(
     oplog: &OpLog,
     spans: &[IdSpan],
@@ -262,14 +330,32 @@ pub(crate) fn decode_updates(oplog: &mut OpLog, body: Bytes) -> Result<Vec<Chang
     let mut reader: &[u8] = body.as_ref();
     let mut index = 0;
     let self_vv = oplog.vv();
+    let limits = oplog.configure.decode_limits();
+    let mut n_changes: usize = 0;
     let mut changes = Vec::new();
     while !reader.is_empty() {
         let old_reader_len = reader.len();
-        let len = leb128::read::unsigned(&mut reader).unwrap() as usize;
+        let len = leb128::read::unsigned(&mut reader)
+            .map_err(|_| LoroError::DecodeDataCorruptionError)? as usize;
         index += old_reader_len - reader.len();
+        if reader.len() < len {
+            return Err(LoroError::DecodeDataCorruptionError);
+        }
+
         let block_bytes = body.slice(index..index + len);
         trace!("decoded block_bytes = {:?}", &block_bytes);
-        let new_changes = ChangeStore::decode_block_bytes(block_bytes, &oplog.arena, self_vv)?;
+        if !limits.is_unlimited() {
+            let n = ChangeStore::check_block_limits(&block_bytes, &limits)?;
+            n_changes = n_changes.saturating_add(n);
+            limits.check(DecodeLimit::Changes, n_changes)?;
+        }
+
+        let new_changes = ChangeStore::decode_block_bytes(
+            block_bytes,
+            &oplog.arena,
+            self_vv,
+            &oplog.configure.apply_log,
+        )?;
         changes.extend(new_changes);
         index += len;
         reader = &reader[len..];
@@ -296,6 +382,8 @@ pub(crate) fn decode_snapshot_blob_meta(
     let f = oplog.dag.shallow_since_frontiers().clone();
     let start_timestamp = oplog.get_timestamp_of_version(&f);
     let change_num = oplog.change_store().change_num() as u32;
+    let (root_containers, created_containers) =
+        collect_blob_containers(&oplog.arena, |f| oplog.change_store().visit_all_changes(f));
 
     Ok(ImportBlobMetadata {
         mode,
@@ -305,6 +393,8 @@ pub(crate) fn decode_snapshot_blob_meta(
         start_frontiers: f,
         end_timestamp: timestamp,
         change_num,
+        root_containers,
+        created_containers,
     })
 }
 
@@ -320,6 +410,8 @@ pub(crate) fn decode_updates_blob_meta(
         start_vv.insert(c.id.peer, c.id.counter);
         end_vv.insert(c.id.peer, c.ctr_end());
     }
+    let (root_containers, created_containers) =
+        collect_blob_containers(&oplog.arena, |f| changes.iter().for_each(f));
 
     Ok(ImportBlobMetadata {
         mode: EncodedBlobMode::Updates,
@@ -329,5 +421,7 @@ pub(crate) fn decode_updates_blob_meta(
         start_frontiers: Default::default(),
         end_timestamp: changes.last().map(|x| x.timestamp).unwrap_or(0),
         change_num: changes.len() as u32,
+        root_containers,
+        created_containers,
     })
 }
@@ -0,0 +1,176 @@
+use fxhash::{FxHashMap, FxHashSet};
+use loro_common::{Counter, PeerID};
+use rle::HasLength;
+
+use crate::{
+    change::{get_sys_timestamp, Change, Timestamp},
+    VersionVector,
+};
+
+/// The upper bounds of the buckets in [`OpAgeStats::buckets`], in seconds.
+///
+/// The last bucket has no upper bound.
+pub const OP_AGE_BUCKET_BOUNDS: [Timestamp; 5] = [1, 10, 60, 3600, 86400];
+
+/// The statistics of an import, reported in [`super::ImportStatus::stats`].
+///
+/// They are computed from the decoded changes, so that sync health dashboards
+/// don't need to decode the updates again.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ImportStats {
+    /// The number of changes in the imported blob
+    pub changes: usize,
+    /// The number of ops in the imported blob
+    pub ops: usize,
+    /// The peers that authored the changes in the imported blob
+    pub peers: FxHashSet<PeerID>,
+    /// The age of the imported ops, i.e. the time of the import minus the timestamps
+    /// of their changes.
+    ///
+    /// It's `None` if none of the changes has a timestamp,
+    /// see [`crate::LoroDoc::set_record_timestamp`].
+    pub op_age: Option<OpAgeStats>,
+    /// The number of missing ops on each peer that the pending changes of the doc
+    /// are waiting for after the import.
+    pub causal_gaps: FxHashMap<PeerID, Counter>,
+}
+
+/// The distribution of the op ages in an import, in seconds.
+///
+/// The ages can be negative if the clocks of the peers are ahead of the local clock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpAgeStats {
+    pub min: Timestamp,
+    pub max: Timestamp,
+    /// The sum of the ages of all the ops with timestamps
+    pub sum: Timestamp,
+    /// The number of ops with timestamps
+    pub ops: usize,
+    /// The number of ops whose ages fall into each bucket of [`OP_AGE_BUCKET_BOUNDS`].
+    ///
+    /// `buckets[i]` counts the ages less than `OP_AGE_BUCKET_BOUNDS[i]`
+    /// and not counted by the previous buckets.
+    pub buckets: [usize; OP_AGE_BUCKET_BOUNDS.len() + 1],
+}
+
+impl OpAgeStats {
+    fn new() -> Self {
+        Self {
+            min: Timestamp::MAX,
+            max: Timestamp::MIN,
+            sum: 0,
+            ops: 0,
+            buckets: Default::default(),
+        }
+    }
+
+    fn record(&mut self, age: Timestamp, ops: usize) {
+        self.min = self.min.min(age);
+        self.max = self.max.max(age);
+        self.sum += age * ops as Timestamp;
+        self.ops += ops;
+        let bucket = OP_AGE_BUCKET_BOUNDS
+            .iter()
+            .position(|&bound| age < bound)
+            .unwrap_or(OP_AGE_BUCKET_BOUNDS.len());
+        self.buckets[bucket] += ops;
+    }
+
+    fn merge(&mut self, other: &OpAgeStats) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.ops += other.ops;
+        for (a, b) in self.buckets.iter_mut().zip(other.buckets.iter()) {
+            *a += b;
+        }
+    }
+
+    /// The average age of the ops
+    pub fn mean(&self) -> f64 {
+        self.sum as f64 / self.ops as f64
+    }
+}
+
+impl ImportStats {
+    /// Collect the stats of the decoded changes.
+    ///
+    /// The changes without timestamps are skipped when computing the op ages.
+    pub(crate) fn from_changes(changes: &[Change]) -> Self {
+        // Rounded in the same way as the timestamps of the local changes
+        let now = (get_sys_timestamp() as Timestamp + 500) / 1000;
+        let mut ans = Self::default();
+        let mut op_age = OpAgeStats::new();
+        for change in changes {
+            let ops = change.atom_len();
+            ans.changes += 1;
+            ans.ops += ops;
+            ans.peers.insert(change.id.peer);
+            if change.timestamp != 0 {
+                op_age.record(now - change.timestamp, ops);
+            }
+        }
+
+        ans.op_age = (op_age.ops > 0).then_some(op_age);
+        ans
+    }
+
+    /// Merge the stats of another import into this one.
+    ///
+    /// The causal gaps are replaced by the other's, because they describe the doc after the import.
+    pub(crate) fn merge(&mut self, other: ImportStats) {
+        self.changes += other.changes;
+        self.ops += other.ops;
+        self.peers.extend(other.peers);
+        match (&mut self.op_age, other.op_age) {
+            (Some(a), Some(b)) => a.merge(&b),
+            (a @ None, b) => *a = b,
+            (Some(_), None) => {}
+        }
+        self.causal_gaps = other.causal_gaps;
+    }
+}
+
+/// Calculate the number of missing ops on each peer that the pending changes are waiting for.
+pub(crate) fn calc_causal_gaps<'a>(
+    pending: impl Iterator<Item = &'a Change>,
+    vv: &VersionVector,
+) -> FxHashMap<PeerID, Counter> {
+    let mut required: FxHashMap<PeerID, Counter> = FxHashMap::default();
+    let mut require = |peer: PeerID, end: Counter| {
+        let e = required.entry(peer).or_default();
+        *e = (*e).max(end);
+    };
+    for change in pending {
+        require(change.id.peer, change.id.counter);
+        for dep in change.deps.iter() {
+            require(dep.peer, dep.counter + 1);
+        }
+    }
+
+    required
+        .into_iter()
+        .filter_map(|(peer, end)| {
+            let gap = end - vv.get(&peer).copied().unwrap_or(0);
+            (gap > 0).then_some((peer, gap))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn op_age_buckets() {
+        let mut stats = OpAgeStats::new();
+        stats.record(0, 2);
+        stats.record(30, 1);
+        stats.record(100_000, 1);
+        assert_eq!(stats.min, 0);
+        assert_eq!(stats.max, 100_000);
+        assert_eq!(stats.ops, 4);
+        assert_eq!(stats.buckets, [2, 0, 1, 0, 0, 1]);
+        assert_eq!(stats.mean(), 25007.5);
+    }
+}
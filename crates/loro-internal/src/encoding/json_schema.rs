@@ -1,10 +1,11 @@
 use super::{
     outdated_encode_reordered::{import_changes_to_oplog, ImportChangesResult, ValueRegister},
-    ImportStatus,
+    ImportStats, ImportStatus,
 };
 use crate::{
     arena::SharedArena,
     change::{Change, ChangeRef},
+    configure::DeleteMergeConfig,
     container::{
         list::list_op::{DeleteSpan, DeleteSpanWithId, InnerListOp},
         map::MapSet,
@@ -112,7 +113,13 @@ pub(crate) fn export_json_in_id_span(oplog: &OpLog, mut id_span: IdSpan) -> Vec<
 }
 
 pub(crate) fn import_json(oplog: &mut OpLog, json: JsonSchema) -> LoroResult<ImportStatus> {
-    let changes = decode_changes(json, &oplog.arena)?;
+    let changes = decode_changes(json, &oplog.arena, oplog.configure.extended_value_types())?;
+    oplog.check_container_type_conflicts(&changes)?;
+    let mut stats = ImportStats::from_changes(&changes);
+    oplog
+        .configure
+        .apply_log
+        .record_duplicates(&changes, oplog.vv());
     let ImportChangesResult {
         latest_ids,
         pending_changes,
@@ -123,11 +130,13 @@ pub(crate) fn import_json(oplog: &mut OpLog, json: JsonSchema) -> LoroResult<Imp
     pending_changes.iter().for_each(|c| {
         pending.extends_to_include_id_span(c.id_span());
     });
+    oplog.configure.apply_log.record_pending(&pending);
     oplog.try_apply_pending(latest_ids, Some(&mut imported));
     oplog.import_unknown_lamport_pending_changes(pending_changes)?;
     if !changes_that_have_deps_before_shallow_root.is_empty() {
         return Err(LoroError::ImportUpdatesThatDependsOnOutdatedVersion);
     };
+    stats.causal_gaps = oplog.pending_causal_gaps();
     Ok(ImportStatus {
         success: imported,
         pending: if pending.is_empty() {
@@ -135,6 +144,8 @@ pub(crate) fn import_json(oplog: &mut OpLog, json: JsonSchema) -> LoroResult<Imp
         } else {
             Some(pending)
         },
+        stats,
+        snapshot: None,
     })
 }
 
@@ -236,6 +247,35 @@ fn convert_container_id(container: ContainerID, peers: &Option<Vec<PeerID>>) ->
     }
 }
 
+/// Apply `f` to the ids of the containers referenced by the [`LoroValue::ContainerRef`]s in
+/// `value`, including the nested ones. Their peers go through the peer table like the ones of
+/// [`LoroValue::Container`].
+fn map_container_refs(value: &mut LoroValue, f: &mut dyn FnMut(ContainerID) -> ContainerID) {
+    match value {
+        LoroValue::ContainerRef(id) => *id = f(id.clone()),
+        LoroValue::List(list) if list.iter().any(has_container_ref) => {
+            for v in list.make_mut().iter_mut() {
+                map_container_refs(v, f);
+            }
+        }
+        LoroValue::Map(map) if map.values().any(has_container_ref) => {
+            for v in map.make_mut().values_mut() {
+                map_container_refs(v, f);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn has_container_ref(value: &LoroValue) -> bool {
+    match value {
+        LoroValue::ContainerRef(_) => true,
+        LoroValue::List(list) => list.iter().any(has_container_ref),
+        LoroValue::Map(map) => map.values().any(has_container_ref),
+        _ => false,
+    }
+}
+
 pub(crate) fn get_peer_from_peers(peers: &Option<Vec<PeerID>>, peer: PeerID) -> PeerID {
     match peers {
         Some(peers) => peers[peer as usize],
@@ -274,7 +314,7 @@ pub(crate) fn encode_change_to_json(change: ChangeRef<'_>, arena: &SharedArena)
     }
 }
 
-fn encode_changes(
+pub(crate) fn encode_changes(
     diff_changes: &[Either<BlockChangeRef, Change>],
     arena: &SharedArena,
     mut peer_register: Option<&mut ValueRegister<PeerID>>,
@@ -325,6 +365,10 @@ fn encode_change(
                                         peer_register.as_deref_mut(),
                                     );
                                 }
+                            } else {
+                                map_container_refs(x, &mut |id| {
+                                    register_container_id(id, peer_register.as_deref_mut())
+                                });
                             }
                         });
                         json::ListOp::Insert {
@@ -357,6 +401,10 @@ fn encode_change(
                                         peer_register.as_deref_mut(),
                                     );
                                 }
+                            } else {
+                                map_container_refs(x, &mut |id| {
+                                    register_container_id(id, peer_register.as_deref_mut())
+                                });
                             }
                         });
                         json::MovableListOp::Insert {
@@ -392,7 +440,11 @@ fn encode_change(
                                 value.clone()
                             }
                         } else {
-                            value.clone()
+                            let mut value = value.clone();
+                            map_container_refs(&mut value, &mut |id| {
+                                register_container_id(id, peer_register.as_deref_mut())
+                            });
+                            value
                         };
                         json::MovableListOp::Set {
                             elem_id: register_idlp(elem_id, peer_register.as_deref_mut()),
@@ -428,13 +480,19 @@ fn encode_change(
                         key,
                         value,
                         info,
-                    } => json::TextOp::Mark {
-                        start: *start,
-                        end: *end,
-                        style_key: key.to_string(),
-                        style_value: value.clone(),
-                        info: info.to_byte(),
-                    },
+                    } => {
+                        let mut style_value = value.clone();
+                        map_container_refs(&mut style_value, &mut |id| {
+                            register_container_id(id, peer_register.as_deref_mut())
+                        });
+                        json::TextOp::Mark {
+                            start: *start,
+                            end: *end,
+                            style_key: key.to_string(),
+                            style_value,
+                            info: info.to_byte(),
+                        }
+                    }
                     InnerListOp::StyleEnd => json::TextOp::MarkEnd,
                     _ => unreachable!(),
                 }),
@@ -453,7 +511,11 @@ fn encode_change(
                                 v.clone()
                             }
                         } else {
-                            v.clone()
+                            let mut v = v.clone();
+                            map_container_refs(&mut v, &mut |id| {
+                                register_container_id(id, peer_register.as_deref_mut())
+                            });
+                            v
                         };
                         json::MapOp::Insert {
                             key: key.to_string(),
@@ -542,7 +604,11 @@ fn encode_change(
     c
 }
 
-fn decode_changes(json: JsonSchema, arena: &SharedArena) -> LoroResult<Vec<Change>> {
+fn decode_changes(
+    json: JsonSchema,
+    arena: &SharedArena,
+    extended_value_types: bool,
+) -> LoroResult<Vec<Change>> {
     let JsonSchema { peers, changes, .. } = json;
     let mut ans = Vec::with_capacity(changes.len());
     for json::JsonChange {
@@ -557,7 +623,11 @@ fn decode_changes(json: JsonSchema, arena: &SharedArena) -> LoroResult<Vec<Chang
         let id = convert_id(&id, &peers);
         let mut ops: RleVec<[Op; 1]> = RleVec::new();
         for op in json_ops {
-            ops.push(decode_op(op, arena, &peers)?);
+            // Keep the deletions apart as they were exported
+            DeleteMergeConfig::KEYSTROKE.push_op(
+                &mut ops,
+                decode_op(op, arena, &peers, extended_value_types)?,
+            );
         }
 
         let change = Change {
@@ -573,7 +643,25 @@ fn decode_changes(json: JsonSchema, arena: &SharedArena) -> LoroResult<Vec<Chang
     Ok(ans)
 }
 
-fn decode_op(op: json::JsonOp, arena: &SharedArena, peers: &Option<Vec<PeerID>>) -> LoroResult<Op> {
+/// Parse the tagged objects of the extended value types, which are rejected unless the doc
+/// opted in with [`crate::LoroDoc::set_extended_value_types`]
+fn decode_value(value: LoroValue, extended_value_types: bool) -> LoroResult<LoroValue> {
+    let value = value.parse_tags();
+    if !extended_value_types {
+        if let Some(ty) = crate::op::extended_value_type(&value) {
+            return Err(LoroError::ExtendedValueTypeDisabled(ty.into()));
+        }
+    }
+
+    Ok(value)
+}
+
+fn decode_op(
+    op: json::JsonOp,
+    arena: &SharedArena,
+    peers: &Option<Vec<PeerID>>,
+    extended_value_types: bool,
+) -> LoroResult<Op> {
     let json::JsonOp {
         counter,
         container,
@@ -613,28 +701,35 @@ fn decode_op(op: json::JsonOp, arena: &SharedArena, peers: &Option<Vec<PeerID>>)
                     style_key,
                     style_value,
                     info,
-                } => InnerContent::List(InnerListOp::StyleStart {
-                    start,
-                    end,
-                    key: style_key.into(),
-                    value: style_value,
-                    info: TextStyleInfoFlag::from_byte(info),
-                }),
+                } => {
+                    let mut value = decode_value(style_value, extended_value_types)?;
+                    map_container_refs(&mut value, &mut |id| convert_container_id(id, peers));
+                    InnerContent::List(InnerListOp::StyleStart {
+                        start,
+                        end,
+                        key: style_key.into(),
+                        value,
+                        info: TextStyleInfoFlag::from_byte(info),
+                    })
+                }
                 json::TextOp::MarkEnd => InnerContent::List(InnerListOp::StyleEnd),
             },
             _ => unreachable!(),
         },
         ContainerType::List => match content {
             JsonOpContent::List(list) => match list {
-                json::ListOp::Insert {
-                    pos,
-                    value: mut values,
-                } => {
+                json::ListOp::Insert { pos, value } => {
+                    let mut values = value
+                        .into_iter()
+                        .map(|v| decode_value(v, extended_value_types))
+                        .collect::<LoroResult<Vec<_>>>()?;
                     values.iter_mut().for_each(|v| {
                         if let LoroValue::Container(id) = v {
                             if id.is_normal() {
                                 *id = convert_container_id(id.clone(), peers);
                             }
+                        } else {
+                            map_container_refs(v, &mut |id| convert_container_id(id, peers));
                         }
                     });
                     let range = arena.alloc_values(values.iter().cloned());
@@ -657,15 +752,18 @@ fn decode_op(op: json::JsonOp, arena: &SharedArena, peers: &Option<Vec<PeerID>>)
         },
         ContainerType::MovableList => match content {
             JsonOpContent::MovableList(list) => match list {
-                json::MovableListOp::Insert {
-                    pos,
-                    value: mut values,
-                } => {
+                json::MovableListOp::Insert { pos, value } => {
+                    let mut values = value
+                        .into_iter()
+                        .map(|v| decode_value(v, extended_value_types))
+                        .collect::<LoroResult<Vec<_>>>()?;
                     values.iter_mut().for_each(|v| {
                         if let LoroValue::Container(id) = v {
                             if id.is_normal() {
                                 *id = convert_container_id(id.clone(), peers);
                             }
+                        } else {
+                            map_container_refs(v, &mut |id| convert_container_id(id, peers));
                         }
                     });
                     let range = arena.alloc_values(values.iter().cloned());
@@ -695,10 +793,13 @@ fn decode_op(op: json::JsonOp, arena: &SharedArena, peers: &Option<Vec<PeerID>>)
                         to,
                     })
                 }
-                json::MovableListOp::Set { elem_id, mut value } => {
+                json::MovableListOp::Set { elem_id, value } => {
                     let elem_id = convert_idlp(&elem_id, peers);
+                    let mut value = decode_value(value, extended_value_types)?;
                     if let LoroValue::Container(id) = &mut value {
                         *id = convert_container_id(id.clone(), peers);
+                    } else {
+                        map_container_refs(&mut value, &mut |id| convert_container_id(id, peers));
                     }
                     InnerContent::List(InnerListOp::Set { elem_id, value })
                 }
@@ -707,9 +808,12 @@ fn decode_op(op: json::JsonOp, arena: &SharedArena, peers: &Option<Vec<PeerID>>)
         },
         ContainerType::Map => match content {
             JsonOpContent::Map(map) => match map {
-                json::MapOp::Insert { key, mut value } => {
+                json::MapOp::Insert { key, value } => {
+                    let mut value = decode_value(value, extended_value_types)?;
                     if let LoroValue::Container(id) = &mut value {
                         *id = convert_container_id(id.clone(), peers);
+                    } else {
+                        map_container_refs(&mut value, &mut |id| convert_container_id(id, peers));
                     }
                     InnerContent::Map(MapSet {
                         key: key.into(),
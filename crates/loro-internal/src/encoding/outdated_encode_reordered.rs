@@ -5,8 +5,8 @@ use fxhash::{FxHashMap, FxHashSet};
 use generic_btree::rle::Sliceable;
 use itertools::Itertools;
 use loro_common::{
-    ContainerID, ContainerType, Counter, HasCounterSpan, HasId, HasIdSpan, IdLp, LoroError,
-    LoroResult, PeerID, TreeID, ID,
+    ContainerID, ContainerType, Counter, DecodeLimit, HasCounterSpan, HasId, HasIdSpan, IdLp,
+    LoroError, LoroResult, PeerID, TreeID, ID,
 };
 use rle::HasLength;
 use serde_columnar::{columnar, ColumnarError};
@@ -18,6 +18,7 @@ use crate::version::VersionRange;
 use crate::{
     arena::SharedArena,
     change::{Change, Lamport, Timestamp},
+    configure::DecodeLimits,
     container::{
         idx::ContainerIdx, list::list_op::DeleteSpanWithId, richtext::TextStyleInfoFlag,
         tree::tree_op::TreeOp,
@@ -138,6 +139,11 @@ pub(crate) fn encode_updates(oplog: &OpLog, vv: &VersionVector) -> Vec<u8> {
 
 #[instrument(skip_all)]
 pub(crate) fn decode_updates(oplog: &mut OpLog, bytes: &[u8]) -> LoroResult<Vec<Change>> {
+    let limits = oplog.configure.decode_limits();
+    if !limits.is_unlimited() {
+        check_changes_limits(bytes, &limits)?;
+    }
+
     let iter = serde_columnar::iter_from_bytes::<EncodedDoc>(bytes)?;
     let mut arenas = decode_arena(&iter.arenas)?;
     let ops_map = extract_ops(
@@ -147,6 +153,7 @@ pub(crate) fn decode_updates(oplog: &mut OpLog, bytes: &[u8]) -> LoroResult<Vec<
         &oplog.arena,
         &mut arenas,
         false,
+        &limits,
     )?
     .ops_map;
     let DecodedArenas {
@@ -168,7 +175,53 @@ pub(crate) fn decode_updates(oplog: &mut OpLog, bytes: &[u8]) -> LoroResult<Vec<
     Ok(changes)
 }
 
+/// Check the number of the changes and the ops in each change before any of them is decoded
+fn check_changes_limits(bytes: &[u8], limits: &DecodeLimits) -> LoroResult<()> {
+    let iter = serde_columnar::iter_from_bytes::<EncodedDoc>(bytes)?;
+    let mut n_changes = 0;
+    for change in iter.changes {
+        let EncodedChange { len, .. } = change?;
+        limits.check(DecodeLimit::OpsPerChange, len)?;
+        n_changes += 1;
+        limits.check(DecodeLimit::Changes, n_changes)?;
+    }
+
+    Ok(())
+}
+
 pub fn decode_import_blob_meta(parsed: ParsedHeaderAndBody) -> LoroResult<ImportBlobMetadata> {
+    let arena = SharedArena::new();
+    let changes = {
+        let iter = serde_columnar::iter_from_bytes::<EncodedDoc>(parsed.body)?;
+        let mut arenas = decode_arena(&iter.arenas)?;
+        let ops_map = extract_ops(
+            &iter.raw_values,
+            iter.ops,
+            iter.delete_starts,
+            &arena,
+            &mut arenas,
+            false,
+            &DecodeLimits::UNLIMITED,
+        )?
+        .ops_map;
+        let DecodedArenas {
+            peer_ids,
+            deps,
+            keys,
+            ..
+        } = arenas;
+        decode_changes(
+            iter.changes,
+            iter.start_counters,
+            &peer_ids,
+            &keys,
+            deps,
+            ops_map,
+        )?
+    };
+    let (root_containers, created_containers) =
+        super::collect_blob_containers(&arena, |f| changes.iter().for_each(f));
+
     let iterators = serde_columnar::iter_from_bytes::<EncodedDoc>(parsed.body)?;
     let DecodedArenas { peer_ids, .. } = decode_arena(&iterators.arenas)?;
     let start_vv: VersionVector = iterators
@@ -225,6 +278,8 @@ pub fn decode_import_blob_meta(parsed: ParsedHeaderAndBody) -> LoroResult<Import
         start_timestamp,
         end_timestamp,
         change_num,
+        root_containers,
+        created_containers,
     })
 }
 
@@ -365,8 +420,9 @@ fn extract_ops(
     shared_arena: &SharedArena,
     arenas: &mut DecodedArenas<'_>,
     should_extract_ops_with_ids: bool,
+    limits: &DecodeLimits,
 ) -> LoroResult<ExtractedOps> {
-    let mut value_reader = ValueReader::new(raw_values);
+    let mut value_reader = ValueReader::new(raw_values).with_limits(limits);
     let mut ops_map: FxHashMap<PeerID, Vec<Op>> = FxHashMap::default();
     let containers: Vec<_> = arenas
         .containers
@@ -391,6 +447,7 @@ fn extract_ops(
         let peer = arenas.peer_ids[peer_idx as usize];
         let cid = &containers[container_index as usize];
         let kind = ValueKind::from_u8(value_type);
+        let is_delete = matches!(kind, ValueKind::DeleteSeq);
         let value = Value::decode(kind, &mut value_reader, arenas, ID::new(peer, counter))?;
 
         let content = decode_op(
@@ -403,6 +460,9 @@ fn extract_ops(
             prop,
             ID::new(peer, counter),
         )?;
+        if is_delete {
+            limits.check(DecodeLimit::DeleteSpan, content.atom_len())?;
+        }
 
         let container = shared_arena.register_container(cid);
 
@@ -665,6 +725,10 @@ pub(crate) fn decode_snapshot(doc: &LoroDoc, bytes: &[u8]) -> LoroResult<()> {
     state.check_before_decode_snapshot()?;
     assert!(state.frontiers.is_empty());
     assert!(oplog.frontiers().is_empty());
+    let limits = oplog.configure.decode_limits();
+    if !limits.is_unlimited() {
+        check_changes_limits(bytes, &limits)?;
+    }
 
     let iter = serde_columnar::iter_from_bytes::<EncodedDoc>(bytes)?;
     let mut arenas = decode_arena(&iter.arenas)?;
@@ -679,6 +743,7 @@ pub(crate) fn decode_snapshot(doc: &LoroDoc, bytes: &[u8]) -> LoroResult<()> {
         &oplog.arena,
         &mut arenas,
         true,
+        &limits,
     )?;
     let DecodedArenas {
         peer_ids,
@@ -735,6 +800,8 @@ pub(crate) fn decode_snapshot(doc: &LoroDoc, bytes: &[u8]) -> LoroResult<()> {
                 Ok(ImportStatus {
                     success: Default::default(),
                     pending: None,
+                    stats: Default::default(),
+                    snapshot: None,
                 })
             },
             "".into(),
@@ -1635,6 +1702,13 @@ pub(crate) struct EncodedDeleteStartId {
     len: isize,
 }
 
+impl EncodedDeleteStartId {
+    /// The number of the deleted elements
+    pub(crate) fn span_len(&self) -> usize {
+        self.len.unsigned_abs()
+    }
+}
+
 #[columnar(vec, ser, de, iterable)]
 #[derive(Debug, Clone)]
 struct EncodedChange {
@@ -1729,6 +1803,31 @@ mod test {
                 ContainerType::Tree,
             )),
         );
+        // a reference can point to any container, including the root ones
+        test_loro_value_read_write(
+            LoroValue::ContainerRef(ContainerID::new_root("name", ContainerType::Text)),
+            None,
+        );
+        test_loro_value_read_write(
+            LoroValue::List(
+                vec![LoroValue::ContainerRef(ContainerID::new_normal(
+                    ID::new(3, 4),
+                    ContainerType::MovableList,
+                ))]
+                .into(),
+            ),
+            None,
+        );
+        test_loro_value_read_write(LoroValue::Timestamp(-1_700_000_000_000), None);
+        test_loro_value_read_write(
+            LoroValue::List(
+                vec![LoroValue::Decimal(
+                    loro_common::LoroDecimal::parse("-0012.3400").unwrap(),
+                )]
+                .into(),
+            ),
+            None,
+        );
         test_loro_value_read_write(vec![1i32, 2, 3], None);
         test_loro_value_read_write(
             LoroValue::Map(
@@ -0,0 +1,148 @@
+//! Strip the user content from the updates, e.g. for the relays that archive the updates
+//! for sync bookkeeping but must not store what the users wrote.
+use bytes::Bytes;
+use loro_common::{LoroDecimal, LoroError, LoroListValue, LoroMapValue, LoroResult, LoroValue};
+
+use super::{encode_with, parse_header_and_body, EncodeMode};
+use crate::{
+    apply_log::ApplyLog,
+    arena::SharedArena,
+    change::Change,
+    container::list::list_op::InnerListOp,
+    op::{FutureInnerContent, InnerContent, SliceRange},
+    oplog::ChangeStore,
+    VersionVector,
+};
+
+/// Replace the content of the ops in the updates with placeholders.
+///
+/// The ids, the dependencies, the lamports and the timestamps of the changes are kept, as well
+/// as the containers, the map keys, the style keys and the tree structure, so the stripped
+/// updates can be imported and synced like the original ones. The placeholders keep the
+/// lengths:
+///
+/// - Each character of the inserted text is replaced by U+FFFD, or by U+10FFFD if it's outside
+///   of the BMP, so the Unicode and UTF-16 lengths don't change
+/// - The values are replaced by the zero values of the same types, with the same lengths for
+///   strings, binaries and lists. The child containers are kept.
+/// - The counter increments become 0, and the commit messages are dropped
+///
+/// Only the updates exported by [`crate::loro::ExportMode::Updates`] and the other modes of
+/// the current encoding of updates are supported.
+pub fn strip_update_payloads(update: &[u8]) -> LoroResult<Vec<u8>> {
+    let parsed = parse_header_and_body(update, true)?;
+    if parsed.mode != EncodeMode::FastUpdates {
+        return Err(LoroError::ArgErr(
+            "Only the updates in the current encoding can be stripped".into(),
+        ));
+    }
+
+    let body = Bytes::copy_from_slice(parsed.body);
+    let arena = SharedArena::new();
+    let mut blocks = Vec::new();
+    let mut reader: &[u8] = &body;
+    let mut index = 0;
+    while !reader.is_empty() {
+        let old_reader_len = reader.len();
+        let len = leb128::read::unsigned(&mut reader)
+            .map_err(|_| LoroError::DecodeDataCorruptionError)? as usize;
+        index += old_reader_len - reader.len();
+        if len > reader.len() {
+            return Err(LoroError::DecodeDataCorruptionError);
+        }
+
+        // Each block is stripped on its own, so the blocks keep their boundaries
+        let mut changes = ChangeStore::decode_block_bytes(
+            body.slice(index..index + len),
+            &arena,
+            &VersionVector::new(),
+            &ApplyLog::default(),
+        )?;
+        for change in changes.iter_mut() {
+            strip_change(change, &arena);
+        }
+
+        blocks.push(ChangeStore::encode_block_bytes(&changes, &arena));
+        index += len;
+        reader = &reader[len..];
+    }
+
+    Ok(encode_with(EncodeMode::FastUpdates, &mut |w| {
+        for block in blocks.iter() {
+            leb128::write::unsigned(w, block.len() as u64).unwrap();
+            w.extend_from_slice(block);
+        }
+
+        Ok(())
+    })
+    .unwrap())
+}
+
+fn strip_change(change: &mut Change, arena: &SharedArena) {
+    change.commit_msg = None;
+    for op in change.ops.iter_mut() {
+        match &mut op.content {
+            InnerContent::List(list) => match list {
+                InnerListOp::Insert { slice, .. } => {
+                    let values = arena.get_values(slice.to_range());
+                    let range = arena.alloc_values(values.iter().map(placeholder));
+                    *slice = SliceRange(range.start as u32..range.end as u32);
+                }
+                InnerListOp::InsertText { slice, .. } => {
+                    let text = std::str::from_utf8(slice.as_bytes()).unwrap();
+                    let (new_slice, _) = arena.alloc_str_with_slice(&placeholder_str(text));
+                    *slice = new_slice;
+                }
+                InnerListOp::Set { value, .. } | InnerListOp::StyleStart { value, .. } => {
+                    *value = placeholder(value);
+                }
+                InnerListOp::Delete(_) | InnerListOp::Move { .. } | InnerListOp::StyleEnd => {}
+            },
+            InnerContent::Map(set) => {
+                set.value = set.value.as_ref().map(placeholder);
+            }
+            InnerContent::Tree(_) => {}
+            InnerContent::Future(future) => match future {
+                #[cfg(feature = "counter")]
+                FutureInnerContent::Counter(x) => *x = 0.,
+                FutureInnerContent::Unknown { .. } => {}
+            },
+        }
+    }
+}
+
+fn placeholder_str(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.len_utf16() == 1 {
+                '\u{FFFD}'
+            } else {
+                '\u{10FFFD}'
+            }
+        })
+        .collect()
+}
+
+fn placeholder(value: &LoroValue) -> LoroValue {
+    match value {
+        LoroValue::Null | LoroValue::Container(_) => value.clone(),
+        LoroValue::Bool(_) => LoroValue::Bool(false),
+        LoroValue::Double(_) => LoroValue::Double(0.),
+        LoroValue::I64(_) => LoroValue::I64(0),
+        LoroValue::String(s) => LoroValue::String(placeholder_str(s).into()),
+        LoroValue::Binary(b) => LoroValue::Binary(vec![0; b.len()].into()),
+        LoroValue::List(list) => LoroValue::List(LoroListValue::from(
+            list.iter().map(placeholder).collect::<Vec<_>>(),
+        )),
+        // The keys of a map value are content too, so they are replaced by the indexes
+        LoroValue::Map(map) => LoroValue::Map(LoroMapValue::from(
+            map.values()
+                .enumerate()
+                .map(|(i, v)| (i.to_string(), placeholder(v)))
+                .collect::<Vec<_>>(),
+        )),
+        LoroValue::ContainerRef(_) => value.clone(),
+        LoroValue::Timestamp(_) => LoroValue::Timestamp(0),
+        LoroValue::Decimal(_) => LoroValue::Decimal(LoroDecimal::new(0, 0)),
+    }
+}
@@ -120,6 +120,7 @@ pub(crate) fn export_shallow_snapshot_inner(
         oplog_bytes,
         state_bytes,
         shallow_root_state_bytes,
+        container_checksums: Bytes::new(),
     };
 
     if state_frontiers != latest_frontiers {
@@ -184,6 +185,7 @@ pub(crate) fn export_state_only_snapshot<W: std::io::Write>(
         oplog_bytes,
         state_bytes: None,
         shallow_root_state_bytes: shallow_state_bytes,
+        container_checksums: Bytes::new(),
     };
     _encode_snapshot(snapshot, w);
 
@@ -297,6 +299,7 @@ pub(crate) fn encode_snapshot_at<W: std::io::Write>(
                 oplog_bytes,
                 state_bytes: Some(bytes),
                 shallow_root_state_bytes: Bytes::new(),
+                container_checksums: Bytes::new(),
             },
             w,
         );
@@ -2,14 +2,14 @@ use enum_as_inner::EnumAsInner;
 use fractional_index::FractionalIndex;
 use fxhash::FxHashMap;
 use loro_common::{
-    ContainerID, ContainerType, Counter, InternalString, LoroError, LoroResult, LoroValue, PeerID,
-    TreeID, ID,
+    ContainerID, ContainerType, Counter, DecodeLimit, InternalString, LoroDecimal, LoroError,
+    LoroResult, LoroValue, PeerID, TreeID, ID,
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 use crate::{
-    change::Lamport, container::tree::tree_op::TreeOp,
+    change::Lamport, configure::DecodeLimits, container::tree::tree_op::TreeOp,
     encoding::outdated_encode_reordered::MAX_COLLECTION_SIZE,
 };
 
@@ -70,10 +70,15 @@ pub enum LoroValueKind {
     List,
     Map,
     ContainerType,
+    ContainerRef,
+    Timestamp,
+    Decimal,
 }
 impl LoroValueKind {
-    fn from_u8(kind: u8) -> Self {
-        match kind {
+    /// Returns an error rather than panicking on an unknown kind, which may be written
+    /// by a newer version of Loro
+    fn from_u8(kind: u8) -> LoroResult<Self> {
+        Ok(match kind {
             0 => Self::Null,
             1 => Self::True,
             2 => Self::False,
@@ -84,8 +89,18 @@ impl LoroValueKind {
             7 => Self::List,
             8 => Self::Map,
             9 => Self::ContainerType,
-            _ => unreachable!(),
-        }
+            10 => Self::ContainerRef,
+            11 => Self::Timestamp,
+            12 => Self::Decimal,
+            _ => {
+                return Err(LoroError::DecodeError(
+                    format!(
+                        "Unknown value kind {kind}. It may be encoded by a newer version of Loro"
+                    )
+                    .into_boxed_str(),
+                ))
+            }
+        })
     }
 
     fn to_u8(&self) -> u8 {
@@ -100,6 +115,9 @@ impl LoroValueKind {
             Self::List => 7,
             Self::Map => 8,
             Self::ContainerType => 9,
+            Self::ContainerRef => 10,
+            Self::Timestamp => 11,
+            Self::Decimal => 12,
         }
     }
 }
@@ -598,11 +616,24 @@ pub struct ValueWriter {
 
 pub struct ValueReader<'a> {
     raw: &'a [u8],
+    max_str_len: usize,
+    max_binary_len: usize,
 }
 
 impl<'a> ValueReader<'a> {
     pub fn new(raw: &'a [u8]) -> Self {
-        ValueReader { raw }
+        ValueReader {
+            raw,
+            max_str_len: usize::MAX,
+            max_binary_len: usize::MAX,
+        }
+    }
+
+    /// Fail the reads of the strings and the binaries that exceed `limits`
+    pub(crate) fn with_limits(mut self, limits: &DecodeLimits) -> Self {
+        self.max_str_len = limits.max_str_len;
+        self.max_binary_len = limits.max_binary_len;
+        self
     }
 
     pub fn read_value_type_and_content(
@@ -611,7 +642,7 @@ impl<'a> ValueReader<'a> {
         id: ID,
     ) -> LoroResult<LoroValue> {
         let kind = self.read_u8()?;
-        self.read_value_content(LoroValueKind::from_u8(kind), keys, id)
+        self.read_value_content(LoroValueKind::from_u8(kind)?, keys, id)
     }
 
     pub fn read_value_content(
@@ -632,7 +663,9 @@ impl<'a> ValueReader<'a> {
                 if len > MAX_COLLECTION_SIZE {
                     return Err(LoroError::DecodeDataCorruptionError);
                 }
-                let mut ans = Vec::with_capacity(len);
+                // Each element takes at least one byte, so a corrupted length cannot make
+                // the allocation larger than the input
+                let mut ans = Vec::with_capacity(len.min(self.raw.len()));
                 for i in 0..len {
                     ans.push(self.recursive_read_value_type_and_content(keys, id.inc(i as i32))?);
                 }
@@ -643,7 +676,10 @@ impl<'a> ValueReader<'a> {
                 if len > MAX_COLLECTION_SIZE {
                     return Err(LoroError::DecodeDataCorruptionError);
                 }
-                let mut ans = FxHashMap::with_capacity_and_hasher(len, Default::default());
+                let mut ans = FxHashMap::with_capacity_and_hasher(
+                    len.min(self.raw.len()),
+                    Default::default(),
+                );
                 for _ in 0..len {
                     let key_idx = self.read_usize()?;
                     let key = keys
@@ -665,6 +701,9 @@ impl<'a> ValueReader<'a> {
 
                 LoroValue::Container(container_id)
             }
+            LoroValueKind::ContainerRef => LoroValue::ContainerRef(self.read_container_ref()?),
+            LoroValueKind::Timestamp => LoroValue::Timestamp(self.read_i64()?),
+            LoroValueKind::Decimal => LoroValue::Decimal(self.read_decimal()?),
         })
     }
 
@@ -728,7 +767,7 @@ impl<'a> ValueReader<'a> {
                     0
                 };
                 let kind = self.read_u8()?;
-                let kind = LoroValueKind::from_u8(kind);
+                let kind = LoroValueKind::from_u8(kind)?;
                 let value = match kind {
                     LoroValueKind::Null => LoroValue::Null,
                     LoroValueKind::True => LoroValue::Bool(true),
@@ -741,7 +780,7 @@ impl<'a> ValueReader<'a> {
                         if len > MAX_COLLECTION_SIZE {
                             return Err(LoroError::DecodeDataCorruptionError);
                         }
-                        let ans = Vec::with_capacity(len);
+                        let ans = Vec::with_capacity(len.min(self.raw.len()));
                         stack.push(task);
                         stack.push(Task::ReadList {
                             left: len,
@@ -756,7 +795,10 @@ impl<'a> ValueReader<'a> {
                             return Err(LoroError::DecodeDataCorruptionError);
                         }
 
-                        let ans = FxHashMap::with_capacity_and_hasher(len, Default::default());
+                        let ans = FxHashMap::with_capacity_and_hasher(
+                            len.min(self.raw.len()),
+                            Default::default(),
+                        );
                         stack.push(task);
                         stack.push(Task::ReadMap {
                             left: len,
@@ -777,6 +819,11 @@ impl<'a> ValueReader<'a> {
 
                         LoroValue::Container(container_id)
                     }
+                    LoroValueKind::ContainerRef => {
+                        LoroValue::ContainerRef(self.read_container_ref()?)
+                    }
+                    LoroValueKind::Timestamp => LoroValue::Timestamp(self.read_i64()?),
+                    LoroValueKind::Decimal => LoroValue::Decimal(self.read_decimal()?),
                 };
 
                 task = match task {
@@ -889,18 +936,34 @@ impl<'a> ValueReader<'a> {
 
     pub fn read_str(&mut self) -> LoroResult<&'a str> {
         let len = self.read_usize()?;
+        if len > self.max_str_len {
+            return Err(LoroError::DecodeLimitExceeded {
+                limit: DecodeLimit::StringLength,
+                max: self.max_str_len,
+                actual: len,
+            });
+        }
         if self.raw.len() < len {
             return Err(LoroError::DecodeDataCorruptionError);
         }
 
-        let ans = std::str::from_utf8(&self.raw[..len]).unwrap();
+        let ans = std::str::from_utf8(&self.raw[..len])
+            .map_err(|_| LoroError::DecodeDataCorruptionError)?;
         self.raw = &self.raw[len..];
         Ok(ans)
     }
 
+    fn read_container_ref(&mut self) -> LoroResult<ContainerID> {
+        ContainerID::try_from(self.read_str()?).map_err(|_| LoroError::DecodeDataCorruptionError)
+    }
+
+    fn read_decimal(&mut self) -> LoroResult<LoroDecimal> {
+        LoroDecimal::parse(self.read_str()?).ok_or(LoroError::DecodeDataCorruptionError)
+    }
+
     #[allow(unused)]
     fn read_binary_vec(&mut self) -> LoroResult<Vec<u8>> {
-        let len = self.read_usize()?;
+        let len = self.read_binary_len()?;
         if self.raw.len() < len {
             return Err(LoroError::DecodeDataCorruptionError);
         }
@@ -919,8 +982,21 @@ impl<'a> ValueReader<'a> {
         Ok(ans)
     }
 
-    pub fn read_binary(&mut self) -> LoroResult<&'a [u8]> {
+    fn read_binary_len(&mut self) -> LoroResult<usize> {
         let len = self.read_usize()?;
+        if len > self.max_binary_len {
+            return Err(LoroError::DecodeLimitExceeded {
+                limit: DecodeLimit::BinaryLength,
+                max: self.max_binary_len,
+                actual: len,
+            });
+        }
+
+        Ok(len)
+    }
+
+    pub fn read_binary(&mut self) -> LoroResult<&'a [u8]> {
+        let len = self.read_binary_len()?;
         if self.raw.len() < len {
             return Err(LoroError::DecodeDataCorruptionError);
         }
@@ -1026,7 +1102,10 @@ impl ValueWriter {
             }
             LoroValue::Map(value) => {
                 let mut len = self.write_usize(value.len());
-                for (key, value) in value.iter() {
+                // Sorted, so that the keys are registered in the same order on every platform
+                let mut entries: Vec<_> = value.iter().collect();
+                entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+                for (key, value) in entries {
                     let key_idx = registers.key_mut().register(&key.as_str().into());
                     len += self.write_usize(key_idx);
                     let l = self.write_value_type_and_content(value, registers);
@@ -1039,6 +1118,11 @@ impl ValueWriter {
                 LoroValueKind::ContainerType,
                 self.write_u8(c.container_type().to_u8()),
             ),
+            LoroValue::ContainerRef(c) => {
+                (LoroValueKind::ContainerRef, self.write_str(&c.to_string()))
+            }
+            LoroValue::Timestamp(t) => (LoroValueKind::Timestamp, self.write_i64(*t)),
+            LoroValue::Decimal(d) => (LoroValueKind::Decimal, self.write_str(d.as_str())),
         }
     }
 
@@ -1146,5 +1230,8 @@ fn get_loro_value_kind(value: &LoroValue) -> LoroValueKind {
         LoroValue::Map(_) => LoroValueKind::Map,
         LoroValue::Binary(_) => LoroValueKind::Binary,
         LoroValue::Container(_) => LoroValueKind::ContainerType,
+        LoroValue::ContainerRef(_) => LoroValueKind::ContainerRef,
+        LoroValue::Timestamp(_) => LoroValueKind::Timestamp,
+        LoroValue::Decimal(_) => LoroValueKind::Decimal,
     }
 }
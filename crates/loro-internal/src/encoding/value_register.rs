@@ -1,5 +1,6 @@
 use fxhash::FxHashMap;
 
+/// Assigns each distinct value an index in the order they are registered.
 #[derive(Debug)]
 pub struct ValueRegister<T> {
     map_value_to_index: FxHashMap<T, usize>,
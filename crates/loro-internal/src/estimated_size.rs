@@ -22,6 +22,9 @@ impl EstimatedSize for LoroValue {
                     .sum::<usize>()
             }
             Self::Container(_) => 6,
+            Self::ContainerRef(id) => id.to_bytes().len() + 1,
+            Self::Timestamp(t) => 1 + (64 - t.leading_zeros()) as usize / 7,
+            Self::Decimal(d) => d.as_str().len() + 1,
         }
     }
 }
@@ -1,6 +1,5 @@
 use enum_as_inner::EnumAsInner;
 use fxhash::FxHashMap;
-use itertools::Itertools;
 use loro_delta::{array_vec::ArrayVec, delta_trait::DeltaAttr, DeltaItem, DeltaRope};
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
@@ -12,12 +11,13 @@ use crate::{
     handler::ValueOrHandler,
     op::SliceWithId,
     utils::string_slice::StringSlice,
+    value::unresolved_to_collection,
     InternalString,
 };
 
 use std::{borrow::Cow, hash::Hash};
 
-use loro_common::{ContainerID, LoroValue, TreeID};
+use loro_common::{ContainerID, LoroError, LoroValue, TreeID, ID};
 
 use crate::{container::idx::ContainerIdx, version::Frontiers};
 
@@ -28,6 +28,26 @@ pub struct ContainerDiff {
     pub(crate) idx: ContainerIdx,
     pub is_unknown: bool,
     pub diff: Diff,
+    /// The paragraphs of the text that are changed by the diff, in document order.
+    ///
+    /// It's only filled for text containers when [`crate::LoroDoc::set_text_paragraph_ids`]
+    /// is enabled. A paragraph merged into the previous one by deleting its newline is not
+    /// included, because it no longer exists.
+    pub paragraph_changed: Vec<ParagraphId>,
+}
+
+/// The stable id of a paragraph in a text container.
+///
+/// A paragraph is the run of text between two newlines. It's identified by the id of the
+/// newline that starts it, so it's the same on every peer and is kept when the text inside
+/// it is edited. Splitting a paragraph keeps the id for the first half, and the second half
+/// is identified by the inserted newline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ParagraphId {
+    /// The first paragraph, which has no newline before it
+    First,
+    /// The paragraph that starts after the newline with this id
+    Newline(ID),
 }
 
 /// The kind of the event trigger.
@@ -140,9 +160,170 @@ impl InternalDocDiff<'_> {
     }
 }
 
-pub type Path = SmallVec<[Index; 4]>;
+/// A path from a root container to a value in the doc, made of [`Index`] segments.
+///
+/// The first segment is the name of the root container. It can be built segment by segment,
+/// or parsed from its string form, where the segments are separated by `/`:
+///
+/// - A key is written as is, e.g. `map/key`. The `/` and `\` in a key are escaped with `\`,
+///   and a key that would be read as a number or a tree node id starts with `\`, e.g. the
+///   key `0` is written as `\0`.
+/// - A list index is written as a number, e.g. `list/0`.
+/// - A tree node is written as its id, e.g. `tree/0@1`.
+#[derive(Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Path(SmallVec<[Index; 4]>);
+
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a map key or a root container name
+    pub fn key(mut self, key: impl Into<InternalString>) -> Self {
+        self.0.push(Index::Key(key.into()));
+        self
+    }
+
+    /// Append a list index
+    pub fn seq(mut self, index: usize) -> Self {
+        self.0.push(Index::Seq(index));
+        self
+    }
+
+    /// Append a tree node
+    pub fn node(mut self, node: TreeID) -> Self {
+        self.0.push(Index::Node(node));
+        self
+    }
+
+    pub fn push(&mut self, index: Index) {
+        self.0.push(index);
+    }
+
+    pub fn pop(&mut self) -> Option<Index> {
+        self.0.pop()
+    }
+}
+
+impl std::ops::Deref for Path {
+    type Target = [Index];
 
-#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, enum_as_inner::EnumAsInner)]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Path({:?})", self.to_string())
+    }
+}
+
+impl std::fmt::Display for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, index) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("/")?;
+            }
+
+            match index {
+                Index::Key(key) => {
+                    if !Index::from_segment(key.to_string(), false).is_key() {
+                        f.write_str("\\")?;
+                    }
+
+                    for c in key.chars() {
+                        if c == '/' || c == '\\' {
+                            f.write_str("\\")?;
+                        }
+                        write!(f, "{}", c)?;
+                    }
+                }
+                index => write!(f, "{}", index)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Path {
+    type Err = LoroError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut path = Self::new();
+        let mut segment = String::new();
+        // An escaped char makes the segment a key
+        let mut escaped = false;
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    let c = chars.next().ok_or_else(|| {
+                        LoroError::ArgErr(
+                            format!("Invalid path {:?}: it ends with an escape", s)
+                                .into_boxed_str(),
+                        )
+                    })?;
+                    segment.push(c);
+                    escaped = true;
+                }
+                '/' => path.push(Index::from_segment(
+                    std::mem::take(&mut segment),
+                    std::mem::take(&mut escaped),
+                )),
+                c => segment.push(c),
+            }
+        }
+
+        path.push(Index::from_segment(segment, escaped));
+        Ok(path)
+    }
+}
+
+impl From<Vec<Index>> for Path {
+    fn from(value: Vec<Index>) -> Self {
+        Self(value.into())
+    }
+}
+
+impl From<&[Index]> for Path {
+    fn from(value: &[Index]) -> Self {
+        Self(value.into())
+    }
+}
+
+impl From<Path> for Vec<Index> {
+    fn from(value: Path) -> Self {
+        value.0.into_vec()
+    }
+}
+
+impl FromIterator<Index> for Path {
+    fn from_iter<T: IntoIterator<Item = Index>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl IntoIterator for Path {
+    type Item = Index;
+    type IntoIter = smallvec::IntoIter<[Index; 4]>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Path {
+    type Item = &'a Index;
+    type IntoIter = std::slice::Iter<'a, Index>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize, enum_as_inner::EnumAsInner)]
 pub enum Index {
     Key(InternalString),
     Seq(usize),
@@ -164,7 +345,7 @@ impl std::fmt::Display for Index {
         match self {
             Self::Key(key) => write!(f, "{}", key),
             Self::Seq(s) => write!(f, "{}", s),
-            Self::Node(id) => write!(f, "{}@{}", id.peer, id.counter),
+            Self::Node(id) => write!(f, "{}", id),
         }
     }
 }
@@ -178,21 +359,24 @@ impl From<usize> for Index {
 impl TryFrom<&str> for Index {
     type Error = &'static str;
     fn try_from(s: &str) -> Result<Self, &'static str> {
-        if s.is_empty() {
-            return Ok(Self::Key(InternalString::default()));
+        Ok(Self::from_segment(s.to_string(), false))
+    }
+}
+
+impl Index {
+    /// Read an unescaped segment of a [`Path`]. It's a key if it has escaped chars,
+    /// otherwise it's a list index or a tree node id if it's in their forms.
+    fn from_segment(s: String, escaped: bool) -> Self {
+        if escaped || !s.starts_with(|c: char| c.is_ascii_digit()) {
+            return Self::Key(s.into());
         }
 
-        let c = s.chars().next().unwrap();
-        if c.is_ascii_digit() {
-            if let Ok(seq) = s.parse::<usize>() {
-                Ok(Self::Seq(seq))
-            } else if let Ok(id) = s.try_into() {
-                Ok(Self::Node(id))
-            } else {
-                Ok(Self::Key(InternalString::from(s)))
-            }
+        if let Ok(seq) = s.parse::<usize>() {
+            Self::Seq(seq)
+        } else if let Ok(id) = s.as_str().try_into() {
+            Self::Node(id)
         } else {
-            Ok(Self::Key(InternalString::from(s)))
+            Self::Key(s.into())
         }
     }
 }
@@ -485,12 +669,148 @@ impl Diff {
     }
 }
 
+/// An operation of a JSON Patch (RFC 6902), see [`Diff::to_json_patch`].
+///
+/// It serializes to the JSON form of the operation, e.g. `{"op":"add","path":"/a","value":1}`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum JsonPatchOp {
+    Add { path: String, value: LoroValue },
+    Remove { path: String },
+    Replace { path: String, value: LoroValue },
+    Move { from: String, path: String },
+}
+
+impl Diff {
+    /// Express the diff as JSON Patch operations on the JSON value of the container, which is
+    /// at the JSON Pointer `path_prefix`, e.g. the [`json_pointer`] of the path of the event.
+    ///
+    /// The new child containers are added as empty values, because their content comes in
+    /// their own diffs. The moved elements keep their content, and a diff that moves a
+    /// single element becomes a `move`.
+    ///
+    /// Returns `None` for the text, tree and counter diffs, which JSON Patch can only express
+    /// by replacing the whole value of the container.
+    pub fn to_json_patch(&self, path_prefix: &str) -> Option<Vec<JsonPatchOp>> {
+        let at = |index: &str| format!("{}/{}", path_prefix, escape_pointer_segment(index));
+        match self {
+            Self::List(list) => {
+                let mut ops = Vec::new();
+                let mut index = 0;
+                // The positions of the removed and the moved elements
+                let mut removed = Vec::new();
+                let mut moved = Vec::new();
+                for item in list.iter() {
+                    match item {
+                        DeltaItem::Retain { len, .. } => index += len,
+                        DeltaItem::Replace {
+                            value,
+                            attr,
+                            delete,
+                        } => {
+                            let replaced = if attr.from_move {
+                                0
+                            } else {
+                                (*delete).min(value.len())
+                            };
+                            for (i, v) in value.iter().take(replaced).enumerate() {
+                                ops.push(JsonPatchOp::Replace {
+                                    path: at(&(index + i).to_string()),
+                                    value: unresolved_to_collection(v),
+                                });
+                            }
+                            for _ in replaced..*delete {
+                                removed.push(ops.len());
+                                ops.push(JsonPatchOp::Remove {
+                                    path: at(&(index + replaced).to_string()),
+                                });
+                            }
+                            for (i, v) in value.iter().enumerate().skip(replaced) {
+                                if attr.from_move {
+                                    moved.push(ops.len());
+                                }
+                                ops.push(JsonPatchOp::Add {
+                                    path: at(&(index + i).to_string()),
+                                    value: if attr.from_move {
+                                        v.to_deep_value()
+                                    } else {
+                                        unresolved_to_collection(v)
+                                    },
+                                });
+                            }
+                            index += value.len();
+                        }
+                    }
+                }
+
+                if let ([r], [m], 2) = (&removed[..], &moved[..], ops.len()) {
+                    let pos = |op: &JsonPatchOp| match op {
+                        JsonPatchOp::Add { path, .. } | JsonPatchOp::Remove { path } => {
+                            path[path_prefix.len() + 1..].parse::<usize>().unwrap()
+                        }
+                        _ => unreachable!(),
+                    };
+                    let (mut from, to) = (pos(&ops[*r]), pos(&ops[*m]));
+                    if m < r {
+                        // The element was removed after the insertion before it
+                        from -= 1;
+                    }
+                    ops = vec![JsonPatchOp::Move {
+                        from: at(&from.to_string()),
+                        path: at(&to.to_string()),
+                    }];
+                }
+
+                Some(ops)
+            }
+            Self::Map(map) => {
+                let mut updated: Vec<_> = map.updated.iter().collect();
+                updated.sort_unstable_by(|a, b| a.0.cmp(b.0));
+                let ops = updated
+                    .into_iter()
+                    .map(|(key, v)| match &v.value {
+                        Some(v) => JsonPatchOp::Add {
+                            path: at(key),
+                            value: unresolved_to_collection(v),
+                        },
+                        None => JsonPatchOp::Remove { path: at(key) },
+                    })
+                    .collect();
+                Some(ops)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The JSON Pointer (RFC 6901) of a [`Path`] in the JSON value of the doc.
+///
+/// The tree nodes are referred to by their ids.
+pub fn json_pointer(path: &[Index]) -> String {
+    let mut ans = String::new();
+    for index in path {
+        ans.push('/');
+        ans.push_str(&escape_pointer_segment(&index.to_string()));
+    }
+    ans
+}
+
+fn escape_pointer_segment(s: &str) -> Cow<'_, str> {
+    if s.contains(['~', '/']) {
+        Cow::Owned(s.replace('~', "~0").replace('/', "~1"))
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+/// Parse the string form of a [`Path`]
 pub fn str_to_path(s: &str) -> Option<Vec<Index>> {
-    s.split('/').map(|x| x.try_into()).try_collect().ok()
+    s.parse::<Path>().ok().map(Into::into)
 }
 
+/// Get the string form of a [`Path`]
 pub fn path_to_str(path: &[Index]) -> String {
-    path.iter().map(|x| x.to_string()).join("/")
+    Path::from(path).to_string()
 }
 
 #[cfg(test)]
@@ -502,6 +822,75 @@ mod test {
 
     use crate::{ApplyDiff, LoroDoc};
 
+    use super::{json_pointer, Index, JsonPatchOp, Path};
+
+    #[test]
+    fn json_patch_of_events() {
+        let doc = LoroDoc::new_auto_commit();
+        let list = doc.get_list("list");
+        list.push(1).unwrap();
+        list.push(2).unwrap();
+        list.push(3).unwrap();
+        let movable = doc.get_movable_list("movable");
+        movable.push("a").unwrap();
+        movable.push("b").unwrap();
+        movable.push("c").unwrap();
+        doc.commit_then_renew();
+
+        let patches = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let patches_clone = patches.clone();
+        let _sub = doc.subscribe_root(Arc::new(move |event| {
+            for e in event.events.iter() {
+                let prefix = json_pointer(&e.path.iter().map(|x| x.1.clone()).collect_vec());
+                patches_clone
+                    .lock()
+                    .unwrap()
+                    .push(e.diff.to_json_patch(&prefix));
+            }
+        }));
+
+        list.delete(1, 1).unwrap();
+        list.insert(0, "x").unwrap();
+        doc.commit_then_renew();
+        movable.mov(0, 2).unwrap();
+        doc.commit_then_renew();
+        doc.get_map("map").insert("a/b~", 1).unwrap();
+        doc.get_text("text").insert(0, "t").unwrap();
+        doc.commit_then_renew();
+
+        let patches = patches.lock().unwrap();
+        assert_eq!(
+            patches[0],
+            Some(vec![
+                JsonPatchOp::Add {
+                    path: "/list/0".into(),
+                    value: "x".into()
+                },
+                JsonPatchOp::Remove {
+                    path: "/list/2".into()
+                },
+            ])
+        );
+        assert_eq!(
+            patches[1],
+            Some(vec![JsonPatchOp::Move {
+                from: "/movable/0".into(),
+                path: "/movable/2".into()
+            }])
+        );
+        assert_eq!(patches.len(), 4);
+        assert!(patches[2..].contains(&Some(vec![JsonPatchOp::Add {
+            path: "/map/a~1b~0".into(),
+            value: 1.into()
+        }])));
+        // The text can only be replaced as a whole
+        assert!(patches[2..].contains(&None));
+        assert_eq!(
+            serde_json::to_string(&patches[1].as_ref().unwrap()[0]).unwrap(),
+            r#"{"op":"move","from":"/movable/0","path":"/movable/2"}"#
+        );
+    }
+
     #[test]
     fn test_text_event() {
         let loro = LoroDoc::new();
@@ -516,4 +905,31 @@ mod test {
         text.insert_with_txn(&mut txn, 1, "223").unwrap();
         txn.commit().unwrap();
     }
+
+    #[test]
+    fn path_round_trip() {
+        let node = loro_common::TreeID::new(7, 3);
+        let path = Path::new()
+            .key("root")
+            .key("a/b\\c")
+            .key("12")
+            .key("3@4")
+            .seq(5)
+            .node(node)
+            .key("");
+        let s = path.to_string();
+        assert_eq!(s, r"root/a\/b\\c/\12/\3@4/5/3@7/");
+        assert_eq!(s.parse::<Path>().unwrap(), path);
+
+        let path: Path = "map/key/0".parse().unwrap();
+        assert_eq!(
+            &path[..],
+            &[
+                Index::Key("map".into()),
+                Index::Key("key".into()),
+                Index::Seq(0)
+            ]
+        );
+        assert!(r"map/key\".parse::<Path>().is_err());
+    }
 }
@@ -30,7 +30,9 @@
 //!
 use std::borrow::Cow;
 
-use crate::{version::Frontiers, LoroDoc};
+use loro_common::LoroEncodeError;
+
+use crate::{loro::ExportMode, version::Frontiers, LoroDoc};
 
 impl LoroDoc {
     /// Creates a new LoroDoc at a specified version (Frontiers)
@@ -42,10 +44,29 @@ impl LoroDoc {
             .unwrap();
         let doc = Self::new();
         doc.set_config(&self.config);
+        doc.derive_randomness_from(&self.config);
         if self.auto_commit.load(std::sync::atomic::Ordering::Relaxed) {
             doc.start_auto_commit();
         }
         doc.import(&bytes).unwrap();
         doc
     }
+
+    /// Creates a new shallow LoroDoc at a specified version (Frontiers)
+    ///
+    /// The created doc has the state of the specified version, but it only contains the
+    /// minimal history before it, like [`ExportMode::StateOnly`]. It has a new PeerID and
+    /// the same configuration as this doc, and it can still import the updates after the
+    /// specified version from this doc.
+    pub fn fork_at_shallow(&self, frontiers: &Frontiers) -> Result<Self, LoroEncodeError> {
+        let bytes = self.export(ExportMode::StateOnly(Some(Cow::Borrowed(frontiers))))?;
+        let doc = Self::new();
+        doc.set_config(&self.config);
+        doc.derive_randomness_from(&self.config);
+        if self.auto_commit.load(std::sync::atomic::Ordering::Relaxed) {
+            doc.start_auto_commit();
+        }
+        doc.import(&bytes).unwrap();
+        Ok(doc)
+    }
 }
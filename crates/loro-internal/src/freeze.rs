@@ -0,0 +1,238 @@
+//! Freezing a doc.
+//!
+//! An archived contract or a published revision must not change anymore, but its replicas are
+//! spread across the clients and a flag on one of them doesn't stop the others.
+//! [`LoroDoc::freeze`] records the freeze in the history itself, as the reason set in the
+//! reserved root map [`FROZEN_ROOT_NAME`], so it's synced like any other op. Every replica
+//! whose history has it rejects the local edits with [`LoroError::DocFrozen`]. The imports are
+//! still accepted: the edits made concurrently with the freeze, by the replicas that hadn't
+//! seen it yet, are merged as usual.
+//!
+//! The freeze is monotone: it depends on the ops in the history rather than on the current
+//! value of the map, so a later or concurrent op that deletes or overwrites the reason
+//! doesn't unfreeze the doc. The versions of Loro without this feature don't enforce it.
+use loro_common::{ContainerID, ContainerType, IdLp, LoroError, LoroResult, LoroValue};
+
+use crate::{
+    change::Change,
+    container::{idx::ContainerIdx, map::MapSet},
+    op::InnerContent,
+    oplog::OpLog,
+    state::DocState,
+    LoroDoc,
+};
+
+/// The name of the root map that records the freeze of the doc. It's hidden from the values
+/// of the doc.
+pub const FROZEN_ROOT_NAME: &str = "__frozen";
+const REASON_KEY: &str = "reason";
+
+/// The freeze op with the greatest [`IdLp`] in the history
+#[derive(Debug, Clone)]
+pub(crate) struct Freeze {
+    id: IdLp,
+    reason: String,
+}
+
+impl LoroDoc {
+    /// Freeze the doc permanently. The pending txn is committed with the freeze.
+    ///
+    /// After it, this doc and the replicas that import the freeze reject the local edits with
+    /// [`LoroError::DocFrozen`]. It returns the same error if the doc is already frozen.
+    pub fn freeze(&self, reason: &str) -> LoroResult<()> {
+        if let Some(reason) = self.frozen_reason() {
+            return Err(LoroError::DocFrozen(reason.into()));
+        }
+
+        self.get_map(FROZEN_ROOT_NAME).insert(REASON_KEY, reason)?;
+        self.commit_then_renew();
+        Ok(())
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen_reason().is_some()
+    }
+
+    /// The reason the doc was frozen with, or `None` if it's not frozen. If several replicas
+    /// froze it concurrently, it's the reason of the freeze with the greatest lamport.
+    pub fn frozen_reason(&self) -> Option<String> {
+        let mut oplog = self.oplog.lock().unwrap();
+        let mut state = self.state.lock().unwrap();
+        frozen_reason(&mut oplog, &mut state)
+    }
+}
+
+pub(crate) fn is_frozen_root(id: &ContainerID) -> bool {
+    matches!(
+        id,
+        ContainerID::Root {
+            name,
+            container_type: ContainerType::Map
+        } if name.as_str() == FROZEN_ROOT_NAME
+    )
+}
+
+/// The reason of the freeze in the history, checked before each local op is applied.
+///
+/// The history is only visited the first time it's called after the changes are loaded
+/// in bulk, and only if the reserved map exists. The result is cached in the oplog and
+/// updated by [`OpLog::record_freeze`] when new changes are inserted.
+pub(crate) fn frozen_reason(oplog: &mut OpLog, state: &mut DocState) -> Option<String> {
+    if oplog.frozen.is_none() {
+        let freeze = find_freeze(oplog, state);
+        oplog.frozen = Some(freeze);
+    }
+
+    let freeze = oplog.frozen.as_ref().unwrap().as_ref()?;
+    Some(freeze.reason.clone())
+}
+
+fn find_freeze(oplog: &OpLog, state: &mut DocState) -> Option<Freeze> {
+    let idx = oplog
+        .arena
+        .id_to_idx(&ContainerID::new_root(FROZEN_ROOT_NAME, ContainerType::Map))?;
+    let mut ans = None;
+    oplog.change_store().visit_all_changes(&mut |c| {
+        record_freeze_in_change(&mut ans, c, idx);
+    });
+    if ans.is_none() && oplog.is_shallow() {
+        // The freeze op may be trimmed from a shallow history, but then it was made before
+        // all the remaining ops
+        if let LoroValue::Map(map) = state.get_value_by_idx(idx) {
+            if let Some(LoroValue::String(reason)) = map.get(REASON_KEY) {
+                ans = Some(Freeze {
+                    id: IdLp::new(0, 0),
+                    reason: reason.to_string(),
+                });
+            }
+        }
+    }
+
+    ans
+}
+
+fn record_freeze_in_change(ans: &mut Option<Freeze>, change: &Change, idx: ContainerIdx) {
+    for op in change.ops().iter() {
+        if op.container != idx {
+            continue;
+        }
+
+        let InnerContent::Map(MapSet {
+            key,
+            value: Some(LoroValue::String(reason)),
+        }) = &op.content
+        else {
+            continue;
+        };
+        if key.as_str() != REASON_KEY {
+            continue;
+        }
+
+        let id = IdLp::new(
+            change.id.peer,
+            change.lamport + (op.counter - change.id.counter) as u32,
+        );
+        if ans.as_ref().map_or(true, |f| f.id < id) {
+            *ans = Some(Freeze {
+                id,
+                reason: reason.to_string(),
+            });
+        }
+    }
+}
+
+impl OpLog {
+    /// Update the cached freeze with a new change
+    pub(crate) fn record_freeze(&mut self, change: &Change) {
+        let Some(frozen) = self.frozen.as_mut() else {
+            return;
+        };
+        if let Some(idx) = self
+            .arena
+            .id_to_idx(&ContainerID::new_root(FROZEN_ROOT_NAME, ContainerType::Map))
+        {
+            record_freeze_in_change(frozen, change, idx);
+        }
+    }
+
+    /// Drop the cached freeze so that it will be found again, used when the changes are
+    /// imported without going through `insert_new_change`.
+    pub(crate) fn reset_frozen(&mut self) {
+        self.frozen = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::loro::ExportMode;
+
+    #[test]
+    fn frozen_doc_rejects_local_edits() {
+        let a = LoroDoc::new_auto_commit();
+        a.set_peer_id(1).unwrap();
+        let b = LoroDoc::new_auto_commit();
+        b.set_peer_id(2).unwrap();
+        a.get_text("text").insert(0, "signed").unwrap();
+        a.commit_then_renew();
+        b.import(&a.export(ExportMode::all_updates()).unwrap())
+            .unwrap();
+
+        a.freeze("archived").unwrap();
+        assert_eq!(a.frozen_reason(), Some("archived".into()));
+        assert_eq!(
+            a.get_text("text").insert(0, "x"),
+            Err(LoroError::DocFrozen("archived".into()))
+        );
+        assert!(a.freeze("again").is_err());
+
+        // An edit concurrent with the freeze is still merged
+        b.get_text("text").insert(6, "!").unwrap();
+        b.commit_then_renew();
+        a.import(&b.export(ExportMode::all_updates()).unwrap())
+            .unwrap();
+        assert_eq!(a.get_text("text").to_string(), "signed!");
+
+        b.import(&a.export(ExportMode::all_updates()).unwrap())
+            .unwrap();
+        assert!(b.is_frozen());
+        assert!(matches!(
+            b.get_map("map").insert("k", 1),
+            Err(LoroError::DocFrozen(_))
+        ));
+        assert_eq!(b.get_text("text").to_string(), "signed!");
+    }
+
+    #[test]
+    fn freeze_is_monotone_and_hidden() {
+        let a = LoroDoc::new_auto_commit();
+        a.set_peer_id(1).unwrap();
+        let b = LoroDoc::new_auto_commit();
+        b.set_peer_id(2).unwrap();
+        a.get_text("text").insert(0, "signed").unwrap();
+        a.freeze("archived").unwrap();
+        let value = a.get_deep_value();
+        assert_eq!(
+            value.as_map().unwrap().keys().collect::<Vec<_>>(),
+            vec!["text"]
+        );
+
+        // A peer that doesn't know the freeze deletes the reason with a greater lamport
+        b.get_text("other").insert(0, "long edit history").unwrap();
+        b.get_map(FROZEN_ROOT_NAME).delete(REASON_KEY).unwrap();
+        b.commit_then_renew();
+        a.import(&b.export(ExportMode::all_updates()).unwrap())
+            .unwrap();
+        assert_eq!(a.frozen_reason(), Some("archived".into()));
+        assert!(a.get_text("text").insert(0, "x").is_err());
+
+        // It's found again from the history of a snapshot
+        let c = LoroDoc::from_snapshot(&a.export(ExportMode::Snapshot).unwrap()).unwrap();
+        assert_eq!(c.frozen_reason(), Some("archived".into()));
+        assert!(!c
+            .get_deep_value()
+            .as_map()
+            .unwrap()
+            .contains_key(FROZEN_ROOT_NAME));
+    }
+}
@@ -1,5 +1,6 @@
 use super::{state::DocState, txn::Transaction};
 use crate::{
+    apply_log::IgnoredOp,
     container::{
         idx::ContainerIdx,
         list::list_op::{DeleteSpan, DeleteSpanWithId, ListOp},
@@ -8,7 +9,7 @@ use crate::{
     cursor::{Cursor, Side},
     delta::{DeltaItem, Meta, StyleMeta, TreeExternalDiff},
     diff::{diff, diff_impl::UpdateTimeoutError, OperateProxy},
-    event::{Diff, TextDiff, TextDiffItem, TextMeta},
+    event::{Diff, ParagraphId, TextDiff, TextDiffItem, TextMeta},
     op::ListSlice,
     state::{IndexType, State, TreeParentId},
     txn::EventHint,
@@ -20,8 +21,8 @@ use enum_as_inner::EnumAsInner;
 use fxhash::FxHashMap;
 use generic_btree::rle::HasLength;
 use loro_common::{
-    ContainerID, ContainerType, IdFull, InternalString, LoroError, LoroResult, LoroValue, PeerID,
-    TreeID, ID,
+    ContainerID, ContainerType, Counter, IdFull, InternalString, LoroError, LoroResult, LoroValue,
+    PeerID, TreeID, ID,
 };
 use serde::{Deserialize, Serialize};
 use std::{
@@ -29,20 +30,43 @@ use std::{
     cmp::Reverse,
     collections::BinaryHeap,
     fmt::Debug,
-    ops::Deref,
+    ops::{Deref, Range},
     sync::{Arc, Mutex},
 };
 use tracing::{error, info, instrument};
 
-pub use crate::diff::diff_impl::UpdateOptions;
-pub use tree::TreeHandler;
+pub use crate::diff::diff_impl::{DiffMode, UpdateOptions};
+pub use tree::{FlatTreeNode, TreeHandler};
+mod list_sync;
 mod movable_list_apply_delta;
 mod tree;
 
+/// Where [`ListHandler::insert_after`] and [`ListHandler::insert_before`] insert when the
+/// anchor element has been deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnchorFallback {
+    /// Fail with [`LoroError::NotFoundError`]
+    #[default]
+    Error,
+    /// Insert where the anchor was before it got deleted
+    Nearest,
+    /// Insert at the start of the list
+    Start,
+    /// Insert at the end of the list
+    End,
+}
+
 const INSERT_CONTAINER_VALUE_ARG_ERROR: &str =
     "Cannot insert a LoroValue::Container directly. To create child container, use insert_container";
 
+mod last_modified;
+mod list_sort;
+mod ruby;
+mod text_segmentation;
 mod text_update;
+pub use last_modified::LastModified;
+pub use ruby::{RubyAnnotation, RubyMarkup, RUBY_STYLE_KEY};
+pub use text_segmentation::{TextBoundary, TextDirection};
 
 pub trait HandlerTrait: Clone + Sized {
     fn is_attached(&self) -> bool;
@@ -1362,6 +1386,32 @@ impl TextHandler {
         }
     }
 
+    /// Get the ids of the paragraphs in document order. See [`ParagraphId`].
+    ///
+    /// Returns `None` if the text is detached, because its newlines have no ids yet.
+    pub fn paragraph_ids(&self) -> Option<Vec<ParagraphId>> {
+        match &self.inner {
+            MaybeDetached::Detached(_) => None,
+            MaybeDetached::Attached(a) => Some(
+                a.with_state(|state| state.as_richtext_state_mut().unwrap().get_paragraph_ids()),
+            ),
+        }
+    }
+
+    /// Get the lines of the text split by `'\n'`, with the ids of their paragraphs.
+    ///
+    /// The ids are kept across the edits, so a line-based view can be updated with the
+    /// paragraphs reported by the events, see [`crate::LoroDoc::set_text_paragraph_ids`].
+    /// Returns `None` if the text is detached, because its newlines have no ids yet.
+    pub fn lines(&self) -> Option<Vec<(ParagraphId, String)>> {
+        match &self.inner {
+            MaybeDetached::Detached(_) => None,
+            MaybeDetached::Attached(a) => {
+                Some(a.with_state(|state| state.as_richtext_state_mut().unwrap().get_lines()))
+            }
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         match &self.inner {
             MaybeDetached::Detached(t) => t.lock().unwrap().value.is_empty(),
@@ -1508,6 +1558,43 @@ impl TextHandler {
         }
     }
 
+    /// Get the text in the Unicode code point `range`
+    pub fn slice_unicode(&self, range: Range<usize>) -> LoroResult<String> {
+        self.slice_with(range, PosType::Unicode)
+    }
+
+    /// Get the text in the UTF-16 `range`
+    pub fn slice_utf16(&self, range: Range<usize>) -> LoroResult<String> {
+        self.slice_with(range, PosType::Utf16)
+    }
+
+    /// Get the text in the UTF-8 byte `range`
+    pub fn slice_utf8(&self, range: Range<usize>) -> LoroResult<String> {
+        self.slice_with(range, PosType::Bytes)
+    }
+
+    fn slice_with(&self, range: Range<usize>, pos_type: PosType) -> LoroResult<String> {
+        match &self.inner {
+            MaybeDetached::Detached(t) => {
+                if range.end < range.start {
+                    return Err(LoroError::EndIndexLessThanStartIndex {
+                        start: range.start,
+                        end: range.end,
+                    });
+                }
+
+                let t = t.lock().unwrap();
+                t.value.get_text_slice(range.start, range.len(), pos_type)
+            }
+            MaybeDetached::Attached(a) => a.with_state(|state| {
+                state
+                    .as_richtext_state_mut()
+                    .unwrap()
+                    .get_text_slice(range, pos_type)
+            }),
+        }
+    }
+
     /// `pos` is a Event Index:
     ///
     /// - if feature="wasm", pos is a UTF-16 index
@@ -2061,12 +2148,19 @@ impl TextHandler {
         });
 
         if skip {
+            doc_state.config.apply_log.record(|| IgnoredOp::StyleNoop {
+                container: inner.id.clone(),
+                key,
+                start,
+                end,
+            });
             return Ok(());
         }
 
         let entity_start = entity_range.start;
         let entity_end = entity_range.end;
         let style_config = doc_state.config.text_style_config.try_read().unwrap();
+        style_config.validate(&key, &value)?;
         let flag = if is_delete {
             style_config
                 .get_style_flag_for_unmark(&key)
@@ -2235,7 +2329,39 @@ impl TextHandler {
         text: &str,
         options: UpdateOptions,
     ) -> Result<(), UpdateTimeoutError> {
-        let hook = text_update::DiffHookForLine::new(self, text);
+        self.update_by_segments(text, options, |s| s.split_inclusive('\n').collect())
+    }
+
+    /// Update the text to `text` with the diff computed on the units of `mode`.
+    ///
+    /// The unchanged units are kept, so the concurrent edits inside of them still merge, and
+    /// the cursors in them stay valid.
+    pub fn update_with_mode(
+        &self,
+        text: &str,
+        mode: DiffMode,
+        options: UpdateOptions,
+    ) -> Result<(), UpdateTimeoutError> {
+        use unicode_segmentation::UnicodeSegmentation;
+        match mode {
+            DiffMode::Char => self.update(text, options),
+            DiffMode::Word => {
+                self.update_by_segments(text, options, |s| s.split_word_bounds().collect())
+            }
+            DiffMode::Sentence => {
+                self.update_by_segments(text, options, |s| s.split_sentence_bounds().collect())
+            }
+            DiffMode::Line => self.update_by_line(text, options),
+        }
+    }
+
+    fn update_by_segments(
+        &self,
+        text: &str,
+        options: UpdateOptions,
+        segment: fn(&str) -> Vec<&str>,
+    ) -> Result<(), UpdateTimeoutError> {
+        let hook = text_update::DiffHookForSegments::new(self, text, segment);
         let old_lines = hook.get_old_arr().to_vec();
         let new_lines = hook.get_new_arr().to_vec();
         diff(
@@ -2267,54 +2393,14 @@ impl TextHandler {
     ) -> Option<Cursor> {
         match &self.inner {
             MaybeDetached::Detached(_) => None,
-            MaybeDetached::Attached(a) => {
-                let (id, len, origin_pos) = a.with_state(|s| {
-                    let s = s.as_richtext_state_mut().unwrap();
-                    (
-                        s.get_stable_position(index, get_by_event_index),
-                        if get_by_event_index {
-                            s.len_event()
-                        } else {
-                            s.len_unicode()
-                        },
-                        if get_by_event_index {
-                            s.event_index_to_unicode_index(index)
-                        } else {
-                            index
-                        },
-                    )
-                });
-
-                if len == 0 {
-                    return Some(Cursor {
-                        id: None,
-                        container: self.id(),
-                        side: if side == Side::Middle {
-                            Side::Left
-                        } else {
-                            side
-                        },
-                        origin_pos: 0,
-                    });
-                }
-
-                if len <= index {
-                    return Some(Cursor {
-                        id: None,
-                        container: self.id(),
-                        side: Side::Right,
-                        origin_pos: len,
-                    });
-                }
-
-                let id = id?;
-                Some(Cursor {
-                    id: Some(id),
-                    container: self.id(),
+            MaybeDetached::Attached(a) => a.with_state(|s| {
+                s.as_richtext_state_mut().unwrap().get_cursor_internal(
+                    self.id(),
+                    index,
                     side,
-                    origin_pos,
-                })
-            }
+                    get_by_event_index,
+                )
+            }),
         }
     }
 
@@ -2336,7 +2422,7 @@ impl TextHandler {
         }
     }
 
-    fn get_delta(&self) -> Vec<TextDelta> {
+    pub(crate) fn get_delta(&self) -> Vec<TextDelta> {
         self.with_state(|state| {
             let state = state.as_richtext_state_mut().unwrap();
             Ok(state.get_delta())
@@ -2437,6 +2523,151 @@ impl ListHandler {
         self.insert_with_txn(txn, pos, v)
     }
 
+    /// Insert the values at `pos` with one op.
+    ///
+    /// The adjacent numbers of the same type are stored as dense arrays in the state, so it's
+    /// the efficient way to insert numeric data like time series or coordinates. The snapshots
+    /// still encode them one value per element.
+    pub fn insert_many(&self, pos: usize, values: Vec<LoroValue>) -> LoroResult<()> {
+        match &self.inner {
+            MaybeDetached::Detached(l) => {
+                let mut list = l.lock().unwrap();
+                list.value
+                    .splice(pos..pos, values.into_iter().map(ValueOrHandler::Value));
+                Ok(())
+            }
+            MaybeDetached::Attached(a) => {
+                a.with_txn(|txn| self.insert_many_with_txn(txn, pos, values))
+            }
+        }
+    }
+
+    pub fn insert_many_with_txn(
+        &self,
+        txn: &mut Transaction,
+        pos: usize,
+        values: Vec<LoroValue>,
+    ) -> LoroResult<()> {
+        if pos > self.len() {
+            return Err(LoroError::OutOfBound {
+                pos,
+                info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+                len: self.len(),
+            });
+        }
+
+        let inner = self.inner.try_attached_state()?;
+        if values.iter().any(|v| v.is_container()) {
+            return Err(LoroError::ArgErr(
+                INSERT_CONTAINER_VALUE_ARG_ERROR
+                    .to_string()
+                    .into_boxed_str(),
+            ));
+        }
+
+        if values.is_empty() {
+            return Ok(());
+        }
+
+        let len = values.len() as u32;
+        txn.apply_local_op(
+            inner.container_idx,
+            crate::op::RawOpContent::List(crate::container::list::list_op::ListOp::Insert {
+                slice: ListSlice::RawData(Cow::Owned(values)),
+                pos,
+            }),
+            EventHint::InsertList { len, pos },
+            &inner.doc,
+        )
+    }
+
+    /// Push the values to the end of the list with one op.
+    ///
+    /// See [`ListHandler::insert_many`].
+    pub fn push_slice<T: Clone + Into<LoroValue>>(&self, values: &[T]) -> LoroResult<()> {
+        let values = values.iter().cloned().map(Into::into).collect();
+        self.insert_many(self.len(), values)
+    }
+
+    /// Replace the values starting at `pos` with `values`. The values past the end of the
+    /// list are appended.
+    ///
+    /// The list has no set op, so in the same transaction the old values are deleted with one
+    /// op per run of consecutive ids, e.g. one op for the values inserted by one op, and the
+    /// new values are inserted with one op.
+    pub fn set_range<T: Clone + Into<LoroValue>>(
+        &self,
+        pos: usize,
+        values: &[T],
+    ) -> LoroResult<()> {
+        let values: Vec<LoroValue> = values.iter().cloned().map(Into::into).collect();
+        match &self.inner {
+            MaybeDetached::Detached(l) => {
+                let mut list = l.lock().unwrap();
+                let len = list.value.len();
+                if pos > len {
+                    return Err(LoroError::OutOfBound {
+                        pos,
+                        info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+                        len,
+                    });
+                }
+
+                let end = (pos + values.len()).min(len);
+                list.value
+                    .splice(pos..end, values.into_iter().map(ValueOrHandler::Value));
+                Ok(())
+            }
+            MaybeDetached::Attached(a) => a.with_txn(|txn| {
+                let overlap = self.len().saturating_sub(pos).min(values.len());
+                self.delete_with_txn(txn, pos, overlap)?;
+                self.insert_many_with_txn(txn, pos, values)
+            }),
+        }
+    }
+
+    /// Get the values as `f64`s, or `None` if any of them isn't a `f64`.
+    ///
+    /// It copies the dense arrays in the state without creating a [`LoroValue`] per element.
+    pub fn to_f64_vec(&self) -> Option<Vec<f64>> {
+        match &self.inner {
+            MaybeDetached::Detached(l) => {
+                let list = l.lock().unwrap();
+                list.value
+                    .iter()
+                    .map(|v| match v {
+                        ValueOrHandler::Value(LoroValue::Double(x)) => Some(*x),
+                        _ => None,
+                    })
+                    .collect()
+            }
+            MaybeDetached::Attached(a) => {
+                a.with_state(|state| state.as_list_state().unwrap().to_f64_vec())
+            }
+        }
+    }
+
+    /// Get the values as `i64`s, or `None` if any of them isn't an `i64`.
+    ///
+    /// It copies the dense arrays in the state without creating a [`LoroValue`] per element.
+    pub fn to_i64_vec(&self) -> Option<Vec<i64>> {
+        match &self.inner {
+            MaybeDetached::Detached(l) => {
+                let list = l.lock().unwrap();
+                list.value
+                    .iter()
+                    .map(|v| match v {
+                        ValueOrHandler::Value(LoroValue::I64(x)) => Some(*x),
+                        _ => None,
+                    })
+                    .collect()
+            }
+            MaybeDetached::Attached(a) => {
+                a.with_state(|state| state.as_list_state().unwrap().to_i64_vec())
+            }
+        }
+    }
+
     pub fn pop(&self) -> LoroResult<Option<LoroValue>> {
         match &self.inner {
             MaybeDetached::Detached(l) => {
@@ -2532,22 +2763,33 @@ impl ListHandler {
         }
 
         let inner = self.inner.try_attached_state()?;
-        let ids: Vec<_> = inner.with_state(|state| {
+        // The elements with consecutive ids are deleted with one op
+        let spans: Vec<(ID, usize)> = inner.with_state(|state| {
             let list = state.as_list_state().unwrap();
-            (pos..pos + len)
-                .map(|i| list.get_id_at(i).unwrap())
-                .collect()
+            let mut spans: Vec<(ID, usize)> = Vec::new();
+            for i in pos..pos + len {
+                let id = list.get_id_at(i).unwrap().id();
+                match spans.last_mut() {
+                    Some((start, n))
+                        if start.peer == id.peer && start.counter + *n as Counter == id.counter =>
+                    {
+                        *n += 1
+                    }
+                    _ => spans.push((id, 1)),
+                }
+            }
+            spans
         });
 
-        for id in ids.into_iter() {
+        for (id, len) in spans.into_iter() {
             txn.apply_local_op(
                 inner.container_idx,
                 crate::op::RawOpContent::List(ListOp::Delete(DeleteSpanWithId::new(
-                    id.id(),
+                    id,
                     pos as isize,
-                    1,
+                    len as isize,
                 ))),
-                EventHint::DeleteList(DeleteSpan::new(pos as isize, 1)),
+                EventHint::DeleteList(DeleteSpan::new(pos as isize, len as isize)),
                 &inner.doc,
             )?;
         }
@@ -2576,9 +2818,9 @@ impl ListHandler {
                 }
             }
             MaybeDetached::Attached(a) => {
-                let Some(value) = a.with_state(|state| {
-                    state.as_list_state().as_ref().unwrap().get(index).cloned()
-                }) else {
+                let Some(value) =
+                    a.with_state(|state| state.as_list_state().as_ref().unwrap().get(index))
+                else {
                     return Err(LoroError::OutOfBound {
                         pos: index,
                         info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
@@ -2624,11 +2866,29 @@ impl ListHandler {
             MaybeDetached::Detached(l) => l.lock().unwrap().value.get(index).map(|x| x.to_value()),
             MaybeDetached::Attached(a) => a.with_state(|state| {
                 let a = state.as_list_state().unwrap();
-                a.get(index).cloned()
+                a.get(index)
             }),
         }
     }
 
+    /// Get the values in `range` without copying the rest of the list, e.g. to render the
+    /// visible window of a long list. The child containers are returned as their ids.
+    ///
+    /// The range is clamped to the length of the list.
+    pub fn get_range(&self, range: std::ops::Range<usize>) -> Vec<LoroValue> {
+        match &self.inner {
+            MaybeDetached::Detached(l) => {
+                let l = l.lock().unwrap();
+                let end = range.end.min(l.value.len());
+                let start = range.start.min(end);
+                l.value[start..end].iter().map(|x| x.to_value()).collect()
+            }
+            MaybeDetached::Attached(a) => {
+                a.with_state(|state| state.as_list_state().unwrap().get_range(range))
+            }
+        }
+    }
+
     /// Get value at given index, if it's a container, return a handler to the container
     pub fn get_(&self, index: usize) -> Option<ValueOrHandler> {
         match &self.inner {
@@ -2637,8 +2897,7 @@ impl ListHandler {
                 l.value.get(index).cloned()
             }
             MaybeDetached::Attached(inner) => {
-                let value =
-                    inner.with_state(|state| state.as_list_state().unwrap().get(index).cloned());
+                let value = inner.with_state(|state| state.as_list_state().unwrap().get(index));
                 match value {
                     Some(LoroValue::Container(container_id)) => Some(ValueOrHandler::Handler(
                         create_handler(inner, container_id.clone()),
@@ -2668,13 +2927,10 @@ impl ListHandler {
                     for v in a.iter() {
                         match v {
                             LoroValue::Container(c) => {
-                                temp.push(ValueOrHandler::Handler(create_handler(
-                                    inner,
-                                    c.clone(),
-                                )));
+                                temp.push(ValueOrHandler::Handler(create_handler(inner, c)));
                             }
                             value => {
-                                temp.push(ValueOrHandler::Value(value.clone()));
+                                temp.push(ValueOrHandler::Value(value));
                             }
                         }
                     }
@@ -2690,40 +2946,7 @@ impl ListHandler {
         match &self.inner {
             MaybeDetached::Detached(_) => None,
             MaybeDetached::Attached(a) => {
-                let (id, len) = a.with_state(|s| {
-                    let l = s.as_list_state().unwrap();
-                    (l.get_id_at(pos), l.len())
-                });
-
-                if len == 0 {
-                    return Some(Cursor {
-                        id: None,
-                        container: self.id(),
-                        side: if side == Side::Middle {
-                            Side::Left
-                        } else {
-                            side
-                        },
-                        origin_pos: 0,
-                    });
-                }
-
-                if len <= pos {
-                    return Some(Cursor {
-                        id: None,
-                        container: self.id(),
-                        side: Side::Right,
-                        origin_pos: len,
-                    });
-                }
-
-                let id = id?;
-                Some(Cursor {
-                    id: Some(id.id()),
-                    container: self.id(),
-                    side,
-                    origin_pos: pos,
-                })
+                a.with_state(|s| s.as_list_state().unwrap().get_cursor(self.id(), pos, side))
             }
         }
     }
@@ -2819,6 +3042,54 @@ impl ListHandler {
             }),
         }
     }
+
+    /// Insert the value right after the element with the id `elem`.
+    ///
+    /// The id is the one returned by [`ListHandler::get_id_at`], so the position doesn't
+    /// shift with the concurrent edits. If the element has been deleted, the position is
+    /// decided by `fallback`.
+    pub fn insert_after(
+        &self,
+        elem: ID,
+        v: impl Into<LoroValue>,
+        fallback: AnchorFallback,
+    ) -> LoroResult<()> {
+        let pos = self.resolve_anchor(elem, true, fallback)?;
+        self.insert(pos, v)
+    }
+
+    /// Insert the value right before the element with the id `elem`.
+    ///
+    /// If the element has been deleted, the position is decided by `fallback`.
+    pub fn insert_before(
+        &self,
+        elem: ID,
+        v: impl Into<LoroValue>,
+        fallback: AnchorFallback,
+    ) -> LoroResult<()> {
+        let pos = self.resolve_anchor(elem, false, fallback)?;
+        self.insert(pos, v)
+    }
+
+    fn resolve_anchor(&self, elem: ID, after: bool, fallback: AnchorFallback) -> LoroResult<usize> {
+        let a = self.inner.try_attached_state()?;
+        let index = a.with_state(|state| state.as_list_state().unwrap().get_index_of_id(elem));
+        if let Some(index) = index {
+            return Ok(if after { index + 1 } else { index });
+        }
+
+        let not_found = || LoroError::NotFoundError(format!("List element {}", elem).into());
+        match fallback {
+            AnchorFallback::Error => Err(not_found()),
+            AnchorFallback::Start => Ok(0),
+            AnchorFallback::End => Ok(self.len()),
+            AnchorFallback::Nearest => {
+                let cursor = Cursor::new(Some(elem), self.id(), Side::Middle, 0);
+                let ans = a.doc.query_pos(&cursor).map_err(|_| not_found())?;
+                Ok(ans.current.pos.min(self.len()))
+            }
+        }
+    }
 }
 
 impl MovableListHandler {
@@ -3988,6 +4259,11 @@ pub mod counter {
 
         fn increment_with_txn(&self, txn: &mut Transaction, n: f64) -> LoroResult<()> {
             let inner = self.inner.try_attached_state()?;
+            // Record the rounded increment, so that the op holds the exact decimal
+            let n = match inner.doc.config.counter_config() {
+                Some(config) => config.round(n),
+                None => n,
+            };
             txn.apply_local_op(
                 inner.container_idx,
                 crate::op::RawOpContent::Counter(n),
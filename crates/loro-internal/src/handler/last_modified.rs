@@ -0,0 +1,140 @@
+//! Looking up who changed an entry last, and when.
+//!
+//! Field-level "edited by Alice 2 minutes ago" badges need the op behind the current value
+//! of a map key or a list element. The state already keeps its id and lamport to resolve the
+//! concurrent edits, so the lookup doesn't scan the history: only the timestamp is read from
+//! the change that contains the op.
+use loro_common::{HasLamportSpan, IdLp, Lamport, Timestamp};
+
+use super::*;
+
+/// The op behind the current value of a map key or a list element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LastModified {
+    pub peer: PeerID,
+    pub lamport: Lamport,
+    /// The timestamp of the change that contains the op, in seconds. It's 0 if the doc doesn't
+    /// record timestamps or the change is before the shallow root.
+    pub timestamp: Timestamp,
+}
+
+impl LastModified {
+    fn new(doc: &LoroDoc, id: IdLp) -> Self {
+        let timestamp = doc
+            .oplog()
+            .lock()
+            .unwrap()
+            .get_change_with_lamport_lte(id.peer, id.lamport)
+            .filter(|c| id.lamport < c.lamport_end())
+            .map(|c| c.timestamp)
+            .unwrap_or_default();
+        Self {
+            peer: id.peer,
+            lamport: id.lamport,
+            timestamp,
+        }
+    }
+}
+
+impl MapHandler {
+    /// The op that set the current value of `key`. A deleted key returns the deletion.
+    ///
+    /// Returns `None` if the key has never been set or the map is detached.
+    pub fn last_modified(&self, key: &str) -> Option<LastModified> {
+        let MaybeDetached::Attached(a) = &self.inner else {
+            return None;
+        };
+        let id = a.with_state(|state| state.as_map_state().unwrap().get_winner(key))?;
+        Some(LastModified::new(&a.doc(), id))
+    }
+}
+
+impl ListHandler {
+    /// The op that inserted the element `elem_id`, as returned by [`ListHandler::get_id_at`].
+    ///
+    /// Returns `None` if the element is deleted or the list is detached.
+    pub fn elem_last_modified(&self, elem_id: ID) -> Option<LastModified> {
+        let MaybeDetached::Attached(a) = &self.inner else {
+            return None;
+        };
+        let id = a.with_state(|state| {
+            let list = state.as_list_state().unwrap();
+            list.get_id_at(list.get_index_of_id(elem_id)?)
+        })?;
+        Some(LastModified::new(&a.doc(), id.idlp()))
+    }
+}
+
+impl MovableListHandler {
+    /// The latest op that set or moved the element at `pos`.
+    ///
+    /// Returns `None` if `pos` is out of bounds or the list is detached.
+    pub fn last_modified_at(&self, pos: usize) -> Option<LastModified> {
+        let MaybeDetached::Attached(a) = &self.inner else {
+            return None;
+        };
+        let id = a.with_state(|state| {
+            state
+                .as_movable_list_state()
+                .unwrap()
+                .get_last_modified_at(pos)
+        })?;
+        Some(LastModified::new(&a.doc(), id))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{encoding::ExportMode, LoroDoc};
+
+    #[test]
+    fn last_modified_of_entries() {
+        let doc = LoroDoc::new_auto_commit();
+        doc.set_peer_id(1).unwrap();
+        doc.set_record_timestamp(true);
+        let map = doc.get_map("map");
+        map.insert("a", 1).unwrap();
+        map.insert("b", 1).unwrap();
+        let list = doc.get_list("list");
+        list.push(1).unwrap();
+        list.push(2).unwrap();
+        let movable = doc.get_movable_list("movable");
+        movable.push(1).unwrap();
+        movable.push(2).unwrap();
+        doc.commit_then_renew();
+
+        let other = LoroDoc::new_auto_commit();
+        other.set_peer_id(2).unwrap();
+        other
+            .import(&doc.export(ExportMode::all_updates()).unwrap())
+            .unwrap();
+        other.get_map("map").insert("b", 2).unwrap();
+        other.get_movable_list("movable").mov(0, 1).unwrap();
+        other.commit_then_renew();
+        doc.import(&other.export(ExportMode::all_updates()).unwrap())
+            .unwrap();
+
+        let a = map.last_modified("a").unwrap();
+        assert_eq!((a.peer, a.lamport), (1, 0));
+        assert!(a.timestamp > 0);
+        let b = map.last_modified("b").unwrap();
+        assert_eq!((b.peer, b.lamport, b.timestamp), (2, 6, 0));
+        assert!(map.last_modified("c").is_none());
+
+        let id = list.get_id_at(1).unwrap();
+        let second = list.elem_last_modified(id).unwrap();
+        assert_eq!((second.peer, second.lamport), (1, 3));
+        list.delete(1, 1).unwrap();
+        assert!(list.elem_last_modified(id).is_none());
+
+        // The moved element reports the move, the other one its insertion
+        let moved = movable.last_modified_at(1).unwrap();
+        assert_eq!((moved.peer, moved.lamport), (2, 7));
+        let kept = movable.last_modified_at(0).unwrap();
+        assert_eq!((kept.peer, kept.lamport), (1, 5));
+        assert!(movable.last_modified_at(2).is_none());
+        assert!(crate::MapHandler::new_detached()
+            .last_modified("a")
+            .is_none());
+    }
+}
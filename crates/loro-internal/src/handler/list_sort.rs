@@ -0,0 +1,186 @@
+//! Sorting a movable list with the fewest moves.
+//!
+//! A "sort by column" feature that moves every element creates `n` move ops, and each of
+//! them can conflict with the concurrent moves of the other peers. The elements on a longest
+//! increasing subsequence of the target order are already sorted relative to each other, so
+//! only the other elements are moved, which is the minimal number of moves.
+use std::cmp::Ordering;
+
+use super::*;
+
+impl MovableListHandler {
+    /// Sort the list with the comparator `cmp` on the shallow values, in a single commit.
+    ///
+    /// The sort is stable. Only the elements outside of a longest increasing subsequence of
+    /// the sorted order are moved. Returns the number of the moves.
+    pub fn sort_by(
+        &self,
+        mut cmp: impl FnMut(&LoroValue, &LoroValue) -> Ordering,
+    ) -> LoroResult<usize> {
+        match &self.inner {
+            MaybeDetached::Detached(d) => {
+                let mut d = d.lock().unwrap();
+                let values: Vec<_> = d.value.iter().map(|v| v.to_value()).collect();
+                let moves = sort_moves(&values, &mut cmp);
+                for &(from, to) in moves.iter() {
+                    let v = d.value.remove(from);
+                    d.value.insert(to, v);
+                }
+                Ok(moves.len())
+            }
+            MaybeDetached::Attached(a) => {
+                let LoroValue::List(values) = self.get_value() else {
+                    unreachable!()
+                };
+                let moves = sort_moves(&values, &mut cmp);
+                a.with_txn(|txn| {
+                    for &(from, to) in moves.iter() {
+                        self.move_with_txn(txn, from, to)?;
+                    }
+                    Ok(moves.len())
+                })
+            }
+        }
+    }
+}
+
+/// The `(from, to)` moves that sort `values`, applied in order like
+/// [`MovableListHandler::mov`]
+fn sort_moves(
+    values: &[LoroValue],
+    cmp: &mut impl FnMut(&LoroValue, &LoroValue) -> Ordering,
+) -> Vec<(usize, usize)> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| cmp(&values[a], &values[b]));
+    // The rank of every element in the sorted order
+    let mut rank = vec![0; values.len()];
+    for (r, &i) in order.iter().enumerate() {
+        rank[i] = r;
+    }
+
+    let mut fixed = longest_increasing_subsequence(&rank);
+    // The ranks of the elements in their current order
+    let mut current = rank.clone();
+    let mut moves = Vec::new();
+    // Every element is placed after the element of the previous rank, which is fixed by then
+    for r in 0..values.len() {
+        let i = order[r];
+        if fixed[i] {
+            continue;
+        }
+
+        let from = current.iter().position(|&x| x == r).unwrap();
+        current.remove(from);
+        let to = match r {
+            0 => 0,
+            r => current.iter().position(|&x| x == r - 1).unwrap() + 1,
+        };
+        current.insert(to, r);
+        fixed[i] = true;
+        if from != to {
+            moves.push((from, to));
+        }
+    }
+
+    moves
+}
+
+/// Mark the elements of a longest strictly increasing subsequence of `seq`
+fn longest_increasing_subsequence(seq: &[usize]) -> Vec<bool> {
+    // `tails[k]` is the index of the smallest tail of the increasing subsequences of length
+    // `k + 1`, and `prev[i]` is the index before `i` in the subsequence that ends at `i`
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev = vec![usize::MAX; seq.len()];
+    for (i, &x) in seq.iter().enumerate() {
+        let k = tails.partition_point(|&j| seq[j] < x);
+        if k > 0 {
+            prev[i] = tails[k - 1];
+        }
+        if k == tails.len() {
+            tails.push(i);
+        } else {
+            tails[k] = i;
+        }
+    }
+
+    let mut ans = vec![false; seq.len()];
+    let mut i = tails.last().copied().unwrap_or(usize::MAX);
+    while i != usize::MAX {
+        ans[i] = true;
+        i = prev[i];
+    }
+
+    ans
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::value::ToJson;
+
+    fn asc(a: &LoroValue, b: &LoroValue) -> Ordering {
+        a.as_i64().cmp(&b.as_i64())
+    }
+
+    #[test]
+    fn sort_with_minimal_moves() {
+        let doc = LoroDoc::new_auto_commit();
+        let list = doc.get_movable_list("list");
+        for v in [1, 2, 3, 4, 5, 0] {
+            list.push(v.into()).unwrap();
+        }
+        doc.commit_then_renew();
+
+        let counter = doc.oplog_vv().get(&doc.peer_id()).copied().unwrap_or(0);
+        // Moving the 0 to the front is enough
+        assert_eq!(list.sort_by(asc).unwrap(), 1);
+        doc.commit_then_renew();
+        assert_eq!(
+            doc.oplog_vv().get(&doc.peer_id()).copied().unwrap_or(0),
+            counter + 1
+        );
+        assert_eq!(list.get_value(), vec![0, 1, 2, 3, 4, 5].into());
+
+        // Reversing keeps one element
+        assert_eq!(list.sort_by(|a, b| asc(b, a)).unwrap(), 5);
+        assert_eq!(list.get_value(), vec![5, 4, 3, 2, 1, 0].into());
+        assert_eq!(list.sort_by(|a, b| asc(b, a)).unwrap(), 0);
+
+        let detached = MovableListHandler::new_detached();
+        for v in [(1, "b"), (2, "c"), (0, "a"), (1, "d")] {
+            detached
+                .push(vec![LoroValue::from(v.0), v.1.into()].into())
+                .unwrap();
+        }
+        // The sort is stable
+        let by_first =
+            |a: &LoroValue, b: &LoroValue| asc(&a.as_list().unwrap()[0], &b.as_list().unwrap()[0]);
+        assert_eq!(detached.sort_by(by_first).unwrap(), 2);
+        assert_eq!(
+            detached.get_value().to_json_value(),
+            serde_json::json!([[0, "a"], [1, "b"], [1, "d"], [2, "c"]])
+        );
+    }
+
+    #[test]
+    fn sort_moves_sort_every_permutation() {
+        use itertools::Itertools;
+
+        for n in 0..6 {
+            for perm in (0..n as i64).permutations(n) {
+                let values: Vec<LoroValue> = perm.iter().map(|&x| x.into()).collect();
+                let moves = sort_moves(&values, &mut asc);
+                let mut list = perm.clone();
+                for (from, to) in moves.iter().copied() {
+                    let v = list.remove(from);
+                    list.insert(to, v);
+                }
+                assert_eq!(list, (0..n as i64).collect::<Vec<_>>());
+                let lis = longest_increasing_subsequence(
+                    &perm.iter().map(|&x| x as usize).collect::<Vec<_>>(),
+                );
+                assert_eq!(moves.len(), n - lis.iter().filter(|x| **x).count());
+            }
+        }
+    }
+}
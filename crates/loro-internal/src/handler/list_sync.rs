@@ -0,0 +1,348 @@
+//! Reconcile a list with a target vector by keys.
+//!
+//! The elements of the list and the target are matched by the keys returned by `key_fn`.
+//! The matched elements are kept, the others are deleted or inserted. A [MovableListHandler]
+//! also moves the matched elements instead of recreating them, and it moves as few of them
+//! as possible, i.e. the ones out of the longest increasing run of the matched elements.
+//! The matched elements with changed values are replaced, or set for a [MovableListHandler].
+use std::{collections::VecDeque, hash::Hash};
+
+use super::*;
+use crate::diff::{diff, DiffHandler, OperateProxy};
+
+#[derive(Debug, Clone, PartialEq)]
+enum ListSyncOp {
+    Insert(usize, LoroValue),
+    Delete(usize, usize),
+    Move(usize, usize),
+    Set(usize, LoroValue),
+}
+
+impl ListHandler {
+    /// Update the list to `target` with a minimal diff of the elements keyed by `key_fn`.
+    ///
+    /// The ops are applied in one transaction. The container values in `target` can only
+    /// match the existing child containers, because new containers can't be inserted by value.
+    pub fn sync_to<K: Hash + Eq>(
+        &self,
+        target: &[LoroValue],
+        key_fn: impl FnMut(&LoroValue) -> K,
+    ) -> LoroResult<()> {
+        let current = self.get_value().into_list().unwrap();
+        let ops = plan_list_sync(&current, target, key_fn);
+        match &self.inner {
+            MaybeDetached::Detached(_) => ops.into_iter().try_for_each(|op| match op {
+                ListSyncOp::Insert(pos, v) => self.insert(pos, v),
+                ListSyncOp::Delete(pos, len) => self.delete(pos, len),
+                ListSyncOp::Move(..) | ListSyncOp::Set(..) => unreachable!(),
+            }),
+            MaybeDetached::Attached(a) => a.with_txn(|txn| {
+                ops.into_iter().try_for_each(|op| match op {
+                    ListSyncOp::Insert(pos, v) => self.insert_with_txn(txn, pos, v),
+                    ListSyncOp::Delete(pos, len) => self.delete_with_txn(txn, pos, len),
+                    ListSyncOp::Move(..) | ListSyncOp::Set(..) => unreachable!(),
+                })
+            }),
+        }
+    }
+}
+
+impl MovableListHandler {
+    /// Update the list to `target` with a minimal diff of the elements keyed by `key_fn`.
+    ///
+    /// The matched elements are moved and set instead of being recreated. The ops are applied
+    /// in one transaction.
+    pub fn sync_to<K: Hash + Eq>(
+        &self,
+        target: &[LoroValue],
+        key_fn: impl FnMut(&LoroValue) -> K,
+    ) -> LoroResult<()> {
+        let current = self.get_value().into_list().unwrap();
+        let ops = plan_movable_list_sync(&current, target, key_fn);
+        match &self.inner {
+            MaybeDetached::Detached(_) => ops.into_iter().try_for_each(|op| match op {
+                ListSyncOp::Insert(pos, v) => self.insert(pos, v),
+                ListSyncOp::Delete(pos, len) => self.delete(pos, len),
+                ListSyncOp::Move(from, to) => self.mov(from, to),
+                ListSyncOp::Set(pos, v) => self.set(pos, v),
+            }),
+            MaybeDetached::Attached(a) => a.with_txn(|txn| {
+                ops.into_iter().try_for_each(|op| match op {
+                    ListSyncOp::Insert(pos, v) => self.insert_with_txn(txn, pos, v),
+                    ListSyncOp::Delete(pos, len) => self.delete_with_txn(txn, pos, len),
+                    ListSyncOp::Move(from, to) => self.move_with_txn(txn, from, to),
+                    ListSyncOp::Set(pos, v) => self.set_with_txn(txn, pos, v),
+                })
+            }),
+        }
+    }
+}
+
+/// Map the keys to small integers, so the keys of both sides can be diffed as `u32`s
+fn intern_keys<K: Hash + Eq>(
+    current: &[LoroValue],
+    target: &[LoroValue],
+    mut key_fn: impl FnMut(&LoroValue) -> K,
+) -> (Vec<u32>, Vec<u32>) {
+    let mut ids: FxHashMap<K, u32> = FxHashMap::default();
+    let mut intern = |v: &LoroValue| {
+        let next = ids.len() as u32;
+        *ids.entry(key_fn(v)).or_insert(next)
+    };
+    let old = current.iter().map(&mut intern).collect();
+    let new = target.iter().map(&mut intern).collect();
+    (old, new)
+}
+
+/// Collect the ops of the myers diff, and the pairs of the matched `(old, new)` indexes
+struct SyncDiffHook<'a> {
+    target: &'a [LoroValue],
+    ops: Vec<ListSyncOp>,
+    matched: Vec<(usize, usize)>,
+    last_old_index: usize,
+    last_new_index: usize,
+    current_index: usize,
+}
+
+impl SyncDiffHook<'_> {
+    fn match_until(&mut self, old_index: usize) {
+        for i in 0..old_index - self.last_old_index {
+            self.matched
+                .push((self.last_old_index + i, self.last_new_index + i));
+        }
+
+        self.current_index += old_index - self.last_old_index;
+        self.last_new_index += old_index - self.last_old_index;
+        self.last_old_index = old_index;
+    }
+}
+
+impl DiffHandler for SyncDiffHook<'_> {
+    fn insert(&mut self, old_index: usize, new_index: usize, new_len: usize) {
+        if old_index > self.last_old_index {
+            self.match_until(old_index);
+        }
+
+        for (i, v) in self.target[new_index..new_index + new_len]
+            .iter()
+            .enumerate()
+        {
+            self.ops
+                .push(ListSyncOp::Insert(self.current_index + i, v.clone()));
+        }
+
+        self.current_index += new_len;
+        self.last_new_index = new_index + new_len;
+    }
+
+    fn delete(&mut self, old_index: usize, old_len: usize) {
+        self.match_until(old_index);
+        self.ops
+            .push(ListSyncOp::Delete(self.current_index, old_len));
+        self.last_old_index = old_index + old_len;
+    }
+}
+
+fn plan_list_sync<K: Hash + Eq>(
+    current: &[LoroValue],
+    target: &[LoroValue],
+    key_fn: impl FnMut(&LoroValue) -> K,
+) -> Vec<ListSyncOp> {
+    let (old, new) = intern_keys(current, target, key_fn);
+    let mut proxy = OperateProxy::new(SyncDiffHook {
+        target,
+        ops: Vec::new(),
+        matched: Vec::new(),
+        last_old_index: 0,
+        last_new_index: 0,
+        current_index: 0,
+    });
+    // It can only fail by the timeout, which isn't set
+    diff(&mut proxy, Default::default(), &old, &new).unwrap();
+    let mut hook = proxy.unwrap();
+    hook.match_until(current.len());
+    let SyncDiffHook {
+        mut ops, matched, ..
+    } = hook;
+    for (old_index, new_index) in matched {
+        if current[old_index] != target[new_index] {
+            ops.push(ListSyncOp::Delete(new_index, 1));
+            ops.push(ListSyncOp::Insert(new_index, target[new_index].clone()));
+        }
+    }
+
+    ops
+}
+
+fn plan_movable_list_sync<K: Hash + Eq>(
+    current: &[LoroValue],
+    target: &[LoroValue],
+    key_fn: impl FnMut(&LoroValue) -> K,
+) -> Vec<ListSyncOp> {
+    let (old, new) = intern_keys(current, target, key_fn);
+    let mut unmatched: FxHashMap<u32, VecDeque<usize>> = FxHashMap::default();
+    for (i, key) in old.iter().enumerate() {
+        unmatched.entry(*key).or_default().push_back(i);
+    }
+
+    // The old index matched by each target element, in the order of the keys
+    let matched: Vec<Option<usize>> = new
+        .iter()
+        .map(|key| unmatched.get_mut(key).and_then(|q| q.pop_front()))
+        .collect();
+    let mut new_index_of_old = vec![None; old.len()];
+    for (new_index, old_index) in matched.iter().enumerate() {
+        if let Some(old_index) = old_index {
+            new_index_of_old[*old_index] = Some(new_index);
+        }
+    }
+
+    let mut ops = Vec::new();
+    // Delete backward so that the indexes of the earlier elements stay the same
+    let mut end = old.len();
+    while end > 0 {
+        if new_index_of_old[end - 1].is_some() {
+            end -= 1;
+            continue;
+        }
+
+        let mut start = end - 1;
+        while start > 0 && new_index_of_old[start - 1].is_none() {
+            start -= 1;
+        }
+
+        ops.push(ListSyncOp::Delete(start, end - start));
+        end = start;
+    }
+
+    // The target indexes of the elements in the list, in the current order
+    let mut order: Vec<usize> = new_index_of_old.into_iter().flatten().collect();
+    let mut stay = vec![false; new.len()];
+    for i in longest_increasing_subsequence(&order) {
+        stay[order[i]] = true;
+    }
+
+    // Place every element right after the element before it in the target. The elements in
+    // the longest increasing run are already in order, so only the others are moved.
+    for new_index in 0..new.len() {
+        let pos = if new_index == 0 {
+            0
+        } else {
+            order.iter().position(|x| *x == new_index - 1).unwrap() + 1
+        };
+        if matched[new_index].is_none() {
+            ops.push(ListSyncOp::Insert(pos, target[new_index].clone()));
+            order.insert(pos, new_index);
+        } else if !stay[new_index] {
+            let from = order.iter().position(|x| *x == new_index).unwrap();
+            let to = if from < pos { pos - 1 } else { pos };
+            if from != to {
+                ops.push(ListSyncOp::Move(from, to));
+                order.remove(from);
+                order.insert(to, new_index);
+            }
+        }
+    }
+
+    for (new_index, old_index) in matched.into_iter().enumerate() {
+        if let Some(old_index) = old_index {
+            if current[old_index] != target[new_index] {
+                ops.push(ListSyncOp::Set(new_index, target[new_index].clone()));
+            }
+        }
+    }
+
+    ops
+}
+
+/// The indexes of a longest strictly increasing subsequence
+fn longest_increasing_subsequence(seq: &[usize]) -> Vec<usize> {
+    // `tails[k]` is the index of the smallest tail of the increasing subsequences of length k + 1
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev: Vec<Option<usize>> = vec![None; seq.len()];
+    for (i, x) in seq.iter().enumerate() {
+        let k = tails.partition_point(|&t| seq[t] < *x);
+        prev[i] = k.checked_sub(1).map(|k| tails[k]);
+        if k == tails.len() {
+            tails.push(i);
+        } else {
+            tails[k] = i;
+        }
+    }
+
+    let mut ans = Vec::with_capacity(tails.len());
+    let mut cur = tails.last().copied();
+    while let Some(i) = cur {
+        ans.push(i);
+        cur = prev[i];
+    }
+
+    ans.reverse();
+    ans
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn apply(list: &mut Vec<LoroValue>, ops: &[ListSyncOp]) {
+        for op in ops {
+            match op {
+                ListSyncOp::Insert(pos, v) => list.insert(*pos, v.clone()),
+                ListSyncOp::Delete(pos, len) => {
+                    list.drain(*pos..*pos + *len);
+                }
+                ListSyncOp::Move(from, to) => {
+                    let v = list.remove(*from);
+                    list.insert(*to, v);
+                }
+                ListSyncOp::Set(pos, v) => list[*pos] = v.clone(),
+            }
+        }
+    }
+
+    fn values(s: &[(&str, i64)]) -> Vec<LoroValue> {
+        s.iter()
+            .map(|(k, v)| LoroValue::from(vec![LoroValue::from(*k), LoroValue::from(*v)]))
+            .collect()
+    }
+
+    fn key(v: &LoroValue) -> String {
+        v.as_list().unwrap()[0].as_string().unwrap().to_string()
+    }
+
+    #[test]
+    fn plan_minimal_sync_ops() {
+        let current = values(&[("a", 1), ("b", 2), ("c", 3), ("d", 4)]);
+        let target = values(&[("b", 2), ("c", 30), ("d", 4), ("a", 1), ("e", 5)]);
+
+        let ops = plan_movable_list_sync(&current, &target, key);
+        assert_eq!(
+            ops,
+            vec![
+                ListSyncOp::Move(0, 3),
+                ListSyncOp::Insert(4, target[4].clone()),
+                ListSyncOp::Set(1, target[1].clone()),
+            ]
+        );
+        let mut list = current.clone();
+        apply(&mut list, &ops);
+        assert_eq!(list, target);
+
+        let ops = plan_list_sync(&current, &target, key);
+        assert_eq!(ops.len(), 5);
+        let mut list = current.clone();
+        apply(&mut list, &ops);
+        assert_eq!(list, target);
+
+        let target = values(&[("d", 4), ("x", 0), ("b", 2), ("b", 3)]);
+        for ops in [
+            plan_list_sync(&current, &target, key),
+            plan_movable_list_sync(&current, &target, key),
+        ] {
+            let mut list = current.clone();
+            apply(&mut list, &ops);
+            assert_eq!(list, target);
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Ruby annotations on rich text.
+//!
+//! Ruby (furigana, pronunciation hints) pairs a range of the base text with a short annotation
+//! text rendered above it. Flat styles like `bold` only say whether a char has a style, so a
+//! ruby is stored as the style [`RUBY_STYLE_KEY`] whose value is the annotation text: the
+//! chars of the same ruby share the value, and a run of them is the base text of the pair.
+//! The pairs can be read back with [`TextHandler::rubies`] and rendered as `<ruby>` elements
+//! or the `{base|annotation}` Markdown extension with [`TextHandler::to_ruby_markup`].
+use super::*;
+
+/// The style key of the ruby annotations. It's configured by
+/// [`StyleConfigMap::default_rich_text_config`](crate::configure::StyleConfigMap::default_rich_text_config).
+pub const RUBY_STYLE_KEY: &str = "ruby";
+
+/// A base text range with its ruby annotation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RubyAnnotation {
+    /// The event index range of the base text
+    pub range: Range<usize>,
+    pub base: String,
+    /// The annotation text
+    pub text: String,
+}
+
+/// The markup rendered by [`TextHandler::to_ruby_markup`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RubyMarkup {
+    /// `<ruby>base<rt>annotation</rt></ruby>`, with the text escaped
+    Html,
+    /// `{base|annotation}`, the ruby extension of several Markdown dialects
+    Markdown,
+}
+
+impl TextHandler {
+    /// Attach the annotation `text` to the base text in the event index `range`.
+    ///
+    /// It replaces the annotations that overlap the range inside of it. The adjacent ranges
+    /// with the same annotation text are read as one pair.
+    pub fn set_ruby(&self, range: Range<usize>, text: &str) -> LoroResult<()> {
+        if text.is_empty() {
+            return Err(LoroError::ArgErr(
+                "The ruby text cannot be empty, use remove_ruby instead".into(),
+            ));
+        }
+
+        self.mark(range.start, range.end, RUBY_STYLE_KEY, text.into())
+    }
+
+    /// Remove the ruby annotations in the event index `range`
+    pub fn remove_ruby(&self, range: Range<usize>) -> LoroResult<()> {
+        self.unmark(range.start, range.end, RUBY_STYLE_KEY)
+    }
+
+    /// Get the annotated ranges of the text in document order
+    pub fn rubies(&self) -> Vec<RubyAnnotation> {
+        let mut ans = Vec::new();
+        let mut pos = 0;
+        for (base, ruby) in self.ruby_runs() {
+            let len = event_len(&base);
+            if let Some(text) = ruby {
+                ans.push(RubyAnnotation {
+                    range: pos..pos + len,
+                    base,
+                    text,
+                });
+            }
+            pos += len;
+        }
+
+        ans
+    }
+
+    /// Render the text with its ruby annotations as `markup`. The other styles are dropped.
+    pub fn to_ruby_markup(&self, markup: RubyMarkup) -> String {
+        let mut ans = String::new();
+        for (base, ruby) in self.ruby_runs() {
+            match (ruby, markup) {
+                (None, RubyMarkup::Html) => push_escaped_html(&mut ans, &base),
+                (None, RubyMarkup::Markdown) => ans.push_str(&base),
+                (Some(text), RubyMarkup::Html) => {
+                    ans.push_str("<ruby>");
+                    push_escaped_html(&mut ans, &base);
+                    ans.push_str("<rt>");
+                    push_escaped_html(&mut ans, &text);
+                    ans.push_str("</rt></ruby>");
+                }
+                (Some(text), RubyMarkup::Markdown) => {
+                    ans.push('{');
+                    ans.push_str(&base);
+                    ans.push('|');
+                    ans.push_str(&text);
+                    ans.push('}');
+                }
+            }
+        }
+
+        ans
+    }
+
+    /// Split the text into the runs of the same ruby annotation, or of no annotation
+    fn ruby_runs(&self) -> Vec<(String, Option<String>)> {
+        let LoroValue::List(spans) = self.get_richtext_value() else {
+            unreachable!()
+        };
+        let mut runs: Vec<(String, Option<String>)> = Vec::new();
+        for span in spans.iter() {
+            let span = span.as_map().unwrap();
+            let insert = span.get("insert").unwrap().as_string().unwrap();
+            let ruby = span
+                .get("attributes")
+                .and_then(|attrs| attrs.as_map().unwrap().get(RUBY_STYLE_KEY))
+                .and_then(|v| v.as_string())
+                .map(|s| s.to_string());
+            match runs.last_mut() {
+                Some((base, last)) if *last == ruby => base.push_str(insert),
+                _ => runs.push((insert.to_string(), ruby)),
+            }
+        }
+
+        runs
+    }
+}
+
+fn push_escaped_html(ans: &mut String, s: &str) {
+    for c in s.chars() {
+        match c {
+            '&' => ans.push_str("&amp;"),
+            '<' => ans.push_str("&lt;"),
+            '>' => ans.push_str("&gt;"),
+            '"' => ans.push_str("&quot;"),
+            c => ans.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ruby_annotations() {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        text.insert(0, "日本語を読む").unwrap();
+        text.set_ruby(0..2, "にほん").unwrap();
+        text.set_ruby(2..3, "ご").unwrap();
+        text.mark(1, 5, "bold", true.into()).unwrap();
+        assert_eq!(
+            text.rubies(),
+            vec![
+                RubyAnnotation {
+                    range: 0..2,
+                    base: "日本".into(),
+                    text: "にほん".into(),
+                },
+                RubyAnnotation {
+                    range: 2..3,
+                    base: "語".into(),
+                    text: "ご".into(),
+                },
+            ]
+        );
+        assert_eq!(
+            text.to_ruby_markup(RubyMarkup::Html),
+            "<ruby>日本<rt>にほん</rt></ruby><ruby>語<rt>ご</rt></ruby>を読む"
+        );
+        assert_eq!(
+            text.to_ruby_markup(RubyMarkup::Markdown),
+            "{日本|にほん}{語|ご}を読む"
+        );
+
+        // The annotations don't expand to the inserted text
+        text.insert(2, "<>").unwrap();
+        assert_eq!(
+            text.to_ruby_markup(RubyMarkup::Html),
+            "<ruby>日本<rt>にほん</rt></ruby>&lt;&gt;<ruby>語<rt>ご</rt></ruby>を読む"
+        );
+
+        text.remove_ruby(0..4).unwrap();
+        assert_eq!(text.rubies().len(), 1);
+        assert_eq!(text.rubies()[0].range, 4..5);
+        assert!(text.set_ruby(0..1, "").is_err());
+        assert!(matches!(
+            text.mark(0, 1, RUBY_STYLE_KEY, 1.into()),
+            Err(LoroError::InvalidStyleValue { .. })
+        ));
+
+        let new_doc = LoroDoc::new_auto_commit();
+        new_doc
+            .import(&doc.export(crate::loro::ExportMode::Snapshot).unwrap())
+            .unwrap();
+        assert_eq!(new_doc.get_text("text").rubies(), text.rubies());
+    }
+}
@@ -0,0 +1,231 @@
+//! Editing text by the Unicode text boundaries.
+//!
+//! Editors delete a word with ctrl+backspace and select a word with a double click. The
+//! boundaries of the words and the graphemes are defined by UAX #29. Segmenting the text here
+//! makes every frontend agree on them, instead of each one segmenting the exported string with
+//! its own rules.
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::*;
+
+/// The direction of an edit relative to the cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextDirection {
+    Backward,
+    Forward,
+}
+
+/// The kind of the Unicode text boundary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextBoundary {
+    /// Extended grapheme clusters, i.e. the user-perceived characters
+    Grapheme,
+    Word,
+    Sentence,
+}
+
+impl TextHandler {
+    /// Delete the word before or after the event index `pos`, like ctrl+backspace and ctrl+delete.
+    ///
+    /// The whitespace next to `pos` is deleted together with the word. If `pos` is inside a
+    /// word, only the part of the word before or after `pos` is deleted. Returns the deleted
+    /// range in event indexes.
+    pub fn delete_word(&self, pos: usize, direction: TextDirection) -> LoroResult<Range<usize>> {
+        let s = self.to_string();
+        let byte_pos = event_index_to_byte(&s, pos)?;
+        let range = word_deletion_range(&s, byte_pos, direction);
+        let ans = byte_to_event_index(&s, range.start)..byte_to_event_index(&s, range.end);
+        if !range.is_empty() {
+            self.delete_utf8(range.start, range.len())?;
+        }
+
+        Ok(ans)
+    }
+
+    /// Expand the event index `range` to the nearest boundaries of `kind` around it.
+    ///
+    /// An empty range is expanded to the segment containing it, e.g. the word under the cursor
+    /// of a double click. At the end of the text, it's expanded to the last segment.
+    pub fn expand_to_boundary(
+        &self,
+        range: Range<usize>,
+        kind: TextBoundary,
+    ) -> LoroResult<Range<usize>> {
+        if range.end < range.start {
+            return Err(LoroError::EndIndexLessThanStartIndex {
+                start: range.start,
+                end: range.end,
+            });
+        }
+
+        let s = self.to_string();
+        let start = event_index_to_byte(&s, range.start)?;
+        let end = event_index_to_byte(&s, range.end)?;
+        if s.is_empty() {
+            return Ok(0..0);
+        }
+
+        let bounds = boundaries(&s, kind);
+        let (start, end) = if start == end {
+            // The segment containing `start`, or the last one at the end of the text
+            let i = bounds
+                .partition_point(|&b| b <= start)
+                .min(bounds.len() - 1);
+            (bounds[i - 1], bounds[i])
+        } else {
+            let i = bounds.partition_point(|&b| b <= start);
+            let j = bounds.partition_point(|&b| b < end);
+            (bounds[i - 1], bounds[j])
+        };
+
+        Ok(byte_to_event_index(&s, start)..byte_to_event_index(&s, end))
+    }
+}
+
+/// The byte offsets of the boundaries of `kind` in `s`, including 0 and `s.len()`
+fn boundaries(s: &str, kind: TextBoundary) -> Vec<usize> {
+    let mut ans: Vec<usize> = match kind {
+        TextBoundary::Grapheme => s.grapheme_indices(true).map(|(i, _)| i).collect(),
+        TextBoundary::Word => s.split_word_bound_indices().map(|(i, _)| i).collect(),
+        TextBoundary::Sentence => s.split_sentence_bound_indices().map(|(i, _)| i).collect(),
+    };
+    if ans.first() != Some(&0) {
+        ans.insert(0, 0);
+    }
+    if ans.last() != Some(&s.len()) {
+        ans.push(s.len());
+    }
+
+    ans
+}
+
+/// The byte range deleted by [`TextHandler::delete_word`] at the byte offset `pos`
+fn word_deletion_range(s: &str, pos: usize, direction: TextDirection) -> Range<usize> {
+    fn is_space(seg: &str) -> bool {
+        seg.chars().all(char::is_whitespace)
+    }
+
+    match direction {
+        TextDirection::Backward => {
+            let mut start = pos;
+            for (i, seg) in s[..pos].split_word_bound_indices().rev() {
+                start = i;
+                if !is_space(seg) {
+                    break;
+                }
+            }
+
+            start..pos
+        }
+        TextDirection::Forward => {
+            let mut end = pos;
+            for (i, seg) in s[pos..].split_word_bound_indices() {
+                end = pos + i + seg.len();
+                if !is_space(seg) {
+                    break;
+                }
+            }
+
+            pos..end
+        }
+    }
+}
+
+fn event_index_to_byte(s: &str, pos: usize) -> LoroResult<usize> {
+    let mut index = 0;
+    for (i, c) in s.char_indices() {
+        if index >= pos {
+            return if index == pos {
+                Ok(i)
+            } else {
+                Err(LoroError::UTF16InUnicodeCodePoint { pos })
+            };
+        }
+
+        index += if cfg!(feature = "wasm") {
+            c.len_utf16()
+        } else {
+            1
+        };
+    }
+
+    if index == pos {
+        Ok(s.len())
+    } else {
+        Err(LoroError::OutOfBound {
+            pos,
+            len: index,
+            info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+        })
+    }
+}
+
+fn byte_to_event_index(s: &str, pos: usize) -> usize {
+    if cfg!(feature = "wasm") {
+        s[..pos].encode_utf16().count()
+    } else {
+        s[..pos].chars().count()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::LoroDoc;
+
+    #[test]
+    fn delete_and_select_words() {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        text.insert(0, "Hello, wonderful  world").unwrap();
+        assert_eq!(
+            text.delete_word(23, TextDirection::Backward).unwrap(),
+            18..23
+        );
+        assert_eq!(text.to_string(), "Hello, wonderful  ");
+        assert_eq!(
+            text.delete_word(18, TextDirection::Backward).unwrap(),
+            7..18
+        );
+        assert_eq!(text.to_string(), "Hello, ");
+        assert_eq!(text.delete_word(5, TextDirection::Forward).unwrap(), 5..6);
+        assert_eq!(text.delete_word(5, TextDirection::Forward).unwrap(), 5..6);
+        assert_eq!(text.to_string(), "Hello");
+        assert_eq!(text.delete_word(0, TextDirection::Backward).unwrap(), 0..0);
+
+        text.insert(5, " big world").unwrap();
+        assert_eq!(
+            text.expand_to_boundary(7..7, TextBoundary::Word).unwrap(),
+            6..9
+        );
+        assert_eq!(
+            text.expand_to_boundary(2..8, TextBoundary::Word).unwrap(),
+            0..9
+        );
+        assert_eq!(
+            text.expand_to_boundary(15..15, TextBoundary::Word).unwrap(),
+            10..15
+        );
+        assert!(text.expand_to_boundary(0..16, TextBoundary::Word).is_err());
+    }
+
+    #[test]
+    fn graphemes() {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        // "e" with a combining acute accent
+        text.insert(0, "ae\u{301}b").unwrap();
+        assert_eq!(
+            text.expand_to_boundary(2..2, TextBoundary::Grapheme)
+                .unwrap(),
+            1..3
+        );
+        assert_eq!(
+            text.expand_to_boundary(1..2, TextBoundary::Grapheme)
+                .unwrap(),
+            1..3
+        );
+    }
+}
@@ -56,7 +56,8 @@ impl DiffHandler for DiffHook<'_> {
     }
 }
 
-pub(super) struct DiffHookForLine<'a> {
+/// Diffs the text by segments, e.g. lines or words
+pub(super) struct DiffHookForSegments<'a> {
     text: &'a TextHandler,
     old: Vec<u32>,
     new: Vec<u32>,
@@ -67,8 +68,12 @@ pub(super) struct DiffHookForLine<'a> {
     current_index: usize,
 }
 
-impl<'a> DiffHookForLine<'a> {
-    pub(crate) fn new(text: &'a TextHandler, new_str: &str) -> Self {
+impl<'a> DiffHookForSegments<'a> {
+    pub(crate) fn new(
+        text: &'a TextHandler,
+        new_str: &str,
+        segment: fn(&str) -> Vec<&str>,
+    ) -> Self {
         let mut this = Self {
             text,
             old: Vec::new(),
@@ -80,13 +85,13 @@ impl<'a> DiffHookForLine<'a> {
         };
 
         let text_str = text.to_string();
-        for line in text_str.split_inclusive('\n') {
+        for line in segment(&text_str) {
             let line: Arc<str> = Arc::from(line);
             let id = this.register_line(line);
             this.old.push(id as u32);
         }
 
-        for line in new_str.split_inclusive('\n') {
+        for line in segment(new_str) {
             let line: Arc<str> = Arc::from(line);
             let id = this.register_line(line);
             this.new.push(id as u32);
@@ -115,7 +120,7 @@ impl<'a> DiffHookForLine<'a> {
     }
 }
 
-impl DiffHandler for DiffHookForLine<'_> {
+impl DiffHandler for DiffHookForSegments<'_> {
     fn insert(&mut self, old_index: usize, new_index: usize, new_len: usize) {
         trace!("insert line {old_index} {new_index} {new_len}");
         if self.last_old_index < old_index {
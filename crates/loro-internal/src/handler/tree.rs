@@ -1,7 +1,7 @@
 use std::{collections::VecDeque, sync::Arc};
 
 use fractional_index::FractionalIndex;
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 use loro_common::{
     ContainerID, ContainerType, Counter, IdLp, LoroError, LoroResult, LoroTreeError, LoroValue,
     PeerID, TreeID, ID,
@@ -21,6 +21,19 @@ use crate::{
 
 use super::{create_handler, Handler, MaybeDetached};
 
+/// A node in the flat description of a tree. See [`TreeHandler::import_flat`].
+#[derive(Debug, Clone)]
+pub struct FlatTreeNode {
+    /// The external id of the node, e.g. a file path or a primary key
+    pub id: String,
+    /// The external id of the parent node, `None` if it's a root node
+    pub parent: Option<String>,
+    /// The order of the node among its siblings
+    pub index: usize,
+    /// The expected entries of the meta map of the node
+    pub meta: FxHashMap<String, LoroValue>,
+}
+
 #[derive(Clone)]
 pub struct TreeHandler {
     pub(super) inner: MaybeDetached<TreeInner>,
@@ -515,7 +528,7 @@ impl TreeHandler {
         let inner = self.inner.try_attached_state()?;
         let target = TreeID::from_id(txn.next_id());
 
-        match self.generate_position_at(&target, &parent, index, cfg) {
+        match self.generate_position_at(&target, &parent, index, cfg, target.id()) {
             FractionalIndexGenResult::Ok(position) => {
                 self.create_with_position(inner, txn, target, parent, index, position)
             }
@@ -634,7 +647,7 @@ impl TreeHandler {
             self.delete_position(&parent, &target);
         }
 
-        match self.generate_position_at(&target, &parent, index, cfg) {
+        match self.generate_position_at(&target, &parent, index, cfg, txn.next_id()) {
             FractionalIndexGenResult::Ok(position) => {
                 self.mov_with_position(inner, txn, target, parent, index, position, old_index)
             }
@@ -712,6 +725,166 @@ impl TreeHandler {
         )
     }
 
+    /// Make the tree match the given flat parent-pointer description in one transaction.
+    ///
+    /// The external id of each node is stored in its meta map under `id_key`, which is used to
+    /// match the existing nodes with the description on later imports. Only the differences are
+    /// turned into ops:
+    ///
+    /// - the nodes that are not in the tree are created,
+    /// - the nodes whose parent or index are changed are moved,
+    /// - the meta entries are updated to match the description,
+    /// - the nodes that are not in the description are deleted.
+    ///
+    /// Siblings are ordered by `index`. It returns the [TreeID] of each node by its external id.
+    pub fn import_flat(
+        &self,
+        nodes: &[FlatTreeNode],
+        id_key: &str,
+    ) -> LoroResult<FxHashMap<String, TreeID>> {
+        let MaybeDetached::Attached(a) = &self.inner else {
+            return Err(LoroError::MisuseDetachedContainer {
+                method: "import_flat",
+            });
+        };
+
+        let mut ids = FxHashSet::default();
+        let mut children: FxHashMap<Option<&str>, Vec<&FlatTreeNode>> = FxHashMap::default();
+        for node in nodes {
+            if !ids.insert(node.id.as_str()) {
+                return Err(LoroError::ArgErr(
+                    format!("Duplicated node id: {}", node.id).into_boxed_str(),
+                ));
+            }
+            children
+                .entry(node.parent.as_deref())
+                .or_default()
+                .push(node);
+        }
+
+        for parent in children.keys().flatten() {
+            if !ids.contains(parent) {
+                return Err(LoroError::ArgErr(
+                    format!("Parent node not found: {}", parent).into_boxed_str(),
+                ));
+            }
+        }
+
+        for siblings in children.values_mut() {
+            siblings.sort_by_key(|n| n.index);
+        }
+
+        // Parents must be placed before their children
+        let mut order = Vec::with_capacity(nodes.len());
+        let mut queue = VecDeque::new();
+        queue.push_back(None);
+        while let Some(parent) = queue.pop_front() {
+            if let Some(siblings) = children.get(&parent) {
+                for node in siblings {
+                    order.push(*node);
+                    queue.push_back(Some(node.id.as_str()));
+                }
+            }
+        }
+
+        if order.len() != nodes.len() {
+            return Err(LoroError::ArgErr(
+                "The parent links of the nodes contain a cycle"
+                    .to_string()
+                    .into_boxed_str(),
+            ));
+        }
+
+        let mut existing: FxHashMap<String, TreeID> = FxHashMap::default();
+        let mut unmatched = Vec::new();
+        for id in self.nodes() {
+            if self.is_node_deleted(&id)? {
+                continue;
+            }
+
+            let key = self
+                .get_meta(id)?
+                .get(id_key)
+                .and_then(|v| v.as_string().map(|s| s.to_string()));
+            match key {
+                Some(key) if ids.contains(key.as_str()) && !existing.contains_key(&key) => {
+                    existing.insert(key, id);
+                }
+                _ => unmatched.push(id),
+            }
+        }
+
+        // The unmatched ancestors of the matched nodes can only be deleted after the matched
+        // nodes are moved out of them. The other unmatched nodes are deleted first, so that the
+        // indexes of the moves are computed without them.
+        let mut ancestors = FxHashSet::default();
+        for target in existing.values() {
+            let mut parent = self.get_node_parent(target);
+            while let Some(TreeParentId::Node(p)) = parent {
+                if !ancestors.insert(p) {
+                    break;
+                }
+                parent = self.get_node_parent(&p);
+            }
+        }
+        let (delete_last, delete_first): (Vec<_>, Vec<_>) =
+            unmatched.into_iter().partition(|id| ancestors.contains(id));
+
+        a.with_txn(|txn| {
+            for target in delete_first {
+                // It may be deleted along with its deleted ancestor
+                if !self.is_node_deleted(&target)? {
+                    self.delete_with_txn(txn, target)?;
+                }
+            }
+
+            let mut ans: FxHashMap<String, TreeID> = FxHashMap::default();
+            let mut next_index: FxHashMap<Option<&str>, usize> = FxHashMap::default();
+            for node in order {
+                let parent = match &node.parent {
+                    Some(p) => TreeParentId::Node(ans[p.as_str()]),
+                    None => TreeParentId::Root,
+                };
+                let index = next_index.entry(node.parent.as_deref()).or_insert(0);
+                let target = match existing.get(&node.id) {
+                    Some(&target) => {
+                        self.mov_with_txn(txn, target, parent, *index, FiIfNotConfigured::Throw)?;
+                        target
+                    }
+                    None => self.create_with_txn(txn, parent, *index, FiIfNotConfigured::Throw)?,
+                };
+                *index += 1;
+
+                let meta = self.get_meta(target)?;
+                // Inserting the same value is a no-op
+                meta.insert_with_txn(txn, id_key, node.id.clone().into())?;
+                for (key, value) in node.meta.iter() {
+                    if key != id_key {
+                        meta.insert_with_txn(txn, key, value.clone())?;
+                    }
+                }
+                let outdated: Vec<_> = meta
+                    .keys()
+                    .filter(|k| k.as_str() != id_key && !node.meta.contains_key(k.as_str()))
+                    .collect();
+                for key in outdated {
+                    meta.delete_with_txn(txn, &key)?;
+                }
+
+                ans.insert(node.id.clone(), target);
+            }
+
+            for target in delete_last {
+                // It may be deleted along with its deleted ancestor
+                if !self.is_node_deleted(&target)? {
+                    self.delete_with_txn(txn, target)?;
+                }
+            }
+
+            Ok(ans)
+        })
+    }
+
     pub fn get_meta(&self, target: TreeID) -> LoroResult<MapHandler> {
         match &self.inner {
             MaybeDetached::Detached(d) => {
@@ -900,19 +1073,22 @@ impl TreeHandler {
         }
     }
 
+    /// `op_id` is the id of the op that will use the position, which seeds the jitter.
     fn generate_position_at(
         &self,
         target: &TreeID,
         parent: &TreeParentId,
         index: usize,
         cfg: FiIfNotConfigured,
+        op_id: ID,
     ) -> FractionalIndexGenResult {
         let MaybeDetached::Attached(a) = &self.inner else {
             unreachable!()
         };
+        let jitter_seed = a.doc.config.jitter_seed(op_id);
         a.with_state(|state| {
             let a = state.as_tree_state_mut().unwrap();
-            a.generate_position_at(target, parent, index, cfg)
+            a.generate_position_at(target, parent, index, cfg, jitter_seed)
         })
     }
 
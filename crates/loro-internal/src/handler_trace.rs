@@ -0,0 +1,312 @@
+//! The traces of the handler calls made through the JS bindings.
+//!
+//! `loro-wasm` records the mutating calls, e.g. `text.insert` or `doc.import`, in the order
+//! they were made, together with the final states of the docs. The Rust tests replay the
+//! trace on [`crate::LoroDoc`] and compare the states, so that a bug reported from JS can be
+//! turned into a Rust regression test by dropping its trace into the test suite.
+//!
+//! The arguments are recorded as they were passed by JS, so the text positions are
+//! UTF-16 indexes.
+use loro_common::{ContainerID, ContainerType, LoroValue, PeerID, TreeID, ID};
+use serde::{Deserialize, Serialize};
+
+use crate::VersionVector;
+
+/// A recorded sequence of handler calls on one or more docs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HandlerTrace {
+    pub steps: Vec<TraceStep>,
+    /// The states of the docs when the recording stopped
+    pub checkpoints: Vec<TraceCheckpoint>,
+}
+
+/// A handler call on the doc at index `doc`.
+///
+/// The docs are indexed in the order they were first seen by the recorder.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceStep {
+    pub doc: usize,
+    #[serde(flatten)]
+    pub action: TraceAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TraceAction {
+    /// The doc is seen by the recorder for the first time.
+    ///
+    /// The snapshot is the history of the doc before the recording, if any.
+    NewDoc {
+        peer: PeerID,
+        snapshot: Option<Vec<u8>>,
+    },
+    SetPeerId {
+        peer: PeerID,
+    },
+    Commit,
+    Import {
+        bytes: Vec<u8>,
+    },
+    Checkout {
+        frontiers: Vec<ID>,
+    },
+    CheckoutToLatest,
+    /// The text styles and their expand types, e.g. `("bold", "after")`
+    ConfigTextStyle {
+        styles: Vec<(String, String)>,
+    },
+    TextInsert {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        pos: usize,
+        text: String,
+    },
+    TextDelete {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        pos: usize,
+        len: usize,
+    },
+    TextMark {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        start: usize,
+        end: usize,
+        key: String,
+        value: LoroValue,
+    },
+    TextUnmark {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        start: usize,
+        end: usize,
+        key: String,
+    },
+    MapSet {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        key: String,
+        value: LoroValue,
+    },
+    MapDelete {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        key: String,
+    },
+    /// Only the type of the child is recorded, the edits on it before it's attached are not.
+    MapSetContainer {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        key: String,
+        kind: ContainerType,
+    },
+    /// Insert into a list or a movable list
+    ListInsert {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        pos: usize,
+        value: LoroValue,
+    },
+    /// Delete from a list or a movable list
+    ListDelete {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        pos: usize,
+        len: usize,
+    },
+    /// Only the type of the child is recorded, the edits on it before it's attached are not.
+    ListInsertContainer {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        pos: usize,
+        kind: ContainerType,
+    },
+    MovableListSet {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        pos: usize,
+        value: LoroValue,
+    },
+    /// Only the type of the child is recorded, the edits on it before it's attached are not.
+    MovableListSetContainer {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        pos: usize,
+        kind: ContainerType,
+    },
+    MovableListMove {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        from: usize,
+        to: usize,
+    },
+    TreeCreate {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        #[serde(with = "serde_impl::option_tree_id")]
+        parent: Option<TreeID>,
+        index: Option<usize>,
+    },
+    TreeMove {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        #[serde(with = "serde_impl::tree_id")]
+        target: TreeID,
+        #[serde(with = "serde_impl::option_tree_id")]
+        parent: Option<TreeID>,
+        index: Option<usize>,
+    },
+    /// Move `target` to be right after `other`
+    TreeMoveAfter {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        #[serde(with = "serde_impl::tree_id")]
+        target: TreeID,
+        #[serde(with = "serde_impl::tree_id")]
+        other: TreeID,
+    },
+    /// Move `target` to be right before `other`
+    TreeMoveBefore {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        #[serde(with = "serde_impl::tree_id")]
+        target: TreeID,
+        #[serde(with = "serde_impl::tree_id")]
+        other: TreeID,
+    },
+    TreeDelete {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        #[serde(with = "serde_impl::tree_id")]
+        target: TreeID,
+    },
+    CounterIncrement {
+        #[serde(with = "serde_impl::container_id")]
+        container: ContainerID,
+        value: f64,
+    },
+}
+
+/// The state of the doc at index `doc` when the recording stopped.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceCheckpoint {
+    pub doc: usize,
+    /// The deep value of the doc in JSON
+    pub value: serde_json::Value,
+    pub oplog_vv: VersionVector,
+}
+
+impl HandlerTrace {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+mod serde_impl {
+    pub mod container_id {
+        use loro_common::ContainerID;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S>(id: &ContainerID, s: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            s.serialize_str(&id.to_string())
+        }
+
+        pub fn deserialize<'de, D>(d: D) -> Result<ContainerID, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let str: String = Deserialize::deserialize(d)?;
+            ContainerID::try_from(str.as_str())
+                .map_err(|_| serde::de::Error::custom("invalid container id"))
+        }
+    }
+
+    pub mod tree_id {
+        use loro_common::TreeID;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S>(id: &TreeID, s: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            s.serialize_str(&id.to_string())
+        }
+
+        pub fn deserialize<'de, D>(d: D) -> Result<TreeID, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let str: String = Deserialize::deserialize(d)?;
+            TreeID::try_from(str.as_str()).map_err(|_| serde::de::Error::custom("invalid tree id"))
+        }
+    }
+
+    pub mod option_tree_id {
+        use loro_common::TreeID;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S>(id: &Option<TreeID>, s: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match id {
+                Some(id) => s.serialize_str(&id.to_string()),
+                None => s.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D>(d: D) -> Result<Option<TreeID>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let str: Option<String> = Deserialize::deserialize(d)?;
+            str.map(|str| {
+                TreeID::try_from(str.as_str())
+                    .map_err(|_| serde::de::Error::custom("invalid tree id"))
+            })
+            .transpose()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn trace_json_roundtrip() {
+        let trace = HandlerTrace {
+            steps: vec![
+                TraceStep {
+                    doc: 0,
+                    action: TraceAction::NewDoc {
+                        peer: 1,
+                        snapshot: None,
+                    },
+                },
+                TraceStep {
+                    doc: 0,
+                    action: TraceAction::TreeCreate {
+                        container: ContainerID::new_root("tree", ContainerType::Tree),
+                        parent: Some(TreeID::new(1, 0)),
+                        index: None,
+                    },
+                },
+            ],
+            checkpoints: vec![],
+        };
+        let json = trace.to_json();
+        assert!(json.contains(r#""type": "treeCreate""#));
+        assert!(json.contains(r#""container": "cid:root-tree:Tree""#));
+        assert!(json.contains(r#""parent": "0@1""#));
+        let decoded = HandlerTrace::from_json(&json).unwrap();
+        assert_eq!(decoded.steps, trace.steps);
+    }
+}
@@ -28,6 +28,7 @@ use crate::{
     op::{InnerContent, RichOp, SliceWithId},
     oplog::ChangeStore,
     state::{ContainerCreationContext, GcStore},
+    version::ImVersionVector,
     OpLog, VersionVector,
 };
 
@@ -194,7 +195,6 @@ impl ContainerHistoryCache {
 
         let default_ctx = ContainerCreationContext {
             configure: &Default::default(),
-            peer: 0,
         };
 
         trace!("init_cache_by_visit_all_change_slow");
@@ -323,7 +323,6 @@ impl ContainerHistoryCache {
                 idx,
                 ContainerCreationContext {
                     configure: &Default::default(),
-                    peer: 0,
                 },
             )
             .as_richtext_state()
@@ -362,18 +361,17 @@ impl ContainerHistoryCache {
             idx,
             ContainerCreationContext {
                 configure: &Default::default(),
-                peer: 0,
             },
         );
 
         let mut ans = Vec::with_capacity(target_span.atom_len());
         match list_state {
             crate::state::State::ListState(list) => {
-                for v in list.iter_with_id() {
-                    if target_span.contains(v.id.id()) {
+                for (id, v) in list.iter_with_id() {
+                    if target_span.contains(id.id()) {
                         ans.push(SliceWithId {
-                            values: Either::Right(v.v.clone()),
-                            id: v.id,
+                            values: Either::Right(v),
+                            id,
                             elem_id: None,
                         })
                     }
@@ -598,6 +596,81 @@ impl MapHistoryCache {
 
         ans
     }
+
+    /// Get the concurrent sets on `key` at `vv`, i.e. the ops in `vv` that no other op on the
+    /// key has seen, sorted by their lamports and peers.
+    ///
+    /// The value recorded for the shallow root is only returned if there are no ops after it.
+    pub fn get_key_heads_at_vv(
+        &self,
+        container: ContainerIdx,
+        key: &InternalString,
+        vv: &VersionVector,
+        oplog: &OpLog,
+    ) -> Vec<GroupedMapOpInfo> {
+        let Some(key_idx) = self.keys.get(key) else {
+            return Vec::new();
+        };
+        let bound = |key: u32| MapHistoryCacheEntry {
+            container,
+            key,
+            lamport: 0,
+            peer: 0,
+            counter_or_value: Either::Left(0),
+        };
+        let range = (
+            Bound::Included(bound(key_idx as u32)),
+            Bound::Excluded(bound(key_idx as u32 + 1)),
+        );
+
+        let mut heads: Vec<(GroupedMapOpInfo, Option<ImVersionVector>)> = Vec::new();
+        // An op can't have seen the ops with greater lamports, so the heads are found first
+        for entry in self.map.range(range).rev() {
+            match &entry.counter_or_value {
+                Either::Left(cnt) => {
+                    if vv.get(&entry.peer).copied().unwrap_or(0) <= *cnt {
+                        continue;
+                    }
+
+                    let id = ID::new(entry.peer, *cnt);
+                    if heads
+                        .iter()
+                        .any(|(_, seen)| seen.as_ref().is_some_and(|vv| vv.includes_id(id)))
+                    {
+                        continue;
+                    }
+
+                    let op = oplog.get_op_that_includes(id).unwrap();
+                    let InnerContent::Map(map) = &op.content else {
+                        unreachable!()
+                    };
+                    heads.push((
+                        GroupedMapOpInfo {
+                            value: map.value.clone(),
+                            lamport: entry.lamport,
+                            peer: entry.peer,
+                        },
+                        oplog.dag.get_vv(id),
+                    ));
+                }
+                Either::Right(v) => {
+                    if heads.is_empty() {
+                        heads.push((
+                            GroupedMapOpInfo {
+                                value: (**v).clone(),
+                                lamport: entry.lamport,
+                                peer: entry.peer,
+                            },
+                            None,
+                        ));
+                    }
+                }
+            }
+        }
+
+        heads.reverse();
+        heads.into_iter().map(|(info, _)| info).collect()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,151 @@
+//! Sharing the versions of a doc as [`ImVersionVector`]s.
+//!
+//! A server holding thousands of docs reads their versions on every request, and
+//! [`LoroDoc::oplog_vv`] clones a hash map with an entry for every peer each time. The
+//! [`ImVersionVector`]s returned here are cached per doc until its version changes, and they
+//! share their structure with the previous ones, so reading an unchanged version and cloning
+//! it across threads are cheap. [`ExportMode::UpdatesIm`](crate::loro::ExportMode::UpdatesIm)
+//! exports from such a version without converting it back.
+use loro_common::{IdSpan, PeerID};
+
+use crate::{
+    oplog::OpLog,
+    version::{Frontiers, ImVersionVector, VersionVector},
+    LoroDoc,
+};
+
+/// The last versions returned by [`LoroDoc::oplog_im_vv`] and [`LoroDoc::state_im_vv`], with
+/// the frontiers they were computed at
+#[derive(Debug, Default)]
+pub(crate) struct ImVersionCache {
+    oplog: Option<(Frontiers, ImVersionVector)>,
+    state: Option<(Frontiers, ImVersionVector)>,
+}
+
+impl LoroDoc {
+    /// Get the version vector of the [`OpLog`] as an [`ImVersionVector`].
+    ///
+    /// It's cached until the oplog changes, and it's cheap to clone.
+    pub fn oplog_im_vv(&self) -> ImVersionVector {
+        let oplog = self.oplog().lock().unwrap();
+        let mut cache = self.im_version_cache.lock().unwrap();
+        cached_im_vv(&mut cache.oplog, oplog.frontiers(), || oplog.vv().clone())
+    }
+
+    /// Get the version vector of the [`DocState`](crate::DocState) as an [`ImVersionVector`].
+    ///
+    /// It's cached until the state changes, and it's cheap to clone.
+    pub fn state_im_vv(&self) -> ImVersionVector {
+        let oplog = self.oplog().lock().unwrap();
+        let frontiers = self.app_state().lock().unwrap().frontiers.clone();
+        let mut cache = self.im_version_cache.lock().unwrap();
+        cached_im_vv(&mut cache.state, &frontiers, || {
+            oplog.dag.frontiers_to_vv(&frontiers).unwrap()
+        })
+    }
+}
+
+fn cached_im_vv(
+    slot: &mut Option<(Frontiers, ImVersionVector)>,
+    frontiers: &Frontiers,
+    vv: impl FnOnce() -> VersionVector,
+) -> ImVersionVector {
+    if let Some((f, im_vv)) = slot.as_ref() {
+        if f == frontiers {
+            return im_vv.clone();
+        }
+    }
+
+    let vv = vv();
+    // Update the previous version in place, so that the unchanged entries stay shared with
+    // the clones of it
+    let mut im_vv = slot.take().map(|(_, v)| v).unwrap_or_default();
+    let removed: Vec<PeerID> = im_vv
+        .iter()
+        .filter(|(peer, _)| !vv.contains_key(peer))
+        .map(|(peer, _)| *peer)
+        .collect();
+    for peer in removed {
+        im_vv.remove(&peer);
+    }
+    for (&peer, &counter) in vv.iter() {
+        if im_vv.get(&peer) != Some(&counter) {
+            im_vv.insert(peer, counter);
+        }
+    }
+
+    *slot = Some((frontiers.clone(), im_vv.clone()));
+    im_vv
+}
+
+/// The spans of the history that are not included by `from`, without the ones before the
+/// shallow root
+pub(crate) fn spans_since(oplog: &OpLog, from: &ImVersionVector) -> Vec<IdSpan> {
+    let shallow_since_vv = oplog.shallow_since_vv();
+    let mut spans: Vec<IdSpan> = oplog
+        .vv()
+        .iter()
+        .filter_map(|(&peer, &end)| {
+            let start = from
+                .get(&peer)
+                .copied()
+                .unwrap_or(0)
+                .max(shallow_since_vv.get(&peer).copied().unwrap_or(0));
+            (start < end).then(|| IdSpan::new(peer, start, end))
+        })
+        .collect();
+    spans.sort_unstable_by_key(|span| span.peer);
+    spans
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::loro::ExportMode;
+
+    #[test]
+    fn im_vv_is_cached_until_the_version_changes() {
+        let doc = LoroDoc::new_auto_commit();
+        doc.set_peer_id(1).unwrap();
+        doc.get_text("text").insert(0, "hello").unwrap();
+        doc.commit_then_renew();
+
+        let vv = doc.oplog_im_vv();
+        assert_eq!(vv.to_vv(), doc.oplog_vv());
+        assert_eq!(doc.state_im_vv().to_vv(), doc.state_vv());
+
+        doc.get_text("text").insert(5, " world").unwrap();
+        doc.commit_then_renew();
+        assert_eq!(vv.get(&1), Some(&5));
+        assert_eq!(doc.oplog_im_vv().get(&1), Some(&11));
+
+        doc.checkout(&Frontiers::from_id(loro_common::ID::new(1, 4)))
+            .unwrap();
+        assert_eq!(doc.state_im_vv().get(&1), Some(&5));
+        doc.checkout_to_latest();
+        assert_eq!(doc.state_im_vv().get(&1), Some(&11));
+
+        let other = LoroDoc::new_auto_commit();
+        other.set_peer_id(2).unwrap();
+        other
+            .import(
+                &doc.export(ExportMode::updates_im(&Default::default()))
+                    .unwrap(),
+            )
+            .unwrap();
+        other.get_text("text").insert(0, "> ").unwrap();
+        other.commit_then_renew();
+        let updates = other
+            .export(ExportMode::updates_im(&doc.oplog_im_vv()))
+            .unwrap();
+        assert_eq!(
+            updates.len(),
+            other
+                .export(ExportMode::updates(&doc.oplog_vv()))
+                .unwrap()
+                .len()
+        );
+        doc.import(&updates).unwrap();
+        assert_eq!(doc.get_text("text").to_string(), "> hello world");
+    }
+}
@@ -0,0 +1,211 @@
+//! Importing through a pipeline of policy stages.
+//!
+//! Servers often decrypt a message, check who sent which ops, and drop the changes they don't
+//! accept before importing. Doing it with raw bytes means decoding the same update several
+//! times. An [`ImportPipeline`] runs the stages on the intermediate structures of a single
+//! import instead: the transforms get the bytes, the validators get the decoded blob metadata,
+//! and the filters get the decoded changes right before they are added to the history.
+use std::borrow::Cow;
+
+use loro_common::{ContainerID, LoroResult};
+
+use crate::{
+    arena::SharedArena,
+    change::Change,
+    encoding::{ImportBlobMetadata, ImportStatus},
+    ChangeMeta, LoroDoc,
+};
+
+type Transform = Box<dyn Fn(Vec<u8>) -> LoroResult<Vec<u8>> + Send + Sync>;
+type Validator = Box<dyn Fn(&ImportBlobMetadata) -> LoroResult<()> + Send + Sync>;
+type Filter = Box<dyn Fn(&DecodedChange) -> LoroResult<bool> + Send + Sync>;
+
+/// The stages run by [`LoroDoc::import_with_pipeline`], in the order of their kinds:
+/// transforms, then validators, then filters. The stages of the same kind run in the order
+/// they were added. An error in any stage aborts the import before anything is applied.
+#[derive(Default)]
+pub struct ImportPipeline {
+    transforms: Vec<Transform>,
+    validators: Vec<Validator>,
+    filters: Vec<Filter>,
+}
+
+impl std::fmt::Debug for ImportPipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ImportPipeline")
+            .field("transforms", &self.transforms.len())
+            .field("validators", &self.validators.len())
+            .field("filters", &self.filters.len())
+            .finish()
+    }
+}
+
+impl ImportPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a stage that rewrites the bytes before they are parsed, e.g. to decrypt them
+    pub fn transform(
+        mut self,
+        f: impl Fn(Vec<u8>) -> LoroResult<Vec<u8>> + Send + Sync + 'static,
+    ) -> Self {
+        self.transforms.push(Box::new(f));
+        self
+    }
+
+    /// Add a stage that checks the metadata of the blob, e.g. its mode or version range,
+    /// before its changes are decoded
+    pub fn validate(
+        mut self,
+        f: impl Fn(&ImportBlobMetadata) -> LoroResult<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.validators.push(Box::new(f));
+        self
+    }
+
+    /// Add a stage that decides whether each decoded change is imported.
+    ///
+    /// The changes that depend on a dropped change stay pending, as if it was missing.
+    pub fn filter(
+        mut self,
+        f: impl Fn(&DecodedChange) -> LoroResult<bool> + Send + Sync + 'static,
+    ) -> Self {
+        self.filters.push(Box::new(f));
+        self
+    }
+
+    pub(crate) fn has_filters(&self) -> bool {
+        !self.filters.is_empty()
+    }
+
+    pub(crate) fn filter_changes(
+        &self,
+        changes: Vec<Change>,
+        arena: &SharedArena,
+    ) -> LoroResult<Vec<Change>> {
+        let mut ans = Vec::with_capacity(changes.len());
+        'outer: for change in changes {
+            let decoded = DecodedChange {
+                change: &change,
+                arena,
+            };
+            for filter in self.filters.iter() {
+                if !filter(&decoded)? {
+                    continue 'outer;
+                }
+            }
+
+            ans.push(change);
+        }
+
+        Ok(ans)
+    }
+}
+
+/// A change decoded from an imported blob, as seen by the filters of an [`ImportPipeline`]
+pub struct DecodedChange<'a> {
+    change: &'a Change,
+    arena: &'a SharedArena,
+}
+
+impl DecodedChange<'_> {
+    pub fn meta(&self) -> ChangeMeta {
+        ChangeMeta::from_change(self.change)
+    }
+
+    /// The containers modified by the ops of the change
+    pub fn containers(&self) -> Vec<ContainerID> {
+        let mut containers: Vec<_> = self.change.ops().iter().map(|op| op.container).collect();
+        containers.sort_unstable();
+        containers.dedup();
+        containers
+            .into_iter()
+            .map(|idx| self.arena.idx_to_id(idx).unwrap())
+            .collect()
+    }
+}
+
+impl LoroDoc {
+    /// Import `bytes` through the stages of `pipeline`.
+    ///
+    /// The blob is parsed once: the validators see its metadata, and the filters see its
+    /// changes as they are decoded for the import. With filters, a snapshot is imported as
+    /// updates even into an empty doc, so the history of a shallow snapshot can't be imported
+    /// that way.
+    pub fn import_with_pipeline(
+        &self,
+        bytes: &[u8],
+        pipeline: &ImportPipeline,
+    ) -> LoroResult<ImportStatus> {
+        let mut bytes = Cow::Borrowed(bytes);
+        for transform in pipeline.transforms.iter() {
+            bytes = Cow::Owned(transform(bytes.into_owned())?);
+        }
+
+        if !pipeline.validators.is_empty() {
+            let meta = LoroDoc::decode_import_blob_meta(&bytes, false)?;
+            for validate in pipeline.validators.iter() {
+                validate(&meta)?;
+            }
+        }
+
+        self.import_inner(&bytes, Default::default(), Some(pipeline))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoding::ExportMode;
+    use loro_common::LoroError;
+
+    #[test]
+    fn import_through_stages() {
+        let a = LoroDoc::new_auto_commit();
+        a.set_peer_id(1).unwrap();
+        // The changes with different messages are not merged
+        for (i, msg) in ["a", "b", "c"].into_iter().enumerate() {
+            match i {
+                1 => a.get_map("secret").insert("key", 1).unwrap(),
+                _ => a.get_text("text").insert(0, msg).unwrap(),
+            }
+            a.set_next_commit_message(msg);
+            a.commit_then_renew();
+        }
+        // The "encryption" flips the bits
+        let bytes: Vec<u8> = a
+            .export(ExportMode::all_updates())
+            .unwrap()
+            .into_iter()
+            .map(|b| !b)
+            .collect();
+
+        let secret = a.get_map("secret").id();
+        let pipeline = ImportPipeline::new()
+            .transform(|bytes| Ok(bytes.into_iter().map(|b| !b).collect()))
+            .validate(|meta| {
+                if meta.mode.is_snapshot() {
+                    return Err(LoroError::ArgErr("Snapshots are not accepted".into()));
+                }
+                Ok(())
+            })
+            .filter(move |change| Ok(!change.containers().contains(&secret)));
+        let b = LoroDoc::new_auto_commit();
+        let status = b.import_with_pipeline(&bytes, &pipeline).unwrap();
+        assert_eq!(b.get_text("text").to_string(), "a");
+        assert!(b.get_map("secret").get("key").is_none());
+        // The change after the dropped one is pending
+        assert!(status.pending.is_some());
+
+        let snapshot: Vec<u8> = a
+            .export(ExportMode::Snapshot)
+            .unwrap()
+            .into_iter()
+            .map(|b| !b)
+            .collect();
+        let c = LoroDoc::new_auto_commit();
+        assert!(c.import_with_pipeline(&snapshot, &pipeline).is_err());
+        assert!(c.oplog_vv().is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+//! Application invariants that are checked after the remote updates are applied.
+//!
+//! Merging concurrent edits always converges, but the merged state can break a constraint that
+//! every replica kept locally, e.g. two replicas that both spend the remaining budget. The
+//! invariants added by [`LoroDoc::add_invariant`] are checked after every import that changes
+//! the state, and the violations are sent to the subscribers of
+//! [`LoroDoc::subscribe_invariant_violation`], which can repair the state in a new transaction.
+use std::sync::Arc;
+
+use loro_common::{ContainerID, LoroValue};
+
+use crate::{event::Index, version::Frontiers, LoroDoc, Subscription};
+
+/// A check of the doc state, added by [`LoroDoc::add_invariant`]
+pub type Invariant = Arc<dyn Fn(&DocStateView) -> Result<(), Violation> + Send + Sync>;
+/// The callback of the invariant violations. The subscription is removed if it returns false.
+pub type InvariantViolationCallback =
+    Box<dyn Fn(&InvariantViolation) -> bool + Send + Sync + 'static>;
+
+/// Why the state breaks an invariant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// The containers that break the invariant, which the repair should edit
+    pub containers: Vec<ContainerID>,
+    pub message: String,
+}
+
+impl Violation {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            containers: Vec::new(),
+            message: message.into(),
+        }
+    }
+
+    /// Add a container that breaks the invariant
+    pub fn with_container(mut self, id: ContainerID) -> Self {
+        self.containers.push(id);
+        self
+    }
+}
+
+/// The event emitted when the state breaks an invariant after an import
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvariantViolation {
+    /// The name the invariant was added with
+    pub invariant: String,
+    pub violation: Violation,
+    /// The version of the state that breaks the invariant
+    pub frontiers: Frontiers,
+}
+
+/// A read-only view of the doc state, passed to the invariants.
+pub struct DocStateView<'a> {
+    doc: &'a LoroDoc,
+}
+
+impl DocStateView<'_> {
+    /// The version of the state
+    pub fn frontiers(&self) -> Frontiers {
+        self.doc.state_frontiers()
+    }
+
+    pub fn get_deep_value(&self) -> LoroValue {
+        self.doc.get_deep_value()
+    }
+
+    /// Get the deep value of the container, `None` if it doesn't exist.
+    pub fn get_container_deep_value(&self, id: &ContainerID) -> Option<LoroValue> {
+        let mut state = self.doc.app_state().lock().unwrap();
+        let idx = state.arena.id_to_idx(id)?;
+        Some(state.get_container_deep_value(idx))
+    }
+
+    /// Get the deep value at the path, `None` if it doesn't exist.
+    pub fn get_by_path(&self, path: &[Index]) -> Option<LoroValue> {
+        let value = self
+            .doc
+            .app_state()
+            .lock()
+            .unwrap()
+            .get_value_by_path(path)?;
+        match value {
+            LoroValue::Container(id) => self.get_container_deep_value(&id),
+            value => Some(value),
+        }
+    }
+}
+
+impl LoroDoc {
+    /// Add an invariant that is checked after every import that changes the state.
+    ///
+    /// The violations are sent to [`LoroDoc::subscribe_invariant_violation`]. The local edits
+    /// are not checked, since the app can keep the invariants itself when it edits. The
+    /// invariant is removed when the returned subscription is dropped or unsubscribed.
+    pub fn add_invariant(&self, name: &str, invariant: Invariant) -> Subscription {
+        let (sub, activate) = self.invariants.insert((), (name.to_string(), invariant));
+        activate();
+        sub
+    }
+
+    /// Subscribe to the violations of the invariants added by [`LoroDoc::add_invariant`].
+    ///
+    /// The callback runs after the import, when the doc is unlocked, so it can repair the state
+    /// with new edits. The repairs are local edits, so they don't trigger the checks again.
+    pub fn subscribe_invariant_violation(
+        &self,
+        callback: InvariantViolationCallback,
+    ) -> Subscription {
+        let (sub, activate) = self.invariant_violation_subs.inner().insert((), callback);
+        activate();
+        sub
+    }
+
+    /// Check the invariants against the current state and emit the violations
+    pub(crate) fn check_invariants(&self) {
+        if self.is_detached() || self.invariants.is_empty() {
+            return;
+        }
+
+        let view = DocStateView { doc: self };
+        let mut violations = Vec::new();
+        let frontiers = self.state_frontiers();
+        let _ = self.invariants.retain(&(), &mut |(name, check)| {
+            if let Err(violation) = check(&view) {
+                violations.push(InvariantViolation {
+                    invariant: name.clone(),
+                    violation,
+                    frontiers: frontiers.clone(),
+                });
+            }
+            true
+        });
+
+        for violation in violations {
+            self.invariant_violation_subs.emit(&(), violation);
+        }
+    }
+}
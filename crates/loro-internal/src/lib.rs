@@ -11,6 +11,7 @@ pub mod arena;
 pub mod diff;
 pub mod diff_calc;
 pub mod handler;
+pub mod handler_trace;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 mod change_meta;
@@ -21,38 +22,87 @@ use diff_calc::DiffCalculator;
 use lock::LoroMutex;
 
 pub use change_meta::ChangeMeta;
-pub use event::{ContainerDiff, DiffEvent, DocDiff, ListDiff, ListDiffInsertItem, ListDiffItem};
+pub use container_info::{ContainerFilter, ContainerInfo, DanglingContainerRef};
+pub use event::{
+    json_pointer, ContainerDiff, DiffEvent, DocDiff, JsonPatchOp, ListDiff, ListDiffInsertItem,
+    ListDiffItem, ParagraphId,
+};
 pub use fxhash::FxHashMap;
 pub use handler::{
-    BasicHandler, HandlerTrait, ListHandler, MapHandler, MovableListHandler, TextHandler,
-    TreeHandler, UnknownHandler,
+    AnchorFallback, BasicHandler, HandlerTrait, ListHandler, MapHandler, MovableListHandler,
+    TextHandler, TreeHandler, UnknownHandler,
 };
+use invariant::{Invariant, InvariantViolation, InvariantViolationCallback};
 pub use loro_common;
 pub use oplog::OpLog;
 pub use state::DocState;
 pub use state::{TreeNode, TreeNodeWithChildren, TreeParentId};
-use subscription::{LocalUpdateCallback, Observer, PeerIdUpdateCallback};
+use subscription::{
+    LocalUpdateCallback, Observer, PeerIdUpdateCallback, VersionUpdateCallbackInner,
+};
 use txn::Transaction;
 pub use undo::UndoManager;
-use utils::subscription::SubscriberSetWithQueue;
 pub use utils::subscription::Subscription;
+use utils::subscription::{SubscriberSet, SubscriberSetWithQueue};
 pub mod allocation;
+pub mod apply_log;
+pub mod author_report;
 pub mod awareness;
+pub(crate) mod batch;
+pub mod block_store;
 pub mod change;
+pub mod chunked_snapshot;
+pub mod commit_metadata;
+pub mod compression_dict;
 pub mod configure;
 pub mod container;
+mod container_checksum;
+mod container_info;
+pub mod container_template;
+mod container_upgrade;
+pub mod convergence;
+pub mod deleted_content;
+pub mod durable_subscription;
+pub use container_upgrade::UPGRADE_COMMIT_MSG_PREFIX;
 pub mod cursor;
 pub mod dag;
 pub mod encoding;
 pub(crate) mod fork;
+mod freeze;
+pub use freeze::FROZEN_ROOT_NAME;
 pub mod id;
+mod im_version;
+pub mod import_pipeline;
+pub mod invariant;
 #[cfg(feature = "jsonpath")]
 pub mod jsonpath;
 pub mod kv_store;
 pub mod loro;
+pub mod maintenance;
+pub mod map_conflict;
+pub mod memory_stats;
+#[cfg(feature = "mmap")]
+mod mmap;
 pub mod op;
+pub mod op_explanation;
 pub mod oplog;
+mod partial_checkout;
+pub mod peer_compaction;
+mod peer_rotation;
+mod pending_work;
+mod prefetch;
+pub use peer_rotation::PEER_ROTATION_COMMIT_MSG_PREFIX;
+pub use pending_work::PollStatus;
+pub mod replay;
+mod schema;
+mod settled;
+pub use settled::SettledCallback;
+mod single_writer;
+mod snapshot_import;
 pub mod subscription;
+mod subtree;
+pub use subtree::{COPIED_FROM_METADATA_KEY, COPIED_VERSION_METADATA_KEY, SUBTREE_ROOT_NAME};
+pub mod tree_overlay;
 pub mod txn;
 pub mod version;
 
@@ -74,6 +124,7 @@ pub mod estimated_size;
 pub(crate) mod history_cache;
 pub(crate) mod macros;
 pub(crate) mod state;
+pub mod state_snapshot;
 pub mod undo;
 pub(crate) mod value;
 
@@ -85,10 +136,10 @@ pub use encoding::json_schema::json;
 pub use fractional_index::FractionalIndex;
 pub use loro_common::{loro_value, to_value};
 pub use loro_common::{
-    Counter, CounterSpan, IdLp, IdSpan, IdSpanVector, Lamport, LoroEncodeError, LoroError,
-    LoroResult, LoroTreeError, PeerID, TreeID, ID,
+    Counter, CounterSpan, DecodeLimit, IdLp, IdSpan, IdSpanVector, Lamport, LoroEncodeError,
+    LoroError, LoroResult, LoroTreeError, PeerID, TreeID, ID,
 };
-pub use loro_common::{LoroBinaryValue, LoroListValue, LoroMapValue, LoroStringValue};
+pub use loro_common::{LoroBinaryValue, LoroDecimal, LoroListValue, LoroMapValue, LoroStringValue};
 #[cfg(feature = "wasm")]
 pub use value::wasm;
 pub use value::{ApplyDiff, LoroValue, ToJson};
@@ -155,8 +206,24 @@ pub struct LoroDocInner {
     txn: Arc<LoroMutex<Option<Transaction>>>,
     auto_commit: AtomicBool,
     detached: AtomicBool,
+    /// Whether it's inside [`LoroDoc::batch`], where the auto commits are deferred
+    batching: AtomicBool,
     local_update_subs: SubscriberSetWithQueue<(), LocalUpdateCallback, Vec<u8>>,
     peer_id_change_subs: SubscriberSetWithQueue<(), PeerIdUpdateCallback, ID>,
+    version_change_subs:
+        SubscriberSetWithQueue<(), VersionUpdateCallbackInner, (version::Frontiers, VersionVector)>,
+    invariants: SubscriberSet<(), (String, Invariant)>,
+    invariant_violation_subs:
+        SubscriberSetWithQueue<(), InvariantViolationCallback, InvariantViolation>,
+    /// The work scheduled by [`LoroDoc::checkout_in_slices`] and [`LoroDoc::import_in_slices`]
+    pending_work: std::sync::Mutex<Option<pending_work::PendingWork>>,
+    settled: settled::SettledSignal,
+    /// The checksums of the containers in the imported snapshot
+    container_checksums: std::sync::Mutex<Option<container_checksum::SnapshotChecksums>>,
+    /// The versions returned by [`LoroDoc::oplog_im_vv`] and [`LoroDoc::state_im_vv`]
+    im_version_cache: std::sync::Mutex<im_version::ImVersionCache>,
+    /// The versions hinted by [`LoroDoc::prefetch_version`] and their precomputed diffs
+    version_prefetch: std::sync::Mutex<prefetch::VersionPrefetch>,
 }
 
 /// The version of the loro crate
@@ -5,7 +5,7 @@ pub(crate) use crate::LoroDocInner;
 use crate::{
     arena::SharedArena,
     change::Timestamp,
-    configure::{Configure, DefaultRandom, SecureRandomGenerator, StyleConfig},
+    configure::{Configure, ContainerTypeConflictPolicy, StyleConfig},
     container::{
         idx::ContainerIdx, list::list_op::InnerListOp, richtext::config::StyleConfigMap,
         IntoContainerId,
@@ -14,22 +14,24 @@ use crate::{
     dag::{Dag, DagUtils},
     diff_calc::DiffCalculator,
     encoding::{
-        self, decode_snapshot, export_fast_snapshot, export_fast_updates,
-        export_fast_updates_in_range, export_shallow_snapshot, export_snapshot, export_snapshot_at,
-        export_state_only_snapshot,
+        self, decode_snapshot, export_fast_snapshot, export_fast_squashed_updates,
+        export_fast_updates, export_fast_updates_in_range, export_fast_updates_to,
+        export_shallow_snapshot, export_snapshot, export_snapshot_at, export_state_only_snapshot,
         json_schema::{encode_change_to_json, json::JsonSchema},
-        parse_header_and_body, EncodeMode, ImportBlobMetadata, ImportStatus, ParsedHeaderAndBody,
+        parse_header_and_body, EncodeMode, ImportBlobMetadata, ImportStats, ImportStatus,
+        ParsedHeaderAndBody,
     },
     event::{str_to_path, EventTriggerKind, Index, InternalDocDiff},
     handler::{Handler, MovableListHandler, TextHandler, TreeHandler, ValueOrHandler},
     id::PeerID,
+    import_pipeline::ImportPipeline,
     json::JsonChange,
     op::InnerContent,
-    oplog::{loro_dag::FrontiersNotIncluded, OpLog},
+    oplog::{loro_dag::FrontiersNotIncluded, ContainerTypeConflict, OpLog},
     state::DocState,
-    subscription::{LocalUpdateCallback, Observer, Subscriber},
+    subscription::{LocalUpdateCallback, Observer, Subscriber, SubscriberPriority},
     undo::DiffBatch,
-    utils::subscription::{SubscriberSetWithQueue, Subscription},
+    utils::subscription::{SubscriberSet, SubscriberSetWithQueue, Subscription},
     version::{shrink_frontiers, Frontiers, ImVersionVector, VersionRange, VersionVectorDiff},
     ChangeMeta, DocDiff, HandlerTrait, InternalString, ListHandler, LoroDoc, LoroError, MapHandler,
     VersionVector,
@@ -39,16 +41,17 @@ use crate::{
     lock::{LoroLockGroup, LoroMutex},
     txn::Transaction,
 };
+use bytes::Bytes;
 use either::Either;
 use fxhash::{FxHashMap, FxHashSet};
 use loro_common::{
-    ContainerID, ContainerType, HasIdSpan, HasLamportSpan, IdSpan, LoroEncodeError, LoroResult,
-    LoroValue, ID,
+    ContainerID, ContainerType, Counter, HasIdSpan, HasLamportSpan, IdSpan, LoroEncodeError,
+    LoroResult, LoroValue, ID,
 };
 use rle::HasLength;
 use std::{
     borrow::Cow,
-    cmp::Ordering,
+    cmp::{Ordering, Reverse},
     collections::{hash_map::Entry, BinaryHeap},
     ops::ControlFlow,
     sync::{
@@ -79,7 +82,10 @@ impl std::fmt::Debug for LoroDocInner {
 
 impl LoroDoc {
     pub fn new() -> Self {
-        let oplog = OpLog::new();
+        Self::new_with_oplog(OpLog::new())
+    }
+
+    pub(crate) fn new_with_oplog(oplog: OpLog) -> Self {
         let arena = oplog.arena.clone();
         let config: Configure = oplog.configure.clone();
         let lock_group = LoroLockGroup::new();
@@ -92,6 +98,7 @@ impl LoroDoc {
                 config,
                 detached: AtomicBool::new(false),
                 auto_commit: AtomicBool::new(false),
+                batching: AtomicBool::new(false),
                 observer: Arc::new(Observer::new(arena.clone())),
                 diff_calculator: Arc::new(
                     lock_group.new_lock(DiffCalculator::new(true), LockKind::DiffCalculator),
@@ -100,6 +107,14 @@ impl LoroDoc {
                 arena,
                 local_update_subs: SubscriberSetWithQueue::new(),
                 peer_id_change_subs: SubscriberSetWithQueue::new(),
+                version_change_subs: SubscriberSetWithQueue::new(),
+                invariants: SubscriberSet::new(),
+                invariant_violation_subs: SubscriberSetWithQueue::new(),
+                pending_work: Default::default(),
+                settled: Default::default(),
+                container_checksums: Default::default(),
+                im_version_cache: Default::default(),
+                version_prefetch: Default::default(),
             }
         });
         Self { inner }
@@ -110,12 +125,14 @@ impl LoroDoc {
             return self.fork_at(&self.state_frontiers());
         }
 
+        self.flush_pending_events();
         let (options, txn) = self.commit_then_stop();
         drop(txn);
         let snapshot = encoding::fast_snapshot::encode_snapshot_inner(self);
         let doc = Self::new();
         encoding::fast_snapshot::decode_snapshot_inner(snapshot, &doc).unwrap();
         doc.set_config(&self.config);
+        doc.derive_randomness_from(&self.config);
         if self.auto_commit.load(std::sync::atomic::Ordering::Relaxed) {
             doc.start_auto_commit();
         }
@@ -195,7 +212,7 @@ impl LoroDoc {
 
     /// Renews the PeerID for the document.
     pub(crate) fn renew_peer_id(&self) {
-        let peer_id = DefaultRandom.next_u64();
+        let peer_id = self.config.next_random_u64();
         self.set_peer_id(peer_id).unwrap();
     }
 
@@ -242,6 +259,23 @@ impl LoroDoc {
             return (None, Some(txn_guard));
         }
 
+        if config.immediate_renew && self.batching.load(Acquire) {
+            // The commit is deferred to the end of the batch
+            if let Some(txn) = txn_guard.as_mut() {
+                if let Some(origin) = config.origin.clone() {
+                    txn.set_origin(origin);
+                }
+                if let Some(timestamp) = config.timestamp {
+                    txn.set_timestamp(timestamp);
+                }
+                if let Some(msg) = config.commit_msg.as_ref() {
+                    txn.set_msg(Some(msg.clone()));
+                }
+            }
+
+            return (None, None);
+        }
+
         loop {
             let txn = txn_guard.take();
             let Some(mut txn) = txn else {
@@ -292,18 +326,15 @@ impl LoroDoc {
         }
     }
 
-    /// Set the commit message of the next commit
+    /// Set the commit message of the next commit, keeping the metadata set by
+    /// [`LoroDoc::set_next_commit_metadata`]
     pub fn set_next_commit_message(&self, message: &str) {
         let mut binding = self.txn.lock().unwrap();
         let Some(txn) = binding.as_mut() else {
             return;
         };
 
-        if message.is_empty() {
-            txn.set_msg(None)
-        } else {
-            txn.set_msg(Some(message.into()))
-        }
+        txn.set_message(message)
     }
 
     /// Set the origin of the next commit
@@ -370,9 +401,145 @@ impl LoroDoc {
         self.config.detached_editing()
     }
 
+    /// Set the max number of consecutive imports whose events are composed into one event.
+    ///
+    /// When many small remote updates are imported one by one, the events of these imports
+    /// are held back and the diffs are composed per container, so that the subscribers receive
+    /// one consolidated event instead of many tiny ones. The held events are emitted when
+    ///
+    /// - the window is full,
+    /// - another kind of event is emitted (e.g. a local commit or a checkout),
+    /// - or [`LoroDoc::flush_pending_events`] is called.
+    ///
+    /// The default value is 1, which means the events of every import are emitted immediately.
+    pub fn set_import_event_coalescing_window(&self, window: usize) {
+        self.config.set_import_event_coalescing_window(window);
+        if window <= 1 {
+            self.flush_pending_events();
+        }
+    }
+
+    /// Set how to handle the imported ops that target a container whose type conflicts
+    /// with a known container. See [`ContainerTypeConflictPolicy`].
+    pub fn set_container_type_conflict_policy(&self, policy: ContainerTypeConflictPolicy) {
+        self.config.set_container_type_conflict_policy(policy);
+    }
+
+    /// Set whether the text events report the ids of the changed paragraphs in
+    /// [`ContainerDiff::paragraph_changed`](crate::event::ContainerDiff::paragraph_changed).
+    ///
+    /// It's disabled by default, because finding the paragraphs needs to scan the text
+    /// before the changed ranges.
+    pub fn set_text_paragraph_ids(&self, enabled: bool) {
+        self.config.set_text_paragraph_ids(enabled);
+    }
+
+    /// Set whether the local ops can write [`LoroValue::ContainerRef`], [`LoroValue::Timestamp`]
+    /// and [`LoroValue::Decimal`] values.
+    ///
+    /// It's disabled by default, because the versions of Loro released before the value
+    /// types were added can't decode the updates and the snapshots that contain them. Only
+    /// enable it when all the peers of the doc support it. The binary updates and snapshots
+    /// are imported either way, but the JSON updates with such values are rejected unless
+    /// it's enabled.
+    ///
+    /// Once enabled, the local ops parse the single-entry tagged objects as the values they
+    /// represent, see [`LoroValue::parse_tags`].
+    pub fn set_extended_value_types(&self, enabled: bool) {
+        self.config.set_extended_value_types(enabled);
+    }
+
+    /// Set whether the fast snapshots exported by the doc store the checksums of the
+    /// container states, which [`LoroDoc::verify_container`] checks against.
+    ///
+    /// It's disabled by default, because computing them decodes the state of every alive
+    /// container. The checksums in the imported snapshots are loaded either way.
+    pub fn set_snapshot_checksums(&self, enabled: bool) {
+        self.config.set_snapshot_checksums(enabled);
+    }
+
+    /// Make the internal randomness of the doc deterministic by deriving it from `seed`.
+    ///
+    /// It covers the peer ids generated by the doc afterwards, e.g. when it's forked or
+    /// edited in detached mode, and the jitter of the fractional indexes of trees. The
+    /// jitter only depends on the seed and the id of the op, so replaying a history with
+    /// the same peer ids produces byte-identical encodings.
+    ///
+    /// With `None`, the peer ids are generated by the system's secure random generator,
+    /// and the jitter uses the seed 0.
+    pub fn set_random_seed(&self, seed: Option<u64>) {
+        self.config.set_random_seed(seed);
+    }
+
+    /// Get the container type conflicts quarantined by the imports.
+    ///
+    /// It's always empty unless the policy is [`ContainerTypeConflictPolicy::Quarantine`].
+    pub fn container_type_conflicts(&self) -> Vec<ContainerTypeConflict> {
+        self.oplog
+            .lock()
+            .unwrap()
+            .container_type_conflicts()
+            .to_vec()
+    }
+
+    /// Emit the events that are held back by the import event coalescing window.
+    pub fn flush_pending_events(&self) {
+        if self.state.lock().unwrap().has_coalesced_imports() {
+            self.emit_events();
+        }
+    }
+
+    /// Set whether identical string and binary values share one allocation. Default is `false`.
+    ///
+    /// When enabled, the short strings and binaries inserted into lists, movable lists and maps
+    /// (locally or by importing updates) are looked up in a doc-level pool, so repeated values
+    /// like enum-like tags of log records are stored only once in the state and the history.
+    ///
+    /// It only affects the memory layout. The encoding and the values are unchanged, so the
+    /// snapshots don't reference the pooled values by index.
+    #[inline]
+    pub fn set_value_interning(&self, enabled: bool) {
+        self.arena.set_value_interning(enabled);
+    }
+
+    #[inline]
+    pub fn is_value_interning_enabled(&self) -> bool {
+        self.arena.is_value_interning_enabled()
+    }
+
     #[inline]
     pub fn config_text_style(&self, text_style: StyleConfigMap) {
-        self.config.text_style_config.try_write().unwrap().map = text_style.map;
+        let mut config = self.config.text_style_config.try_write().unwrap();
+        config.map = text_style.map;
+        config.import_policy = text_style.import_policy;
+    }
+
+    /// Set how the counters accumulate their increments. `None` keeps the plain floating
+    /// point arithmetic.
+    ///
+    /// It should be set before the counters are edited or imported, and to the same config
+    /// on all the replicas.
+    #[inline]
+    pub fn config_counter(&self, config: Option<crate::configure::CounterConfig>) {
+        *self.config.counter_config.write().unwrap() = config;
+    }
+
+    /// Set the limits checked when the updates and the snapshots are imported
+    #[inline]
+    pub fn set_decode_limits(&self, limits: crate::configure::DecodeLimits) {
+        *self.config.decode_limits.write().unwrap() = limits;
+    }
+
+    /// Set how the consecutive local deletions are merged into one op
+    #[inline]
+    pub fn set_delete_merge_config(&self, config: crate::configure::DeleteMergeConfig) {
+        *self.config.delete_merge.write().unwrap() = config;
+    }
+
+    /// Take the imported styles flagged by [`crate::configure::StyleImportPolicy::Flag`]
+    #[inline]
+    pub fn take_flagged_styles(&self) -> Vec<crate::configure::InvalidStyle> {
+        std::mem::take(&mut self.config.text_style_config.write().unwrap().flagged)
     }
 
     #[inline]
@@ -396,6 +563,30 @@ impl LoroDoc {
         }
     }
 
+    /// Like [`LoroDoc::from_snapshot`], but the doc references `bytes` instead of copying them.
+    ///
+    /// The blocks of the history and the container states are decoded lazily from `bytes`
+    /// when they are accessed, and copied out only when they are modified.
+    pub fn from_snapshot_bytes(bytes: Bytes) -> LoroResult<Self> {
+        let doc = Self::new();
+        let ParsedHeaderAndBody { mode, body, .. } = parse_header_and_body(&bytes, true)?;
+        match mode {
+            EncodeMode::FastSnapshot => {
+                encoding::fast_snapshot::decode_snapshot(&doc, bytes.slice_ref(body))?;
+            }
+            mode if mode.is_snapshot() => {
+                decode_snapshot(&doc, mode, body)?;
+            }
+            _ => {
+                return Err(LoroError::DecodeError(
+                    "Invalid encode mode".to_string().into(),
+                ))
+            }
+        }
+
+        Ok(doc)
+    }
+
     /// Is the document empty? (no ops)
     #[inline(always)]
     pub fn can_reset_with_snapshot(&self) -> bool {
@@ -490,11 +681,25 @@ impl LoroDoc {
         bytes: &[u8],
         origin: InternalString,
     ) -> Result<ImportStatus, LoroError> {
+        self.import_inner(bytes, origin, None)
+    }
+
+    pub(crate) fn import_inner(
+        &self,
+        bytes: &[u8],
+        origin: InternalString,
+        pipeline: Option<&ImportPipeline>,
+    ) -> Result<ImportStatus, LoroError> {
+        let bytes = self.decompress_blob(bytes)?;
         let (options, txn) = self.commit_then_stop();
         assert!(txn.is_none());
-        let ans = self._import_with(bytes, origin);
+        let ans = self._import_with(&bytes, origin, pipeline);
         drop(txn);
         self.renew_txn_if_auto_commit(options);
+        if matches!(&ans, Ok(s) if !s.success.is_empty()) {
+            self.emit_version_change();
+            self.check_invariants();
+        }
         ans
     }
 
@@ -503,9 +708,13 @@ impl LoroDoc {
         &self,
         bytes: &[u8],
         origin: InternalString,
+        pipeline: Option<&ImportPipeline>,
     ) -> Result<ImportStatus, LoroError> {
         ensure_cov::notify_cov("loro_internal::import");
+        self.check_single_writer_import(bytes)?;
         let parsed = parse_header_and_body(bytes, true)?;
+        // The filters need the changes, so the snapshot can't reset the doc
+        let filtering = pipeline.is_some_and(ImportPipeline::has_filters);
         info!("Importing with mode={:?}", &parsed.mode);
         let result = match parsed.mode {
             EncodeMode::OutdatedRle => {
@@ -520,29 +729,29 @@ impl LoroDoc {
                 );
                 let _e = s.enter();
                 self.update_oplog_and_apply_delta_to_state_if_needed(
-                    |oplog| oplog.decode(parsed),
+                    |oplog| oplog.decode(parsed, pipeline),
                     origin,
                 )
             }
             EncodeMode::OutdatedSnapshot => {
-                if self.can_reset_with_snapshot() {
+                if !filtering && self.can_reset_with_snapshot() {
                     tracing::info!("Init by snapshot {}", self.peer_id());
                     decode_snapshot(self, parsed.mode, parsed.body)
                 } else {
                     self.update_oplog_and_apply_delta_to_state_if_needed(
-                        |oplog| oplog.decode(parsed),
+                        |oplog| oplog.decode(parsed, pipeline),
                         origin,
                     )
                 }
             }
             EncodeMode::FastSnapshot => {
-                if self.can_reset_with_snapshot() {
+                if !filtering && self.can_reset_with_snapshot() {
                     ensure_cov::notify_cov("loro_internal::import::snapshot");
                     tracing::info!("Init by fast snapshot {}", self.peer_id());
                     decode_snapshot(self, parsed.mode, parsed.body)
                 } else {
                     self.update_oplog_and_apply_delta_to_state_if_needed(
-                        |oplog| oplog.decode(parsed),
+                        |oplog| oplog.decode(parsed, pipeline),
                         origin,
                     )
 
@@ -553,7 +762,7 @@ impl LoroDoc {
                 }
             }
             EncodeMode::FastUpdates => self.update_oplog_and_apply_delta_to_state_if_needed(
-                |oplog| oplog.decode(parsed),
+                |oplog| oplog.decode(parsed, pipeline),
                 origin,
             ),
             EncodeMode::Auto => {
@@ -561,6 +770,12 @@ impl LoroDoc {
             }
         };
 
+        let window = self.config.import_event_coalescing_window();
+        if !self.state.lock().unwrap().should_emit_import_events(window) {
+            // Hold the events back so that they can be composed with the following imports
+            return result;
+        }
+
         self.emit_events();
         result
     }
@@ -596,6 +811,13 @@ impl LoroDoc {
                     },
                     diff_mode,
                 );
+                if let Ok(status) = &result {
+                    self.config.apply_log.record_overridden_map_ops(
+                        &oplog,
+                        &mut state,
+                        &status.success,
+                    );
+                }
             }
             result
         } else {
@@ -603,7 +825,7 @@ impl LoroDoc {
         }
     }
 
-    fn emit_events(&self) {
+    pub(crate) fn emit_events(&self) {
         // we should not hold the lock when emitting events
         let events = {
             let mut state = self.state.lock().unwrap();
@@ -624,6 +846,7 @@ impl LoroDoc {
         if self.is_shallow() {
             return Err(LoroEncodeError::ShallowSnapshotIncompatibleWithOldFormat);
         }
+        self.flush_pending_events();
         let (options, txn) = self.commit_then_stop();
         drop(txn);
         let ans = export_snapshot(self);
@@ -637,6 +860,10 @@ impl LoroDoc {
     #[tracing::instrument(skip_all)]
     pub fn import_json_updates<T: TryInto<JsonSchema>>(&self, json: T) -> LoroResult<ImportStatus> {
         let json = json.try_into().map_err(|_| LoroError::InvalidJsonSchema)?;
+        self.check_single_writer_peers(json.changes.iter().map(|c| match &json.peers {
+            Some(peers) => peers.get(c.id.peer as usize).copied().unwrap_or(c.id.peer),
+            None => c.id.peer,
+        }))?;
         let (options, txn) = self.commit_then_stop();
         let result = self.update_oplog_and_apply_delta_to_state_if_needed(
             |oplog| crate::encoding::json_schema::import_json(oplog, json),
@@ -645,6 +872,10 @@ impl LoroDoc {
         self.emit_events();
         drop(txn);
         self.renew_txn_if_auto_commit(options);
+        if matches!(&result, Ok(s) if !s.success.is_empty()) {
+            self.emit_version_change();
+            self.check_invariants();
+        }
         result
     }
 
@@ -853,12 +1084,14 @@ impl LoroDoc {
         id_span: IdSpan,
         container_remap: &mut FxHashMap<ContainerID, ContainerID>,
         post_transform_base: Option<&DiffBatch>,
+        exclude_container: &dyn Fn(&ContainerID) -> bool,
         before_diff: &mut dyn FnMut(&DiffBatch),
     ) -> LoroResult<CommitWhenDrop> {
         if !self.can_edit() {
             return Err(LoroError::EditWhenDetached);
         }
 
+        self.flush_pending_events();
         let (options, txn) = self.commit_then_stop();
         if !self
             .oplog()
@@ -879,7 +1112,7 @@ impl LoroDoc {
         };
 
         let spans = self.oplog.lock().unwrap().split_span_based_on_deps(id_span);
-        let diff = crate::undo::undo(
+        let mut diff = crate::undo::undo(
             spans,
             match post_transform_base {
                 Some(d) => Either::Right(d),
@@ -897,6 +1130,7 @@ impl LoroDoc {
             before_diff,
         );
 
+        diff.retain(|id| !exclude_container(id));
         // println!("\nundo_internal: diff: {:?}", diff);
         // println!("container remap: {:?}", container_remap);
 
@@ -956,6 +1190,7 @@ impl LoroDoc {
             }
         }
 
+        self.flush_pending_events();
         let (options, txn) = self.commit_then_stop();
         let was_detached = self.is_detached();
         let old_frontiers = self.state_frontiers();
@@ -987,6 +1222,99 @@ impl LoroDoc {
         Ok(DiffBatch::new(e))
     }
 
+    /// Get the deep values of the container `id` at each of the given versions.
+    ///
+    /// The state is moved from one version to the next, so only the history between the
+    /// adjacent versions is traversed, and the diff calculator is reused across them. Passing
+    /// the versions in their causal order is the fastest. The state is restored to the current
+    /// version afterwards, and no event is emitted.
+    ///
+    /// The value of a container that doesn't exist at a version is its empty value.
+    pub fn values_at(
+        &self,
+        versions: &[Frontiers],
+        id: &ContainerID,
+    ) -> LoroResult<Vec<LoroValue>> {
+        {
+            let oplog = self.oplog.lock().unwrap();
+            for id in versions.iter().flat_map(|f| f.iter()) {
+                if !oplog.dag.contains(id) {
+                    return Err(LoroError::FrontiersNotFound(id));
+                }
+            }
+        }
+        let handler =
+            self.get_handler(id.clone())
+                .ok_or_else(|| LoroError::ContainersNotFound {
+                    containers: Box::new(vec![id.clone()]),
+                })?;
+
+        self.flush_pending_events();
+        let (options, txn) = self.commit_then_stop();
+        let was_detached = self.is_detached();
+        let old_frontiers = self.state_frontiers();
+        let was_recording = {
+            let mut state = self.state.lock().unwrap();
+            let is_recording = state.is_recording();
+            state.stop_and_clear_recording();
+            is_recording
+        };
+        let ans = versions
+            .iter()
+            .map(|f| {
+                self._checkout_without_emitting(f, true, false)?;
+                Ok(handler.get_deep_value())
+            })
+            .collect();
+        self._checkout_without_emitting(&old_frontiers, false, false)
+            .unwrap();
+        drop(txn);
+        if !was_detached {
+            self.set_detached(false);
+            self.renew_txn_if_auto_commit(options);
+        }
+        if was_recording {
+            self.state.lock().unwrap().start_recording();
+        }
+        ans
+    }
+
+    /// Map a position in a list, movable list or text container at version `from` to the
+    /// corresponding position at version `to`.
+    ///
+    /// It's calculated by transforming the position by the diff of the container between the
+    /// two versions, so it can be used to migrate bookmarks, comments and annotations. If the
+    /// element at the position is deleted, it's mapped to the position where the deletion
+    /// happened. The position follows the element at it, so the elements inserted exactly at
+    /// the position are placed before it.
+    ///
+    /// Moves are not tracked. A move in a movable list is a deletion and an insertion in the
+    /// diff, so a moved element is mapped to the position it was removed from.
+    ///
+    /// The position of a text is in Unicode code points.
+    ///
+    /// Returns `None` if the container is not a list, movable list or text, or if any of
+    /// the versions is not included in the doc.
+    pub fn map_position(
+        &self,
+        container: &ContainerID,
+        pos: usize,
+        from: &Frontiers,
+        to: &Frontiers,
+    ) -> Option<usize> {
+        if !matches!(
+            container.container_type(),
+            ContainerType::List | ContainerType::MovableList | ContainerType::Text
+        ) {
+            return None;
+        }
+
+        Some(match self.diff_container(container, from, to).ok()? {
+            Some(d) => d.transform_cursor(pos, true),
+            None => pos,
+        })
+    }
+
     /// Apply a diff to the current state.
     #[inline(always)]
     pub fn apply_diff(&self, diff: DiffBatch) -> LoroResult<()> {
@@ -1091,22 +1419,74 @@ impl LoroDoc {
         self.oplog().lock().unwrap().cmp_frontiers(a, b)
     }
 
+    /// The version that includes the ops of both `a` and `b`. See [Frontiers::union].
+    #[inline]
+    pub fn frontiers_union(
+        &self,
+        a: &Frontiers,
+        b: &Frontiers,
+    ) -> Result<Frontiers, FrontiersNotIncluded> {
+        a.union(b, self.oplog().lock().unwrap().dag())
+    }
+
+    /// The version that only includes the ops included by both `a` and `b`.
+    /// See [Frontiers::intersection].
+    #[inline]
+    pub fn frontiers_intersection(
+        &self,
+        a: &Frontiers,
+        b: &Frontiers,
+    ) -> Result<Frontiers, FrontiersNotIncluded> {
+        a.intersection(b, self.oplog().lock().unwrap().dag())
+    }
+
+    /// Find the greatest common ancestor of `a` and `b`. See [Frontiers::common_ancestor].
+    #[inline]
+    pub fn find_common_ancestor(
+        &self,
+        a: &Frontiers,
+        b: &Frontiers,
+    ) -> Result<Frontiers, FrontiersNotIncluded> {
+        a.common_ancestor(b, self.oplog().lock().unwrap().dag())
+    }
+
     pub fn subscribe_root(&self, callback: Subscriber) -> Subscription {
+        self.subscribe_root_with_priority(SubscriberPriority::Normal, callback)
+    }
+
+    /// Subscribe to all the events in the lane `priority`.
+    pub fn subscribe_root_with_priority(
+        &self,
+        priority: SubscriberPriority,
+        callback: Subscriber,
+    ) -> Subscription {
         let mut state = self.state.lock().unwrap();
         if !state.is_recording() {
             state.start_recording();
         }
 
-        self.observer.subscribe_root(callback)
+        self.observer.subscribe_root(priority, callback)
     }
 
+    /// Subscribe to the events of the container and of all its descendants.
     pub fn subscribe(&self, container_id: &ContainerID, callback: Subscriber) -> Subscription {
+        self.subscribe_with_priority(container_id, SubscriberPriority::Normal, callback)
+    }
+
+    /// Subscribe to the events of the container and of all its descendants in the lane
+    /// `priority`.
+    pub fn subscribe_with_priority(
+        &self,
+        container_id: &ContainerID,
+        priority: SubscriberPriority,
+        callback: Subscriber,
+    ) -> Subscription {
         let mut state = self.state.lock().unwrap();
         if !state.is_recording() {
             state.start_recording();
         }
 
-        self.observer.subscribe(container_id, callback)
+        self.observer.subscribe(container_id, priority, callback)
     }
 
     pub fn subscribe_local_update(&self, callback: LocalUpdateCallback) -> Subscription {
@@ -1128,15 +1508,24 @@ impl LoroDoc {
 
         let mut success = VersionRange::default();
         let mut pending = VersionRange::default();
-        let mut meta_arr = bytes
+        let mut stats = ImportStats::default();
+        let mut snapshot = None;
+        // The metadata is read from the header, which a compressed blob doesn't expose
+        let blobs = bytes
+            .iter()
+            .map(|b| self.decompress_blob(b))
+            .collect::<LoroResult<Vec<_>>>()?;
+        let mut meta_arr = blobs
             .iter()
-            .map(|b| Ok((Self::decode_import_blob_meta(b, false)?, b)))
-            .collect::<LoroResult<Vec<(ImportBlobMetadata, &Vec<u8>)>>>()?;
+            .map(|b| Ok((Self::decode_import_blob_meta(b, false)?, &**b)))
+            .collect::<LoroResult<Vec<(ImportBlobMetadata, &[u8])>>>()?;
         meta_arr.sort_by(|a, b| {
             a.0.mode
                 .cmp(&b.0.mode)
                 .then(b.0.change_num.cmp(&a.0.change_num))
         });
+        // Import the blobs after the blobs they depend on, so the changes don't wait in pending
+        let meta_arr = sort_blobs_by_causal_order(meta_arr, &self.oplog_vv());
 
         let (options, txn) = self.commit_then_stop();
         drop(txn);
@@ -1171,6 +1560,9 @@ impl LoroDoc {
                             }
                         }
                     }
+
+                    stats.merge(s.stats);
+                    snapshot = snapshot.or(s.snapshot);
                 }
                 Err(e) => {
                     err = Some(e);
@@ -1180,6 +1572,7 @@ impl LoroDoc {
 
         let mut oplog = self.oplog.lock().unwrap();
         oplog.batch_importing = false;
+        stats.causal_gaps = oplog.pending_causal_gaps();
         drop(oplog);
 
         if !is_detached {
@@ -1187,6 +1580,10 @@ impl LoroDoc {
         }
 
         self.renew_txn_if_auto_commit(options);
+        if !success.is_empty() {
+            self.check_invariants();
+        }
+
         if let Some(err) = err {
             return Err(err);
         }
@@ -1198,6 +1595,8 @@ impl LoroDoc {
             } else {
                 Some(pending)
             },
+            stats,
+            snapshot,
         })
     }
 
@@ -1276,6 +1675,7 @@ impl LoroDoc {
         to_commit_then_renew: bool,
     ) -> Result<(), LoroError> {
         assert!(self.txn.is_locked());
+        self._revert_partial_checkout_without_emitting();
         let from_frontiers = self.state_frontiers();
         info!(
             "checkout from={:?} to={:?} cur_vv={:?}",
@@ -1325,8 +1725,14 @@ impl LoroDoc {
         };
 
         self.set_detached(true);
-        let (diff, diff_mode) =
-            calc.calc_diff_internal(&oplog, before, &state.frontiers, after, &frontiers, None);
+        let prefetched = self
+            .version_prefetch
+            .lock()
+            .unwrap()
+            .take_diff(&state.frontiers, &frontiers);
+        let (diff, diff_mode) = prefetched.unwrap_or_else(|| {
+            calc.calc_diff_internal(&oplog, before, &state.frontiers, after, &frontiers, None)
+        });
         state.apply_diff(
             InternalDocDiff {
                 origin: "checkout".into(),
@@ -1672,6 +2078,37 @@ impl LoroDoc {
         DocAnalysis::analyze(self)
     }
 
+    /// Get the estimated payload bytes of all the ops of the given container in the history.
+    ///
+    /// The inserted values and texts are counted by their sizes, so it can be used to find
+    /// out which containers are responsible for the size of the document.
+    /// The ops in the pending transaction are not included.
+    pub fn container_history_size(&self, id: &ContainerID) -> usize {
+        let Some(idx) = self.arena.id_to_idx(id) else {
+            return 0;
+        };
+
+        self.oplog
+            .lock()
+            .unwrap()
+            .container_history_sizes()
+            .get(&idx)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Get the estimated payload bytes of the ops of every container in the history.
+    ///
+    /// See [`LoroDoc::container_history_size`].
+    pub fn container_history_sizes(&self) -> FxHashMap<ContainerID, usize> {
+        let mut oplog = self.oplog.lock().unwrap();
+        oplog
+            .container_history_sizes()
+            .iter()
+            .map(|(idx, size)| (self.arena.idx_to_id(*idx).unwrap(), *size))
+            .collect()
+    }
+
     /// Get the path from the root to the container
     pub fn get_path_to_container(&self, id: &ContainerID) -> Option<Vec<(ContainerID, Index)>> {
         let mut state = self.state.lock().unwrap();
@@ -1681,13 +2118,22 @@ impl LoroDoc {
 
     #[instrument(skip(self))]
     pub fn export(&self, mode: ExportMode) -> Result<Vec<u8>, LoroEncodeError> {
+        self.flush_pending_events();
         let (options, txn) = self.commit_then_stop();
         let ans = match mode {
             ExportMode::Snapshot => export_fast_snapshot(self),
             ExportMode::Updates { from } => export_fast_updates(self, &from),
+            ExportMode::UpdatesSquashed {
+                from,
+                merge_interval,
+            } => export_fast_squashed_updates(self, &from, merge_interval),
             ExportMode::UpdatesInRange { spans } => {
                 export_fast_updates_in_range(&self.oplog.lock().unwrap(), spans.as_ref())
             }
+            ExportMode::UpdatesIm { from } => {
+                let oplog = self.oplog.lock().unwrap();
+                export_fast_updates_in_range(&oplog, &crate::im_version::spans_since(&oplog, &from))
+            }
             ExportMode::ShallowSnapshot(f) => export_shallow_snapshot(self, &f)?,
             ExportMode::StateOnly(f) => match f {
                 Some(f) => export_state_only_snapshot(self, &f)?,
@@ -1701,6 +2147,23 @@ impl LoroDoc {
         Ok(ans)
     }
 
+    /// Write the updates since `from` to `writer`, without buffering the whole update in
+    /// memory.
+    ///
+    /// The output can be imported like the output of `export(ExportMode::Updates { from })`.
+    pub fn export_updates_stream(
+        &self,
+        from: &VersionVector,
+        mut writer: impl std::io::Write,
+    ) -> std::io::Result<()> {
+        self.flush_pending_events();
+        let (options, txn) = self.commit_then_stop();
+        let ans = export_fast_updates_to(self, from, &mut writer);
+        drop(txn);
+        self.renew_txn_if_auto_commit(options);
+        ans
+    }
+
     /// The doc only contains the history since the shallow history start version vector.
     ///
     /// This is empty if the doc is not shallow.
@@ -1818,6 +2281,18 @@ impl LoroDoc {
         Ok(())
     }
 
+    /// Get the changes that are in `to` but not in `from`, in causal order.
+    ///
+    /// See [`OpLog::iter_changes_causal`].
+    pub fn iter_changes_causal(
+        &self,
+        from: &Frontiers,
+        to: &Frontiers,
+    ) -> Result<impl Iterator<Item = ChangeMeta>, FrontiersNotIncluded> {
+        self.commit_then_renew();
+        self.oplog().lock().unwrap().iter_changes_causal(from, to)
+    }
+
     pub fn get_changed_containers_in(&self, id: ID, len: usize) -> FxHashSet<ContainerID> {
         self.commit_then_renew();
         let mut set = FxHashSet::default();
@@ -1945,6 +2420,78 @@ impl Default for CommitOptions {
     }
 }
 
+/// Order the blobs so that each one comes after the blobs that contain its start frontiers,
+/// which are not included by `known`. Otherwise the given order is kept.
+///
+/// Overlapping blobs may depend on each other. Such cycles are broken by the given order.
+fn sort_blobs_by_causal_order<'a>(
+    blobs: Vec<(ImportBlobMetadata, &'a [u8])>,
+    known: &VersionVector,
+) -> Vec<(ImportBlobMetadata, &'a [u8])> {
+    // The counter ranges of each peer in the blobs
+    let mut ranges: FxHashMap<PeerID, Vec<(Counter, Counter, usize)>> = FxHashMap::default();
+    for (i, (meta, _)) in blobs.iter().enumerate() {
+        for (peer, &end) in meta.partial_end_vv.iter() {
+            let start = meta.partial_start_vv.get(peer).copied().unwrap_or(0);
+            if start < end {
+                ranges.entry(*peer).or_default().push((start, end, i));
+            }
+        }
+    }
+
+    let mut in_degree = vec![0; blobs.len()];
+    let mut succ = vec![Vec::new(); blobs.len()];
+    for (i, (meta, _)) in blobs.iter().enumerate() {
+        for id in meta.start_frontiers.iter() {
+            if known.includes_id(id) {
+                continue;
+            }
+
+            let Some(ranges) = ranges.get(&id.peer) else {
+                continue;
+            };
+            for &(start, end, j) in ranges {
+                if j != i && start <= id.counter && id.counter < end {
+                    succ[j].push(i);
+                    in_degree[i] += 1;
+                }
+            }
+        }
+    }
+
+    let mut order = Vec::with_capacity(blobs.len());
+    let mut visited = vec![false; blobs.len()];
+    let mut ready: BinaryHeap<Reverse<usize>> = (0..blobs.len())
+        .filter(|&i| in_degree[i] == 0)
+        .map(Reverse)
+        .collect();
+    while order.len() < blobs.len() {
+        let i = match ready.pop() {
+            Some(Reverse(i)) => i,
+            // A cycle: take the first blob left
+            None => (0..blobs.len()).find(|&i| !visited[i]).unwrap(),
+        };
+        if visited[i] {
+            continue;
+        }
+
+        visited[i] = true;
+        order.push(i);
+        for &j in succ[i].iter() {
+            in_degree[j] -= 1;
+            if in_degree[j] == 0 && !visited[j] {
+                ready.push(Reverse(j));
+            }
+        }
+    }
+
+    let mut blobs: Vec<_> = blobs.into_iter().map(Some).collect();
+    order
+        .into_iter()
+        .map(|i| blobs[i].take().unwrap())
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use loro_common::ID;
@@ -2002,6 +2549,113 @@ mod test {
         }
     }
 
+    #[test]
+    fn import_batch_in_causal_order() {
+        let a = LoroDoc::new_auto_commit();
+        a.set_peer_id(1).unwrap();
+        let text = a.get_text("text");
+        let mut updates = Vec::new();
+        for s in ["a", "b", "c"] {
+            let vv = a.oplog_vv();
+            text.insert(0, s).unwrap();
+            a.commit_then_renew();
+            updates.push(a.export(crate::loro::ExportMode::updates(&vv)).unwrap());
+        }
+        updates.reverse();
+
+        let b = LoroDoc::new_auto_commit();
+        let events = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let _sub = {
+            let events = events.clone();
+            b.subscribe_root(std::sync::Arc::new(move |e| {
+                assert_eq!(e.events.len(), 1);
+                events.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }))
+        };
+        let status = b.import_batch(&updates).unwrap();
+        assert!(status.pending.is_none());
+        assert_eq!(events.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(b.get_deep_value(), a.get_deep_value());
+    }
+
+    #[test]
+    fn reject_blobs_exceeding_decode_limits() {
+        use crate::configure::DecodeLimits;
+        use loro_common::{DecodeLimit, LoroError};
+
+        let a = LoroDoc::new_auto_commit();
+        a.set_peer_id(1).unwrap();
+        a.get_text("text").insert(0, "hello world").unwrap();
+        a.commit_then_renew();
+        let b = a.fork();
+        b.set_peer_id(2).unwrap();
+        b.get_text("text").delete(0, 6).unwrap();
+        b.commit_then_renew();
+        let updates = b.export(crate::loro::ExportMode::all_updates()).unwrap();
+        let snapshot = b.export(crate::loro::ExportMode::Snapshot).unwrap();
+
+        let cases = [
+            (
+                DecodeLimits {
+                    max_changes: 1,
+                    ..Default::default()
+                },
+                DecodeLimit::Changes,
+                1,
+                2,
+            ),
+            (
+                DecodeLimits {
+                    max_ops_per_change: 10,
+                    ..Default::default()
+                },
+                DecodeLimit::OpsPerChange,
+                10,
+                11,
+            ),
+            (
+                DecodeLimits {
+                    max_str_len: 5,
+                    ..Default::default()
+                },
+                DecodeLimit::StringLength,
+                5,
+                11,
+            ),
+            (
+                DecodeLimits {
+                    max_delete_span: 5,
+                    ..Default::default()
+                },
+                DecodeLimit::DeleteSpan,
+                5,
+                6,
+            ),
+        ];
+        for (limits, limit, max, actual) in cases {
+            for blob in [&updates, &snapshot] {
+                let doc = LoroDoc::new_auto_commit();
+                doc.set_decode_limits(limits);
+                assert_eq!(
+                    doc.import(blob).unwrap_err(),
+                    LoroError::DecodeLimitExceeded { limit, max, actual }
+                );
+                assert!(doc.oplog_vv().is_empty());
+            }
+        }
+
+        let doc = LoroDoc::new_auto_commit();
+        doc.set_decode_limits(DecodeLimits {
+            max_changes: 2,
+            max_ops_per_change: 11,
+            max_str_len: 11,
+            max_binary_len: 0,
+            max_delete_span: 6,
+        });
+        doc.import(&updates).unwrap();
+        assert_eq!(doc.get_deep_value(), b.get_deep_value());
+    }
+
     #[test]
     fn import_batch_err_181() {
         let a = LoroDoc::new_auto_commit();
@@ -2014,4 +2668,29 @@ mod test {
         drop(oplog);
         b.export_from(&Default::default());
     }
+
+    #[test]
+    fn import_batch_of_compressed_blobs() {
+        use crate::compression_dict::CompressionDictionary;
+
+        let a = LoroDoc::new_auto_commit();
+        a.set_peer_id(1).unwrap();
+        let text = a.get_text("text");
+        let mut updates = Vec::new();
+        for s in ["a", "b", "c"] {
+            let vv = a.oplog_vv();
+            text.insert(0, s).unwrap();
+            a.commit_then_renew();
+            updates.push(a.export(crate::loro::ExportMode::updates(&vv)).unwrap());
+        }
+
+        let dict = CompressionDictionary::train(&updates, 4096);
+        let b = LoroDoc::new_auto_commit();
+        b.add_compression_dictionary(dict.clone());
+        let compressed: Vec<Vec<u8>> = updates.iter().rev().map(|u| dict.compress(u)).collect();
+        assert!(compressed.iter().all(|u| u.starts_with(b"lozd")));
+        let status = b.import_batch(&compressed).unwrap();
+        assert!(status.pending.is_none());
+        assert_eq!(b.get_deep_value(), a.get_deep_value());
+    }
 }
@@ -0,0 +1,120 @@
+//! Housekeeping that the host can schedule when it's idle.
+//!
+//! The doc defers some work until it's needed: the new changes are compressed into blocks and
+//! the changed container states are encoded when the doc is exported, and the caches built by
+//! checkout are kept until they are freed. [`LoroDoc::run_maintenance`] does this work in small
+//! slices within a time budget, so it doesn't pile up on the next export, and
+//! [`LoroDoc::pending_maintenance`] tells how much of it is left. It also precomputes the
+//! checkouts of the versions hinted by [`LoroDoc::prefetch_version`].
+//!
+//! The arena is not collected, because the container indexes must stay valid for the
+//! lifetime of the doc, and the style anchors of the text are part of its history.
+use std::time::Duration;
+
+use crate::{change::get_sys_timestamp, LoroDoc};
+
+/// The number of change blocks compressed in one slice
+const CHANGE_BLOCKS_PER_SLICE: usize = 8;
+/// The number of container states encoded in one slice
+const STATES_PER_SLICE: usize = 32;
+
+/// The maintenance work that is pending on a doc, see [`LoroDoc::pending_maintenance`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaintenanceHint {
+    /// The blocks of changes that are not compressed yet
+    pub change_blocks: usize,
+    /// The container states that changed since they were last encoded
+    pub container_states: usize,
+    /// Whether there are checkout caches that can be freed. They are rebuilt when needed,
+    /// and kept while the doc is detached or there are prefetch hints, since it's likely to
+    /// check out again.
+    pub caches: bool,
+    /// The hinted versions whose checkouts are not precomputed from the current version
+    pub prefetch_versions: usize,
+}
+
+impl MaintenanceHint {
+    /// Whether there is no pending work
+    pub fn is_empty(&self) -> bool {
+        self.change_blocks == 0
+            && self.container_states == 0
+            && !self.caches
+            && self.prefetch_versions == 0
+    }
+}
+
+/// What [`LoroDoc::run_maintenance`] has done.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaintenanceReport {
+    pub compressed_change_blocks: usize,
+    pub encoded_container_states: usize,
+    pub prefetched_versions: usize,
+    pub freed_caches: bool,
+    /// The work left when the budget ran out
+    pub pending: MaintenanceHint,
+}
+
+impl LoroDoc {
+    /// Do the pending housekeeping in slices until it's done or the budget runs out.
+    ///
+    /// It compresses the new changes into blocks, encodes the changed container states,
+    /// precomputes the checkouts of the hinted versions, and frees the checkout caches, in
+    /// this order. A slice is only started within the budget,
+    /// so it may overrun the budget by one slice. The pending transaction is not committed.
+    pub fn run_maintenance(&self, budget: Duration) -> MaintenanceReport {
+        let start = get_sys_timestamp();
+        let budget = budget.as_secs_f64() * 1000.;
+        let has_time = || get_sys_timestamp() - start < budget;
+        let mut report = MaintenanceReport::default();
+        while has_time() {
+            let n = self
+                .oplog
+                .lock()
+                .unwrap()
+                .compact_change_store_partially(CHANGE_BLOCKS_PER_SLICE);
+            report.compressed_change_blocks += n;
+            if n < CHANGE_BLOCKS_PER_SLICE {
+                break;
+            }
+        }
+
+        while has_time() {
+            let n = self
+                .state
+                .lock()
+                .unwrap()
+                .encode_some_states(STATES_PER_SLICE);
+            report.encoded_container_states += n;
+            if n < STATES_PER_SLICE {
+                break;
+            }
+        }
+
+        while has_time() && self.prefetch_next_version() {
+            report.prefetched_versions += 1;
+        }
+
+        if has_time() && self.has_freeable_caches() {
+            self.free_history_cache();
+            self.free_diff_calculator();
+            report.freed_caches = true;
+        }
+
+        report.pending = self.pending_maintenance();
+        report
+    }
+
+    /// How much housekeeping [`LoroDoc::run_maintenance`] has left to do.
+    pub fn pending_maintenance(&self) -> MaintenanceHint {
+        MaintenanceHint {
+            change_blocks: self.oplog.lock().unwrap().unflushed_change_blocks(),
+            container_states: self.state.lock().unwrap().unencoded_state_count(),
+            caches: self.has_freeable_caches(),
+            prefetch_versions: self.pending_prefetch(),
+        }
+    }
+
+    fn has_freeable_caches(&self) -> bool {
+        !self.is_detached() && self.has_history_cache() && !self.has_prefetch_hints()
+    }
+}
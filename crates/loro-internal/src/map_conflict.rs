@@ -0,0 +1,234 @@
+//! Resolving the concurrent sets on a map key with a custom rule.
+//!
+//! A map keeps the value of the last writer of each key, so one of two concurrent sets is
+//! lost. Some domains need other rules, e.g. a map of max-wins counters. A
+//! [`MapConflictResolver`] is called with the concurrent candidates of a key, the sets that
+//! none of the others has seen, and it picks one of them or merges them into a new value. The
+//! candidates are folded in the order of their [`IdLp`]s, so every replica that has the same
+//! history computes the same value, whatever the order of the imports.
+use std::sync::Arc;
+
+use loro_common::{ContainerID, IdLp, InternalString, LoroValue};
+
+use crate::{
+    container::idx::ContainerIdx,
+    delta::MapValue,
+    history_cache::{ContainerHistoryCache, GroupedMapOpInfo},
+    LoroDoc, OpLog, VersionVector,
+};
+
+/// One of the concurrent sets on a map key. `value` is `None` if the key was deleted.
+#[derive(Debug, Clone, Copy)]
+pub struct MapConflictCandidate<'a> {
+    pub value: Option<&'a LoroValue>,
+    pub id: IdLp,
+}
+
+/// The result of a [`MapConflictResolver`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapConflictResolution {
+    First,
+    Second,
+    /// Replace both of the candidates with a new value
+    Merge(LoroValue),
+}
+
+type ResolverFn = dyn Fn(
+        &ContainerID,
+        &str,
+        MapConflictCandidate<'_>,
+        MapConflictCandidate<'_>,
+    ) -> MapConflictResolution
+    + Send
+    + Sync;
+
+/// The rule set by [`LoroDoc::set_map_conflict_resolver`]
+#[derive(Clone)]
+pub struct MapConflictResolver(Arc<ResolverFn>);
+
+impl MapConflictResolver {
+    /// `f` resolves the concurrent sets on the key of the map container. The first
+    /// candidate has the smaller [`IdLp`], and it may be the result of the candidates before
+    /// it.
+    ///
+    /// It must be deterministic, and every replica must use the same rule.
+    pub fn new(
+        f: impl Fn(
+                &ContainerID,
+                &str,
+                MapConflictCandidate<'_>,
+                MapConflictCandidate<'_>,
+            ) -> MapConflictResolution
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        Self(Arc::new(f))
+    }
+}
+
+impl std::fmt::Debug for MapConflictResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MapConflictResolver")
+    }
+}
+
+impl LoroDoc {
+    /// Set the rule to resolve the concurrent sets on the same key of a map, instead of
+    /// keeping the value of the last writer. The keys whose candidates include a child
+    /// container are still resolved by the last writer.
+    ///
+    /// It should be set before the maps are imported or checked out, and to the same resolver
+    /// on all the replicas. The local sets are never concurrent with the state they are
+    /// applied to, so they always win.
+    pub fn set_map_conflict_resolver(&self, resolver: Option<MapConflictResolver>) {
+        *self.config.map_conflict_resolver.write().unwrap() = resolver;
+    }
+}
+
+/// The value of `key` at `vv` resolved by `resolver`, or `None` if the key has never been set
+pub(crate) fn resolve_map_key(
+    cache: &mut ContainerHistoryCache,
+    oplog: &OpLog,
+    container_idx: ContainerIdx,
+    key: &InternalString,
+    vv: &VersionVector,
+    resolver: &MapConflictResolver,
+) -> Option<MapValue> {
+    let heads = cache
+        .get_checkout_index()
+        .map
+        .get_key_heads_at_vv(container_idx, key, vv, oplog);
+    let container = (heads.len() > 1).then(|| oplog.arena.idx_to_id(container_idx).unwrap());
+    let mut heads = heads.into_iter();
+    let GroupedMapOpInfo {
+        mut value,
+        lamport,
+        peer,
+    } = heads.next()?;
+    let mut id = IdLp::new(peer, lamport);
+    let is_container = |v: &Option<LoroValue>| matches!(v, Some(LoroValue::Container(_)));
+    for head in heads {
+        let head_id = IdLp::new(head.peer, head.lamport);
+        value = if is_container(&value) || is_container(&head.value) {
+            head.value
+        } else {
+            match (resolver.0)(
+                container.as_ref().unwrap(),
+                key,
+                MapConflictCandidate {
+                    value: value.as_ref(),
+                    id,
+                },
+                MapConflictCandidate {
+                    value: head.value.as_ref(),
+                    id: head_id,
+                },
+            ) {
+                MapConflictResolution::First => value,
+                MapConflictResolution::Second => head.value,
+                MapConflictResolution::Merge(v) => Some(v),
+            }
+        };
+        // The id of the last writer is kept, so that the later sets still override it
+        id = head_id;
+    }
+
+    Some(MapValue {
+        value,
+        lamp: id.lamport,
+        peer: id.peer,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{encoding::ExportMode, value::ToJson};
+
+    fn max_wins() -> MapConflictResolver {
+        MapConflictResolver::new(|_, _, a, b| match (a.value, b.value) {
+            (Some(LoroValue::I64(x)), Some(LoroValue::I64(y))) if x > y => {
+                MapConflictResolution::First
+            }
+            (Some(LoroValue::I64(_)), Some(LoroValue::I64(_))) => MapConflictResolution::Second,
+            (Some(LoroValue::List(x)), Some(LoroValue::List(y))) => MapConflictResolution::Merge(
+                x.iter().chain(y.iter()).cloned().collect::<Vec<_>>().into(),
+            ),
+            _ => MapConflictResolution::Second,
+        })
+    }
+
+    #[test]
+    fn resolve_concurrent_sets() {
+        let docs: Vec<LoroDoc> = (1..=3)
+            .map(|peer| {
+                let doc = LoroDoc::new_auto_commit();
+                doc.set_peer_id(peer).unwrap();
+                doc.set_map_conflict_resolver(Some(max_wins()));
+                doc
+            })
+            .collect();
+        // The greatest lamport is the smallest value
+        docs[0].get_map("map").insert("score", 10).unwrap();
+        docs[1].get_map("map").insert("score", 5).unwrap();
+        docs[1].get_map("map").insert("other", 1).unwrap();
+        docs[1].get_map("map").insert("score", 3).unwrap();
+        docs[2].get_map("map").insert("score", 7).unwrap();
+        docs[0]
+            .get_map("map")
+            .insert("tags", vec![LoroValue::from("a")])
+            .unwrap();
+        docs[2]
+            .get_map("map")
+            .insert("tags", vec![LoroValue::from("b")])
+            .unwrap();
+        for doc in docs.iter() {
+            doc.commit_then_renew();
+        }
+
+        let updates: Vec<_> = docs
+            .iter()
+            .map(|d| d.export(ExportMode::all_updates()).unwrap())
+            .collect();
+        for (doc, order) in docs.iter().zip([[1, 2], [2, 0], [0, 1]]) {
+            for i in order {
+                doc.import(&updates[i]).unwrap();
+            }
+        }
+
+        let expected = serde_json::json!({"map": {"score": 10, "other": 1, "tags": ["a", "b"]}});
+        for doc in docs.iter() {
+            assert_eq!(doc.get_deep_value().to_json_value(), expected);
+        }
+
+        // A set that has seen the others wins
+        docs[0].get_map("map").insert("score", 1).unwrap();
+        docs[0].commit_then_renew();
+        docs[1]
+            .import(
+                &docs[0]
+                    .export(ExportMode::updates(&docs[1].oplog_vv()))
+                    .unwrap(),
+            )
+            .unwrap();
+        assert_eq!(docs[1].get_map("map").get("score"), Some(1.into()));
+
+        // The checkouts resolve the same values
+        let f = docs[1].state_frontiers();
+        docs[1].checkout(&Default::default()).unwrap();
+        docs[1].checkout(&f).unwrap();
+        assert_eq!(docs[1].get_map("map").get("score"), Some(1.into()));
+        let new_doc = LoroDoc::new_auto_commit();
+        new_doc.set_map_conflict_resolver(Some(max_wins()));
+        new_doc
+            .import(&docs[2].export(ExportMode::Snapshot).unwrap())
+            .unwrap();
+        new_doc.checkout(&Default::default()).unwrap();
+        new_doc.checkout_to_latest();
+        assert_eq!(
+            new_doc.get_deep_value().to_json_value(),
+            docs[2].get_deep_value().to_json_value()
+        );
+    }
+}
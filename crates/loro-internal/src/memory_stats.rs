@@ -0,0 +1,151 @@
+//! Estimating the memory used by a doc.
+//!
+//! Apps plan their capacity and warn about documents that grow too large. The doc already
+//! tracks the sizes of its parts, so [`LoroDoc::memory_stats`] only collects them: it doesn't
+//! decode the container states that are still encoded, nor compress the history, and it's
+//! cheap enough to be called periodically. The sizes are estimates, not exact allocations.
+use fxhash::FxHashMap;
+use loro_common::{ContainerID, LoroValue};
+
+use crate::LoroDoc;
+
+/// The estimated memory usage of a doc, see [`LoroDoc::memory_stats`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// The estimated size of the state of every container in bytes. The states that are not
+    /// decoded yet count their encoded sizes.
+    pub containers: FxHashMap<ContainerID, usize>,
+    /// The size of the compressed blocks of the history in bytes
+    pub oplog_bytes: usize,
+    /// The blocks of the history that are not compressed yet, which are not counted by
+    /// `oplog_bytes`. [`LoroDoc::run_maintenance`] compresses them.
+    pub uncompressed_change_blocks: usize,
+    /// The bytes of the inserted text kept by the arena
+    pub arena_text_bytes: usize,
+    /// The number of the inserted values kept by the arena
+    pub arena_values: usize,
+    /// The number of the containers registered in the arena, including the deleted ones
+    pub arena_containers: usize,
+    pub changes: usize,
+    pub ops: usize,
+}
+
+impl MemoryStats {
+    /// The sum of the estimated sizes of the container states
+    pub fn state_bytes(&self) -> usize {
+        self.containers.values().sum()
+    }
+
+    /// Convert the stats into a map, whose `containers` map the container ids to their sizes
+    pub fn to_value(&self) -> LoroValue {
+        let containers: FxHashMap<String, LoroValue> = self
+            .containers
+            .iter()
+            .map(|(id, size)| (id.to_string(), (*size as i64).into()))
+            .collect();
+        let mut map: FxHashMap<String, LoroValue> = FxHashMap::default();
+        map.insert("containers".into(), containers.into());
+        for (key, value) in [
+            ("state_bytes", self.state_bytes()),
+            ("oplog_bytes", self.oplog_bytes),
+            (
+                "uncompressed_change_blocks",
+                self.uncompressed_change_blocks,
+            ),
+            ("arena_text_bytes", self.arena_text_bytes),
+            ("arena_values", self.arena_values),
+            ("arena_containers", self.arena_containers),
+            ("changes", self.changes),
+            ("ops", self.ops),
+        ] {
+            map.insert(key.into(), (value as i64).into());
+        }
+
+        map.into()
+    }
+}
+
+impl LoroDoc {
+    /// Estimate the memory used by the states of the containers, the history and the arena.
+    ///
+    /// The ops in the pending transaction are included in the states, but not in the history.
+    pub fn memory_stats(&self) -> MemoryStats {
+        let sizes = self
+            .app_state()
+            .lock()
+            .unwrap()
+            .store
+            .estimate_container_sizes();
+        let containers = sizes
+            .into_iter()
+            .map(|(idx, size)| (self.arena.idx_to_id(idx).unwrap(), size))
+            .collect();
+        let (oplog_bytes, uncompressed_change_blocks) = {
+            let oplog = self.oplog().lock().unwrap();
+            (
+                oplog.change_store_kv_size(),
+                oplog.unflushed_change_blocks(),
+            )
+        };
+
+        MemoryStats {
+            containers,
+            oplog_bytes,
+            uncompressed_change_blocks,
+            arena_text_bytes: self.arena.str_len_bytes(),
+            arena_values: self.arena.values_len(),
+            arena_containers: self.arena.with_idx_to_id(|ids| ids.len()),
+            changes: self.len_changes(),
+            ops: self.len_ops(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoding::ExportMode;
+
+    #[test]
+    fn memory_stats_of_doc() {
+        let doc = LoroDoc::new_auto_commit();
+        let text = doc.get_text("text");
+        text.insert(0, "hello world").unwrap();
+        let map = doc.get_map("map");
+        map.insert("a", 1).unwrap();
+        doc.commit_then_renew();
+
+        let stats = doc.memory_stats();
+        assert!(stats.containers[&text.id()] > 0);
+        assert!(stats.containers.contains_key(&map.id()));
+        assert_eq!(stats.ops, 12);
+        assert_eq!(stats.changes, 1);
+        assert!(stats.arena_text_bytes >= "hello world".len());
+        assert_eq!(stats.arena_containers, 2);
+
+        doc.run_maintenance(std::time::Duration::from_secs(10));
+        let compressed = doc.memory_stats();
+        assert_eq!(compressed.uncompressed_change_blocks, 0);
+        assert!(compressed.oplog_bytes > 0);
+
+        // The states of a new doc are only decoded when they are read
+        let new_doc = LoroDoc::new_auto_commit();
+        new_doc
+            .import(&doc.export(ExportMode::Snapshot).unwrap())
+            .unwrap();
+        let stats = new_doc.memory_stats();
+        assert!(stats.containers[&text.id()] > 0);
+        assert_eq!(stats.ops, 12);
+        let value = stats.to_value();
+        let value = value.as_map().unwrap();
+        assert_eq!(value["ops"], LoroValue::I64(12));
+        assert_eq!(
+            value["state_bytes"],
+            LoroValue::I64(stats.state_bytes() as i64)
+        );
+        assert!(value["containers"]
+            .as_map()
+            .unwrap()
+            .contains_key(&text.id().to_string()));
+    }
+}
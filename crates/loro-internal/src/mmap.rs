@@ -0,0 +1,32 @@
+//! Open the snapshot files by memory mapping them.
+//!
+//! The doc decodes the blocks of the history and the container states lazily from the mapped
+//! file, so the parts that are never accessed are never read into memory. It cuts the memory
+//! usage when opening many large docs, e.g. in a desktop app that lists the recent documents.
+use std::{fs::File, path::Path};
+
+use bytes::Bytes;
+use loro_common::{LoroError, LoroResult};
+
+use crate::LoroDoc;
+
+impl LoroDoc {
+    /// Open the snapshot file by memory mapping it, see [`LoroDoc::from_snapshot_bytes`].
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this process or any other, while the
+    /// doc or any value decoded lazily from it is alive. Otherwise it's undefined behavior.
+    /// Write the new snapshots to another file and rename it over the old one instead.
+    pub unsafe fn open_mmap(path: impl AsRef<Path>) -> LoroResult<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| {
+            LoroError::ArgErr(format!("Cannot open {}: {}", path.display(), e).into_boxed_str())
+        })?;
+        // SAFETY: the caller guarantees that the file is not modified while it's mapped
+        let mmap = unsafe { memmap2::Mmap::map(&file) }.map_err(|e| {
+            LoroError::ArgErr(format!("Cannot map {}: {}", path.display(), e).into_boxed_str())
+        })?;
+        Self::from_snapshot_bytes(Bytes::from_owner(mmap))
+    }
+}
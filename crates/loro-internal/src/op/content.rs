@@ -14,6 +14,7 @@ use crate::{
         tree::tree_op::TreeOp,
     },
     encoding::OwnedValue,
+    op::ListSlice,
 };
 
 #[derive(EnumAsInner, Debug, Clone)]
@@ -174,6 +175,55 @@ impl RawOpContent<'_> {
             },
         }
     }
+
+    /// The name of the first value in the op whose type can't be decoded by the older
+    /// versions of Loro. See [`crate::LoroDoc::set_extended_value_types`].
+    pub(crate) fn extended_value_type(&self) -> Option<&'static str> {
+        match self {
+            Self::Map(MapSet {
+                value: Some(value), ..
+            }) => extended_value_type(value),
+            Self::List(ListOp::Insert {
+                slice: ListSlice::RawData(values),
+                ..
+            }) => values.iter().find_map(extended_value_type),
+            Self::List(ListOp::Set { value, .. } | ListOp::StyleStart { value, .. }) => {
+                extended_value_type(value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Parse the tagged objects in the values of the op as the extended value types, see
+    /// [`LoroValue::parse_tags`]
+    pub(crate) fn parse_tags(&mut self) {
+        match self {
+            Self::Map(MapSet {
+                value: Some(value), ..
+            })
+            | Self::List(ListOp::Set { value, .. } | ListOp::StyleStart { value, .. }) => {
+                *value = std::mem::take(value).parse_tags();
+            }
+            Self::List(ListOp::Insert {
+                slice: ListSlice::RawData(values),
+                ..
+            }) => {
+                *values = values.iter().cloned().map(LoroValue::parse_tags).collect();
+            }
+            _ => {}
+        }
+    }
+}
+
+pub(crate) fn extended_value_type(value: &LoroValue) -> Option<&'static str> {
+    match value {
+        LoroValue::ContainerRef(_) => Some("ContainerRef"),
+        LoroValue::Timestamp(_) => Some("Timestamp"),
+        LoroValue::Decimal(_) => Some("Decimal"),
+        LoroValue::List(list) => list.iter().find_map(extended_value_type),
+        LoroValue::Map(map) => map.values().find_map(extended_value_type),
+        _ => None,
+    }
 }
 
 impl HasLength for RawOpContent<'_> {
@@ -0,0 +1,372 @@
+//! Explaining where an op landed after a merge.
+//!
+//! "Why did this edit land there" is answered by the causal relationship of the op with the
+//! concurrent ops on the same container, and by how the container resolved them.
+//! [`LoroDoc::explain`] collects both for a single op, so they can be read without knowing
+//! the internals of the containers.
+use std::fmt::Display;
+
+use loro_common::{ContainerID, Counter, IdLp, IdSpan, Lamport, PeerID, ID};
+use rle::HasLength;
+use serde_json::json;
+
+use crate::{
+    arena::SharedArena,
+    container::{list::list_op::InnerListOp, tree::tree_op::TreeOp},
+    cursor::{Cursor, Side},
+    op::{FutureInnerContent, InnerContent, Op},
+    state::State,
+    version::Frontiers,
+    LoroDoc,
+};
+
+/// How an op was ordered and resolved, see [`LoroDoc::explain`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpExplanation {
+    /// The explained atom
+    pub id: ID,
+    pub lamport: Lamport,
+    /// The ops the atom depends on directly
+    pub deps: Frontiers,
+    /// The whole op that contains the atom
+    pub op: IdSpan,
+    pub container: ContainerID,
+    /// A readable description of the op, e.g. `insert "abc" at 3`
+    pub content: String,
+    /// The ops on the same container that are concurrent with the atom, i.e. neither of them
+    /// happened before the other
+    pub concurrent: Vec<IdSpan>,
+    pub outcome: OpOutcome,
+}
+
+/// How the container resolved an op, in the current state of the doc
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpOutcome {
+    /// The atom inserted an element, which is now at the event index `pos`. If the element
+    /// is `deleted`, `pos` is where it was deleted from.
+    Inserted { pos: usize, deleted: bool },
+    /// The op wrote a map entry, the value or the position of a movable list element, or the
+    /// parent of a tree node, which is resolved by the last writer. `winner` is the op whose
+    /// write is in the current state, if there is any.
+    LastWriterWins { won: bool, winner: Option<IdLp> },
+    /// There is nothing to resolve, e.g. for deletions and styles
+    Other,
+}
+
+impl OpExplanation {
+    pub fn to_json(&self) -> String {
+        let outcome = match self.outcome {
+            OpOutcome::Inserted { pos, deleted } => {
+                json!({"type": "inserted", "pos": pos, "deleted": deleted})
+            }
+            OpOutcome::LastWriterWins { won, winner } => json!({
+                "type": "lastWriterWins",
+                "won": won,
+                "winner": winner.map(|w| w.to_string()),
+            }),
+            OpOutcome::Other => json!({"type": "other"}),
+        };
+        let value = json!({
+            "id": self.id.to_string(),
+            "lamport": self.lamport,
+            "deps": self.deps.iter().map(|id| id.to_string()).collect::<Vec<_>>(),
+            "op": span_to_string(&self.op),
+            "container": self.container.to_string(),
+            "content": self.content,
+            "concurrent": self.concurrent.iter().map(span_to_string).collect::<Vec<_>>(),
+            "outcome": outcome,
+        });
+        serde_json::to_string_pretty(&value).unwrap()
+    }
+}
+
+impl Display for OpExplanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} (lamport {}) in {}",
+            self.id, self.lamport, self.container
+        )?;
+        writeln!(f, "  op: {} {}", span_to_string(&self.op), self.content)?;
+        let deps: Vec<String> = self.deps.iter().map(|id| id.to_string()).collect();
+        writeln!(f, "  deps: [{}]", deps.join(", "))?;
+        let concurrent: Vec<String> = self.concurrent.iter().map(span_to_string).collect();
+        writeln!(f, "  concurrent with: [{}]", concurrent.join(", "))?;
+        match self.outcome {
+            OpOutcome::Inserted {
+                pos,
+                deleted: false,
+            } => write!(f, "  outcome: inserted, now at {pos}"),
+            OpOutcome::Inserted { pos, deleted: true } => {
+                write!(f, "  outcome: inserted, deleted at {pos}")
+            }
+            OpOutcome::LastWriterWins { won: true, .. } => write!(f, "  outcome: won"),
+            OpOutcome::LastWriterWins {
+                won: false,
+                winner: Some(winner),
+            } => write!(f, "  outcome: lost to {winner}"),
+            OpOutcome::LastWriterWins { winner: None, .. } => {
+                write!(f, "  outcome: lost, the target no longer exists")
+            }
+            OpOutcome::Other => write!(f, "  outcome: -"),
+        }
+    }
+}
+
+impl LoroDoc {
+    /// Explain the op atom `id`: its deps and lamport, the concurrent ops on the same
+    /// container, and how the container resolved it in the current state.
+    ///
+    /// It scans the whole history, so it's meant for debugging. Returns `None` if the op is
+    /// not in the doc.
+    pub fn explain(&self, id: ID) -> Option<OpExplanation> {
+        let (mut ans, op, op_idlp) = {
+            let oplog = self.oplog().lock().unwrap();
+            let change = oplog.get_change_at(id)?;
+            let op = (*change.get_op_with_counter(id.counter)?).clone();
+            let op_lamport = change.lamport + (op.counter - change.id.counter) as Lamport;
+            let lamport = change.lamport + (id.counter - change.id.counter) as Lamport;
+            let deps = oplog.get_deps_of(id)?;
+            let container = oplog.arena.idx_to_id(op.container)?;
+            drop(change);
+
+            // Collect the candidates first: computing the versions may load the changes,
+            // which cannot be done while visiting them
+            let mut candidates: Vec<(PeerID, Lamport, Frontiers, Vec<(Counter, Counter)>)> =
+                Vec::new();
+            oplog.change_store().visit_all_changes(&mut |c| {
+                if c.id.peer == id.peer {
+                    return;
+                }
+
+                let spans: Vec<_> = c
+                    .ops()
+                    .iter()
+                    .filter(|o| o.container == op.container)
+                    .map(|o| (o.counter, o.counter + o.atom_len() as Counter))
+                    .collect();
+                if !spans.is_empty() {
+                    candidates.push((c.id.peer, c.lamport, c.deps.clone(), spans));
+                }
+            });
+
+            let past = oplog.dag.frontiers_to_vv(&deps).unwrap_or_default();
+            let mut concurrent = Vec::new();
+            for (peer, c_lamport, c_deps, spans) in candidates {
+                // The changes after the atom depend on it, so their lamports are greater
+                if c_lamport > lamport
+                    && oplog
+                        .dag
+                        .frontiers_to_vv(&c_deps)
+                        .is_some_and(|vv| vv.includes_id(id))
+                {
+                    continue;
+                }
+
+                let known = past.get(&peer).copied().unwrap_or(0);
+                for (start, end) in spans {
+                    if start.max(known) < end {
+                        concurrent.push(IdSpan::new(peer, start.max(known), end));
+                    }
+                }
+            }
+
+            concurrent.sort_by_key(|s| (s.peer, s.counter.start));
+            let ans = OpExplanation {
+                id,
+                lamport,
+                deps,
+                op: IdSpan::new(id.peer, op.counter, op.counter + op.atom_len() as Counter),
+                container,
+                content: describe(&op, &oplog.arena),
+                concurrent,
+                outcome: OpOutcome::Other,
+            };
+            (ans, op, IdLp::new(id.peer, op_lamport))
+        };
+
+        ans.outcome = self.resolve_outcome(&ans, &op, op_idlp);
+        Some(ans)
+    }
+
+    fn resolve_outcome(&self, ans: &OpExplanation, op: &Op, op_idlp: IdLp) -> OpOutcome {
+        let lww = |winner: Option<IdLp>| OpOutcome::LastWriterWins {
+            won: winner == Some(op_idlp),
+            winner,
+        };
+        match &op.content {
+            InnerContent::List(InnerListOp::Insert { .. } | InnerListOp::InsertText { .. }) => {
+                let cursor = Cursor::new(Some(ans.id), ans.container.clone(), Side::Middle, 0);
+                match self.query_pos(&cursor) {
+                    Ok(r) => OpOutcome::Inserted {
+                        pos: r.current.pos,
+                        deleted: r.update.is_some(),
+                    },
+                    Err(_) => OpOutcome::Other,
+                }
+            }
+            InnerContent::List(InnerListOp::Move { elem_id, .. }) => {
+                let winner = self.with_container_state(op, |s| match s {
+                    State::MovableListState(l) => {
+                        l.elements().get(&elem_id.compact()).map(|e| e.pos)
+                    }
+                    _ => None,
+                });
+                lww(winner)
+            }
+            InnerContent::List(InnerListOp::Set { elem_id, .. }) => {
+                let winner = self.with_container_state(op, |s| match s {
+                    State::MovableListState(l) => {
+                        l.elements().get(&elem_id.compact()).map(|e| e.value_id)
+                    }
+                    _ => None,
+                });
+                lww(winner)
+            }
+            InnerContent::Map(set) => {
+                let winner = self.with_container_state(op, |s| match s {
+                    State::MapState(m) => m.get_winner(&set.key),
+                    _ => None,
+                });
+                lww(winner)
+            }
+            InnerContent::Tree(t) => {
+                let winner = self.with_container_state(op, |s| match s {
+                    State::TreeState(tree) => tree.get_last_move_id(&t.target()),
+                    _ => None,
+                });
+                let winner = winner.map(|id| self.oplog().lock().unwrap().id_to_idlp(id));
+                lww(winner)
+            }
+            _ => OpOutcome::Other,
+        }
+    }
+
+    fn with_container_state<R>(&self, op: &Op, f: impl FnOnce(&State) -> Option<R>) -> Option<R> {
+        let mut state = self.app_state().lock().unwrap();
+        state.with_state(op.container, f)
+    }
+}
+
+fn span_to_string(span: &IdSpan) -> String {
+    format!("{}..{}@{}", span.counter.start, span.counter.end, span.peer)
+}
+
+/// A readable description of the content of `op`
+pub(crate) fn describe(op: &Op, arena: &SharedArena) -> String {
+    match &op.content {
+        InnerContent::List(l) => match l {
+            InnerListOp::Insert { slice, pos } if slice.is_unknown() => {
+                format!("insert {} elements at {pos}", op.atom_len())
+            }
+            InnerListOp::Insert { slice, pos } => {
+                format!("insert {:?} at {pos}", arena.get_values(slice.to_range()))
+            }
+            InnerListOp::InsertText { slice, pos, .. } => {
+                format!(
+                    "insert {:?} at {pos}",
+                    std::str::from_utf8(slice).unwrap_or("")
+                )
+            }
+            InnerListOp::Delete(d) => format!("delete {} at {}", d.span.len(), d.span.start()),
+            InnerListOp::Move { from, to, elem_id } => {
+                format!("move the element {elem_id} from {from} to {to}")
+            }
+            InnerListOp::Set { elem_id, value } => {
+                format!("set the element {elem_id} to {value:?}")
+            }
+            InnerListOp::StyleStart {
+                start,
+                end,
+                key,
+                value,
+                ..
+            } => format!("mark {start}..{end} with {key} = {value:?}"),
+            InnerListOp::StyleEnd => "end the style".to_string(),
+        },
+        InnerContent::Map(set) => match &set.value {
+            Some(v) => format!("set {:?} to {v:?}", set.key.as_str()),
+            None => format!("delete {:?}", set.key.as_str()),
+        },
+        InnerContent::Tree(t) => match &**t {
+            TreeOp::Create { target, parent, .. } | TreeOp::Move { target, parent, .. } => {
+                let verb = if matches!(&**t, TreeOp::Create { .. }) {
+                    "create"
+                } else {
+                    "move"
+                };
+                match parent {
+                    Some(p) => format!("{verb} {target} under {p}"),
+                    None => format!("{verb} {target} under the root"),
+                }
+            }
+            TreeOp::Delete { target } => format!("delete {target}"),
+        },
+        InnerContent::Future(f) => match f {
+            #[cfg(feature = "counter")]
+            FutureInnerContent::Counter(n) => format!("increment by {n}"),
+            FutureInnerContent::Unknown { .. } => "an op of an unknown type".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::HandlerTrait;
+
+    #[test]
+    fn explain_concurrent_edits() {
+        let a = LoroDoc::new_auto_commit();
+        a.set_peer_id(1).unwrap();
+        a.get_text("text").insert(0, "ac").unwrap();
+        a.commit_then_renew();
+        let b = a.fork();
+        b.set_peer_id(2).unwrap();
+
+        a.get_text("text").insert(1, "b").unwrap();
+        a.get_map("map").insert("k", 1).unwrap();
+        a.commit_then_renew();
+        b.get_text("text").insert(1, "B").unwrap();
+        b.get_map("map").insert("k", 2).unwrap();
+        b.commit_then_renew();
+        a.import(&b.export(crate::loro::ExportMode::all_updates()).unwrap())
+            .unwrap();
+        let text = a.get_text("text").to_string();
+
+        let e = a.explain(ID::new(1, 2)).unwrap();
+        assert_eq!(e.deps, Frontiers::from_id(ID::new(1, 1)));
+        assert_eq!(e.container, a.get_text("text").id());
+        assert_eq!(e.content, "insert \"b\" at 1");
+        assert_eq!(e.concurrent, vec![IdSpan::new(2, 0, 1)]);
+        assert_eq!(
+            e.outcome,
+            OpOutcome::Inserted {
+                pos: text.find('b').unwrap(),
+                deleted: false
+            }
+        );
+
+        let lost = a.explain(ID::new(1, 3)).unwrap();
+        let won = a.explain(ID::new(2, 1)).unwrap();
+        assert_eq!(lost.concurrent, vec![IdSpan::new(2, 1, 2)]);
+        assert_eq!(
+            lost.outcome,
+            OpOutcome::LastWriterWins {
+                won: false,
+                winner: Some(IdLp::new(2, won.lamport))
+            }
+        );
+        assert!(matches!(
+            won.outcome,
+            OpOutcome::LastWriterWins { won: true, .. }
+        ));
+        assert!(lost.to_string().contains("lost to"));
+        let json: serde_json::Value = serde_json::from_str(&lost.to_json()).unwrap();
+        assert_eq!(json["concurrent"][0], "1..2@2");
+
+        // The first op happened before all the others
+        assert!(a.explain(ID::new(1, 0)).unwrap().concurrent.is_empty());
+        assert!(a.explain(ID::new(3, 0)).is_none());
+    }
+}
@@ -1,13 +1,16 @@
 mod change_store;
+mod container_type_conflict;
+mod history_size;
 pub(crate) mod loro_dag;
 mod pending_changes;
 
 use bytes::Bytes;
+use fxhash::FxHashMap;
 use std::borrow::Cow;
 use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::rc::Rc;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use tracing::{debug, trace, trace_span};
 
 use self::change_store::iter::MergedChangeIter;
@@ -15,6 +18,7 @@ use self::pending_changes::PendingChanges;
 use super::arena::SharedArena;
 use crate::change::{get_sys_timestamp, Change, Lamport, Timestamp};
 use crate::configure::Configure;
+use crate::container::idx::ContainerIdx;
 use crate::container::list::list_op;
 use crate::dag::{Dag, DagUtils};
 use crate::diff_calc::DiffMode;
@@ -22,17 +26,20 @@ use crate::encoding::{decode_oplog, encode_oplog, EncodeMode};
 use crate::encoding::{ImportStatus, ParsedHeaderAndBody};
 use crate::history_cache::ContainerHistoryCache;
 use crate::id::{Counter, PeerID, ID};
+use crate::import_pipeline::ImportPipeline;
+use crate::kv_store::KvStore;
 use crate::op::{FutureInnerContent, ListSlice, RawOpContent, RemoteOp, RichOp};
 use crate::span::{HasCounterSpan, HasLamportSpan};
 use crate::version::{Frontiers, ImVersionVector, VersionVector};
-use crate::LoroError;
+use crate::{ChangeMeta, LoroError};
 use change_store::BlockOpRef;
 use loro_common::{IdLp, IdSpan};
 use rle::{HasLength, RleVec, Sliceable};
 use smallvec::SmallVec;
 
-pub use self::loro_dag::{AppDag, AppDagNode, FrontiersNotIncluded};
+pub use self::loro_dag::{AppDag, AppDagNode, DagNodeInfo, FrontiersNotIncluded};
 pub use change_store::{BlockChangeRef, ChangeStore};
+pub use container_type_conflict::ContainerTypeConflict;
 
 /// [OpLog] store all the ops i.e. the history.
 /// It allows multiple [AppState] to attach to it.
@@ -54,6 +61,14 @@ pub struct OpLog {
     /// If so the Dag's frontiers won't be updated until the batch is finished.
     pub(crate) batch_importing: bool,
     pub(crate) configure: Configure,
+    /// The estimated payload bytes of the ops of each container.
+    /// It's None until it's queried for the first time.
+    container_history_sizes: Option<FxHashMap<ContainerIdx, usize>>,
+    /// The container type conflicts quarantined by the imports
+    container_type_conflicts: Vec<ContainerTypeConflict>,
+    /// The freeze found in the history, see [`crate::freeze`].
+    /// It's None until it's queried for the first time.
+    pub(crate) frozen: Option<Option<crate::freeze::Freeze>>,
 }
 
 impl std::fmt::Debug for OpLog {
@@ -68,9 +83,27 @@ impl std::fmt::Debug for OpLog {
 impl OpLog {
     #[inline]
     pub(crate) fn new() -> Self {
+        Self::new_with_kv(None)
+    }
+
+    /// Create an oplog whose encoded change blocks are kept in `kv`, or in memory if it's
+    /// `None`
+    pub(crate) fn new_with_kv(kv: Option<Arc<Mutex<dyn KvStore>>>) -> Self {
         let arena = SharedArena::new();
         let cfg = Configure::default();
-        let change_store = ChangeStore::new_mem(&arena, cfg.merge_interval_in_s.clone());
+        let change_store = match kv {
+            Some(kv) => ChangeStore::new_with_kv(
+                &arena,
+                cfg.merge_interval_in_s.clone(),
+                cfg.delete_merge.clone(),
+                kv,
+            ),
+            None => ChangeStore::new_mem(
+                &arena,
+                cfg.merge_interval_in_s.clone(),
+                cfg.delete_merge.clone(),
+            ),
+        };
         Self {
             history_cache: Mutex::new(ContainerHistoryCache::new(change_store.clone(), None)),
             dag: AppDag::new(change_store.clone()),
@@ -79,6 +112,9 @@ impl OpLog {
             pending_changes: Default::default(),
             batch_importing: false,
             configure: cfg,
+            container_history_sizes: None,
+            container_type_conflicts: Vec::new(),
+            frozen: None,
         }
     }
 
@@ -137,6 +173,8 @@ impl OpLog {
             .unwrap()
             .insert_by_new_change(&change, true, true);
         self.register_container_and_parent_link(&change);
+        self.record_history_size(&change);
+        self.record_freeze(&change);
         self.change_store.insert_change(change, true, from_local);
     }
 
@@ -349,7 +387,36 @@ impl OpLog {
     #[inline(always)]
     pub(crate) fn export_blocks_from<W: std::io::Write>(&self, vv: &VersionVector, w: &mut W) {
         self.change_store
-            .export_blocks_from(vv, self.shallow_since_vv(), self.vv(), w)
+            .export_blocks_from(vv, self.shallow_since_vv(), self.vv(), None, w)
+    }
+
+    /// Encode the blocks since `vv` one by one. See [ChangeStore::for_each_block_from].
+    #[inline(always)]
+    pub(crate) fn for_each_block_from(
+        &self,
+        vv: &VersionVector,
+        f: &mut dyn FnMut(&[u8]) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        self.change_store
+            .for_each_block_from(vv, self.shallow_since_vv(), self.vv(), f)
+    }
+
+    /// Export the blocks since `vv`, where the consecutive changes from the same peer
+    /// within `merge_interval` (in seconds) are squashed into one change.
+    #[inline(always)]
+    pub(crate) fn export_squashed_blocks_from<W: std::io::Write>(
+        &self,
+        vv: &VersionVector,
+        merge_interval: i64,
+        w: &mut W,
+    ) {
+        self.change_store.export_blocks_from(
+            vv,
+            self.shallow_since_vv(),
+            self.vv(),
+            Some(merge_interval),
+            w,
+        )
     }
 
     #[inline(always)]
@@ -366,8 +433,12 @@ impl OpLog {
     }
 
     #[inline(always)]
-    pub(crate) fn decode(&mut self, data: ParsedHeaderAndBody) -> Result<ImportStatus, LoroError> {
-        decode_oplog(self, data)
+    pub(crate) fn decode(
+        &mut self,
+        data: ParsedHeaderAndBody,
+        pipeline: Option<&ImportPipeline>,
+    ) -> Result<ImportStatus, LoroError> {
+        decode_oplog(self, data, pipeline)
     }
 
     /// iterates over all changes between LCA(common ancestors) to the merged version of (`from` and `to`) causally
@@ -484,12 +555,54 @@ impl OpLog {
         from: &VersionVector,
         to: &VersionVector,
     ) -> impl Iterator<Item = BlockChangeRef> + 'a {
-        let spans: Vec<_> = from.diff_iter(to).1.collect();
+        let mut spans: Vec<_> = from.diff_iter(to).1.collect();
+        // The order of the peers in the version vectors depends on the hasher
+        spans.sort_unstable_by_key(|span| span.peer);
         spans
             .into_iter()
             .flat_map(move |span| self.change_store.iter_changes(span))
     }
 
+    /// Iterate over the changes that are in `to` but not in `from`, in causal order.
+    ///
+    /// A change is never yielded before the changes it depends on. The concurrent changes are
+    /// ordered by their lamports and then by their peers, so the order is the same on every
+    /// replica. The changes that are only partially in the range are trimmed to it.
+    pub fn iter_changes_causal(
+        &self,
+        from: &Frontiers,
+        to: &Frontiers,
+    ) -> Result<impl Iterator<Item = ChangeMeta>, FrontiersNotIncluded> {
+        let from_vv = self.dag.frontiers_to_vv(from).ok_or(FrontiersNotIncluded)?;
+        let to_vv = self.dag.frontiers_to_vv(to).ok_or(FrontiersNotIncluded)?;
+        let mut ans = Vec::new();
+        for (peer, span) in from_vv.diff(&to_vv).forward {
+            for change in self
+                .change_store
+                .iter_changes(IdSpan::new(peer, span.start, span.end))
+            {
+                let start = change.id.counter.max(span.start);
+                let end = change.ctr_end().min(span.end);
+                if start >= end {
+                    continue;
+                }
+
+                let mut meta = ChangeMeta::from_change(&change);
+                if start > change.id.counter {
+                    meta.id = ID::new(peer, start);
+                    meta.lamport += (start - change.id.counter) as Lamport;
+                    meta.deps = Frontiers::from_id(ID::new(peer, start - 1));
+                }
+                meta.len = (end - start) as usize;
+                ans.push(meta);
+            }
+        }
+
+        // A change has a greater lamport than all the changes it depends on
+        ans.sort_unstable_by_key(|c| (c.lamport, c.id.peer));
+        Ok(ans.into_iter())
+    }
+
     pub(crate) fn iter_changes_causally_rev<'a>(
         &'a self,
         from: &VersionVector,
@@ -567,6 +680,18 @@ impl OpLog {
             .flush_and_compact(self.dag.vv(), self.dag.frontiers());
     }
 
+    /// Like [`OpLog::compact_change_store`], but flushes at most `max_blocks` blocks.
+    /// Returns the number of flushed blocks.
+    pub(crate) fn compact_change_store_partially(&mut self, max_blocks: usize) -> usize {
+        self.change_store
+            .flush_blocks(max_blocks, self.dag.vv(), self.dag.frontiers())
+    }
+
+    #[inline]
+    pub(crate) fn unflushed_change_blocks(&self) -> usize {
+        self.change_store.unflushed_block_count()
+    }
+
     #[inline]
     pub fn change_store_kv_size(&self) -> usize {
         self.change_store.kv_size()
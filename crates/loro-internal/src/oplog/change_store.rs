@@ -1,8 +1,12 @@
-use self::block_encode::{decode_block, decode_header, encode_block, ChangesBlockHeader};
+use self::block_encode::{
+    check_block_limits, decode_block, decode_header, encode_block, ChangesBlockHeader,
+};
 use super::{loro_dag::AppDagNodeInner, AppDagNode};
 use crate::{
+    apply_log::ApplyLog,
     arena::SharedArena,
     change::Change,
+    configure::{DecodeLimits, DeleteMergeConfig},
     estimated_size::EstimatedSize,
     kv_store::KvStore,
     op::Op,
@@ -14,8 +18,8 @@ use block_encode::decode_block_range;
 use bytes::Bytes;
 use itertools::Itertools;
 use loro_common::{
-    Counter, HasCounter, HasCounterSpan, HasId, HasIdSpan, HasLamportSpan, IdLp, IdSpan, Lamport,
-    LoroError, LoroResult, PeerID, ID,
+    Counter, DecodeLimit, HasCounter, HasCounterSpan, HasId, HasIdSpan, HasLamportSpan, IdLp,
+    IdSpan, Lamport, LoroError, LoroResult, PeerID, ID,
 };
 use loro_kv_store::{mem_store::MemKvConfig, MemKvStore};
 use once_cell::sync::OnceCell;
@@ -24,7 +28,7 @@ use std::{
     cmp::Ordering,
     collections::{BTreeMap, VecDeque},
     ops::{Bound, Deref},
-    sync::{atomic::AtomicI64, Arc, Mutex},
+    sync::{atomic::AtomicI64, Arc, Mutex, RwLock},
 };
 use tracing::{debug, info_span, trace, warn};
 
@@ -68,6 +72,7 @@ pub struct ChangeStore {
     /// The version vector of the external kv store.
     external_vv: Arc<Mutex<VersionVector>>,
     merge_interval: Arc<AtomicI64>,
+    delete_merge: Arc<RwLock<DeleteMergeConfig>>,
 }
 
 #[derive(Debug, Clone)]
@@ -111,8 +116,36 @@ pub const START_FRONTIERS_KEY: &[u8] = b"sf";
 pub const VV_KEY: &[u8] = b"vv";
 pub const FRONTIERS_KEY: &[u8] = b"fr";
 
+/// Read the version vector stored under `key`
+fn read_vv(kv: &dyn KvStore, key: &[u8]) -> Result<Option<VersionVector>, LoroError> {
+    match kv.get(key) {
+        Some(bytes) if !bytes.is_empty() => VersionVector::decode(&bytes).map(Some),
+        _ => Ok(None),
+    }
+}
+
 impl ChangeStore {
-    pub fn new_mem(a: &SharedArena, merge_interval: Arc<AtomicI64>) -> Self {
+    pub fn new_mem(
+        a: &SharedArena,
+        merge_interval: Arc<AtomicI64>,
+        delete_merge: Arc<RwLock<DeleteMergeConfig>>,
+    ) -> Self {
+        Self::new_with_kv(
+            a,
+            merge_interval,
+            delete_merge,
+            Arc::new(Mutex::new(MemKvStore::new(MemKvConfig::default()))),
+        )
+    }
+
+    /// Create a change store whose encoded blocks are kept in `kv`. If `kv` isn't empty, its
+    /// version should be loaded with [`ChangeStore::load_version`].
+    pub(crate) fn new_with_kv(
+        a: &SharedArena,
+        merge_interval: Arc<AtomicI64>,
+        delete_merge: Arc<RwLock<DeleteMergeConfig>>,
+        kv: Arc<Mutex<dyn KvStore>>,
+    ) -> Self {
         Self {
             inner: Arc::new(Mutex::new(ChangeStoreInner {
                 start_vv: ImVersionVector::new(),
@@ -121,15 +154,19 @@ impl ChangeStore {
             })),
             arena: a.clone(),
             external_vv: Arc::new(Mutex::new(VersionVector::new())),
-            external_kv: Arc::new(Mutex::new(MemKvStore::new(MemKvConfig::default()))),
-            // external_kv: Arc::new(Mutex::new(BTreeMap::default())),
+            external_kv: kv,
             merge_interval,
+            delete_merge,
         }
     }
 
     #[cfg(test)]
     fn new_for_test() -> Self {
-        Self::new_mem(&SharedArena::new(), Arc::new(AtomicI64::new(0)))
+        Self::new_mem(
+            &SharedArena::new(),
+            Arc::new(AtomicI64::new(0)),
+            Default::default(),
+        )
     }
 
     pub(super) fn encode_all(&self, vv: &VersionVector, frontiers: &Frontiers) -> Bytes {
@@ -146,7 +183,11 @@ impl ChangeStore {
         latest_vv: &VersionVector,
         latest_frontiers: &Frontiers,
     ) -> Bytes {
-        let new_store = Self::new_mem(&self.arena, self.merge_interval.clone());
+        let new_store = Self::new_mem(
+            &self.arena,
+            self.merge_interval.clone(),
+            self.delete_merge.clone(),
+        );
         for span in latest_vv.sub_iter(start_vv) {
             // PERF: this can be optimized by reusing the current encoded blocks
             // In the current method, it needs to parse and re-encode the blocks
@@ -175,7 +216,11 @@ impl ChangeStore {
     }
 
     pub(super) fn export_blocks_in_range<W: std::io::Write>(&self, spans: &[IdSpan], w: &mut W) {
-        let new_store = Self::new_mem(&self.arena, self.merge_interval.clone());
+        let new_store = Self::new_mem(
+            &self.arena,
+            self.merge_interval.clone(),
+            self.delete_merge.clone(),
+        );
         for span in spans {
             let mut span = *span;
             span.normalize_();
@@ -220,7 +265,7 @@ impl ChangeStore {
         arena: &SharedArena,
         self_vv: &VersionVector,
     ) -> Result<Vec<Change>, LoroError> {
-        let change_store = Self::new_mem(arena, Arc::new(AtomicI64::new(0)));
+        let change_store = Self::new_mem(arena, Arc::new(AtomicI64::new(0)), Default::default());
         let _ = change_store.import_all(bytes)?;
         let mut changes = Vec::new();
         change_store.visit_all_changes(&mut |c| {
@@ -243,12 +288,14 @@ impl ChangeStore {
         bytes: Bytes,
         arena: &SharedArena,
         self_vv: &VersionVector,
+        apply_log: &ApplyLog,
     ) -> LoroResult<Vec<Change>> {
         let mut ans = ChangesBlockBytes::new(bytes).parse(arena)?;
         if ans.is_empty() {
             return Ok(ans);
         }
 
+        apply_log.record_duplicates(&ans, self_vv);
         let start = self_vv.get(&ans[0].peer()).copied().unwrap_or(0);
         ans.retain_mut(|c| {
             if c.id.counter >= start {
@@ -264,6 +311,36 @@ impl ChangeStore {
         Ok(ans)
     }
 
+    /// Check the bytes of a block against `limits` before they are decoded by
+    /// [`ChangeStore::decode_block_bytes`]. Returns the number of the changes in the block.
+    pub(crate) fn check_block_limits(bytes: &[u8], limits: &DecodeLimits) -> LoroResult<usize> {
+        check_block_limits(bytes, limits)
+    }
+
+    /// Check all the blocks of the encoded change store of a snapshot against `limits`
+    pub(crate) fn check_snapshot_limits(bytes: Bytes, limits: &DecodeLimits) -> LoroResult<()> {
+        let mut kv = MemKvStore::new(MemKvConfig::default());
+        kv.import_all(bytes)
+            .map_err(|e| LoroError::DecodeError(e.into_boxed_str()))?;
+        let mut n_changes: usize = 0;
+        for (key, block) in kv.scan(Bound::Unbounded, Bound::Unbounded) {
+            if key.len() != 12 {
+                continue;
+            }
+
+            n_changes = n_changes.saturating_add(check_block_limits(&block, limits)?);
+            limits.check(DecodeLimit::Changes, n_changes)?;
+        }
+
+        Ok(())
+    }
+
+    /// Encode the consecutive changes of one peer into the bytes of a block, which can be
+    /// decoded by [`ChangeStore::decode_block_bytes`]
+    pub(crate) fn encode_block_bytes(changes: &[Change], arena: &SharedArena) -> Vec<u8> {
+        encode_block(changes, arena)
+    }
+
     pub fn get_dag_nodes_that_contains(&self, id: ID) -> Option<Vec<AppDagNode>> {
         let block = self.get_block_that_contains(id)?;
         Some(block.content.iter_dag_nodes())
@@ -287,6 +364,37 @@ impl ChangeStore {
         }
     }
 
+    /// Parse all the blocks one by one. After each block, `f` is called with the number of the
+    /// parsed ops and the number of all the ops in the store.
+    ///
+    /// No lock is held when `f` is called.
+    pub(crate) fn parse_all_blocks(&self, f: &mut dyn FnMut(usize, usize)) -> LoroResult<()> {
+        self.ensure_block_loaded_in_range(Bound::Unbounded, Bound::Unbounded);
+        let blocks: Vec<(ID, usize)> = self
+            .inner
+            .lock()
+            .unwrap()
+            .mem_parsed_kv
+            .iter()
+            .map(|(id, block)| {
+                let (start, end) = block.counter_range;
+                (*id, (end - start) as usize)
+            })
+            .collect();
+        let total = blocks.iter().map(|(_, len)| len).sum();
+        let mut loaded = 0;
+        for (id, len) in blocks {
+            if let Some(block) = self.inner.lock().unwrap().mem_parsed_kv.get_mut(&id) {
+                block.ensure_changes(&self.arena)?;
+            }
+
+            loaded += len;
+            f(loaded, total);
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn iter_blocks(&self, id_span: IdSpan) -> Vec<(Arc<ChangesBlock>, usize, usize)> {
         if id_span.counter.start == id_span.counter.end {
             return vec![];
@@ -462,6 +570,7 @@ impl ChangeStore {
         &self,
         arena: SharedArena,
         merge_interval: Arc<AtomicI64>,
+        delete_merge: Arc<RwLock<DeleteMergeConfig>>,
         vv: &VersionVector,
         frontiers: &Frontiers,
     ) -> Self {
@@ -477,6 +586,7 @@ impl ChangeStore {
             external_vv: Arc::new(Mutex::new(self.external_vv.lock().unwrap().clone())),
             external_kv: self.external_kv.lock().unwrap().clone_store(),
             merge_interval,
+            delete_merge,
         }
     }
 
@@ -494,9 +604,16 @@ impl ChangeStore {
         start_vv: &VersionVector,
         shallow_since_vv: &ImVersionVector,
         latest_vv: &VersionVector,
+        squash_interval: Option<i64>,
         w: &mut W,
     ) {
-        let new_store = Self::new_mem(&self.arena, self.merge_interval.clone());
+        // When squashing, the changes are inserted as local changes so that the consecutive
+        // changes from the same peer within the interval are merged into one change
+        let (merge_interval, is_local) = match squash_interval {
+            Some(interval) => (Arc::new(AtomicI64::new(interval)), true),
+            None => (self.merge_interval.clone(), false),
+        };
+        let new_store = Self::new_mem(&self.arena, merge_interval, self.delete_merge.clone());
         for mut span in latest_vv.sub_iter(start_vv) {
             let counter_lower_bound = shallow_since_vv.get(&span.peer).copied().unwrap_or(0);
             span.counter.start = span.counter.start.max(counter_lower_bound);
@@ -517,7 +634,7 @@ impl ChangeStore {
 
                 assert_ne!(start, end);
                 let ch = c.slice(start, end);
-                new_store.insert_change(ch, false, false);
+                new_store.insert_change(ch, false, is_local);
             }
         }
 
@@ -525,13 +642,66 @@ impl ChangeStore {
         encode_blocks_in_store(new_store, arena, w);
     }
 
+    /// Encode the changes between `start_vv` and `latest_vv` into blocks, and pass the blocks
+    /// to `f` one by one.
+    ///
+    /// Unlike [ChangeStore::export_blocks_from], only the changes of the current block are kept
+    /// in memory.
+    pub(crate) fn for_each_block_from(
+        &self,
+        start_vv: &VersionVector,
+        shallow_since_vv: &ImVersionVector,
+        latest_vv: &VersionVector,
+        f: &mut dyn FnMut(&[u8]) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        for mut span in latest_vv.sub_iter(start_vv) {
+            let counter_lower_bound = shallow_since_vv.get(&span.peer).copied().unwrap_or(0);
+            span.counter.start = span.counter.start.max(counter_lower_bound);
+            span.counter.end = span.counter.end.max(counter_lower_bound);
+            if span.counter.start >= span.counter.end {
+                continue;
+            }
+
+            let mut block: Vec<Change> = Vec::new();
+            let mut block_size = 0;
+            for c in self.iter_changes(span) {
+                let start = ((span.counter.start - c.id.counter).max(0) as usize).min(c.atom_len());
+                let end = ((span.counter.end - c.id.counter).max(0) as usize).min(c.atom_len());
+                if start == end {
+                    continue;
+                }
+
+                let ch = c.slice(start, end);
+                let size = ch.estimate_storage_size();
+                if !block.is_empty() && block_size + size > MAX_BLOCK_SIZE {
+                    f(&encode_block(&block, &self.arena))?;
+                    block.clear();
+                    block_size = 0;
+                }
+
+                block_size += size;
+                block.push(ch);
+            }
+
+            if !block.is_empty() {
+                f(&encode_block(&block, &self.arena))?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn fork_changes_up_to(
         &self,
         start_vv: &ImVersionVector,
         frontiers: &Frontiers,
         vv: &VersionVector,
     ) -> Bytes {
-        let new_store = Self::new_mem(&self.arena, self.merge_interval.clone());
+        let new_store = Self::new_mem(
+            &self.arena,
+            self.merge_interval.clone(),
+            self.delete_merge.clone(),
+        );
         for mut span in vv.sub_iter_im(start_vv) {
             let counter_lower_bound = start_vv.get(&span.peer).copied().unwrap_or(0);
             span.counter.start = span.counter.start.max(counter_lower_bound);
@@ -590,14 +760,16 @@ mod mut_external_kv {
             kv_store
                 .import_all(bytes)
                 .map_err(|e| LoroError::DecodeError(e.into_boxed_str()))?;
-            let vv_bytes = kv_store.get(VV_KEY).unwrap_or_default();
-            let vv = VersionVector::decode(&vv_bytes).unwrap();
-            let start_vv_bytes = kv_store.get(START_VV_KEY).unwrap_or_default();
-            let start_vv = if start_vv_bytes.is_empty() {
-                Default::default()
-            } else {
-                VersionVector::decode(&start_vv_bytes).unwrap()
-            };
+            drop(kv_store);
+            self.load_version()
+        }
+
+        /// Read the version of the history in the kv store
+        pub(crate) fn load_version(&self) -> Result<BatchDecodeInfo, LoroError> {
+            #[allow(unused_mut)]
+            let mut kv_store = self.external_kv.lock().unwrap();
+            let vv = read_vv(&*kv_store, VV_KEY)?.unwrap_or_default();
+            let start_vv = read_vv(&*kv_store, START_VV_KEY)?.unwrap_or_default();
 
             #[cfg(test)]
             {
@@ -656,11 +828,29 @@ mod mut_external_kv {
 
         /// Flush the cached change to kv_store
         pub(crate) fn flush_and_compact(&self, vv: &VersionVector, frontiers: &Frontiers) {
+            self.flush_blocks(usize::MAX, vv, frontiers);
+        }
+
+        /// Flush at most `max_blocks` of the cached blocks to kv_store, in the order of their ids.
+        ///
+        /// The version of the kv_store is only updated once all the blocks are flushed.
+        /// Returns the number of flushed blocks.
+        pub(crate) fn flush_blocks(
+            &self,
+            max_blocks: usize,
+            vv: &VersionVector,
+            frontiers: &Frontiers,
+        ) -> usize {
             let mut inner = self.inner.lock().unwrap();
             let mut store = self.external_kv.lock().unwrap();
             let mut external_vv = self.external_vv.lock().unwrap();
+            let mut flushed = 0;
             for (id, block) in inner.mem_parsed_kv.iter_mut() {
                 if !block.flushed {
+                    if flushed == max_blocks {
+                        return flushed;
+                    }
+
                     let id_bytes = id.to_bytes();
                     let counter_start = external_vv.get(&id.peer).copied().unwrap_or(0);
                     assert!(
@@ -677,6 +867,7 @@ mod mut_external_kv {
                     let bytes = block.to_bytes(&self.arena);
                     store.set(&id_bytes, bytes.bytes);
                     Arc::make_mut(block).flushed = true;
+                    flushed += 1;
                 }
             }
 
@@ -692,6 +883,22 @@ mod mut_external_kv {
             let frontiers_bytes = frontiers.encode();
             store.set(VV_KEY, vv_bytes.into());
             store.set(FRONTIERS_KEY, frontiers_bytes.into());
+            flushed
+        }
+
+        /// The number of the cached blocks that are not flushed to kv_store yet
+        pub(crate) fn unflushed_block_count(&self) -> usize {
+            let inner = self.inner.lock().unwrap();
+            inner.mem_parsed_kv.values().filter(|b| !b.flushed).count()
+        }
+
+        /// Drop the cached blocks that are flushed to kv_store. They are loaded from it again
+        /// when they are accessed. Returns the number of dropped blocks.
+        pub(crate) fn evict_flushed_blocks(&self) -> usize {
+            let mut inner = self.inner.lock().unwrap();
+            let len = inner.mem_parsed_kv.len();
+            inner.mem_parsed_kv.retain(|_, block| !block.flushed);
+            len - inner.mem_parsed_kv.len()
         }
     }
 }
@@ -742,6 +949,7 @@ mod mut_inner_kv {
                         } else {
                             0
                         },
+                        &self.delete_merge.read().unwrap(),
                         &self.arena,
                     ) {
                         Ok(_) => {
@@ -971,7 +1179,9 @@ mod mut_inner_kv {
                     );
                     new_change.ops.push(op);
                 } else {
-                    new_change.ops.push(op);
+                    // The ops are from the same change, so the adjacent deletions were kept
+                    // apart on purpose
+                    DeleteMergeConfig::KEYSTROKE.push_op(&mut new_change.ops, op);
                 }
             }
 
@@ -1263,6 +1473,7 @@ impl ChangesBlock {
         change: Change,
         new_change_size: usize,
         merge_interval: i64,
+        delete_merge: &DeleteMergeConfig,
         a: &SharedArena,
     ) -> Result<(), Change> {
         if self.counter_range.1 != change.id.counter {
@@ -1282,11 +1493,13 @@ impl ChangesBlock {
                 if last.can_merge_right(&change, merge_interval)
                     && (!is_full
                         || (change.ops.len() == 1
-                            && last.ops.last().unwrap().is_mergable(&change.ops[0], &()))) =>
+                            && last.ops.last().unwrap().is_mergable(&change.ops[0], &())
+                            && delete_merge
+                                .can_merge(last.ops.last().unwrap(), &change.ops[0]))) =>
             {
                 for op in change.ops.into_iter() {
                     let size = op.estimate_storage_size();
-                    if !last.ops.push(op) {
+                    if !delete_merge.push_op(&mut last.ops, op) {
                         this.estimated_size += size;
                     }
                 }
@@ -1605,6 +1818,23 @@ mod test {
         test_encode_decode(doc);
     }
 
+    #[test]
+    fn vv_keys_keep_the_legacy_encoding() {
+        let doc = LoroDoc::new_auto_commit();
+        doc.get_text("t").insert(0, "hello").unwrap();
+        doc.commit_then_renew();
+        let oplog = doc.oplog().lock().unwrap();
+        let bytes = oplog
+            .change_store
+            .encode_all(oplog.vv(), oplog.dag.frontiers());
+        let store = ChangeStore::new_for_test();
+        let _ = store.import_all(bytes).unwrap();
+        let kv = store.external_kv.lock().unwrap();
+        // The older versions decode the version vector from this key
+        let vv = VersionVector::decode(&kv.get(VV_KEY).unwrap()).unwrap();
+        assert_eq!(&vv, oplog.vv());
+    }
+
     #[test]
     fn test_synced_doc() -> LoroResult<()> {
         let doc_a = LoroDoc::new_auto_commit();
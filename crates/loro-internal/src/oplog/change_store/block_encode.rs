@@ -71,8 +71,8 @@ use std::sync::Arc;
 
 use fractional_index::FractionalIndex;
 use loro_common::{
-    ContainerID, Counter, HasCounterSpan, HasLamportSpan, InternalString, Lamport, LoroError,
-    LoroResult, PeerID, TreeID, ID,
+    ContainerID, Counter, DecodeLimit, HasCounterSpan, HasLamportSpan, InternalString, Lamport,
+    LoroError, LoroResult, PeerID, TreeID, ID,
 };
 use once_cell::sync::OnceCell;
 use rle::HasLength;
@@ -83,6 +83,7 @@ use tracing::info;
 use super::block_meta_encode::decode_changes_header;
 use crate::arena::SharedArena;
 use crate::change::{Change, Timestamp};
+use crate::configure::{DecodeLimits, DeleteMergeConfig};
 use crate::container::tree::tree_op;
 use crate::encoding::arena::{ContainerArena, PositionArena};
 use crate::encoding::value_register::ValueRegister;
@@ -301,6 +302,27 @@ fn decode_keys(mut bytes: &[u8]) -> Vec<InternalString> {
     keys
 }
 
+/// Like [`decode_keys`], but fails on the corrupted keys and the keys longer than the limit
+fn decode_keys_with_limits(
+    mut bytes: &[u8],
+    limits: &DecodeLimits,
+) -> LoroResult<Vec<InternalString>> {
+    let mut keys = Vec::new();
+    while !bytes.is_empty() {
+        let len = leb128::read::unsigned(&mut bytes)
+            .map_err(|_| LoroError::DecodeDataCorruptionError)? as usize;
+        limits.check(DecodeLimit::StringLength, len)?;
+        let key = bytes
+            .get(..len)
+            .and_then(|b| std::str::from_utf8(b).ok())
+            .ok_or(LoroError::DecodeDataCorruptionError)?;
+        keys.push(key.into());
+        bytes = &bytes[len..];
+    }
+
+    Ok(keys)
+}
+
 struct Registers {
     peer_register: ValueRegister<PeerID>,
     key_register: ValueRegister<loro_common::InternalString>,
@@ -527,6 +549,76 @@ pub fn decode_cids(
     Ok(header)
 }
 
+/// Check the block against `limits` before it's decoded. Returns the number of its changes.
+///
+/// Only the lengths stored in the block are read, so nothing proportional to them is
+/// allocated before they are checked.
+pub(crate) fn check_block_limits(m_bytes: &[u8], limits: &DecodeLimits) -> LoroResult<usize> {
+    let doc: EncodedBlock = postcard::from_bytes(m_bytes).map_err(|e| {
+        LoroError::DecodeError(format!("Decode block error {}", e).into_boxed_str())
+    })?;
+    let n_changes = doc.n_changes as usize;
+    limits.check(DecodeLimit::Changes, n_changes)?;
+    if n_changes == 0 {
+        return Err(LoroError::DecodeDataCorruptionError);
+    }
+
+    // The header starts with the peers, followed by the atom lengths of the changes except
+    // the last one
+    let mut header: &[u8] = &doc.header;
+    let peer_num = leb128::read::unsigned(&mut header)
+        .map_err(|_| LoroError::DecodeDataCorruptionError)? as usize;
+    let peer_bytes = header
+        .get(..peer_num.saturating_mul(8))
+        .ok_or(LoroError::DecodeDataCorruptionError)?;
+    let peers: Vec<PeerID> = peer_bytes
+        .chunks_exact(8)
+        .map(|b| PeerID::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+    header = &header[peer_bytes.len()..];
+    let mut last_len = doc.counter_len as usize;
+    for _ in 1..n_changes {
+        let len = leb128::read::unsigned(&mut header)
+            .map_err(|_| LoroError::DecodeDataCorruptionError)? as usize;
+        limits.check(DecodeLimit::OpsPerChange, len)?;
+        last_len = last_len
+            .checked_sub(len)
+            .ok_or(LoroError::DecodeDataCorruptionError)?;
+    }
+    limits.check(DecodeLimit::OpsPerChange, last_len)?;
+
+    if !doc.delete_start_ids.is_empty() {
+        let del_iter =
+            serde_columnar::iter_from_bytes::<EncodedDeleteStartIds>(&doc.delete_start_ids)?;
+        for del in del_iter.delete_start_ids {
+            limits.check(DecodeLimit::DeleteSpan, del?.span_len())?;
+        }
+    }
+
+    let keys = decode_keys_with_limits(&doc.keys, limits)?;
+    let decode_arena = ValueDecodeArena {
+        peers: &peers,
+        keys: &keys,
+    };
+    let mut value_reader = ValueReader::new(&doc.values).with_limits(limits);
+    let peer = peers.first().copied().unwrap_or_default();
+    let mut counter = doc.counter_start as Counter;
+    for op in serde_columnar::iter_from_bytes::<EncodedOps>(&doc.ops)?.ops {
+        let EncodedOp {
+            value_type, len, ..
+        } = op?;
+        Value::decode(
+            ValueKind::from_u8(value_type),
+            &mut value_reader,
+            &decode_arena,
+            ID::new(peer, counter),
+        )?;
+        counter = counter.wrapping_add(len as Counter);
+    }
+
+    Ok(n_changes)
+}
+
 // MARK: decode_block
 pub fn decode_block(
     m_bytes: &[u8],
@@ -654,7 +746,9 @@ pub fn decode_block(
             content,
         };
 
-        changes[change_index].ops.push(op);
+        // The deletions were merged before they were encoded, so the adjacent ones were kept
+        // apart by the `DeleteMergeConfig` of the author
+        DeleteMergeConfig::KEYSTROKE.push_op(&mut changes[change_index].ops, op);
         counter += len as Counter;
         if counter >= header.counters[change_index + 1] {
             change_index += 1;
@@ -0,0 +1,131 @@
+use fxhash::{FxHashMap, FxHashSet};
+use loro_common::{ContainerID, ContainerType, LoroError, LoroResult, ID};
+
+use crate::{change::Change, configure::ContainerTypeConflictPolicy, container::idx::ContainerIdx};
+
+use super::OpLog;
+
+/// An imported container that shares its creation id with a known container of another type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerTypeConflict {
+    /// The container that was known by the doc first
+    pub existing: ContainerID,
+    /// The conflicting container targeted by the imported ops
+    pub incoming: ContainerID,
+    /// The id of the first imported change that has ops on the incoming container
+    pub change: ID,
+}
+
+impl OpLog {
+    /// Find the containers targeted by the imported changes whose types conflict with
+    /// the known containers, and handle them by the configured policy.
+    ///
+    /// It must be called after the changes are decoded and before they are imported.
+    pub(crate) fn check_container_type_conflicts(&mut self, changes: &[Change]) -> LoroResult<()> {
+        let mut visited = FxHashSet::default();
+        let mut conflicts = Vec::new();
+        for change in changes {
+            for op in change.ops().iter() {
+                if !visited.insert(op.container) {
+                    continue;
+                }
+
+                let id = self.arena.idx_to_id(op.container).unwrap();
+                let ContainerID::Normal { peer, counter, .. } = id else {
+                    continue;
+                };
+
+                let existing = ContainerType::ALL_TYPES.into_iter().find_map(|t| {
+                    if t == id.container_type() {
+                        return None;
+                    }
+
+                    let other = ContainerID::new_normal(ID::new(peer, counter), t);
+                    let other_idx = self.arena.id_to_idx(&other)?;
+                    // The one registered first wins
+                    (other_idx.to_index() < op.container.to_index()).then_some(other)
+                });
+                if let Some(existing) = existing {
+                    conflicts.push((
+                        op.container,
+                        ContainerTypeConflict {
+                            existing,
+                            incoming: id,
+                            change: change.id,
+                        },
+                    ));
+                }
+            }
+        }
+
+        self.handle_container_type_conflicts(conflicts)
+    }
+
+    /// Find the containers in the state of an imported snapshot that share their creation
+    /// ids with each other but have different types, and handle them by the configured policy.
+    ///
+    /// It must be called after the state of the snapshot is decoded, which registers all
+    /// of its containers in the arena. The snapshots without a state are not checked,
+    /// because their containers are only known after the ops are applied.
+    pub(crate) fn check_snapshot_container_type_conflicts(&mut self) -> LoroResult<()> {
+        let mut known: FxHashMap<ID, ContainerID> = FxHashMap::default();
+        let mut conflicts = Vec::new();
+        for (i, id) in self.arena.export_containers().into_iter().enumerate() {
+            let ContainerID::Normal { peer, counter, .. } = id else {
+                continue;
+            };
+
+            let creation = ID::new(peer, counter);
+            match known.get(&creation) {
+                Some(existing) => {
+                    let change = self.lookup_change(creation).map_or(creation, |c| c.id);
+                    conflicts.push((
+                        ContainerIdx::from_index_and_type(i as u32, id.container_type()),
+                        ContainerTypeConflict {
+                            existing: existing.clone(),
+                            incoming: id,
+                            change,
+                        },
+                    ));
+                }
+                None => {
+                    known.insert(creation, id);
+                }
+            }
+        }
+
+        self.handle_container_type_conflicts(conflicts)
+    }
+
+    /// The arena is only changed if the conflicts are quarantined, where the conflicting
+    /// containers are detached from their parents.
+    fn handle_container_type_conflicts(
+        &mut self,
+        conflicts: Vec<(ContainerIdx, ContainerTypeConflict)>,
+    ) -> LoroResult<()> {
+        if conflicts.is_empty() {
+            return Ok(());
+        }
+
+        for (_, c) in conflicts.iter() {
+            tracing::warn!(existing=?c.existing, incoming=?c.incoming, "Container type conflict");
+        }
+
+        match self.configure.container_type_conflict_policy() {
+            ContainerTypeConflictPolicy::Reject => Err(LoroError::ContainerTypeConflict {
+                containers: Box::new(conflicts.into_iter().map(|(_, c)| c.incoming).collect()),
+            }),
+            ContainerTypeConflictPolicy::Quarantine => {
+                for (idx, c) in conflicts {
+                    self.arena.set_parent(idx, None);
+                    self.container_type_conflicts.push(c);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn container_type_conflicts(&self) -> &[ContainerTypeConflict] {
+        &self.container_type_conflicts
+    }
+}
@@ -0,0 +1,81 @@
+use fxhash::FxHashMap;
+use loro_common::ContainerType;
+
+use crate::{
+    arena::SharedArena,
+    change::Change,
+    container::{idx::ContainerIdx, list::list_op::InnerListOp, map::MapSet},
+    estimated_size::EstimatedSize,
+    op::{InnerContent, Op},
+};
+
+use super::OpLog;
+
+impl OpLog {
+    /// Get the estimated payload bytes of the ops of each container in the history.
+    ///
+    /// It's calculated by visiting all the changes the first time it's called, and it's
+    /// updated incrementally when new changes are inserted after that.
+    pub(crate) fn container_history_sizes(&mut self) -> &FxHashMap<ContainerIdx, usize> {
+        if self.container_history_sizes.is_none() {
+            let mut sizes = FxHashMap::default();
+            let arena = &self.arena;
+            self.change_store.visit_all_changes(&mut |c| {
+                record_change_size(&mut sizes, c, arena);
+            });
+            self.container_history_sizes = Some(sizes);
+        }
+
+        self.container_history_sizes.as_ref().unwrap()
+    }
+
+    /// Drop the sizes so that they will be recalculated, used when the changes are
+    /// imported without going through `insert_new_change`.
+    pub(crate) fn reset_container_history_sizes(&mut self) {
+        self.container_history_sizes = None;
+    }
+
+    pub(super) fn record_history_size(&mut self, change: &Change) {
+        if let Some(sizes) = self.container_history_sizes.as_mut() {
+            record_change_size(sizes, change, &self.arena);
+        }
+    }
+}
+
+fn record_change_size(
+    sizes: &mut FxHashMap<ContainerIdx, usize>,
+    change: &Change,
+    arena: &SharedArena,
+) {
+    for op in change.ops().iter() {
+        *sizes.entry(op.container).or_default() += op_payload_size(op, arena);
+    }
+}
+
+/// The estimated encoded size of the op, where the inserted values are counted by their sizes
+fn op_payload_size(op: &Op, arena: &SharedArena) -> usize {
+    match &op.content {
+        InnerContent::List(InnerListOp::Insert { slice, .. })
+            if !slice.is_unknown()
+                && matches!(
+                    op.container.get_type(),
+                    ContainerType::List | ContainerType::MovableList
+                ) =>
+        {
+            arena
+                .get_values(slice.to_range())
+                .iter()
+                .map(|v| v.estimate_storage_size())
+                .sum()
+        }
+        InnerContent::List(InnerListOp::Set { value, .. }) => 7 + value.estimate_storage_size(),
+        InnerContent::Map(MapSet { key, value }) => {
+            3 + key.len()
+                + value
+                    .as_ref()
+                    .map(|v| v.estimate_storage_size())
+                    .unwrap_or(0)
+        }
+        _ => op.estimate_storage_size(),
+    }
+}
@@ -9,9 +9,9 @@ use once_cell::sync::OnceCell;
 use rle::{HasIndex, HasLength, Mergable, Sliceable};
 use smallvec::SmallVec;
 use std::cmp::Ordering;
-use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Display;
-use std::ops::{ControlFlow, Deref};
+use std::ops::Deref;
 use std::sync::{Arc, Mutex};
 use tracing::{instrument, trace};
 
@@ -429,6 +429,31 @@ impl AppDag {
         }
     }
 
+    fn load_all_nodes(&self) {
+        let unparsed_vv = self.unparsed_vv.lock().unwrap().clone();
+        for (peer, cnt) in unparsed_vv.iter() {
+            if *cnt == 0 {
+                continue;
+            }
+
+            let mut end_cnt = *cnt;
+            let init_counter = self.shallow_since_vv.get(peer).copied().unwrap_or(0);
+            while end_cnt > init_counter {
+                let cnt = end_cnt - 1;
+                self.ensure_lazy_load_node(ID::new(*peer, cnt));
+                end_cnt = self
+                    .unparsed_vv
+                    .lock()
+                    .unwrap()
+                    .get(peer)
+                    .copied()
+                    .unwrap_or(0);
+            }
+        }
+
+        self.unparsed_vv.lock().unwrap().clear();
+    }
+
     pub fn total_parsed_dag_node(&self) -> usize {
         self.map.lock().unwrap().len()
     }
@@ -462,31 +487,7 @@ impl AppDag {
     /// 5. Frontiers are correctly calculated
     #[instrument(skip(self))]
     pub fn check_dag_correctness(&self) {
-        {
-            // parse all nodes
-            let unparsed_vv = self.unparsed_vv.lock().unwrap().clone();
-            for (peer, cnt) in unparsed_vv.iter() {
-                if *cnt == 0 {
-                    continue;
-                }
-
-                let mut end_cnt = *cnt;
-                let init_counter = self.shallow_since_vv.get(peer).copied().unwrap_or(0);
-                while end_cnt > init_counter {
-                    let cnt = end_cnt - 1;
-                    self.ensure_lazy_load_node(ID::new(*peer, cnt));
-                    end_cnt = self
-                        .unparsed_vv
-                        .lock()
-                        .unwrap()
-                        .get(peer)
-                        .copied()
-                        .unwrap_or(0);
-                }
-            }
-
-            self.unparsed_vv.lock().unwrap().clear();
-        }
+        self.load_all_nodes();
         {
             // check property 1: Counter is continuous
             let map = self.map.lock().unwrap();
@@ -604,57 +605,6 @@ impl AppDag {
         false
     }
 
-    /// Travel the ancestors of the given id, and call the callback for each node
-    ///
-    /// It will travel the ancestors in the reverse order (from the greatest lamport to the smallest)
-    pub(crate) fn travel_ancestors(
-        &self,
-        id: ID,
-        f: &mut dyn FnMut(&AppDagNode) -> ControlFlow<()>,
-    ) {
-        struct PendingNode(AppDagNode);
-        impl PartialEq for PendingNode {
-            fn eq(&self, other: &Self) -> bool {
-                self.0.lamport_last() == other.0.lamport_last() && self.0.peer == other.0.peer
-            }
-        }
-        impl Eq for PendingNode {}
-        impl PartialOrd for PendingNode {
-            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-                Some(self.cmp(other))
-            }
-        }
-        impl Ord for PendingNode {
-            fn cmp(&self, other: &Self) -> Ordering {
-                self.0
-                    .lamport_last()
-                    .cmp(&other.0.lamport_last())
-                    .then_with(|| self.0.peer.cmp(&other.0.peer))
-            }
-        }
-
-        let mut visited = FxHashSet::default();
-        let mut pending: BinaryHeap<PendingNode> = BinaryHeap::new();
-        pending.push(PendingNode(self.get(id).unwrap()));
-        while let Some(PendingNode(node)) = pending.pop() {
-            if f(&node).is_break() {
-                break;
-            }
-
-            for dep in node.deps.iter() {
-                let Some(dep_node) = self.get(dep) else {
-                    continue;
-                };
-                if visited.contains(&dep_node.id_start()) {
-                    continue;
-                }
-
-                visited.insert(dep_node.id_start());
-                pending.push(PendingNode(dep_node));
-            }
-        }
-    }
-
     pub(crate) fn update_version_on_new_local_op(
         &mut self,
         deps: &Frontiers,
@@ -1115,6 +1065,93 @@ impl AppDag {
         let b = self.frontiers_to_vv(b).ok_or(FrontiersNotIncluded)?;
         Ok(a.partial_cmp(&b))
     }
+
+    /// The ops that the op `id` depends on directly.
+    ///
+    /// Returns `None` if the op is not in the dag, e.g. if it's before the shallow root.
+    pub fn parents_of(&self, id: ID) -> Option<Frontiers> {
+        let node = self.get(id)?;
+        if id.counter == node.cnt {
+            Some(node.deps.clone())
+        } else {
+            Some(ID::new(id.peer, id.counter - 1).into())
+        }
+    }
+
+    /// The ops that depend on the op `id` directly, ordered by their ids.
+    ///
+    /// It loads and scans the whole dag.
+    pub fn children_of(&self, id: ID) -> Vec<ID> {
+        self.load_all_nodes();
+        let map = self.map.lock().unwrap();
+        let mut ans: Vec<ID> = map
+            .values()
+            .chain(self.pending_txn_node.iter())
+            .filter_map(|node| {
+                if node.contains_id(id) {
+                    // The next op of the node depends on the previous one
+                    (id.counter + 1 < node.ctr_end()).then(|| id.inc(1))
+                } else {
+                    node.deps.contains(&id).then(|| node.id_start())
+                }
+            })
+            .collect();
+        ans.sort_unstable();
+        ans
+    }
+
+    /// The nodes of the ops that are in `to` but not in `from`, in the causal order.
+    ///
+    /// The nodes are cut at the boundaries of the range, so the first node of a peer may start
+    /// in the middle of a node of the dag. It depends on its previous op then.
+    pub fn iter_dag_nodes_between(
+        &self,
+        from: &Frontiers,
+        to: &Frontiers,
+    ) -> Result<impl Iterator<Item = DagNodeInfo>, FrontiersNotIncluded> {
+        let from_vv = self.frontiers_to_vv(from).ok_or(FrontiersNotIncluded)?;
+        let to_vv = self.frontiers_to_vv(to).ok_or(FrontiersNotIncluded)?;
+        let mut ans = Vec::new();
+        for (peer, span) in from_vv.diff(&to_vv).forward {
+            let mut counter = span.start;
+            while counter < span.end {
+                let Some(node) = self.get(ID::new(peer, counter)) else {
+                    break;
+                };
+
+                let end = node.ctr_end().min(span.end);
+                let offset = counter - node.cnt;
+                ans.push(DagNodeInfo {
+                    id: ID::new(peer, counter),
+                    lamport: node.lamport + offset as Lamport,
+                    deps: if offset == 0 {
+                        node.deps.clone()
+                    } else {
+                        ID::new(peer, counter - 1).into()
+                    },
+                    len: (end - counter) as usize,
+                });
+                counter = end;
+            }
+        }
+
+        // A node has a greater lamport than all the nodes it depends on
+        ans.sort_unstable_by_key(|n| (n.lamport, n.id.peer));
+        Ok(ans.into_iter())
+    }
+}
+
+/// A node of the causal graph returned by [`AppDag::iter_dag_nodes_between`]: a run of ops of
+/// one peer, in which every op depends on the previous one except the first one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DagNodeInfo {
+    /// The id of the first op
+    pub id: ID,
+    /// The lamport of the first op
+    pub lamport: Lamport,
+    /// The ops that the first op depends on
+    pub deps: Frontiers,
+    pub len: usize,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -37,6 +37,13 @@ impl PendingChanges {
     pub fn is_empty(&self) -> bool {
         self.changes.is_empty()
     }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Change> {
+        self.changes
+            .values()
+            .flat_map(|tree| tree.values())
+            .flat_map(|changes| changes.iter().map(|c| c.deref()))
+    }
 }
 
 impl OpLog {
@@ -65,6 +72,11 @@ impl OpLog {
 }
 
 impl OpLog {
+    /// The number of missing ops on each peer that the pending changes are waiting for.
+    pub(crate) fn pending_causal_gaps(&self) -> FxHashMap<PeerID, Counter> {
+        crate::encoding::calc_causal_gaps(self.pending_changes.iter(), self.vv())
+    }
+
     /// Try to apply pending changes.
     ///
     /// `new_ids` are the ID of the op that is just applied.
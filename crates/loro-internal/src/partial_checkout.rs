@@ -0,0 +1,293 @@
+//! Checkout a subset of the containers to a different version.
+//!
+//! [`LoroDoc::checkout_containers`] time-travels only the given containers, while the
+//! rest of the [DocState][crate::DocState] stays at [`DocState::frontiers`][crate::DocState].
+//! It's designed for previewing, e.g. comparing one chapter of a book across versions without
+//! checking out the whole book.
+//!
+//! # Semantics
+//!
+//! - The doc enters the detached mode, so it's readonly and the imported updates will only be
+//!   recorded in the [OpLog][crate::OpLog].
+//! - The version of each container can be queried via [`LoroDoc::get_container_version`].
+//! - Any operation that checks out the whole doc (`checkout`, `checkout_to_latest`, `attach`,
+//!   exporting a snapshot in detached mode, ...) drops the partial view first.
+use std::borrow::Cow;
+
+use fxhash::FxHashSet;
+use loro_common::{ContainerID, LoroError, LoroResult};
+
+use crate::{
+    container::idx::ContainerIdx,
+    dag::Dag,
+    diff_calc::{DiffCalculator, DiffMode},
+    event::{Diff, EventTriggerKind, InternalDocDiff},
+    undo::DiffBatch,
+    version::{shrink_frontiers, Frontiers},
+    LoroDoc,
+};
+
+/// The containers that are checked out to `frontiers` while the rest of the
+/// state stays at the version of the [DocState][crate::DocState].
+#[derive(Debug, Clone)]
+pub(crate) struct PartialCheckout {
+    pub frontiers: Frontiers,
+    pub containers: FxHashSet<ContainerIdx>,
+}
+
+impl LoroDoc {
+    /// Checkout only the given containers to the target version, leaving the other
+    /// containers at the current state version.
+    ///
+    /// The doc will enter the detached mode. Call `checkout_to_latest` or `checkout` to
+    /// leave the partial view. Calling this method again replaces the previous partial view.
+    ///
+    /// It's not supported when detached editing is enabled, because the local ops would be
+    /// based on a state that doesn't match any version in the history.
+    pub fn checkout_containers(
+        &self,
+        frontiers: &Frontiers,
+        containers: &[ContainerID],
+    ) -> LoroResult<()> {
+        if self.config.detached_editing() {
+            return Err(LoroError::ArgErr(
+                "Partial checkout is not supported when detached editing is enabled"
+                    .to_string()
+                    .into_boxed_str(),
+            ));
+        }
+
+        let (options, guard) = self.commit_then_stop();
+        let ans = self._checkout_containers_without_emitting(frontiers, containers);
+        self.emit_events();
+        drop(guard);
+        self.renew_txn_if_auto_commit(options);
+        ans
+    }
+
+    /// Get the version that the state of the given container reflects.
+    ///
+    /// It's the state frontiers unless the container is checked out by
+    /// [`LoroDoc::checkout_containers`].
+    pub fn get_container_version(&self, id: &ContainerID) -> Frontiers {
+        let state = self.state.lock().unwrap();
+        if let Some(partial) = state.partial_checkout.as_ref() {
+            if let Some(idx) = self.arena.id_to_idx(id) {
+                if partial.containers.contains(&idx) {
+                    return partial.frontiers.clone();
+                }
+            }
+        }
+
+        state.frontiers.clone()
+    }
+
+    /// Whether some of the containers are checked out by [`LoroDoc::checkout_containers`].
+    pub fn is_partially_checked_out(&self) -> bool {
+        self.state.lock().unwrap().partial_checkout.is_some()
+    }
+
+    /// NOTE: The caller of this method should ensure the txn is locked and set to None
+    fn _checkout_containers_without_emitting(
+        &self,
+        frontiers: &Frontiers,
+        containers: &[ContainerID],
+    ) -> LoroResult<()> {
+        assert!(self.txn.is_locked());
+        let mut missing = Vec::new();
+        let mut idx_set = FxHashSet::default();
+        for id in containers {
+            if !self.has_container(id) {
+                missing.push(id.clone());
+                continue;
+            }
+
+            idx_set.insert(self.arena.register_container(id));
+        }
+
+        if !missing.is_empty() {
+            return Err(LoroError::ContainersNotFound {
+                containers: Box::new(missing),
+            });
+        }
+
+        // Make sure every container starts from the state version
+        self._revert_partial_checkout_without_emitting();
+        let oplog = self.oplog.lock().unwrap();
+        if oplog.dag.is_before_shallow_root(frontiers) {
+            return Err(LoroError::SwitchToVersionBeforeShallowRoot);
+        }
+
+        for id in frontiers.iter() {
+            if !oplog.dag.contains(id) {
+                return Err(LoroError::FrontiersNotFound(id));
+            }
+        }
+
+        let frontiers = shrink_frontiers(frontiers, &oplog.dag)
+            .map_err(|_| LoroError::SwitchToVersionBeforeShallowRoot)?;
+        let mut state = self.state.lock().unwrap();
+        let state_frontiers = state.frontiers.clone();
+        if idx_set.is_empty() || frontiers == state_frontiers {
+            return Ok(());
+        }
+
+        let before = oplog.dag.frontiers_to_vv(&state_frontiers).unwrap();
+        let Some(after) = oplog.dag.frontiers_to_vv(&frontiers) else {
+            return Err(LoroError::NotFoundError(
+                format!("Cannot find the specified version {:?}", frontiers).into_boxed_str(),
+            ));
+        };
+
+        self.set_detached(true);
+        // Use a persisted calculator so that the diff is always calculated in the checkout mode
+        let mut calc = DiffCalculator::new(true);
+        let (diff, _) = calc.calc_diff_internal(
+            &oplog,
+            &before,
+            &state_frontiers,
+            &after,
+            &frontiers,
+            Some(&|idx| idx_set.contains(&idx)),
+        );
+        state.apply_diff(
+            InternalDocDiff {
+                origin: "checkout".into(),
+                diff: Cow::Owned(diff),
+                by: EventTriggerKind::Checkout,
+                // The version of the state is not changed
+                new_version: Cow::Owned(state_frontiers),
+            },
+            DiffMode::Checkout,
+        );
+        state.partial_checkout = Some(PartialCheckout {
+            frontiers,
+            containers: idx_set,
+        });
+        Ok(())
+    }
+
+    /// Calculate the diff of a single container between two versions.
+    ///
+    /// Only the history of the container is traversed, and the other containers are left
+    /// untouched. The doc is restored to its previous state and partial view afterwards, and no
+    /// event is emitted. It returns `None` if the container is not changed between the versions.
+    pub(crate) fn diff_container(
+        &self,
+        id: &ContainerID,
+        from: &Frontiers,
+        to: &Frontiers,
+    ) -> LoroResult<Option<Diff>> {
+        self.flush_pending_events();
+        let (options, txn) = self.commit_then_stop();
+        let was_detached = self.is_detached();
+        let (was_recording, old_partial) = {
+            let mut state = self.state.lock().unwrap();
+            let is_recording = state.is_recording();
+            state.stop_and_clear_recording();
+            (is_recording, state.partial_checkout.clone())
+        };
+
+        let ans = self
+            ._checkout_containers_without_emitting(from, std::slice::from_ref(id))
+            .map(|_| {
+                let oplog = self.oplog.lock().unwrap();
+                let mut state = self.state.lock().unwrap();
+                let idx = self.arena.register_container(id);
+                let state_frontiers = state.frontiers.clone();
+                let from = state
+                    .partial_checkout
+                    .as_ref()
+                    .map(|p| p.frontiers.clone())
+                    .unwrap_or_else(|| state_frontiers.clone());
+                let to = shrink_frontiers(to, &oplog.dag).unwrap_or_else(|_| to.clone());
+                let (Some(before), Some(after)) = (
+                    oplog.dag.frontiers_to_vv(&from),
+                    oplog.dag.frontiers_to_vv(&to),
+                ) else {
+                    return None;
+                };
+
+                let mut calc = DiffCalculator::new(true);
+                let (diff, _) = calc.calc_diff_internal(
+                    &oplog,
+                    &before,
+                    &from,
+                    &after,
+                    &to,
+                    Some(&|target| target == idx),
+                );
+                state.start_recording();
+                state.apply_diff(
+                    InternalDocDiff {
+                        origin: "checkout".into(),
+                        diff: Cow::Owned(diff),
+                        by: EventTriggerKind::Checkout,
+                        new_version: Cow::Owned(state_frontiers),
+                    },
+                    DiffMode::Checkout,
+                );
+                let events = state.take_events();
+                state.stop_and_clear_recording();
+                // Let the revert below bring the container back from `to`
+                state.partial_checkout = Some(PartialCheckout {
+                    frontiers: to,
+                    containers: [idx].into_iter().collect(),
+                });
+                DiffBatch::new(events).cid_to_events.remove(id)
+            });
+
+        self._revert_partial_checkout_without_emitting();
+        if let Some(partial) = old_partial {
+            let containers: Vec<_> = partial
+                .containers
+                .iter()
+                .filter_map(|&idx| self.arena.idx_to_id(idx))
+                .collect();
+            self._checkout_containers_without_emitting(&partial.frontiers, &containers)
+                .unwrap();
+        }
+        drop(txn);
+        if !was_detached {
+            self.set_detached(false);
+            self.renew_txn_if_auto_commit(options);
+        }
+        if was_recording {
+            self.state.lock().unwrap().start_recording();
+        }
+        ans
+    }
+
+    /// Bring the partially checked out containers back to the state version.
+    ///
+    /// NOTE: The caller of this method should ensure the txn is locked and set to None
+    pub(crate) fn _revert_partial_checkout_without_emitting(&self) {
+        let oplog = self.oplog.lock().unwrap();
+        let mut state = self.state.lock().unwrap();
+        let Some(partial) = state.partial_checkout.take() else {
+            return;
+        };
+
+        let state_frontiers = state.frontiers.clone();
+        let before = oplog.dag.frontiers_to_vv(&partial.frontiers).unwrap();
+        let after = oplog.dag.frontiers_to_vv(&state_frontiers).unwrap();
+        let mut calc = DiffCalculator::new(true);
+        let (diff, _) = calc.calc_diff_internal(
+            &oplog,
+            &before,
+            &partial.frontiers,
+            &after,
+            &state_frontiers,
+            Some(&|idx| partial.containers.contains(&idx)),
+        );
+        state.apply_diff(
+            InternalDocDiff {
+                origin: "checkout".into(),
+                diff: Cow::Owned(diff),
+                by: EventTriggerKind::Checkout,
+                new_version: Cow::Owned(state_frontiers),
+            },
+            DiffMode::Checkout,
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! Remapping the peers of the history into a dense table.
+//!
+//! A doc edited by thousands of anonymous visitors carries thousands of random 64-bit peer
+//! ids, in every version vector, every frontiers and every id of the history.
+//! [`LoroDoc::compact_peers`] rebuilds the doc with the peers renamed to `0..n`, which are
+//! encoded in one or two bytes. The renaming keeps the order of the peers, so the concurrent
+//! edits are resolved as before and the compacted doc has the same state.
+//!
+//! The compacted doc has a new history: it cannot be synced with the replicas of the old doc,
+//! so every replica has to switch to it. The [`PeerMapping`] translates the references kept
+//! outside the doc, e.g. cursors or stored versions. The references kept inside the doc, i.e.
+//! the container ids and the [`loro_common::LoroValue::ContainerRef`] values, are translated
+//! with the history.
+//!
+//! All the peers are renamed, not only the retired ones, because the renaming has to keep
+//! their order. The table isn't stored in the snapshots of the compacted doc: it's returned
+//! to the caller, which can store it with [`PeerMapping::encode`].
+use loro_common::{ContainerID, LoroError, LoroResult, PeerID, ID};
+
+use crate::{
+    commit_metadata::{compose_commit_message, parse_commit_message},
+    peer_rotation::PEER_ROTATION_COMMIT_MSG_PREFIX,
+    version::{Frontiers, VersionVector},
+    LoroDoc,
+};
+
+/// The peer table of a doc compacted by [`LoroDoc::compact_peers`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeerMapping {
+    /// The original peer ids in ascending order, indexed by the compact peer ids
+    original: Vec<PeerID>,
+}
+
+impl PeerMapping {
+    /// The original peer ids, indexed by the compact peer ids
+    pub fn original_peers(&self) -> &[PeerID] {
+        &self.original
+    }
+
+    pub fn to_compact(&self, peer: PeerID) -> Option<PeerID> {
+        self.original.binary_search(&peer).ok().map(|i| i as PeerID)
+    }
+
+    pub fn to_original(&self, peer: PeerID) -> Option<PeerID> {
+        self.original.get(usize::try_from(peer).ok()?).copied()
+    }
+
+    pub fn translate_id(&self, id: ID) -> Option<ID> {
+        Some(ID::new(self.to_compact(id.peer)?, id.counter))
+    }
+
+    /// Translate the id of a container created by a peer of the mapping. The root containers
+    /// are returned as they are.
+    pub fn translate_container(&self, id: &ContainerID) -> Option<ContainerID> {
+        match id {
+            ContainerID::Root { .. } => Some(id.clone()),
+            ContainerID::Normal {
+                peer,
+                counter,
+                container_type,
+            } => Some(ContainerID::Normal {
+                peer: self.to_compact(*peer)?,
+                counter: *counter,
+                container_type: *container_type,
+            }),
+        }
+    }
+
+    /// Translate a version of the old doc. Returns `None` if it refers to an unknown peer.
+    pub fn translate_frontiers(&self, frontiers: &Frontiers) -> Option<Frontiers> {
+        frontiers.iter().map(|id| self.translate_id(id)).collect()
+    }
+
+    /// Translate a version vector of the old doc. Returns `None` if it refers to an unknown peer.
+    pub fn translate_vv(&self, vv: &VersionVector) -> Option<VersionVector> {
+        let mut ans = VersionVector::new();
+        for (peer, counter) in vv.iter() {
+            ans.insert(self.to_compact(*peer)?, *counter);
+        }
+        Some(ans)
+    }
+
+    /// Encode the table. The sorted peers are delta encoded.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut ans = Vec::new();
+        leb128::write::unsigned(&mut ans, self.original.len() as u64).unwrap();
+        let mut last = 0;
+        for &peer in self.original.iter() {
+            leb128::write::unsigned(&mut ans, peer - last).unwrap();
+            last = peer;
+        }
+
+        ans
+    }
+
+    pub fn decode(bytes: &[u8]) -> LoroResult<Self> {
+        let err = || LoroError::DecodeError("Invalid peer mapping".into());
+        let mut reader = bytes;
+        let len = leb128::read::unsigned(&mut reader).map_err(|_| err())? as usize;
+        let mut original = Vec::with_capacity(len.min(bytes.len()));
+        let mut peer: PeerID = 0;
+        for i in 0..len {
+            let delta = leb128::read::unsigned(&mut reader).map_err(|_| err())?;
+            if i > 0 && delta == 0 {
+                return Err(err());
+            }
+
+            peer = peer.checked_add(delta).ok_or_else(err)?;
+            original.push(peer);
+        }
+
+        if !reader.is_empty() {
+            return Err(err());
+        }
+
+        Ok(Self { original })
+    }
+}
+
+impl LoroDoc {
+    /// Create a copy of the doc whose peers are renamed to `0..n` in their order, and return
+    /// it with the table of the original peers.
+    ///
+    /// The history is copied with the same changes, lamports, timestamps and messages. The
+    /// links of [`LoroDoc::rotate_peer_id`] in the commit messages are translated too. The new
+    /// doc has the same configuration and a random peer id.
+    ///
+    /// It returns an error for a shallow doc, whose history before the shallow root cannot
+    /// be rebuilt.
+    pub fn compact_peers(&self) -> LoroResult<(LoroDoc, PeerMapping)> {
+        if self.is_shallow() {
+            return Err(LoroError::ArgErr(
+                "Cannot compact the peers of a shallow doc".into(),
+            ));
+        }
+
+        let mut json = self.export_json_updates(&Default::default(), &self.oplog_vv(), true);
+        let peers = json.peers.get_or_insert_with(Vec::new);
+        let mut original = peers.clone();
+        original.sort_unstable();
+        let mapping = PeerMapping { original };
+        for peer in peers.iter_mut() {
+            *peer = mapping.to_compact(*peer).unwrap();
+        }
+
+        for change in json.changes.iter_mut() {
+            let Some(msg) = &change.msg else {
+                continue;
+            };
+            let (text, metadata) = parse_commit_message(msg);
+            let Some(old) = text
+                .strip_prefix(PEER_ROTATION_COMMIT_MSG_PREFIX)
+                .and_then(|x| x.parse().ok())
+                .and_then(|old| mapping.to_compact(old))
+            else {
+                continue;
+            };
+            let text = format!("{}{}", PEER_ROTATION_COMMIT_MSG_PREFIX, old);
+            change.msg = compose_commit_message(&text, &metadata).map(|x| x.to_string());
+        }
+
+        let doc = LoroDoc::new();
+        doc.set_config(&self.config);
+        doc.derive_randomness_from(&self.config);
+        doc.import_json_updates(json)?;
+        if self.auto_commit.load(std::sync::atomic::Ordering::Relaxed) {
+            doc.start_auto_commit();
+        }
+
+        Ok((doc, mapping))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{cursor::Side, encoding::ExportMode, HandlerTrait};
+    use loro_common::LoroValue;
+
+    #[test]
+    fn compact_peers_keeps_state() {
+        let a = LoroDoc::new_auto_commit();
+        a.set_peer_id(u64::MAX - 10).unwrap();
+        let b = LoroDoc::new_auto_commit();
+        b.set_peer_id(1 << 40).unwrap();
+        // Concurrent inserts at the same position are ordered by the peers
+        a.get_text("text").insert(0, "a").unwrap();
+        b.get_text("text").insert(0, "b").unwrap();
+        a.get_map("map").insert("k", "a").unwrap();
+        b.get_map("map").insert("k", "b").unwrap();
+        let list = b
+            .get_map("map")
+            .insert_container("list", crate::ListHandler::new_detached())
+            .unwrap();
+        list.push(1).unwrap();
+        b.commit_then_renew();
+        let cursor = list.get_cursor(0, Side::Middle).unwrap();
+        a.import(&b.export(ExportMode::all_updates()).unwrap())
+            .unwrap();
+        b.import(&a.export(ExportMode::all_updates()).unwrap())
+            .unwrap();
+        a.rotate_peer_id().unwrap();
+        a.get_text("text").insert(0, "c").unwrap();
+        a.commit_then_renew();
+
+        let (doc, mapping) = a.compact_peers().unwrap();
+        assert_eq!(doc.get_deep_value(), a.get_deep_value());
+        assert_eq!(mapping.original_peers().len(), 3);
+        assert_eq!(mapping.to_compact(1 << 40), Some(0));
+        assert_eq!(mapping.to_original(2), Some(u64::MAX - 10));
+        assert_eq!(
+            doc.get_peer_rotations(),
+            vec![(2, mapping.to_compact(a.peer_id()).unwrap())]
+        );
+        assert_eq!(doc.oplog_vv(), mapping.translate_vv(&a.oplog_vv()).unwrap());
+        assert!(doc.oplog_vv().encode().len() < a.oplog_vv().encode().len());
+
+        let list_id = mapping.translate_container(&list.id()).unwrap();
+        assert_eq!(
+            doc.get_handler(list_id).unwrap().get_deep_value(),
+            list.get_deep_value()
+        );
+        let id = mapping.translate_id(cursor.id.unwrap()).unwrap();
+        assert_eq!(id.peer, 0);
+        assert_eq!(PeerMapping::decode(&mapping.encode()).unwrap(), mapping);
+        assert!(PeerMapping::decode(&[2, 1, 0]).is_err());
+    }
+
+    #[test]
+    fn compact_peers_translates_container_refs() {
+        let a = LoroDoc::new_auto_commit();
+        a.set_peer_id(u64::MAX - 10).unwrap();
+        a.set_extended_value_types(true);
+        let map = a.get_map("map");
+        let list = map
+            .insert_container("list", crate::ListHandler::new_detached())
+            .unwrap();
+        list.push(1).unwrap();
+        map.insert("ref", LoroValue::ContainerRef(list.id()))
+            .unwrap();
+        map.insert("nested", vec![LoroValue::ContainerRef(list.id())])
+            .unwrap();
+        a.get_list("refs")
+            .insert(0, LoroValue::ContainerRef(list.id()))
+            .unwrap();
+        a.commit_then_renew();
+
+        let (doc, mapping) = a.compact_peers().unwrap();
+        let list_id = mapping.translate_container(&list.id()).unwrap();
+        assert_ne!(list_id, list.id());
+        assert!(doc.has_container(&list_id));
+        let map = doc.get_map("map");
+        assert_eq!(
+            map.get("ref"),
+            Some(LoroValue::ContainerRef(list_id.clone()))
+        );
+        assert_eq!(
+            map.get("nested"),
+            Some(LoroValue::from(vec![LoroValue::ContainerRef(
+                list_id.clone()
+            )]))
+        );
+        assert_eq!(
+            doc.get_list("refs").get(0),
+            Some(LoroValue::ContainerRef(list_id))
+        );
+    }
+}
@@ -0,0 +1,78 @@
+//! Rotate the peer id of a doc without closing it.
+use std::sync::atomic::Ordering;
+
+use loro_common::{LoroError, LoroResult, PeerID};
+
+use crate::LoroDoc;
+
+/// The commit message prefix of the first change made after [`LoroDoc::rotate_peer_id`].
+///
+/// The rest of the message is the previous peer id in decimal.
+pub const PEER_ROTATION_COMMIT_MSG_PREFIX: &str = "loro:rotate-peer ";
+
+impl LoroDoc {
+    /// Switch the doc to a new random peer id and return it.
+    ///
+    /// The pending changes are committed with the old peer id, and the following changes are
+    /// made by the new peer from counter 0, so rotating periodically avoids exhausting the
+    /// counters and unlinks the future edits from the old peer id. The doc stays open and
+    /// the handlers stay valid.
+    ///
+    /// The first change made by the new peer carries a commit message that links it to the
+    /// old peer id (see [`PEER_ROTATION_COMMIT_MSG_PREFIX`]), which can be read back by
+    /// [`LoroDoc::get_peer_rotations`]. Setting another commit message for that change drops
+    /// the link.
+    pub fn rotate_peer_id(&self) -> LoroResult<PeerID> {
+        if self.batching.load(Ordering::Acquire) {
+            return Err(LoroError::TransactionError(
+                "Cannot rotate the peer id inside a batch"
+                    .to_string()
+                    .into_boxed_str(),
+            ));
+        }
+
+        self.commit_then_renew();
+        // If the previous rotation made no change, the link starts from its old peer
+        let old = self
+            .pending_peer_rotation()
+            .unwrap_or_else(|| self.peer_id());
+        let current = self.peer_id();
+        let new = loop {
+            let peer = self.config.next_random_u64();
+            if peer != PeerID::MAX && peer != old && peer != current {
+                break peer;
+            }
+        };
+
+        self.set_peer_id(new)?;
+        self.set_next_commit_message(&format!("{}{}", PEER_ROTATION_COMMIT_MSG_PREFIX, old));
+        Ok(new)
+    }
+
+    /// Get the `(old, new)` pairs of the peer ids linked by [`LoroDoc::rotate_peer_id`]
+    /// in the history, in the order of the changes in the oplog.
+    pub fn get_peer_rotations(&self) -> Vec<(PeerID, PeerID)> {
+        let oplog = self.oplog.lock().unwrap();
+        let mut ans = Vec::new();
+        oplog.change_store().visit_all_changes(&mut |c| {
+            if let Some(old) = c.message().and_then(|msg| parse_rotation_msg(msg)) {
+                ans.push((old, c.id.peer));
+            }
+        });
+
+        ans
+    }
+
+    fn pending_peer_rotation(&self) -> Option<PeerID> {
+        let txn = self.txn.lock().unwrap();
+        txn.as_ref()
+            .and_then(|txn| txn.msg().as_deref())
+            .and_then(parse_rotation_msg)
+    }
+}
+
+fn parse_rotation_msg(msg: &str) -> Option<PeerID> {
+    msg.strip_prefix(PEER_ROTATION_COMMIT_MSG_PREFIX)?
+        .parse()
+        .ok()
+}
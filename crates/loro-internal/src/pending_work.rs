@@ -0,0 +1,258 @@
+//! Spreading long checkouts and imports over several calls.
+//!
+//! A checkout or an import applies the whole diff between two versions at once, which can
+//! block a single-threaded host, e.g. the main thread of a web page, for hundreds of
+//! milliseconds. [`LoroDoc::checkout_in_slices`] and [`LoroDoc::import_in_slices`] only
+//! schedule the work. Each call of [`LoroDoc::poll_pending_work`] then moves the state one
+//! step closer to the target, through intermediate versions that are at most `budget` ops
+//! away from each other, so the host can do other work between the calls.
+//!
+//! The state first reverts the ops that are not in the target, from the last to the first,
+//! and then applies the missing ops in the causal order. Every intermediate version is
+//! therefore a valid version of the doc, and the events of each step are emitted like the
+//! events of a checkout.
+use std::collections::VecDeque;
+
+use loro_common::{Counter, IdSpan, LoroError, LoroResult, ID};
+
+use crate::{
+    encoding::{parse_header_and_body, EncodeMode, ImportStatus},
+    version::Frontiers,
+    LoroDoc,
+};
+
+/// Whether [`LoroDoc::poll_pending_work`] has finished the scheduled work
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollStatus {
+    /// The state hasn't reached the target yet
+    Pending,
+    /// There is no scheduled work left
+    Complete,
+}
+
+#[derive(Debug)]
+pub(crate) struct PendingWork {
+    /// The spans to revert, in the causal order. They are reverted from the last one.
+    retreat: Vec<IdSpan>,
+    /// The spans to apply, in the causal order
+    forward: VecDeque<IdSpan>,
+    /// The version of the state after the last step. If the state is checked out by
+    /// another call in the meantime, the work is dropped.
+    expected: Frontiers,
+    target: Frontiers,
+    /// Whether to attach the doc once the target is reached
+    attach: bool,
+}
+
+impl LoroDoc {
+    /// Schedule a checkout to `frontiers`, which is carried out by
+    /// [`LoroDoc::poll_pending_work`].
+    ///
+    /// The doc is detached immediately, like [`LoroDoc::checkout`] does. The work scheduled
+    /// before is replaced.
+    pub fn checkout_in_slices(&self, frontiers: &Frontiers) -> LoroResult<()> {
+        self.detach();
+        self.schedule_pending_work(frontiers, false)
+    }
+
+    /// Import `bytes` into the history, and schedule applying them to the state, which is
+    /// carried out by [`LoroDoc::poll_pending_work`].
+    ///
+    /// The doc stays detached until the work is complete, so it cannot be edited in the
+    /// meantime unless detached editing is enabled. The changes are reported by the events of
+    /// the steps, which are triggered by a checkout.
+    ///
+    /// If the doc is detached by the user, the updates are only recorded in the history, like
+    /// [`LoroDoc::import`] does. A snapshot imported into an empty doc is loaded at once,
+    /// because it's not diffed.
+    pub fn import_in_slices(&self, bytes: &[u8]) -> LoroResult<ImportStatus> {
+        let attach = !self.is_detached()
+            || self
+                .pending_work
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_some_and(|w| w.attach);
+        let is_snapshot = matches!(
+            parse_header_and_body(bytes, true)?.mode,
+            EncodeMode::FastSnapshot | EncodeMode::OutdatedSnapshot
+        );
+        if !attach || (is_snapshot && self.can_reset_with_snapshot()) {
+            return self.import(bytes);
+        }
+
+        self.detach();
+        let ans = self.import(bytes);
+        // Schedule even if the import failed, so the doc is attached again
+        self.schedule_pending_work(&self.oplog_frontiers(), true)?;
+        ans
+    }
+
+    /// Whether there is work scheduled by [`LoroDoc::checkout_in_slices`] or
+    /// [`LoroDoc::import_in_slices`]
+    pub fn has_pending_work(&self) -> bool {
+        self.pending_work.lock().unwrap().is_some()
+    }
+
+    /// Move the state toward the target of the scheduled work by at most `budget` ops,
+    /// emitting the events of the step.
+    ///
+    /// At least one op is processed per call, and a single op may still be expensive, e.g.
+    /// a deletion of a long text. Returns [`PollStatus::Complete`] once the target is reached
+    /// or if there is nothing to do.
+    pub fn poll_pending_work(&self, budget: usize) -> LoroResult<PollStatus> {
+        let Some(mut work) = self.pending_work.lock().unwrap().take() else {
+            return Ok(PollStatus::Complete);
+        };
+        self.commit_then_renew();
+        if self.state_frontiers() != work.expected {
+            return Ok(PollStatus::Complete);
+        }
+
+        let mut vv = self.state_vv();
+        let mut budget = budget.max(1);
+        while budget > 0 {
+            let n = if let Some(span) = work.retreat.last_mut() {
+                let n = budget.min((span.counter.end - span.counter.start) as usize);
+                span.counter.end -= n as Counter;
+                vv.set_end(ID::new(span.peer, span.counter.end));
+                if span.counter.start == span.counter.end {
+                    work.retreat.pop();
+                }
+                n
+            } else if let Some(span) = work.forward.front_mut() {
+                let n = budget.min((span.counter.end - span.counter.start) as usize);
+                span.counter.start += n as Counter;
+                vv.set_end(ID::new(span.peer, span.counter.start));
+                if span.counter.start == span.counter.end {
+                    work.forward.pop_front();
+                }
+                n
+            } else {
+                break;
+            };
+            budget -= n;
+        }
+
+        if work.retreat.is_empty() && work.forward.is_empty() {
+            if work.attach {
+                self.checkout_to_latest();
+                return Ok(PollStatus::Complete);
+            }
+
+            self.checkout(&work.target)?;
+            return Ok(PollStatus::Complete);
+        }
+
+        self.checkout(&self.vv_to_frontiers(&vv))?;
+        work.expected = self.state_frontiers();
+        let mut pending = self.pending_work.lock().unwrap();
+        // The event handlers may have scheduled new work
+        if pending.is_none() {
+            *pending = Some(work);
+        }
+
+        Ok(PollStatus::Pending)
+    }
+
+    fn schedule_pending_work(&self, target: &Frontiers, attach: bool) -> LoroResult<()> {
+        let current = self.state_frontiers();
+        let oplog = self.oplog.lock().unwrap();
+        if oplog.dag.is_before_shallow_root(target) {
+            return Err(LoroError::SwitchToVersionBeforeShallowRoot);
+        }
+        if let Some(id) = target.iter().find(|id| !oplog.dag.contains(*id)) {
+            return Err(LoroError::FrontiersNotFound(id));
+        }
+
+        let to_span = |c: crate::ChangeMeta| {
+            IdSpan::new(c.id.peer, c.id.counter, c.id.counter + c.len as Counter)
+        };
+        let retreat = oplog
+            .iter_changes_causal(target, &current)
+            .map_err(|_| LoroError::NotFoundError("Cannot find the state version".into()))?
+            .map(to_span)
+            .collect();
+        let forward = oplog
+            .iter_changes_causal(&current, target)
+            .map_err(|_| LoroError::NotFoundError("Cannot find the target version".into()))?
+            .map(to_span)
+            .collect();
+        drop(oplog);
+        *self.pending_work.lock().unwrap() = Some(PendingWork {
+            retreat,
+            forward,
+            expected: current,
+            target: target.clone(),
+            attach,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::EventTriggerKind;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[test]
+    fn import_and_checkout_in_slices() {
+        let a = LoroDoc::new_auto_commit();
+        a.set_peer_id(1).unwrap();
+        let text = a.get_text("text");
+        let mut v5 = Frontiers::default();
+        for i in 0..10 {
+            text.insert(0, &"a".repeat(10)).unwrap();
+            a.commit_then_renew();
+            if i == 4 {
+                v5 = a.oplog_frontiers();
+            }
+        }
+        a.get_map("map").insert("k", 1).unwrap();
+        a.commit_then_renew();
+
+        let b = LoroDoc::new_auto_commit();
+        b.set_peer_id(2).unwrap();
+        b.get_text("text").insert(0, "b").unwrap();
+        b.commit_then_renew();
+        let steps = Arc::new(AtomicUsize::new(0));
+        let steps_clone = steps.clone();
+        let _sub = b.subscribe_root(Arc::new(move |e| {
+            assert_eq!(e.event_meta.by, EventTriggerKind::Checkout);
+            steps_clone.fetch_add(1, Ordering::Relaxed);
+        }));
+        b.import_in_slices(&a.export_from(&Default::default()))
+            .unwrap();
+        assert!(b.is_detached());
+        assert!(b.has_pending_work());
+        assert_eq!(b.get_text("text").to_string(), "b");
+        let mut polls = 1;
+        while b.poll_pending_work(30).unwrap() == PollStatus::Pending {
+            assert!(b.get_text("text").len_unicode() <= 1 + 30 * polls);
+            polls += 1;
+        }
+        assert_eq!(polls, 4);
+        assert_eq!(steps.load(Ordering::Relaxed), 4);
+        assert!(!b.is_detached());
+        assert!(!b.has_pending_work());
+        a.import(&b.export_from(&Default::default())).unwrap();
+        assert_eq!(a.get_deep_value(), b.get_deep_value());
+
+        b.checkout_in_slices(&v5).unwrap();
+        while b.poll_pending_work(7).unwrap() == PollStatus::Pending {}
+        assert_eq!(b.state_frontiers(), v5);
+        assert!(b.is_detached());
+        assert_eq!(b.get_text("text").len_unicode(), 50);
+        assert!(b.get_map("map").is_empty());
+
+        // Another checkout drops the scheduled work
+        b.checkout_in_slices(&a.oplog_frontiers()).unwrap();
+        b.checkout_to_latest();
+        assert_eq!(b.poll_pending_work(1).unwrap(), PollStatus::Complete);
+        assert!(!b.has_pending_work());
+    }
+}
@@ -0,0 +1,192 @@
+//! Precomputing the checkouts of the versions that are likely to be visited.
+//!
+//! The first checkout on a large doc builds the history caches and the trackers of the diff
+//! calculator, which stalls a history slider for seconds on its first drag. The host hints the
+//! versions it's about to show with [`LoroDoc::prefetch_version`], and
+//! [`LoroDoc::run_maintenance`] builds the caches and computes the diffs from the current
+//! version to them in its idle slices. A checkout applies the precomputed diff if the doc is
+//! still at the version it was computed from, and the diffs from the other versions are
+//! computed again by the next maintenance.
+use loro_common::{LoroError, LoroResult};
+
+use crate::{
+    diff_calc::DiffMode,
+    event::InternalContainerDiff,
+    version::{shrink_frontiers, Frontiers},
+    LoroDoc,
+};
+
+/// The number of hinted versions that are kept. The oldest hint is dropped first.
+const MAX_PREFETCH_VERSIONS: usize = 8;
+
+#[derive(Debug, Default)]
+pub(crate) struct VersionPrefetch {
+    /// The hinted versions, from the oldest hint
+    versions: Vec<Frontiers>,
+    diffs: Vec<PrefetchedDiff>,
+}
+
+#[derive(Debug)]
+struct PrefetchedDiff {
+    from: Frontiers,
+    to: Frontiers,
+    diff: Vec<InternalContainerDiff>,
+    mode: DiffMode,
+}
+
+impl VersionPrefetch {
+    /// Take the diff precomputed from `from` to `to`
+    pub(crate) fn take_diff(
+        &mut self,
+        from: &Frontiers,
+        to: &Frontiers,
+    ) -> Option<(Vec<InternalContainerDiff>, DiffMode)> {
+        let i = self
+            .diffs
+            .iter()
+            .position(|d| &d.from == from && &d.to == to)?;
+        let PrefetchedDiff { diff, mode, .. } = self.diffs.swap_remove(i);
+        Some((diff, mode))
+    }
+
+    /// The next hinted version without a diff from `from`
+    fn next_version(&self, from: &Frontiers) -> Option<&Frontiers> {
+        self.versions
+            .iter()
+            .find(|v| *v != from && !self.diffs.iter().any(|d| &d.from == from && &d.to == *v))
+    }
+}
+
+impl LoroDoc {
+    /// Hint that the version is likely to be checked out soon, e.g. when the history slider
+    /// gets close to it.
+    ///
+    /// [`LoroDoc::run_maintenance`] precomputes the checkout from the current version to it,
+    /// and keeps the checkout caches while there are hints. Only the last few hints are kept.
+    pub fn prefetch_version(&self, frontiers: &Frontiers) -> LoroResult<()> {
+        let oplog = self.oplog.lock().unwrap();
+        for id in frontiers.iter() {
+            if !oplog.dag.contains(id) {
+                return Err(LoroError::FrontiersNotFound(id));
+            }
+        }
+
+        if oplog.dag.is_before_shallow_root(frontiers) {
+            return Err(LoroError::SwitchToVersionBeforeShallowRoot);
+        }
+
+        // Checkouts use the shrunk frontiers, so they are the key of the diffs
+        let frontiers = shrink_frontiers(frontiers, &oplog.dag)
+            .map_err(|_| LoroError::SwitchToVersionBeforeShallowRoot)?;
+        drop(oplog);
+        let mut prefetch = self.version_prefetch.lock().unwrap();
+        prefetch.versions.retain(|v| v != &frontiers);
+        prefetch.versions.push(frontiers);
+        if prefetch.versions.len() > MAX_PREFETCH_VERSIONS {
+            let dropped = prefetch.versions.remove(0);
+            prefetch.diffs.retain(|d| d.to != dropped);
+        }
+
+        Ok(())
+    }
+
+    /// Drop the hinted versions and their precomputed diffs
+    pub fn clear_prefetched_versions(&self) {
+        *self.version_prefetch.lock().unwrap() = Default::default();
+    }
+
+    pub(crate) fn has_prefetch_hints(&self) -> bool {
+        !self.version_prefetch.lock().unwrap().versions.is_empty()
+    }
+
+    /// The number of hinted versions without a diff from the current version
+    pub(crate) fn pending_prefetch(&self) -> usize {
+        let from = self.state.lock().unwrap().frontiers.clone();
+        let prefetch = self.version_prefetch.lock().unwrap();
+        prefetch
+            .versions
+            .iter()
+            .filter(|v| {
+                *v != &from && !prefetch.diffs.iter().any(|d| d.from == from && &d.to == *v)
+            })
+            .count()
+    }
+
+    /// Compute the diff to the next hinted version. Returns false if there is nothing left to
+    /// precompute.
+    pub(crate) fn prefetch_next_version(&self) -> bool {
+        let oplog = self.oplog.lock().unwrap();
+        let state = self.state.lock().unwrap();
+        let mut calc = self.diff_calculator.lock().unwrap();
+        let mut prefetch = self.version_prefetch.lock().unwrap();
+        let from = state.frontiers.clone();
+        prefetch.diffs.retain(|d| d.from == from);
+        let Some(to) = prefetch.next_version(&from).cloned() else {
+            return false;
+        };
+
+        let (Some(before), Some(after)) = (
+            oplog.dag.frontiers_to_vv(&from),
+            oplog.dag.frontiers_to_vv(&to),
+        ) else {
+            prefetch.versions.retain(|v| v != &to);
+            return true;
+        };
+
+        let (diff, mode) = calc.calc_diff_internal(&oplog, &before, &from, &after, &to, None);
+        prefetch.diffs.push(PrefetchedDiff {
+            from,
+            to,
+            diff,
+            mode,
+        });
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use loro_common::ID;
+
+    #[test]
+    fn checkout_prefetched_versions() {
+        let doc = LoroDoc::new_auto_commit();
+        doc.set_peer_id(1).unwrap();
+        let text = doc.get_text("text");
+        for i in 0..100 {
+            text.insert(i, "a").unwrap();
+            doc.get_map("map").insert("i", i as i64).unwrap();
+            doc.commit_then_renew();
+        }
+
+        let v10 = Frontiers::from_id(ID::new(1, 19));
+        let v50 = Frontiers::from_id(ID::new(1, 99));
+        doc.prefetch_version(&v10).unwrap();
+        doc.prefetch_version(&v50).unwrap();
+        assert!(doc
+            .prefetch_version(&Frontiers::from_id(ID::new(2, 0)))
+            .is_err());
+        assert_eq!(doc.pending_maintenance().prefetch_versions, 2);
+
+        let report = doc.run_maintenance(std::time::Duration::from_secs(10));
+        assert_eq!(report.prefetched_versions, 2);
+        assert!(report.pending.is_empty());
+        assert!(doc.has_history_cache());
+
+        doc.checkout(&v10).unwrap();
+        assert_eq!(text.to_string().len(), 10);
+        assert_eq!(doc.get_map("map").get("i"), Some(9.into()));
+        // The diff to the other version is computed again from the new version
+        assert_eq!(doc.pending_maintenance().prefetch_versions, 1);
+        doc.run_maintenance(std::time::Duration::from_secs(10));
+        doc.checkout(&v50).unwrap();
+        assert_eq!(text.to_string().len(), 50);
+        assert_eq!(doc.get_map("map").get("i"), Some(49.into()));
+
+        doc.checkout_to_latest();
+        assert_eq!(text.to_string().len(), 100);
+        doc.clear_prefetched_versions();
+        assert!(doc.pending_maintenance().caches);
+    }
+}
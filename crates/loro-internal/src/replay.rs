@@ -0,0 +1,99 @@
+//! Replay the history of a document op by op.
+//!
+//! [`LoroDoc::replay`] drives a visitor with the ops that are not included in a given version,
+//! in causal order. It returns the version that includes every visited op, which can be passed
+//! to the next call to resume the replay. It's designed for the consumers that process the
+//! history incrementally, e.g. a search indexer, without maintaining a shadow doc.
+use std::ops::ControlFlow;
+
+use either::Either;
+use loro_common::{ContainerID, HasCounterSpan, Lamport, ID};
+use rle::{HasLength, Sliceable};
+
+use crate::{
+    change::Timestamp, encoding::json_schema::encode_changes, json::JsonOpContent,
+    oplog::BlockChangeRef, version::VersionVector, LoroDoc,
+};
+
+/// An op visited by [`LoroDoc::replay`].
+///
+/// The content is resolved in the same way as the JSON encoding, so the positions of
+/// the list and text ops are the positions in the version that the op is based on.
+#[derive(Debug, Clone)]
+pub struct ReplayOp {
+    pub id: ID,
+    pub lamport: Lamport,
+    /// The timestamp of the change that contains the op
+    pub timestamp: Timestamp,
+    pub container: ContainerID,
+    pub content: JsonOpContent,
+}
+
+impl ReplayOp {
+    /// The number of atom ops in this op, e.g. the length of the inserted text
+    pub fn atom_len(&self) -> usize {
+        self.content.op_len()
+    }
+}
+
+impl LoroDoc {
+    /// Visit the ops that are not included in `from` in causal order.
+    ///
+    /// The visitor can return [`ControlFlow::Break`] to stop the replay, e.g. when it has
+    /// processed enough ops for this round. The returned version includes `from` and all the
+    /// visited ops (including the one that breaks), so it can be used as the `from` of the next
+    /// call to resume the replay.
+    ///
+    /// The ops before the shallow root are not available, so they are skipped.
+    ///
+    /// NOTE: The oplog is locked during the replay, so the visitor should not access the doc.
+    pub fn replay(
+        &self,
+        from: &VersionVector,
+        visitor: &mut dyn FnMut(ReplayOp) -> ControlFlow<()>,
+    ) -> VersionVector {
+        self.commit_then_renew();
+        let oplog = self.oplog.lock().unwrap();
+        let mut checkpoint = from.clone();
+        for (&peer, &counter) in oplog.dag.shallow_since_vv().iter() {
+            checkpoint.extend_to_include_end_id(ID::new(peer, counter));
+        }
+
+        let end = oplog.vv().clone();
+        let mut changes: Vec<BlockChangeRef> =
+            oplog.iter_changes_peer_by_peer(&checkpoint, &end).collect();
+        // Lamport order is a valid causal order
+        changes.sort_by_key(|c| (c.lamport, c.id.peer));
+        for change in changes {
+            let start = checkpoint.get(&change.id.peer).copied().unwrap_or(0);
+            if start >= change.ctr_end() {
+                continue;
+            }
+
+            let change = if start > change.id.counter {
+                let len = change.atom_len();
+                Either::Right(change.slice((start - change.id.counter) as usize, len))
+            } else {
+                Either::Left(change)
+            };
+
+            let json = encode_changes(&[change], &oplog.arena, None).pop().unwrap();
+            for op in json.ops {
+                let id = ID::new(json.id.peer, op.counter);
+                let op = ReplayOp {
+                    id,
+                    lamport: json.lamport + (op.counter - json.id.counter) as Lamport,
+                    timestamp: json.timestamp,
+                    container: op.container,
+                    content: op.content,
+                };
+                checkpoint.extend_to_include_end_id(id.inc(op.atom_len() as i32));
+                if visitor(op).is_break() {
+                    return checkpoint;
+                }
+            }
+        }
+
+        checkpoint
+    }
+}
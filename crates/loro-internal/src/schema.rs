@@ -0,0 +1,385 @@
+//! Infer a JSON Schema that describes the current shape of a document.
+//!
+//! The schema follows [JSON Schema 2020-12](https://json-schema.org/draft/2020-12/schema)
+//! and describes the JSON value of [`LoroDoc::get_deep_value`]. Each container is annotated
+//! with its type by the `x-loro-container` keyword, so that the schema can be used to
+//! document the containers of a doc as well as to validate its JSON value.
+//!
+//! The schema is inferred from the current state, so
+//!
+//! - the properties of a map are the keys that are currently present,
+//! - the items of a list are described by the union of the schemas of its current elements,
+//! - the schemas of maps with the same container type are merged, so a list of records
+//!   that gained new fields over time is described by one object schema.
+//!
+//! The nodes of a tree are nested in the `children` of their parents, so the schema of the
+//! nodes of each tree is defined in the `$defs` of the root schema and refers to itself.
+use std::collections::BTreeMap;
+
+use fxhash::FxHashMap;
+use loro_common::{
+    ContainerType, LoroValue, LORO_CONTAINER_REF_TAG, LORO_DECIMAL_TAG, LORO_TIMESTAMP_TAG,
+};
+
+use crate::{container::idx::ContainerIdx, state::DocState, LoroDoc};
+
+const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+const CONTAINER_KEYWORD: &str = "x-loro-container";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Schema {
+    /// Matches any value. It's the item schema of an empty list.
+    Any,
+    Null,
+    Boolean,
+    Integer,
+    Number,
+    String,
+    Binary,
+    Object {
+        container: Option<ContainerType>,
+        properties: BTreeMap<String, Schema>,
+    },
+    Array {
+        container: Option<ContainerType>,
+        items: Box<Schema>,
+    },
+    /// A text, counter or unknown container
+    Leaf(ContainerType),
+    AnyOf(Vec<Schema>),
+    /// The schema named so in the `$defs` of the root schema
+    Ref(String),
+}
+
+/// The schemas in the `$defs` of the root schema, by name
+type Defs = BTreeMap<String, Schema>;
+
+impl Schema {
+    fn is_compatible(&self, other: &Schema) -> bool {
+        match (self, other) {
+            (Schema::Object { container: a, .. }, Schema::Object { container: b, .. }) => a == b,
+            (Schema::Array { container: a, .. }, Schema::Array { container: b, .. }) => a == b,
+            (a, b) => a == b,
+        }
+    }
+
+    fn merge(self, other: Schema) -> Schema {
+        match (self, other) {
+            (Schema::Any, x) | (x, Schema::Any) => x,
+            (
+                Schema::Object {
+                    container,
+                    mut properties,
+                },
+                Schema::Object {
+                    container: other_container,
+                    properties: other_properties,
+                },
+            ) if container == other_container => {
+                for (key, schema) in other_properties {
+                    let merged = match properties.remove(&key) {
+                        Some(old) => old.merge(schema),
+                        None => schema,
+                    };
+                    properties.insert(key, merged);
+                }
+
+                Schema::Object {
+                    container,
+                    properties,
+                }
+            }
+            (
+                Schema::Array { container, items },
+                Schema::Array {
+                    container: other_container,
+                    items: other_items,
+                },
+            ) if container == other_container => Schema::Array {
+                container,
+                items: Box::new(items.merge(*other_items)),
+            },
+            (Schema::AnyOf(mut variants), Schema::AnyOf(others)) => {
+                for s in others {
+                    add_variant(&mut variants, s);
+                }
+                Schema::AnyOf(variants)
+            }
+            (Schema::AnyOf(mut variants), x) | (x, Schema::AnyOf(mut variants)) => {
+                add_variant(&mut variants, x);
+                Schema::AnyOf(variants)
+            }
+            (a, b) => {
+                if a == b {
+                    a
+                } else {
+                    Schema::AnyOf(vec![a, b])
+                }
+            }
+        }
+    }
+
+    fn into_value(self) -> LoroValue {
+        let mut map: FxHashMap<String, LoroValue> = FxHashMap::default();
+        match self {
+            Schema::Any => {}
+            Schema::Null => {
+                map.insert("type".into(), "null".into());
+            }
+            Schema::Boolean => {
+                map.insert("type".into(), "boolean".into());
+            }
+            Schema::Integer => {
+                map.insert("type".into(), "integer".into());
+            }
+            Schema::Number => {
+                map.insert("type".into(), "number".into());
+            }
+            Schema::String => {
+                map.insert("type".into(), "string".into());
+            }
+            Schema::Binary => {
+                // Binaries are serialized as arrays of bytes
+                map.insert("type".into(), "array".into());
+                map.insert(
+                    "items".into(),
+                    LoroValue::Map(
+                        vec![
+                            ("type".to_string(), LoroValue::from("integer")),
+                            ("minimum".to_string(), LoroValue::I64(0)),
+                            ("maximum".to_string(), LoroValue::I64(255)),
+                        ]
+                        .into(),
+                    ),
+                );
+            }
+            Schema::Object {
+                container,
+                properties,
+            } => {
+                map.insert("type".into(), "object".into());
+                if let Some(c) = container {
+                    map.insert(CONTAINER_KEYWORD.into(), c.to_string().into());
+                }
+                let properties: FxHashMap<String, LoroValue> = properties
+                    .into_iter()
+                    .map(|(k, v)| (k, v.into_value()))
+                    .collect();
+                map.insert("properties".into(), LoroValue::Map(properties.into()));
+            }
+            Schema::Array { container, items } => {
+                map.insert("type".into(), "array".into());
+                if let Some(c) = container {
+                    map.insert(CONTAINER_KEYWORD.into(), c.to_string().into());
+                }
+                map.insert("items".into(), items.into_value());
+            }
+            Schema::Leaf(c) => {
+                match c {
+                    ContainerType::Text => {
+                        map.insert("type".into(), "string".into());
+                    }
+                    #[cfg(feature = "counter")]
+                    ContainerType::Counter => {
+                        map.insert("type".into(), "number".into());
+                    }
+                    _ => {}
+                }
+                map.insert(CONTAINER_KEYWORD.into(), c.to_string().into());
+            }
+            Schema::AnyOf(variants) => {
+                let variants: Vec<LoroValue> =
+                    variants.into_iter().map(|x| x.into_value()).collect();
+                map.insert("anyOf".into(), LoroValue::List(variants.into()));
+            }
+            Schema::Ref(name) => {
+                map.insert("$ref".into(), format!("#/$defs/{}", name).into());
+            }
+        }
+
+        LoroValue::Map(map.into())
+    }
+}
+
+fn add_variant(variants: &mut Vec<Schema>, schema: Schema) {
+    if let Some(existing) = variants.iter_mut().find(|x| x.is_compatible(&schema)) {
+        let old = std::mem::replace(existing, Schema::Any);
+        *existing = old.merge(schema);
+    } else {
+        variants.push(schema);
+    }
+}
+
+fn merge_all(schemas: impl Iterator<Item = Schema>) -> Schema {
+    schemas.fold(Schema::Any, |acc, x| acc.merge(x))
+}
+
+/// The schema of a value serialized as the object `{tag: value}`
+fn tagged(tag: &str, value: Schema) -> Schema {
+    Schema::Object {
+        container: None,
+        properties: BTreeMap::from([(tag.to_string(), value)]),
+    }
+}
+
+fn infer_value(state: &mut DocState, defs: &mut Defs, value: &LoroValue) -> Schema {
+    match value {
+        LoroValue::Null => Schema::Null,
+        LoroValue::Bool(_) => Schema::Boolean,
+        LoroValue::Double(_) => Schema::Number,
+        LoroValue::I64(_) => Schema::Integer,
+        LoroValue::Binary(_) => Schema::Binary,
+        LoroValue::String(_) => Schema::String,
+        // The extended value types are serialized as tagged objects
+        LoroValue::ContainerRef(_) => tagged(LORO_CONTAINER_REF_TAG, Schema::String),
+        LoroValue::Timestamp(_) => tagged(LORO_TIMESTAMP_TAG, Schema::Integer),
+        LoroValue::Decimal(_) => tagged(LORO_DECIMAL_TAG, Schema::String),
+        LoroValue::List(list) => Schema::Array {
+            container: None,
+            items: Box::new(merge_all(list.iter().map(|x| infer_value(state, defs, x)))),
+        },
+        LoroValue::Map(map) => Schema::Object {
+            container: None,
+            properties: map
+                .iter()
+                .map(|(k, v)| (k.clone(), infer_value(state, defs, v)))
+                .collect(),
+        },
+        LoroValue::Container(id) => {
+            let idx = state.arena.register_container(id);
+            infer_container(state, defs, idx)
+        }
+    }
+}
+
+fn infer_container(state: &mut DocState, defs: &mut Defs, idx: ContainerIdx) -> Schema {
+    let ty = idx.get_type();
+    let value = state.get_value_by_idx(idx);
+    match ty {
+        ContainerType::Map => {
+            let Schema::Object { properties, .. } = infer_value(state, defs, &value) else {
+                unreachable!()
+            };
+            Schema::Object {
+                container: Some(ty),
+                properties,
+            }
+        }
+        ContainerType::List | ContainerType::MovableList => {
+            let Schema::Array { items, .. } = infer_value(state, defs, &value) else {
+                unreachable!()
+            };
+            Schema::Array {
+                container: Some(ty),
+                items,
+            }
+        }
+        ContainerType::Tree => {
+            let mut meta = Schema::Any;
+            infer_tree_nodes_meta(state, defs, &value, &mut meta);
+            let mut properties = BTreeMap::new();
+            properties.insert("id".to_string(), Schema::String);
+            properties.insert(
+                "parent".to_string(),
+                Schema::AnyOf(vec![Schema::String, Schema::Null]),
+            );
+            properties.insert("index".to_string(), Schema::Integer);
+            properties.insert("fractional_index".to_string(), Schema::String);
+            properties.insert(
+                "meta".to_string(),
+                meta.merge(Schema::Object {
+                    container: Some(ContainerType::Map),
+                    properties: BTreeMap::new(),
+                }),
+            );
+            let name = format!("treeNode{}", defs.len());
+            properties.insert(
+                "children".to_string(),
+                Schema::Array {
+                    container: None,
+                    items: Box::new(Schema::Ref(name.clone())),
+                },
+            );
+            defs.insert(
+                name.clone(),
+                Schema::Object {
+                    container: None,
+                    properties,
+                },
+            );
+            Schema::Array {
+                container: Some(ty),
+                items: Box::new(Schema::Ref(name)),
+            }
+        }
+        _ => Schema::Leaf(ty),
+    }
+}
+
+fn infer_tree_nodes_meta(
+    state: &mut DocState,
+    defs: &mut Defs,
+    nodes: &LoroValue,
+    meta: &mut Schema,
+) {
+    let Some(nodes) = nodes.as_list() else {
+        return;
+    };
+
+    for node in nodes.iter() {
+        let Some(node) = node.as_map() else {
+            continue;
+        };
+
+        if let Some(m) = node.get("meta") {
+            let schema = infer_value(state, defs, m);
+            *meta = std::mem::replace(meta, Schema::Any).merge(schema);
+        }
+
+        if let Some(children) = node.get("children") {
+            infer_tree_nodes_meta(state, defs, children, meta);
+        }
+    }
+}
+
+impl LoroDoc {
+    /// Infer a [JSON Schema](https://json-schema.org/) that describes the current shape
+    /// of the document.
+    ///
+    /// The schema describes the value of [`LoroDoc::get_deep_value`], and the type of each
+    /// container is annotated by the `x-loro-container` keyword. It's inferred from the
+    /// current state: map properties are the present keys, and list items are described by
+    /// the merged schema of the current elements.
+    pub fn infer_schema(&self) -> LoroValue {
+        let mut state = self.state.lock().unwrap();
+        let roots = state.visible_root_containers();
+        let mut properties = BTreeMap::new();
+        let mut defs = Defs::new();
+        for root in roots {
+            let id = state.arena.idx_to_id(root).unwrap();
+            let loro_common::ContainerID::Root { name, .. } = id else {
+                unreachable!()
+            };
+            properties.insert(
+                name.to_string(),
+                infer_container(&mut state, &mut defs, root),
+            );
+        }
+
+        let LoroValue::Map(mut schema) = (Schema::Object {
+            container: None,
+            properties,
+        })
+        .into_value() else {
+            unreachable!()
+        };
+        let schema_mut = schema.make_mut();
+        schema_mut.insert("$schema".into(), SCHEMA_DIALECT.into());
+        if !defs.is_empty() {
+            let defs: FxHashMap<String, LoroValue> =
+                defs.into_iter().map(|(k, v)| (k, v.into_value())).collect();
+            schema_mut.insert("$defs".into(), LoroValue::Map(defs.into()));
+        }
+        LoroValue::Map(schema)
+    }
+}
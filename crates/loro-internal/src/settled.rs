@@ -0,0 +1,153 @@
+//! Notifying when the doc has settled.
+//!
+//! Autosave and expensive recomputations should run once the doc stops changing, rather than
+//! after every event. [`LoroDoc::subscribe_settled`] fires when no commit or import has
+//! happened for a quiet period and there is no pending work. The doc has no clock of its own:
+//! the host drives the signal by calling [`LoroDoc::tick`] with the current time, e.g. from
+//! its frame loop or a timer, and the activity is detected at the granularity of the ticks.
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+
+use crate::{
+    utils::subscription::SubscriberSetWithQueue, version::Frontiers, LoroDoc, Subscription,
+};
+
+/// The callback of [`LoroDoc::subscribe_settled`]. The subscription is removed if it
+/// returns false.
+pub type SettledCallback = Box<dyn Fn() -> bool + Send + Sync + 'static>;
+
+type SettledCallbackInner = Box<dyn Fn(&SettledTick) -> bool + Send + Sync + 'static>;
+
+#[derive(Debug, Clone, Copy)]
+struct SettledTick {
+    /// The number of the activities seen by the ticks
+    seq: u64,
+    /// The time since the last activity
+    quiet_for: i64,
+}
+
+#[derive(Debug, Default)]
+struct Tracker {
+    frontiers: Frontiers,
+    last_activity: i64,
+    seq: u64,
+}
+
+pub(crate) struct SettledSignal {
+    tracker: Mutex<Tracker>,
+    subs: SubscriberSetWithQueue<(), SettledCallbackInner, SettledTick>,
+}
+
+impl Default for SettledSignal {
+    fn default() -> Self {
+        Self {
+            tracker: Default::default(),
+            subs: SubscriberSetWithQueue::new(),
+        }
+    }
+}
+
+impl LoroDoc {
+    /// Subscribe to the settled signal, which fires once `quiet_ms` after the last commit or
+    /// import, if the doc has no pending ops and no work scheduled by
+    /// [`LoroDoc::import_in_slices`] or [`LoroDoc::checkout_in_slices`].
+    ///
+    /// It fires once per burst of activity after the subscription, from a call of
+    /// [`LoroDoc::tick`].
+    pub fn subscribe_settled(&self, quiet_ms: i64, callback: SettledCallback) -> Subscription {
+        let fired = AtomicU64::new(self.settled.tracker.lock().unwrap().seq);
+        let (s, enable) = self.settled.subs.inner().insert(
+            (),
+            Box::new(move |tick| {
+                if tick.quiet_for < quiet_ms || fired.load(Ordering::Relaxed) == tick.seq {
+                    return true;
+                }
+
+                fired.store(tick.seq, Ordering::Relaxed);
+                callback()
+            }),
+        );
+        enable();
+        s
+    }
+
+    /// Drive the settled signal with the current time `now`, in milliseconds.
+    ///
+    /// The commits and imports since the previous tick are recorded as an activity at `now`.
+    /// The uncommitted ops and the scheduled work are recorded as an activity at every tick.
+    pub fn tick(&self, now: i64) {
+        let frontiers = self.oplog_frontiers();
+        let busy = self.get_pending_txn_len() > 0 || self.has_pending_work();
+        let tick = {
+            let mut tracker = self.settled.tracker.lock().unwrap();
+            if busy || tracker.frontiers != frontiers {
+                tracker.frontiers = frontiers;
+                tracker.last_activity = now;
+                tracker.seq += 1;
+            }
+
+            SettledTick {
+                seq: tracker.seq,
+                quiet_for: now - tracker.last_activity,
+            }
+        };
+
+        if busy || self.settled.subs.inner().is_empty() {
+            return;
+        }
+
+        self.settled.subs.emit(&(), tick);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{atomic::AtomicUsize, Arc};
+
+    #[test]
+    fn fire_once_after_quiet_period() {
+        let doc = LoroDoc::new_auto_commit();
+        let count = Arc::new(AtomicUsize::new(0));
+        let count_clone = count.clone();
+        let _sub = doc.subscribe_settled(
+            100,
+            Box::new(move || {
+                count_clone.fetch_add(1, Ordering::Relaxed);
+                true
+            }),
+        );
+
+        doc.tick(0);
+        doc.tick(500);
+        // Nothing happened yet
+        assert_eq!(count.load(Ordering::Relaxed), 0);
+
+        doc.get_text("text").insert(0, "a").unwrap();
+        // The pending ops keep the doc busy
+        doc.tick(1000);
+        doc.tick(1200);
+        assert_eq!(count.load(Ordering::Relaxed), 0);
+        doc.commit_then_renew();
+        doc.tick(1250);
+        doc.tick(1340);
+        assert_eq!(count.load(Ordering::Relaxed), 0);
+        doc.tick(1350);
+        doc.tick(2000);
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+
+        let other = LoroDoc::new_auto_commit();
+        other.get_text("text").insert(0, "b").unwrap();
+        doc.import(
+            &other
+                .export(crate::encoding::ExportMode::all_updates())
+                .unwrap(),
+        )
+        .unwrap();
+        doc.tick(3000);
+        doc.tick(3100);
+        assert_eq!(count.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,189 @@
+//! Docs edited by a single peer.
+//!
+//! Many docs are generated by a server and only read by the clients. Their history has the
+//! ops of one peer, so a version is just the number of ops of that peer, and its frontiers
+//! are the id of the last of them. [`LoroDoc::set_single_writer`] enforces it: the doc rejects
+//! the local ops and the imported updates of any other peer with
+//! [`LoroError::SingleWriterViolation`], so the versions can be exchanged as the sequence
+//! numbers of [`LoroDoc::seq`] instead of version vectors.
+//!
+//! There is no single-writer encoding. The updates and the snapshots of a single-writer doc
+//! are encoded like the other ones, with a peer table and full version vectors and frontiers,
+//! which have a single entry here. Skipping them would need a new encoding that every reader
+//! must understand, while it saves only a few bytes per blob. Only the versions exchanged by
+//! the application can be shortened to `seq`.
+use loro_common::{Counter, LoroError, LoroResult, PeerID, ID};
+
+use crate::{
+    version::{Frontiers, VersionVector},
+    LoroDoc,
+};
+
+impl LoroDoc {
+    /// Enable or disable the single-writer mode, which is disabled by default.
+    ///
+    /// In this mode, the doc only accepts the ops of the peer whose ops are in the history.
+    /// The readers of a single-writer doc can enable it too: their own edits are rejected
+    /// once they have imported the ops of the writer.
+    ///
+    /// It returns an error if the history already has the ops of several peers.
+    pub fn set_single_writer(&self, enabled: bool) -> LoroResult<()> {
+        if enabled {
+            let vv = self.oplog_vv();
+            let mut peers: Vec<PeerID> = vv
+                .iter()
+                .filter(|(_, counter)| **counter > 0)
+                .map(|(peer, _)| *peer)
+                .collect();
+            peers.sort_unstable();
+            if let [writer, peer, ..] = peers[..] {
+                return Err(LoroError::SingleWriterViolation { writer, peer });
+            }
+        }
+
+        self.config.set_single_writer(enabled);
+        Ok(())
+    }
+
+    pub fn is_single_writer(&self) -> bool {
+        self.config.single_writer()
+    }
+
+    /// The peer of the ops in the history of a single-writer doc, or `None` if the history
+    /// is empty.
+    pub fn writer(&self) -> Option<PeerID> {
+        writer_of(&self.oplog_vv())
+    }
+
+    /// The number of ops in the history, including the pending ones.
+    ///
+    /// In the single-writer mode, it identifies the version of the doc: the doc at `seq` has
+    /// the first `seq` ops of the writer.
+    pub fn seq(&self) -> Counter {
+        self.oplog_vv().values().sum()
+    }
+
+    /// The frontiers of the version that has the first `seq` ops of the writer.
+    ///
+    /// It returns an error if the doc is not in the single-writer mode, or if the doc doesn't
+    /// have `seq` ops.
+    pub fn frontiers_at_seq(&self, seq: Counter) -> LoroResult<Frontiers> {
+        if !self.is_single_writer() {
+            return Err(LoroError::ArgErr(
+                "The doc is not in the single-writer mode".into(),
+            ));
+        }
+        if seq == 0 {
+            return Ok(Frontiers::default());
+        }
+
+        let vv = self.oplog_vv();
+        let id = ID::new(writer_of(&vv).unwrap_or_default(), seq - 1);
+        if !vv.includes_id(id) {
+            return Err(LoroError::FrontiersNotFound(id));
+        }
+
+        Ok(Frontiers::from_id(id))
+    }
+
+    /// Check that importing `bytes` doesn't bring the ops of a second peer into a
+    /// single-writer doc
+    pub(crate) fn check_single_writer_import(&self, bytes: &[u8]) -> LoroResult<()> {
+        if !self.config.single_writer() {
+            return Ok(());
+        }
+
+        let meta = LoroDoc::decode_import_blob_meta(bytes, false)?;
+        let peers = meta
+            .partial_end_vv
+            .iter()
+            .filter(|(peer, end)| **end > meta.partial_start_vv.get(peer).copied().unwrap_or(0))
+            .map(|(peer, _)| *peer);
+        self.check_single_writer_peers(peers)
+    }
+
+    /// Check that the ops of `peers` can be added to the history of a single-writer doc
+    pub(crate) fn check_single_writer_peers(
+        &self,
+        peers: impl IntoIterator<Item = PeerID>,
+    ) -> LoroResult<()> {
+        if !self.config.single_writer() {
+            return Ok(());
+        }
+
+        let mut writer = writer_of(&self.oplog_vv());
+        for peer in peers {
+            match writer {
+                Some(writer) if writer != peer => {
+                    return Err(LoroError::SingleWriterViolation { writer, peer });
+                }
+                _ => writer = Some(peer),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Check that a local op of `peer` can be added to the history `vv` of a single-writer doc
+pub(crate) fn check_single_writer(vv: &VersionVector, peer: PeerID) -> LoroResult<()> {
+    match writer_of(vv) {
+        Some(writer) if writer != peer => Err(LoroError::SingleWriterViolation { writer, peer }),
+        _ => Ok(()),
+    }
+}
+
+fn writer_of(vv: &VersionVector) -> Option<PeerID> {
+    vv.iter()
+        .find(|(_, counter)| **counter > 0)
+        .map(|(peer, _)| *peer)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoding::ExportMode;
+
+    #[test]
+    fn single_writer_rejects_other_peers() {
+        let writer = LoroDoc::new_auto_commit();
+        writer.set_peer_id(1).unwrap();
+        writer.set_single_writer(true).unwrap();
+        writer.get_text("text").insert(0, "hello").unwrap();
+        writer.commit_then_renew();
+        assert_eq!(writer.seq(), 5);
+        assert_eq!(writer.writer(), Some(1));
+
+        let reader = LoroDoc::new_auto_commit();
+        reader.set_peer_id(2).unwrap();
+        reader.set_single_writer(true).unwrap();
+        reader
+            .import(&writer.export(ExportMode::all_updates()).unwrap())
+            .unwrap();
+        assert_eq!(
+            reader.get_text("text").insert(0, "x"),
+            Err(LoroError::SingleWriterViolation { writer: 1, peer: 2 })
+        );
+        assert_eq!(reader.get_text("text").to_string(), "hello");
+        assert_eq!(
+            reader.frontiers_at_seq(3).unwrap(),
+            Frontiers::from_id(ID::new(1, 2))
+        );
+        assert!(reader.frontiers_at_seq(6).is_err());
+
+        let other = LoroDoc::new_auto_commit();
+        other.set_peer_id(3).unwrap();
+        other.get_text("text").insert(0, "x").unwrap();
+        other.commit_then_renew();
+        let bytes = other.export(ExportMode::all_updates()).unwrap();
+        assert_eq!(
+            reader.import(&bytes).unwrap_err(),
+            LoroError::SingleWriterViolation { writer: 1, peer: 3 }
+        );
+        assert_eq!(reader.seq(), 5);
+
+        reader.set_single_writer(false).unwrap();
+        reader.import(&bytes).unwrap();
+        assert!(reader.set_single_writer(true).is_err());
+    }
+}
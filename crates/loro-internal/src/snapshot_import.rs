@@ -0,0 +1,109 @@
+//! Importing large snapshots with progress reports.
+//!
+//! [`LoroDoc::import`] decodes a snapshot lazily, so the first reads of the history or of the
+//! containers pay for the decoding at unpredictable times. Embedders that load big snapshots
+//! on the main thread would rather pay for it upfront, in small steps, and render the progress
+//! between them. [`LoroDoc::import_snapshot_with_progress`] parses the change blocks one by
+//! one, reporting the number of the loaded ops after each block, and then decodes the state.
+use std::io::Read;
+
+use loro_common::{LoroError, LoroResult};
+
+use crate::{
+    encoding::{decode_snapshot, parse_header_and_body, EncodeMode, ImportStatus},
+    LoroDoc,
+};
+
+impl LoroDoc {
+    /// Import a snapshot from `reader`, eagerly decoding its history and state.
+    ///
+    /// `progress` is called with `(loaded_ops, total_ops)` after each change block is parsed,
+    /// and once more with `(total_ops, total_ops)` after the state is decoded. No lock of the
+    /// doc is held when it's called.
+    ///
+    /// If the doc is not empty or the data is not a snapshot, it's imported like
+    /// [`LoroDoc::import`], and `progress` is only called once at the end.
+    pub fn import_snapshot_with_progress(
+        &self,
+        mut reader: impl Read,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> LoroResult<ImportStatus> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(|e| {
+            LoroError::DecodeError(format!("Failed to read the snapshot: {e}").into_boxed_str())
+        })?;
+        let parsed = parse_header_and_body(&bytes, true)?;
+        if parsed.mode != EncodeMode::FastSnapshot || !self.can_reset_with_snapshot() {
+            let status = self.import(&bytes)?;
+            let total = self.len_ops();
+            progress(total, total);
+            return Ok(status);
+        }
+
+        let (options, txn) = self.commit_then_stop();
+        drop(txn);
+        let ans = decode_snapshot(self, parsed.mode, parsed.body).and_then(|status| {
+            let store = self.oplog().lock().unwrap().change_store().clone();
+            let mut total = 0;
+            store.parse_all_blocks(&mut |loaded, all| {
+                total = all;
+                progress(loaded, all);
+            })?;
+            self.app_state()
+                .lock()
+                .unwrap()
+                .ensure_all_alive_containers();
+            progress(total, total);
+            Ok(status)
+        });
+        self.renew_txn_if_auto_commit(options);
+        if ans.is_ok() {
+            self.emit_version_change();
+            self.check_invariants();
+        }
+
+        ans
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ListHandler;
+
+    #[test]
+    fn report_progress_per_block() {
+        let doc = LoroDoc::new_auto_commit();
+        doc.set_peer_id(1).unwrap();
+        let list: ListHandler = doc.get_list("list");
+        for i in 0..10_000 {
+            list.push(i).unwrap();
+            if i % 100 == 0 {
+                doc.commit_then_renew();
+            }
+        }
+        doc.commit_then_renew();
+        let snapshot = doc.export(crate::encoding::ExportMode::Snapshot).unwrap();
+
+        let new_doc = LoroDoc::new_auto_commit();
+        let mut calls = Vec::new();
+        new_doc
+            .import_snapshot_with_progress(snapshot.as_slice(), &mut |loaded, total| {
+                calls.push((loaded, total))
+            })
+            .unwrap();
+        assert!(calls.len() >= 2);
+        assert!(calls.windows(2).all(|w| w[0].0 <= w[1].0));
+        assert_eq!(calls.last(), Some(&(10_000, 10_000)));
+        assert_eq!(new_doc.get_deep_value(), doc.get_deep_value());
+
+        // Fall back to the normal import for a non-empty doc
+        let mut calls = Vec::new();
+        new_doc
+            .import_snapshot_with_progress(snapshot.as_slice(), &mut |loaded, total| {
+                calls.push((loaded, total))
+            })
+            .unwrap();
+        assert_eq!(calls, vec![(10_000, 10_000)]);
+    }
+}
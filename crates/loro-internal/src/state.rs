@@ -1,10 +1,7 @@
 use std::{
     borrow::Cow,
     io::Write,
-    sync::{
-        atomic::{AtomicU64, Ordering},
-        Arc, Mutex, RwLock, Weak,
-    },
+    sync::{atomic::AtomicU64, Arc, Mutex, RwLock, Weak},
 };
 
 use container_store::ContainerStore;
@@ -12,24 +9,28 @@ use dead_containers_cache::DeadContainersCache;
 use enum_as_inner::EnumAsInner;
 use enum_dispatch::enum_dispatch;
 use fxhash::{FxHashMap, FxHashSet};
+use generic_btree::rle::HasLength;
 use itertools::Itertools;
 use loro_common::{ContainerID, LoroError, LoroResult, TreeID};
 use loro_delta::DeltaItem;
 use tracing::{info_span, instrument, warn};
 
 use crate::{
-    configure::{Configure, DefaultRandom, SecureRandomGenerator},
+    configure::Configure,
     container::{idx::ContainerIdx, richtext::config::StyleConfigMap, ContainerIdRaw},
     cursor::Cursor,
     delta::TreeExternalDiff,
     diff_calc::{DiffCalculator, DiffMode},
     encoding::{StateSnapshotDecodeContext, StateSnapshotEncoder},
-    event::{Diff, EventTriggerKind, Index, InternalContainerDiff, InternalDiff},
+    event::{
+        Diff, EventTriggerKind, Index, InternalContainerDiff, InternalDiff, ParagraphId, TextDiff,
+    },
     fx_map,
     handler::ValueOrHandler,
     id::PeerID,
     lock::{LoroLockGroup, LoroMutex},
     op::{Op, RawOp},
+    partial_checkout::PartialCheckout,
     version::Frontiers,
     ContainerDiff, ContainerType, DocDiff, InternalString, LoroDocInner, LoroValue, OpLog,
 };
@@ -75,11 +76,21 @@ pub struct DocState {
     // txn related stuff
     in_txn: bool,
     changed_idx_in_txn: FxHashSet<ContainerIdx>,
+    /// The states of the containers before they are changed by the current transaction.
+    ///
+    /// It's only recorded inside [`crate::LoroDoc::batch`], so that the uncommitted changes
+    /// can be rolled back.
+    txn_backup: Option<FxHashMap<ContainerIdx, Option<State>>>,
 
     // diff related stuff
     event_recorder: EventRecorder,
 
     dead_containers_cache: DeadContainersCache,
+
+    /// The containers whose state is checked out to a version other than [`DocState::frontiers`].
+    ///
+    /// See [`crate::LoroDoc::checkout_containers`]
+    pub(crate) partial_checkout: Option<PartialCheckout>,
 }
 
 impl std::fmt::Debug for DocState {
@@ -93,7 +104,6 @@ impl std::fmt::Debug for DocState {
 #[derive(Clone, Copy)]
 pub(crate) struct ContainerCreationContext<'a> {
     pub configure: &'a Configure,
-    pub peer: PeerID,
 }
 
 pub(crate) struct DiffApplyContext<'a> {
@@ -311,8 +321,8 @@ impl State {
         Self::RichtextState(Box::new(RichtextState::new(idx, config)))
     }
 
-    pub fn new_tree(idx: ContainerIdx, peer: PeerID) -> Self {
-        Self::TreeState(Box::new(TreeState::new(idx, peer)))
+    pub fn new_tree(idx: ContainerIdx) -> Self {
+        Self::TreeState(Box::new(TreeState::new(idx)))
     }
 
     pub fn new_unknown(idx: ContainerIdx) -> Self {
@@ -356,13 +366,13 @@ impl DocState {
         config: Configure,
         lock_group: &LoroLockGroup,
     ) -> Arc<LoroMutex<Self>> {
-        let peer = DefaultRandom.next_u64();
+        let peer = config.next_random_u64();
         // TODO: maybe we should switch to certain version in oplog?
 
         let peer = Arc::new(AtomicU64::new(peer));
         Arc::new(lock_group.new_lock(
             Self {
-                store: ContainerStore::new(arena.clone(), config.clone(), peer.clone()),
+                store: ContainerStore::new(arena.clone(), config.clone()),
                 peer,
                 arena,
                 frontiers: Frontiers::default(),
@@ -370,8 +380,10 @@ impl DocState {
                 config,
                 in_txn: false,
                 changed_idx_in_txn: FxHashSet::default(),
+                txn_backup: None,
                 event_recorder: Default::default(),
                 dead_containers_cache: Default::default(),
+                partial_checkout: None,
             },
             crate::lock::LockKind::DocState,
         ))
@@ -383,8 +395,8 @@ impl DocState {
         arena: SharedArena,
         config: Configure,
     ) -> Arc<Mutex<Self>> {
-        let peer = Arc::new(AtomicU64::new(DefaultRandom.next_u64()));
-        let store = self.store.fork(arena.clone(), peer.clone(), config.clone());
+        let peer = Arc::new(AtomicU64::new(config.next_random_u64()));
+        let store = self.store.fork(arena.clone(), config.clone());
         Arc::new(Mutex::new(Self {
             peer,
             frontiers: self.frontiers.clone(),
@@ -394,11 +406,54 @@ impl DocState {
             doc,
             in_txn: false,
             changed_idx_in_txn: FxHashSet::default(),
+            txn_backup: None,
             event_recorder: Default::default(),
             dead_containers_cache: Default::default(),
+            partial_checkout: None,
         }))
     }
 
+    /// Fork the state for reading it without holding the lock of this state.
+    ///
+    /// The fork shares the arena and the encoded states of the containers with this state,
+    /// so it only encodes the containers that changed since they were last encoded. The
+    /// fork decodes the states lazily when they are read. It's not attached to a doc, so
+    /// it must not be edited or used to emit events.
+    pub(crate) fn fork_readonly(&mut self) -> Self {
+        Self {
+            peer: Arc::new(AtomicU64::new(
+                self.peer.load(std::sync::atomic::Ordering::Relaxed),
+            )),
+            frontiers: self.frontiers.clone(),
+            store: self.store.fork_readonly(),
+            arena: self.arena.clone(),
+            config: self.config.clone(),
+            doc: Weak::new(),
+            in_txn: false,
+            changed_idx_in_txn: FxHashSet::default(),
+            txn_backup: None,
+            event_recorder: Default::default(),
+            dead_containers_cache: Default::default(),
+            partial_checkout: None,
+        }
+    }
+
+    /// Whether the state of the container is in the store
+    pub(crate) fn has_container_state(&mut self, idx: ContainerIdx) -> bool {
+        self.store.contains(idx)
+    }
+
+    /// Encode at most `max` of the container states that changed since they were last encoded.
+    /// Returns the number of encoded states.
+    pub(crate) fn encode_some_states(&mut self, max: usize) -> usize {
+        self.store.flush_some(max)
+    }
+
+    /// The number of the container states that changed since they were last encoded
+    pub(crate) fn unencoded_state_count(&self) -> usize {
+        self.store.unflushed_len()
+    }
+
     pub fn start_recording(&mut self) {
         if self.is_recording() {
             return;
@@ -420,13 +475,14 @@ impl DocState {
 
     pub fn refresh_peer_id(&mut self) {
         self.peer.store(
-            DefaultRandom.next_u64(),
+            self.config.next_random_u64(),
             std::sync::atomic::Ordering::Relaxed,
         );
     }
 
     /// Take all the diffs that are recorded and convert them to events.
     pub fn take_events(&mut self) -> Vec<DocDiff> {
+        self.event_recorder.coalesced_imports = 0;
         if !self.is_recording() {
             return vec![];
         }
@@ -435,6 +491,24 @@ impl DocState {
         std::mem::take(&mut self.event_recorder.events)
     }
 
+    /// Count an import for the import event coalescing window.
+    ///
+    /// Return whether the events should be emitted now. If not, the diffs stay in the
+    /// recorder and will be composed with the diffs of the following imports.
+    pub(crate) fn should_emit_import_events(&mut self, window: usize) -> bool {
+        if window <= 1 || !self.is_recording() {
+            return true;
+        }
+
+        self.event_recorder.coalesced_imports += 1;
+        self.event_recorder.coalesced_imports >= window
+    }
+
+    /// Whether there are events held back by the import event coalescing window.
+    pub(crate) fn has_coalesced_imports(&self) -> bool {
+        self.event_recorder.coalesced_imports > 0
+    }
+
     /// Record the next diff.
     /// Caller should call [pre_txn] before calling this.
     ///
@@ -596,7 +670,10 @@ impl DocState {
             }
 
             let idx = diff.idx;
-            let internal_diff = std::mem::take(&mut diff.diff);
+            let mut internal_diff = std::mem::take(&mut diff.diff);
+            if let crate::event::DiffVariant::Internal(d) = &mut internal_diff {
+                self.arena.intern_internal_diff(d);
+            }
             match &internal_diff {
                 crate::event::DiffVariant::None => {
                     if is_recording {
@@ -710,6 +787,7 @@ impl DocState {
     pub fn apply_local_op(&mut self, raw_op: &RawOp, op: &Op) -> LoroResult<()> {
         // set parent first, `MapContainer` will only be created for TreeID that does not contain
         self.set_container_parent_by_raw_op(raw_op);
+        self.backup_before_local_change(op.container);
         let state = self.store.get_or_create_mut(op.container);
         if self.in_txn {
             self.changed_idx_in_txn.insert(op.container);
@@ -731,6 +809,52 @@ impl DocState {
         self.in_txn = false;
     }
 
+    /// Start recording the states of the containers before they are changed by local ops.
+    pub(crate) fn start_txn_backup(&mut self) {
+        self.txn_backup = Some(FxHashMap::default());
+    }
+
+    pub(crate) fn stop_txn_backup(&mut self) {
+        self.txn_backup = None;
+    }
+
+    #[cfg(test)]
+    pub(crate) fn is_txn_backup_enabled(&self) -> bool {
+        self.txn_backup.is_some()
+    }
+
+    fn backup_before_local_change(&mut self, idx: ContainerIdx) {
+        let Some(backup) = self.txn_backup.as_mut() else {
+            return;
+        };
+
+        if backup.contains_key(&idx) {
+            return;
+        }
+
+        let state = self
+            .store
+            .get_container_mut(idx)
+            .map(|s| s.fork(&self.config));
+        backup.insert(idx, state);
+    }
+
+    /// Restore the recorded states of the containers changed by the uncommitted ops.
+    ///
+    /// The caller should make sure the transaction is aborted.
+    pub(crate) fn rollback_txn_backup(&mut self) {
+        let Some(backup) = self.txn_backup.as_mut() else {
+            return;
+        };
+
+        for (idx, state) in std::mem::take(backup) {
+            self.store.replace_state(idx, state);
+        }
+
+        self.changed_idx_in_txn.clear();
+        self.dead_containers_cache.clear();
+    }
+
     pub fn iter_and_decode_all(&mut self) -> impl Iterator<Item = &mut State> {
         self.store.iter_and_decode_all()
     }
@@ -763,6 +887,10 @@ impl DocState {
 
     pub(crate) fn commit_txn(&mut self, new_frontiers: Frontiers, diff: Option<InternalDocDiff>) {
         self.in_txn = false;
+        if let Some(backup) = self.txn_backup.as_mut() {
+            // The committed changes cannot be rolled back
+            backup.clear();
+        }
         self.frontiers = new_frontiers;
         if self.is_recording() {
             self.record_diff(diff.unwrap());
@@ -869,7 +997,6 @@ impl DocState {
                             idx,
                             ContainerCreationContext {
                                 configure: &self.config,
-                                peer: self.peer.load(Ordering::Relaxed),
                             },
                         )
                         .to_diff(&self.doc)
@@ -961,8 +1088,16 @@ impl DocState {
         !self.in_txn && self.arena.can_import_snapshot() && self.store.can_import_snapshot()
     }
 
+    /// The root containers shown in the values of the doc, i.e. without the reserved
+    /// [`crate::FROZEN_ROOT_NAME`] map
+    pub(crate) fn visible_root_containers(&self) -> Vec<ContainerIdx> {
+        let mut roots = self.arena.root_containers();
+        roots.retain(|idx| !crate::freeze::is_frozen_root(&self.arena.idx_to_id(*idx).unwrap()));
+        roots
+    }
+
     pub fn get_value(&self) -> LoroValue {
-        let roots = self.arena.root_containers();
+        let roots = self.visible_root_containers();
         let ans: loro_common::LoroMapValue = roots
             .into_iter()
             .map(|idx| {
@@ -981,12 +1116,21 @@ impl DocState {
     }
 
     pub fn get_deep_value(&mut self) -> LoroValue {
-        let roots = self.arena.root_containers();
+        let roots = self.visible_root_containers();
+        self.get_deep_value_of_roots(roots)
+    }
+
+    /// Get the deep value of the doc that only includes the given root containers
+    pub(crate) fn get_deep_value_of_roots(&mut self, roots: Vec<ContainerIdx>) -> LoroValue {
         let mut ans = FxHashMap::with_capacity_and_hasher(roots.len(), Default::default());
         for root_idx in roots {
             let id = self.arena.idx_to_id(root_idx).unwrap();
             match id {
                 loro_common::ContainerID::Root { name, .. } => {
+                    if self.is_shadowed_root(&ans, &name, root_idx) {
+                        continue;
+                    }
+
                     ans.insert(name.to_string(), self.get_container_deep_value(root_idx));
                 }
                 loro_common::ContainerID::Normal { .. } => {
@@ -998,13 +1142,35 @@ impl DocState {
         LoroValue::Map(ans.into())
     }
 
+    /// Whether the root container should not replace the root with the same name in `ans`.
+    ///
+    /// Roots of different types may share a name, e.g. after a list is upgraded to a
+    /// movable list by [`crate::LoroDoc::upgrade_container`]. An empty root never hides
+    /// a non-empty one, so that the upgraded container stays visible.
+    fn is_shadowed_root(
+        &mut self,
+        ans: &FxHashMap<String, LoroValue>,
+        name: &str,
+        root_idx: ContainerIdx,
+    ) -> bool {
+        ans.contains_key(name)
+            && self
+                .store
+                .get_container_mut(root_idx)
+                .map_or(true, |s| s.is_state_empty())
+    }
+
     pub fn get_deep_value_with_id(&mut self) -> LoroValue {
-        let roots = self.arena.root_containers();
+        let roots = self.visible_root_containers();
         let mut ans = FxHashMap::with_capacity_and_hasher(roots.len(), Default::default());
         for root_idx in roots {
             let id = self.arena.idx_to_id(root_idx).unwrap();
             match id.clone() {
                 loro_common::ContainerID::Root { name, .. } => {
+                    if self.is_shadowed_root(&ans, &name, root_idx) {
+                        continue;
+                    }
+
                     ans.insert(
                         name.to_string(),
                         self.get_container_deep_value_with_id(root_idx, Some(id)),
@@ -1269,13 +1435,21 @@ impl DocState {
                 let idx = container;
                 let id = self.arena.get_container_id(idx).unwrap();
                 let is_unknown = id.is_unknown();
+                let diff = diff.into_external().unwrap();
+                let paragraph_changed = match &diff {
+                    Diff::Text(text) if self.config.text_paragraph_ids() => {
+                        self.get_changed_paragraphs(idx, text)
+                    }
+                    _ => Vec::new(),
+                };
 
                 ContainerDiff {
                     id,
                     idx,
-                    diff: diff.into_external().unwrap(),
+                    diff,
                     is_unknown,
                     path,
+                    paragraph_changed,
                 }
             })
             .collect();
@@ -1301,6 +1475,37 @@ impl DocState {
         }
     }
 
+    /// Find the paragraphs of the text touched by the diff, which must be applied to the state.
+    fn get_changed_paragraphs(&mut self, idx: ContainerIdx, diff: &TextDiff) -> Vec<ParagraphId> {
+        let mut ranges = Vec::new();
+        let mut index = 0;
+        for item in diff.iter() {
+            match item {
+                DeltaItem::Retain { len, attr } => {
+                    if !attr.0.is_empty() {
+                        ranges.push(index..index + len);
+                    }
+                    index += len;
+                }
+                DeltaItem::Replace { value, delete, .. } => {
+                    let len = value.rle_len();
+                    if len > 0 || *delete > 0 {
+                        ranges.push(index..index + len);
+                    }
+                    index += len;
+                }
+            }
+        }
+
+        let Some(state) = self.store.get_container_mut(idx) else {
+            return Vec::new();
+        };
+        state
+            .as_richtext_state_mut()
+            .unwrap()
+            .get_paragraphs_in_event_ranges(&ranges)
+    }
+
     pub(crate) fn get_reachable(&mut self, id: &ContainerID) -> bool {
         if matches!(id, ContainerID::Root { .. }) {
             return true;
@@ -1475,9 +1680,7 @@ impl DocState {
     }
 
     pub fn create_state(&self, idx: ContainerIdx) -> State {
-        let config = &self.config;
-        let peer = self.peer.load(std::sync::atomic::Ordering::Relaxed);
-        create_state_(idx, config, peer)
+        create_state_(idx, &self.config)
     }
 
     pub fn create_unknown_state(&self, idx: ContainerIdx) -> State {
@@ -1552,7 +1755,7 @@ impl DocState {
                             let Some(LoroValue::Container(c)) = l.get(*index.as_seq()?) else {
                                 return None;
                             };
-                            state_idx = CurContainer::Container(self.arena.register_container(c));
+                            state_idx = CurContainer::Container(self.arena.register_container(&c));
                         }
                         State::MovableListState(l) => {
                             let Some(LoroValue::Container(c)) =
@@ -1642,7 +1845,7 @@ impl DocState {
         let parent_state = self.store.get_or_create_mut(parent_idx);
         let index = path.last().unwrap();
         let value: LoroValue = match parent_state {
-            State::ListState(l) => l.get(*index.as_seq()?).cloned()?,
+            State::ListState(l) => l.get(*index.as_seq()?)?,
             State::MovableListState(l) => l.get(*index.as_seq()?, IndexType::ForUser).cloned()?,
             State::MapState(m) => {
                 if let Some(key) = index.as_key() {
@@ -1691,7 +1894,7 @@ impl DocState {
     }
 }
 
-fn create_state_(idx: ContainerIdx, config: &Configure, peer: u64) -> State {
+fn create_state_(idx: ContainerIdx, config: &Configure) -> State {
     match idx.get_type() {
         ContainerType::Map => State::MapState(Box::new(MapState::new(idx))),
         ContainerType::List => State::ListState(Box::new(ListState::new(idx))),
@@ -1699,12 +1902,13 @@ fn create_state_(idx: ContainerIdx, config: &Configure, peer: u64) -> State {
             idx,
             config.text_style_config.clone(),
         ))),
-        ContainerType::Tree => State::TreeState(Box::new(TreeState::new(idx, peer))),
+        ContainerType::Tree => State::TreeState(Box::new(TreeState::new(idx))),
         ContainerType::MovableList => State::MovableListState(Box::new(MovableListState::new(idx))),
         #[cfg(feature = "counter")]
-        ContainerType::Counter => {
-            State::CounterState(Box::new(counter_state::CounterState::new(idx)))
-        }
+        ContainerType::Counter => State::CounterState(Box::new(counter_state::CounterState::new(
+            idx,
+            config.counter_config.clone(),
+        ))),
         ContainerType::Unknown(_) => State::UnknownState(UnknownState::new(idx)),
     }
 }
@@ -1764,6 +1968,8 @@ struct EventRecorder {
     diffs: Vec<InternalDocDiff<'static>>,
     events: Vec<DocDiff>,
     diff_start_version: Option<Frontiers>,
+    /// The number of imports whose events are held back by the import event coalescing window
+    coalesced_imports: usize,
 }
 
 impl EventRecorder {
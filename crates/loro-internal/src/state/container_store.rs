@@ -4,9 +4,10 @@ use crate::{
     utils::kv_wrapper::KvWrapper, version::Frontiers,
 };
 use bytes::Bytes;
+use fxhash::FxHashMap;
 use inner_store::InnerStore;
 use loro_common::{ContainerID, LoroResult, LoroValue};
-use std::sync::{atomic::AtomicU64, Arc, Mutex};
+use std::sync::{Arc, Mutex};
 
 pub(crate) use container_wrapper::ContainerWrapper;
 
@@ -40,7 +41,6 @@ pub(crate) struct ContainerStore {
     store: InnerStore,
     shallow_root_store: Option<Arc<GcStore>>,
     conf: Configure,
-    peer: Arc<AtomicU64>,
 }
 
 pub(crate) const FRONTIERS_KEY: &[u8] = b"fr";
@@ -62,19 +62,17 @@ macro_rules! ctx {
     ($self:expr) => {
         ContainerCreationContext {
             configure: &$self.conf,
-            peer: $self.peer.load(std::sync::atomic::Ordering::Relaxed),
         }
     };
 }
 
 impl ContainerStore {
-    pub fn new(arena: SharedArena, conf: Configure, peer: Arc<AtomicU64>) -> Self {
+    pub fn new(arena: SharedArena, conf: Configure) -> Self {
         Self {
             store: InnerStore::new(arena.clone()),
             arena,
             conf,
             shallow_root_store: None,
-            peer,
         }
     }
 
@@ -99,6 +97,10 @@ impl ContainerStore {
             .map(|x| x.get_state(idx, ctx!(self)))
     }
 
+    pub(crate) fn contains(&mut self, idx: ContainerIdx) -> bool {
+        self.store.get_mut(idx).is_some()
+    }
+
     pub fn shallow_root_store(&self) -> Option<&Arc<GcStore>> {
         self.shallow_root_store.as_ref()
     }
@@ -117,6 +119,15 @@ impl ContainerStore {
         self.store.flush()
     }
 
+    /// Encode at most `max` of the changed containers, see [`InnerStore::flush_some`]
+    pub(crate) fn flush_some(&mut self, max: usize) -> usize {
+        self.store.flush_some(max)
+    }
+
+    pub(crate) fn unflushed_len(&self) -> usize {
+        self.store.unflushed_len()
+    }
+
     pub fn shallow_root_frontiers(&self) -> Option<&Frontiers> {
         self.shallow_root_store
             .as_ref()
@@ -158,7 +169,6 @@ impl ContainerStore {
                 *idx,
                 ContainerCreationContext {
                     configure: &self.conf,
-                    peer: self.peer.load(std::sync::atomic::Ordering::Relaxed),
                 },
             )
         })
@@ -187,14 +197,23 @@ impl ContainerStore {
         self.store.iter_all_container_ids()
     }
 
+    /// Replace the state of the container. If the state is `None`, the container is reset
+    /// to an empty state.
+    pub(super) fn replace_state(&mut self, idx: ContainerIdx, state: Option<State>) {
+        let state = state.unwrap_or_else(|| super::create_state_(idx, &self.conf));
+        let mut wrapper = Some(ContainerWrapper::new(state, &self.arena));
+        let slot = self
+            .store
+            .get_or_insert_with(idx, || wrapper.take().unwrap());
+        if let Some(wrapper) = wrapper {
+            *slot = wrapper;
+        }
+    }
+
     pub(super) fn get_or_create_mut(&mut self, idx: ContainerIdx) -> &mut State {
         self.store
             .get_or_insert_with(idx, || {
-                let state = super::create_state_(
-                    idx,
-                    &self.conf,
-                    self.peer.load(std::sync::atomic::Ordering::Relaxed),
-                );
+                let state = super::create_state_(idx, &self.conf);
                 ContainerWrapper::new(state, &self.arena)
             })
             .get_state_mut(idx, ctx!(self))
@@ -203,11 +222,7 @@ impl ContainerStore {
     pub(crate) fn ensure_container(&mut self, id: &loro_common::ContainerID) {
         let idx = self.arena.register_container(id);
         self.store.ensure_container(idx, || {
-            let state = super::create_state_(
-                idx,
-                &self.conf,
-                self.peer.load(std::sync::atomic::Ordering::Relaxed),
-            );
+            let state = super::create_state_(idx, &self.conf);
             ContainerWrapper::new(state, &self.arena)
         });
     }
@@ -215,11 +230,7 @@ impl ContainerStore {
     pub(super) fn get_or_create_imm(&mut self, idx: ContainerIdx) -> &State {
         self.store
             .get_or_insert_with(idx, || {
-                let state = super::create_state_(
-                    idx,
-                    &self.conf,
-                    self.peer.load(std::sync::atomic::Ordering::Relaxed),
-                );
+                let state = super::create_state_(idx, &self.conf);
                 ContainerWrapper::new(state, &self.arena)
             })
             .get_state(idx, ctx!(self))
@@ -229,21 +240,29 @@ impl ContainerStore {
         self.store.estimate_size()
     }
 
-    pub(crate) fn fork(
-        &mut self,
-        arena: SharedArena,
-        peer: Arc<AtomicU64>,
-        config: Configure,
-    ) -> Self {
+    pub(crate) fn estimate_container_sizes(&self) -> FxHashMap<ContainerIdx, usize> {
+        self.store.estimate_container_sizes()
+    }
+
+    pub(crate) fn fork(&mut self, arena: SharedArena, config: Configure) -> Self {
         Self {
             store: self.store.fork(arena.clone(), &config),
             arena,
             conf: config,
-            peer,
             shallow_root_store: None,
         }
     }
 
+    /// Fork the store for reading, sharing the arena and the encoded container states
+    pub(crate) fn fork_readonly(&mut self) -> Self {
+        Self {
+            store: self.store.fork_readonly(),
+            arena: self.arena.clone(),
+            conf: self.conf.clone(),
+            shallow_root_store: self.shallow_root_store.clone(),
+        }
+    }
+
     #[allow(unused)]
     fn check_eq_after_parsing(&mut self, other: &mut Self) {
         if self.store.len() != other.store.len() {
@@ -343,11 +362,7 @@ mod test {
     use crate::{state::TreeParentId, ListHandler, LoroDoc, MapHandler, MovableListHandler};
 
     fn decode_container_store(bytes: Bytes) -> ContainerStore {
-        let mut new_store = ContainerStore::new(
-            SharedArena::new(),
-            Configure::default(),
-            Arc::new(AtomicU64::new(233)),
-        );
+        let mut new_store = ContainerStore::new(SharedArena::new(), Configure::default());
 
         new_store.decode(bytes).unwrap();
         new_store
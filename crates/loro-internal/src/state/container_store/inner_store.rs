@@ -119,6 +119,30 @@ impl InnerStore {
             }));
     }
 
+    /// Encode at most `max` of the containers that changed since they were last flushed.
+    /// Returns the number of encoded containers.
+    pub(crate) fn flush_some(&mut self, max: usize) -> usize {
+        let mut n = 0;
+        self.kv
+            .set_all(self.store.iter_mut().filter_map(|(idx, c)| {
+                if n == max || c.is_flushed() {
+                    return None;
+                }
+
+                n += 1;
+                let cid = self.arena.get_container_id(*idx).unwrap();
+                let cid: Bytes = cid.to_bytes().into();
+                let value = c.encode();
+                c.set_flushed(true);
+                Some((cid, value))
+            }));
+        n
+    }
+
+    pub(crate) fn unflushed_len(&self) -> usize {
+        self.store.values().filter(|c| !c.is_flushed()).count()
+    }
+
     pub(crate) fn get_kv(&self) -> &KvWrapper {
         &self.kv
     }
@@ -250,6 +274,24 @@ impl InnerStore {
         new_store
     }
 
+    /// Fork the store with the encoded states of the containers, which share the bytes
+    /// with this store. The states are decoded lazily by the fork.
+    pub(crate) fn fork_readonly(&mut self) -> Self {
+        self.load_all();
+        let store: FxHashMap<_, _> = self
+            .store
+            .iter_mut()
+            .map(|(idx, c)| (*idx, ContainerWrapper::new_from_bytes(c.encode())))
+            .collect();
+        Self {
+            arena: self.arena.clone(),
+            len: store.len(),
+            store,
+            kv: KvWrapper::new_mem(),
+            all_loaded: true,
+        }
+    }
+
     pub(crate) fn len(&self) -> usize {
         self.len
     }
@@ -267,4 +309,28 @@ impl InnerStore {
                 .map(|c| if c.is_flushed() { 0 } else { c.estimate_size() })
                 .sum::<usize>()
     }
+
+    /// The estimated size of every container, without decoding the containers that are only
+    /// in `kv`, whose encoded sizes are used instead
+    pub(crate) fn estimate_container_sizes(&self) -> FxHashMap<ContainerIdx, usize> {
+        let mut ans = FxHashMap::default();
+        if !self.all_loaded {
+            self.kv.with_kv(|kv| {
+                for (k, v) in kv.scan(Bound::Unbounded, Bound::Unbounded) {
+                    if k.as_ref() == FRONTIERS_KEY {
+                        continue;
+                    }
+
+                    let idx = self.arena.register_container(&ContainerID::from_bytes(&k));
+                    ans.insert(idx, v.len());
+                }
+            });
+        }
+
+        for (idx, c) in self.store.iter() {
+            ans.insert(*idx, c.estimate_size());
+        }
+
+        ans
+    }
 }
@@ -1,9 +1,9 @@
-use std::sync::Weak;
+use std::sync::{Arc, RwLock, Weak};
 
 use loro_common::{ContainerID, LoroError, LoroResult, LoroValue};
 
 use crate::{
-    configure::Configure,
+    configure::{Configure, CounterConfig, CounterOverflowPolicy},
     container::idx::ContainerIdx,
     encoding::{StateSnapshotDecodeContext, StateSnapshotEncoder},
     event::{Diff, Index, InternalDiff},
@@ -17,11 +17,28 @@ use super::{ApplyLocalOpReturn, ContainerState, DiffApplyContext};
 pub struct CounterState {
     idx: ContainerIdx,
     value: f64,
+    config: Arc<RwLock<Option<CounterConfig>>>,
 }
 
 impl CounterState {
-    pub(crate) fn new(idx: ContainerIdx) -> Self {
-        Self { idx, value: 0. }
+    pub(crate) fn new(idx: ContainerIdx, config: Arc<RwLock<Option<CounterConfig>>>) -> Self {
+        Self {
+            idx,
+            value: 0.,
+            config,
+        }
+    }
+
+    /// Add `diff` to the value following the config, and return the actual change
+    fn add(&mut self, diff: f64) -> f64 {
+        let Some(config) = *self.config.read().unwrap() else {
+            self.value += diff;
+            return diff;
+        };
+
+        let old = self.value;
+        (self.value, _) = config.add(old, diff);
+        config.round(self.value - old)
     }
 }
 
@@ -41,8 +58,7 @@ impl ContainerState for CounterState {
     #[must_use]
     fn apply_diff_and_convert(&mut self, diff: InternalDiff, _ctx: DiffApplyContext) -> Diff {
         if let InternalDiff::Counter(diff) = diff {
-            self.value += diff;
-            Diff::Counter(diff)
+            Diff::Counter(self.add(diff))
         } else {
             unreachable!()
         }
@@ -54,7 +70,15 @@ impl ContainerState for CounterState {
 
     fn apply_local_op(&mut self, raw_op: &RawOp, _op: &Op) -> LoroResult<ApplyLocalOpReturn> {
         if let RawOpContent::Counter(diff) = raw_op.content {
-            self.value += diff;
+            if let Some(config) = *self.config.read().unwrap() {
+                if config.overflow == CounterOverflowPolicy::Reject
+                    && config.add(self.value, diff).1
+                {
+                    return Err(LoroError::CounterOverflow);
+                }
+            }
+
+            self.add(diff);
             Ok(Default::default())
         } else {
             unreachable!()
@@ -107,8 +131,12 @@ impl ContainerState for CounterState {
         false
     }
 
-    fn fork(&self, _config: &Configure) -> Self {
-        self.clone()
+    fn fork(&self, config: &Configure) -> Self {
+        Self {
+            idx: self.idx,
+            value: self.value,
+            config: config.counter_config.clone(),
+        }
     }
 }
 
@@ -133,12 +161,12 @@ mod snapshot {
         fn decode_snapshot_fast(
             idx: ContainerIdx,
             v: (LoroValue, &[u8]),
-            _ctx: crate::state::ContainerCreationContext,
+            ctx: crate::state::ContainerCreationContext,
         ) -> LoroResult<Self>
         where
             Self: Sized,
         {
-            let mut counter = Self::new(idx);
+            let mut counter = Self::new(idx, ctx.configure.counter_config.clone());
             counter.value = *v.0.as_double().unwrap();
             Ok(counter)
         }
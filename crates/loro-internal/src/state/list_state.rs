@@ -1,9 +1,14 @@
-use std::{io::Write, ops::RangeBounds, sync::Weak};
+use std::{
+    io::Write,
+    ops::{Range, RangeBounds},
+    sync::Weak,
+};
 
 use super::{ApplyLocalOpReturn, ContainerState, DiffApplyContext, FastStateSnapshot};
 use crate::{
     configure::Configure,
     container::{idx::ContainerIdx, list::list_op::ListOp, ContainerID},
+    cursor::Side,
     encoding::{EncodeMode, StateSnapshotDecodeContext, StateSnapshotEncoder},
     event::{Diff, Index, InternalDiff, ListDiff},
     handler::ValueOrHandler,
@@ -17,7 +22,7 @@ use generic_btree::{
     rle::{CanRemove, HasLength, Mergeable, Sliceable, TryInsert},
     BTree, BTreeTrait, Cursor, LeafIndex, LengthFinder, UseLengthFinder,
 };
-use loro_common::{IdFull, IdLpSpan, LoroResult, ID};
+use loro_common::{Counter, IdFull, IdLpSpan, Lamport, LoroResult, ID};
 use loro_delta::array_vec::ArrayVec;
 
 #[derive(Debug)]
@@ -37,41 +42,179 @@ impl Clone for ListState {
     }
 }
 
+/// A run of the values in the list, starting at `id`.
+///
+/// The adjacent numbers of the same type with consecutive ids, e.g. the ones inserted by
+/// [`crate::handler::ListHandler::push_slice`], are stored in a dense array instead of one
+/// element per value.
+///
+/// It's only the in-memory layout: the snapshots still encode the list value with one
+/// [`LoroValue`] per element, so that the older versions can decode them, and the runs are
+/// rebuilt when the state is decoded.
 #[derive(Debug, Clone)]
 pub(crate) struct Elem {
-    pub v: LoroValue,
+    pub v: ElemValue,
     pub id: IdFull,
 }
 
+#[derive(Debug, Clone)]
+pub(crate) enum ElemValue {
+    Value(LoroValue),
+    F64(Vec<f64>),
+    I64(Vec<i64>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NumberType {
+    F64,
+    I64,
+}
+
+impl ElemValue {
+    fn len(&self) -> usize {
+        match self {
+            Self::Value(_) => 1,
+            Self::F64(v) => v.len(),
+            Self::I64(v) => v.len(),
+        }
+    }
+
+    fn get(&self, offset: usize) -> LoroValue {
+        match self {
+            Self::Value(v) => {
+                debug_assert_eq!(offset, 0);
+                v.clone()
+            }
+            Self::F64(v) => LoroValue::Double(v[offset]),
+            Self::I64(v) => LoroValue::I64(v[offset]),
+        }
+    }
+
+    pub(crate) fn as_container(&self) -> Option<&ContainerID> {
+        match self {
+            Self::Value(LoroValue::Container(c)) => Some(c),
+            _ => None,
+        }
+    }
+
+    fn extend_to(&self, out: &mut Vec<LoroValue>) {
+        match self {
+            Self::Value(v) => out.push(v.clone()),
+            Self::F64(v) => out.extend(v.iter().map(|x| LoroValue::Double(*x))),
+            Self::I64(v) => out.extend(v.iter().map(|x| LoroValue::I64(*x))),
+        }
+    }
+
+    fn number_type(&self) -> Option<NumberType> {
+        match self {
+            Self::Value(LoroValue::Double(_)) | Self::F64(_) => Some(NumberType::F64),
+            Self::Value(LoroValue::I64(_)) | Self::I64(_) => Some(NumberType::I64),
+            Self::Value(_) => None,
+        }
+    }
+
+    /// Convert a single number to a dense run
+    fn into_run(self) -> Self {
+        match self {
+            Self::Value(LoroValue::Double(x)) => Self::F64(vec![x]),
+            Self::Value(LoroValue::I64(x)) => Self::I64(vec![x]),
+            v => v,
+        }
+    }
+
+    /// Append the numbers of the same type
+    fn append(&mut self, rhs: &Self) {
+        if let Self::Value(v) = self {
+            *self = Self::Value(std::mem::take(v)).into_run();
+        }
+
+        match (self, rhs) {
+            (Self::F64(a), Self::F64(b)) => a.extend_from_slice(b),
+            (Self::F64(a), Self::Value(LoroValue::Double(b))) => a.push(*b),
+            (Self::I64(a), Self::I64(b)) => a.extend_from_slice(b),
+            (Self::I64(a), Self::Value(LoroValue::I64(b))) => a.push(*b),
+            _ => unreachable!(),
+        }
+    }
+
+    fn slice(&self, range: std::ops::Range<usize>) -> Self {
+        match self {
+            Self::Value(v) => {
+                assert_eq!(range, 0..1);
+                Self::Value(v.clone())
+            }
+            Self::F64(v) => Self::F64(v[range].to_vec()),
+            Self::I64(v) => Self::I64(v[range].to_vec()),
+        }
+    }
+
+    fn split_off(&mut self, pos: usize) -> Self {
+        match self {
+            Self::Value(_) => unreachable!(),
+            Self::F64(v) => Self::F64(v.split_off(pos)),
+            Self::I64(v) => Self::I64(v.split_off(pos)),
+        }
+    }
+}
+
+impl Elem {
+    /// Group the values into runs. The adjacent numbers of the same type with
+    /// consecutive ids are merged.
+    fn from_values(values: impl IntoIterator<Item = (LoroValue, IdFull)>) -> Vec<Elem> {
+        let mut ans: Vec<Elem> = Vec::new();
+        for (v, id) in values {
+            let elem = Elem {
+                v: ElemValue::Value(v),
+                id,
+            };
+            match ans.last_mut() {
+                Some(last) if last.can_merge(&elem) => last.merge_right(&elem),
+                _ => ans.push(elem),
+            }
+        }
+
+        ans
+    }
+}
+
 impl HasLength for Elem {
     fn rle_len(&self) -> usize {
-        1
+        self.v.len()
     }
 }
 
 impl Sliceable for Elem {
     fn _slice(&self, range: std::ops::Range<usize>) -> Self {
-        assert_eq!(range.start, 0);
-        assert_eq!(range.end, 1);
-        self.clone()
+        Self {
+            id: self.id.inc(range.start as i32),
+            v: self.v.slice(range),
+        }
     }
 
-    fn split(&mut self, _pos: usize) -> Self {
-        unreachable!()
+    fn split(&mut self, pos: usize) -> Self {
+        Self {
+            v: self.v.split_off(pos),
+            id: self.id.inc(pos as i32),
+        }
     }
 }
 
 impl Mergeable for Elem {
-    fn can_merge(&self, _rhs: &Self) -> bool {
-        false
+    fn can_merge(&self, rhs: &Self) -> bool {
+        self.v.number_type().is_some()
+            && self.v.number_type() == rhs.v.number_type()
+            && self.id.inc(self.rle_len() as i32) == rhs.id
     }
 
-    fn merge_right(&mut self, _rhs: &Self) {
-        unreachable!()
+    fn merge_right(&mut self, rhs: &Self) {
+        self.v.append(&rhs.v)
     }
 
-    fn merge_left(&mut self, _left: &Self) {
-        unreachable!()
+    fn merge_left(&mut self, left: &Self) {
+        let mut v = left.v.clone().into_run();
+        v.append(&self.v);
+        self.v = v;
+        self.id = left.id;
     }
 }
 
@@ -83,7 +226,7 @@ impl TryInsert for Elem {
 
 impl CanRemove for Elem {
     fn can_remove(&self) -> bool {
-        false
+        self.rle_len() == 0
     }
 }
 
@@ -120,8 +263,8 @@ impl BTreeTrait for ListImpl {
     }
 
     #[inline(always)]
-    fn get_elem_cache(_elem: &Self::Elem) -> Self::Cache {
-        1
+    fn get_elem_cache(elem: &Self::Elem) -> Self::Cache {
+        elem.rle_len() as isize
     }
 
     #[inline(always)]
@@ -167,8 +310,8 @@ impl ListState {
                 generic_btree::PreviousCache::NodeCache(cache) => {
                     index += *cache;
                 }
-                generic_btree::PreviousCache::PrevSiblingElem(..) => {
-                    index += 1;
+                generic_btree::PreviousCache::PrevSiblingElem(elem) => {
+                    index += elem.rle_len() as isize;
                 }
                 generic_btree::PreviousCache::ThisElemAndOffset { .. } => {}
             });
@@ -177,72 +320,80 @@ impl ListState {
     }
 
     pub fn insert(&mut self, index: usize, value: LoroValue, id: IdFull) {
-        if index > self.len() {
-            panic!("Index {index} out of range. The length is {}", self.len());
-        }
-
-        if self.list.is_empty() {
-            let idx = self.list.push(Elem {
-                v: value.clone(),
-                id,
-            });
-
-            if value.is_container() {
-                self.child_container_to_leaf
-                    .insert(value.into_container().unwrap(), idx.leaf);
-            }
-            return;
-        }
-
-        let (leaf, data) = self.list.insert::<LengthFinder>(
-            &index,
+        self.insert_elem(
+            index,
             Elem {
-                v: value.clone(),
+                v: ElemValue::Value(value),
                 id,
             },
         );
+    }
 
-        if value.is_container() {
-            self.child_container_to_leaf
-                .insert(value.into_container().unwrap(), leaf.leaf);
+    fn insert_elem(&mut self, index: usize, elem: Elem) {
+        if index > self.len() {
+            panic!("Index {index} out of range. The length is {}", self.len());
         }
 
-        assert!(data.arr.is_empty());
-    }
-
-    pub fn push(&mut self, value: LoroValue, id: IdFull) {
-        if self.list.is_empty() {
-            let idx = self.list.push(Elem {
-                v: value.clone(),
-                id,
-            });
+        let container = elem.v.as_container().cloned();
+        let cursor = if self.list.is_empty() {
+            self.list.push(elem)
+        } else {
+            self.list.insert::<LengthFinder>(&index, elem).0
+        };
 
-            if value.is_container() {
-                self.child_container_to_leaf
-                    .insert(value.into_container().unwrap(), idx.leaf);
-            }
-            return;
+        if let Some(container) = container {
+            self.child_container_to_leaf.insert(container, cursor.leaf);
         }
+    }
 
-        let leaf = self.list.push(Elem {
-            v: value.clone(),
+    pub fn push(&mut self, value: LoroValue, id: IdFull) {
+        let container = value.as_container().cloned();
+        let cursor = self.list.push(Elem {
+            v: ElemValue::Value(value),
             id,
         });
 
-        if value.is_container() {
-            self.child_container_to_leaf
-                .insert(value.into_container().unwrap(), leaf.leaf);
+        if let Some(container) = container {
+            self.child_container_to_leaf.insert(container, cursor.leaf);
         }
     }
 
     pub fn delete(&mut self, index: usize) -> LoroValue {
-        let leaf = self.list.query::<LengthFinder>(&index);
-        let leaf = self.list.remove_leaf(leaf.unwrap().cursor).unwrap();
-        if leaf.v.is_container() {
-            self.child_container_to_leaf
-                .remove(leaf.v.as_container().unwrap());
+        let cursor = self.list.query::<LengthFinder>(&index).unwrap().cursor;
+        let elem = self.list.get_elem(cursor.leaf).unwrap();
+        if elem.rle_len() > 1 {
+            let value = elem.v.get(cursor.offset);
+            self.delete_in_run(cursor, 1);
+            return value;
         }
-        leaf.v
+
+        let elem = self.list.remove_leaf(cursor).unwrap();
+        if let Some(container) = elem.v.as_container() {
+            self.child_container_to_leaf.remove(container);
+        }
+        elem.v.get(0)
+    }
+
+    /// Delete `len` values in the run that the cursor points to
+    fn delete_in_run(&mut self, cursor: Cursor, len: usize) {
+        let elem = self.list.get_elem(cursor.leaf).unwrap();
+        if cursor.offset == 0 && len == elem.rle_len() {
+            self.list.remove_leaf(cursor);
+            return;
+        }
+
+        self.list.update_leaf(cursor.leaf, |elem| {
+            let right = elem.split(cursor.offset);
+            let right = right._slice(len..right.rle_len());
+            if elem.rle_len() == 0 {
+                *elem = right;
+                (true, None, None)
+            } else if right.rle_len() == 0 {
+                (true, None, None)
+            } else {
+                (true, Some(right), None)
+            }
+        });
     }
 
     pub fn delete_range(
@@ -260,6 +411,10 @@ impl ListState {
             std::ops::Bound::Excluded(x) => *x,
             std::ops::Bound::Unbounded => self.len(),
         };
+        if start >= end {
+            return;
+        }
+
         if end - start == 1 {
             if let LoroValue::Container(c) = self.delete(start) {
                 if let Some(notify_deletion) = &mut notify_deletion {
@@ -269,38 +424,74 @@ impl ListState {
             return;
         }
 
-        let list = &mut self.list;
-        let q = start..end;
-        let start1 = list.query::<LengthFinder>(&q.start);
-        let end1 = list.query::<LengthFinder>(&q.end);
-        for v in iter::Drain::new(list, start1, end1) {
-            if v.v.is_container() {
-                self.child_container_to_leaf
-                    .remove(v.v.as_container().unwrap());
+        let start1 = self.list.query::<LengthFinder>(&start);
+        let end1 = self.list.query::<LengthFinder>(&end);
+        if let (Some(s), Some(e)) = (&start1, &end1) {
+            if s.cursor.leaf == e.cursor.leaf {
+                // The range is inside a run of numbers
+                self.delete_in_run(s.cursor, end - start);
+                return;
+            }
+        }
+
+        for v in iter::Drain::new(&mut self.list, start1, end1) {
+            if let ElemValue::Value(LoroValue::Container(c)) = v.v {
+                self.child_container_to_leaf.remove(&c);
                 if let Some(notify_deletion) = &mut notify_deletion {
-                    notify_deletion.push(v.v.into_container().unwrap());
+                    notify_deletion.push(c);
                 }
             }
         }
     }
 
-    // PERF: use &[LoroValue]
-    // PERF: batch
     pub fn insert_batch(&mut self, index: usize, values: Vec<LoroValue>, start_id: IdFull) {
-        let mut id = start_id;
-        for (i, value) in values.into_iter().enumerate() {
-            self.insert(index + i, value, id);
-            id = id.inc(1);
+        let mut index = index;
+        let ids = (0..).map(|i| start_id.inc(i));
+        for elem in Elem::from_values(values.into_iter().zip(ids)) {
+            let len = elem.rle_len();
+            self.insert_elem(index, elem);
+            index += len;
         }
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = &LoroValue> {
-        self.list.iter().map(|x| &x.v)
+    pub fn iter(&self) -> impl Iterator<Item = LoroValue> + '_ {
+        self.list
+            .iter()
+            .flat_map(|x| (0..x.rle_len()).map(move |i| x.v.get(i)))
     }
 
-    #[allow(unused)]
-    pub(crate) fn iter_with_id(&self) -> impl Iterator<Item = &Elem> {
-        self.list.iter()
+    /// Iterate over the values in `range`, starting from the leaf that contains
+    /// `range.start` instead of the beginning of the list.
+    ///
+    /// The range is clamped to the length of the list.
+    pub fn iter_range(&self, range: Range<usize>) -> impl Iterator<Item = LoroValue> + '_ {
+        let end = range.end.min(self.len());
+        let start = range.start.min(end);
+        let iter = (start < end).then(|| {
+            let from = self.list.query::<LengthFinder>(&start).unwrap().cursor;
+            let to = self.list.query::<LengthFinder>(&end).unwrap().cursor;
+            self.list.iter_range(from..to).flat_map(|slice| {
+                let elem = slice.elem;
+                let len = elem.rle_len();
+                let start = slice.start.unwrap_or(0);
+                let end = slice.end.map_or(len, |x| x.min(len));
+                (start..end).map(move |i| elem.v.get(i))
+            })
+        });
+        iter.into_iter().flatten()
+    }
+
+    /// Copy the values in `range`. It's `O(log n + range.len())`.
+    ///
+    /// The range is clamped to the length of the list.
+    pub fn get_range(&self, range: Range<usize>) -> Vec<LoroValue> {
+        self.iter_range(range).collect()
+    }
+
+    pub(crate) fn iter_with_id(&self) -> impl Iterator<Item = (IdFull, LoroValue)> + '_ {
+        self.list
+            .iter()
+            .flat_map(|x| (0..x.rle_len()).map(move |i| (x.id.inc(i as i32), x.v.get(i))))
     }
 
     pub fn len(&self) -> usize {
@@ -309,16 +500,42 @@ impl ListState {
 
     fn to_vec(&self) -> Vec<LoroValue> {
         let mut ans = Vec::with_capacity(self.len());
-        for value in self.list.iter() {
-            ans.push(value.v.clone());
+        for elem in self.list.iter() {
+            elem.v.extend_to(&mut ans);
         }
         ans
     }
 
-    pub fn get(&self, index: usize) -> Option<&LoroValue> {
+    /// Copy the values to a `Vec<f64>`, or return `None` if any of them isn't a `f64`
+    pub(crate) fn to_f64_vec(&self) -> Option<Vec<f64>> {
+        let mut ans = Vec::with_capacity(self.len());
+        for elem in self.list.iter() {
+            match &elem.v {
+                ElemValue::F64(v) => ans.extend_from_slice(v),
+                ElemValue::Value(LoroValue::Double(x)) => ans.push(*x),
+                _ => return None,
+            }
+        }
+        Some(ans)
+    }
+
+    /// Copy the values to a `Vec<i64>`, or return `None` if any of them isn't an `i64`
+    pub(crate) fn to_i64_vec(&self) -> Option<Vec<i64>> {
+        let mut ans = Vec::with_capacity(self.len());
+        for elem in self.list.iter() {
+            match &elem.v {
+                ElemValue::I64(v) => ans.extend_from_slice(v),
+                ElemValue::Value(LoroValue::I64(x)) => ans.push(*x),
+                _ => return None,
+            }
+        }
+        Some(ans)
+    }
+
+    pub fn get(&self, index: usize) -> Option<LoroValue> {
         let result = self.list.query::<LengthFinder>(&index)?;
         if result.found {
-            Some(&result.elem(&self.list).unwrap().v)
+            Some(result.elem(&self.list).unwrap().v.get(result.cursor.offset))
         } else {
             None
         }
@@ -327,26 +544,57 @@ impl ListState {
     pub fn get_id_at(&self, index: usize) -> Option<IdFull> {
         let result = self.list.query::<LengthFinder>(&index)?;
         if result.found {
-            Some(result.elem(&self.list).unwrap().id)
+            Some(
+                result
+                    .elem(&self.list)
+                    .unwrap()
+                    .id
+                    .inc(result.cursor.offset as i32),
+            )
         } else {
             None
         }
     }
 
+    /// Get a cursor of `pos` that stays at the same element after the remote edits.
+    ///
+    /// `container` is the id of this list. Resolve the cursor with
+    /// [`LoroDoc::query_pos`](crate::LoroDoc::query_pos).
+    pub fn get_cursor(
+        &self,
+        container: ContainerID,
+        pos: usize,
+        side: Side,
+    ) -> Option<crate::cursor::Cursor> {
+        crate::cursor::Cursor::anchored(
+            container,
+            self.get_id_at(pos).map(|id| id.id()),
+            self.len(),
+            pos,
+            side,
+        )
+    }
+
     #[allow(unused)]
     pub(crate) fn check(&self) {
         for value in self.iter() {
-            if let LoroValue::Container(c) = value {
+            if let LoroValue::Container(c) = &value {
                 self.get_child_index(c).unwrap();
             }
         }
     }
 
     pub fn get_index_of_id(&self, id: ID) -> Option<usize> {
-        for (i, elem) in self.iter_with_id().enumerate() {
-            if elem.id.id() == id {
-                return Some(i);
+        let mut index = 0;
+        for elem in self.list.iter() {
+            let len = elem.rle_len();
+            if elem.id.peer == id.peer
+                && elem.id.counter <= id.counter
+                && id.counter < elem.id.counter + len as Counter
+            {
+                return Some(index + (id.counter - elem.id.counter) as usize);
             }
+            index += len;
         }
         None
     }
@@ -359,8 +607,18 @@ impl ContainerState for ListState {
 
     fn estimate_size(&self) -> usize {
         // TODO: this is inaccurate
+        let runs: usize = self
+            .list
+            .iter()
+            .map(|elem| match &elem.v {
+                ElemValue::Value(_) => 0,
+                ElemValue::F64(v) => v.len() * std::mem::size_of::<f64>(),
+                ElemValue::I64(v) => v.len() * std::mem::size_of::<i64>(),
+            })
+            .sum();
         self.list.node_len() * std::mem::size_of::<isize>()
-            + self.len() * std::mem::size_of::<Elem>()
+            + self.list.iter().count() * std::mem::size_of::<Elem>()
+            + runs
             + self.child_container_to_leaf.len() * std::mem::size_of::<(ContainerID, LeafIndex)>()
     }
 
@@ -511,8 +769,8 @@ impl ContainerState for ListState {
     fn get_child_containers(&self) -> Vec<ContainerID> {
         let mut ans = Vec::new();
         for elem in self.list.iter() {
-            if elem.v.is_container() {
-                ans.push(elem.v.as_container().unwrap().clone());
+            if let Some(c) = elem.v.as_container() {
+                ans.push(c.clone());
             }
         }
         ans
@@ -521,7 +779,11 @@ impl ContainerState for ListState {
     #[doc = "Get a list of ops that can be used to restore the state to the current state"]
     fn encode_snapshot(&self, mut encoder: StateSnapshotEncoder) -> Vec<u8> {
         for elem in self.list.iter() {
-            let id_span: IdLpSpan = elem.id.idlp().into();
+            let id_span = IdLpSpan::new(
+                elem.id.peer,
+                elem.id.lamport,
+                elem.id.lamport + elem.rle_len() as Lamport,
+            );
             encoder.encode_op(id_span, || unimplemented!());
         }
 
@@ -589,8 +851,7 @@ mod snapshot {
             postcard::to_io(&*value, &mut w).unwrap();
             let mut peers: ValueRegister<PeerID> = ValueRegister::new();
             let mut ids = Vec::with_capacity(self.len());
-            for elem in self.iter_with_id() {
-                let id = elem.id;
+            for (id, _) in self.iter_with_id() {
                 let peer_idx = peers.register(&id.peer);
                 ids.push(EncodedListId {
                     peer_idx,
@@ -636,18 +897,19 @@ mod snapshot {
 
             let EncodedListIds { ids } = serde_columnar::from_bytes(bytes).unwrap();
 
-            let list = v.as_list().unwrap();
+            let list = v.into_list().unwrap();
             let mut ans = Self::new(idx);
-            for (i, id) in ids.into_iter().enumerate() {
-                ans.insert(
-                    i,
-                    list[i].clone(),
-                    IdFull::new(
-                        peers[id.peer_idx],
-                        id.counter as Counter,
-                        (id.lamport_sub_counter + id.counter) as Lamport,
-                    ),
-                );
+            let ids = ids.into_iter().map(|id| {
+                IdFull::new(
+                    peers[id.peer_idx],
+                    id.counter as Counter,
+                    (id.lamport_sub_counter + id.counter) as Lamport,
+                )
+            });
+            // The numbers are stored in the same format as the other values, and they are
+            // merged back into the dense runs here
+            for elem in Elem::from_values(list.iter().cloned().zip(ids)) {
+                ans.insert_elem(ans.len(), elem);
             }
 
             Ok(ans)
@@ -682,6 +944,40 @@ mod test {
         assert_eq!(list.get_child_container_index(&id("abc")), Some(2));
     }
 
+    #[test]
+    fn get_range() {
+        let mut list = ListState::new(ContainerIdx::from_index_and_type(
+            0,
+            loro_common::ContainerType::List,
+        ));
+        list.insert_batch(
+            0,
+            (0..100).map(LoroValue::I64).collect(),
+            IdFull::new(0, 0, 0),
+        );
+        list.insert(50, LoroValue::Bool(true), IdFull::new(1, 0, 100));
+        for i in 0..200 {
+            list.insert(0, LoroValue::I64(-i), IdFull::new(2, i as Counter, 0));
+        }
+
+        let all = list.iter().collect_vec();
+        for (start, end) in [
+            (0, 1),
+            (10, 20),
+            (240, 260),
+            (249, 251),
+            (0, 301),
+            (300, 301),
+        ] {
+            assert_eq!(list.get_range(start..end), all[start..end]);
+        }
+        assert_eq!(list.get_range(290..400), all[290..]);
+        assert!(list.get_range(400..500).is_empty());
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = list.get_range(20..10);
+        assert!(empty.is_empty());
+    }
+
     #[test]
     fn test_list_fast_snapshot() {
         let mut list = ListState::new(ContainerIdx::from_index_and_type(
@@ -704,7 +1000,6 @@ mod test {
             (v.clone(), left),
             ContainerCreationContext {
                 configure: &Default::default(),
-                peer: 0,
             },
         )
         .unwrap();
@@ -714,17 +1009,67 @@ mod test {
             vec![LoroValue::I64(0), LoroValue::I64(2), LoroValue::I64(4)].into()
         );
         assert_eq!(new_list.get_value(), v);
-        let v = new_list.list.iter().collect_vec();
-        assert_eq!(v[0].id.peer, 0);
-        assert_eq!(v[0].id.counter, 0 as Counter);
-        assert_eq!(v[0].id.lamport, 0 as Lamport);
-
-        assert_eq!(v[1].id.peer, 1);
-        assert_eq!(v[1].id.counter, 1 as Counter);
-        assert_eq!(v[1].id.lamport, 1 as Lamport);
-
-        assert_eq!(v[2].id.peer, 1);
-        assert_eq!(v[2].id.counter, 2 as Counter);
-        assert_eq!(v[2].id.lamport, 2 as Lamport);
+        let v = new_list.iter_with_id().collect_vec();
+        assert_eq!(v[0].0.peer, 0);
+        assert_eq!(v[0].0.counter, 0 as Counter);
+        assert_eq!(v[0].0.lamport, 0 as Lamport);
+
+        assert_eq!(v[1].0.peer, 1);
+        assert_eq!(v[1].0.counter, 1 as Counter);
+        assert_eq!(v[1].0.lamport, 1 as Lamport);
+
+        assert_eq!(v[2].0.peer, 1);
+        assert_eq!(v[2].0.counter, 2 as Counter);
+        assert_eq!(v[2].0.lamport, 2 as Lamport);
+        // The numbers with consecutive ids are decoded into one run
+        assert_eq!(new_list.list.iter().count(), 2);
+    }
+
+    #[test]
+    fn cursor_is_anchored_to_the_element() {
+        let mut list = ListState::new(ContainerIdx::from_index_and_type(
+            0,
+            loro_common::ContainerType::List,
+        ));
+        let container = ContainerID::new_root("list", crate::ContainerType::List);
+        let cursor = list.get_cursor(container.clone(), 0, Side::Middle).unwrap();
+        assert_eq!(cursor.id, None);
+        assert_eq!(cursor.side, Side::Left);
+
+        list.insert(0, LoroValue::I64(0), IdFull::new(1, 0, 0));
+        list.insert(1, LoroValue::I64(1), IdFull::new(1, 1, 1));
+        let cursor = list.get_cursor(container.clone(), 1, Side::Middle).unwrap();
+        assert_eq!(cursor.id, Some(ID::new(1, 1)));
+        list.insert(0, LoroValue::I64(2), IdFull::new(2, 0, 2));
+        assert_eq!(list.get_index_of_id(cursor.id.unwrap()), Some(2));
+
+        let end = list.get_cursor(container, 3, Side::Middle).unwrap();
+        assert_eq!(end.id, None);
+        assert_eq!(end.side, Side::Right);
+    }
+
+    #[test]
+    fn numbers_are_stored_in_dense_runs() {
+        let mut list = ListState::new(ContainerIdx::from_index_and_type(
+            0,
+            loro_common::ContainerType::List,
+        ));
+        let values = (0..10).map(|i| LoroValue::Double(i as f64)).collect();
+        list.insert_batch(0, values, IdFull::new(1, 0, 0));
+        list.insert(5, LoroValue::Bool(true), IdFull::new(2, 0, 10));
+        assert_eq!(list.list.iter().count(), 3);
+        assert_eq!(list.len(), 11);
+        assert_eq!(list.get(5), Some(LoroValue::Bool(true)));
+        assert_eq!(list.get(6), Some(LoroValue::Double(5.)));
+        assert_eq!(list.get_id_at(7), Some(IdFull::new(1, 6, 6)));
+        assert_eq!(list.get_index_of_id(ID::new(1, 8)), Some(9));
+
+        list.delete_range(7..9, None);
+        assert_eq!(list.delete(1), LoroValue::Double(1.));
+        assert_eq!(list.to_f64_vec(), None);
+        list.delete(4);
+        assert_eq!(list.to_f64_vec(), Some(vec![0., 2., 3., 4., 5., 8., 9.]));
+        assert_eq!(list.get_id_at(5), Some(IdFull::new(1, 8, 8)));
+        assert_eq!(list.len(), 7);
     }
 }
@@ -275,6 +275,11 @@ impl MapState {
         self.size
     }
 
+    /// The id of the op that set the current value of the key
+    pub(crate) fn get_winner(&self, key: &str) -> Option<IdLp> {
+        self.map.get(&key.into()).map(|v| IdLp::new(v.peer, v.lamp))
+    }
+
     pub fn get_last_edit_peer(&self, key: &str) -> Option<PeerID> {
         self.map.get(&key.into()).map(|v| v.peer)
     }
@@ -302,7 +307,7 @@ mod snapshot {
             // 3. Groups of (leb128 peer_idx, leb128 lamport), each has a respective map entry
             //    from either 1 or 2 when they all sorted by the key strings
             let value = self.get_value().into_map().unwrap();
-            postcard::to_io(&*value, &mut w).unwrap();
+            postcard::to_io(&value, &mut w).unwrap();
 
             let keys_with_none_value = self
                 .map
@@ -461,7 +466,6 @@ mod snapshot {
                 (value, bytes),
                 ContainerCreationContext {
                     configure: &Default::default(),
-                    peer: 0,
                 },
             )
             .unwrap();
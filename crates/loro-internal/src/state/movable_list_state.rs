@@ -1002,6 +1002,16 @@ impl MovableListState {
                     .and_then(|x| self.inner.elements().get(&x).map(|x| x.value_id.peer))
             })
     }
+
+    /// The id of the latest op that set or moved the element at `pos`
+    pub(crate) fn get_last_modified_at(&self, pos: usize) -> Option<IdLp> {
+        let elem_id = self
+            .inner
+            .get_list_item_at(pos, IndexType::ForUser)?
+            .pointed_by?;
+        let elem = self.inner.elements().get(&elem_id)?;
+        Some(elem.value_id.max(elem.pos))
+    }
 }
 
 impl ContainerState for MovableListState {
@@ -1862,7 +1872,6 @@ mod snapshot {
                 (v.clone(), bytes),
                 ContainerCreationContext {
                     configure: &Default::default(),
-                    peer: 0,
                 },
             )
             .unwrap();
@@ -13,7 +13,7 @@ use crate::{
         idx::ContainerIdx,
         list::list_op,
         richtext::{
-            config::StyleConfigMap,
+            config::{InvalidStyle, StyleConfigMap, StyleImportPolicy},
             richtext_state::{
                 DrainInfo, EntityRangeInfo, IterRangeItem, PosType, RichtextStateChunk,
             },
@@ -22,7 +22,7 @@ use crate::{
     },
     delta::{StyleMeta, StyleMetaItem},
     encoding::{EncodeMode, StateSnapshotDecodeContext, StateSnapshotEncoder},
-    event::{Diff, Index, InternalDiff, TextDiff},
+    event::{Diff, Index, InternalDiff, ParagraphId, TextDiff},
     handler::TextDelta,
     op::{Op, RawOp},
     utils::{lazy::LazyLoad, string_slice::StringSlice},
@@ -57,6 +57,33 @@ impl RichtextState {
         }
     }
 
+    /// Whether the style from the remote or the history should be applied, according to the
+    /// [StyleImportPolicy] of the style config
+    fn should_apply_style(&self, style: &StyleOp) -> bool {
+        if self.config.read().unwrap().import_policy == StyleImportPolicy::Keep {
+            return true;
+        }
+
+        let mut config = self.config.write().unwrap();
+        let Err(reason) = config.check_imported(style) else {
+            return true;
+        };
+
+        match config.import_policy {
+            StyleImportPolicy::Keep => true,
+            StyleImportPolicy::Drop => false,
+            StyleImportPolicy::Flag => {
+                config.flagged.push(InvalidStyle {
+                    id: ID::new(style.peer, style.cnt),
+                    key: style.key.clone(),
+                    value: style.value.clone(),
+                    reason,
+                });
+                true
+            }
+        }
+    }
+
     #[inline]
     fn update_version(&mut self) {
         self.version_id = self.version_id.wrapping_add(1);
@@ -102,6 +129,38 @@ impl RichtextState {
         self.state.get_mut().get_text_slice_by_event_index(pos, len)
     }
 
+    /// Get the text in the Unicode code point `range`
+    pub fn slice_unicode(&mut self, range: Range<usize>) -> LoroResult<String> {
+        self.get_text_slice(range, PosType::Unicode)
+    }
+
+    /// Get the text in the UTF-16 `range`, e.g. the offsets of the DOM
+    pub fn slice_utf16(&mut self, range: Range<usize>) -> LoroResult<String> {
+        self.get_text_slice(range, PosType::Utf16)
+    }
+
+    /// Get the text in the UTF-8 byte `range`
+    pub fn slice_utf8(&mut self, range: Range<usize>) -> LoroResult<String> {
+        self.get_text_slice(range, PosType::Bytes)
+    }
+
+    pub(crate) fn get_text_slice(
+        &mut self,
+        range: Range<usize>,
+        pos_type: PosType,
+    ) -> LoroResult<String> {
+        if range.end < range.start {
+            return Err(LoroError::EndIndexLessThanStartIndex {
+                start: range.start,
+                end: range.end,
+            });
+        }
+
+        self.state
+            .get_mut()
+            .get_text_slice(range.start, range.len(), pos_type)
+    }
+
     pub(crate) fn get_char_by_event_index(&mut self, pos: usize) -> Result<char, ()> {
         self.state.get_mut().get_char_by_event_index(pos)
     }
@@ -255,6 +314,109 @@ impl RichtextState {
         None
     }
 
+    /// Get the event indexes and the ids of the newlines before the event index `end`.
+    fn newlines_before(&mut self, end: usize) -> Vec<(usize, ID)> {
+        let mut ans = Vec::new();
+        let mut index = 0;
+        for chunk in self.state.get_mut().iter_chunk() {
+            if index >= end {
+                break;
+            }
+
+            let RichtextStateChunk::Text(t) = chunk else {
+                continue;
+            };
+            if !t.as_str().contains('\n') {
+                index += t.event_len() as usize;
+                continue;
+            }
+
+            for (i, c) in t.as_str().chars().enumerate() {
+                if index >= end {
+                    break;
+                }
+
+                if c == '\n' {
+                    ans.push((index, t.id().inc(i as i32)));
+                }
+                index += if cfg!(feature = "wasm") {
+                    c.len_utf16()
+                } else {
+                    1
+                };
+            }
+        }
+
+        ans
+    }
+
+    /// Get the ids of all the paragraphs in document order.
+    pub(crate) fn get_paragraph_ids(&mut self) -> Vec<ParagraphId> {
+        std::iter::once(ParagraphId::First)
+            .chain(
+                self.newlines_before(usize::MAX)
+                    .into_iter()
+                    .map(|(_, id)| ParagraphId::Newline(id)),
+            )
+            .collect()
+    }
+
+    /// Get the paragraphs in document order, with their text without the newlines.
+    pub(crate) fn get_lines(&mut self) -> Vec<(ParagraphId, String)> {
+        let mut ans = vec![(ParagraphId::First, String::new())];
+        for chunk in self.state.get_mut().iter_chunk() {
+            let RichtextStateChunk::Text(t) = chunk else {
+                continue;
+            };
+            let mut rest = t.as_str();
+            // The offset of `rest` in unicode chars, by which the ids of the chars increase
+            let mut offset = 0;
+            while let Some(i) = rest.find('\n') {
+                ans.last_mut().unwrap().1.push_str(&rest[..i]);
+                offset += rest[..i].chars().count();
+                let id = t.id().inc(offset as i32);
+                ans.push((ParagraphId::Newline(id), String::new()));
+                offset += 1;
+                rest = &rest[i + 1..];
+            }
+            ans.last_mut().unwrap().1.push_str(rest);
+        }
+
+        ans
+    }
+
+    /// Get the ids of the paragraphs that overlap the event index ranges, in document order.
+    ///
+    /// The ranges must be sorted and must not overlap. An empty range selects the paragraph
+    /// that contains the cursor at its start.
+    pub(crate) fn get_paragraphs_in_event_ranges(
+        &mut self,
+        ranges: &[Range<usize>],
+    ) -> Vec<ParagraphId> {
+        let Some(end) = ranges.last().map(|r| r.end) else {
+            return Vec::new();
+        };
+
+        let newlines = self.newlines_before(end);
+        let mut ans = Vec::new();
+        for r in ranges {
+            let i = newlines.partition_point(|(index, _)| *index < r.start);
+            ans.push(match i {
+                0 => ParagraphId::First,
+                i => ParagraphId::Newline(newlines[i - 1].1),
+            });
+            ans.extend(
+                newlines[i..]
+                    .iter()
+                    .take_while(|(index, _)| *index < r.end)
+                    .map(|(_, id)| ParagraphId::Newline(*id)),
+            );
+        }
+
+        ans.dedup();
+        ans
+    }
+
     pub(crate) fn get_delta(&mut self) -> Vec<TextDelta> {
         let mut delta = Vec::new();
         // TODO: merge last
@@ -452,6 +614,7 @@ impl ContainerState for RichtextState {
                                             },
                                         );
                                     }
+                                    AnchorType::End if !self.should_apply_style(style) => {}
                                     AnchorType::End => {
                                         // get the pair of style anchor. now we can annotate the range
                                         let Pos {
@@ -535,7 +698,7 @@ impl ContainerState for RichtextState {
 
                                 if *anchor_type == AnchorType::Start {
                                     style_starts.insert(style.clone(), entity_index);
-                                } else {
+                                } else if self.should_apply_style(style) {
                                     let start_pos = match style_starts.get(style) {
                                         Some(x) => *x,
                                         None => {
@@ -848,6 +1011,44 @@ impl RichtextState {
         self.state.get_mut().get_richtext_value()
     }
 
+    /// Get a cursor of the event index `pos` that stays at the same character after the
+    /// remote edits.
+    ///
+    /// `container` is the id of this text. Resolve the cursor with
+    /// [`LoroDoc::query_pos`](crate::LoroDoc::query_pos).
+    pub fn get_cursor(
+        &mut self,
+        container: ContainerID,
+        pos: usize,
+        side: crate::cursor::Side,
+    ) -> Option<crate::cursor::Cursor> {
+        self.get_cursor_internal(container, pos, side, true)
+    }
+
+    /// Like [`RichtextState::get_cursor`], but `pos` is a unicode index if
+    /// `get_by_event_index` is false
+    pub(crate) fn get_cursor_internal(
+        &mut self,
+        container: ContainerID,
+        pos: usize,
+        side: crate::cursor::Side,
+        get_by_event_index: bool,
+    ) -> Option<crate::cursor::Cursor> {
+        let len = if get_by_event_index {
+            self.len_event()
+        } else {
+            self.len_unicode()
+        };
+        let id = self.get_stable_position(pos, get_by_event_index);
+        let mut ans = crate::cursor::Cursor::anchored(container, id, len, pos, side)?;
+        if ans.id.is_some() && get_by_event_index {
+            // The origin pos of a text cursor is a unicode index
+            ans.origin_pos = self.event_index_to_unicode_index(pos);
+        }
+
+        Some(ans)
+    }
+
     #[inline]
     pub(crate) fn get_stable_position(
         &mut self,
@@ -1204,7 +1405,6 @@ mod snapshot {
                 decoded,
                 ContainerCreationContext {
                     configure: &Default::default(),
-                    peer: 1,
                 },
             )
             .unwrap();
@@ -4,7 +4,7 @@ use fractional_index::FractionalIndex;
 use fxhash::{FxHashMap, FxHashSet};
 use itertools::Itertools;
 use loro_common::{
-    ContainerID, IdFull, IdLp, LoroError, LoroResult, LoroTreeError, LoroValue, PeerID, TreeID,
+    ContainerID, IdFull, IdLp, LoroError, LoroResult, LoroTreeError, LoroValue, TreeID,
     DELETED_TREE_ROOT, ID,
 };
 use rand::SeedableRng;
@@ -33,10 +33,7 @@ use crate::{DocState, LoroDocInner};
 
 #[derive(Clone, Debug, EnumAsInner)]
 pub enum TreeFractionalIndexConfigInner {
-    GenerateFractionalIndex {
-        jitter: u8,
-        rng: Box<rand::rngs::StdRng>,
-    },
+    GenerateFractionalIndex { jitter: u8 },
     MoveDisabled,
 }
 
@@ -49,7 +46,6 @@ pub struct TreeState {
     trees: FxHashMap<TreeID, TreeStateNode>,
     children: TreeChildrenCache,
     fractional_index_config: TreeFractionalIndexConfigInner,
-    peer_id: PeerID,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -659,16 +655,14 @@ pub(crate) enum FiIfNotConfigured {
 }
 
 impl TreeState {
-    pub fn new(idx: ContainerIdx, peer_id: PeerID) -> Self {
+    pub fn new(idx: ContainerIdx) -> Self {
         Self {
             idx,
             trees: FxHashMap::default(),
             children: Default::default(),
             fractional_index_config: TreeFractionalIndexConfigInner::GenerateFractionalIndex {
                 jitter: 0,
-                rng: Box::new(rand::rngs::StdRng::seed_from_u64(0)),
             },
-            peer_id,
         }
     }
 
@@ -910,25 +904,31 @@ impl TreeState {
         }
     }
 
+    /// Generate the fractional index for the node moved or created at `index`.
+    ///
+    /// `jitter_seed` seeds the random jitter, so that the same op always gets the
+    /// same position. See [`Configure::jitter_seed`].
     pub(crate) fn generate_position_at(
         &mut self,
         target: &TreeID,
         parent: &TreeParentId,
         index: usize,
         cfg: FiIfNotConfigured,
+        jitter_seed: u64,
     ) -> FractionalIndexGenResult {
         match &mut self.fractional_index_config {
-            TreeFractionalIndexConfigInner::GenerateFractionalIndex { jitter, rng } => {
+            TreeFractionalIndexConfigInner::GenerateFractionalIndex { jitter } => {
                 if *jitter == 0 {
                     self.children
                         .entry(*parent)
                         .or_default()
                         .generate_fi_at(index, target)
                 } else {
+                    let mut rng = rand::rngs::StdRng::seed_from_u64(jitter_seed);
                     self.children
                         .entry(*parent)
                         .or_default()
-                        .generate_fi_at_jitter(index, target, rng.as_mut(), *jitter)
+                        .generate_fi_at_jitter(index, target, &mut rng, *jitter)
                 }
             }
             TreeFractionalIndexConfigInner::MoveDisabled => match cfg {
@@ -951,17 +951,8 @@ impl TreeState {
     }
 
     pub(crate) fn enable_generate_fractional_index(&mut self, jitter: u8) {
-        if let TreeFractionalIndexConfigInner::GenerateFractionalIndex {
-            jitter: old_jitter, ..
-        } = &mut self.fractional_index_config
-        {
-            *old_jitter = jitter;
-            return;
-        }
-        self.fractional_index_config = TreeFractionalIndexConfigInner::GenerateFractionalIndex {
-            jitter,
-            rng: Box::new(rand::rngs::StdRng::seed_from_u64(self.peer_id)),
-        };
+        self.fractional_index_config =
+            TreeFractionalIndexConfigInner::GenerateFractionalIndex { jitter };
     }
 
     pub(crate) fn disable_generate_fractional_index(&mut self) {
@@ -1743,7 +1734,7 @@ mod snapshot {
         fn decode_snapshot_fast(
             idx: crate::container::idx::ContainerIdx,
             (_, mut bytes): (loro_common::LoroValue, &[u8]),
-            ctx: crate::state::ContainerCreationContext,
+            _ctx: crate::state::ContainerCreationContext,
         ) -> loro_common::LoroResult<Self>
         where
             Self: Sized,
@@ -1756,7 +1747,7 @@ mod snapshot {
                 peers.push(PeerID::from_le_bytes(buf));
             }
 
-            let mut tree = Self::new(idx, ctx.peer);
+            let mut tree = Self::new(idx);
             let encoded: EncodedTree = serde_columnar::from_bytes(bytes)?;
             let fractional_indexes = PositionArena::decode(&encoded.fractional_indexes).unwrap();
             let fractional_indexes = fractional_indexes.parse_to_positions();
@@ -1832,7 +1823,6 @@ mod snapshot {
                 (LoroValue::Null, &bytes),
                 ContainerCreationContext {
                     configure: &Default::default(),
-                    peer: 0,
                 },
             )
             .unwrap();
@@ -0,0 +1,199 @@
+//! Read-only copies of the doc state that can be read on other threads.
+//!
+//! [`LoroDoc::snapshot_state`] forks the state without copying the container states: the fork
+//! shares the arena and the encoded states with the doc, and decodes them lazily. Reading the
+//! fork doesn't lock the doc, so a background exporter can read a consistent state while the
+//! doc keeps being edited.
+//!
+//! [`LoroDoc::checkout_to`] moves such a fork to another version instead of checking out the
+//! doc, so the app can show a past version while the doc stays attached and editable, without
+//! checking it back out afterwards.
+use std::{borrow::Cow, sync::Mutex};
+
+use loro_common::{ContainerID, ContainerType, LoroError, LoroResult, LoroValue};
+
+use crate::{
+    container::{idx::ContainerIdx, IntoContainerId},
+    diff_calc::{DiffCalculator, DiffMode},
+    event::{EventTriggerKind, InternalDocDiff},
+    oplog::OpLog,
+    partial_checkout::PartialCheckout,
+    state::DocState,
+    version::{shrink_frontiers, Frontiers},
+    LoroDoc,
+};
+
+/// A read-only copy of the state of a doc, created by [`LoroDoc::snapshot_state`].
+#[derive(Debug)]
+pub struct StateSnapshot {
+    state: Mutex<DocState>,
+    /// The root containers at the time of the snapshot, since the shared arena may
+    /// register more roots later
+    roots: Vec<ContainerIdx>,
+    frontiers: Frontiers,
+}
+
+impl LoroDoc {
+    /// Take a read-only copy of the current state.
+    ///
+    /// The pending transaction is committed first. It only encodes the containers that changed
+    /// since they were last encoded, and the copy decodes the states lazily when they are read.
+    pub fn snapshot_state(&self) -> StateSnapshot {
+        self.commit_then_renew();
+        let mut state = self.state.lock().unwrap();
+        let frontiers = state.frontiers.clone();
+        StateSnapshot {
+            state: Mutex::new(state.fork_readonly()),
+            roots: self.arena.root_containers(),
+            frontiers,
+        }
+    }
+
+    /// Get a read-only copy of the state at the given version, without checking out the doc.
+    ///
+    /// The current state is forked like [`LoroDoc::snapshot_state`] and the fork is checked
+    /// out to the version, so the doc keeps its state and stays editable. The pending
+    /// transaction is committed first.
+    pub fn checkout_to(&self, frontiers: &Frontiers) -> LoroResult<StateSnapshot> {
+        let (options, txn) = self.commit_then_stop();
+        let forked = self.fork_state_for_checkout(frontiers);
+        drop(txn);
+        self.renew_txn_if_auto_commit(options);
+        let (mut state, partial, frontiers) = forked?;
+
+        // The doc can be edited again while the fork is checked out, which doesn't change the
+        // diff between the two versions
+        let oplog = self.oplog.lock().unwrap();
+        if let Some(partial) = partial {
+            // Bring the partially checked out containers back to the version of the state
+            let to = state.frontiers.clone();
+            checkout_fork(
+                &oplog,
+                &mut state,
+                &partial.frontiers,
+                to,
+                Some(&|idx| partial.containers.contains(&idx)),
+            );
+        }
+
+        let from = state.frontiers.clone();
+        checkout_fork(&oplog, &mut state, &from, frontiers.clone(), None);
+        drop(oplog);
+        Ok(StateSnapshot {
+            state: Mutex::new(state),
+            roots: self.arena.root_containers(),
+            frontiers,
+        })
+    }
+
+    fn fork_state_for_checkout(
+        &self,
+        frontiers: &Frontiers,
+    ) -> LoroResult<(DocState, Option<PartialCheckout>, Frontiers)> {
+        let oplog = self.oplog.lock().unwrap();
+        for id in frontiers.iter() {
+            if !oplog.dag.contains(id) {
+                return Err(LoroError::FrontiersNotFound(id));
+            }
+        }
+
+        if oplog.dag.is_before_shallow_root(frontiers) {
+            return Err(LoroError::SwitchToVersionBeforeShallowRoot);
+        }
+
+        let frontiers = shrink_frontiers(frontiers, &oplog.dag)
+            .map_err(|_| LoroError::SwitchToVersionBeforeShallowRoot)?;
+        let mut state = self.state.lock().unwrap();
+        Ok((
+            state.fork_readonly(),
+            state.partial_checkout.clone(),
+            frontiers,
+        ))
+    }
+}
+
+/// Check out the containers of a forked state, or the ones accepted by `filter`, from `from`
+/// to `to`
+fn checkout_fork(
+    oplog: &OpLog,
+    state: &mut DocState,
+    from: &Frontiers,
+    to: Frontiers,
+    filter: Option<&dyn Fn(ContainerIdx) -> bool>,
+) {
+    if from == &to {
+        return;
+    }
+
+    let before = oplog.dag.frontiers_to_vv(from).unwrap();
+    let after = oplog.dag.frontiers_to_vv(&to).unwrap();
+    // Use a persisted calculator so that the diff is always calculated in the checkout mode
+    let mut calc = DiffCalculator::new(true);
+    let (diff, _) = calc.calc_diff_internal(oplog, &before, from, &after, &to, filter);
+    state.apply_diff(
+        InternalDocDiff {
+            origin: "checkout".into(),
+            diff: Cow::Owned(diff),
+            by: EventTriggerKind::Checkout,
+            new_version: Cow::Owned(to),
+        },
+        DiffMode::Checkout,
+    );
+}
+
+impl StateSnapshot {
+    /// The version of the state
+    pub fn frontiers(&self) -> &Frontiers {
+        &self.frontiers
+    }
+
+    /// Get the shallow value of the root containers, like [`LoroDoc::get_value`].
+    pub fn get_value(&self) -> LoroValue {
+        let state = self.state.lock().unwrap();
+        let ans: loro_common::LoroMapValue = self
+            .roots
+            .iter()
+            .map(|&idx| {
+                let id = state.arena.idx_to_id(idx).unwrap();
+                let ContainerID::Root { name, .. } = &id else {
+                    unreachable!()
+                };
+                (name.to_string(), LoroValue::Container(id))
+            })
+            .collect();
+        LoroValue::Map(ans)
+    }
+
+    /// Get the content of a text container, or `None` if it didn't exist in the state.
+    pub fn get_text<I: IntoContainerId>(&self, id: I) -> Option<String> {
+        let id = {
+            let state = self.state.lock().unwrap();
+            id.into_container_id(&state.arena, ContainerType::Text)
+        };
+        match self.get_container_deep_value(&id)? {
+            LoroValue::String(s) => Some(s.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Get the deep value of the state, like [`LoroDoc::get_deep_value`].
+    pub fn get_deep_value(&self) -> LoroValue {
+        self.state
+            .lock()
+            .unwrap()
+            .get_deep_value_of_roots(self.roots.clone())
+    }
+
+    /// Get the deep value of the container.
+    ///
+    /// Returns `None` if the container didn't exist in the state when it was copied.
+    pub fn get_container_deep_value(&self, id: &ContainerID) -> Option<LoroValue> {
+        let mut state = self.state.lock().unwrap();
+        let idx = state.arena.id_to_idx(id)?;
+        if !self.roots.contains(&idx) && !state.has_container_state(idx) {
+            return None;
+        }
+
+        Some(state.get_container_deep_value(idx))
+    }
+}
@@ -3,14 +3,15 @@ use super::{
     event::{DiffEvent, DocDiff},
 };
 use crate::{
-    container::idx::ContainerIdx, utils::subscription::SubscriberSet, ContainerDiff, LoroDoc,
-    Subscription,
+    container::idx::ContainerIdx, utils::subscription::SubscriberSet, version::Frontiers,
+    ContainerDiff, LoroDoc, Subscription, VersionVector,
 };
-use fxhash::FxHashMap;
 use loro_common::{ContainerID, ID};
 use smallvec::SmallVec;
 use std::{
-    collections::VecDeque,
+    any::Any,
+    collections::{BTreeMap, VecDeque},
+    panic::AssertUnwindSafe,
     sync::{Arc, Mutex},
 };
 
@@ -18,8 +19,31 @@ use std::{
 pub type LocalUpdateCallback = Box<dyn Fn(&Vec<u8>) -> bool + Send + Sync + 'static>;
 /// The callback of the peer id change. The second argument is the next counter for the peer.
 pub type PeerIdUpdateCallback = Box<dyn Fn(&ID) -> bool + Send + Sync + 'static>;
+/// The callback of the version change. It receives the new frontiers and version vector of the [crate::OpLog].
+pub type VersionUpdateCallback =
+    Box<dyn Fn(&Frontiers, &VersionVector) -> bool + Send + Sync + 'static>;
+pub(crate) type VersionUpdateCallbackInner =
+    Box<dyn Fn(&(Frontiers, VersionVector)) -> bool + Send + Sync + 'static>;
 pub type Subscriber = Arc<dyn (for<'a> Fn(DiffEvent<'a>)) + Send + Sync>;
 
+/// The lane of an event subscriber.
+///
+/// The subscribers of an event are invoked lane by lane, in the declaration order, so e.g. an
+/// editor view can render before an indexer or a persistence layer sees the event. In a lane,
+/// the container subscribers are invoked before the root subscribers, and the subscribers of
+/// the same target in the order of subscription.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum SubscriberPriority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl SubscriberPriority {
+    const ALL: [SubscriberPriority; 3] = [Self::High, Self::Normal, Self::Low];
+}
+
 impl LoroDoc {
     /// Subscribe to the changes of the peer id.
     pub fn subscribe_peer_id_change(&self, callback: PeerIdUpdateCallback) -> Subscription {
@@ -27,17 +51,47 @@ impl LoroDoc {
         enable();
         s
     }
+
+    /// Subscribe to the version changes of the [crate::OpLog].
+    ///
+    /// The callback is invoked after every commit or import that advances the version.
+    /// Unlike [LoroDoc::subscribe_root], it doesn't require calculating the container diffs.
+    pub fn subscribe_version(&self, callback: VersionUpdateCallback) -> Subscription {
+        let (s, enable) = self
+            .version_change_subs
+            .inner()
+            .insert((), Box::new(move |(f, vv)| callback(f, vv)));
+        enable();
+        s
+    }
+
+    pub(crate) fn emit_version_change(&self) {
+        if self.version_change_subs.inner().is_empty() {
+            return;
+        }
+
+        let (f, vv) = {
+            let oplog = self.oplog.lock().unwrap();
+            (oplog.frontiers().clone(), oplog.vv().clone())
+        };
+        self.version_change_subs.emit(&(), (f, vv));
+    }
 }
 
 struct ObserverInner {
-    subscriber_set: SubscriberSet<Option<ContainerIdx>, Subscriber>,
+    /// The subscribers of each [SubscriberPriority]
+    lanes: [SubscriberSet<Option<ContainerIdx>, Subscriber>; 3],
     queue: Arc<Mutex<VecDeque<DocDiff>>>,
 }
 
 impl Default for ObserverInner {
     fn default() -> Self {
         Self {
-            subscriber_set: SubscriberSet::new(),
+            lanes: [
+                SubscriberSet::new(),
+                SubscriberSet::new(),
+                SubscriberSet::new(),
+            ],
             queue: Arc::new(Mutex::new(VecDeque::new())),
         }
     }
@@ -62,17 +116,24 @@ impl Observer {
         }
     }
 
-    pub fn subscribe(&self, id: &ContainerID, callback: Subscriber) -> Subscription {
+    pub fn subscribe(
+        &self,
+        id: &ContainerID,
+        priority: SubscriberPriority,
+        callback: Subscriber,
+    ) -> Subscription {
         let idx = self.arena.register_container(id);
-        let inner = &self.inner;
-        let (sub, enable) = inner.subscriber_set.insert(Some(idx), callback);
+        let (sub, enable) = self.inner.lanes[priority as usize].insert(Some(idx), callback);
         enable();
         sub
     }
 
-    pub fn subscribe_root(&self, callback: Subscriber) -> Subscription {
-        let inner = &self.inner;
-        let (sub, enable) = inner.subscriber_set.insert(None, callback);
+    pub fn subscribe_root(
+        &self,
+        priority: SubscriberPriority,
+        callback: Subscriber,
+    ) -> Subscription {
+        let (sub, enable) = self.inner.lanes[priority as usize].insert(None, callback);
         enable();
         sub
     }
@@ -89,14 +150,17 @@ impl Observer {
     }
 
     // When emitting changes, we need to make sure that the observer is not locked.
+    //
+    // A panicking subscriber doesn't prevent the others from being invoked. The first panic is
+    // resumed once all of them have been invoked.
     fn emit_inner(&self, doc_diff: DocDiff) -> bool {
         let inner = &self.inner;
-        let mut container_events_map: FxHashMap<ContainerIdx, SmallVec<[&ContainerDiff; 1]>> =
+        let mut container_events_map: BTreeMap<ContainerIdx, SmallVec<[&ContainerDiff; 1]>> =
             Default::default();
         for container_diff in doc_diff.diff.iter() {
             self.arena
                 .with_ancestors(container_diff.idx, |ancestor, _| {
-                    if inner.subscriber_set.may_include(&Some(ancestor)) {
+                    if inner.lanes.iter().any(|s| s.may_include(&Some(ancestor))) {
                         container_events_map
                             .entry(ancestor)
                             .or_default()
@@ -108,48 +172,66 @@ impl Observer {
         {
             // Check whether we are calling events recursively.
             // If so, push the event to the queue
-            if inner.subscriber_set.is_recursive_calling(&None)
-                || container_events_map
-                    .keys()
-                    .any(|x| inner.subscriber_set.is_recursive_calling(&Some(*x)))
-            {
+            if inner.lanes.iter().any(|s| {
+                s.is_recursive_calling(&None)
+                    || container_events_map
+                        .keys()
+                        .any(|x| s.is_recursive_calling(&Some(*x)))
+            }) {
                 drop(container_events_map);
                 inner.queue.lock().unwrap().push_back(doc_diff);
                 return false;
             }
         }
 
-        for (container_idx, container_diffs) in container_events_map {
-            inner
-                .subscriber_set
-                .retain(&Some(container_idx), &mut |callback| {
-                    (callback)(DiffEvent {
-                        current_target: Some(self.arena.get_container_id(container_idx).unwrap()),
-                        events: &container_diffs,
-                        event_meta: &doc_diff,
+        let events: Vec<_> = doc_diff.diff.iter().collect();
+        let mut panic = None;
+        for priority in SubscriberPriority::ALL {
+            let lane = &inner.lanes[priority as usize];
+            for (container_idx, container_diffs) in container_events_map.iter() {
+                lane.retain(&Some(*container_idx), &mut |callback| {
+                    call_isolated(&mut panic, || {
+                        (callback)(DiffEvent {
+                            current_target: Some(
+                                self.arena.get_container_id(*container_idx).unwrap(),
+                            ),
+                            events: container_diffs,
+                            event_meta: &doc_diff,
+                        })
                     });
                     true
                 })
                 .unwrap();
-        }
+            }
 
-        let events: Vec<_> = doc_diff.diff.iter().collect();
-        inner
-            .subscriber_set
-            .retain(&None, &mut |callback| {
-                (callback)(DiffEvent {
-                    current_target: None,
-                    events: &events,
-                    event_meta: &doc_diff,
+            lane.retain(&None, &mut |callback| {
+                call_isolated(&mut panic, || {
+                    (callback)(DiffEvent {
+                        current_target: None,
+                        events: &events,
+                        event_meta: &doc_diff,
+                    })
                 });
                 true
             })
             .unwrap();
+        }
+
+        if let Some(payload) = panic {
+            std::panic::resume_unwind(payload);
+        }
 
         true
     }
 }
 
+/// Call `f`, and keep the payload of the first panic in `panic`
+fn call_isolated(panic: &mut Option<Box<dyn Any + Send>>, f: impl FnOnce()) {
+    if let Err(payload) = std::panic::catch_unwind(AssertUnwindSafe(f)) {
+        panic.get_or_insert(payload);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::sync::atomic::{AtomicUsize, Ordering};
@@ -220,4 +302,75 @@ mod test {
         }
         assert_eq!(count.load(Ordering::SeqCst), 2);
     }
+
+    #[test]
+    fn subscribe_descendants() {
+        let loro = LoroDoc::new_auto_commit();
+        let map = loro.get_map("map");
+        let map_id = map.id();
+        let count = Arc::new(AtomicUsize::new(0));
+        let count_cp = Arc::clone(&count);
+        let _sub = loro.subscribe(
+            &map.id(),
+            Arc::new(move |e| {
+                assert_eq!(e.current_target.as_ref(), Some(&map_id));
+                count_cp.fetch_add(e.events.len(), Ordering::SeqCst);
+            }),
+        );
+
+        // The child is created after the subscription
+        let list = map
+            .insert_container("list", crate::ListHandler::new_detached())
+            .unwrap();
+        loro.commit_then_renew();
+        let created = count.load(Ordering::SeqCst);
+        assert!(created > 0);
+        list.push(1).unwrap();
+        loro.commit_then_renew();
+        assert_eq!(count.load(Ordering::SeqCst), created + 1);
+        loro.get_list("other").push(1).unwrap();
+        loro.commit_then_renew();
+        assert_eq!(count.load(Ordering::SeqCst), created + 1);
+    }
+
+    #[test]
+    fn priority_lanes() {
+        let loro = LoroDoc::new();
+        let text = loro.get_text("text");
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let o = order.clone();
+        let _low = loro.subscribe_root_with_priority(
+            SubscriberPriority::Low,
+            Arc::new(move |_| o.lock().unwrap().push("low")),
+        );
+        let o = order.clone();
+        let _normal = loro.subscribe(
+            &text.id(),
+            Arc::new(move |_| o.lock().unwrap().push("normal")),
+        );
+        let o = order.clone();
+        let high = loro.subscribe_root_with_priority(
+            SubscriberPriority::High,
+            Arc::new(move |_| {
+                o.lock().unwrap().push("high");
+                panic!("the view crashed");
+            }),
+        );
+
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut txn = loro.txn().unwrap();
+            text.insert_with_txn(&mut txn, 0, "123").unwrap();
+            txn.commit().unwrap();
+        }));
+        // The panic is resumed after the other lanes are invoked
+        assert!(result.is_err());
+        assert_eq!(*order.lock().unwrap(), vec!["high", "normal", "low"]);
+
+        drop(high);
+        order.lock().unwrap().clear();
+        let mut txn = loro.txn().unwrap();
+        text.insert_with_txn(&mut txn, 0, "123").unwrap();
+        txn.commit().unwrap();
+        assert_eq!(*order.lock().unwrap(), vec!["normal", "low"]);
+    }
 }
@@ -0,0 +1,373 @@
+//! Moving container subtrees between documents.
+//!
+//! A workspace that keeps every page in one doc grows until loading it is too slow. With
+//! [`LoroDoc::extract_subtree`], a page can be split out into its own doc, and with
+//! [`LoroDoc::merge_in`], a doc can be embedded into another one again. A single container
+//! can be copied into another doc with [`Handler::copy_into`]. The content is copied,
+//! including the styles of the texts and the nested containers, but the history is not: the
+//! containers get new ids in the target doc.
+use loro_common::{
+    check_root_container_name, ContainerID, ContainerType, LoroError, LoroResult, LoroValue,
+};
+
+use crate::{
+    event::Index,
+    handler::{Handler, TextDelta, ValueOrHandler},
+    state::TreeParentId,
+    HandlerTrait, LoroDoc, TreeHandler,
+};
+
+/// The name of the root container of the doc created by [`LoroDoc::extract_subtree`], if
+/// the extracted container is not a root container
+pub const SUBTREE_ROOT_NAME: &str = "root";
+
+/// The commit metadata key of the id of the container copied by [`Handler::copy_into`]
+pub const COPIED_FROM_METADATA_KEY: &str = "copied_from";
+/// The commit metadata key of the version of the source doc copied by [`Handler::copy_into`],
+/// as the comma-separated ids of its frontiers
+pub const COPIED_VERSION_METADATA_KEY: &str = "copied_version";
+
+impl LoroDoc {
+    /// Create a new doc that contains a copy of the container `id` and its descendants.
+    ///
+    /// The copy is the root container of the new doc. It keeps the name of `id` if `id` is a
+    /// root container, and is named [`SUBTREE_ROOT_NAME`] otherwise. The new doc has the same
+    /// configuration, and its content is created in a single change.
+    pub fn extract_subtree(&self, id: &ContainerID) -> LoroResult<LoroDoc> {
+        let src = self
+            .get_handler(id.clone())
+            .ok_or_else(|| LoroError::ContainersNotFound {
+                containers: Box::new(vec![id.clone()]),
+            })?;
+        let name = match id {
+            ContainerID::Root { name, .. } => name.as_str(),
+            ContainerID::Normal { .. } => SUBTREE_ROOT_NAME,
+        };
+
+        let doc = LoroDoc::new_auto_commit();
+        doc.config.set_config(&self.config);
+        let dst = doc
+            .get_handler(ContainerID::new_root(name, id.container_type()))
+            .unwrap();
+        copy_container(&src, &dst)?;
+        doc.commit_then_renew();
+        Ok(doc)
+    }
+
+    /// Copy the only root container of `other` into this doc at `path`, in a single commit.
+    /// Returns the handler of the copy.
+    ///
+    /// - If `path` has one element, the content is merged into the root container of the same
+    ///   name: the list items and the tree nodes are appended, the map entries are overwritten,
+    ///   the text is appended and the counter is incremented.
+    /// - Otherwise, a new container is created in the container at `path[..path.len() - 1]`,
+    ///   at the key or the index of the last element of `path`.
+    pub fn merge_in(&self, other: &LoroDoc, path: &[Index]) -> LoroResult<Handler> {
+        let LoroValue::Map(roots) = other.get_value() else {
+            unreachable!()
+        };
+        let mut roots = roots.values().filter_map(|v| v.as_container());
+        let (Some(src_id), None) = (roots.next(), roots.next()) else {
+            return Err(LoroError::ArgErr(
+                "The merged doc must have exactly one root container".into(),
+            ));
+        };
+        let src = other.get_handler(src_id.clone()).unwrap();
+        self.copy_to_path(&src, path, &[])
+    }
+
+    /// Copy `src` to `path` in a single commit with the given metadata, see
+    /// [`LoroDoc::merge_in`] for how `path` is interpreted
+    fn copy_to_path(
+        &self,
+        src: &Handler,
+        path: &[Index],
+        metadata: &[(&str, String)],
+    ) -> LoroResult<Handler> {
+        let kind = src.kind();
+        self.commit_then_renew();
+        for (key, value) in metadata {
+            self.set_next_commit_metadata(key, value);
+        }
+        let ans = match path {
+            [] => Err(LoroError::ArgErr("The path cannot be empty".into())),
+            [Index::Key(name)] if !check_root_container_name(name) => Err(LoroError::ArgErr(
+                format!("Invalid root container name {name}").into_boxed_str(),
+            )),
+            [Index::Key(name)] => Ok(self.get_handler(ContainerID::new_root(name, kind)).unwrap()),
+            [parent @ .., last] => self.insert_empty_container(parent, last, kind),
+        }
+        .and_then(|dst| {
+            copy_container(src, &dst)?;
+            Ok(dst)
+        });
+        self.commit_then_renew();
+        ans
+    }
+
+    fn insert_empty_container(
+        &self,
+        parent: &[Index],
+        index: &Index,
+        kind: ContainerType,
+    ) -> LoroResult<Handler> {
+        let Some(ValueOrHandler::Handler(parent)) = self.get_by_path(parent) else {
+            return Err(LoroError::ArgErr(
+                "The path doesn't point into a container".into(),
+            ));
+        };
+
+        let child = Handler::new_unattached(kind);
+        match (&parent, index) {
+            (Handler::Map(m), Index::Key(key)) => m.insert_container(key, child),
+            (Handler::List(l), Index::Seq(i)) => l.insert_container(*i, child),
+            (Handler::MovableList(l), Index::Seq(i)) => l.insert_container(*i, child),
+            _ => Err(LoroError::ArgErr(
+                format!(
+                    "Cannot insert a container at {index:?} of a {}",
+                    parent.kind()
+                )
+                .into_boxed_str(),
+            )),
+        }
+    }
+}
+
+impl Handler {
+    /// Copy the current content of the container into `doc` at `path`, in a single commit.
+    /// Returns the handler of the copy.
+    ///
+    /// The copy has new container ids, and keeps the styles of the texts. `path` is
+    /// interpreted like in [`LoroDoc::merge_in`]: a path of one key merges the content into
+    /// the root container of that name. If `record_source` is true and the container is
+    /// attached, the commit records its id and the version of its doc in the metadata, under
+    /// [`COPIED_FROM_METADATA_KEY`] and [`COPIED_VERSION_METADATA_KEY`].
+    pub fn copy_into(
+        &self,
+        doc: &LoroDoc,
+        path: &[Index],
+        record_source: bool,
+    ) -> LoroResult<Handler> {
+        let mut metadata = Vec::new();
+        if let Some(src_doc) = self.doc().filter(|_| record_source) {
+            let version = src_doc
+                .state_frontiers()
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            metadata.push((COPIED_FROM_METADATA_KEY, self.id().to_string()));
+            metadata.push((COPIED_VERSION_METADATA_KEY, version));
+        }
+
+        doc.copy_to_path(self, path, &metadata)
+    }
+}
+
+/// Copy the content of `src` to the end of `dst`, which is of the same type
+fn copy_container(src: &Handler, dst: &Handler) -> LoroResult<()> {
+    match (src, dst) {
+        (Handler::Text(src), Handler::Text(dst)) => {
+            let mut delta = src.get_delta();
+            if delta.is_empty() {
+                return Ok(());
+            }
+
+            if !dst.is_empty() {
+                delta.insert(
+                    0,
+                    TextDelta::Retain {
+                        retain: dst.len_event(),
+                        attributes: None,
+                    },
+                );
+            }
+
+            dst.apply_delta(&delta)
+        }
+        (Handler::List(src), Handler::List(dst)) => {
+            let mut items = Vec::with_capacity(src.len());
+            src.for_each(|v| items.push(v));
+            for item in items {
+                match item {
+                    ValueOrHandler::Value(v) => dst.push(v)?,
+                    ValueOrHandler::Handler(h) => {
+                        let new = dst.push_container(Handler::new_unattached(h.kind()))?;
+                        copy_container(&h, &new)?;
+                    }
+                }
+            }
+
+            Ok(())
+        }
+        (Handler::MovableList(src), Handler::MovableList(dst)) => {
+            let mut items = Vec::with_capacity(src.len());
+            src.for_each(|v| items.push(v));
+            for item in items {
+                match item {
+                    ValueOrHandler::Value(v) => dst.push(v)?,
+                    ValueOrHandler::Handler(h) => {
+                        let new = dst.push_container(Handler::new_unattached(h.kind()))?;
+                        copy_container(&h, &new)?;
+                    }
+                }
+            }
+
+            Ok(())
+        }
+        (Handler::Map(src), Handler::Map(dst)) => {
+            let mut entries = Vec::with_capacity(src.len());
+            src.for_each(|k, v| entries.push((k.to_string(), v)));
+            for (k, v) in entries {
+                match v {
+                    ValueOrHandler::Value(v) => dst.insert(&k, v)?,
+                    ValueOrHandler::Handler(h) => {
+                        let new = dst.insert_container(&k, Handler::new_unattached(h.kind()))?;
+                        copy_container(&h, &new)?;
+                    }
+                }
+            }
+
+            Ok(())
+        }
+        (Handler::Tree(src), Handler::Tree(dst)) => {
+            if !src.is_fractional_index_enabled() {
+                dst.disable_fractional_index();
+            }
+
+            copy_tree_children(src, dst, TreeParentId::Root, TreeParentId::Root)
+        }
+        #[cfg(feature = "counter")]
+        (Handler::Counter(src), Handler::Counter(dst)) => {
+            dst.increment(*src.get_value().as_double().unwrap())
+        }
+        _ => Err(LoroError::ArgErr(
+            format!(
+                "Cannot copy a {} container into a {}",
+                src.kind(),
+                dst.kind()
+            )
+            .into_boxed_str(),
+        )),
+    }
+}
+
+fn copy_tree_children(
+    src: &TreeHandler,
+    dst: &TreeHandler,
+    src_parent: TreeParentId,
+    dst_parent: TreeParentId,
+) -> LoroResult<()> {
+    for node in src.children(&src_parent).unwrap_or_default() {
+        let new = dst.create(dst_parent)?;
+        copy_container(
+            &Handler::Map(src.get_meta(node)?),
+            &Handler::Map(dst.get_meta(new)?),
+        )?;
+        copy_tree_children(src, dst, TreeParentId::Node(node), TreeParentId::Node(new))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ListHandler, MapHandler, TextHandler};
+
+    #[test]
+    fn extract_and_merge_back() {
+        let doc = LoroDoc::new_auto_commit();
+        let pages = doc.get_list("pages");
+        let page = pages
+            .insert_container(0, MapHandler::new_detached())
+            .unwrap();
+        let title = page
+            .insert_container("title", TextHandler::new_detached())
+            .unwrap();
+        title.insert(0, "Hello world").unwrap();
+        title.mark(0, 5, "bold", true.into()).unwrap();
+        let blocks = page
+            .insert_container("blocks", ListHandler::new_detached())
+            .unwrap();
+        blocks.push(1).unwrap();
+        let tree = page
+            .insert_container("outline", TreeHandler::new_detached())
+            .unwrap();
+        let node = tree.create(TreeParentId::Root).unwrap();
+        tree.get_meta(node).unwrap().insert("name", "a").unwrap();
+        tree.create(TreeParentId::Node(node)).unwrap();
+        doc.commit_then_renew();
+
+        let page_doc = doc.extract_subtree(&page.id()).unwrap();
+        assert_eq!(page_doc.len_changes(), 1);
+        let copy = page_doc.get_map(SUBTREE_ROOT_NAME);
+        assert_eq!(copy.get_deep_value(), page.get_deep_value());
+        let copied_title = copy.get_child_handler("title").unwrap();
+        assert_eq!(
+            copied_title.as_text().unwrap().get_richtext_value(),
+            title.get_richtext_value()
+        );
+
+        let merged = doc
+            .merge_in(&page_doc, &[Index::Key("pages".into()), Index::Seq(1)])
+            .unwrap();
+        assert_ne!(merged.id(), page.id());
+        assert_eq!(merged.get_deep_value(), page.get_deep_value());
+        assert_eq!(pages.len(), 2);
+
+        let other = LoroDoc::new_auto_commit();
+        other.get_list("pages").push("intro").unwrap();
+        let list_doc = doc.extract_subtree(&pages.id()).unwrap();
+        other
+            .merge_in(&list_doc, &[Index::Key("pages".into())])
+            .unwrap();
+        assert_eq!(other.get_list("pages").len(), 3);
+        assert!(doc
+            .merge_in(
+                &page_doc,
+                &[Index::Key("pages".into()), Index::Key("x".into())]
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn copy_into_other_doc() {
+        let doc = LoroDoc::new_auto_commit();
+        doc.set_peer_id(1).unwrap();
+        let title = doc.get_text("title");
+        title.insert(0, "Hello world").unwrap();
+        title.mark(0, 5, "bold", true.into()).unwrap();
+        doc.commit_then_renew();
+
+        let other = LoroDoc::new_auto_commit();
+        other.set_peer_id(2).unwrap();
+        let page = other.get_map("page");
+        let copy = Handler::Text(title.clone())
+            .copy_into(
+                &other,
+                &[Index::Key("page".into()), Index::Key("title".into())],
+                true,
+            )
+            .unwrap();
+        assert_eq!(copy.id().container_type(), ContainerType::Text);
+        assert_eq!(
+            copy.as_text().unwrap().get_richtext_value(),
+            title.get_richtext_value()
+        );
+        assert_eq!(page.get_child_handler("title").unwrap().id(), copy.id());
+
+        let change = other
+            .oplog()
+            .lock()
+            .unwrap()
+            .get_change_at(loro_common::ID::new(2, 0))
+            .unwrap();
+        let metadata = crate::ChangeMeta::from_change(&change).metadata();
+        assert_eq!(metadata[COPIED_FROM_METADATA_KEY], title.id().to_string());
+        assert_eq!(
+            metadata[COPIED_VERSION_METADATA_KEY],
+            doc.state_frontiers().as_single().unwrap().to_string()
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! Per-session flags of tree nodes that are not recorded in the history.
+//!
+//! The UI state of a tree, e.g. whether a node is expanded, shouldn't be part of the doc,
+//! but it needs to follow the nodes when the tree changes. [`TreeOverlay`] stores the flags
+//! in an [`EphemeralStore`] keyed by the node ids, and resolves them against the current
+//! structure of the tree, so the flags of the deleted nodes are ignored by the queries.
+//!
+//! The flags can be shared with the other sessions of the same user, e.g. other tabs, by
+//! the updates of the underlying [`EphemeralStore`].
+use fxhash::FxHashMap;
+use loro_common::{LoroResult, LoroTreeError, LoroValue, TreeID};
+
+use crate::{
+    awareness::{EphemeralEventTrigger, EphemeralStore, LocalEphemeralCallback},
+    handler::TreeHandler,
+    Subscription, TreeParentId,
+};
+
+const EXPANDED: &str = "expanded";
+
+/// The flags of the tree nodes changed in a [`TreeOverlay`].
+#[derive(Debug, Clone)]
+pub struct TreeOverlayEvent {
+    pub by: EphemeralEventTrigger,
+    /// The node ids and the names of the flags that are added or updated
+    pub updated: Vec<(TreeID, String)>,
+    /// The node ids and the names of the flags that are removed
+    pub removed: Vec<(TreeID, String)>,
+}
+
+pub type TreeOverlaySubscriber = Box<dyn Fn(&TreeOverlayEvent) -> bool + Send + Sync + 'static>;
+
+/// Non-replicated flags attached to the nodes of a tree, e.g. the expanded state in the UI.
+///
+/// # Example
+///
+/// ```rust
+/// use loro_internal::{tree_overlay::TreeOverlay, LoroDoc, TreeParentId};
+///
+/// let doc = LoroDoc::new_auto_commit();
+/// let tree = doc.get_tree("tree");
+/// let root = tree.create(TreeParentId::Root).unwrap();
+/// let child = tree.create(root.into()).unwrap();
+/// let mut overlay = TreeOverlay::new(tree.clone(), i64::MAX);
+/// assert_eq!(overlay.visible_nodes(), vec![root]);
+/// overlay.set_expanded(root, true).unwrap();
+/// assert_eq!(overlay.visible_nodes(), vec![root, child]);
+/// ```
+pub struct TreeOverlay {
+    tree: TreeHandler,
+    store: EphemeralStore,
+}
+
+impl std::fmt::Debug for TreeOverlay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TreeOverlay")
+            .field("tree", &self.tree)
+            .field("store", &self.store)
+            .finish()
+    }
+}
+
+fn to_key(node: TreeID, flag: &str) -> String {
+    format!("{}/{}", node, flag)
+}
+
+fn parse_key(key: &str) -> Option<(TreeID, &str)> {
+    let (node, flag) = key.split_once('/')?;
+    let node = TreeID::try_from(node).ok()?;
+    Some((node, flag))
+}
+
+impl TreeOverlay {
+    /// Create an overlay of the tree.
+    ///
+    /// The flags that are not updated within `timeout` milliseconds are removed by
+    /// [`TreeOverlay::remove_outdated`]. Use `i64::MAX` to keep them for the whole session.
+    pub fn new(tree: TreeHandler, timeout: i64) -> Self {
+        Self {
+            tree,
+            store: EphemeralStore::new(timeout),
+        }
+    }
+
+    pub fn tree(&self) -> &TreeHandler {
+        &self.tree
+    }
+
+    fn is_alive(&self, node: &TreeID) -> bool {
+        !self.tree.is_node_unexist(node) && matches!(self.tree.is_node_deleted(node), Ok(false))
+    }
+
+    /// Set the flag of the node. The node must exist and not be deleted.
+    pub fn set(&mut self, node: TreeID, flag: &str, value: impl Into<LoroValue>) -> LoroResult<()> {
+        if !self.is_alive(&node) {
+            return Err(LoroTreeError::TreeNodeNotExist(node).into());
+        }
+
+        self.store.set(&to_key(node, flag), value);
+        Ok(())
+    }
+
+    /// Remove the flag of the node.
+    pub fn remove(&mut self, node: TreeID, flag: &str) {
+        let key = to_key(node, flag);
+        if self.store.get(&key).is_some() {
+            self.store.delete(&key);
+        }
+    }
+
+    /// Get the flag of the node. It's `None` if the node is deleted.
+    pub fn get(&self, node: TreeID, flag: &str) -> Option<LoroValue> {
+        let value = self.store.get(&to_key(node, flag))?;
+        self.is_alive(&node).then_some(value)
+    }
+
+    /// Get all the flags of the node. It's empty if the node is deleted.
+    pub fn flags(&self, node: TreeID) -> FxHashMap<String, LoroValue> {
+        if !self.is_alive(&node) {
+            return FxHashMap::default();
+        }
+
+        self.store
+            .get_all_states()
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let (n, flag) = parse_key(&key)?;
+                (n == node).then(|| (flag.to_string(), value))
+            })
+            .collect()
+    }
+
+    /// Get the alive nodes that have the flag.
+    pub fn nodes_with_flag(&self, flag: &str) -> Vec<TreeID> {
+        let mut ans: Vec<TreeID> = self
+            .store
+            .keys()
+            .filter_map(parse_key)
+            .filter(|(node, f)| *f == flag && self.is_alive(node))
+            .map(|(node, _)| node)
+            .collect();
+        ans.sort_unstable();
+        ans
+    }
+
+    /// Mark the node as expanded or collapsed. The nodes are collapsed by default.
+    pub fn set_expanded(&mut self, node: TreeID, expanded: bool) -> LoroResult<()> {
+        if expanded {
+            self.set(node, EXPANDED, true)
+        } else {
+            self.remove(node, EXPANDED);
+            Ok(())
+        }
+    }
+
+    pub fn is_expanded(&self, node: TreeID) -> bool {
+        matches!(self.get(node, EXPANDED), Some(LoroValue::Bool(true)))
+    }
+
+    /// Get the nodes that are visible in the current structure of the tree in depth-first
+    /// order, i.e. the roots and the children of the visible expanded nodes.
+    pub fn visible_nodes(&self) -> Vec<TreeID> {
+        let mut ans = Vec::new();
+        let mut stack: Vec<TreeID> = self.tree.roots();
+        stack.reverse();
+        while let Some(node) = stack.pop() {
+            ans.push(node);
+            if self.is_expanded(node) {
+                if let Some(children) = self.tree.children(&TreeParentId::Node(node)) {
+                    stack.extend(children.into_iter().rev());
+                }
+            }
+        }
+
+        ans
+    }
+
+    /// Remove the flags of the deleted nodes, and return the removed nodes.
+    ///
+    /// The flags of the nodes that are not in the tree yet are kept, because their
+    /// creations may be imported later.
+    pub fn prune(&mut self) -> Vec<TreeID> {
+        let mut removed_nodes = Vec::new();
+        let keys: Vec<String> = self.store.keys().map(|k| k.to_string()).collect();
+        for key in keys {
+            let Some((node, _)) = parse_key(&key) else {
+                continue;
+            };
+
+            if matches!(self.tree.is_node_deleted(&node), Ok(true)) {
+                self.store.delete(&key);
+                if !removed_nodes.contains(&node) {
+                    removed_nodes.push(node);
+                }
+            }
+        }
+
+        removed_nodes
+    }
+
+    /// Remove the flags that are not updated within the timeout.
+    pub fn remove_outdated(&mut self) {
+        self.store.remove_outdated();
+    }
+
+    /// Subscribe the changes of the flags.
+    pub fn subscribe(&self, callback: TreeOverlaySubscriber) -> Subscription {
+        self.store.subscribe(Box::new(move |e| {
+            let parse = |keys: &[String]| -> Vec<(TreeID, String)> {
+                keys.iter()
+                    .filter_map(|k| parse_key(k))
+                    .map(|(node, flag)| (node, flag.to_string()))
+                    .collect()
+            };
+
+            let mut updated = parse(&e.added);
+            updated.extend(parse(&e.updated));
+            callback(&TreeOverlayEvent {
+                by: e.by,
+                updated,
+                removed: parse(&e.removed),
+            })
+        }))
+    }
+
+    /// Subscribe the local updates, which can be applied to the overlays of the other
+    /// sessions by [`TreeOverlay::apply`].
+    pub fn subscribe_local_updates(&self, callback: LocalEphemeralCallback) -> Subscription {
+        self.store.subscribe_local_updates(callback)
+    }
+
+    pub fn encode_all(&self) -> Vec<u8> {
+        self.store.encode_all()
+    }
+
+    pub fn apply(&mut self, data: &[u8]) {
+        self.store.apply(data)
+    }
+}
@@ -67,6 +67,7 @@ impl crate::LoroDoc {
 
         let obs = self.observer.clone();
         let local_update_subs_weak = self.local_update_subs.downgrade();
+        let version_change_subs_weak = self.version_change_subs.downgrade();
         txn.set_on_commit(Box::new(move |state, oplog, id_span| {
             let mut state = state.lock().unwrap();
             let events = state.take_events();
@@ -86,6 +87,16 @@ impl crate::LoroDoc {
                     local_update_subs.emit(&(), bytes);
                 }
             }
+
+            if let Some(version_change_subs) = version_change_subs_weak.upgrade() {
+                if !version_change_subs.inner().is_empty() {
+                    let version = {
+                        let oplog = oplog.lock().unwrap();
+                        (oplog.frontiers().clone(), oplog.vv().clone())
+                    };
+                    version_change_subs.emit(&(), version);
+                }
+            }
         }));
 
         Ok(txn)
@@ -224,6 +235,20 @@ pub(super) enum EventHint {
     Counter(f64),
 }
 
+impl EventHint {
+    /// Parse the values like [`RawOpContent::parse_tags`], so that the events match the ops
+    fn parse_tags(&mut self) {
+        match self {
+            Self::Mark { style, .. } => style.data = std::mem::take(&mut style.data).parse_tags(),
+            Self::SetList { value, .. }
+            | Self::Map {
+                value: Some(value), ..
+            } => *value = std::mem::take(value).parse_tags(),
+            _ => {}
+        }
+    }
+}
+
 impl generic_btree::rle::HasLength for EventHint {
     fn rle_len(&self) -> usize {
         match self {
@@ -407,6 +432,16 @@ impl Transaction {
         self._commit()
     }
 
+    /// Drop the transaction without committing it.
+    ///
+    /// The ops have already been applied to the state, so the caller should revert them.
+    pub(crate) fn abort(mut self) {
+        self.finished = true;
+        if let Some(doc) = self.doc.upgrade() {
+            doc.state.lock().unwrap().abort_txn();
+        }
+    }
+
     #[tracing::instrument(level = "debug", skip(self))]
     fn _commit(&mut self) -> Result<Option<CommitOptions>, LoroError> {
         if self.finished {
@@ -501,8 +536,8 @@ impl Transaction {
     pub(super) fn apply_local_op(
         &mut self,
         container: ContainerIdx,
-        content: RawOpContent,
-        event: EventHint,
+        mut content: RawOpContent,
+        mut event: EventHint,
         // check whether context and txn are referring to the same state context
         doc: &LoroDoc,
     ) -> LoroResult<()> {
@@ -527,6 +562,15 @@ impl Transaction {
 
         let len = content.content_len();
         assert!(len > 0);
+        if doc.config.extended_value_types() {
+            // The user maps that look like tagged objects are only parsed after opting in
+            content.parse_tags();
+            event.parse_tags();
+        } else if let Some(ty) = content.extended_value_type() {
+            return Err(LoroError::ExtendedValueTypeDisabled(ty.into()));
+        }
+
+        self.arena.intern_raw_op_content(&mut content);
         let raw_op = RawOp {
             id: ID {
                 peer: self.peer,
@@ -538,7 +582,14 @@ impl Transaction {
         };
 
         let mut oplog = doc.oplog.lock().unwrap();
+        if doc.config.single_writer() {
+            crate::single_writer::check_single_writer(oplog.vv(), self.peer)?;
+        }
         let mut state = doc.state.lock().unwrap();
+        if let Some(reason) = crate::freeze::frozen_reason(&mut oplog, &mut state) {
+            return Err(LoroError::DocFrozen(reason.into()));
+        }
+
         if state.is_deleted(container) {
             return Err(LoroError::ContainerDeleted {
                 container: Box::new(state.arena.idx_to_id(container).unwrap()),
@@ -585,7 +636,7 @@ impl Transaction {
                 self.event_hints.push(event);
             }
         }
-        self.local_ops.push(op);
+        doc.config.delete_merge().push_op(&mut self.local_ops, op);
         Ok(())
     }
 
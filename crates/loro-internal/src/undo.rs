@@ -4,13 +4,14 @@ use std::{
 };
 
 use either::Either;
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 use loro_common::{
     ContainerID, Counter, CounterSpan, HasIdSpan, IdSpan, LoroResult, LoroValue, PeerID,
 };
 use tracing::{debug_span, info_span, instrument};
 
 use crate::{
+    arena::SharedArena,
     change::{get_sys_timestamp, Timestamp},
     cursor::{AbsolutePosition, Cursor},
     delta::TreeExternalDiff,
@@ -78,6 +79,13 @@ impl DiffBatch {
         self.order.clear();
     }
 
+    /// Keep only the diffs of the containers for which `f` returns true
+    pub fn retain(&mut self, mut f: impl FnMut(&ContainerID) -> bool) {
+        self.order.retain(|cid| f(cid));
+        let order = &self.order;
+        self.cid_to_events.retain(|cid, _| order.contains(cid));
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (&ContainerID, &Diff)> + '_ {
         self.order
             .iter()
@@ -198,6 +206,7 @@ struct UndoManagerInner {
     merge_interval_in_ms: i64,
     max_stack_size: usize,
     exclude_origin_prefixes: Vec<Box<str>>,
+    exclude_containers: FxHashSet<ContainerID>,
     last_popped_selection: Option<Vec<CursorWithPos>>,
     on_push: Option<OnPush>,
     on_pop: Option<OnPop>,
@@ -214,6 +223,7 @@ impl std::fmt::Debug for UndoManagerInner {
             .field("merge_interval", &self.merge_interval_in_ms)
             .field("max_stack_size", &self.max_stack_size)
             .field("exclude_origin_prefixes", &self.exclude_origin_prefixes)
+            .field("exclude_containers", &self.exclude_containers)
             .finish()
     }
 }
@@ -414,6 +424,7 @@ impl UndoManagerInner {
             last_undo_time: 0,
             max_stack_size: usize::MAX,
             exclude_origin_prefixes: vec![],
+            exclude_containers: Default::default(),
             last_popped_selection: None,
             on_pop: None,
             on_push: None,
@@ -454,6 +465,27 @@ impl UndoManagerInner {
     }
 }
 
+/// Whether the container or one of its ancestors is excluded from the undo
+fn is_container_excluded(
+    arena: &SharedArena,
+    excluded: &FxHashSet<ContainerID>,
+    id: &ContainerID,
+) -> bool {
+    if excluded.is_empty() {
+        return false;
+    }
+
+    let mut idx = arena.id_to_idx(id);
+    while let Some(i) = idx {
+        if excluded.contains(&arena.idx_to_id(i).unwrap()) {
+            return true;
+        }
+        idx = arena.get_parent(i);
+    }
+
+    excluded.contains(id)
+}
+
 fn get_counter_end(doc: &LoroDoc, peer: PeerID) -> Counter {
     doc.oplog()
         .lock()
@@ -477,6 +509,7 @@ impl UndoManager {
         let inner_clone2 = inner.clone();
         let remap_containers = Arc::new(Mutex::new(FxHashMap::default()));
         let remap_containers_clone = remap_containers.clone();
+        let arena = doc.arena().clone();
         let undo_sub = doc.subscribe_root(Arc::new(move |event| match event.event_meta.by {
             EventTriggerKind::Local => {
                 // TODO: PERF undo can be significantly faster if we can get
@@ -497,10 +530,14 @@ impl UndoManager {
                         .exclude_origin_prefixes
                         .iter()
                         .any(|x| event.event_meta.origin.starts_with(&**x))
+                        || (!event.events.is_empty()
+                            && event.events.iter().all(|e| {
+                                is_container_excluded(&arena, &inner.exclude_containers, &e.id)
+                            }))
                     {
-                        // If the event is from the excluded origin, we don't record it
-                        // in the undo stack. But we need to record its effect like it's
-                        // a remote event.
+                        // If the event is from the excluded origin or only changes the
+                        // excluded containers, we don't record it in the undo stack. But we
+                        // need to record its effect like it's a remote event.
                         inner.undo_stack.compose_remote_event(event.events);
                         inner.redo_stack.compose_remote_event(event.events);
                         inner.next_counter = Some(id.counter + 1);
@@ -558,18 +595,24 @@ impl UndoManager {
         }
     }
 
+    /// The peer whose local changes are undone
     pub fn peer(&self) -> PeerID {
         self.peer.load(std::sync::atomic::Ordering::Relaxed)
     }
 
+    /// The local changes made within `interval` ms after the first change of an undo step are
+    /// merged into that step.
     pub fn set_merge_interval(&mut self, interval: i64) {
         self.inner.lock().unwrap().merge_interval_in_ms = interval;
     }
 
+    /// Drop the oldest undo steps beyond `size`
     pub fn set_max_undo_steps(&mut self, size: usize) {
         self.inner.lock().unwrap().max_stack_size = size;
     }
 
+    /// The local changes whose origin starts with `prefix` are not recorded. They are treated
+    /// like remote changes.
     pub fn add_exclude_origin_prefix(&mut self, prefix: &str) {
         self.inner
             .lock()
@@ -578,6 +621,14 @@ impl UndoManager {
             .push(prefix.into());
     }
 
+    /// The changes of the container `id` and its descendants are not undone or redone, e.g.
+    /// the view settings of an editor. A commit that only changes the excluded containers
+    /// is treated like a remote change.
+    pub fn add_exclude_container(&mut self, id: ContainerID) {
+        self.inner.lock().unwrap().exclude_containers.insert(id);
+    }
+
+    /// End the current undo step, so the following changes are not merged into it.
     pub fn record_new_checkpoint(&mut self) -> LoroResult<()> {
         self.doc.commit_then_renew();
         let counter = get_counter_end(&self.doc, self.peer());
@@ -665,6 +716,8 @@ impl UndoManager {
         // rather than using the current selection directly.
         self.record_new_checkpoint()?;
         let end_counter = get_counter_end(doc, self.peer());
+        let arena = doc.arena().clone();
+        let exclude_containers = self.inner.lock().unwrap().exclude_containers.clone();
         let mut top = {
             let mut inner = self.inner.lock().unwrap();
             inner.processing_undo = true;
@@ -685,6 +738,7 @@ impl UndoManager {
                     },
                     &mut self.container_remap.lock().unwrap(),
                     Some(&remote_change_clone),
+                    &|id| is_container_excluded(&arena, &exclude_containers, id),
                     &mut |diff| {
                         info_span!("transform remote diff").in_scope(|| {
                             let mut inner = inner.lock().unwrap();
@@ -1,20 +1,27 @@
+mod compact;
+mod delta;
+mod encoded;
 mod frontiers;
+pub use compact::CompactVersionVector;
+pub use encoded::EncodedVersionVectorIter;
 pub use frontiers::Frontiers;
 
 use crate::{
     change::Lamport,
+    dag::Dag,
     id::{Counter, ID},
     oplog::AppDag,
     span::{CounterSpan, IdSpan},
     LoroError, PeerID,
 };
 use fxhash::FxHashMap;
-use loro_common::{HasCounter, HasCounterSpan, HasIdSpan, HasLamportSpan, IdFull, IdSpanVector};
+use loro_common::{HasCounter, HasCounterSpan, IdFull, IdSpanVector};
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 use std::{
     cmp::Ordering,
-    ops::{ControlFlow, Deref, DerefMut},
+    collections::BinaryHeap,
+    ops::{Deref, DerefMut},
 };
 
 /// [VersionVector](https://en.wikipedia.org/wiki/Version_vector)
@@ -198,8 +205,13 @@ impl ImVersionVector {
         self.0.contains_key(k)
     }
 
+    /// Encode the entries sorted by the peers, so that the equal version vectors have the
+    /// same bytes
     pub fn encode(&self) -> Vec<u8> {
-        postcard::to_allocvec(self).unwrap()
+        let mut entries: Vec<(PeerID, Counter)> = self.0.iter().map(|(&p, &c)| (p, c)).collect();
+        entries.sort_unstable();
+        // A sequence of pairs has the same encoding as a map
+        postcard::to_allocvec(&entries).unwrap()
     }
 
     pub fn decode(bytes: &[u8]) -> Result<Self, LoroError> {
@@ -838,8 +850,13 @@ impl VersionVector {
     }
 
     #[inline(always)]
+    /// Encode the entries sorted by the peers, so that the equal version vectors have the
+    /// same bytes
     pub fn encode(&self) -> Vec<u8> {
-        postcard::to_allocvec(self).unwrap()
+        let mut entries: Vec<(PeerID, Counter)> = self.0.iter().map(|(&p, &c)| (p, c)).collect();
+        entries.sort_unstable();
+        // A sequence of pairs has the same encoding as a map
+        postcard::to_allocvec(&entries).unwrap()
     }
 
     #[inline(always)]
@@ -867,6 +884,10 @@ impl VersionVector {
 }
 
 /// Use minimal set of ids to represent the frontiers
+///
+/// An id is removed if it's an ancestor of another id. The ancestors of all the ids are
+/// traveled together once, from the greatest lamport to the smallest, and the travel stops
+/// below the smallest lamport of the ids, so it scales to the frontiers of wide merges.
 #[tracing::instrument(skip(dag))]
 pub fn shrink_frontiers(last_ids: &Frontiers, dag: &AppDag) -> Result<Frontiers, ID> {
     // it only keep the ids of ops that are concurrent to each other
@@ -875,52 +896,61 @@ pub fn shrink_frontiers(last_ids: &Frontiers, dag: &AppDag) -> Result<Frontiers,
         return Ok(last_ids.clone());
     }
 
-    let mut last_ids = {
-        let ids = filter_duplicated_peer_id(last_ids);
-        if last_ids.len() == 1 {
-            let mut frontiers = Frontiers::default();
-            frontiers.push(last_ids.as_single().unwrap());
-            return Ok(frontiers);
-        }
+    let ids = filter_duplicated_peer_id(last_ids);
+    if ids.len() == 1 {
+        return Ok(Frontiers::from_id(ids[0]));
+    }
 
-        let mut last_ids = Vec::with_capacity(ids.len());
-        for id in ids {
-            let Some(lamport) = dag.get_lamport(&id) else {
-                return Err(id);
-            };
-            last_ids.push(IdFull::new(id.peer, id.counter, lamport))
+    let mut last_ids = Vec::with_capacity(ids.len());
+    for id in ids {
+        let Some(lamport) = dag.get_lamport(&id) else {
+            return Err(id);
+        };
+        last_ids.push(IdFull::new(id.peer, id.counter, lamport))
+    }
+
+    let min_lamport = last_ids.iter().map(|x| x.lamport).min().unwrap();
+    // The ops are visited in the descending order of lamport, so the visited ops of a peer
+    // always have decreasing counters. For each peer, it records the greatest visited
+    // counter, and the smallest counter whose ancestors have been pushed.
+    let mut visited: FxHashMap<PeerID, (Counter, Counter)> = FxHashMap::default();
+    let mut pending: BinaryHeap<(Lamport, PeerID, Counter)> = BinaryHeap::new();
+    let push_deps = |pending: &mut BinaryHeap<_>, id: ID| -> Counter {
+        let node = dag.get(id).unwrap();
+        for dep in node.deps.iter() {
+            // The deps before the shallow root cannot reach any of the ids
+            if let Some(lamport) = dag.get_lamport(&dep) {
+                pending.push((lamport, dep.peer, dep.counter));
+            }
         }
-
-        last_ids
+        node.cnt
     };
+    for id in last_ids.iter() {
+        push_deps(&mut pending, id.id());
+    }
 
-    let mut frontiers = Vec::new();
-    // Iterate from the greatest lamport to the smallest
-    last_ids.sort_by_key(|x| x.lamport);
-    for id in last_ids.iter().rev() {
-        let mut should_insert = true;
-        let mut len = 0;
-        // travel backward because they have more similar lamport
-        for f_id in frontiers.iter().rev() {
-            dag.travel_ancestors(*f_id, &mut |x| {
-                len += 1;
-                if x.contains_id(id.id()) {
-                    should_insert = false;
-                    ControlFlow::Break(())
-                } else if x.lamport_last() < id.lamport {
-                    // Already travel to a node with smaller lamport, no need to continue, we are sure two ops are concurrent now
-                    ControlFlow::Break(())
-                } else {
-                    ControlFlow::Continue(())
-                }
-            });
+    while let Some((lamport, peer, counter)) = pending.pop() {
+        if lamport < min_lamport {
+            break;
         }
 
-        if should_insert {
-            frontiers.push(id.id());
+        match visited.get_mut(&peer) {
+            Some((_, start)) if *start <= counter => {}
+            Some((_, start)) => *start = push_deps(&mut pending, ID::new(peer, counter)),
+            None => {
+                let start = push_deps(&mut pending, ID::new(peer, counter));
+                visited.insert(peer, (counter, start));
+            }
         }
     }
 
+    // Iterate from the greatest lamport to the smallest
+    last_ids.sort_by_key(|x| std::cmp::Reverse(x.lamport));
+    let frontiers: Vec<ID> = last_ids
+        .iter()
+        .filter(|id| !matches!(visited.get(&id.peer), Some((max, _)) if *max >= id.counter))
+        .map(|id| id.id())
+        .collect();
     Ok(frontiers.into())
 }
 
@@ -1098,4 +1128,45 @@ mod tests {
         let decoded_vv = VersionVector::from_im_vv(&decoded_im_vv);
         assert_eq!(vv, decoded_vv);
     }
+
+    #[test]
+    fn shrink_wide_frontiers() {
+        let doc = crate::LoroDoc::new_auto_commit();
+        doc.set_peer_id(0).unwrap();
+        doc.get_text("text").insert(0, "base").unwrap();
+        doc.commit_then_renew();
+        let base = doc.oplog_frontiers().as_single().unwrap();
+        let mut heads = Vec::new();
+        for peer in 1..=200 {
+            let fork = doc.fork();
+            fork.set_peer_id(peer).unwrap();
+            fork.get_text("text").insert(0, "x").unwrap();
+            fork.commit_then_renew();
+            heads.push(fork.oplog_frontiers().as_single().unwrap());
+            doc.import(&fork.export_from(&doc.oplog_vv())).unwrap();
+        }
+
+        let oplog = doc.oplog().lock().unwrap();
+        let mut ids = heads.clone();
+        ids.push(base);
+        let shrunk = shrink_frontiers(&ids.into(), &oplog.dag).unwrap();
+        assert_eq!(shrunk.len(), 200);
+        assert!(heads.iter().all(|id| shrunk.contains(id)));
+        assert_eq!(&shrunk, oplog.frontiers());
+        drop(oplog);
+
+        // A change after the merge dominates all the heads
+        doc.get_text("text").insert(0, "y").unwrap();
+        doc.commit_then_renew();
+        let last = doc.oplog_frontiers().as_single().unwrap();
+        let oplog = doc.oplog().lock().unwrap();
+        let mut ids = heads;
+        ids.push(last);
+        let shrunk = shrink_frontiers(&ids.into(), &oplog.dag).unwrap();
+        assert_eq!(shrunk, Frontiers::from_id(last));
+        assert_eq!(
+            shrink_frontiers(&vec![ID::new(999, 0), last].into(), &oplog.dag),
+            Err(ID::new(999, 0))
+        );
+    }
 }
@@ -0,0 +1,198 @@
+//! A compact form of [`VersionVector`] for the docs that were edited by lots of peers.
+//!
+//! Long-lived docs collect an entry for every peer that ever edited them, and most of those
+//! peers are ephemeral ones with a few ops each. [`CompactVersionVector`] groups the peers by
+//! their counters, so the peers that share a counter are stored as one run, and the encoding
+//! stores the sorted peers of each run as deltas.
+//!
+//! The change store doesn't use it: the snapshots keep storing the version vectors in the
+//! form of [`VersionVector::encode`], because the older versions of Loro only read that form,
+//! and writing both forms would make every snapshot larger. Storing the compact form needs a
+//! new snapshot format version. It's meant for the applications that keep or send the
+//! version vectors of such docs themselves.
+use loro_common::{Counter, LoroError, PeerID, ID};
+
+use super::VersionVector;
+
+/// A [`VersionVector`] stored as the runs of peers that have the same counter.
+///
+/// It's smaller than [`VersionVector`] in memory and when encoded, but the lookups have to
+/// search every run. Convert it to a [`VersionVector`] to compare or to merge it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompactVersionVector {
+    /// Sorted by the counter, and the peers of each run are sorted
+    runs: Vec<(Counter, Vec<PeerID>)>,
+}
+
+impl CompactVersionVector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_vv(vv: &VersionVector) -> Self {
+        let mut entries: Vec<(Counter, PeerID)> = vv.iter().map(|(p, c)| (*c, *p)).collect();
+        entries.sort_unstable();
+        let mut runs: Vec<(Counter, Vec<PeerID>)> = Vec::new();
+        for (counter, peer) in entries {
+            match runs.last_mut() {
+                Some((c, peers)) if *c == counter => peers.push(peer),
+                _ => runs.push((counter, vec![peer])),
+            }
+        }
+
+        Self { runs }
+    }
+
+    pub fn to_vv(&self) -> VersionVector {
+        self.iter().collect()
+    }
+
+    /// The number of peers
+    pub fn len(&self) -> usize {
+        self.runs.iter().map(|(_, peers)| peers.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    /// The number of runs of peers with the same counter
+    pub fn run_count(&self) -> usize {
+        self.runs.len()
+    }
+
+    pub fn get(&self, peer: PeerID) -> Option<Counter> {
+        self.runs
+            .iter()
+            .find(|(_, peers)| peers.binary_search(&peer).is_ok())
+            .map(|(counter, _)| *counter)
+    }
+
+    pub fn includes_id(&self, id: ID) -> bool {
+        self.get(id.peer).is_some_and(|end| end > id.counter)
+    }
+
+    /// Iterate over the entries, ordered by the counter and then by the peer
+    pub fn iter(&self) -> impl Iterator<Item = (PeerID, Counter)> + '_ {
+        self.runs
+            .iter()
+            .flat_map(|(counter, peers)| peers.iter().map(move |peer| (*peer, *counter)))
+    }
+
+    /// Encode the runs. The counters and the peers of each run are delta encoded.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut ans = Vec::new();
+        leb128::write::unsigned(&mut ans, self.runs.len() as u64).unwrap();
+        let mut last_counter = 0;
+        for (counter, peers) in self.runs.iter() {
+            leb128::write::unsigned(&mut ans, (*counter - last_counter) as u64).unwrap();
+            last_counter = *counter;
+            leb128::write::unsigned(&mut ans, peers.len() as u64).unwrap();
+            let mut last_peer = 0;
+            for peer in peers {
+                leb128::write::unsigned(&mut ans, peer - last_peer).unwrap();
+                last_peer = *peer;
+            }
+        }
+
+        ans
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, LoroError> {
+        let mut reader = bytes;
+        let mut read =
+            || leb128::read::unsigned(&mut reader).map_err(|_| LoroError::DecodeVersionVectorError);
+        let run_count = read()? as usize;
+        let mut runs = Vec::with_capacity(run_count.min(bytes.len()));
+        let mut counter: Counter = 0;
+        for i in 0..run_count {
+            let delta =
+                Counter::try_from(read()?).map_err(|_| LoroError::DecodeVersionVectorError)?;
+            if i > 0 && delta == 0 {
+                return Err(LoroError::DecodeVersionVectorError);
+            }
+
+            counter = counter
+                .checked_add(delta)
+                .ok_or(LoroError::DecodeVersionVectorError)?;
+            let peer_count = read()? as usize;
+            let mut peers = Vec::with_capacity(peer_count.min(bytes.len()));
+            let mut peer: PeerID = 0;
+            for j in 0..peer_count {
+                let delta = read()?;
+                if j > 0 && delta == 0 {
+                    return Err(LoroError::DecodeVersionVectorError);
+                }
+
+                peer = peer
+                    .checked_add(delta)
+                    .ok_or(LoroError::DecodeVersionVectorError)?;
+                peers.push(peer);
+            }
+
+            runs.push((counter, peers));
+        }
+
+        if !reader.is_empty() {
+            return Err(LoroError::DecodeVersionVectorError);
+        }
+
+        Ok(Self { runs })
+    }
+}
+
+impl From<&VersionVector> for CompactVersionVector {
+    fn from(vv: &VersionVector) -> Self {
+        Self::from_vv(vv)
+    }
+}
+
+impl From<&CompactVersionVector> for VersionVector {
+    fn from(vv: &CompactVersionVector) -> Self {
+        vv.to_vv()
+    }
+}
+
+impl VersionVector {
+    /// Encode the version vector in the form of [`CompactVersionVector::encode`]
+    pub fn encode_compact(&self) -> Vec<u8> {
+        CompactVersionVector::from_vv(self).encode()
+    }
+
+    pub fn decode_compact(bytes: &[u8]) -> Result<Self, LoroError> {
+        Ok(CompactVersionVector::decode(bytes)?.to_vv())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vv;
+
+    #[test]
+    fn compact_vv_groups_peers_by_counter() {
+        let vv: VersionVector = vv!(7 => 1, 3 => 1, 100 => 5, u64::MAX => 1, 2 => 0);
+        let compact = CompactVersionVector::from_vv(&vv);
+        assert_eq!(compact.len(), 5);
+        assert_eq!(compact.run_count(), 3);
+        assert_eq!(compact.get(3), Some(1));
+        assert_eq!(compact.get(4), None);
+        assert!(compact.includes_id(ID::new(100, 4)));
+        assert!(!compact.includes_id(ID::new(2, 0)));
+
+        let bytes = compact.encode();
+        assert_eq!(CompactVersionVector::decode(&bytes).unwrap(), compact);
+        assert_eq!(VersionVector::decode_compact(&bytes).unwrap(), vv);
+        assert!(CompactVersionVector::decode(&bytes[..bytes.len() - 1]).is_err());
+
+        // Lots of random peers with a few ops each
+        let many: VersionVector = (0..1000u64)
+            .map(|p| (p.wrapping_mul(0x9E3779B97F4A7C15), 1 + (p % 3) as Counter))
+            .collect();
+        assert!(many.encode_compact().len() < many.encode().len() * 4 / 5);
+        assert_eq!(
+            VersionVector::decode_compact(&many.encode_compact()).unwrap(),
+            many
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! Encoding a [`VersionVector`] relative to a base version.
+//!
+//! The sync messages and the presence messages send version vectors to the peers that
+//! already know an earlier version. For the docs edited by hundreds of peers,
+//! [`VersionVector::encode_delta`] keeps those messages small by encoding only the peers whose
+//! counters differ from the base.
+use loro_common::{Counter, LoroError, PeerID};
+
+use super::VersionVector;
+
+impl VersionVector {
+    /// Encode the entries that differ from `base`, which has to be passed to
+    /// [`VersionVector::decode_delta`] too.
+    ///
+    /// The changed peers are sorted and delta encoded. The peers that are in `base` but not in
+    /// `self` are encoded as removed.
+    pub fn encode_delta(&self, base: &VersionVector) -> Vec<u8> {
+        // The counter is stored plus one, so that zero marks a removed peer
+        let mut changed: Vec<(PeerID, u64)> = self
+            .iter()
+            .filter(|(peer, counter)| base.get(*peer) != Some(*counter))
+            .map(|(peer, counter)| (*peer, *counter as u64 + 1))
+            .chain(
+                base.keys()
+                    .filter(|peer| !self.contains_key(peer))
+                    .map(|peer| (*peer, 0)),
+            )
+            .collect();
+        changed.sort_unstable();
+
+        let mut ans = Vec::new();
+        leb128::write::unsigned(&mut ans, changed.len() as u64).unwrap();
+        let mut last_peer = 0;
+        for (peer, counter) in changed {
+            leb128::write::unsigned(&mut ans, peer - last_peer).unwrap();
+            last_peer = peer;
+            leb128::write::unsigned(&mut ans, counter).unwrap();
+        }
+
+        ans
+    }
+
+    /// Decode the bytes of [`VersionVector::encode_delta`] encoded against `base`
+    pub fn decode_delta(base: &VersionVector, bytes: &[u8]) -> Result<Self, LoroError> {
+        let mut reader = bytes;
+        let mut read =
+            || leb128::read::unsigned(&mut reader).map_err(|_| LoroError::DecodeVersionVectorError);
+        let len = read()? as usize;
+        let mut ans = base.clone();
+        let mut peer: PeerID = 0;
+        for i in 0..len {
+            let delta = read()?;
+            if i > 0 && delta == 0 {
+                return Err(LoroError::DecodeVersionVectorError);
+            }
+
+            peer = peer
+                .checked_add(delta)
+                .ok_or(LoroError::DecodeVersionVectorError)?;
+            match read()? {
+                0 => {
+                    ans.remove(&peer);
+                }
+                counter => {
+                    let counter = Counter::try_from(counter - 1)
+                        .map_err(|_| LoroError::DecodeVersionVectorError)?;
+                    ans.insert(peer, counter);
+                }
+            }
+        }
+
+        if !reader.is_empty() {
+            return Err(LoroError::DecodeVersionVectorError);
+        }
+
+        Ok(ans)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vv;
+
+    #[test]
+    fn delta_encoding() {
+        let base: VersionVector = (0..500u64).map(|p| (p * 7919, 10)).collect();
+        let mut vv = base.clone();
+        vv.insert(7919, 11);
+        vv.insert(u64::MAX, 0);
+        vv.remove(&0);
+        let bytes = vv.encode_delta(&base);
+        assert!(bytes.len() < 32);
+        assert_eq!(VersionVector::decode_delta(&base, &bytes).unwrap(), vv);
+
+        assert_eq!(base.encode_delta(&base), vec![0]);
+        let empty = VersionVector::new();
+        assert_eq!(
+            VersionVector::decode_delta(&empty, &vv.encode_delta(&empty)).unwrap(),
+            vv
+        );
+        assert_eq!(
+            VersionVector::decode_delta(&vv, &empty.encode_delta(&vv)).unwrap(),
+            empty
+        );
+
+        let bytes = vv!(1 => 1, 2 => 2).encode_delta(&empty);
+        assert!(VersionVector::decode_delta(&empty, &bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+//! Helpers for the encoded [`VersionVector`]s.
+//!
+//! They read and merge the bytes of [`VersionVector::encode`] without decoding them into
+//! hash maps, for the servers that keep lots of stored version vectors, e.g. the sync
+//! cursors of the clients in a KV store.
+use itertools::Itertools;
+use loro_common::{Counter, LoroError, PeerID};
+
+use super::VersionVector;
+
+/// An iterator over the entries of an encoded [`VersionVector`].
+///
+/// It's created by [`VersionVector::iter_encoded`]. The entries are decoded lazily, and it
+/// yields an error and stops if the bytes are malformed.
+#[derive(Debug, Clone)]
+pub struct EncodedVersionVectorIter<'a> {
+    bytes: &'a [u8],
+    remaining: usize,
+}
+
+impl Iterator for EncodedVersionVectorIter<'_> {
+    type Item = Result<(PeerID, Counter), LoroError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        match postcard::take_from_bytes::<(PeerID, Counter)>(self.bytes) {
+            Ok((entry, rest)) => {
+                self.bytes = rest;
+                self.remaining -= 1;
+                Some(Ok(entry))
+            }
+            Err(_) => {
+                self.remaining = 0;
+                Some(Err(LoroError::DecodeVersionVectorError))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+impl VersionVector {
+    /// Iterate over the entries of an encoded version vector without decoding it.
+    pub fn iter_encoded(bytes: &[u8]) -> Result<EncodedVersionVectorIter<'_>, LoroError> {
+        let (remaining, bytes) = postcard::take_from_bytes::<usize>(bytes)
+            .map_err(|_| LoroError::DecodeVersionVectorError)?;
+        Ok(EncodedVersionVectorIter { bytes, remaining })
+    }
+
+    /// Get the counter of the peer from an encoded version vector without decoding it.
+    pub fn get_encoded(bytes: &[u8], peer: PeerID) -> Result<Option<Counter>, LoroError> {
+        for entry in Self::iter_encoded(bytes)? {
+            let (p, counter) = entry?;
+            if p == peer {
+                return Ok(Some(counter));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Merge the encoded version vector `b` into the encoded version vector `a`, so that
+    /// `a` includes the max counter of each peer.
+    ///
+    /// `a` is left untouched if `b` doesn't include anything new. Returns whether `a` is
+    /// changed, so the caller can skip writing it back to the storage.
+    pub fn merge_encoded(a: &mut Vec<u8>, b: &[u8]) -> Result<bool, LoroError> {
+        let mut entries: Vec<(PeerID, Counter)> = Self::iter_encoded(a)?.try_collect()?;
+        entries.sort_unstable_by_key(|(peer, _)| *peer);
+        let mut new_entries = Vec::new();
+        for entry in Self::iter_encoded(b)? {
+            let (peer, counter) = entry?;
+            match entries.binary_search_by_key(&peer, |(p, _)| *p) {
+                Ok(i) => {
+                    if entries[i].1 < counter {
+                        entries[i].1 = counter;
+                        new_entries.push((peer, counter));
+                    }
+                }
+                Err(_) => new_entries.push((peer, counter)),
+            }
+        }
+
+        if new_entries.is_empty() {
+            return Ok(false);
+        }
+
+        for (peer, counter) in new_entries {
+            if let Err(i) = entries.binary_search_by_key(&peer, |(p, _)| *p) {
+                entries.insert(i, (peer, counter));
+            }
+        }
+
+        // A sequence of pairs has the same encoding as a map
+        *a = postcard::to_allocvec(&entries).unwrap();
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vv;
+
+    #[test]
+    fn merge_encoded_vv() {
+        let a: VersionVector = vv!(1 => 10, 2 => 5);
+        let b: VersionVector = vv!(2 => 8, 3 => 1);
+        let mut bytes = a.encode();
+        let pairs: Vec<_> = VersionVector::iter_encoded(&bytes)
+            .unwrap()
+            .try_collect()
+            .unwrap();
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(VersionVector::get_encoded(&bytes, 1).unwrap(), Some(10));
+        assert_eq!(VersionVector::get_encoded(&bytes, 3).unwrap(), None);
+
+        assert!(VersionVector::merge_encoded(&mut bytes, &b.encode()).unwrap());
+        let mut expected = a.clone();
+        expected.merge(&b);
+        assert_eq!(VersionVector::decode(&bytes).unwrap(), expected);
+        assert!(!VersionVector::merge_encoded(&mut bytes, &a.encode()).unwrap());
+        assert!(VersionVector::merge_encoded(&mut bytes, &[5, 1]).is_err());
+    }
+}
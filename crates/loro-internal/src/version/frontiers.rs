@@ -1,4 +1,8 @@
 use super::*;
+use crate::{
+    dag::{Dag, DagUtils},
+    oplog::FrontiersNotIncluded,
+};
 use either::Either;
 
 /// Frontiers representation.
@@ -15,7 +19,7 @@ pub enum Frontiers {
     Map(InternalMap),
 }
 
-use std::{fmt, sync::Arc};
+use std::{collections::BTreeMap, fmt, sync::Arc};
 
 impl fmt::Debug for Frontiers {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -45,57 +49,137 @@ impl fmt::Debug for FrontiersDebugHelper<'_> {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct InternalMap(Arc<FxHashMap<PeerID, Counter>>);
+/// Above this many peers the IDs are kept in a [`BTreeMap`] instead of a sorted vec,
+/// so that updating the frontiers of a doc with many concurrent peers stays O(log n).
+const SORTED_VEC_MAX_LEN: usize = 16;
+
+#[derive(Debug, Clone)]
+pub struct InternalMap(Arc<InternalMapRepr>);
+
+#[derive(Debug, Clone)]
+enum InternalMapRepr {
+    /// Sorted by peer, with at most one ID per peer
+    Vec(SmallVec<[ID; 4]>),
+    BTree(BTreeMap<PeerID, Counter>),
+}
 
 impl InternalMap {
     fn new() -> Self {
-        Self(Arc::new(FxHashMap::default()))
+        Self(Arc::new(InternalMapRepr::Vec(SmallVec::new())))
     }
 
     fn len(&self) -> usize {
-        self.0.len()
+        match self.0.as_ref() {
+            InternalMapRepr::Vec(vec) => vec.len(),
+            InternalMapRepr::BTree(map) => map.len(),
+        }
     }
 
+    /// Iterate the IDs in the order of their peers
     fn iter(&self) -> impl Iterator<Item = ID> + '_ {
-        self.0
-            .iter()
-            .map(|(&peer, &counter)| ID::new(peer, counter))
+        match self.0.as_ref() {
+            InternalMapRepr::Vec(vec) => Either::Left(vec.iter().copied()),
+            InternalMapRepr::BTree(map) => {
+                Either::Right(map.iter().map(|(&peer, &counter)| ID::new(peer, counter)))
+            }
+        }
     }
 
     fn contains(&self, id: &ID) -> bool {
-        self.0
-            .get(&id.peer)
-            .is_some_and(|&counter| counter == id.counter)
+        match self.0.as_ref() {
+            InternalMapRepr::Vec(vec) => vec
+                .binary_search_by_key(&id.peer, |x| x.peer)
+                .is_ok_and(|i| vec[i].counter == id.counter),
+            InternalMapRepr::BTree(map) => map
+                .get(&id.peer)
+                .is_some_and(|&counter| counter == id.counter),
+        }
     }
 
     fn insert(&mut self, id: ID) {
-        Arc::make_mut(&mut self.0)
-            .entry(id.peer)
-            .and_modify(|e| *e = (*e).max(id.counter))
-            .or_insert(id.counter);
+        let repr = Arc::make_mut(&mut self.0);
+        match repr {
+            InternalMapRepr::Vec(vec) => match vec.binary_search_by_key(&id.peer, |x| x.peer) {
+                Ok(i) => vec[i].counter = vec[i].counter.max(id.counter),
+                Err(i) => {
+                    if vec.len() < SORTED_VEC_MAX_LEN {
+                        vec.insert(i, id);
+                    } else {
+                        let mut map: BTreeMap<PeerID, Counter> =
+                            vec.iter().map(|x| (x.peer, x.counter)).collect();
+                        map.insert(id.peer, id.counter);
+                        *repr = InternalMapRepr::BTree(map);
+                    }
+                }
+            },
+            InternalMapRepr::BTree(map) => {
+                map.entry(id.peer)
+                    .and_modify(|e| *e = (*e).max(id.counter))
+                    .or_insert(id.counter);
+            }
+        }
     }
 
     fn remove(&mut self, id: &ID) -> bool {
-        let map = Arc::make_mut(&mut self.0);
-        if let Some(counter) = map.get_mut(&id.peer) {
-            if *counter == id.counter {
+        if !self.contains(id) {
+            return false;
+        }
+
+        match Arc::make_mut(&mut self.0) {
+            InternalMapRepr::Vec(vec) => {
+                let i = vec.binary_search_by_key(&id.peer, |x| x.peer).unwrap();
+                vec.remove(i);
+            }
+            InternalMapRepr::BTree(map) => {
                 map.remove(&id.peer);
-                return true;
             }
         }
-        false
+
+        self.shrink_if_small();
+        true
     }
 
     fn retain<F>(&mut self, mut f: F)
     where
         F: FnMut(&ID) -> bool,
     {
-        let map = Arc::make_mut(&mut self.0);
-        map.retain(|&peer, &mut counter| f(&ID::new(peer, counter)));
+        match Arc::make_mut(&mut self.0) {
+            InternalMapRepr::Vec(vec) => vec.retain(|id| f(id)),
+            InternalMapRepr::BTree(map) => {
+                map.retain(|&peer, &mut counter| f(&ID::new(peer, counter)))
+            }
+        }
+
+        self.shrink_if_small();
+    }
+
+    /// Switch back to the sorted vec once the map is well below the threshold.
+    ///
+    /// It waits until half of the threshold so that a frontiers hovering around
+    /// the threshold won't be converted back and forth.
+    fn shrink_if_small(&mut self) {
+        if let InternalMapRepr::BTree(map) = self.0.as_ref() {
+            if map.len() <= SORTED_VEC_MAX_LEN / 2 {
+                let vec = map
+                    .iter()
+                    .map(|(&peer, &counter)| ID::new(peer, counter))
+                    .collect();
+                self.0 = Arc::new(InternalMapRepr::Vec(vec));
+            }
+        }
     }
 }
 
+impl PartialEq for InternalMap {
+    fn eq(&self, other: &Self) -> bool {
+        // Both representations iterate in the order of peers
+        Arc::ptr_eq(&self.0, &other.0)
+            || (self.len() == other.len() && self.iter().eq(other.iter()))
+    }
+}
+
+impl Eq for InternalMap {}
+
 impl Frontiers {
     pub fn len(&self) -> usize {
         match self {
@@ -246,7 +330,8 @@ impl Frontiers {
 
     #[inline]
     pub(crate) fn with_capacity(_cap: usize) -> Self {
-        // TODO
+        // The map is only created when the second peer is pushed, and it grows
+        // from a small inline vec, so there is nothing worth reserving here
         Self::None
     }
 
@@ -293,10 +378,7 @@ impl Frontiers {
                 }
                 Self::Map(internal_map) => {
                     let mut map = internal_map.clone();
-                    Arc::make_mut(&mut map.0)
-                        .entry(id.peer)
-                        .and_modify(|c| *c = (*c).max(id.counter))
-                        .or_insert(id.counter);
+                    map.insert(id);
                     *self = Self::Map(map);
                 }
             }
@@ -305,11 +387,8 @@ impl Frontiers {
         }
 
         let Self::Map(map) = self else { unreachable!() };
-        let map = Arc::make_mut(&mut map.0);
         for id in other.iter() {
-            map.entry(id.peer)
-                .and_modify(|c| *c = (*c).max(id.counter))
-                .or_insert(id.counter);
+            map.insert(id);
         }
     }
 
@@ -329,13 +408,69 @@ impl Frontiers {
             Self::None => {}
             Self::ID(_) => {}
             Self::Map(map) => {
-                if let Some((&peer, &counter)) = map.0.iter().next() {
-                    *self = Self::ID(ID::new(peer, counter));
+                if let Some(id) = map.iter().next() {
+                    *self = Self::ID(id);
                 }
             }
         }
     }
 }
+
+/// Set operations on the versions that the [Frontiers] represent.
+///
+/// They reason about the versions causally, so the results are always the minimal
+/// [Frontiers] of a valid version. All the ids must be included by the given [AppDag].
+impl Frontiers {
+    /// The version that includes the ops of both `self` and `other`.
+    pub fn union(&self, other: &Self, dag: &AppDag) -> Result<Self, FrontiersNotIncluded> {
+        if self.is_empty() {
+            return Ok(other.clone());
+        }
+
+        if other.is_empty() {
+            return Ok(self.clone());
+        }
+
+        let ids: Self = self.iter().chain(other.iter()).collect();
+        shrink_frontiers(&ids, dag).map_err(|_| FrontiersNotIncluded)
+    }
+
+    /// The version that only includes the ops included by both `self` and `other`.
+    ///
+    /// It's the same version as [Frontiers::common_ancestor], but it's computed from the
+    /// version vectors instead of walking the DAG.
+    pub fn intersection(&self, other: &Self, dag: &AppDag) -> Result<Self, FrontiersNotIncluded> {
+        let a = dag.frontiers_to_vv(self).ok_or(FrontiersNotIncluded)?;
+        let b = dag.frontiers_to_vv(other).ok_or(FrontiersNotIncluded)?;
+        Ok(dag.vv_to_frontiers(&a.intersection(&b)))
+    }
+
+    /// Compare the versions causally.
+    ///
+    /// Returns `None` if the versions are concurrent.
+    pub fn cmp_with(
+        &self,
+        other: &Self,
+        dag: &AppDag,
+    ) -> Result<Option<Ordering>, FrontiersNotIncluded> {
+        dag.cmp_frontiers(self, other)
+    }
+
+    /// Find the greatest common ancestor of the two versions by walking the DAG backward.
+    ///
+    /// It's faster than [Frontiers::intersection] when the branches forked recently.
+    pub fn common_ancestor(
+        &self,
+        other: &Self,
+        dag: &AppDag,
+    ) -> Result<Self, FrontiersNotIncluded> {
+        if self.iter().chain(other.iter()).any(|id| !dag.contains(id)) {
+            return Err(FrontiersNotIncluded);
+        }
+
+        Ok(dag.find_common_ancestor(self, other).0)
+    }
+}
 impl From<&[ID]> for Frontiers {
     fn from(ids: &[ID]) -> Self {
         match ids.len() {
@@ -540,6 +675,67 @@ mod tests {
         assert_eq!(frontiers, Frontiers::None);
     }
 
+    #[test]
+    fn test_frontiers_with_many_peers() {
+        let n = SORTED_VEC_MAX_LEN as PeerID * 4;
+        let mut frontiers = Frontiers::None;
+        for peer in (0..n).rev() {
+            frontiers.push(ID::new(peer, 0));
+        }
+        assert_eq!(frontiers.len(), n as usize);
+        assert!(matches!(
+            frontiers.as_map().unwrap().0.as_ref(),
+            InternalMapRepr::BTree(_)
+        ));
+        // Pushing the same peer only keeps the greater counter
+        frontiers.push(ID::new(3, 10));
+        frontiers.push(ID::new(3, 5));
+        assert!(frontiers.contains(&ID::new(3, 10)));
+        assert!(!frontiers.contains(&ID::new(3, 0)));
+        assert_eq!(frontiers.len(), n as usize);
+
+        // Equality doesn't depend on the representation or the insertion order
+        let same: Frontiers = frontiers.iter().collect();
+        assert_eq!(frontiers, same);
+        let ids: Vec<ID> = frontiers.iter().collect();
+        assert!(ids.windows(2).all(|w| w[0].peer < w[1].peer));
+
+        let mut shrunk = frontiers.clone();
+        shrunk.retain(|id| id.peer < 4);
+        assert_eq!(shrunk.len(), 4);
+        assert!(matches!(
+            shrunk.as_map().unwrap().0.as_ref(),
+            InternalMapRepr::Vec(_)
+        ));
+        assert_eq!(
+            shrunk,
+            Frontiers::from(vec![
+                ID::new(2, 0),
+                ID::new(0, 0),
+                ID::new(3, 10),
+                ID::new(1, 0)
+            ])
+        );
+        // The clone is not affected
+        assert_eq!(frontiers.len(), n as usize);
+
+        for peer in 0..n - 1 {
+            frontiers.remove(&ID::new(peer, if peer == 3 { 10 } else { 0 }));
+        }
+        assert_eq!(frontiers, Frontiers::ID(ID::new(n - 1, 0)));
+    }
+
+    #[test]
+    fn test_update_frontiers_on_new_change_with_many_peers() {
+        let n = SORTED_VEC_MAX_LEN as PeerID * 2;
+        let mut frontiers: Frontiers = (0..n).map(|peer| ID::new(peer, 0)).collect();
+        let deps: Frontiers = [ID::new(0, 0), ID::new(n - 1, 0)].into();
+        frontiers.update_frontiers_on_new_change(ID::new(0, 1), &deps);
+        assert_eq!(frontiers.len(), n as usize - 1);
+        assert!(frontiers.contains(&ID::new(0, 1)));
+        assert!(!frontiers.contains(&ID::new(n - 1, 0)));
+    }
+
     #[test]
     fn test_frontiers_encode_decode() {
         let mut frontiers = Frontiers::None;
@@ -144,6 +144,20 @@ export class Awareness<T extends Value = Value> {
         this.startTimerIfNotEmpty();
     }
 
+    /**
+     * The same as `apply`. It's named after y-protocols/awareness.
+     */
+    applyUpdate(bytes: Uint8Array, origin = "remote") {
+        this.apply(bytes, origin);
+    }
+
+    /**
+     * Encode the states of the given peers, or all the peers if `peers` is not given.
+     */
+    encodeUpdate(peers?: PeerID[]): Uint8Array {
+        return peers == null ? this.inner.encodeAll() : this.inner.encode(peers);
+    }
+
     setLocalState(state: T) {
         const wasEmpty = this.inner.getState(this.peer) == null;
         this.inner.setLocalState(state);
@@ -245,14 +259,29 @@ export class Awareness<T extends Value = Value> {
  * // Apply the encoded value
  * store2.apply(encoded);
  * ```
+ *
+ * Rapid local changes like cursor moves can be coalesced by passing a throttle interval in
+ * milliseconds. The local updates are then sent at most once per interval, and the last change
+ * is always sent when the interval ends.
+ *
+ * ```ts
+ * const store = new EphemeralStore(30_000, 50);
+ * store.subscribeLocalUpdates((data) => {
+ *     // At most one update per 50ms
+ * });
+ * ```
  */
 export class EphemeralStore<T extends Value = Value> {
     inner: EphemeralStoreWasm<T>;
     private timer: number | undefined;
+    private flushTimer: number | undefined;
     private timeout: number;
-    constructor(timeout: number = 30000) {
+    private throttle: number;
+    constructor(timeout: number = 30000, throttle: number = 0) {
         this.inner = new EphemeralStoreWasm(timeout);
         this.timeout = timeout;
+        this.throttle = throttle;
+        this.inner.setLocalUpdateThrottle(throttle);
     }
 
     apply(bytes: Uint8Array) {
@@ -260,11 +289,40 @@ export class EphemeralStore<T extends Value = Value> {
         this.startTimerIfNotEmpty();
     }
 
+    /**
+     * The same as `apply`. It's named after y-protocols/awareness.
+     */
+    applyUpdate(bytes: Uint8Array) {
+        this.apply(bytes);
+    }
+
     set(key: string, value: T) {
         this.inner.set(key, value);
+        this.scheduleFlushIfPending();
         this.startTimerIfNotEmpty();
     }
 
+    delete(key: string) {
+        this.inner.delete(key);
+        this.scheduleFlushIfPending();
+    }
+
+    /**
+     * The same as `set`. It's named after y-protocols/awareness.
+     */
+    setLocalState(key: string, value: T) {
+        this.set(key, value);
+    }
+
+    /**
+     * Send the local changes held back by the throttle now.
+     */
+    flush() {
+        clearTimeout(this.flushTimer);
+        this.flushTimer = undefined;
+        this.inner.flushLocalUpdates();
+    }
+
     get(key: string): T | undefined {
         return this.inner.get(key);
     }
@@ -281,12 +339,20 @@ export class EphemeralStore<T extends Value = Value> {
         return this.inner.encodeAll();
     }
 
+    /**
+     * Encode the states of the given keys, or all the keys if `keys` is not given.
+     */
+    encodeUpdate(keys?: string[]): Uint8Array {
+        return keys == null ? this.inner.encodeAll() : this.inner.encodeKeys(keys);
+    }
+
     keys(): string[] {
         return this.inner.keys();
     }
 
     destroy() {
         clearInterval(this.timer);
+        clearTimeout(this.flushTimer);
     }
 
     subscribe(listener: EphemeralListener) {
@@ -297,6 +363,17 @@ export class EphemeralStore<T extends Value = Value> {
         return this.inner.subscribeLocalUpdates(listener);
     }
 
+    private scheduleFlushIfPending() {
+        if (this.flushTimer != null || !this.inner.hasPendingLocalUpdates()) {
+            return;
+        }
+
+        this.flushTimer = setTimeout(() => {
+            this.flushTimer = undefined;
+            this.inner.flushLocalUpdates();
+        }, this.throttle) as unknown as number;
+    }
+
     private startTimerIfNotEmpty() {
         if (this.inner.isEmpty() || this.timer != null) {
             return;
@@ -312,6 +389,114 @@ export class EphemeralStore<T extends Value = Value> {
     }
 }
 
+const SYNC_PROTOCOL_VERSION = 1;
+
+/**
+ * The main-thread side of a `SyncDriver` that runs a doc in a worker.
+ *
+ * It calls the methods of the remote doc and its containers, and acknowledges the events
+ * it receives so that the driver keeps sending them.
+ *
+ * @example
+ * ```ts
+ * const channel = new MessageChannel();
+ * // In the worker
+ * const driver = new SyncDriver(doc, channel.port1);
+ * // In the main thread
+ * const client = new SyncClient(channel.port2);
+ * client.onEvent = (event) => console.log(event);
+ * await client.call(null, "getText", "text");
+ * ```
+ */
+export class SyncClient {
+    /** Called with every event of the remote doc in the JSON form */
+    onEvent?: (event: unknown) => void;
+    /** Called when the driver dropped events. The state should be read again. */
+    onLagged?: () => void;
+    /** Called with the updates of the local edits in the worker, if they are forwarded */
+    onLocalUpdate?: (bytes: Uint8Array) => void;
+    private port: MessagePort;
+    private nextId = 0;
+    private pending = new Map<number, { resolve: (v: any) => void, reject: (e: Error) => void }>();
+    private readyPromise: Promise<PeerID>;
+
+    constructor(port: MessagePort) {
+        this.port = port;
+        let onReady: (peer: PeerID) => void;
+        this.readyPromise = new Promise((resolve) => {
+            onReady = resolve;
+        });
+        port.onmessage = (e: MessageEvent) => {
+            const msg = e.data;
+            if (msg == null || msg.v !== SYNC_PROTOCOL_VERSION) {
+                return;
+            }
+
+            switch (msg.type) {
+                case "ready":
+                    onReady(msg.peer);
+                    break;
+                case "event":
+                    this.port.postMessage({ v: SYNC_PROTOCOL_VERSION, type: "ack", seq: msg.seq });
+                    this.onEvent?.(msg.event);
+                    break;
+                case "lagged":
+                    this.onLagged?.();
+                    break;
+                case "localUpdate":
+                    this.onLocalUpdate?.(msg.bytes);
+                    break;
+                case "result":
+                case "error": {
+                    const pending = this.pending.get(msg.id);
+                    if (pending == null) {
+                        return;
+                    }
+
+                    this.pending.delete(msg.id);
+                    if (msg.type === "result") {
+                        pending.resolve(msg.value);
+                    } else {
+                        pending.reject(new Error(msg.message));
+                    }
+                    break;
+                }
+            }
+        };
+    }
+
+    /**
+     * Resolves with the peer id of the remote doc once the driver is attached
+     */
+    ready(): Promise<PeerID> {
+        return this.readyPromise;
+    }
+
+    /**
+     * Call `method` on the container `target`, or on the doc if `target` is `null`.
+     *
+     * A returned container is resolved as `{ containerId }`.
+     */
+    call<T = unknown>(target: ContainerID | null, method: string, ...args: unknown[]): Promise<T> {
+        const id = this.nextId++;
+        return new Promise((resolve, reject) => {
+            this.pending.set(id, { resolve, reject });
+            this.port.postMessage({ v: SYNC_PROTOCOL_VERSION, type: "call", id, target, method, args });
+        });
+    }
+
+    /**
+     * Stop listening to the port. The pending calls are rejected.
+     */
+    close() {
+        this.port.onmessage = null;
+        for (const { reject } of this.pending.values()) {
+            reject(new Error("SyncClient is closed"));
+        }
+        this.pending.clear();
+    }
+}
+
 LoroDoc.prototype.toJsonWithReplacer = function (replacer: (key: string | number, value: Value | Container) => Value | Container | undefined) {
     const processed = new Set<string>();
     const doc = this;
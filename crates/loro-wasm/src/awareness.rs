@@ -291,6 +291,29 @@ impl EphemeralStoreWasm {
         self.inner.encode_all()
     }
 
+    /// Encodes the states of the given keys into one update.
+    pub fn encodeKeys(&self, keys: Vec<String>) -> Vec<u8> {
+        self.inner.encode_keys(keys.iter().map(|x| x.as_str()))
+    }
+
+    /// Sets the min interval between two local updates, in milliseconds.
+    ///
+    /// Rapid local changes within the interval are coalesced into one local update.
+    /// Call `flushLocalUpdates` to send the pending changes.
+    pub fn setLocalUpdateThrottle(&mut self, throttle: f64) {
+        self.inner.set_local_update_throttle(throttle as i64);
+    }
+
+    /// Sends the pending local changes to the local update subscribers as one update.
+    pub fn flushLocalUpdates(&mut self) {
+        self.inner.flush_local_updates();
+    }
+
+    /// Whether there are local changes that haven't been sent to the local update subscribers.
+    pub fn hasPendingLocalUpdates(&self) -> bool {
+        self.inner.has_pending_local_updates()
+    }
+
     pub fn apply(&mut self, data: &[u8]) {
         self.inner.apply(data);
     }
@@ -1,8 +1,11 @@
 use js_sys::{Array, Map, Object, Reflect, Uint8Array};
-use loro_common::{ContainerID, IdLp, LoroListValue, LoroMapValue, LoroValue};
+use loro_common::{
+    ContainerID, IdLp, LoroListValue, LoroMapValue, LoroValue, LORO_CONTAINER_REF_TAG,
+    LORO_DECIMAL_TAG, LORO_ESCAPED_MAP_TAG, LORO_TIMESTAMP_TAG,
+};
 use loro_delta::{array_vec, DeltaRopeBuilder};
 use loro_internal::delta::{ResolvedMapDelta, ResolvedMapValue};
-use loro_internal::encoding::{ImportBlobMetadata, ImportStatus};
+use loro_internal::encoding::{ImportBlobMetadata, ImportStats, ImportStatus, SnapshotImportMode};
 use loro_internal::event::{Diff, ListDeltaMeta, ListDiff, TextDiff, TextMeta};
 use loro_internal::handler::{Handler, ValueOrHandler};
 use loro_internal::version::VersionRange;
@@ -349,15 +352,44 @@ pub fn convert(value: LoroValue) -> JsValue {
         }
         LoroValue::Map(m) => {
             let m = m.unwrap();
+            let escaped = LoroValue::is_tagged_map(&m);
             let map = Object::new();
             for (k, v) in m.into_iter() {
                 let str: &str = &k;
                 js_sys::Reflect::set(&map, &JsValue::from_str(str), &convert(v)).unwrap();
             }
 
+            if escaped {
+                let wrapper = Object::new();
+                js_sys::Reflect::set(&wrapper, &JsValue::from_str(LORO_ESCAPED_MAP_TAG), &map)
+                    .unwrap();
+                return wrapper.into_js_result().unwrap();
+            }
+
             map.into_js_result().unwrap()
         }
         LoroValue::Container(container_id) => JsValue::from(&container_id),
+        LoroValue::ContainerRef(container_id) => {
+            let map = Object::new();
+            js_sys::Reflect::set(
+                &map,
+                &JsValue::from_str(LORO_CONTAINER_REF_TAG),
+                &JsValue::from_str(&container_id.to_string()),
+            )
+            .unwrap();
+            map.into_js_result().unwrap()
+        }
+        LoroValue::Timestamp(t) => js_sys::Date::new(&JsValue::from_f64(t as f64)).into(),
+        LoroValue::Decimal(d) => {
+            let map = Object::new();
+            js_sys::Reflect::set(
+                &map,
+                &JsValue::from_str(LORO_DECIMAL_TAG),
+                &JsValue::from_str(d.as_str()),
+            )
+            .unwrap();
+            map.into_js_result().unwrap()
+        }
         LoroValue::Binary(binary) => {
             let binary = binary.unwrap();
             let arr = Uint8Array::new_with_length(binary.len() as u32);
@@ -454,6 +486,56 @@ pub(crate) fn import_status_to_js_value(status: ImportStatus) -> JsValue {
         },
     )
     .unwrap();
+    js_sys::Reflect::set(
+        &obj,
+        &JsValue::from_str("stats"),
+        &import_stats_to_js_value(status.stats),
+    )
+    .unwrap();
+    js_sys::Reflect::set(
+        &obj,
+        &JsValue::from_str("snapshot"),
+        &match status.snapshot {
+            None => JsValue::null(),
+            Some(SnapshotImportMode::Reset) => JsValue::from_str("reset"),
+            Some(SnapshotImportMode::Merged) => JsValue::from_str("merged"),
+        },
+    )
+    .unwrap();
+    obj.into()
+}
+
+fn import_stats_to_js_value(stats: ImportStats) -> JsValue {
+    let obj = Object::new();
+    js_sys::Reflect::set(&obj, &"changes".into(), &(stats.changes as f64).into()).unwrap();
+    js_sys::Reflect::set(&obj, &"ops".into(), &(stats.ops as f64).into()).unwrap();
+    let peers = Array::new();
+    for peer in stats.peers {
+        peers.push(&JsValue::from_str(&peer.to_string()));
+    }
+    js_sys::Reflect::set(&obj, &"peers".into(), &peers.into()).unwrap();
+    let op_age = match stats.op_age {
+        None => JsValue::null(),
+        Some(age) => {
+            let age_obj = Object::new();
+            js_sys::Reflect::set(&age_obj, &"min".into(), &(age.min as f64).into()).unwrap();
+            js_sys::Reflect::set(&age_obj, &"max".into(), &(age.max as f64).into()).unwrap();
+            js_sys::Reflect::set(&age_obj, &"mean".into(), &age.mean().into()).unwrap();
+            js_sys::Reflect::set(&age_obj, &"ops".into(), &(age.ops as f64).into()).unwrap();
+            let buckets = Array::new();
+            for b in age.buckets {
+                buckets.push(&(b as f64).into());
+            }
+            js_sys::Reflect::set(&age_obj, &"buckets".into(), &buckets.into()).unwrap();
+            age_obj.into()
+        }
+    };
+    js_sys::Reflect::set(&obj, &"opAge".into(), &op_age).unwrap();
+    let gaps = Map::new();
+    for (peer, gap) in stats.causal_gaps {
+        gaps.set(&JsValue::from_str(&peer.to_string()), &gap.into());
+    }
+    js_sys::Reflect::set(&obj, &"causalGaps".into(), &gaps.into()).unwrap();
     obj.into()
 }
 
@@ -614,6 +696,12 @@ pub(crate) fn js_value_to_loro_value(js: &JsValue) -> LoroValue {
         } else {
             LoroValue::String(s.into())
         }
+    } else if let Some(date) = js.dyn_ref::<js_sys::Date>() {
+        // Parsed as a timestamp by the docs with the extended value types enabled
+        LoroValue::Map(LoroMapValue::from(vec![(
+            LORO_TIMESTAMP_TAG.to_string(),
+            LoroValue::I64(date.get_time() as i64),
+        )]))
     } else if js.is_array() {
         let arr = Array::from(js);
         let mut vec = Vec::with_capacity(arr.length() as usize);
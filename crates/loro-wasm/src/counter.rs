@@ -1,13 +1,14 @@
 use super::subscription_to_js_function_callback;
 use loro_internal::{
     handler::{counter::CounterHandler, Handler},
+    handler_trace::TraceAction,
     HandlerTrait,
 };
 use std::sync::Arc;
 use wasm_bindgen::prelude::*;
 
 use crate::{
-    call_after_micro_task, convert::handler_to_js_value, observer, JsContainerID,
+    call_after_micro_task, convert::handler_to_js_value, observer, trace, JsContainerID,
     JsContainerOrUndefined, JsCounterStr, JsLoroTreeOrUndefined, JsResult,
 };
 
@@ -48,13 +49,21 @@ impl LoroCounter {
 
     /// Increment the counter by the given value.
     pub fn increment(&self, value: f64) -> JsResult<()> {
-        self.handler.increment(value)?;
+        let step = trace::record(&self.handler, |container| TraceAction::CounterIncrement {
+            container,
+            value,
+        });
+        step.finish(self.handler.increment(value))?;
         Ok(())
     }
 
     /// Decrement the counter by the given value.
     pub fn decrement(&self, value: f64) -> JsResult<()> {
-        self.handler.decrement(value)?;
+        let step = trace::record(&self.handler, |container| TraceAction::CounterIncrement {
+            container,
+            value: -value,
+        });
+        step.finish(self.handler.decrement(value))?;
         Ok(())
     }
 
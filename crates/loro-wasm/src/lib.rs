@@ -20,6 +20,7 @@ use loro_internal::{
         Handler, ListHandler, MapHandler, TextDelta, TextHandler, TreeHandler, UpdateOptions,
         ValueOrHandler,
     },
+    handler_trace::TraceAction,
     id::{Counter, PeerID, TreeID, ID},
     json::JsonSchema,
     loro::{CommitOptions, ExportMode},
@@ -32,7 +33,7 @@ use loro_internal::{
 };
 use rle::HasLength;
 use serde::{Deserialize, Serialize};
-use std::{cell::RefCell, cmp::Ordering, ops::ControlFlow, rc::Rc, sync::Arc};
+use std::{borrow::Cow, cell::RefCell, cmp::Ordering, ops::ControlFlow, rc::Rc, sync::Arc};
 use wasm_bindgen::{__rt::IntoJsResult, prelude::*, throw_val};
 use wasm_bindgen_derive::TryFromJsValue;
 
@@ -41,9 +42,12 @@ pub use counter::LoroCounter;
 
 mod awareness;
 mod log;
+mod sync_driver;
+mod trace;
 
 use crate::convert::{handler_to_js_value, js_to_container, js_to_cursor};
 pub use awareness::{AwarenessWasm, EphemeralStoreWasm};
+pub use sync_driver::SyncDriver;
 
 mod convert;
 
@@ -428,6 +432,43 @@ impl LoroDoc {
         self.0.set_record_timestamp(auto_record);
     }
 
+    /// Set whether the text events report the ids of the changed paragraphs in
+    /// `paragraphChanged`. Default is `false`.
+    ///
+    /// A paragraph is identified by the id of the newline before it, or `null` for the
+    /// first paragraph, so block-based renderers can re-render only the affected paragraphs.
+    #[wasm_bindgen(js_name = "setTextParagraphIds")]
+    pub fn set_text_paragraph_ids(&self, enabled: bool) {
+        self.0.set_text_paragraph_ids(enabled);
+    }
+
+    /// Set whether the local edits can write the references to containers, the dates and
+    /// the decimals. Default is `false`, because the older versions of Loro can't decode them.
+    ///
+    /// Once enabled, the local edits read the single-entry objects like
+    /// `{"🦜ref": "cid:root-a:Map"}` and the `Date`s as such values. Otherwise they stay
+    /// plain objects, and a `Date` is stored as `{"🦜ts": <milliseconds>}`.
+    #[wasm_bindgen(js_name = "setExtendedValueTypes")]
+    pub fn set_extended_value_types(&self, enabled: bool) {
+        self.0.set_extended_value_types(enabled);
+    }
+
+    /// Set whether the exported snapshots store the checksums of the container states.
+    /// Default is `false`, because computing them slows down the export.
+    #[wasm_bindgen(js_name = "setSnapshotChecksums")]
+    pub fn set_snapshot_checksums(&self, enabled: bool) {
+        self.0.set_snapshot_checksums(enabled);
+    }
+
+    /// Make the internal randomness of the doc deterministic by deriving it from `seed`.
+    ///
+    /// It covers the peer ids generated by the doc afterwards and the jitter of the
+    /// fractional indexes of trees. Pass `undefined` to use the secure random generator.
+    #[wasm_bindgen(js_name = "setRandomSeed")]
+    pub fn set_random_seed(&self, seed: Option<u64>) {
+        self.0.set_random_seed(seed);
+    }
+
     /// If two continuous local changes are within (<=) the interval(**in seconds**), they will be merged into one change.
     ///
     /// The default value is 1_000 seconds.
@@ -479,6 +520,7 @@ impl LoroDoc {
     #[wasm_bindgen(js_name = "configTextStyle")]
     pub fn config_text_style(&self, styles: JsTextStyles) -> JsResult<()> {
         let mut style_config = StyleConfigMap::new();
+        let mut traced_styles = Vec::new();
         // read key value pair in styles
         for key in Reflect::own_keys(&styles)?.iter() {
             let value = Reflect::get(&styles, &key).unwrap();
@@ -492,14 +534,19 @@ impl LoroDoc {
             let expand_str = expand.as_string().unwrap();
             // read allowOverlap value from value
             style_config.insert(
-                key.into(),
+                key.as_str().into(),
                 StyleConfig {
                     expand: ExpandType::try_from_str(&expand_str)
                         .expect("`expand` must be one of `none`, `start`, `end`, `both`"),
+                    value_type: None,
                 },
             );
+            traced_styles.push((key, expand_str));
         }
 
+        trace::record_doc(&self.0, || TraceAction::ConfigTextStyle {
+            styles: traced_styles,
+        });
         self.0.config_text_style(style_config);
         Ok(())
     }
@@ -634,6 +681,15 @@ impl LoroDoc {
         Ok(Self(self.0.fork_at(&ids_to_frontiers(frontiers)?)))
     }
 
+    /// Creates a new shallow LoroDoc at a specified version (Frontiers)
+    ///
+    /// The created doc has the state of the specified version, but only contains the minimal
+    /// history before it. It has a new PeerID and the same configuration.
+    #[wasm_bindgen(js_name = "forkAtShallow")]
+    pub fn fork_at_shallow(&self, frontiers: Vec<JsID>) -> JsResult<LoroDoc> {
+        Ok(Self(self.0.fork_at_shallow(&ids_to_frontiers(frontiers)?)?))
+    }
+
     /// Checkout the `DocState` to the latest version of `OpLog`.
     ///
     /// > The document becomes detached during a `checkout` operation.
@@ -658,6 +714,7 @@ impl LoroDoc {
     /// ```
     #[wasm_bindgen(js_name = "checkoutToLatest")]
     pub fn checkout_to_latest(&mut self) -> JsResult<()> {
+        trace::record_doc(&self.0, || TraceAction::CheckoutToLatest);
         self.0.checkout_to_latest();
         Ok(())
     }
@@ -821,7 +878,11 @@ impl LoroDoc {
     /// console.log(doc.toJSON()); // {"text": ""}
     /// ```
     pub fn checkout(&mut self, frontiers: Vec<JsID>) -> JsResult<()> {
-        self.0.checkout(&ids_to_frontiers(frontiers)?)?;
+        let frontiers = ids_to_frontiers(frontiers)?;
+        let step = trace::record_doc(&self.0, || TraceAction::Checkout {
+            frontiers: frontiers.iter().collect(),
+        });
+        step.finish(self.0.checkout(&frontiers))?;
         Ok(())
     }
 
@@ -847,10 +908,24 @@ impl LoroDoc {
     #[wasm_bindgen(js_name = "setPeerId", method)]
     pub fn set_peer_id(&self, peer_id: JsIntoPeerID) -> JsResult<()> {
         let id = js_peer_to_peer(peer_id.into())?;
-        self.0.set_peer_id(id)?;
+        let step = trace::record_doc(&self.0, || TraceAction::SetPeerId { peer: id });
+        step.finish(self.0.set_peer_id(id))?;
         Ok(())
     }
 
+    /// Switch to a new random peer id and return it.
+    ///
+    /// The pending changes are committed with the old peer id, and the following changes
+    /// are made by the new peer. The doc and the containers stay usable. The first change of
+    /// the new peer records the old peer id in its commit message.
+    #[wasm_bindgen(js_name = "rotatePeerId", method)]
+    pub fn rotate_peer_id(&self) -> JsResult<JsStrPeerID> {
+        let peer = self.0.rotate_peer_id()?;
+        trace::record_doc(&self.0, || TraceAction::SetPeerId { peer });
+        let v: JsValue = format!("{}", peer).into();
+        Ok(v.into())
+    }
+
     /// Commit the cumulative auto-committed transaction.
     ///
     /// You can specify the `origin`, `timestamp`, and `message` of the commit.
@@ -873,12 +948,12 @@ impl LoroDoc {
     ///
     /// NOTE: The `origin` will not be persisted, but the `message` will.
     pub fn commit(&self, options: Option<JsCommitOption>) -> JsResult<()> {
-        if let Some(options) = options {
-            let options = js_commit_option_to_commit_options(options)?;
-            self.0.commit_with(options);
-        } else {
-            self.0.commit_with(CommitOptions::default());
-        }
+        let options = match options {
+            Some(options) => js_commit_option_to_commit_options(options)?,
+            None => CommitOptions::default(),
+        };
+        trace::record_doc(&self.0, || TraceAction::Commit);
+        self.0.commit_with(options);
         Ok(())
     }
 
@@ -1471,7 +1546,10 @@ impl LoroDoc {
     /// doc2.import(updates);
     /// ```
     pub fn import(&self, update_or_snapshot: &[u8]) -> JsResult<JsImportStatus> {
-        let status = self.0.import(update_or_snapshot)?;
+        let step = trace::record_doc(&self.0, || TraceAction::Import {
+            bytes: update_or_snapshot.to_vec(),
+        });
+        let status = step.finish(self.0.import(update_or_snapshot))?;
         Ok(import_status_to_js_value(status).into())
     }
 
@@ -2195,6 +2273,16 @@ fn container_diff_to_js_value(event: &loro_internal::ContainerDiff, for_json: bo
         &convert_container_path_to_js_value(&event.path),
     )
     .unwrap();
+    if !event.paragraph_changed.is_empty() {
+        let arr = Array::new();
+        for p in event.paragraph_changed.iter() {
+            arr.push(&match p {
+                loro_internal::ParagraphId::First => JsValue::NULL,
+                loro_internal::ParagraphId::Newline(id) => id_to_js(id),
+            });
+        }
+        Reflect::set(&obj, &"paragraphChanged".into(), &arr.into()).unwrap();
+    }
     obj.into()
 }
 
@@ -2364,7 +2452,12 @@ impl LoroText {
     /// text.insert(0, "Hello");
     /// ```
     pub fn insert(&mut self, index: usize, content: &str) -> JsResult<()> {
-        self.handler.insert(index, content)?;
+        let step = trace::record(&self.handler, |container| TraceAction::TextInsert {
+            container,
+            pos: index,
+            text: content.to_string(),
+        });
+        step.finish(self.handler.insert(index, content))?;
         Ok(())
     }
 
@@ -2417,7 +2510,17 @@ impl LoroText {
     /// text.splice(2, 3, "llo"); // "llo"
     /// ```
     pub fn splice(&mut self, pos: usize, len: usize, s: &str) -> JsResult<String> {
-        match self.handler.splice(pos, len, s) {
+        let delete = trace::record(&self.handler, |container| TraceAction::TextDelete {
+            container,
+            pos,
+            len,
+        });
+        let insert = trace::record(&self.handler, |container| TraceAction::TextInsert {
+            container,
+            pos,
+            text: s.to_string(),
+        });
+        match delete.finish(insert.finish(self.handler.splice(pos, len, s))) {
             Ok(x) => Ok(x),
             Err(x) => Err(x.into()),
         }
@@ -2453,7 +2556,12 @@ impl LoroText {
     /// console.log(s); // "Ho"
     /// ```
     pub fn delete(&mut self, index: usize, len: usize) -> JsResult<()> {
-        self.handler.delete(index, len)?;
+        let step = trace::record(&self.handler, |container| TraceAction::TextDelete {
+            container,
+            pos: index,
+            len,
+        });
+        step.finish(self.handler.delete(index, len))?;
         Ok(())
     }
 
@@ -2495,7 +2603,14 @@ impl LoroText {
     pub fn mark(&self, range: JsRange, key: &str, value: JsValue) -> Result<(), JsError> {
         let range: MarkRange = serde_wasm_bindgen::from_value(range.into())?;
         let value: LoroValue = LoroValue::from(value);
-        self.handler.mark(range.start, range.end, key, value)?;
+        let step = trace::record(&self.handler, |container| TraceAction::TextMark {
+            container,
+            start: range.start,
+            end: range.end,
+            key: key.to_string(),
+            value: value.clone(),
+        });
+        step.finish(self.handler.mark(range.start, range.end, key, value))?;
         Ok(())
     }
 
@@ -2519,7 +2634,13 @@ impl LoroText {
     pub fn unmark(&self, range: JsRange, key: &str) -> Result<(), JsValue> {
         // Internally, this may be marking with null or deleting all the marks with key in the range entirely.
         let range: MarkRange = serde_wasm_bindgen::from_value(range.into())?;
-        self.handler.unmark(range.start, range.end, key)?;
+        let step = trace::record(&self.handler, |container| TraceAction::TextUnmark {
+            container,
+            start: range.start,
+            end: range.end,
+            key: key.to_string(),
+        });
+        step.finish(self.handler.unmark(range.start, range.end, key))?;
         Ok(())
     }
 
@@ -2692,7 +2813,12 @@ impl LoroText {
 
     /// Push a string to the end of the text.
     pub fn push(&mut self, s: &str) -> JsResult<()> {
-        self.handler.push_str(s)?;
+        let step = trace::record(&self.handler, |container| TraceAction::TextInsert {
+            container,
+            pos: self.handler.len_utf16(),
+            text: s.to_string(),
+        });
+        step.finish(self.handler.push_str(s))?;
         Ok(())
     }
 
@@ -2770,7 +2896,12 @@ impl LoroMap {
     #[wasm_bindgen(js_name = "set", skip_typescript)]
     pub fn insert(&mut self, key: &str, value: JsLoroValue) -> JsResult<()> {
         let v: JsValue = value.into();
-        self.handler.insert(key, v)?;
+        let step = trace::record(&self.handler, |container| TraceAction::MapSet {
+            container,
+            key: key.to_string(),
+            value: v.clone().into(),
+        });
+        step.finish(self.handler.insert(key, v))?;
         Ok(())
     }
 
@@ -2786,7 +2917,11 @@ impl LoroMap {
     /// map.delete("foo");
     /// ```
     pub fn delete(&mut self, key: &str) -> JsResult<()> {
-        self.handler.delete(key)?;
+        let step = trace::record(&self.handler, |container| TraceAction::MapDelete {
+            container,
+            key: key.to_string(),
+        });
+        step.finish(self.handler.delete(key))?;
         Ok(())
     }
 
@@ -2946,7 +3081,12 @@ impl LoroMap {
     #[wasm_bindgen(js_name = "setContainer", skip_typescript)]
     pub fn insert_container(&mut self, key: &str, child: JsContainer) -> JsResult<JsContainer> {
         let child = convert::js_to_container(child)?;
-        let c = self.handler.insert_container(key, child.to_handler())?;
+        let step = trace::record(&self.handler, |container| TraceAction::MapSetContainer {
+            container,
+            key: key.to_string(),
+            kind: child.to_handler().c_type(),
+        });
+        let c = step.finish(self.handler.insert_container(key, child.to_handler()))?;
         Ok(handler_to_js_value(c, false).into())
     }
 
@@ -3144,7 +3284,12 @@ impl LoroList {
     #[wasm_bindgen(skip_typescript)]
     pub fn insert(&mut self, index: usize, value: JsLoroValue) -> JsResult<()> {
         let v: JsValue = value.into();
-        self.handler.insert(index, v)?;
+        let step = trace::record(&self.handler, |container| TraceAction::ListInsert {
+            container,
+            pos: index,
+            value: v.clone().into(),
+        });
+        step.finish(self.handler.insert(index, v))?;
         Ok(())
     }
 
@@ -3161,7 +3306,12 @@ impl LoroList {
     /// console.log(list.value);  // []
     /// ```
     pub fn delete(&mut self, index: usize, len: usize) -> JsResult<()> {
-        self.handler.delete(index, len)?;
+        let step = trace::record(&self.handler, |container| TraceAction::ListDelete {
+            container,
+            pos: index,
+            len,
+        });
+        step.finish(self.handler.delete(index, len))?;
         Ok(())
     }
 
@@ -3266,7 +3416,14 @@ impl LoroList {
     #[wasm_bindgen(js_name = "insertContainer", skip_typescript)]
     pub fn insert_container(&mut self, index: usize, child: JsContainer) -> JsResult<JsContainer> {
         let child = js_to_container(child)?;
-        let c = self.handler.insert_container(index, child.to_handler())?;
+        let step = trace::record(&self.handler, |container| {
+            TraceAction::ListInsertContainer {
+                container,
+                pos: index,
+                kind: child.to_handler().c_type(),
+            }
+        });
+        let c = step.finish(self.handler.insert_container(index, child.to_handler()))?;
         Ok(handler_to_js_value(c, false).into())
     }
 
@@ -3390,7 +3547,12 @@ impl LoroList {
     #[wasm_bindgen(skip_typescript)]
     pub fn push(&self, value: JsLoroValue) -> JsResult<()> {
         let v: JsValue = value.into();
-        self.handler.push(v)?;
+        let step = trace::record(&self.handler, |container| TraceAction::ListInsert {
+            container,
+            pos: self.handler.len(),
+            value: v.clone().into(),
+        });
+        step.finish(self.handler.push(v))?;
         Ok(())
     }
 
@@ -3499,7 +3661,12 @@ impl LoroMovableList {
     #[wasm_bindgen(skip_typescript)]
     pub fn insert(&mut self, index: usize, value: JsLoroValue) -> JsResult<()> {
         let v: JsValue = value.into();
-        self.handler.insert(index, v)?;
+        let step = trace::record(&self.handler, |container| TraceAction::ListInsert {
+            container,
+            pos: index,
+            value: v.clone().into(),
+        });
+        step.finish(self.handler.insert(index, v))?;
         Ok(())
     }
 
@@ -3516,7 +3683,12 @@ impl LoroMovableList {
     /// console.log(list.value);  // []
     /// ```
     pub fn delete(&mut self, index: usize, len: usize) -> JsResult<()> {
-        self.handler.delete(index, len)?;
+        let step = trace::record(&self.handler, |container| TraceAction::ListDelete {
+            container,
+            pos: index,
+            len,
+        });
+        step.finish(self.handler.delete(index, len))?;
         Ok(())
     }
 
@@ -3621,7 +3793,14 @@ impl LoroMovableList {
     #[wasm_bindgen(js_name = "insertContainer", skip_typescript)]
     pub fn insert_container(&mut self, index: usize, child: JsContainer) -> JsResult<JsContainer> {
         let child = js_to_container(child)?;
-        let c = self.handler.insert_container(index, child.to_handler())?;
+        let step = trace::record(&self.handler, |container| {
+            TraceAction::ListInsertContainer {
+                container,
+                pos: index,
+                kind: child.to_handler().c_type(),
+            }
+        });
+        let c = step.finish(self.handler.insert_container(index, child.to_handler()))?;
         Ok(handler_to_js_value(c, false).into())
     }
 
@@ -3751,7 +3930,12 @@ impl LoroMovableList {
     /// operations in a MovableList.
     #[wasm_bindgen(js_name = "move")]
     pub fn mov(&self, from: usize, to: usize) -> JsResult<()> {
-        self.handler.mov(from, to)?;
+        let step = trace::record(&self.handler, |container| TraceAction::MovableListMove {
+            container,
+            from,
+            to,
+        });
+        step.finish(self.handler.mov(from, to))?;
         Ok(())
     }
 
@@ -3767,7 +3951,12 @@ impl LoroMovableList {
     #[wasm_bindgen(skip_typescript)]
     pub fn set(&self, pos: usize, value: JsLoroValue) -> JsResult<()> {
         let v: JsValue = value.into();
-        self.handler.set(pos, v)?;
+        let step = trace::record(&self.handler, |container| TraceAction::MovableListSet {
+            container,
+            pos,
+            value: v.clone().into(),
+        });
+        step.finish(self.handler.set(pos, v))?;
         Ok(())
     }
 
@@ -3775,7 +3964,14 @@ impl LoroMovableList {
     #[wasm_bindgen(skip_typescript)]
     pub fn setContainer(&self, pos: usize, child: JsContainer) -> JsResult<JsContainer> {
         let child = js_to_container(child)?;
-        let c = self.handler.set_container(pos, child.to_handler())?;
+        let step = trace::record(&self.handler, |container| {
+            TraceAction::MovableListSetContainer {
+                container,
+                pos,
+                kind: child.to_handler().c_type(),
+            }
+        });
+        let c = step.finish(self.handler.set_container(pos, child.to_handler()))?;
         Ok(handler_to_js_value(c, false).into())
     }
 
@@ -3783,7 +3979,12 @@ impl LoroMovableList {
     #[wasm_bindgen(skip_typescript)]
     pub fn push(&self, value: JsLoroValue) -> JsResult<()> {
         let v: JsValue = value.into();
-        self.handler.push(v.into())?;
+        let step = trace::record(&self.handler, |container| TraceAction::ListInsert {
+            container,
+            pos: self.handler.len(),
+            value: v.clone().into(),
+        });
+        step.finish(self.handler.push(v.into()))?;
         Ok(())
     }
 
@@ -3925,11 +4126,16 @@ impl LoroTreeNode {
     // Using custom typescript for generic on returned node
     #[wasm_bindgen(js_name = "createNode", skip_typescript)]
     pub fn create_node(&self, index: Option<usize>) -> JsResult<LoroTreeNode> {
-        let id = if let Some(index) = index {
-            self.tree.create_at(TreeParentId::Node(self.id), index)?
+        let step = trace::record(&self.tree, |container| TraceAction::TreeCreate {
+            container,
+            parent: Some(self.id),
+            index,
+        });
+        let id = step.finish(if let Some(index) = index {
+            self.tree.create_at(TreeParentId::Node(self.id), index)
         } else {
-            self.tree.create(TreeParentId::Node(self.id))?
-        };
+            self.tree.create(TreeParentId::Node(self.id))
+        })?;
         let node = LoroTreeNode::from_tree(id, self.tree.clone());
         Ok(node)
     }
@@ -3938,12 +4144,18 @@ impl LoroTreeNode {
     #[wasm_bindgen(js_name = "move", skip_typescript)]
     pub fn mov(&self, parent: &JsTreeNodeOrUndefined, index: Option<usize>) -> JsResult<()> {
         let parent: Option<LoroTreeNode> = parse_js_tree_node(parent)?;
-        if let Some(index) = index {
+        let step = trace::record(&self.tree, |container| TraceAction::TreeMove {
+            container,
+            target: self.id,
+            parent: parent.as_ref().map(|x| x.id),
+            index,
+        });
+        step.finish(if let Some(index) = index {
             self.tree
-                .move_to(self.id, parent.map(|x| x.id).into(), index)?
+                .move_to(self.id, parent.map(|x| x.id).into(), index)
         } else {
-            self.tree.mov(self.id, parent.map(|x| x.id).into())?;
-        }
+            self.tree.mov(self.id, parent.map(|x| x.id).into())
+        })?;
 
         Ok(())
     }
@@ -3966,7 +4178,12 @@ impl LoroTreeNode {
     /// ```
     #[wasm_bindgen(js_name = "moveAfter")]
     pub fn mov_after(&self, target: &LoroTreeNode) -> JsResult<()> {
-        self.tree.mov_after(self.id, target.id)?;
+        let step = trace::record(&self.tree, |container| TraceAction::TreeMoveAfter {
+            container,
+            target: self.id,
+            other: target.id,
+        });
+        step.finish(self.tree.mov_after(self.id, target.id))?;
         Ok(())
     }
 
@@ -3988,7 +4205,12 @@ impl LoroTreeNode {
     /// ```
     #[wasm_bindgen(js_name = "moveBefore")]
     pub fn mov_before(&self, target: &LoroTreeNode) -> JsResult<()> {
-        self.tree.mov_before(self.id, target.id)?;
+        let step = trace::record(&self.tree, |container| TraceAction::TreeMoveBefore {
+            container,
+            target: self.id,
+            other: target.id,
+        });
+        step.finish(self.tree.mov_before(self.id, target.id))?;
         Ok(())
     }
 
@@ -4140,11 +4362,16 @@ impl LoroTree {
         index: Option<usize>,
     ) -> JsResult<LoroTreeNode> {
         let parent: Option<TreeID> = parse_js_parent(parent)?;
-        let id = if let Some(index) = index {
-            self.handler.create_at(parent.into(), index)?
+        let step = trace::record(&self.handler, |container| TraceAction::TreeCreate {
+            container,
+            parent,
+            index,
+        });
+        let id = step.finish(if let Some(index) = index {
+            self.handler.create_at(parent.into(), index)
         } else {
-            self.handler.create(parent.into())?
-        };
+            self.handler.create(parent.into())
+        })?;
         let node = LoroTreeNode::from_tree(id, self.handler.clone());
         Ok(node)
     }
@@ -4175,12 +4402,18 @@ impl LoroTree {
     ) -> JsResult<()> {
         let target = parse_js_tree_id(target)?;
         let parent = parse_js_parent(parent)?;
+        let step = trace::record(&self.handler, |container| TraceAction::TreeMove {
+            container,
+            target,
+            parent,
+            index,
+        });
 
-        if let Some(index) = index {
-            self.handler.move_to(target, parent.into(), index)?
+        step.finish(if let Some(index) = index {
+            self.handler.move_to(target, parent.into(), index)
         } else {
-            self.handler.mov(target, parent.into())?
-        };
+            self.handler.mov(target, parent.into())
+        })?;
 
         Ok(())
     }
@@ -4199,7 +4432,11 @@ impl LoroTree {
     /// ```
     pub fn delete(&mut self, target: &JsTreeID) -> JsResult<()> {
         let target = parse_js_tree_id(target)?;
-        self.handler.delete(target)?;
+        let step = trace::record(&self.handler, |container| TraceAction::TreeDelete {
+            container,
+            target,
+        });
+        step.finish(self.handler.delete(target))?;
         Ok(())
     }
 
@@ -5098,6 +5335,22 @@ fn js_to_export_mode(js_mode: JsExportMode) -> JsResult<ExportMode<'static>> {
                 Ok(ExportMode::updates_owned(from.0.clone()))
             }
         }
+        "squashed-update" => {
+            let from = js_sys::Reflect::get(&js_value, &JsValue::from_str("from"))?;
+            let from = if from.is_undefined() {
+                Default::default()
+            } else {
+                js_to_version_vector(from)?.0.clone()
+            };
+            let merge_interval =
+                js_sys::Reflect::get(&js_value, &JsValue::from_str("mergeInterval"))?
+                    .as_f64()
+                    .ok_or_else(|| JsError::new("Invalid mergeInterval"))?;
+            Ok(ExportMode::UpdatesSquashed {
+                from: Cow::Owned(from),
+                merge_interval: merge_interval as i64,
+            })
+        }
         "snapshot" => Ok(ExportMode::Snapshot),
         "shallow-snapshot" => {
             let frontiers: JsValue =
@@ -5389,6 +5642,11 @@ export interface Change {
 
 /**
  * Data types supported by loro
+ *
+ * Timestamps are represented by `Date`s. As JS has no decimal type, decimals are
+ * represented by the objects with the single key `🦜dec`, e.g. `{ "🦜dec": "12.05" }`.
+ * The non-owning references to containers are represented by the objects with the
+ * single key `🦜ref`, e.g. `{ "🦜ref": "cid:root-todos:List" }`.
  */
 export type Value =
   | ContainerID
@@ -5398,6 +5656,7 @@ export type Value =
   | null
   | { [key: string]: Value }
   | Uint8Array
+  | Date
   | Value[]
   | undefined;
 
@@ -5640,6 +5899,14 @@ export interface TextUpdateOptions {
 export type ExportMode = {
     mode: "update",
     from?: VersionVector,
+} | {
+    /**
+     * The updates where the consecutive changes from the same peer whose timestamps
+     * are within `mergeInterval` seconds are squashed into one change.
+     */
+    mode: "squashed-update",
+    from?: VersionVector,
+    mergeInterval: number,
 } | {
     mode: "snapshot",
 } | {
@@ -5746,9 +6013,34 @@ export type UnknownOp = {
 
 export type CounterSpan = { start: number, end: number };
 
+/**
+ * The result of an import.
+ *
+ * - `snapshot`: how the imported snapshot was applied. It's `"reset"` if the doc was empty and
+ *   initialized with the snapshot, `"merged"` if the changes in the snapshot were merged into the
+ *   existing history, or null if the imported blob is not a snapshot.
+ */
 export type ImportStatus = {
   success: Map<PeerID, CounterSpan>,
-  pending: Map<PeerID, CounterSpan> | null
+  pending: Map<PeerID, CounterSpan> | null,
+  stats: ImportStats,
+  snapshot: "reset" | "merged" | null
+}
+
+/**
+ * The statistics of an import.
+ *
+ * - `opAge`: the age of the imported ops in seconds, i.e. the time of the import minus the
+ *   timestamps of their changes. It's null if none of the changes has a timestamp.
+ *   `buckets` counts the ops whose ages are below 1s, 10s, 1min, 1h, 1d and above.
+ * - `causalGaps`: the number of missing ops on each peer that the pending changes are waiting for.
+ */
+export type ImportStats = {
+  changes: number,
+  ops: number,
+  peers: PeerID[],
+  opAge: { min: number, max: number, mean: number, ops: number, buckets: number[] } | null,
+  causalGaps: Map<PeerID, number>
 }
 
 export type Frontiers = OpId[];
@@ -5803,6 +6095,13 @@ export interface LoroEvent {
      * The absolute path of the event's emitter, which can be an index of a list container or a key of a map container.
      */
     path: Path;
+    /**
+     * The paragraphs of the text changed by the diff, in document order. `null` stands for
+     * the first paragraph; the others are identified by the newline before them.
+     *
+     * It's only set for text events when `doc.setTextParagraphIds(true)` is called.
+     */
+    paragraphChanged?: (OpId | null)[];
 }
 
 export type ListDiff = {
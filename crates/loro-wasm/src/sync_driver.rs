@@ -0,0 +1,396 @@
+//! The worker side of the protocol that runs a doc off the main thread.
+//!
+//! A [`SyncDriver`] owns the doc in a worker and talks to the main thread through a
+//! `MessagePort`. The main thread calls the methods of the doc and its containers with
+//! `call` messages, and receives the events of the doc as JSON diffs. The byte buffers in the
+//! replies and the local updates are transferred instead of copied.
+//!
+//! Every message has the protocol version in `v`. The messages from the main thread:
+//!
+//! - `{ v, type: "call", id, target, method, args }` calls `method` on the container
+//!   `target`, or on the doc if `target` is `null`. It's answered by
+//!   `{ v, type: "result", id, value }` or `{ v, type: "error", id, message }`. A returned
+//!   container is replaced by `{ containerId }`, and the other wasm objects by their `toJSON()`.
+//! - `{ v, type: "ack", seq }` acknowledges the events up to `seq`.
+//!
+//! The messages from the worker:
+//!
+//! - `{ v, type: "ready", peer }` is sent once the driver is attached to the port.
+//! - `{ v, type: "event", seq, event }` carries an event of the doc in the JSON form.
+//! - `{ v, type: "lagged" }` replaces the events dropped by the backpressure. The main thread
+//!   should read the state again.
+//! - `{ v, type: "localUpdate", bytes }` carries the updates of the local edits, if
+//!   `forwardLocalUpdates` is enabled.
+//!
+//! At most `maxInFlight` events are sent without being acknowledged. The later events wait in
+//! a queue, and if the queue grows over `maxQueued`, they are dropped and a `lagged` message
+//! is sent instead.
+use std::{cell::RefCell, collections::VecDeque, rc::Rc, sync::Arc};
+
+use js_sys::{Array, Function, Object, Reflect, Uint8Array};
+use loro_internal::Subscription;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    console_error, convert::js_to_container, diff_event_to_js_value, observer, JsResult, LoroDoc,
+};
+
+/// The version of the message protocol. The messages with another version are rejected.
+pub const SYNC_PROTOCOL_VERSION: u32 = 1;
+
+#[wasm_bindgen]
+extern "C" {
+    /// The port to the main thread
+    #[wasm_bindgen(typescript_type = "MessagePort")]
+    pub type JsMessagePort;
+    #[wasm_bindgen(method, js_name = "postMessage")]
+    fn post_message(this: &JsMessagePort, message: &JsValue, transfer: &Array);
+    #[wasm_bindgen(method, setter = "onmessage")]
+    fn set_onmessage(this: &JsMessagePort, f: Option<&Function>);
+    /// Options of the sync driver
+    #[wasm_bindgen(
+        typescript_type = "{ maxInFlight?: number, maxQueued?: number, forwardLocalUpdates?: boolean }"
+    )]
+    pub type JsSyncDriverOptions;
+}
+
+struct DriverState {
+    port: JsMessagePort,
+    max_in_flight: u64,
+    max_queued: usize,
+    /// The seq of the last sent event
+    sent: u64,
+    /// The seq of the last acknowledged event
+    acked: u64,
+    queue: VecDeque<JsValue>,
+    /// Events were dropped and the `lagged` message is not sent yet
+    lagged: bool,
+}
+
+impl DriverState {
+    fn post(&self, message: &Object) {
+        self.port.post_message(message, &Array::new());
+    }
+
+    fn push_event(&mut self, event: JsValue) {
+        if self.lagged || self.queue.len() >= self.max_queued {
+            self.queue.clear();
+            self.lagged = true;
+        } else {
+            self.queue.push_back(event);
+        }
+
+        self.flush();
+    }
+
+    /// Send the queued events that fit in the window
+    fn flush(&mut self) {
+        while self.sent - self.acked < self.max_in_flight {
+            if self.lagged {
+                self.lagged = false;
+                self.post(&message("lagged"));
+                continue;
+            }
+
+            let Some(event) = self.queue.pop_front() else {
+                break;
+            };
+
+            self.sent += 1;
+            let msg = message("event");
+            set(&msg, "seq", &(self.sent as f64).into());
+            set(&msg, "event", &event);
+            self.post(&msg);
+        }
+    }
+}
+
+/// Runs a doc in a worker and serves the main thread through a `MessagePort`.
+///
+/// See the module docs of `sync_driver.rs` for the protocol.
+#[wasm_bindgen]
+pub struct SyncDriver {
+    doc: JsValue,
+    state: Rc<RefCell<DriverState>>,
+    _on_message: Closure<dyn FnMut(JsValue)>,
+    _on_event: Closure<dyn FnMut(JsValue)>,
+    subs: Vec<Subscription>,
+}
+
+#[wasm_bindgen]
+impl SyncDriver {
+    /// Attach the doc to the port. The driver keeps serving the port until `detach()` is
+    /// called.
+    ///
+    /// Options:
+    /// - `maxInFlight`: the events that can be sent without being acknowledged, 16 by default
+    /// - `maxQueued`: the events that can wait for the acknowledgements before they are
+    ///   replaced by a `lagged` message, 1024 by default
+    /// - `forwardLocalUpdates`: send the updates of the local edits, false by default
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        doc: &LoroDoc,
+        port: JsMessagePort,
+        options: Option<JsSyncDriverOptions>,
+    ) -> SyncDriver {
+        let options: JsValue = options.map(|x| x.into()).unwrap_or(JsValue::UNDEFINED);
+        let get_number = |key: &str, default: f64| -> f64 {
+            if !options.is_object() {
+                return default;
+            }
+
+            Reflect::get(&options, &key.into())
+                .ok()
+                .and_then(|x| x.as_f64())
+                .unwrap_or(default)
+        };
+        let max_in_flight = get_number("maxInFlight", 16.).max(1.) as u64;
+        let max_queued = get_number("maxQueued", 1024.).max(0.) as usize;
+        let forward_local_updates = options.is_object()
+            && Reflect::get(&options, &"forwardLocalUpdates".into())
+                .ok()
+                .and_then(|x| x.as_bool())
+                .unwrap_or(false);
+
+        let state = Rc::new(RefCell::new(DriverState {
+            port,
+            max_in_flight,
+            max_queued,
+            sent: 0,
+            acked: 0,
+            queue: VecDeque::new(),
+            lagged: false,
+        }));
+
+        let doc_js: JsValue = LoroDoc(doc.0.clone()).into();
+        let on_message = {
+            let state = state.clone();
+            let doc_js = doc_js.clone();
+            Closure::wrap(Box::new(move |e: JsValue| {
+                let data = Reflect::get(&e, &"data".into()).unwrap_or(JsValue::UNDEFINED);
+                handle_message(&state, &doc_js, &data);
+            }) as Box<dyn FnMut(JsValue)>)
+        };
+
+        let on_event = {
+            let state = state.clone();
+            Closure::wrap(Box::new(move |event: JsValue| {
+                // The event can be emitted while a call is running, which doesn't hold
+                // the state
+                if let Ok(mut state) = state.try_borrow_mut() {
+                    state.push_event(event);
+                } else {
+                    console_error!("SyncDriver: the event is emitted reentrantly");
+                }
+            }) as Box<dyn FnMut(JsValue)>)
+        };
+
+        let mut subs = Vec::new();
+        let observer =
+            observer::Observer::new(on_event.as_ref().unchecked_ref::<Function>().clone());
+        subs.push(doc.0.subscribe_root(Arc::new(move |e| {
+            if let Err(e) = observer.call1(&diff_event_to_js_value(e, true)) {
+                console_error!("Error: {:?}", e);
+            }
+        })));
+
+        if forward_local_updates {
+            // The local updates are never dropped, so they skip the event queue
+            let port = state.borrow().port.clone();
+            let observer = observer::Observer::new(
+                Closure::wrap(Box::new(move |bytes: Uint8Array| {
+                    let msg = message("localUpdate");
+                    set(&msg, "bytes", &bytes);
+                    port.post_message(&msg, &Array::of1(&bytes.buffer()));
+                }) as Box<dyn FnMut(Uint8Array)>)
+                .into_js_value()
+                .unchecked_into(),
+            );
+            subs.push(doc.0.subscribe_local_update(Box::new(move |e| {
+                let arr = Uint8Array::new_with_length(e.len() as u32);
+                arr.copy_from(e);
+                if let Err(e) = observer.call1(&arr.into()) {
+                    console_error!("Error: {:?}", e);
+                }
+                true
+            })));
+        }
+
+        {
+            let state = state.borrow();
+            state
+                .port
+                .set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+            let ready = message("ready");
+            set(&ready, "peer", &doc.0.peer_id().to_string().into());
+            state.post(&ready);
+        }
+
+        SyncDriver {
+            doc: doc_js,
+            state,
+            _on_message: on_message,
+            _on_event: on_event,
+            subs,
+        }
+    }
+
+    /// The doc served by the driver
+    #[wasm_bindgen(getter)]
+    pub fn doc(&self) -> JsValue {
+        self.doc.clone()
+    }
+
+    /// The number of the events that are sent but not acknowledged
+    #[wasm_bindgen(js_name = "inFlight")]
+    pub fn in_flight(&self) -> f64 {
+        let state = self.state.borrow();
+        (state.sent - state.acked) as f64
+    }
+
+    /// The number of the events waiting for the acknowledgements
+    pub fn queued(&self) -> usize {
+        self.state.borrow().queue.len()
+    }
+
+    /// Stop serving the port and unsubscribe from the doc
+    pub fn detach(&mut self) {
+        self.state.borrow().port.set_onmessage(None);
+        self.subs.clear();
+    }
+}
+
+fn handle_message(state: &Rc<RefCell<DriverState>>, doc: &JsValue, data: &JsValue) {
+    let ty = Reflect::get(data, &"type".into())
+        .ok()
+        .and_then(|x| x.as_string());
+    let id = Reflect::get(data, &"id".into()).unwrap_or(JsValue::UNDEFINED);
+    let version = Reflect::get(data, &"v".into())
+        .ok()
+        .and_then(|x| x.as_f64());
+    if version != Some(SYNC_PROTOCOL_VERSION as f64) {
+        let msg = error_message(
+            &id,
+            &format!(
+                "Unsupported protocol version {:?}, expected {}",
+                version, SYNC_PROTOCOL_VERSION
+            ),
+        );
+        state.borrow().post(&msg);
+        return;
+    }
+
+    match ty.as_deref() {
+        Some("ack") => {
+            let seq = Reflect::get(data, &"seq".into())
+                .ok()
+                .and_then(|x| x.as_f64())
+                .unwrap_or(0.) as u64;
+            let mut state = state.borrow_mut();
+            state.acked = state.acked.max(seq.min(state.sent));
+            state.flush();
+        }
+        Some("call") => {
+            // The state is not borrowed during the call, so the events emitted by the call
+            // can be queued
+            let ans = call(doc, data);
+            let state = state.borrow();
+            match ans {
+                Ok(value) => {
+                    let msg = message("result");
+                    set(&msg, "id", &id);
+                    let transfer = Array::new();
+                    if let Some(bytes) = value.dyn_ref::<Uint8Array>() {
+                        transfer.push(&bytes.buffer());
+                    }
+                    set(&msg, "value", &value);
+                    state.port.post_message(&msg, &transfer);
+                }
+                Err(e) => {
+                    let message = e
+                        .dyn_ref::<js_sys::Error>()
+                        .map(|e| String::from(e.message()))
+                        .or_else(|| e.as_string())
+                        .unwrap_or_else(|| format!("{:?}", e));
+                    state.post(&error_message(&id, &message));
+                }
+            }
+        }
+        _ => {
+            state.borrow().post(&error_message(
+                &id,
+                &format!("Unknown message type {:?}", ty),
+            ));
+        }
+    }
+}
+
+fn call(doc: &JsValue, data: &JsValue) -> JsResult<JsValue> {
+    let target = Reflect::get(data, &"target".into())?;
+    let method = Reflect::get(data, &"method".into())?;
+    let args: Array = match Reflect::get(data, &"args".into())? {
+        args if args.is_undefined() => Array::new(),
+        args => args.dyn_into()?,
+    };
+
+    let this = if target.is_null() || target.is_undefined() {
+        doc.clone()
+    } else {
+        let get_container: Function = Reflect::get(doc, &"getContainerById".into())?.into();
+        let container = get_container.call1(doc, &target)?;
+        if container.is_undefined() {
+            return Err(JsValue::from_str(&format!(
+                "Container {:?} is not found",
+                target
+            )));
+        }
+        container
+    };
+
+    let f = Reflect::get(&this, &method)?;
+    let Some(f) = f.dyn_ref::<Function>() else {
+        return Err(JsValue::from_str(&format!("{:?} is not a method", method)));
+    };
+
+    to_cloneable(Reflect::apply(f, &this, &args)?)
+}
+
+/// Replace the containers in the returned value by their ids, and the other wasm objects by
+/// their JSON form, so the value can be posted
+fn to_cloneable(value: JsValue) -> JsResult<JsValue> {
+    if !value.is_object() || value.is_instance_of::<Uint8Array>() {
+        return Ok(value);
+    }
+
+    if js_to_container(value.clone().unchecked_into()).is_ok() {
+        let ans = Object::new();
+        set(&ans, "containerId", &Reflect::get(&value, &"id".into())?);
+        return Ok(ans.into());
+    }
+
+    if Reflect::has(&value, &"__wbg_ptr".into())? {
+        if let Ok(to_json) = Reflect::get(&value, &"toJSON".into())?.dyn_into::<Function>() {
+            return to_json.call0(&value);
+        }
+    }
+
+    Ok(value)
+}
+
+fn message(ty: &str) -> Object {
+    let msg = Object::new();
+    set(&msg, "v", &SYNC_PROTOCOL_VERSION.into());
+    set(&msg, "type", &ty.into());
+    msg
+}
+
+fn error_message(id: &JsValue, message: &str) -> Object {
+    let msg = self::message("error");
+    set(&msg, "id", id);
+    set(&msg, "message", &message.into());
+    msg
+}
+
+fn set(obj: &Object, key: &str, value: &JsValue) {
+    Reflect::set(obj, &key.into(), value).unwrap();
+}
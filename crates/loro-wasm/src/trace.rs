@@ -0,0 +1,149 @@
+//! Record the handler calls made from JS, so that they can be replayed in the Rust tests.
+//!
+//! See [`loro_internal::handler_trace`] for the format of the traces.
+use std::cell::RefCell;
+
+use loro_internal::{
+    handler_trace::{HandlerTrace, TraceAction, TraceCheckpoint, TraceStep},
+    loro::ExportMode,
+    ContainerID, HandlerTrait, LoroDoc,
+};
+use wasm_bindgen::prelude::*;
+
+#[derive(Default)]
+struct Recorder {
+    docs: Vec<LoroDoc>,
+    trace: HandlerTrace,
+}
+
+thread_local! {
+    static RECORDER: RefCell<Option<Recorder>> = const { RefCell::new(None) };
+}
+
+impl Recorder {
+    fn find_doc(&self, doc: &LoroDoc) -> Option<usize> {
+        self.docs
+            .iter()
+            .position(|d| std::ptr::eq::<loro_internal::LoroDocInner>(&**d, &**doc))
+    }
+}
+
+fn is_recording() -> bool {
+    RECORDER.with(|r| r.borrow().is_some())
+}
+
+/// A step of the trace recorded before its call is made.
+///
+/// The step is recorded before the call, so that the trace of a call that panics ends with
+/// it. Pass the result of a fallible call to [`Recorded::finish`], which removes the step if
+/// the call failed: the trace only has the calls that succeeded, and the replay can expect
+/// every step to succeed.
+pub(crate) struct Recorded(Option<usize>);
+
+impl Recorded {
+    pub fn finish<T, E>(self, result: Result<T, E>) -> Result<T, E> {
+        if let (Some(index), Err(_)) = (self.0, &result) {
+            RECORDER.with(|r| {
+                if let Some(r) = r.borrow_mut().as_mut() {
+                    if index < r.trace.steps.len() {
+                        r.trace.steps.remove(index);
+                    }
+                }
+            });
+        }
+
+        result
+    }
+}
+
+/// Record a call on the doc. It should be called before the call is made.
+pub(crate) fn record_doc(doc: &LoroDoc, action: impl FnOnce() -> TraceAction) -> Recorded {
+    if !is_recording() {
+        return Recorded(None);
+    }
+
+    let known = RECORDER.with(|r| r.borrow().as_ref().and_then(|r| r.find_doc(doc)));
+    let new_doc = if known.is_none() {
+        // Exporting may emit the pending events, so it must not hold the recorder
+        let snapshot =
+            (!doc.oplog_frontiers().is_empty()).then(|| doc.export(ExportMode::Snapshot).unwrap());
+        Some(TraceAction::NewDoc {
+            peer: doc.peer_id(),
+            snapshot,
+        })
+    } else {
+        None
+    };
+
+    let index = RECORDER.with(|r| {
+        let mut r = r.borrow_mut();
+        let r = r.as_mut()?;
+
+        let index = match r.find_doc(doc) {
+            Some(index) => index,
+            None => {
+                r.docs.push(doc.clone());
+                let index = r.docs.len() - 1;
+                if let Some(action) = new_doc {
+                    r.trace.steps.push(TraceStep { doc: index, action });
+                }
+                index
+            }
+        };
+        r.trace.steps.push(TraceStep {
+            doc: index,
+            action: action(),
+        });
+        Some(r.trace.steps.len() - 1)
+    });
+    Recorded(index)
+}
+
+/// Record a call on the container. The calls on the detached containers are skipped.
+#[must_use]
+pub(crate) fn record<H: HandlerTrait>(
+    handler: &H,
+    action: impl FnOnce(ContainerID) -> TraceAction,
+) -> Recorded {
+    if !is_recording() {
+        return Recorded(None);
+    }
+
+    match handler.doc() {
+        Some(doc) => record_doc(&doc, || action(handler.id())),
+        None => Recorded(None),
+    }
+}
+
+/// Start recording the handler calls on all the docs.
+///
+/// The recording made by the previous `startTrace` is discarded. The mutating calls, e.g.
+/// `text.insert`, `map.set` and `doc.import`, are recorded, so that the trace can be replayed
+/// by the Rust tests to reproduce a bug. The calls that return an error are left out.
+#[wasm_bindgen(js_name = "startTrace")]
+pub fn start_trace() {
+    RECORDER.with(|r| *r.borrow_mut() = Some(Recorder::default()));
+}
+
+/// Stop recording the handler calls and return the trace in JSON.
+///
+/// By default, the states of the docs are attached to the trace, so that the replay can
+/// compare them. Pass `false` if the docs are broken, e.g. after a panic.
+///
+/// It returns `undefined` if `startTrace` was not called.
+#[wasm_bindgen(js_name = "stopTrace")]
+pub fn stop_trace(checkpoint: Option<bool>) -> Option<String> {
+    let recorder = RECORDER.with(|r| r.borrow_mut().take())?;
+    let mut trace = recorder.trace;
+    if checkpoint.unwrap_or(true) {
+        for (i, doc) in recorder.docs.iter().enumerate() {
+            trace.checkpoints.push(TraceCheckpoint {
+                doc: i,
+                value: serde_json::to_value(doc.get_deep_value()).unwrap(),
+                oplog_vv: doc.oplog_vv(),
+            });
+        }
+    }
+
+    Some(trace.to_json())
+}
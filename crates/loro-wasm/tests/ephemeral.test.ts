@@ -115,4 +115,43 @@ describe("EphemeralStore", () => {
             b: Uint8Array.from([5, 6, 7, 8]),
         });
     });
+
+    it("throttle local updates", async () => {
+        const a = new EphemeralStore(30_000, 20);
+        const b = new EphemeralStore(30_000);
+        let updates = 0;
+        a.subscribeLocalUpdates((bytes) => {
+            updates += 1;
+            b.applyUpdate(bytes);
+        });
+        a.set("cursor", 1);
+        expect(updates).toBe(1);
+        a.set("cursor", 2);
+        a.set("cursor", 3);
+        a.set("selection", 4);
+        expect(updates).toBe(1);
+        expect(b.get("cursor")).toBe(1);
+        await new Promise((r) => setTimeout(r, 40));
+        expect(updates).toBe(2);
+        expect(b.get("cursor")).toBe(3);
+        expect(b.get("selection")).toBe(4);
+
+        a.set("cursor", 5);
+        a.flush();
+        expect(b.get("cursor")).toBe(5);
+        a.destroy();
+        b.destroy();
+    });
+
+    it("encode update of some keys", () => {
+        const a = new EphemeralStore();
+        const b = new EphemeralStore();
+        a.set("a", 1);
+        a.set("b", 2);
+        a.set("c", 3);
+        b.applyUpdate(a.encodeUpdate(["a", "c"]));
+        expect(b.getAllStates()).toEqual({ a: 1, c: 3 });
+        a.destroy();
+        b.destroy();
+    });
 }); 
\ No newline at end of file
@@ -0,0 +1,36 @@
+import { describe, expect, it } from "vitest";
+import { LoroDoc, SyncClient, SyncDriver } from "../bundler/index";
+
+describe("SyncDriver", () => {
+    it("serves calls and events over a port", async () => {
+        const doc = new LoroDoc();
+        doc.setPeerId(1);
+        const channel = new MessageChannel();
+        const driver = new SyncDriver(doc, channel.port1, { forwardLocalUpdates: true });
+        const client = new SyncClient(channel.port2);
+        expect(await client.ready()).toBe("1");
+
+        const events: unknown[] = [];
+        client.onEvent = (e) => events.push(e);
+        const updates: Uint8Array[] = [];
+        client.onLocalUpdate = (bytes) => updates.push(bytes);
+
+        const text = await client.call<{ containerId: string }>(null, "getText", "text");
+        expect(text.containerId).toBe("cid:root-text:Text");
+        await client.call(text.containerId as any, "insert", 0, "hello");
+        await client.call(null, "commit");
+        expect(await client.call(null, "toJSON")).toStrictEqual({ text: "hello" });
+        await expect(client.call(null, "noSuchMethod")).rejects.toThrow();
+
+        await new Promise((r) => setTimeout(r, 10));
+        expect(events.length).toBe(1);
+        expect(updates.length).toBe(1);
+        const other = new LoroDoc();
+        other.import(updates[0]);
+        expect(other.getText("text").toString()).toBe("hello");
+
+        client.close();
+        driver.detach();
+        channel.port1.close();
+    });
+});
@@ -0,0 +1,86 @@
+use std::{any::Any, fmt::Debug};
+
+use loro_internal::{container::ContainerID, ContainerType, HandlerTrait, LoroValue};
+
+use crate::{Container, ContainerTrait, SealedTrait};
+
+/// A container of any type, returned by [`LoroDoc::get_handler`](crate::LoroDoc::get_handler).
+pub type AnyHandler = Box<dyn DynHandler>;
+
+/// The object safe part of [`ContainerTrait`], implemented by all the containers.
+///
+/// [`ContainerTrait`] can't be made into a trait object because of its associated handler type
+/// and the methods that return `Self`. This trait lets the tools that work with any container,
+/// such as inspectors or migration scripts, hold them as [`AnyHandler`] and downcast them to
+/// the concrete types when needed.
+#[allow(private_bounds)]
+pub trait DynHandler: SealedTrait + Debug + Send + Sync + Any {
+    /// Get the container id.
+    fn id(&self) -> ContainerID;
+    /// Get the type of the container.
+    fn get_type(&self) -> ContainerType;
+    /// Get the shallow value of the container.
+    fn get_value(&self) -> LoroValue;
+    /// Get the deep value of the container.
+    fn get_deep_value(&self) -> LoroValue;
+    /// Convert to a [`Container`], which gives access to the methods of [`ContainerTrait`].
+    fn as_container(&self) -> Container;
+    /// Convert to [`Any`] for downcasting.
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T> DynHandler for T
+where
+    T: ContainerTrait + Debug + Send + Sync + Any,
+{
+    fn id(&self) -> ContainerID {
+        HandlerTrait::id(&self.to_handler())
+    }
+
+    fn get_type(&self) -> ContainerType {
+        self.to_handler().kind()
+    }
+
+    fn get_value(&self) -> LoroValue {
+        HandlerTrait::get_value(&self.to_handler())
+    }
+
+    fn get_deep_value(&self) -> LoroValue {
+        HandlerTrait::get_deep_value(&self.to_handler())
+    }
+
+    fn as_container(&self) -> Container {
+        self.to_container()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl dyn DynHandler {
+    /// Whether the container is of type `T`.
+    pub fn is<T: ContainerTrait + 'static>(&self) -> bool {
+        self.as_any().is::<T>()
+    }
+
+    /// Downcast to the concrete container type, `None` if it's not of type `T`.
+    pub fn downcast_ref<T: ContainerTrait + 'static>(&self) -> Option<&T> {
+        self.as_any().downcast_ref::<T>()
+    }
+}
+
+impl From<Container> for AnyHandler {
+    fn from(container: Container) -> Self {
+        match container {
+            Container::List(x) => Box::new(x),
+            Container::Map(x) => Box::new(x),
+            Container::Text(x) => Box::new(x),
+            Container::Tree(x) => Box::new(x),
+            Container::MovableList(x) => Box::new(x),
+            #[cfg(feature = "counter")]
+            Container::Counter(x) => Box::new(x),
+            Container::Unknown(x) => Box::new(x),
+        }
+    }
+}
@@ -9,8 +9,9 @@ use loro_internal::delta::{ResolvedMapDelta, ResolvedMapValue};
 use loro_internal::event::{EventTriggerKind, ListDeltaMeta};
 use loro_internal::handler::{TextDelta, ValueOrHandler};
 use loro_internal::undo::DiffBatch as InnerDiffBatch;
+pub use loro_internal::ParagraphId;
 use loro_internal::{
-    event::{Diff as DiffInner, Index},
+    event::{json_pointer, Diff as DiffInner, Index, JsonPatchOp, Path},
     ContainerDiff as ContainerDiffInner, DiffEvent as DiffEventInner,
 };
 use loro_internal::{FxHashMap, ListDiffInsertItem};
@@ -23,6 +24,9 @@ use crate::ValueOrContainer;
 /// A subscriber to the event.
 pub type Subscriber = Arc<dyn (for<'a> Fn(DiffEvent<'a>)) + Send + Sync>;
 
+/// A subscriber to the numbered event batches of [`super::LoroDoc::subscribe_durable`].
+pub type DurableSubscriber = Arc<dyn (for<'a> Fn(u64, DiffEvent<'a>)) + Send + Sync>;
+
 /// An event that is triggered by a change in the state of a [super::LoroDoc].
 #[derive(Debug)]
 pub struct DiffEvent<'a> {
@@ -47,6 +51,11 @@ pub struct ContainerDiff<'a> {
     pub is_unknown: bool,
     /// The diff
     pub diff: Diff<'a>,
+    /// The paragraphs of the text that are changed by the diff, in document order.
+    ///
+    /// It's only filled for text containers when [`crate::LoroDoc::set_text_paragraph_ids`]
+    /// is enabled.
+    pub paragraph_changed: &'a [ParagraphId],
 }
 
 /// A concrete diff.
@@ -121,6 +130,71 @@ impl<'a> From<DiffEventInner<'a>> for DiffEvent<'a> {
     }
 }
 
+impl ContainerDiff<'_> {
+    /// The path from the root to the target container, which can be passed to
+    /// [`crate::LoroDoc::get_by_path`].
+    pub fn index_path(&self) -> Path {
+        self.path.iter().map(|(_, index)| index.clone()).collect()
+    }
+
+    /// Express the diff as JSON Patch (RFC 6902) operations on the JSON value of the doc.
+    ///
+    /// See [`Diff::to_json_patch`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{JsonPatchOp, LoroDoc};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let patches = Arc::new(Mutex::new(Vec::new()));
+    /// let patches_clone = patches.clone();
+    /// let _sub = doc.subscribe_root(Arc::new(move |e| {
+    ///     for diff in e.events.iter() {
+    ///         patches_clone.lock().unwrap().extend(diff.to_json_patch().unwrap());
+    ///     }
+    /// }));
+    /// doc.get_list("list").insert(0, 1).unwrap();
+    /// doc.commit();
+    /// assert_eq!(
+    ///     *patches.lock().unwrap(),
+    ///     vec![JsonPatchOp::Add {
+    ///         path: "/list/0".into(),
+    ///         value: 1.into()
+    ///     }]
+    /// );
+    /// ```
+    pub fn to_json_patch(&self) -> Option<Vec<JsonPatchOp>> {
+        self.diff.to_json_patch(&json_pointer(&self.index_path()))
+    }
+}
+
+impl Diff<'_> {
+    /// Express the diff as JSON Patch (RFC 6902) operations on the JSON value of the
+    /// container, which is at the JSON Pointer `path_prefix`.
+    ///
+    /// The new child containers are added as empty values, because their content comes in
+    /// their own diffs. A list diff that moves a single element becomes a `move`.
+    ///
+    /// Returns `None` for the text, tree and counter diffs, which JSON Patch can only express
+    /// by replacing the whole value of the container.
+    pub fn to_json_patch(&self, path_prefix: &str) -> Option<Vec<JsonPatchOp>> {
+        let diff = match self {
+            Diff::List(list) => Diff::List(list.clone()),
+            Diff::Map(map) => Diff::Map(MapDelta {
+                updated: map
+                    .updated
+                    .iter()
+                    .map(|(k, v)| (Cow::Owned(k.to_string()), v.clone()))
+                    .collect(),
+            }),
+            _ => return None,
+        };
+        DiffInner::from(diff).to_json_patch(path_prefix)
+    }
+}
+
 impl<'a> From<&'a ContainerDiffInner> for ContainerDiff<'a> {
     fn from(value: &'a ContainerDiffInner) -> Self {
         ContainerDiff {
@@ -128,6 +202,7 @@ impl<'a> From<&'a ContainerDiffInner> for ContainerDiff<'a> {
             path: &value.path,
             is_unknown: value.is_unknown,
             diff: (&value.diff).into(),
+            paragraph_changed: &value.paragraph_changed,
         }
     }
 }
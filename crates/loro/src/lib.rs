@@ -2,14 +2,19 @@
 #![warn(missing_docs)]
 #![warn(missing_debug_implementations)]
 use event::DiffBatch;
-use event::{DiffEvent, Subscriber};
-use fxhash::FxHashSet;
+use event::{DiffEvent, DurableSubscriber, Subscriber};
+use fxhash::{FxHashMap, FxHashSet};
 pub use loro_common::InternalString;
+pub use loro_common::{
+    LORO_CONTAINER_REF_TAG, LORO_DECIMAL_TAG, LORO_ESCAPED_MAP_TAG, LORO_TIMESTAMP_TAG,
+};
 pub use loro_internal::cursor::CannotFindRelativePosition;
 use loro_internal::cursor::Cursor;
 use loro_internal::cursor::PosQueryResult;
 use loro_internal::cursor::Side;
-pub use loro_internal::encoding::ImportStatus;
+pub use loro_internal::encoding::{
+    ImportStats, ImportStatus, OpAgeStats, SnapshotImportMode, OP_AGE_BUCKET_BOUNDS,
+};
 use loro_internal::handler::{HandlerTrait, ValueOrHandler};
 pub use loro_internal::loro::ChangeTravelError;
 pub use loro_internal::undo::{OnPop, UndoItemMeta, UndoOrRedo};
@@ -28,28 +33,67 @@ use std::cmp::Ordering;
 use std::ops::ControlFlow;
 use std::ops::Deref;
 use std::ops::Range;
+use std::ops::RangeBounds;
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::info;
 
-pub use loro_internal::diff::diff_impl::UpdateOptions;
+pub use loro_internal::author_report::{AuthorReport, AuthoredOp};
+pub use loro_internal::commit_metadata::{
+    parse_commit_message, CommitMetadata, COMMIT_METADATA_PREFIX,
+};
+pub use loro_internal::compression_dict::{compressed_blob_dictionary_id, CompressionDictionary};
 pub use loro_internal::diff::diff_impl::UpdateTimeoutError;
+pub use loro_internal::diff::diff_impl::{DiffMode, UpdateOptions};
+pub use loro_internal::peer_compaction::PeerMapping;
 pub use loro_internal::subscription::LocalUpdateCallback;
 pub use loro_internal::subscription::PeerIdUpdateCallback;
+pub use loro_internal::subscription::SubscriberPriority;
+pub use loro_internal::subscription::VersionUpdateCallback;
 pub use loro_internal::ChangeMeta;
+pub use loro_internal::PollStatus;
+pub use loro_internal::SettledCallback;
 pub use loro_internal::LORO_VERSION;
+pub use loro_internal::{ContainerFilter, ContainerInfo, DanglingContainerRef, ParagraphId};
+pub use loro_internal::{
+    COPIED_FROM_METADATA_KEY, COPIED_VERSION_METADATA_KEY, FROZEN_ROOT_NAME, SUBTREE_ROOT_NAME,
+};
 pub mod event;
+pub use loro_internal::apply_log::IgnoredOp;
 pub use loro_internal::awareness;
+pub use loro_internal::block_store::{BlockStore, FileBlockStore, MemBlockStore};
 pub use loro_internal::change::Timestamp;
-pub use loro_internal::configure::Configure;
-pub use loro_internal::configure::{StyleConfig, StyleConfigMap};
+pub use loro_internal::chunked_snapshot::{ChunkHash, ChunkedSnapshot, SnapshotChunk};
+pub use loro_internal::configure::{
+    Configure, ContainerTypeConflictPolicy, CounterConfig, CounterOverflowPolicy, DecodeLimits,
+    DeleteMergeConfig,
+};
+pub use loro_internal::configure::{
+    InvalidStyle, InvalidStyleReason, StyleConfig, StyleConfigMap, StyleImportPolicy,
+    StyleValueType,
+};
 pub use loro_internal::container::richtext::ExpandType;
 pub use loro_internal::container::{ContainerID, ContainerType, IntoContainerId};
+pub use loro_internal::container_template::ContainerTemplate;
+pub use loro_internal::convergence::{ConvergenceDigest, DivergenceBundle};
 pub use loro_internal::cursor;
+pub use loro_internal::deleted_content::{DeletedListSpan, DeletedSpan};
 pub use loro_internal::delta::{TreeDeltaItem, TreeDiff, TreeDiffItem, TreeExternalDiff};
+pub use loro_internal::durable_subscription::{DurableSubscription, EventsEvicted};
 pub use loro_internal::encoding::ImportBlobMetadata;
-pub use loro_internal::encoding::{EncodedBlobMode, ExportMode};
-pub use loro_internal::event::{EventTriggerKind, Index};
+pub use loro_internal::encoding::{strip_update_payloads, EncodedBlobMode, ExportMode};
+pub use loro_internal::event::{json_pointer, EventTriggerKind, Index, JsonPatchOp, Path};
+pub use loro_internal::handler::AnchorFallback;
+pub use loro_internal::handler::FlatTreeNode;
 pub use loro_internal::handler::TextDelta;
+pub use loro_internal::handler::{
+    LastModified, RubyAnnotation, RubyMarkup, TextBoundary, TextDirection, RUBY_STYLE_KEY,
+};
+pub use loro_internal::handler_trace;
+pub use loro_internal::import_pipeline::{DecodedChange, ImportPipeline};
+pub use loro_internal::invariant::{
+    DocStateView, Invariant, InvariantViolation, InvariantViolationCallback, Violation,
+};
 pub use loro_internal::json;
 pub use loro_internal::json::{
     FutureOp as JsonFutureOp, FutureOpWrapper as JsonFutureOpWrapper, JsonChange, JsonOp,
@@ -59,9 +103,23 @@ pub use loro_internal::json::{
 pub use loro_internal::kv_store::{KvStore, MemKvStore};
 pub use loro_internal::loro::CommitOptions;
 pub use loro_internal::loro::DocAnalysis;
+pub use loro_internal::maintenance::{MaintenanceHint, MaintenanceReport};
+pub use loro_internal::map_conflict::{
+    MapConflictCandidate, MapConflictResolution, MapConflictResolver,
+};
+pub use loro_internal::memory_stats::MemoryStats;
+pub use loro_internal::op_explanation::{OpExplanation, OpOutcome};
+pub use loro_internal::oplog::ContainerTypeConflict;
 pub use loro_internal::oplog::FrontiersNotIncluded;
+pub use loro_internal::oplog::{AppDag, DagNodeInfo};
+pub use loro_internal::replay::ReplayOp;
+pub use loro_internal::state_snapshot::StateSnapshot;
+pub use loro_internal::tree_overlay::{TreeOverlay, TreeOverlayEvent, TreeOverlaySubscriber};
 pub use loro_internal::undo;
-pub use loro_internal::version::{Frontiers, VersionRange, VersionVector, VersionVectorDiff};
+pub use loro_internal::version::{
+    CompactVersionVector, EncodedVersionVectorIter, Frontiers, VersionRange, VersionVector,
+    VersionVectorDiff,
+};
 pub use loro_internal::ApplyDiff;
 pub use loro_internal::Subscription;
 pub use loro_internal::UndoManager as InnerUndoManager;
@@ -70,8 +128,8 @@ pub use loro_internal::{
     Counter, CounterSpan, FractionalIndex, IdLp, IdSpan, Lamport, PeerID, TreeID, TreeParentId, ID,
 };
 pub use loro_internal::{
-    LoroBinaryValue, LoroEncodeError, LoroError, LoroListValue, LoroMapValue, LoroResult,
-    LoroStringValue, LoroTreeError, LoroValue, ToJson,
+    DecodeLimit, LoroBinaryValue, LoroDecimal, LoroEncodeError, LoroError, LoroListValue,
+    LoroMapValue, LoroResult, LoroStringValue, LoroTreeError, LoroValue, ToJson,
 };
 pub use loro_kv_store as kv_store;
 
@@ -85,6 +143,15 @@ mod counter;
 #[cfg(feature = "counter")]
 pub use counter::LoroCounter;
 
+mod text_view;
+pub use text_view::{TextView, TextViewSubscriber};
+
+mod any_handler;
+pub use any_handler::{AnyHandler, DynHandler};
+
+mod remote_cursor;
+pub use remote_cursor::RemoteCursor;
+
 /// `LoroDoc` is the entry for the whole document.
 /// When it's dropped, all the associated [`Handler`]s will be invalidated.
 ///
@@ -131,6 +198,49 @@ impl LoroDoc {
         Self::_new(doc)
     }
 
+    /// Open a snapshot file by memory mapping it.
+    ///
+    /// The history and the container states are decoded lazily from the mapped file when
+    /// they are accessed, so opening a large doc doesn't read all of it into memory.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this process or any other, while the
+    /// doc is alive. Otherwise it's undefined behavior. To save the doc, write the new
+    /// snapshot to another file and rename it over the old one.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn open_mmap(path: impl AsRef<std::path::Path>) -> LoroResult<Self> {
+        let doc = InnerLoroDoc::open_mmap(path)?;
+        doc.start_auto_commit();
+        Ok(Self::_new(doc))
+    }
+
+    /// Create a doc whose history is kept in a [`BlockStore`] instead of in memory.
+    ///
+    /// The change blocks already in the store are loaded when they are accessed, and the
+    /// state is computed from them. The new changes are written into the store by
+    /// [`LoroDoc::flush_block_store`]. The state itself is not stored.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{FileBlockStore, LoroDoc};
+    ///
+    /// let dir = std::env::temp_dir().join(format!("loro-doc-{}", std::process::id()));
+    /// let doc = LoroDoc::from_block_store(FileBlockStore::open(&dir).unwrap()).unwrap();
+    /// doc.get_text("text").insert(0, "hello").unwrap();
+    /// doc.flush_block_store();
+    /// drop(doc);
+    ///
+    /// let doc = LoroDoc::from_block_store(FileBlockStore::open(&dir).unwrap()).unwrap();
+    /// assert_eq!(doc.get_text("text").to_string(), "hello");
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn from_block_store(store: impl BlockStore + 'static) -> LoroResult<Self> {
+        let doc = InnerLoroDoc::from_block_store(store)?;
+        doc.start_auto_commit();
+        Ok(Self::_new(doc))
+    }
+
     /// Duplicate the document with a different PeerID
     ///
     /// The time complexity and space complexity of this operation are both O(n),
@@ -152,6 +262,36 @@ impl LoroDoc {
         Self::_new(new_doc)
     }
 
+    /// Fork the document at the given frontiers as a shallow document.
+    ///
+    /// The created doc has the state at the given frontiers, but only contains the minimal
+    /// history before it (see [`ExportMode::StateOnly`]). It has a new peer id and the same
+    /// configuration. It's useful for creating a document from a template version
+    /// without carrying over its history.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::LoroDoc;
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Template").unwrap();
+    /// doc.commit();
+    /// let template = doc.state_frontiers();
+    /// text.insert(8, " edited").unwrap();
+    /// doc.commit();
+    ///
+    /// let new_doc = doc.fork_at_shallow(&template).unwrap();
+    /// assert_eq!(new_doc.get_text("text").to_string(), "Template");
+    /// assert!(new_doc.is_shallow());
+    /// assert_ne!(new_doc.peer_id(), doc.peer_id());
+    /// ```
+    pub fn fork_at_shallow(&self, frontiers: &Frontiers) -> Result<Self, LoroEncodeError> {
+        let new_doc = self.doc.fork_at_shallow(frontiers)?;
+        new_doc.start_auto_commit();
+        Ok(Self::_new(new_doc))
+    }
+
     /// Get the configurations of the document.
     #[inline]
     pub fn config(&self) -> &Configure {
@@ -242,438 +382,1529 @@ impl LoroDoc {
         self.doc.set_change_merge_interval(interval);
     }
 
-    /// Set the rich text format configuration of the document.
+    /// Set the rule to resolve the concurrent sets on the same key of a map, instead of
+    /// keeping the value of the last writer. `None` restores the default.
     ///
-    /// You need to config it if you use rich text `mark` method.
-    /// Specifically, you need to config the `expand` property of each style.
+    /// The candidates are passed in the order of their lamports and peers, so the result
+    /// doesn't depend on the order of the imports. The resolver must be deterministic and the
+    /// same on all the replicas, and it should be set before the maps are imported or checked
+    /// out. The keys whose candidates include a child container are still resolved by the last
+    /// writer.
     ///
-    /// Expand is used to specify the behavior of expanding when new text is inserted at the
-    /// beginning or end of the style.
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, LoroValue, MapConflictResolution, MapConflictResolver};
+    ///
+    /// let max_wins = || {
+    ///     MapConflictResolver::new(|_, _, a, b| match (a.value, b.value) {
+    ///         (Some(LoroValue::I64(x)), Some(LoroValue::I64(y))) if x > y => {
+    ///             MapConflictResolution::First
+    ///         }
+    ///         _ => MapConflictResolution::Second,
+    ///     })
+    /// };
+    /// let a = LoroDoc::new();
+    /// a.set_map_conflict_resolver(Some(max_wins()));
+    /// a.set_peer_id(1).unwrap();
+    /// a.get_map("map").insert("score", 10).unwrap();
+    /// let b = LoroDoc::new();
+    /// b.set_map_conflict_resolver(Some(max_wins()));
+    /// b.set_peer_id(2).unwrap();
+    /// b.get_map("map").insert("score", 5).unwrap();
+    /// a.import(&b.export(loro::ExportMode::all_updates()).unwrap()).unwrap();
+    /// b.import(&a.export(loro::ExportMode::all_updates()).unwrap()).unwrap();
+    /// assert_eq!(a.get_deep_value(), b.get_deep_value());
+    /// assert_eq!(a.get_map("map").get("score").unwrap().get_deep_value(), 10.into());
+    /// ```
     #[inline]
-    pub fn config_text_style(&self, text_style: StyleConfigMap) {
-        self.doc.config_text_style(text_style)
+    pub fn set_map_conflict_resolver(&self, resolver: Option<MapConflictResolver>) {
+        self.doc.set_map_conflict_resolver(resolver);
     }
 
-    /// Configures the default text style for the document.
+    /// Set whether identical string and binary values share one allocation. Default is `false`.
     ///
-    /// This method sets the default text style configuration for the document when using LoroText.
-    /// If `None` is provided, the default style is reset.
+    /// When enabled, the short strings and binaries inserted into lists, movable lists and maps
+    /// (locally or by importing updates) are looked up in a doc-level pool, so repeated values
+    /// like the enum-like tags of log records are stored only once in memory. The pooled
+    /// values that are no longer used by the doc are evicted as the pool grows.
     ///
-    /// # Parameters
+    /// It doesn't change the values or the encoding: the snapshots still store each value
+    /// in place rather than as an index into the pool.
     ///
-    /// - `text_style`: The style configuration to set as the default. `None` to reset.
-    pub fn config_default_text_style(&self, text_style: Option<StyleConfig>) {
-        self.doc.config_default_text_style(text_style);
-    }
-
-    /// Attach the document state to the latest known version.
+    /// # Example
     ///
-    /// > The document becomes detached during a `checkout` operation.
-    /// > Being `detached` implies that the `DocState` is not synchronized with the latest version of the `OpLog`.
-    /// > In a detached state, the document is not editable, and any `import` operations will be
-    /// > recorded in the `OpLog` without being applied to the `DocState`.
+    /// ```
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.set_value_interning(true);
+    /// let list = doc.get_list("logs");
+    /// for _ in 0..3 {
+    ///     list.push("info").unwrap();
+    /// }
+    /// assert_eq!(list.len(), 3);
+    /// ```
     #[inline]
-    pub fn attach(&self) {
-        self.doc.attach()
+    pub fn set_value_interning(&self, enabled: bool) {
+        self.doc.set_value_interning(enabled);
     }
 
-    /// Checkout the `DocState` to a specific version.
-    ///
-    /// The document becomes detached during a `checkout` operation.
-    /// Being `detached` implies that the `DocState` is not synchronized with the latest version of the `OpLog`.
-    /// In a detached state, the document is not editable, and any `import` operations will be
-    /// recorded in the `OpLog` without being applied to the `DocState`.
-    ///
-    /// You should call `attach` to attach the `DocState` to the latest version of `OpLog`.
+    /// Whether value interning is enabled. See [`LoroDoc::set_value_interning`].
     #[inline]
-    pub fn checkout(&self, frontiers: &Frontiers) -> LoroResult<()> {
-        self.doc.checkout(frontiers)
+    pub fn is_value_interning_enabled(&self) -> bool {
+        self.doc.is_value_interning_enabled()
     }
 
-    /// Checkout the `DocState` to the latest version.
+    /// Set the max number of consecutive imports whose events are composed into one event.
     ///
-    /// > The document becomes detached during a `checkout` operation.
-    /// > Being `detached` implies that the `DocState` is not synchronized with the latest version of the `OpLog`.
-    /// > In a detached state, the document is not editable, and any `import` operations will be
-    /// > recorded in the `OpLog` without being applied to the `DocState`.
+    /// When many small remote updates are imported one by one, the events are held back and
+    /// the diffs are composed per container, so editors can apply one consolidated delta
+    /// instead of many one-character patches. The held events are emitted when the window is
+    /// full, when another kind of event is emitted (e.g. after a local commit or a checkout),
+    /// or when [`LoroDoc::flush_pending_events`] is called.
     ///
-    /// This has the same effect as `attach`.
+    /// The default value is 1, which means the events of every import are emitted immediately.
     #[inline]
-    pub fn checkout_to_latest(&self) {
-        self.doc.checkout_to_latest()
+    pub fn set_import_event_coalescing_window(&self, window: usize) {
+        self.doc.set_import_event_coalescing_window(window);
     }
 
-    /// Compare the frontiers with the current OpLog's version.
+    /// Set how to handle the imported ops that target a container whose type conflicts
+    /// with a known container, i.e. they share the same creation id but have different types.
     ///
-    /// If `other` contains any version that's not contained in the current OpLog, return [Ordering::Less].
+    /// It can only be caused by corrupted or malicious data. By default the import is
+    /// rejected with [`LoroError::ContainerTypeConflict`].
     #[inline]
-    pub fn cmp_with_frontiers(&self, other: &Frontiers) -> Ordering {
-        self.doc.cmp_with_frontiers(other)
+    pub fn set_container_type_conflict_policy(&self, policy: ContainerTypeConflictPolicy) {
+        self.doc.set_container_type_conflict_policy(policy);
     }
 
-    /// Compare two frontiers.
+    /// Set how the counters accumulate their increments. `None`, the default, keeps the
+    /// plain floating point arithmetic.
     ///
-    /// If the frontiers are not included in the document, return [`FrontiersNotIncluded`].
+    /// With a precision, the increments are rounded to that many decimal places and summed
+    /// exactly, so the replicas agree on the value regardless of the order of the ops. It
+    /// should be set before the counters are edited or imported, and to the same config on
+    /// all the replicas.
     #[inline]
-    pub fn cmp_frontiers(
-        &self,
-        a: &Frontiers,
-        b: &Frontiers,
-    ) -> Result<Option<Ordering>, FrontiersNotIncluded> {
-        self.doc.cmp_frontiers(a, b)
+    pub fn config_counter(&self, config: Option<CounterConfig>) {
+        self.doc.config_counter(config);
     }
 
-    /// Force the document enter the detached mode.
+    /// Set the limits checked when the updates and the snapshots are imported.
     ///
-    /// In this mode, when you importing new updates, the [loro_internal::DocState] will not be changed.
+    /// A server that imports blobs from untrusted peers can use them to bound the memory
+    /// spent on a single import. A blob that exceeds a limit is rejected with
+    /// [`LoroError::DecodeLimitExceeded`], without changing the doc.
     ///
-    /// Learn more at https://loro.dev/docs/advanced/doc_state_and_oplog#attacheddetached-status
+    /// # Example
+    /// ```
+    /// use loro::{DecodeLimit, DecodeLimits, LoroDoc, LoroError};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.get_text("text").insert(0, "hello world").unwrap();
+    /// let updates = doc.export(loro::ExportMode::all_updates()).unwrap();
+    ///
+    /// let server = LoroDoc::new();
+    /// server.set_decode_limits(DecodeLimits {
+    ///     max_str_len: 5,
+    ///     ..Default::default()
+    /// });
+    /// assert!(matches!(
+    ///     server.import(&updates),
+    ///     Err(LoroError::DecodeLimitExceeded {
+    ///         limit: DecodeLimit::StringLength,
+    ///         ..
+    ///     })
+    /// ));
+    /// ```
     #[inline]
-    pub fn detach(&self) {
-        self.doc.detach()
+    pub fn set_decode_limits(&self, limits: DecodeLimits) {
+        self.doc.set_decode_limits(limits);
     }
 
-    /// Import a batch of updates/snapshot.
+    /// Set how the consecutive local deletions are merged into one op.
     ///
-    /// The data can be in arbitrary order. The import result will be the same.
+    /// By default, the deletions made in a row, e.g. by holding backspace, are stored as a
+    /// single op. [`DeleteMergeConfig::KEYSTROKE`] keeps each of them as its own op. It only
+    /// changes how the history is stored, not the state of the doc.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{DeleteMergeConfig, LoroDoc, VersionVector};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.set_delete_merge_config(DeleteMergeConfig::KEYSTROKE);
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "abc").unwrap();
+    /// for i in (0..3).rev() {
+    ///     text.delete(i, 1).unwrap();
+    /// }
+    /// doc.commit();
+    /// let json = doc.export_json_updates(&VersionVector::default(), &doc.oplog_vv());
+    /// // One insertion and three deletions
+    /// assert_eq!(json.changes[0].ops.len(), 4);
+    /// ```
     #[inline]
-    pub fn import_batch(&self, bytes: &[Vec<u8>]) -> LoroResult<ImportStatus> {
-        self.doc.import_batch(bytes)
+    pub fn set_delete_merge_config(&self, config: DeleteMergeConfig) {
+        self.doc.set_delete_merge_config(config);
     }
 
-    /// Get a [LoroMovableList] by container id.
+    /// Set whether the text events report the ids of the changed paragraphs in
+    /// [`ContainerDiff::paragraph_changed`](crate::event::ContainerDiff::paragraph_changed),
+    /// so that block-based renderers can re-render only the affected paragraphs.
     ///
-    /// If the provided id is string, it will be converted into a root container id with the name of the string.
+    /// It's disabled by default, because finding the paragraphs needs to scan the text
+    /// before the changed ranges. See [`ParagraphId`] for how paragraphs are identified.
     #[inline]
-    pub fn get_movable_list<I: IntoContainerId>(&self, id: I) -> LoroMovableList {
-        LoroMovableList {
-            handler: self.doc.get_movable_list(id),
-        }
+    pub fn set_text_paragraph_ids(&self, enabled: bool) {
+        self.doc.set_text_paragraph_ids(enabled);
     }
 
-    /// Get a [LoroList] by container id.
+    /// Set whether the local ops can write [`LoroValue::ContainerRef`], [`LoroValue::Timestamp`]
+    /// and [`LoroValue::Decimal`] values.
     ///
-    /// If the provided id is string, it will be converted into a root container id with the name of the string.
+    /// It's disabled by default, and writing such a value fails with
+    /// [`LoroError::ExtendedValueTypeDisabled`], because the versions of Loro released
+    /// before the value types were added can't decode the updates and the snapshots that
+    /// contain them. Only enable it when all the peers of the doc support it. The binary
+    /// updates and snapshots are imported either way, but the JSON updates with such values
+    /// are rejected unless it's enabled.
+    ///
+    /// Once enabled, the local ops parse the single-entry tagged objects, e.g.
+    /// `{"🦜ts": 1700000000000}`, as the values they represent, see [`LoroValue::parse_tags`].
     #[inline]
-    pub fn get_list<I: IntoContainerId>(&self, id: I) -> LoroList {
-        LoroList {
-            handler: self.doc.get_list(id),
-        }
+    pub fn set_extended_value_types(&self, enabled: bool) {
+        self.doc.set_extended_value_types(enabled);
     }
 
-    /// Get a [LoroMap] by container id.
+    /// Set whether the snapshots exported by the doc store the checksums of the container
+    /// states, which are checked by [`LoroDoc::verify_container`] and
+    /// [`LoroDoc::import_with_verification`].
     ///
-    /// If the provided id is string, it will be converted into a root container id with the name of the string.
+    /// It's disabled by default, because computing the checksums decodes the state of every
+    /// alive container and slows down the export. The checksums in the imported snapshots
+    /// are loaded either way.
     #[inline]
-    pub fn get_map<I: IntoContainerId>(&self, id: I) -> LoroMap {
-        LoroMap {
-            handler: self.doc.get_map(id),
-        }
+    pub fn set_snapshot_checksums(&self, enabled: bool) {
+        self.doc.set_snapshot_checksums(enabled);
     }
 
-    /// Get a [LoroText] by container id.
+    /// Enable or disable the single-writer mode, which is disabled by default.
     ///
-    /// If the provided id is string, it will be converted into a root container id with the name of the string.
+    /// In this mode, the doc only accepts the ops of the peer whose ops are in the history:
+    /// the local ops and the imports that would add the ops of another peer fail with
+    /// [`LoroError::SingleWriterViolation`]. A version of such a doc is identified by the
+    /// number of ops of the writer, see [`LoroDoc::seq`].
+    ///
+    /// It's only a guard on the history. There is no single-writer encoding: the updates and
+    /// the snapshots still carry a peer table, and the versions in them are still version
+    /// vectors and frontiers, which only have one entry for a single-peer history.
+    ///
+    /// It returns an error if the history already has the ops of several peers.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{ExportMode, LoroDoc, LoroError};
+    ///
+    /// let server = LoroDoc::new();
+    /// server.set_peer_id(1).unwrap();
+    /// server.set_single_writer(true).unwrap();
+    /// server.get_text("text").insert(0, "hello").unwrap();
+    /// server.commit();
+    ///
+    /// let client = LoroDoc::new();
+    /// client.set_peer_id(2).unwrap();
+    /// client.set_single_writer(true).unwrap();
+    /// client.import(&server.export(ExportMode::all_updates()).unwrap()).unwrap();
+    /// assert_eq!(client.seq(), 5);
+    /// assert_eq!(
+    ///     client.get_text("text").insert(0, "x"),
+    ///     Err(LoroError::SingleWriterViolation { writer: 1, peer: 2 })
+    /// );
+    /// ```
     #[inline]
-    pub fn get_text<I: IntoContainerId>(&self, id: I) -> LoroText {
-        LoroText {
-            handler: self.doc.get_text(id),
-        }
+    pub fn set_single_writer(&self, enabled: bool) -> LoroResult<()> {
+        self.doc.set_single_writer(enabled)
     }
 
-    /// Get a [LoroTree] by container id.
-    ///
-    /// If the provided id is string, it will be converted into a root container id with the name of the string.
+    /// Whether the doc is in the single-writer mode
     #[inline]
-    pub fn get_tree<I: IntoContainerId>(&self, id: I) -> LoroTree {
-        LoroTree {
-            handler: self.doc.get_tree(id),
-        }
+    pub fn is_single_writer(&self) -> bool {
+        self.doc.is_single_writer()
     }
 
-    #[cfg(feature = "counter")]
-    /// Get a [LoroCounter] by container id.
-    ///
-    /// If the provided id is string, it will be converted into a root container id with the name of the string.
+    /// The peer of the ops in the history of a single-writer doc, or `None` if the history
+    /// is empty.
     #[inline]
-    pub fn get_counter<I: IntoContainerId>(&self, id: I) -> LoroCounter {
-        LoroCounter {
-            handler: self.doc.get_counter(id),
-        }
+    pub fn writer(&self) -> Option<PeerID> {
+        self.doc.writer()
     }
 
-    /// Commit the cumulative auto commit transaction.
+    /// The number of ops in the history, including the pending ones.
     ///
-    /// There is a transaction behind every operation.
-    /// The events will be emitted after a transaction is committed. A transaction is committed when:
-    ///
-    /// - `doc.commit()` is called.
-    /// - `doc.export(mode)` is called.
-    /// - `doc.import(data)` is called.
-    /// - `doc.checkout(version)` is called.
+    /// In the single-writer mode, it identifies the version of the doc: the doc at `seq` has
+    /// the first `seq` ops of the writer.
     #[inline]
-    pub fn commit(&self) {
-        self.doc.commit_then_renew();
+    pub fn seq(&self) -> Counter {
+        self.doc.seq()
     }
 
-    /// Commit the cumulative auto commit transaction with custom configure.
+    /// The frontiers of the version that has the first `seq` ops of the writer.
     ///
-    /// There is a transaction behind every operation.
-    /// It will automatically commit when users invoke export or import.
-    /// The event will be sent after a transaction is committed
+    /// It returns an error if the doc is not in the single-writer mode, or if the doc doesn't
+    /// have `seq` ops.
     #[inline]
-    pub fn commit_with(&self, options: CommitOptions) {
-        self.doc.commit_with(options);
+    pub fn frontiers_at_seq(&self, seq: Counter) -> LoroResult<Frontiers> {
+        self.doc.frontiers_at_seq(seq)
     }
 
-    /// Set commit message for the current uncommitted changes
+    /// Freeze the doc permanently. The pending txn is committed with the freeze.
     ///
-    /// It will be persisted.
-    pub fn set_next_commit_message(&self, msg: &str) {
-        self.doc.set_next_commit_message(msg)
-    }
-
-    /// Set `origin` for the current uncommitted changes, it can be used to track the source of changes in an event.
+    /// The freeze is an op in the reserved root map [`FROZEN_ROOT_NAME`], so it's synced to
+    /// the other replicas. This doc and the replicas that import it reject the local edits
+    /// with [`LoroError::DocFrozen`], but the edits made concurrently with the freeze are
+    /// still merged when they are imported. The freeze can't be undone: deleting or
+    /// overwriting the reason in the map doesn't unfreeze the doc. The map is hidden from
+    /// the values of the doc.
     ///
-    /// It will NOT be persisted.
-    pub fn set_next_commit_origin(&self, origin: &str) {
-        self.doc.set_next_commit_origin(origin)
-    }
-
-    /// Set the timestamp of the next commit.
+    /// # Example
+    /// ```
+    /// use loro::{ExportMode, LoroDoc, LoroError};
     ///
-    /// It will be persisted and stored in the `OpLog`.
-    /// You can get the timestamp from the [`Change`] type.
-    pub fn set_next_commit_timestamp(&self, timestamp: Timestamp) {
-        self.doc.set_next_commit_timestamp(timestamp)
-    }
-
-    /// Set the options of the next commit.
+    /// let doc = LoroDoc::new();
+    /// doc.get_text("text").insert(0, "v1.0").unwrap();
+    /// doc.freeze("published").unwrap();
+    /// assert_eq!(doc.frozen_reason(), Some("published".to_string()));
     ///
-    /// It will be used when the next commit is performed.
-    pub fn set_next_commit_options(&self, options: CommitOptions) {
-        self.doc.set_next_commit_options(options);
+    /// let replica = LoroDoc::new();
+    /// replica.import(&doc.export(ExportMode::all_updates()).unwrap()).unwrap();
+    /// assert_eq!(
+    ///     replica.get_text("text").insert(0, "x"),
+    ///     Err(LoroError::DocFrozen("published".into()))
+    /// );
+    /// ```
+    #[inline]
+    pub fn freeze(&self, reason: &str) -> LoroResult<()> {
+        self.doc.freeze(reason)
     }
 
-    /// Clear the options of the next commit.
-    pub fn clear_next_commit_options(&self) {
-        self.doc.clear_next_commit_options();
+    /// Whether the doc has been frozen by [`LoroDoc::freeze`]
+    #[inline]
+    pub fn is_frozen(&self) -> bool {
+        self.doc.is_frozen()
     }
 
-    /// Whether the document is in detached mode, where the [loro_internal::DocState] is not
-    /// synchronized with the latest version of the [loro_internal::OpLog].
+    /// The reason the doc was frozen with, or `None` if it's not frozen
     #[inline]
-    pub fn is_detached(&self) -> bool {
-        self.doc.is_detached()
+    pub fn frozen_reason(&self) -> Option<String> {
+        self.doc.frozen_reason()
     }
 
-    /// Import updates/snapshot exported by [`LoroDoc::export_snapshot`] or [`LoroDoc::export_from`].
+    /// Make the internal randomness of the doc deterministic by deriving it from `seed`.
+    ///
+    /// It covers the peer ids generated by the doc afterwards, e.g. by [`LoroDoc::fork`] or
+    /// by editing in detached mode, and the jitter of the fractional indexes of trees
+    /// (see [`LoroTree::enable_fractional_index`]). So replaying the same edits produces
+    /// byte-identical encodings. The current peer id is not changed.
+    ///
+    /// With `None`, which is the default, the peer ids are generated by the system's secure
+    /// random generator, and the jitter uses the seed 0.
     #[inline]
-    pub fn import(&self, bytes: &[u8]) -> Result<ImportStatus, LoroError> {
-        self.doc.import_with(bytes, "".into())
+    pub fn set_random_seed(&self, seed: Option<u64>) {
+        self.doc.set_random_seed(seed);
     }
 
-    /// Import updates/snapshot exported by [`LoroDoc::export_snapshot`] or [`LoroDoc::export_from`].
+    /// Switch the doc to a new random peer id and return it.
     ///
-    /// It marks the import with a custom `origin` string. It can be used to track the import source
-    /// in the generated events.
+    /// The pending changes are committed with the old peer id, and the following changes
+    /// are made by the new peer from counter 0. The doc stays open and the containers stay
+    /// valid, so it can be called periodically to avoid exhausting the counters of a peer
+    /// or to unlink the future edits from the old peer id.
+    ///
+    /// The first change made by the new peer records the old peer id in its commit message,
+    /// which can be read back by [`LoroDoc::get_peer_rotations`]. Setting another commit
+    /// message for that change drops the record.
+    ///
+    /// It fails inside [`LoroDoc::batch`].
+    ///
+    /// # Example
+    /// ```
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.set_peer_id(1).unwrap();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello").unwrap();
+    /// let new_peer = doc.rotate_peer_id().unwrap();
+    /// text.insert(5, " world").unwrap();
+    /// doc.commit();
+    /// assert_eq!(doc.peer_id(), new_peer);
+    /// assert_eq!(doc.get_peer_rotations(), vec![(1, new_peer)]);
+    /// ```
     #[inline]
-    pub fn import_with(&self, bytes: &[u8], origin: &str) -> Result<ImportStatus, LoroError> {
-        self.doc.import_with(bytes, origin.into())
+    pub fn rotate_peer_id(&self) -> LoroResult<PeerID> {
+        self.doc.rotate_peer_id()
     }
 
-    /// Import the json schema updates.
-    ///
-    /// only supports backward compatibility but not forward compatibility.
+    /// Get the `(old, new)` pairs of the peer ids linked by [`LoroDoc::rotate_peer_id`]
+    /// in the history.
     #[inline]
-    pub fn import_json_updates<T: TryInto<JsonSchema>>(
-        &self,
-        json: T,
-    ) -> Result<ImportStatus, LoroError> {
-        self.doc.import_json_updates(json)
+    pub fn get_peer_rotations(&self) -> Vec<(PeerID, PeerID)> {
+        self.doc.get_peer_rotations()
     }
 
-    /// Export the current state with json-string format of the document.
+    /// Create a copy of the document whose peers are renamed to `0..n` in their order, and
+    /// return it with the table of the original peers.
+    ///
+    /// It shrinks the version vectors, the frontiers and the ids of the documents edited by
+    /// many short-lived peers. The state stays the same, but the history is new: every replica
+    /// has to switch to the compacted document, and the [`PeerMapping`] translates the
+    /// references kept outside of it, e.g. cursors or stored versions.
+    ///
+    /// It returns an error for a shallow document.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{ExportMode, LoroDoc};
+    ///
+    /// let a = LoroDoc::new();
+    /// a.set_peer_id(u64::MAX - 1).unwrap();
+    /// a.get_text("text").insert(0, "Hello").unwrap();
+    /// let b = LoroDoc::new();
+    /// b.set_peer_id(1 << 50).unwrap();
+    /// b.import(&a.export(ExportMode::all_updates()).unwrap()).unwrap();
+    /// b.get_text("text").insert(5, " world").unwrap();
+    ///
+    /// let (compacted, mapping) = b.compact_peers().unwrap();
+    /// assert_eq!(compacted.get_deep_value(), b.get_deep_value());
+    /// assert_eq!(mapping.original_peers(), &[1 << 50, u64::MAX - 1]);
+    /// assert_eq!(
+    ///     compacted.oplog_frontiers(),
+    ///     mapping.translate_frontiers(&b.oplog_frontiers()).unwrap()
+    /// );
+    /// ```
     #[inline]
-    pub fn export_json_updates(
-        &self,
-        start_vv: &VersionVector,
-        end_vv: &VersionVector,
-    ) -> JsonSchema {
-        self.doc.export_json_updates(start_vv, end_vv, true)
+    pub fn compact_peers(&self) -> LoroResult<(LoroDoc, PeerMapping)> {
+        let (doc, mapping) = self.doc.compact_peers()?;
+        Ok((Self::_new(doc), mapping))
     }
 
-    /// Export the current state with json-string format of the document, without peer compression.
+    /// List the ops created by the given peers in the changes whose timestamps are in
+    /// `range`, with readable descriptions of their content, e.g. to answer a data-subject
+    /// access request.
     ///
-    /// Compared to [`export_json_updates`], this method does not compress the peer IDs in the updates.
-    /// So the operations are easier to be processed by application code.
+    /// The report can be rendered with [`AuthorReport::to_json`]. The ops before the shallow
+    /// root of a shallow document are not listed.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.set_peer_id(1).unwrap();
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// let report = doc.export_ops_by_peer(&[1], ..);
+    /// assert_eq!(report.ops.len(), 1);
+    /// assert_eq!(report.ops[0].content, "insert \"Hello\" at 0");
+    /// ```
     #[inline]
-    pub fn export_json_updates_without_peer_compression(
+    pub fn export_ops_by_peer(
         &self,
-        start_vv: &VersionVector,
-        end_vv: &VersionVector,
-    ) -> JsonSchema {
-        self.doc.export_json_updates(start_vv, end_vv, false)
+        peers: &[PeerID],
+        range: impl RangeBounds<Timestamp>,
+    ) -> AuthorReport {
+        self.doc.export_ops_by_peer(peers, range)
     }
 
-    /// Export the readable [`Change`]s in the given [`IdSpan`]
-    pub fn export_json_in_id_span(&self, id_span: IdSpan) -> Vec<JsonChange> {
-        self.doc.export_json_in_id_span(id_span)
+    /// Create a copy of the document whose ops created by `peer` have their content replaced
+    /// by placeholders, and whose changes by `peer` have no commit messages.
+    ///
+    /// The history keeps its structure, so the copy syncs with the other redacted replicas,
+    /// but the replicas that aren't redacted still have the content. It returns an error for
+    /// a shallow document.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.set_peer_id(1).unwrap();
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// let redacted = doc.redact_peer(1).unwrap();
+    /// assert_eq!(redacted.get_text("text").to_string(), "\u{FFFD}".repeat(5));
+    /// ```
+    #[inline]
+    pub fn redact_peer(&self, peer: PeerID) -> LoroResult<LoroDoc> {
+        self.doc.redact_peer(peer).map(Self::_new)
     }
 
-    /// Export all the ops not included in the given `VersionVector`
-    #[deprecated(
-        since = "1.0.0",
-        note = "Use `export` with `ExportMode::Updates` instead"
-    )]
+    /// Enable or disable recording the ops that had no effect when they were applied,
+    /// e.g. the duplicated imports or the map values overridden by concurrent edits.
+    ///
+    /// It helps to find out why a change didn't show up. The entries are read by
+    /// [`LoroDoc::take_apply_log`]. Disabling it drops the recorded entries.
     #[inline]
-    pub fn export_from(&self, vv: &VersionVector) -> Vec<u8> {
-        self.doc.export_from(vv)
+    pub fn set_record_apply_log(&self, enabled: bool) {
+        self.doc.set_record_apply_log(enabled);
     }
 
-    /// Export the current state and history of the document.
-    #[deprecated(
-        since = "1.0.0",
-        note = "Use `export` with `ExportMode::Snapshot` instead"
-    )]
+    /// Take the ops that had no effect since the last call.
+    ///
+    /// It's always empty unless [`LoroDoc::set_record_apply_log`] is enabled.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{ExportMode, IgnoredOp, LoroDoc};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.get_map("map").insert("key", 1).unwrap();
+    /// doc.commit();
+    /// let updates = doc.export(ExportMode::all_updates()).unwrap();
+    ///
+    /// doc.set_record_apply_log(true);
+    /// doc.import(&updates).unwrap();
+    /// assert!(matches!(
+    ///     doc.take_apply_log().as_slice(),
+    ///     [IgnoredOp::Duplicate { .. }]
+    /// ));
+    /// ```
     #[inline]
-    pub fn export_snapshot(&self) -> Vec<u8> {
-        self.doc.export_snapshot().unwrap()
+    pub fn take_apply_log(&self) -> Vec<IgnoredOp> {
+        self.doc.take_apply_log()
     }
 
-    /// Convert `Frontiers` into `VersionVector`
+    /// Explain the op `id` for debugging a merge: its deps and lamport, the concurrent ops on
+    /// the same container, and how the container resolved it in the current state.
+    ///
+    /// The explanation can be rendered as text with `to_string()` or as JSON with
+    /// [`OpExplanation::to_json`]. It scans the whole history. Returns `None` if the op is
+    /// not in the doc.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, OpOutcome, ID};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.set_peer_id(1).unwrap();
+    /// doc.get_map("map").insert("key", 1).unwrap();
+    /// doc.commit();
+    /// let explanation = doc.explain(ID::new(1, 0)).unwrap();
+    /// assert!(matches!(
+    ///     explanation.outcome,
+    ///     OpOutcome::LastWriterWins { won: true, .. }
+    /// ));
+    /// ```
     #[inline]
-    pub fn frontiers_to_vv(&self, frontiers: &Frontiers) -> Option<VersionVector> {
-        self.doc.frontiers_to_vv(frontiers)
+    pub fn explain(&self, id: ID) -> Option<OpExplanation> {
+        self.doc.explain(id)
     }
 
-    /// Minimize the frontiers by removing the unnecessary entries.
-    pub fn minimize_frontiers(&self, frontiers: &Frontiers) -> Result<Frontiers, ID> {
-        self.with_oplog(|oplog| shrink_frontiers(frontiers, oplog.dag()))
+    /// Compute a canonical digest of the state at `frontiers`.
+    ///
+    /// Every replica that has the version gets the same digest, no matter how it received the
+    /// history, so replicas can exchange their digests after syncing to detect divergence.
+    /// Check a digest from another replica with [`LoroDoc::verify_convergence`].
+    ///
+    /// It fails if the doc doesn't have the version.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{ConvergenceDigest, ExportMode, LoroDoc};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// let digest = doc.convergence_digest(&doc.oplog_frontiers()).unwrap();
+    ///
+    /// let replica = LoroDoc::new();
+    /// replica.import(&doc.export(ExportMode::all_updates()).unwrap()).unwrap();
+    /// let digest = ConvergenceDigest::decode(&digest.encode()).unwrap();
+    /// assert!(replica.verify_convergence(&digest).unwrap().is_none());
+    /// ```
+    #[inline]
+    pub fn convergence_digest(&self, frontiers: &Frontiers) -> LoroResult<ConvergenceDigest> {
+        self.doc.convergence_digest(frontiers)
     }
 
-    /// Convert `VersionVector` into `Frontiers`
+    /// Check the state of this doc against a digest computed by another replica with
+    /// [`LoroDoc::convergence_digest`].
+    ///
+    /// Returns `None` if the states match. Otherwise it returns a [`DivergenceBundle`] with
+    /// the state of this doc and its updates, which can be attached to a bug report.
+    /// It fails if this doc doesn't have the version of the digest.
     #[inline]
-    pub fn vv_to_frontiers(&self, vv: &VersionVector) -> Frontiers {
-        self.doc.vv_to_frontiers(vv)
+    pub fn verify_convergence(
+        &self,
+        digest: &ConvergenceDigest,
+    ) -> LoroResult<Option<DivergenceBundle>> {
+        self.doc.verify_convergence(digest)
     }
 
-    /// Access the `OpLog`.
+    /// Take a read-only copy of the current state, which can be read on another thread.
     ///
-    /// NOTE: Please be ware that the API in `OpLog` is unstable
+    /// The copy shares the encoded container states with the doc instead of cloning them, and
+    /// reading it doesn't lock the doc. So a background task can export a consistent state while
+    /// the doc keeps being edited. The pending transaction is committed first.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, ToJson};
+    /// use serde_json::json;
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// let snapshot = doc.snapshot_state();
+    /// doc.get_text("text").insert(5, " world").unwrap();
+    /// let value = std::thread::spawn(move || snapshot.get_deep_value())
+    ///     .join()
+    ///     .unwrap();
+    /// assert_eq!(value.to_json_value(), json!({"text": "Hello"}));
+    /// ```
     #[inline]
-    pub fn with_oplog<R>(&self, f: impl FnOnce(&OpLog) -> R) -> R {
-        let oplog = self.doc.oplog().lock().unwrap();
-        f(&oplog)
+    pub fn snapshot_state(&self) -> StateSnapshot {
+        self.doc.snapshot_state()
     }
 
-    /// Access the `DocState`.
+    /// Get a read-only copy of the state at the given version, without checking out the doc.
     ///
-    /// NOTE: Please be ware that the API in `DocState` is unstable
+    /// Unlike [`LoroDoc::checkout`], the doc keeps its state and stays attached, so it can
+    /// still be edited, and it doesn't need to be checked back out. Reading the copy doesn't
+    /// lock the doc. The pending transaction is committed first.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, ToJson};
+    /// use serde_json::json;
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// doc.commit();
+    /// let version = doc.state_frontiers();
+    /// doc.get_text("text").insert(5, " world").unwrap();
+    /// let view = doc.checkout_to(&version).unwrap();
+    /// assert_eq!(view.get_text("text").unwrap(), "Hello");
+    /// assert_eq!(view.get_deep_value().to_json_value(), json!({"text": "Hello"}));
+    /// assert!(!doc.is_detached());
+    /// assert_eq!(doc.get_text("text").to_string(), "Hello world");
+    /// ```
     #[inline]
-    pub fn with_state<R>(&self, f: impl FnOnce(&mut DocState) -> R) -> R {
-        let mut state = self.doc.app_state().lock().unwrap();
-        f(&mut state)
+    pub fn checkout_to(&self, frontiers: &Frontiers) -> LoroResult<StateSnapshot> {
+        self.doc.checkout_to(frontiers)
     }
 
-    /// Get the `VersionVector` version of `OpLog`
+    /// Get the container type conflicts quarantined by the imports.
+    ///
+    /// It's always empty unless the policy is [`ContainerTypeConflictPolicy::Quarantine`].
     #[inline]
-    pub fn oplog_vv(&self) -> VersionVector {
-        self.doc.oplog_vv()
+    pub fn container_type_conflicts(&self) -> Vec<ContainerTypeConflict> {
+        self.doc.container_type_conflicts()
     }
 
-    /// Get the `VersionVector` version of `DocState`
+    /// Emit the events held back by the import event coalescing window.
+    ///
+    /// See [`LoroDoc::set_import_event_coalescing_window`].
     #[inline]
-    pub fn state_vv(&self) -> VersionVector {
-        self.doc.state_vv()
+    pub fn flush_pending_events(&self) {
+        self.doc.flush_pending_events();
     }
 
-    /// The doc only contains the history since this version
+    /// Set the rich text format configuration of the document.
     ///
-    /// This is empty if the doc is not shallow.
+    /// You need to config it if you use rich text `mark` method.
+    /// Specifically, you need to config the `expand` property of each style.
     ///
-    /// The ops included by the shallow history start version vector are not in the doc.
+    /// Expand is used to specify the behavior of expanding when new text is inserted at the
+    /// beginning or end of the style.
+    ///
+    /// A style can also restrict the type of its values with [`StyleConfig::value_type`], and
+    /// `mark` fails on the unknown keys and the values of other types. The styles imported
+    /// from other peers are handled by the [`StyleImportPolicy`] of the config.
     #[inline]
-    pub fn shallow_since_vv(&self) -> ImVersionVector {
-        self.doc.shallow_since_vv()
+    pub fn config_text_style(&self, text_style: StyleConfigMap) {
+        self.doc.config_text_style(text_style)
     }
 
-    /// The doc only contains the history since this version
+    /// Take the imported styles that didn't match the style config, if its
+    /// [`StyleImportPolicy`] is [`StyleImportPolicy::Flag`].
+    #[inline]
+    pub fn take_flagged_styles(&self) -> Vec<InvalidStyle> {
+        self.doc.take_flagged_styles()
+    }
+
+    /// Configures the default text style for the document.
     ///
-    /// This is empty if the doc is not shallow.
+    /// This method sets the default text style configuration for the document when using LoroText.
+    /// If `None` is provided, the default style is reset.
     ///
-    /// The ops included by the shallow history start frontiers are not in the doc.
+    /// # Parameters
+    ///
+    /// - `text_style`: The style configuration to set as the default. `None` to reset.
+    pub fn config_default_text_style(&self, text_style: Option<StyleConfig>) {
+        self.doc.config_default_text_style(text_style);
+    }
+
+    /// Attach the document state to the latest known version.
+    ///
+    /// > The document becomes detached during a `checkout` operation.
+    /// > Being `detached` implies that the `DocState` is not synchronized with the latest version of the `OpLog`.
+    /// > In a detached state, the document is not editable, and any `import` operations will be
+    /// > recorded in the `OpLog` without being applied to the `DocState`.
     #[inline]
-    pub fn shallow_since_frontiers(&self) -> Frontiers {
-        self.doc.shallow_since_frontiers()
+    pub fn attach(&self) {
+        self.doc.attach()
     }
 
-    /// Get the total number of operations in the `OpLog`
+    /// Checkout the `DocState` to a specific version.
+    ///
+    /// The document becomes detached during a `checkout` operation.
+    /// Being `detached` implies that the `DocState` is not synchronized with the latest version of the `OpLog`.
+    /// In a detached state, the document is not editable, and any `import` operations will be
+    /// recorded in the `OpLog` without being applied to the `DocState`.
+    ///
+    /// You should call `attach` to attach the `DocState` to the latest version of `OpLog`.
     #[inline]
-    pub fn len_ops(&self) -> usize {
-        self.doc.len_ops()
+    pub fn checkout(&self, frontiers: &Frontiers) -> LoroResult<()> {
+        self.doc.checkout(frontiers)
     }
 
-    /// Get the total number of changes in the `OpLog`
+    /// Checkout the `DocState` to the latest version.
+    ///
+    /// > The document becomes detached during a `checkout` operation.
+    /// > Being `detached` implies that the `DocState` is not synchronized with the latest version of the `OpLog`.
+    /// > In a detached state, the document is not editable, and any `import` operations will be
+    /// > recorded in the `OpLog` without being applied to the `DocState`.
+    ///
+    /// This has the same effect as `attach`.
     #[inline]
-    pub fn len_changes(&self) -> usize {
-        self.doc.len_changes()
+    pub fn checkout_to_latest(&self) {
+        self.doc.checkout_to_latest()
     }
 
-    /// Get the shallow value of the document.
+    /// Checkout only the given containers to a specific version.
+    ///
+    /// The other containers stay at the current version of the `DocState`. It's useful for
+    /// previewing, e.g. comparing one chapter across versions without checking out the whole book.
+    ///
+    /// The document becomes detached. Calling `checkout`, `checkout_to_latest` or `attach`
+    /// brings all the containers back to the same version.
+    ///
+    /// It returns an error if detached editing is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::LoroDoc;
+    /// let doc = LoroDoc::new();
+    /// let a = doc.get_text("a");
+    /// let b = doc.get_text("b");
+    /// a.insert(0, "Hello").unwrap();
+    /// b.insert(0, "Hi").unwrap();
+    /// doc.commit();
+    /// let v = doc.oplog_frontiers();
+    /// a.insert(5, " World").unwrap();
+    /// b.insert(2, "!").unwrap();
+    /// doc.commit();
+    ///
+    /// doc.checkout_containers(&v, &[a.id()]).unwrap();
+    /// assert_eq!(a.to_string(), "Hello");
+    /// assert_eq!(b.to_string(), "Hi!");
+    /// assert_eq!(doc.get_container_version(&a.id()), v);
+    ///
+    /// doc.checkout_to_latest();
+    /// assert_eq!(a.to_string(), "Hello World");
+    /// ```
     #[inline]
-    pub fn get_value(&self) -> LoroValue {
-        self.doc.get_value()
+    pub fn checkout_containers(
+        &self,
+        frontiers: &Frontiers,
+        containers: &[ContainerID],
+    ) -> LoroResult<()> {
+        self.doc.checkout_containers(frontiers, containers)
     }
 
-    /// Get the entire state of the current DocState
+    /// Schedule a checkout to `frontiers`, which is carried out in steps by
+    /// [`LoroDoc::poll_pending_work`].
+    ///
+    /// The document becomes detached immediately. The work scheduled before is replaced.
     #[inline]
-    pub fn get_deep_value(&self) -> LoroValue {
-        self.doc.get_deep_value()
+    pub fn checkout_in_slices(&self, frontiers: &Frontiers) -> LoroResult<()> {
+        self.doc.checkout_in_slices(frontiers)
     }
 
-    /// Get the entire state of the current DocState with container id
-    pub fn get_deep_value_with_id(&self) -> LoroValue {
-        self.doc
-            .app_state()
-            .lock()
-            .unwrap()
-            .get_deep_value_with_id()
+    /// Import updates into the `OpLog`, and schedule applying them to the `DocState`, which is
+    /// carried out in steps by [`LoroDoc::poll_pending_work`].
+    ///
+    /// It lets single-threaded hosts, e.g. the main thread of a web page, interleave a long
+    /// merge with other work. The document stays detached until the work is complete, and the
+    /// changes are reported by the events of the steps, which are triggered by a checkout.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::{LoroDoc, PollStatus};
+    /// let a = LoroDoc::new();
+    /// a.get_text("text").insert(0, "Hello world").unwrap();
+    /// a.commit();
+    ///
+    /// let b = LoroDoc::new();
+    /// b.import_in_slices(&a.export(loro::ExportMode::all_updates()).unwrap()).unwrap();
+    /// while b.poll_pending_work(4).unwrap() == PollStatus::Pending {
+    ///     // Render a frame
+    /// }
+    /// assert_eq!(b.get_text("text").to_string(), "Hello world");
+    /// assert!(!b.is_detached());
+    /// ```
+    #[inline]
+    pub fn import_in_slices(&self, bytes: &[u8]) -> Result<ImportStatus, LoroError> {
+        self.doc.import_in_slices(bytes)
     }
 
-    /// Get the `Frontiers` version of `OpLog`
+    /// Whether there is work scheduled by [`LoroDoc::checkout_in_slices`] or
+    /// [`LoroDoc::import_in_slices`].
     #[inline]
-    pub fn oplog_frontiers(&self) -> Frontiers {
-        self.doc.oplog_frontiers()
+    pub fn has_pending_work(&self) -> bool {
+        self.doc.has_pending_work()
     }
 
-    /// Get the `Frontiers` version of `DocState`
+    /// Move the `DocState` toward the target of the scheduled work by at most `budget` ops,
+    /// emitting the events of the step.
     ///
-    /// Learn more about [`Frontiers`](https://loro.dev/docs/advanced/version_deep_dive)
+    /// Returns [`PollStatus::Complete`] once the target is reached or if there is nothing to do.
     #[inline]
-    pub fn state_frontiers(&self) -> Frontiers {
-        self.doc.state_frontiers()
+    pub fn poll_pending_work(&self, budget: usize) -> LoroResult<PollStatus> {
+        self.doc.poll_pending_work(budget)
     }
 
-    /// Get the PeerID
+    /// Get the version that the state of the given container reflects.
+    ///
+    /// It's the same as `state_frontiers()` unless the container is checked out by
+    /// [`LoroDoc::checkout_containers`].
     #[inline]
-    pub fn peer_id(&self) -> PeerID {
-        self.doc.peer_id()
+    pub fn get_container_version(&self, id: &ContainerID) -> Frontiers {
+        self.doc.get_container_version(id)
     }
 
-    /// Change the PeerID
-    ///
-    /// NOTE: You need to make sure there is no chance two peer have the same PeerID.
-    /// If it happens, the document will be corrupted.
+    /// Whether some of the containers are checked out by [`LoroDoc::checkout_containers`].
     #[inline]
-    pub fn set_peer_id(&self, peer: PeerID) -> LoroResult<()> {
-        self.doc.set_peer_id(peer)
+    pub fn is_partially_checked_out(&self) -> bool {
+        self.doc.is_partially_checked_out()
     }
 
-    /// Subscribe the events of a container.
+    /// Compare the frontiers with the current OpLog's version.
     ///
-    /// The callback will be invoked after a transaction that change the container.
-    /// Returns a subscription that can be used to unsubscribe.
+    /// If `other` contains any version that's not contained in the current OpLog, return [Ordering::Less].
+    #[inline]
+    pub fn cmp_with_frontiers(&self, other: &Frontiers) -> Ordering {
+        self.doc.cmp_with_frontiers(other)
+    }
+
+    /// Compare two frontiers.
+    ///
+    /// If the frontiers are not included in the document, return [`FrontiersNotIncluded`].
+    #[inline]
+    pub fn cmp_frontiers(
+        &self,
+        a: &Frontiers,
+        b: &Frontiers,
+    ) -> Result<Option<Ordering>, FrontiersNotIncluded> {
+        self.doc.cmp_frontiers(a, b)
+    }
+
+    /// Get the version that includes the ops of both `a` and `b`.
+    ///
+    /// The result is the minimal frontiers of that version.
+    ///
+    /// If the frontiers are not included in the document, return [`FrontiersNotIncluded`].
+    #[inline]
+    pub fn frontiers_union(
+        &self,
+        a: &Frontiers,
+        b: &Frontiers,
+    ) -> Result<Frontiers, FrontiersNotIncluded> {
+        self.doc.frontiers_union(a, b)
+    }
+
+    /// Get the version that only includes the ops included by both `a` and `b`.
+    ///
+    /// If the frontiers are not included in the document, return [`FrontiersNotIncluded`].
+    #[inline]
+    pub fn frontiers_intersection(
+        &self,
+        a: &Frontiers,
+        b: &Frontiers,
+    ) -> Result<Frontiers, FrontiersNotIncluded> {
+        self.doc.frontiers_intersection(a, b)
+    }
+
+    /// Find the greatest common ancestor of `a` and `b`, i.e. the version where the two
+    /// branches forked.
+    ///
+    /// If the frontiers are not included in the document, return [`FrontiersNotIncluded`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.set_peer_id(1).unwrap();
+    /// doc.get_text("text").insert(0, "base").unwrap();
+    /// doc.commit();
+    /// let base = doc.oplog_frontiers();
+    ///
+    /// let fork = doc.fork();
+    /// fork.set_peer_id(2).unwrap();
+    /// fork.get_text("text").insert(0, "a").unwrap();
+    /// fork.commit();
+    /// doc.get_text("text").insert(0, "b").unwrap();
+    /// doc.commit();
+    /// let a = doc.oplog_frontiers();
+    /// doc.import(&fork.export(loro::ExportMode::all_updates()).unwrap()).unwrap();
+    /// let b = fork.oplog_frontiers();
+    ///
+    /// assert_eq!(doc.find_common_ancestor(&a, &b).unwrap(), base);
+    /// assert_eq!(doc.frontiers_union(&a, &b).unwrap(), doc.oplog_frontiers());
+    /// ```
+    #[inline]
+    pub fn find_common_ancestor(
+        &self,
+        a: &Frontiers,
+        b: &Frontiers,
+    ) -> Result<Frontiers, FrontiersNotIncluded> {
+        self.doc.find_common_ancestor(a, b)
+    }
+
+    /// Force the document enter the detached mode.
+    ///
+    /// In this mode, when you importing new updates, the [loro_internal::DocState] will not be changed.
+    ///
+    /// Learn more at https://loro.dev/docs/advanced/doc_state_and_oplog#attacheddetached-status
+    #[inline]
+    pub fn detach(&self) {
+        self.doc.detach()
+    }
+
+    /// Import a batch of updates/snapshot.
+    ///
+    /// The data can be in arbitrary order. The import result will be the same.
+    ///
+    /// The updates are applied in their causal order in one pass, and a single event is
+    /// emitted for the whole batch. It's much faster than importing many small updates
+    /// one by one.
+    #[inline]
+    pub fn import_batch(&self, bytes: &[Vec<u8>]) -> LoroResult<ImportStatus> {
+        self.doc.import_batch(bytes)
+    }
+
+    /// Get a [LoroMovableList] by container id.
+    ///
+    /// If the provided id is string, it will be converted into a root container id with the name of the string.
+    #[inline]
+    pub fn get_movable_list<I: IntoContainerId>(&self, id: I) -> LoroMovableList {
+        LoroMovableList {
+            handler: self.doc.get_movable_list(id),
+        }
+    }
+
+    /// Get a [LoroList] by container id.
+    ///
+    /// If the provided id is string, it will be converted into a root container id with the name of the string.
+    #[inline]
+    pub fn get_list<I: IntoContainerId>(&self, id: I) -> LoroList {
+        LoroList {
+            handler: self.doc.get_list(id),
+        }
+    }
+
+    /// Get a [LoroMap] by container id.
+    ///
+    /// If the provided id is string, it will be converted into a root container id with the name of the string.
+    #[inline]
+    pub fn get_map<I: IntoContainerId>(&self, id: I) -> LoroMap {
+        LoroMap {
+            handler: self.doc.get_map(id),
+        }
+    }
+
+    /// Get a [LoroText] by container id.
+    ///
+    /// If the provided id is string, it will be converted into a root container id with the name of the string.
+    #[inline]
+    pub fn get_text<I: IntoContainerId>(&self, id: I) -> LoroText {
+        LoroText {
+            handler: self.doc.get_text(id),
+        }
+    }
+
+    /// Get a [LoroTree] by container id.
+    ///
+    /// If the provided id is string, it will be converted into a root container id with the name of the string.
+    #[inline]
+    pub fn get_tree<I: IntoContainerId>(&self, id: I) -> LoroTree {
+        LoroTree {
+            handler: self.doc.get_tree(id),
+        }
+    }
+
+    #[cfg(feature = "counter")]
+    /// Get a [LoroCounter] by container id.
+    ///
+    /// If the provided id is string, it will be converted into a root container id with the name of the string.
+    #[inline]
+    pub fn get_counter<I: IntoContainerId>(&self, id: I) -> LoroCounter {
+        LoroCounter {
+            handler: self.doc.get_counter(id),
+        }
+    }
+
+    /// Commit the cumulative auto commit transaction.
+    ///
+    /// There is a transaction behind every operation.
+    /// The events will be emitted after a transaction is committed. A transaction is committed when:
+    ///
+    /// - `doc.commit()` is called.
+    /// - `doc.export(mode)` is called.
+    /// - `doc.import(data)` is called.
+    /// - `doc.checkout(version)` is called.
+    #[inline]
+    pub fn commit(&self) {
+        self.doc.commit_then_renew();
+    }
+
+    /// Commit the cumulative auto commit transaction with custom configure.
+    ///
+    /// There is a transaction behind every operation.
+    /// It will automatically commit when users invoke export or import.
+    /// The event will be sent after a transaction is committed
+    #[inline]
+    pub fn commit_with(&self, options: CommitOptions) {
+        self.doc.commit_with(options);
+    }
+
+    /// Run `f` as one batch of local changes.
+    ///
+    /// The changes made inside `f` are committed as one change when `f` returns `Ok`, so the
+    /// subscribers receive one event batch no matter how many handler calls or `commit`s
+    /// happen inside it. The pending changes before the batch are committed first.
+    ///
+    /// If `f` returns an error, the uncommitted changes made inside it are rolled back and
+    /// nothing is committed.
+    ///
+    /// The methods that switch the version of the doc, e.g. `import`, `export` and
+    /// `checkout`, still commit the pending changes when called inside the batch, and the
+    /// changes committed by them cannot be rolled back. Nested batches are merged into the
+    /// outermost one.
+    ///
+    /// The child containers created inside a rolled-back batch are emptied, but
+    /// [`LoroDoc::has_container`] still returns `true` for them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::{LoroDoc, LoroError};
+    /// # use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+    /// let doc = LoroDoc::new();
+    /// let list = doc.get_list("list");
+    /// let events = Arc::new(AtomicUsize::new(0));
+    /// let events_clone = events.clone();
+    /// let _sub = doc.subscribe_root(Arc::new(move |_| {
+    ///     events_clone.fetch_add(1, Ordering::SeqCst);
+    /// }));
+    /// doc.batch(|| {
+    ///     for i in 0..10 {
+    ///         list.push(i)?;
+    ///         doc.commit();
+    ///     }
+    ///     Ok(())
+    /// })
+    /// .unwrap();
+    /// assert_eq!(events.load(Ordering::SeqCst), 1);
+    ///
+    /// let ans: Result<(), _> = doc.batch(|| {
+    ///     list.clear()?;
+    ///     Err(LoroError::ArgErr("invalid".into()))
+    /// });
+    /// assert!(ans.is_err());
+    /// assert_eq!(list.len(), 10);
+    /// assert_eq!(events.load(Ordering::SeqCst), 1);
+    /// ```
+    #[inline]
+    pub fn batch<R>(&self, f: impl FnOnce() -> LoroResult<R>) -> LoroResult<R> {
+        self.doc.batch(f)
+    }
+
+    /// Set commit message for the current uncommitted changes
+    ///
+    /// It will be persisted.
+    pub fn set_next_commit_message(&self, msg: &str) {
+        self.doc.set_next_commit_message(msg)
+    }
+
+    /// Set an entry of the key-value metadata of the current uncommitted changes.
+    ///
+    /// It's persisted at the end of the commit message (see [`COMMIT_METADATA_PREFIX`]), and
+    /// can be read by [`ChangeMeta::metadata`].
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, ID};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.set_peer_id(1).unwrap();
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// doc.set_next_commit_metadata("author", "alice");
+    /// doc.set_next_commit_message("Greet");
+    /// doc.commit();
+    ///
+    /// let change = doc.get_change(ID::new(1, 0)).unwrap();
+    /// assert_eq!(change.message_text(), "Greet");
+    /// assert_eq!(change.metadata()["author"], "alice");
+    /// ```
+    #[inline]
+    pub fn set_next_commit_metadata(&self, key: &str, value: &str) {
+        self.doc.set_next_commit_metadata(key, value)
+    }
+
+    /// Set `origin` for the current uncommitted changes, it can be used to track the source of changes in an event.
+    ///
+    /// It will NOT be persisted.
+    pub fn set_next_commit_origin(&self, origin: &str) {
+        self.doc.set_next_commit_origin(origin)
+    }
+
+    /// Set the timestamp of the next commit.
+    ///
+    /// It will be persisted and stored in the `OpLog`.
+    /// You can get the timestamp from the [`Change`] type.
+    pub fn set_next_commit_timestamp(&self, timestamp: Timestamp) {
+        self.doc.set_next_commit_timestamp(timestamp)
+    }
+
+    /// Set the options of the next commit.
+    ///
+    /// It will be used when the next commit is performed.
+    pub fn set_next_commit_options(&self, options: CommitOptions) {
+        self.doc.set_next_commit_options(options);
+    }
+
+    /// Clear the options of the next commit.
+    pub fn clear_next_commit_options(&self) {
+        self.doc.clear_next_commit_options();
+    }
+
+    /// Whether the document is in detached mode, where the [loro_internal::DocState] is not
+    /// synchronized with the latest version of the [loro_internal::OpLog].
+    #[inline]
+    pub fn is_detached(&self) -> bool {
+        self.doc.is_detached()
+    }
+
+    /// Import updates/snapshot exported by [`LoroDoc::export_snapshot`] or [`LoroDoc::export_from`].
+    ///
+    /// A snapshot can be imported into a doc that already has history. Its changes are merged
+    /// like updates then, and [`ImportStatus::snapshot`] tells how it was applied.
+    #[inline]
+    pub fn import(&self, bytes: &[u8]) -> Result<ImportStatus, LoroError> {
+        self.doc.import_with(bytes, "".into())
+    }
+
+    /// Import updates/snapshot exported by [`LoroDoc::export_snapshot`] or [`LoroDoc::export_from`].
+    ///
+    /// It marks the import with a custom `origin` string. It can be used to track the import source
+    /// in the generated events.
+    #[inline]
+    pub fn import_with(&self, bytes: &[u8], origin: &str) -> Result<ImportStatus, LoroError> {
+        self.doc.import_with(bytes, origin.into())
+    }
+
+    /// Import `bytes` through the stages of `pipeline`: the transforms rewrite the bytes, e.g.
+    /// to decrypt them, the validators check the metadata of the blob, and the filters decide
+    /// which of its decoded changes are imported.
+    ///
+    /// The blob is decoded once for all the stages. An error in a stage aborts the import
+    /// before anything is applied. The changes that depend on a dropped change stay pending.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{ExportMode, ImportPipeline, LoroDoc, LoroError};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// let updates = doc.export(ExportMode::all_updates()).unwrap();
+    ///
+    /// let pipeline = ImportPipeline::new()
+    ///     .validate(|meta| match meta.change_num {
+    ///         0..=100 => Ok(()),
+    ///         _ => Err(LoroError::ArgErr("Too many changes".into())),
+    ///     })
+    ///     .filter(|change| Ok(change.meta().message.is_none()));
+    /// let new_doc = LoroDoc::new();
+    /// new_doc.import_with_pipeline(&updates, &pipeline).unwrap();
+    /// assert_eq!(new_doc.get_text("text").to_string(), "Hello");
+    /// ```
+    #[inline]
+    pub fn import_with_pipeline(
+        &self,
+        bytes: &[u8],
+        pipeline: &ImportPipeline,
+    ) -> Result<ImportStatus, LoroError> {
+        self.doc.import_with_pipeline(bytes, pipeline)
+    }
+
+    /// Import a snapshot from a reader, decoding its history and state eagerly.
+    ///
+    /// `progress` is called with `(loaded_ops, total_ops)` after each block of the history is
+    /// parsed, and once more when the state is decoded, so the progress of loading a large
+    /// snapshot can be rendered. If the doc is not empty or the data is not a snapshot, it's
+    /// imported like [`LoroDoc::import`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{ExportMode, LoroDoc};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// let snapshot = doc.export(ExportMode::Snapshot).unwrap();
+    ///
+    /// let new_doc = LoroDoc::new();
+    /// let mut last = (0, 0);
+    /// new_doc
+    ///     .import_snapshot_with_progress(snapshot.as_slice(), &mut |loaded, total| {
+    ///         last = (loaded, total);
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(last, (5, 5));
+    /// assert_eq!(new_doc.get_text("text").to_string(), "Hello");
+    /// ```
+    #[inline]
+    pub fn import_snapshot_with_progress(
+        &self,
+        reader: impl std::io::Read,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> LoroResult<ImportStatus> {
+        self.doc.import_snapshot_with_progress(reader, progress)
+    }
+
+    /// Import `bytes`, then check the state of each container of `containers` against its
+    /// checksum in the snapshot.
+    ///
+    /// Only the given containers are decoded to be verified. It returns
+    /// [`LoroError::ContainerChecksumMismatch`] if one of them is corrupted, in which case the
+    /// doc keeps the imported data and should be discarded. See [`LoroDoc::verify_container`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{ExportMode, LoroDoc};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.set_snapshot_checksums(true);
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello").unwrap();
+    /// let snapshot = doc.export(ExportMode::Snapshot).unwrap();
+    ///
+    /// let new_doc = LoroDoc::new();
+    /// new_doc
+    ///     .import_with_verification(&snapshot, &[text.id()])
+    ///     .unwrap();
+    /// assert_eq!(new_doc.get_text("text").to_string(), "Hello");
+    /// ```
+    #[inline]
+    pub fn import_with_verification(
+        &self,
+        bytes: &[u8],
+        containers: &[ContainerID],
+    ) -> LoroResult<ImportStatus> {
+        self.doc.import_with_verification(bytes, containers)
+    }
+
+    /// Check the current state of the container `id` against its checksum in the snapshot the
+    /// doc was loaded from.
+    ///
+    /// The fast snapshots exported with [`LoroDoc::set_snapshot_checksums`] enabled store the
+    /// checksums of all their alive containers. It returns an error if the doc wasn't loaded
+    /// from such a snapshot, or if the state has changed since.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{ExportMode, LoroDoc};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.set_snapshot_checksums(true);
+    /// let map = doc.get_map("map");
+    /// map.insert("a", 1).unwrap();
+    /// let new_doc = LoroDoc::new();
+    /// new_doc
+    ///     .import(&doc.export(ExportMode::Snapshot).unwrap())
+    ///     .unwrap();
+    /// assert!(new_doc.verify_container(&map.id()).unwrap());
+    /// // The state of the doc doesn't match the snapshot anymore
+    /// new_doc.get_map("map").insert("b", 2).unwrap();
+    /// assert!(new_doc.verify_container(&map.id()).is_err());
+    /// ```
+    #[inline]
+    pub fn verify_container(&self, id: &ContainerID) -> LoroResult<bool> {
+        self.doc.verify_container(id)
+    }
+
+    /// Import the json schema updates.
+    ///
+    /// only supports backward compatibility but not forward compatibility.
+    #[inline]
+    pub fn import_json_updates<T: TryInto<JsonSchema>>(
+        &self,
+        json: T,
+    ) -> Result<ImportStatus, LoroError> {
+        self.doc.import_json_updates(json)
+    }
+
+    /// Export the current state with json-string format of the document.
+    #[inline]
+    pub fn export_json_updates(
+        &self,
+        start_vv: &VersionVector,
+        end_vv: &VersionVector,
+    ) -> JsonSchema {
+        self.doc.export_json_updates(start_vv, end_vv, true)
+    }
+
+    /// Export the current state with json-string format of the document, without peer compression.
+    ///
+    /// Compared to [`export_json_updates`], this method does not compress the peer IDs in the updates.
+    /// So the operations are easier to be processed by application code.
+    #[inline]
+    pub fn export_json_updates_without_peer_compression(
+        &self,
+        start_vv: &VersionVector,
+        end_vv: &VersionVector,
+    ) -> JsonSchema {
+        self.doc.export_json_updates(start_vv, end_vv, false)
+    }
+
+    /// Export the readable [`Change`]s in the given [`IdSpan`]
+    pub fn export_json_in_id_span(&self, id_span: IdSpan) -> Vec<JsonChange> {
+        self.doc.export_json_in_id_span(id_span)
+    }
+
+    /// Export all the ops not included in the given `VersionVector`
+    #[deprecated(
+        since = "1.0.0",
+        note = "Use `export` with `ExportMode::Updates` instead"
+    )]
+    #[inline]
+    pub fn export_from(&self, vv: &VersionVector) -> Vec<u8> {
+        self.doc.export_from(vv)
+    }
+
+    /// Export the current state and history of the document.
+    #[deprecated(
+        since = "1.0.0",
+        note = "Use `export` with `ExportMode::Snapshot` instead"
+    )]
+    #[inline]
+    pub fn export_snapshot(&self) -> Vec<u8> {
+        self.doc.export_snapshot().unwrap()
+    }
+
+    /// Convert `Frontiers` into `VersionVector`
+    #[inline]
+    pub fn frontiers_to_vv(&self, frontiers: &Frontiers) -> Option<VersionVector> {
+        self.doc.frontiers_to_vv(frontiers)
+    }
+
+    /// Minimize the frontiers by removing the unnecessary entries.
+    pub fn minimize_frontiers(&self, frontiers: &Frontiers) -> Result<Frontiers, ID> {
+        self.with_oplog(|oplog| shrink_frontiers(frontiers, oplog.dag()))
+    }
+
+    /// Convert `VersionVector` into `Frontiers`
+    #[inline]
+    pub fn vv_to_frontiers(&self, vv: &VersionVector) -> Frontiers {
+        self.doc.vv_to_frontiers(vv)
+    }
+
+    /// Access the `OpLog`.
+    ///
+    /// NOTE: Please be ware that the API in `OpLog` is unstable
+    #[inline]
+    pub fn with_oplog<R>(&self, f: impl FnOnce(&OpLog) -> R) -> R {
+        let oplog = self.doc.oplog().lock().unwrap();
+        f(&oplog)
+    }
+
+    /// Access the `DocState`.
+    ///
+    /// NOTE: Please be ware that the API in `DocState` is unstable
+    #[inline]
+    pub fn with_state<R>(&self, f: impl FnOnce(&mut DocState) -> R) -> R {
+        let mut state = self.doc.app_state().lock().unwrap();
+        f(&mut state)
+    }
+
+    /// Get the `VersionVector` version of `OpLog`
+    #[inline]
+    pub fn oplog_vv(&self) -> VersionVector {
+        self.doc.oplog_vv()
+    }
+
+    /// Get the `VersionVector` version of `DocState`
+    #[inline]
+    pub fn state_vv(&self) -> VersionVector {
+        self.doc.state_vv()
+    }
+
+    /// Get the version vector of the `OpLog` as an [`ImVersionVector`].
+    ///
+    /// It's cached until the oplog changes and it's cheap to clone, so it suits the servers
+    /// that read the versions of many docs on every request. It can be passed to
+    /// [`ExportMode::updates_im`].
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{ExportMode, LoroDoc};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.get_text("text").insert(0, "hello").unwrap();
+    /// doc.commit();
+    /// let vv = doc.oplog_im_vv();
+    /// doc.get_text("text").insert(5, " world").unwrap();
+    /// let updates = doc.export(ExportMode::updates_im(&vv)).unwrap();
+    /// assert!(!updates.is_empty());
+    /// assert_eq!(doc.oplog_im_vv().to_vv(), doc.oplog_vv());
+    /// ```
+    #[inline]
+    pub fn oplog_im_vv(&self) -> ImVersionVector {
+        self.doc.oplog_im_vv()
+    }
+
+    /// Get the version vector of `DocState` as an [`ImVersionVector`].
+    ///
+    /// It's cached until the state changes and it's cheap to clone.
+    #[inline]
+    pub fn state_im_vv(&self) -> ImVersionVector {
+        self.doc.state_im_vv()
+    }
+
+    /// The doc only contains the history since this version
+    ///
+    /// This is empty if the doc is not shallow.
+    ///
+    /// The ops included by the shallow history start version vector are not in the doc.
+    #[inline]
+    pub fn shallow_since_vv(&self) -> ImVersionVector {
+        self.doc.shallow_since_vv()
+    }
+
+    /// The doc only contains the history since this version
+    ///
+    /// This is empty if the doc is not shallow.
+    ///
+    /// The ops included by the shallow history start frontiers are not in the doc.
+    #[inline]
+    pub fn shallow_since_frontiers(&self) -> Frontiers {
+        self.doc.shallow_since_frontiers()
+    }
+
+    /// Get the total number of operations in the `OpLog`
+    #[inline]
+    pub fn len_ops(&self) -> usize {
+        self.doc.len_ops()
+    }
+
+    /// Get the total number of changes in the `OpLog`
+    #[inline]
+    pub fn len_changes(&self) -> usize {
+        self.doc.len_changes()
+    }
+
+    /// Get the shallow value of the document.
+    #[inline]
+    pub fn get_value(&self) -> LoroValue {
+        self.doc.get_value()
+    }
+
+    /// Get the entire state of the current DocState
+    #[inline]
+    pub fn get_deep_value(&self) -> LoroValue {
+        self.doc.get_deep_value()
+    }
+
+    /// Infer a [JSON Schema](https://json-schema.org/) that describes the current shape of the document.
+    ///
+    /// The schema describes the value of [`LoroDoc::get_deep_value`], and the type of each container
+    /// is annotated by the `x-loro-container` keyword. It's inferred from the current state: the
+    /// properties of a map are its present keys, and the items of a list are described by the merged
+    /// schema of its current elements.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{LoroDoc, ToJson};
+    /// use serde_json::json;
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.get_map("user").insert("name", "Alice").unwrap();
+    /// doc.get_text("bio").insert(0, "Hi").unwrap();
+    /// let schema = doc.infer_schema().to_json_value();
+    /// assert_eq!(schema["properties"]["user"]["x-loro-container"], json!("Map"));
+    /// assert_eq!(
+    ///     schema["properties"]["user"]["properties"]["name"]["type"],
+    ///     json!("string")
+    /// );
+    /// assert_eq!(schema["properties"]["bio"]["x-loro-container"], json!("Text"));
+    /// ```
+    #[inline]
+    pub fn infer_schema(&self) -> LoroValue {
+        self.doc.infer_schema()
+    }
+
+    /// Get the entire state of the current DocState with container id
+    pub fn get_deep_value_with_id(&self) -> LoroValue {
+        self.doc
+            .app_state()
+            .lock()
+            .unwrap()
+            .get_deep_value_with_id()
+    }
+
+    /// Get the `Frontiers` version of `OpLog`
+    #[inline]
+    pub fn oplog_frontiers(&self) -> Frontiers {
+        self.doc.oplog_frontiers()
+    }
+
+    /// Get the `Frontiers` version of `DocState`
+    ///
+    /// Learn more about [`Frontiers`](https://loro.dev/docs/advanced/version_deep_dive)
+    #[inline]
+    pub fn state_frontiers(&self) -> Frontiers {
+        self.doc.state_frontiers()
+    }
+
+    /// Get the PeerID
+    #[inline]
+    pub fn peer_id(&self) -> PeerID {
+        self.doc.peer_id()
+    }
+
+    /// Change the PeerID
+    ///
+    /// NOTE: You need to make sure there is no chance two peer have the same PeerID.
+    /// If it happens, the document will be corrupted.
+    #[inline]
+    pub fn set_peer_id(&self, peer: PeerID) -> LoroResult<()> {
+        self.doc.set_peer_id(peer)
+    }
+
+    /// Subscribe the events of a container.
+    ///
+    /// The callback will be invoked after a transaction that change the container or any
+    /// of its descendants, which are resolved through the parents of the containers when
+    /// the events are emitted. The descendants created after the subscription are included.
+    /// Returns a subscription that can be used to unsubscribe.
     ///
     /// The events will be emitted after a transaction is committed. A transaction is committed when:
     ///
@@ -724,6 +1955,20 @@ impl LoroDoc {
         )
     }
 
+    /// Subscribe the events of the container at the path, see [`LoroDoc::subscribe`].
+    ///
+    /// The path is resolved once, so the subscription follows the container even if it's
+    /// moved to another path later. It fails if there is no container at the path.
+    #[inline]
+    pub fn subscribe_path(&self, path: &Path, callback: Subscriber) -> LoroResult<Subscription> {
+        match self.get_by_path(path) {
+            Some(ValueOrContainer::Container(c)) => Ok(self.subscribe(&c.id(), callback)),
+            _ => Err(LoroError::NotFoundError(
+                format!("No container at path {}", path).into_boxed_str(),
+            )),
+        }
+    }
+
     /// Subscribe all the events.
     ///
     /// The callback will be invoked when any part of the [loro_internal::DocState] is changed.
@@ -736,21 +1981,277 @@ impl LoroDoc {
     /// - `doc.import(data)` is called.
     /// - `doc.checkout(version)` is called.
     #[inline]
-    pub fn subscribe_root(&self, callback: Subscriber) -> Subscription {
-        // self.doc.subscribe_root(callback)
-        self.doc.subscribe_root(Arc::new(move |e| {
-            callback(DiffEvent::from(e));
-        }))
-    }
-
-    /// Subscribe the local update of the document.
-    pub fn subscribe_local_update(&self, callback: LocalUpdateCallback) -> Subscription {
-        self.doc.subscribe_local_update(callback)
+    pub fn subscribe_root(&self, callback: Subscriber) -> Subscription {
+        // self.doc.subscribe_root(callback)
+        self.doc.subscribe_root(Arc::new(move |e| {
+            callback(DiffEvent::from(e));
+        }))
+    }
+
+    /// Subscribe the events of a container like [`LoroDoc::subscribe`], in the lane
+    /// `priority`.
+    ///
+    /// The subscribers of an event are invoked lane by lane, from [`SubscriberPriority::High`]
+    /// to [`SubscriberPriority::Low`], e.g. so an editor view renders before an indexer or a
+    /// persistence layer runs. [`LoroDoc::subscribe`] and [`LoroDoc::subscribe_root`]
+    /// subscribe in [`SubscriberPriority::Normal`].
+    ///
+    /// A panicking subscriber doesn't prevent the other subscribers from being invoked. The
+    /// panic is resumed after all of them have been invoked.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, SubscriberPriority};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// let order = Arc::new(Mutex::new(Vec::new()));
+    /// let o = order.clone();
+    /// let _indexer = doc.subscribe_with_priority(
+    ///     &text.id(),
+    ///     SubscriberPriority::Low,
+    ///     Arc::new(move |_| o.lock().unwrap().push("indexer")),
+    /// );
+    /// let o = order.clone();
+    /// let _view = doc.subscribe_with_priority(
+    ///     &text.id(),
+    ///     SubscriberPriority::High,
+    ///     Arc::new(move |_| o.lock().unwrap().push("view")),
+    /// );
+    /// text.insert(0, "Hello").unwrap();
+    /// doc.commit();
+    /// assert_eq!(*order.lock().unwrap(), vec!["view", "indexer"]);
+    /// ```
+    #[inline]
+    pub fn subscribe_with_priority(
+        &self,
+        container_id: &ContainerID,
+        priority: SubscriberPriority,
+        callback: Subscriber,
+    ) -> Subscription {
+        self.doc.subscribe_with_priority(
+            container_id,
+            priority,
+            Arc::new(move |e| {
+                callback(DiffEvent::from(e));
+            }),
+        )
+    }
+
+    /// Subscribe all the events like [`LoroDoc::subscribe_root`], in the lane `priority`.
+    /// See [`LoroDoc::subscribe_with_priority`].
+    #[inline]
+    pub fn subscribe_root_with_priority(
+        &self,
+        priority: SubscriberPriority,
+        callback: Subscriber,
+    ) -> Subscription {
+        self.doc.subscribe_root_with_priority(
+            priority,
+            Arc::new(move |e| {
+                callback(DiffEvent::from(e));
+            }),
+        )
+    }
+
+    /// Subscribe to all the events like [`LoroDoc::subscribe_root`], for a consumer that
+    /// mirrors the doc somewhere else and has to survive crashes.
+    ///
+    /// Every event batch gets a sequence number, and the doc keeps up to `max_retained`
+    /// batches until they are acknowledged by [`DurableSubscription::ack`]. After a crash, the
+    /// consumer calls [`DurableSubscription::resume`] with the last sequence number it applied,
+    /// and the batches after it are delivered again, one by one as they were delivered.
+    ///
+    /// The batches and the sequence numbers are only kept in memory, so they don't survive a
+    /// restart of the process that holds the doc: the sequence numbers of a new subscription
+    /// start from 1. To recover from that, persist the version the mirror is at, and catch up
+    /// with [`LoroDoc::diff`] from it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let applied = Arc::new(Mutex::new(0));
+    /// let applied_clone = applied.clone();
+    /// let sub = doc.subscribe_durable(
+    ///     1024,
+    ///     Arc::new(move |seq, _event| {
+    ///         // Apply the event to the mirror, then persist `seq` with it
+    ///         *applied_clone.lock().unwrap() = seq;
+    ///     }),
+    /// );
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// doc.commit();
+    /// doc.get_text("text").insert(0, "Hi ").unwrap();
+    /// doc.commit();
+    /// assert_eq!(*applied.lock().unwrap(), 2);
+    ///
+    /// // The consumer crashed after persisting the first batch
+    /// assert_eq!(sub.resume(1), Ok(1));
+    /// sub.ack(2);
+    /// assert_eq!(sub.retained_len(), 0);
+    /// ```
+    #[inline]
+    pub fn subscribe_durable(
+        &self,
+        max_retained: usize,
+        callback: DurableSubscriber,
+    ) -> DurableSubscription {
+        self.doc.subscribe_durable(
+            max_retained,
+            Arc::new(move |seq, e| {
+                callback(seq, DiffEvent::from(e));
+            }),
+        )
+    }
+
+    /// Subscribe the local update of the document.
+    pub fn subscribe_local_update(&self, callback: LocalUpdateCallback) -> Subscription {
+        self.doc.subscribe_local_update(callback)
+    }
+
+    /// Subscribe the peer id change of the document.
+    pub fn subscribe_peer_id_change(&self, callback: PeerIdUpdateCallback) -> Subscription {
+        self.doc.subscribe_peer_id_change(callback)
+    }
+
+    /// Subscribe to the settled signal, which fires once `quiet_ms` after the last commit or
+    /// import, if there are no pending ops and no work scheduled by
+    /// [`LoroDoc::import_in_slices`] or [`LoroDoc::checkout_in_slices`].
+    ///
+    /// It's a trigger for autosave or expensive recomputations. The signal is driven by
+    /// [`LoroDoc::tick`], and fires once per burst of activity after the subscription. The
+    /// subscription is removed if the callback returns false.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::LoroDoc;
+    /// # use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+    /// let doc = LoroDoc::new();
+    /// let saved = Arc::new(AtomicBool::new(false));
+    /// let saved_clone = saved.clone();
+    /// let _sub = doc.subscribe_settled(500, Box::new(move || {
+    ///     saved_clone.store(true, Ordering::Relaxed);
+    ///     true
+    /// }));
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// doc.commit();
+    /// doc.tick(1000);
+    /// doc.tick(1200);
+    /// assert!(!saved.load(Ordering::Relaxed));
+    /// doc.tick(1500);
+    /// assert!(saved.load(Ordering::Relaxed));
+    /// ```
+    #[inline]
+    pub fn subscribe_settled(&self, quiet_ms: i64, callback: SettledCallback) -> Subscription {
+        self.doc.subscribe_settled(quiet_ms, callback)
+    }
+
+    /// Drive the settled signal with the current time `now`, in milliseconds.
+    ///
+    /// The commits and imports since the previous tick are recorded as an activity at `now`.
+    #[inline]
+    pub fn tick(&self, now: i64) {
+        self.doc.tick(now)
+    }
+
+    /// Subscribe the version changes of the document.
+    ///
+    /// The callback receives the new frontiers and version vector of the `OpLog`. It's invoked
+    /// after every commit or import that advances the version. It doesn't calculate the
+    /// container diffs, so it's cheaper than [`LoroDoc::subscribe_root`] when you only need
+    /// to know that the version advanced (e.g. sync indicators or persistence triggers).
+    ///
+    /// Return `false` in the callback to unsubscribe.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::LoroDoc;
+    /// # use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+    /// let doc = LoroDoc::new();
+    /// let count = Arc::new(AtomicUsize::new(0));
+    /// let count_clone = count.clone();
+    /// let _sub = doc.subscribe_version(Box::new(move |_frontiers, _vv| {
+    ///     count_clone.fetch_add(1, Ordering::SeqCst);
+    ///     true
+    /// }));
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// doc.commit();
+    /// assert_eq!(count.load(Ordering::SeqCst), 1);
+    /// ```
+    pub fn subscribe_version(&self, callback: VersionUpdateCallback) -> Subscription {
+        self.doc.subscribe_version(callback)
+    }
+
+    /// Add an invariant of the app that is checked after every import that changes the state.
+    ///
+    /// Merging concurrent edits can break a constraint that each peer kept locally. The
+    /// violations are sent to [`LoroDoc::subscribe_invariant_violation`], whose callback can
+    /// repair the state with new edits. The local edits are not checked. The invariant is
+    /// removed when the returned subscription is dropped or unsubscribed.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{ExportMode, LoroDoc, LoroValue, ToJson, Violation};
+    /// use serde_json::json;
+    /// use std::sync::Arc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let budget = doc.get_map("budget");
+    /// let id = budget.id();
+    /// let _invariant = doc.add_invariant(
+    ///     "budget-sum",
+    ///     Arc::new(move |view| {
+    ///         let LoroValue::Map(map) = view.get_container_deep_value(&id).unwrap() else {
+    ///             unreachable!()
+    ///         };
+    ///         let sum: i64 = map.values().map(|v| *v.as_i64().unwrap()).sum();
+    ///         if sum > 100 {
+    ///             return Err(Violation::new("over budget").with_container(id.clone()));
+    ///         }
+    ///         Ok(())
+    ///     }),
+    /// );
+    /// let repair = budget.clone();
+    /// let _sub = doc.subscribe_invariant_violation(Box::new(move |v| {
+    ///     assert_eq!(v.invariant, "budget-sum");
+    ///     repair.insert("b", 40).unwrap();
+    ///     true
+    /// }));
+    ///
+    /// let other = LoroDoc::new();
+    /// other.get_map("budget").insert("a", 60).unwrap();
+    /// budget.insert("b", 60).unwrap();
+    /// doc.commit();
+    /// doc.import(&other.export(ExportMode::all_updates()).unwrap()).unwrap();
+    /// doc.commit();
+    /// assert_eq!(
+    ///     doc.get_deep_value().to_json_value(),
+    ///     json!({"budget": {"a": 60, "b": 40}})
+    /// );
+    /// ```
+    #[inline]
+    pub fn add_invariant(&self, name: &str, invariant: Invariant) -> Subscription {
+        self.doc.add_invariant(name, invariant)
     }
 
-    /// Subscribe the peer id change of the document.
-    pub fn subscribe_peer_id_change(&self, callback: PeerIdUpdateCallback) -> Subscription {
-        self.doc.subscribe_peer_id_change(callback)
+    /// Subscribe to the violations of the invariants added by [`LoroDoc::add_invariant`].
+    ///
+    /// The callback receives the violation and the version of the state that breaks the
+    /// invariant. It runs after the import, so it can edit the doc to repair the state.
+    /// The subscription is removed if the callback returns false.
+    #[inline]
+    pub fn subscribe_invariant_violation(
+        &self,
+        callback: InvariantViolationCallback,
+    ) -> Subscription {
+        self.doc.subscribe_invariant_violation(callback)
     }
 
     /// Estimate the size of the document states in memory.
@@ -767,6 +2268,24 @@ impl LoroDoc {
     }
 
     /// Get the handler by the path.
+    ///
+    /// A [`Path`] can be passed as the path, e.g. the one parsed from a string or the one of
+    /// an event ([`event::ContainerDiff::index_path`]).
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, LoroMap, Path};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let map = doc.get_map("map");
+    /// let child = map.insert_container("a/b", LoroMap::new()).unwrap();
+    /// child.insert("0", "value").unwrap();
+    /// let path = Path::new().key("map").key("a/b").key("0");
+    /// assert_eq!(path.to_string(), "map/a\\/b/\\0");
+    /// assert_eq!(path.to_string().parse::<Path>().unwrap(), path);
+    /// let value = doc.get_by_path(&path).unwrap().into_value().unwrap();
+    /// assert_eq!(value, "value".into());
+    /// ```
     #[inline]
     pub fn get_by_path(&self, path: &[Index]) -> Option<ValueOrContainer> {
         self.doc.get_by_path(path).map(ValueOrContainer::from)
@@ -789,123 +2308,608 @@ impl LoroDoc {
     /// For tree structures, index-based paths follow depth-first traversal order.
     /// The indices start from 0 and represent the position of a node among its siblings.
     ///
+    /// See [`Path`] for how the keys that contain `/` or look like numbers are escaped.
+    ///
     /// # Examples
     /// ```
     /// # use loro::{LoroDoc, LoroValue};
     /// let doc = LoroDoc::new();
     ///
-    /// // Tree example
-    /// let tree = doc.get_tree("tree");
-    /// let root = tree.create(None).unwrap();
-    /// tree.get_meta(root).unwrap().insert("name", "root").unwrap();
-    /// // Access tree by ID or index
-    /// let name1 = doc.get_by_str_path(&format!("tree/{}/name", root)).unwrap().into_value().unwrap();
-    /// let name2 = doc.get_by_str_path("tree/0/name").unwrap().into_value().unwrap();
-    /// assert_eq!(name1, name2);
+    /// // Tree example
+    /// let tree = doc.get_tree("tree");
+    /// let root = tree.create(None).unwrap();
+    /// tree.get_meta(root).unwrap().insert("name", "root").unwrap();
+    /// // Access tree by ID or index
+    /// let name1 = doc.get_by_str_path(&format!("tree/{}/name", root)).unwrap().into_value().unwrap();
+    /// let name2 = doc.get_by_str_path("tree/0/name").unwrap().into_value().unwrap();
+    /// assert_eq!(name1, name2);
+    ///
+    /// // List example
+    /// let list = doc.get_list("list");
+    /// list.insert(0, "first").unwrap();
+    /// list.insert(1, "second").unwrap();
+    /// // Access list by index
+    /// let item = doc.get_by_str_path("list/0");
+    /// assert_eq!(item.unwrap().into_value().unwrap().into_string().unwrap(), "first".into());
+    ///
+    /// // Map example
+    /// let map = doc.get_map("map");
+    /// map.insert("key", "value").unwrap();
+    /// // Access map by key
+    /// let value = doc.get_by_str_path("map/key");
+    /// assert_eq!(value.unwrap().into_value().unwrap().into_string().unwrap(), "value".into());
+    ///
+    /// // MovableList example
+    /// let mlist = doc.get_movable_list("mlist");
+    /// mlist.insert(0, "item").unwrap();
+    /// // Access movable list by index
+    /// let item = doc.get_by_str_path("mlist/0");
+    /// assert_eq!(item.unwrap().into_value().unwrap().into_string().unwrap(), "item".into());
+    /// ```
+    #[inline]
+    pub fn get_by_str_path(&self, path: &str) -> Option<ValueOrContainer> {
+        self.doc.get_by_str_path(path).map(ValueOrContainer::from)
+    }
+
+    /// Get the absolute position of the given cursor.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::{LoroDoc, ToJson};
+    /// let doc = LoroDoc::new();
+    /// let text = &doc.get_text("text");
+    /// text.insert(0, "01234").unwrap();
+    /// let pos = text.get_cursor(5, Default::default()).unwrap();
+    /// assert_eq!(doc.get_cursor_pos(&pos).unwrap().current.pos, 5);
+    /// text.insert(0, "01234").unwrap();
+    /// assert_eq!(doc.get_cursor_pos(&pos).unwrap().current.pos, 10);
+    /// text.delete(0, 10).unwrap();
+    /// assert_eq!(doc.get_cursor_pos(&pos).unwrap().current.pos, 0);
+    /// text.insert(0, "01234").unwrap();
+    /// assert_eq!(doc.get_cursor_pos(&pos).unwrap().current.pos, 5);
+    /// ```
+    #[inline]
+    pub fn get_cursor_pos(
+        &self,
+        cursor: &Cursor,
+    ) -> Result<PosQueryResult, CannotFindRelativePosition> {
+        self.doc.query_pos(cursor)
+    }
+
+    /// Get the inner LoroDoc ref.
+    #[inline]
+    pub fn inner(&self) -> &InnerLoroDoc {
+        &self.doc
+    }
+
+    /// Whether the history cache is built.
+    #[inline]
+    pub fn has_history_cache(&self) -> bool {
+        self.doc.has_history_cache()
+    }
+
+    /// Free the history cache that is used for making checkout faster.
+    ///
+    /// If you use checkout that switching to an old/concurrent version, the history cache will be built.
+    /// You can free it by calling this method.
+    #[inline]
+    pub fn free_history_cache(&self) {
+        self.doc.free_history_cache()
+    }
+
+    /// Free the cached diff calculator that is used for checkout.
+    #[inline]
+    pub fn free_diff_calculator(&self) {
+        self.doc.free_diff_calculator()
+    }
+
+    /// Encoded all ops and history cache to bytes and store them in the kv store.
+    ///
+    /// This will free up the memory that used by parsed ops
+    #[inline]
+    pub fn compact_change_store(&self) {
+        self.doc.compact_change_store()
+    }
+
+    /// Write the new changes into the [`BlockStore`] of a doc created by
+    /// [`LoroDoc::from_block_store`], and drop the blocks loaded from it from memory.
+    ///
+    /// The other docs keep the blocks in their in-memory store.
+    #[inline]
+    pub fn flush_block_store(&self) {
+        self.doc.flush_block_store()
+    }
+
+    /// Do the pending housekeeping in slices until it's done or the budget runs out.
+    ///
+    /// The doc defers compressing the new changes and encoding the changed container states
+    /// until they are exported, and keeps the checkout caches until they are freed. Calling
+    /// this on idle frames spreads that work out. A slice is only started within the budget,
+    /// so it may overrun the budget by one slice. The pending transaction is not committed.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// doc.commit();
+    /// assert!(!doc.pending_maintenance().is_empty());
+    /// let report = doc.run_maintenance(Duration::from_millis(10));
+    /// assert!(report.pending.is_empty());
+    /// ```
+    #[inline]
+    pub fn run_maintenance(&self, budget: Duration) -> MaintenanceReport {
+        self.doc.run_maintenance(budget)
+    }
+
+    /// How much housekeeping [`LoroDoc::run_maintenance`] has left to do.
+    #[inline]
+    pub fn pending_maintenance(&self) -> MaintenanceHint {
+        self.doc.pending_maintenance()
+    }
+
+    /// Hint that the version is likely to be checked out soon, e.g. when the history slider
+    /// gets close to it.
+    ///
+    /// [`LoroDoc::run_maintenance`] builds the checkout caches and precomputes the diff from
+    /// the current version to the hinted one in its idle slices, so the checkout doesn't
+    /// stall on a large doc. The caches are kept while there are hints, and only the last
+    /// few hints are kept.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use loro::{Frontiers, LoroDoc, ID};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.set_peer_id(1).unwrap();
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// doc.commit();
+    /// doc.get_text("text").insert(5, " world").unwrap();
+    /// doc.commit();
+    /// let version = Frontiers::from(ID::new(1, 4));
+    /// doc.prefetch_version(&version).unwrap();
+    /// assert_eq!(doc.pending_maintenance().prefetch_versions, 1);
+    /// doc.run_maintenance(Duration::from_secs(1));
+    /// doc.checkout(&version).unwrap();
+    /// assert_eq!(doc.get_text("text").to_string(), "Hello");
+    /// ```
+    #[inline]
+    pub fn prefetch_version(&self, frontiers: &Frontiers) -> LoroResult<()> {
+        self.doc.prefetch_version(frontiers)
+    }
+
+    /// Drop the versions hinted by [`LoroDoc::prefetch_version`] and their precomputed diffs
+    #[inline]
+    pub fn clear_prefetched_versions(&self) {
+        self.doc.clear_prefetched_versions()
+    }
+
+    /// Estimate the memory used by the doc: the state of every container, the history and
+    /// the arena, with the numbers of the changes and the ops.
+    ///
+    /// The container states that are not decoded yet count their encoded sizes, and the
+    /// history only counts its compressed blocks. Use [`MemoryStats::to_value`] to get them as
+    /// a map.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello").unwrap();
+    /// doc.commit();
+    /// let stats = doc.memory_stats();
+    /// assert!(stats.containers[&text.id()] > 0);
+    /// assert_eq!(stats.ops, 5);
+    /// ```
+    #[inline]
+    pub fn memory_stats(&self) -> MemoryStats {
+        self.doc.memory_stats()
+    }
+
+    /// Export a snapshot split into content-defined chunks, for the storages that deduplicate
+    /// the data by blocks.
+    ///
+    /// The chunk boundaries only depend on the bytes around them, so the chunks that a change
+    /// doesn't touch keep their hashes across exports. Store the chunks by hash, and the
+    /// [index](ChunkedSnapshot::index) that lists them. Load it with
+    /// [`LoroDoc::import_chunked_snapshot`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// let snapshot = doc.export_chunked_snapshot().unwrap();
+    /// let store: HashMap<_, _> = snapshot
+    ///     .chunks
+    ///     .iter()
+    ///     .map(|c| (c.hash, c.bytes.clone()))
+    ///     .collect();
+    ///
+    /// let new_doc = LoroDoc::new();
+    /// new_doc
+    ///     .import_chunked_snapshot(&snapshot.index(), |hash| store.get(hash).cloned())
+    ///     .unwrap();
+    /// assert_eq!(new_doc.get_text("text").to_string(), "Hello");
+    /// ```
+    #[inline]
+    pub fn export_chunked_snapshot(&self) -> Result<ChunkedSnapshot, LoroEncodeError> {
+        self.doc.export_chunked_snapshot()
+    }
+
+    /// Import a snapshot exported by [`LoroDoc::export_chunked_snapshot`] from its index,
+    /// looking up the chunks by hash with `get_chunk`.
+    ///
+    /// It returns an error if a chunk is missing or corrupted.
+    #[inline]
+    pub fn import_chunked_snapshot<C: AsRef<[u8]>>(
+        &self,
+        index: &[u8],
+        get_chunk: impl FnMut(&ChunkHash) -> Option<C>,
+    ) -> LoroResult<ImportStatus> {
+        self.doc.import_chunked_snapshot(index, get_chunk)
+    }
+
+    /// Export the document in the given mode.
+    pub fn export(&self, mode: ExportMode) -> Result<Vec<u8>, LoroEncodeError> {
+        self.doc.export(mode)
+    }
+
+    /// Register a dictionary that the compressed blobs can be imported and exported with.
+    ///
+    /// The blobs compressed with it are decompressed by [`LoroDoc::import`]. A blob compressed
+    /// with a dictionary that is not registered fails to import with
+    /// [`LoroError::CompressionDictionaryNotFound`].
+    #[inline]
+    pub fn add_compression_dictionary(&self, dict: CompressionDictionary) {
+        self.doc.add_compression_dictionary(dict)
+    }
+
+    /// The ids of the registered compression dictionaries, to be advertised to the peers
+    #[inline]
+    pub fn compression_dictionary_ids(&self) -> Vec<u32> {
+        self.doc.compression_dictionary_ids()
+    }
+
+    /// Export in the given mode, compressed with the last registered dictionary that the peer
+    /// also has, according to the ids it advertised. If the peer has none of them, the blob
+    /// is not compressed, so it can be imported by any peer.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{CompressionDictionary, ExportMode, LoroDoc};
+    ///
+    /// let samples: Vec<Vec<u8>> = (0..20)
+    ///     .map(|i| {
+    ///         let doc = LoroDoc::new();
+    ///         doc.get_text("text").insert(0, &"a".repeat(i + 1)).unwrap();
+    ///         doc.export(ExportMode::all_updates()).unwrap()
+    ///     })
+    ///     .collect();
+    /// let dict = CompressionDictionary::train(&samples, 4096);
+    ///
+    /// let a = LoroDoc::new();
+    /// a.add_compression_dictionary(dict.clone());
+    /// a.get_text("text").insert(0, "hello").unwrap();
+    /// let b = LoroDoc::new();
+    /// b.add_compression_dictionary(dict);
+    /// let blob = a
+    ///     .export_compressed(ExportMode::all_updates(), &b.compression_dictionary_ids())
+    ///     .unwrap();
+    /// b.import(&blob).unwrap();
+    /// assert_eq!(b.get_text("text").to_string(), "hello");
+    /// ```
+    #[inline]
+    pub fn export_compressed(
+        &self,
+        mode: ExportMode,
+        peer_dictionary_ids: &[u32],
+    ) -> Result<Vec<u8>, LoroEncodeError> {
+        self.doc.export_compressed(mode, peer_dictionary_ids)
+    }
+
+    /// Write the updates since `from` to `writer`, encoding one block of changes at a time
+    /// instead of buffering the whole update in memory.
+    ///
+    /// The output can be imported like the output of [`ExportMode::Updates`]. The changes are
+    /// encoded twice, because the checksum in the header has to be written before the body.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{LoroDoc, VersionVector};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// let mut file = Vec::new();
+    /// doc.export_updates_stream(&VersionVector::default(), &mut file).unwrap();
+    /// let new_doc = LoroDoc::new();
+    /// new_doc.import(&file).unwrap();
+    /// assert_eq!(new_doc.get_text("text").to_string(), "Hello");
+    /// ```
+    #[inline]
+    pub fn export_updates_stream(
+        &self,
+        from: &VersionVector,
+        writer: impl std::io::Write,
+    ) -> std::io::Result<()> {
+        self.doc.export_updates_stream(from, writer)
+    }
+
+    /// Analyze the container info of the doc
+    ///
+    /// This is used for development and debugging. It can be slow.
+    pub fn analyze(&self) -> DocAnalysis {
+        self.doc.analyze()
+    }
+
+    /// Get the estimated payload bytes of all the ops of the given container in the history.
+    ///
+    /// The inserted values and texts are counted by their sizes, so it can be used to find
+    /// out which containers are responsible for the size of the document, e.g. to decide
+    /// what should be split into separate documents. The ops in the pending transaction
+    /// are not included.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::LoroDoc;
+    /// let doc = LoroDoc::new();
+    /// let list = doc.get_list("logs");
+    /// for i in 0..100 {
+    ///     list.push(format!("log entry {}", i)).unwrap();
+    /// }
+    /// doc.get_map("settings").insert("theme", "dark").unwrap();
+    /// doc.commit();
+    /// let list_size = doc.container_history_size(&list.id());
+    /// let map_size = doc.container_history_size(&doc.get_map("settings").id());
+    /// assert!(list_size > map_size * 10);
+    /// ```
+    #[inline]
+    pub fn container_history_size(&self, id: &ContainerID) -> usize {
+        self.doc.container_history_size(id)
+    }
+
+    /// Get the estimated payload bytes of the ops of every container in the history.
+    ///
+    /// See [`LoroDoc::container_history_size`].
+    #[inline]
+    pub fn container_history_sizes(&self) -> FxHashMap<ContainerID, usize> {
+        self.doc.container_history_sizes()
+    }
+
+    /// Get the path from the root to the container
+    pub fn get_path_to_container(&self, id: &ContainerID) -> Option<Vec<(ContainerID, Index)>> {
+        self.doc.get_path_to_container(id)
+    }
+
+    /// List all the containers known by the document with their metadata.
+    ///
+    /// Unlike walking [`LoroDoc::get_deep_value`], it also returns the containers
+    /// that have been deleted but are still referenced by the history.
+    ///
+    /// # Example
     ///
-    /// // List example
+    /// ```
+    /// # use loro::{ContainerFilter, ContainerType, LoroDoc, LoroList};
+    /// let doc = LoroDoc::new();
     /// let list = doc.get_list("list");
-    /// list.insert(0, "first").unwrap();
-    /// list.insert(1, "second").unwrap();
-    /// // Access list by index
-    /// let item = doc.get_by_str_path("list/0");
-    /// assert_eq!(item.unwrap().into_value().unwrap().into_string().unwrap(), "first".into());
-    ///
-    /// // Map example
-    /// let map = doc.get_map("map");
-    /// map.insert("key", "value").unwrap();
-    /// // Access map by key
-    /// let value = doc.get_by_str_path("map/key");
-    /// assert_eq!(value.unwrap().into_value().unwrap().into_string().unwrap(), "value".into());
+    /// let child = list.insert_container(0, LoroList::new()).unwrap();
+    /// list.delete(0, 1).unwrap();
+    /// doc.commit();
     ///
-    /// // MovableList example
-    /// let mlist = doc.get_movable_list("mlist");
-    /// mlist.insert(0, "item").unwrap();
-    /// // Access movable list by index
-    /// let item = doc.get_by_str_path("mlist/0");
-    /// assert_eq!(item.unwrap().into_value().unwrap().into_string().unwrap(), "item".into());
+    /// let all = doc.list_containers(&ContainerFilter::default());
+    /// assert_eq!(all.len(), 2);
+    /// let info = all.iter().find(|c| c.id == child.id()).unwrap();
+    /// assert!(!info.alive);
+    /// assert_eq!(info.parent, Some(list.id()));
+    ///
+    /// let alive = doc.list_containers(&ContainerFilter {
+    ///     container_type: Some(ContainerType::List),
+    ///     alive_only: true,
+    /// });
+    /// assert_eq!(alive.len(), 1);
+    /// assert_eq!(alive[0].id, list.id());
     /// ```
     #[inline]
-    pub fn get_by_str_path(&self, path: &str) -> Option<ValueOrContainer> {
-        self.doc.get_by_str_path(path).map(ValueOrContainer::from)
+    pub fn list_containers(&self, filter: &ContainerFilter) -> Vec<ContainerInfo> {
+        self.doc.list_containers(filter)
     }
 
-    /// Get the absolute position of the given cursor.
+    /// Get the container referenced by a [`LoroValue::ContainerRef`].
+    ///
+    /// A reference doesn't own its target, so it returns `None` if the target
+    /// doesn't exist or has been deleted.
     ///
     /// # Example
     ///
     /// ```
-    /// # use loro::{LoroDoc, ToJson};
+    /// # use loro::{LoroDoc, LoroList, LoroValue};
     /// let doc = LoroDoc::new();
-    /// let text = &doc.get_text("text");
-    /// text.insert(0, "01234").unwrap();
-    /// let pos = text.get_cursor(5, Default::default()).unwrap();
-    /// assert_eq!(doc.get_cursor_pos(&pos).unwrap().current.pos, 5);
-    /// text.insert(0, "01234").unwrap();
-    /// assert_eq!(doc.get_cursor_pos(&pos).unwrap().current.pos, 10);
-    /// text.delete(0, 10).unwrap();
-    /// assert_eq!(doc.get_cursor_pos(&pos).unwrap().current.pos, 0);
-    /// text.insert(0, "01234").unwrap();
-    /// assert_eq!(doc.get_cursor_pos(&pos).unwrap().current.pos, 5);
+    /// doc.set_extended_value_types(true);
+    /// let todos = doc.get_list("todos");
+    /// let item = todos.insert_container(0, LoroList::new()).unwrap();
+    /// let links = doc.get_list("links");
+    /// links.push(LoroValue::ContainerRef(item.id())).unwrap();
+    ///
+    /// let target = links.get(0).unwrap().into_value().unwrap();
+    /// let target = target.as_container_ref().unwrap();
+    /// assert_eq!(doc.resolve_container_ref(target).unwrap().id(), item.id());
+    ///
+    /// // The reference doesn't keep the target alive
+    /// todos.delete(0, 1).unwrap();
+    /// assert!(doc.resolve_container_ref(target).is_none());
+    /// assert_eq!(doc.dangling_container_refs().len(), 1);
     /// ```
     #[inline]
-    pub fn get_cursor_pos(
-        &self,
-        cursor: &Cursor,
-    ) -> Result<PosQueryResult, CannotFindRelativePosition> {
-        self.doc.query_pos(cursor)
+    pub fn resolve_container_ref(&self, target: &ContainerID) -> Option<Container> {
+        self.doc.resolve_container_ref(target).map(Container::from)
     }
 
-    /// Get the inner LoroDoc ref.
+    /// Get the container by its id as an [`AnyHandler`], which can be used without matching
+    /// on the type of the container.
+    ///
+    /// Returns `None` if the container doesn't exist in the doc.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, LoroText, LoroValue};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello").unwrap();
+    /// let handlers: Vec<_> = [text.id(), doc.get_map("map").id()]
+    ///     .iter()
+    ///     .map(|id| doc.get_handler(id).unwrap())
+    ///     .collect();
+    /// assert_eq!(handlers[0].get_value(), LoroValue::from("Hello"));
+    /// assert_eq!(handlers[1].get_value().into_map().unwrap().len(), 0);
+    /// let text = handlers[0].downcast_ref::<LoroText>().unwrap();
+    /// assert_eq!(text.to_string(), "Hello");
+    /// assert!(!handlers[1].is::<LoroText>());
+    /// ```
     #[inline]
-    pub fn inner(&self) -> &InnerLoroDoc {
-        &self.doc
+    pub fn get_handler(&self, id: &ContainerID) -> Option<AnyHandler> {
+        self.doc
+            .get_handler(id.clone())
+            .map(|h| Container::from(h).into())
     }
 
-    /// Whether the history cache is built.
+    /// Register a template of a container structure under `name`, replacing the template
+    /// registered before. See [`LoroDoc::instantiate_template`].
     #[inline]
-    pub fn has_history_cache(&self) -> bool {
-        self.doc.has_history_cache()
+    pub fn register_template(&self, name: &str, template: ContainerTemplate) -> LoroResult<()> {
+        self.doc.register_template(name, template)
     }
 
-    /// Free the history cache that is used for making checkout faster.
+    /// Register the current content of `container` as a template under `name`.
     ///
-    /// If you use checkout that switching to an old/concurrent version, the history cache will be built.
-    /// You can free it by calling this method.
-    #[inline]
-    pub fn free_history_cache(&self) {
-        self.doc.free_history_cache()
+    /// The styles of the texts are not kept, and trees cannot be templated.
+    pub fn register_template_from<C: ContainerTrait>(
+        &self,
+        name: &str,
+        container: &C,
+    ) -> LoroResult<()> {
+        let template = ContainerTemplate::from_handler(&container.to_container().to_handler())?;
+        self.doc.register_template(name, template)
     }
 
-    /// Free the cached diff calculator that is used for checkout.
+    /// Remove the template registered under `name`. Returns whether it existed.
     #[inline]
-    pub fn free_diff_calculator(&self) {
-        self.doc.free_diff_calculator()
+    pub fn unregister_template(&self, name: &str) -> bool {
+        self.doc.unregister_template(name)
     }
 
-    /// Encoded all ops and history cache to bytes and store them in the kv store.
+    /// Create the structure of the template `name` with fresh container ids, and insert it
+    /// into the list or the movable list `parent` at `index`, in a single commit.
     ///
-    /// This will free up the memory that used by parsed ops
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{LoroDoc, LoroList, LoroMap, LoroText, ToJson};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let card = LoroMap::new();
+    /// card.insert("done", false).unwrap();
+    /// card.insert_container("title", LoroText::new()).unwrap();
+    /// card.insert_container("tags", LoroList::new()).unwrap();
+    /// doc.register_template_from("card", &card).unwrap();
+    ///
+    /// let cards = doc.get_list("cards");
+    /// let first = doc.instantiate_template("card", &cards.id(), 0).unwrap();
+    /// let second = doc.instantiate_template("card", &cards.id(), 1).unwrap();
+    /// assert_ne!(first.id(), second.id());
+    /// assert_eq!(
+    ///     doc.get_deep_value().to_json_value(),
+    ///     serde_json::json!({
+    ///         "cards": [
+    ///             {"done": false, "title": "", "tags": []},
+    ///             {"done": false, "title": "", "tags": []}
+    ///         ]
+    ///     })
+    /// );
+    /// ```
     #[inline]
-    pub fn compact_change_store(&self) {
-        self.doc.compact_change_store()
+    pub fn instantiate_template(
+        &self,
+        name: &str,
+        parent: &ContainerID,
+        index: usize,
+    ) -> LoroResult<Container> {
+        self.doc
+            .instantiate_template(name, parent, index)
+            .map(Container::from_handler)
     }
 
-    /// Export the document in the given mode.
-    pub fn export(&self, mode: ExportMode) -> Result<Vec<u8>, LoroEncodeError> {
-        self.doc.export(mode)
+    /// Create a new doc that contains a copy of the container `id` and its descendants, e.g.
+    /// to split a page out of an overgrown workspace doc.
+    ///
+    /// The copy is the root container of the new doc. It keeps the name of `id` if it's a root
+    /// container, and is named [`SUBTREE_ROOT_NAME`] otherwise. The history is not copied.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, LoroMap, LoroText, ToJson, SUBTREE_ROOT_NAME};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let page = doc.get_list("pages").push_container(LoroMap::new()).unwrap();
+    /// let title = page.insert_container("title", LoroText::new()).unwrap();
+    /// title.insert(0, "Hello").unwrap();
+    ///
+    /// let page_doc = doc.extract_subtree(&page.id()).unwrap();
+    /// assert_eq!(
+    ///     page_doc.get_map(SUBTREE_ROOT_NAME).get_deep_value().to_json_value(),
+    ///     serde_json::json!({"title": "Hello"})
+    /// );
+    /// ```
+    #[inline]
+    pub fn extract_subtree(&self, id: &ContainerID) -> LoroResult<LoroDoc> {
+        self.doc.extract_subtree(id).map(Self::_new)
     }
 
-    /// Analyze the container info of the doc
+    /// Copy the only root container of `other`, e.g. a doc created by
+    /// [`LoroDoc::extract_subtree`], into this doc at `path`, in a single commit.
     ///
-    /// This is used for development and debugging. It can be slow.
-    pub fn analyze(&self) -> DocAnalysis {
-        self.doc.analyze()
+    /// If `path` is a single key, the content is merged into the root container of that name.
+    /// Otherwise a new container is created at the last key or index of `path`, in the
+    /// container that the rest of `path` points to.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{Index, LoroDoc};
+    ///
+    /// let page_doc = LoroDoc::new();
+    /// page_doc.get_text("title").insert(0, "Hello").unwrap();
+    ///
+    /// let doc = LoroDoc::new();
+    /// let pages = doc.get_list("pages");
+    /// let page = doc
+    ///     .merge_in(&page_doc, &[Index::Key("pages".into()), Index::Seq(0)])
+    ///     .unwrap();
+    /// assert_eq!(pages.get(0).unwrap().into_container().unwrap().id(), page.id());
+    /// ```
+    #[inline]
+    pub fn merge_in(&self, other: &LoroDoc, path: &[Index]) -> LoroResult<Container> {
+        self.doc
+            .merge_in(&other.doc, path)
+            .map(Container::from_handler)
     }
 
-    /// Get the path from the root to the container
-    pub fn get_path_to_container(&self, id: &ContainerID) -> Option<Vec<(ContainerID, Index)>> {
-        self.doc.get_path_to_container(id)
+    /// Find all the [`LoroValue::ContainerRef`] values held by the alive containers
+    /// whose targets don't exist or have been deleted.
+    #[inline]
+    pub fn dangling_container_refs(&self) -> Vec<DanglingContainerRef> {
+        self.doc.dangling_container_refs()
     }
 
     /// Evaluate a JSONPath expression on the document and return matching values or handlers.
@@ -971,6 +2975,93 @@ impl LoroDoc {
         self.doc.travel_change_ancestors(ids, f)
     }
 
+    /// Get the metadata of the changes that are in `to` but not in `from`, in causal order.
+    ///
+    /// A change is never yielded before the changes it depends on, and the concurrent changes
+    /// are ordered by their Lamport timestamps and then by their peers. The changes that are
+    /// only partially in the range are trimmed to it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{Frontiers, LoroDoc};
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.set_peer_id(1).unwrap();
+    /// doc.get_text("text").insert(0, "Hello").unwrap();
+    /// doc.commit_with(loro::CommitOptions::new().commit_msg("greet"));
+    /// let v = doc.state_frontiers();
+    /// doc.get_text("text").insert(5, "!").unwrap();
+    /// doc.commit();
+    ///
+    /// let changes: Vec<_> = doc
+    ///     .iter_changes_causal(&Frontiers::default(), &doc.oplog_frontiers())
+    ///     .unwrap()
+    ///     .collect();
+    /// assert_eq!(changes.len(), 2);
+    /// assert_eq!(changes[0].message.as_deref(), Some("greet"));
+    /// let changes: Vec<_> = doc
+    ///     .iter_changes_causal(&v, &doc.oplog_frontiers())
+    ///     .unwrap()
+    ///     .collect();
+    /// assert_eq!(changes.len(), 1);
+    /// assert_eq!(changes[0].id.counter, 5);
+    /// ```
+    #[inline]
+    pub fn iter_changes_causal(
+        &self,
+        from: &Frontiers,
+        to: &Frontiers,
+    ) -> Result<impl Iterator<Item = ChangeMeta>, FrontiersNotIncluded> {
+        self.doc.iter_changes_causal(from, to)
+    }
+
+    /// Visit the ops that are not included in `from` in causal order, and return the version
+    /// that includes `from` and all the visited ops.
+    ///
+    /// The returned version can be passed as `from` to the next call to resume the replay, so
+    /// the history can be processed incrementally (e.g. by a search indexer) without keeping a
+    /// shadow doc. The visitor can return `ControlFlow::Break(())` to stop after the current op.
+    ///
+    /// The content of each op is resolved in the same way as [`LoroDoc::export_json_updates`].
+    /// The visitor should not access the doc, because the history is locked during the replay.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{LoroDoc, VersionVector};
+    /// use std::ops::ControlFlow;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello").unwrap();
+    /// doc.commit();
+    /// doc.get_map("map").insert("key", 1).unwrap();
+    /// doc.commit();
+    ///
+    /// // Process at most one op per round
+    /// let mut ops = Vec::new();
+    /// let checkpoint = doc.replay(&VersionVector::default(), &mut |op| {
+    ///     ops.push(op);
+    ///     ControlFlow::Break(())
+    /// });
+    /// assert_eq!(ops.len(), 1);
+    /// let checkpoint = doc.replay(&checkpoint, &mut |op| {
+    ///     ops.push(op);
+    ///     ControlFlow::Continue(())
+    /// });
+    /// assert_eq!(ops.len(), 2);
+    /// assert_eq!(checkpoint, doc.oplog_vv());
+    /// ```
+    #[inline]
+    pub fn replay(
+        &self,
+        from: &VersionVector,
+        visitor: &mut dyn FnMut(ReplayOp) -> ControlFlow<()>,
+    ) -> VersionVector {
+        self.doc.replay(from, visitor)
+    }
+
     /// Check if the doc contains the full history.
     pub fn is_shallow(&self) -> bool {
         self.doc.is_shallow()
@@ -1021,6 +3112,83 @@ impl LoroDoc {
         self.doc.diff(a, b).map(|x| x.into())
     }
 
+    /// Get the deep values of the container `id` at each of the given versions.
+    ///
+    /// It's much cheaper than checking out each version, because only the history between
+    /// the adjacent versions is traversed. Passing the versions in their causal order is the
+    /// fastest. The doc state is left unchanged and no event is emitted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello").unwrap();
+    /// doc.commit();
+    /// let v1 = doc.oplog_frontiers();
+    /// text.insert(5, " world").unwrap();
+    /// doc.commit();
+    /// let v2 = doc.oplog_frontiers();
+    ///
+    /// let values = doc.values_at(&[v1, v2], &text.id()).unwrap();
+    /// assert_eq!(values, vec!["Hello".into(), "Hello world".into()]);
+    /// assert!(!doc.is_detached());
+    /// ```
+    #[inline]
+    pub fn values_at(
+        &self,
+        versions: &[Frontiers],
+        id: &ContainerID,
+    ) -> LoroResult<Vec<LoroValue>> {
+        self.doc.values_at(versions, id)
+    }
+
+    /// Map a position in a list, movable list or text container at version `from` to the
+    /// corresponding position at version `to`.
+    ///
+    /// It's useful for migrating bookmarks, comments and annotations that are stored as
+    /// positions when the document changes underneath them. If the element at the position
+    /// is deleted, it's mapped to the position where the deletion happened. The position
+    /// follows the element at it, so the elements inserted exactly at the position are
+    /// placed before it. Only the history of the container is traversed.
+    ///
+    /// Moves are not tracked: an element moved in a [LoroMovableList] is mapped to the
+    /// position it was removed from, not to its new position.
+    ///
+    /// The position of a text is in Unicode code points.
+    ///
+    /// Returns `None` if the container is not a list, movable list or text, or if any of
+    /// the versions is not included in the doc.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::LoroDoc;
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello world").unwrap();
+    /// doc.commit();
+    /// let v1 = doc.state_frontiers();
+    /// text.insert(0, "Say: ").unwrap();
+    /// doc.commit();
+    /// let v2 = doc.state_frontiers();
+    /// // The position of "world"
+    /// assert_eq!(doc.map_position(&text.id(), 6, &v1, &v2), Some(11));
+    /// assert_eq!(doc.map_position(&text.id(), 11, &v2, &v1), Some(6));
+    /// ```
+    #[inline]
+    pub fn map_position(
+        &self,
+        container: &ContainerID,
+        pos: usize,
+        from: &Frontiers,
+        to: &Frontiers,
+    ) -> Option<usize> {
+        self.doc.map_position(container, pos, from, to)
+    }
+
     /// Check if the doc contains the target container.
     ///
     /// A root container always exists, while a normal container exists
@@ -1054,6 +3222,52 @@ impl LoroDoc {
     pub fn has_container(&self, container_id: &ContainerID) -> bool {
         self.doc.has_container(container_id)
     }
+
+    /// Upgrade the container to a richer type in place and return the id of the new container.
+    ///
+    /// Only upgrading a [`LoroList`] to a [`LoroMovableList`] is supported. The new container
+    /// takes the place of the old one in its parent, or takes its name if it's a root container.
+    /// The elements keep their order, the child containers are copied, and the old list is
+    /// emptied. It's committed as one change, and the peers that don't know about upgrades see
+    /// it as an ordinary change that replaces the list.
+    ///
+    /// The elements inserted into the old list concurrently are not moved. Upgrading the old
+    /// list again moves them to the end of the new container.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{ContainerType, LoroDoc, ToJson};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let list = doc.get_list("list");
+    /// list.insert(0, 1).unwrap();
+    /// list.insert(1, 2).unwrap();
+    /// let new_id = doc
+    ///     .upgrade_container(&list.id(), ContainerType::MovableList)
+    ///     .unwrap();
+    /// let movable_list = doc.get_movable_list(new_id.clone());
+    /// movable_list.mov(0, 1).unwrap();
+    /// assert_eq!(doc.get_deep_value().to_json_value(), serde_json::json!({"list": [2, 1]}));
+    /// assert_eq!(doc.get_upgraded_container(&list.id()), Some(new_id));
+    /// ```
+    #[inline]
+    pub fn upgrade_container(
+        &self,
+        id: &ContainerID,
+        to: ContainerType,
+    ) -> LoroResult<ContainerID> {
+        self.doc.upgrade_container(id, to)
+    }
+
+    /// Get the container that replaced the given one by [`LoroDoc::upgrade_container`],
+    /// following the later upgrades.
+    ///
+    /// It also resolves the child containers copied by the upgrade. Returns `None` if the
+    /// container was not upgraded.
+    #[inline]
+    pub fn get_upgraded_container(&self, id: &ContainerID) -> Option<ContainerID> {
+        self.doc.get_upgraded_container(id)
+    }
 }
 
 /// It's used to prevent the user from implementing the trait directly.
@@ -1183,36 +3397,149 @@ impl LoroList {
         self.handler.get_(index).map(ValueOrContainer::from)
     }
 
-    /// Get the deep value of the container.
+    /// Get the shallow values in `range` without copying the rest of the list, e.g. to render
+    /// the visible window of a long list.
+    ///
+    /// The sub-containers are represented as [LoroValue::Container]. The range is clamped to
+    /// the length of the list.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, LoroValue};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let list = doc.get_list("list");
+    /// for i in 0..100 {
+    ///     list.push(i).unwrap();
+    /// }
+    /// let window: Vec<LoroValue> = (40..43).map(LoroValue::from).collect();
+    /// assert_eq!(list.get_range(40..43), window);
+    /// assert_eq!(list.get_range(98..120).len(), 2);
+    /// ```
+    #[inline]
+    pub fn get_range(&self, range: std::ops::Range<usize>) -> Vec<LoroValue> {
+        self.handler.get_range(range)
+    }
+
+    /// Get the deep value of the container.
+    #[inline]
+    pub fn get_deep_value(&self) -> LoroValue {
+        self.handler.get_deep_value()
+    }
+
+    /// Get the shallow value of the container.
+    ///
+    /// This does not convert the state of sub-containers; instead, it represents them as [LoroValue::Container].
+    #[inline]
+    pub fn get_value(&self) -> LoroValue {
+        self.handler.get_value()
+    }
+
+    /// Get the ID of the container.
+    #[inline]
+    pub fn id(&self) -> ContainerID {
+        self.handler.id().clone()
+    }
+
+    /// Pop the last element of the list.
+    #[inline]
+    pub fn pop(&self) -> LoroResult<Option<LoroValue>> {
+        self.handler.pop()
+    }
+
+    /// Push a value to the list.
+    #[inline]
+    pub fn push(&self, v: impl Into<LoroValue>) -> LoroResult<()> {
+        self.handler.push(v.into())
+    }
+
+    /// Insert the values at the position with one op.
+    ///
+    /// The adjacent numbers of the same type are stored as dense arrays in memory, so it's
+    /// the efficient way to insert numeric data like time series or coordinates. The dense
+    /// layout isn't used by the encoding: the snapshots still store one value per element.
+    #[inline]
+    pub fn insert_many(&self, pos: usize, values: Vec<LoroValue>) -> LoroResult<()> {
+        self.handler.insert_many(pos, values)
+    }
+
+    /// Push the values to the end of the list with one op.
+    ///
+    /// The numbers are stored as dense arrays in memory, but the snapshots still store one
+    /// value per element, see [`LoroList::insert_many`].
+    ///
+    /// # Example
+    /// ```
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let samples = doc.get_list("samples");
+    /// samples.push_slice(&[0.5, 1.5, 2.5]).unwrap();
+    /// samples.set_range(2, &[3.0, 4.0]).unwrap();
+    /// assert_eq!(samples.to_f64_vec(), Some(vec![0.5, 1.5, 3.0, 4.0]));
+    /// ```
     #[inline]
-    pub fn get_deep_value(&self) -> LoroValue {
-        self.handler.get_deep_value()
+    pub fn push_slice<T: Clone + Into<LoroValue>>(&self, values: &[T]) -> LoroResult<()> {
+        self.handler.push_slice(values)
     }
 
-    /// Get the shallow value of the container.
+    /// Replace the values starting at the position. The values past the end of the list are
+    /// appended.
     ///
-    /// This does not convert the state of sub-containers; instead, it represents them as [LoroValue::Container].
+    /// The old values are deleted with one op if they were inserted by one op, and the new
+    /// values are inserted with one op. Like [`LoroList::insert_many`], the numbers are stored
+    /// as dense arrays in memory only: the snapshots still store one value per element.
     #[inline]
-    pub fn get_value(&self) -> LoroValue {
-        self.handler.get_value()
+    pub fn set_range<T: Clone + Into<LoroValue>>(
+        &self,
+        pos: usize,
+        values: &[T],
+    ) -> LoroResult<()> {
+        self.handler.set_range(pos, values)
     }
 
-    /// Get the ID of the container.
+    /// Get the values as `f64`s, or `None` if any of them isn't a `f64`.
+    ///
+    /// It copies the dense arrays in the state without creating a [`LoroValue`] per element.
     #[inline]
-    pub fn id(&self) -> ContainerID {
-        self.handler.id().clone()
+    pub fn to_f64_vec(&self) -> Option<Vec<f64>> {
+        self.handler.to_f64_vec()
     }
 
-    /// Pop the last element of the list.
+    /// Get the values as `i64`s, or `None` if any of them isn't an `i64`.
+    ///
+    /// It copies the dense arrays in the state without creating a [`LoroValue`] per element.
     #[inline]
-    pub fn pop(&self) -> LoroResult<Option<LoroValue>> {
-        self.handler.pop()
+    pub fn to_i64_vec(&self) -> Option<Vec<i64>> {
+        self.handler.to_i64_vec()
     }
 
-    /// Push a value to the list.
+    /// Update the list to `target` with a minimal diff, matching the elements by the keys
+    /// returned by `key_fn`.
+    ///
+    /// The unmatched elements are deleted or inserted, and the matched elements whose values
+    /// changed are replaced. The ops are applied in one transaction. Use
+    /// [`LoroMovableList::sync_to`] to move the reordered elements instead of recreating them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use loro::{LoroDoc, LoroValue};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let list = doc.get_list("list");
+    /// list.push_slice(&["a", "b", "c"]).unwrap();
+    /// let target: Vec<LoroValue> = vec!["a".into(), "x".into(), "c".into()];
+    /// list.sync_to(&target, |v| v.clone()).unwrap();
+    /// assert_eq!(list.get_value(), LoroValue::from(target));
+    /// ```
     #[inline]
-    pub fn push(&self, v: impl Into<LoroValue>) -> LoroResult<()> {
-        self.handler.push(v.into())
+    pub fn sync_to<K: std::hash::Hash + Eq>(
+        &self,
+        target: &[LoroValue],
+        key_fn: impl FnMut(&LoroValue) -> K,
+    ) -> LoroResult<()> {
+        self.handler.sync_to(target, key_fn)
     }
 
     /// Push a container to the list.
@@ -1304,6 +3631,40 @@ impl LoroList {
         self.handler.get_cursor(pos, side)
     }
 
+    /// Get the values removed by the changes that are in `to` but not in `from`, in causal order.
+    ///
+    /// Each delete op makes one [`DeletedListSpan`] with the peer that removed the values and the
+    /// timestamp of its change, so that the deletions can be shown or restored without replaying
+    /// the history. The values concurrently deleted by several peers show up once per deletion.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let list = doc.get_list("list");
+    /// list.push(1).unwrap();
+    /// list.push(2).unwrap();
+    /// list.push(3).unwrap();
+    /// doc.commit();
+    /// let from = doc.state_frontiers();
+    /// list.delete(0, 2).unwrap();
+    /// doc.commit();
+    /// let deleted = list
+    ///     .deleted_content_between(&from, &doc.state_frontiers())
+    ///     .unwrap();
+    /// assert_eq!(deleted[0].values, vec![1.into(), 2.into()]);
+    /// assert_eq!(deleted[0].deleted_by, doc.peer_id());
+    /// ```
+    #[inline]
+    pub fn deleted_content_between(
+        &self,
+        from: &Frontiers,
+        to: &Frontiers,
+    ) -> LoroResult<Vec<DeletedListSpan>> {
+        self.handler.deleted_content_between(from, to)
+    }
+
     /// Converts the LoroList to a Vec of LoroValue.
     ///
     /// This method unwraps the internal Arc and clones the data if necessary,
@@ -1339,6 +3700,60 @@ impl LoroList {
     pub fn get_id_at(&self, pos: usize) -> Option<ID> {
         self.handler.get_id_at(pos)
     }
+
+    /// Get the peer, the lamport and the timestamp of the op that inserted the element with
+    /// the given ID, or `None` if the element has been deleted.
+    pub fn elem_last_modified(&self, elem_id: ID) -> Option<LastModified> {
+        self.handler.elem_last_modified(elem_id)
+    }
+
+    /// Insert a value right after the element with the given ID.
+    ///
+    /// The ID is the one returned by [`LoroList::get_id_at`]. Unlike the index, it keeps
+    /// pointing to the same element while concurrent edits are imported. If the element has
+    /// been deleted, the position is decided by `fallback`.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{AnchorFallback, LoroDoc, ToJson};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let list = doc.get_list("list");
+    /// list.push("a").unwrap();
+    /// list.push("c").unwrap();
+    /// let a = list.get_id_at(0).unwrap();
+    /// list.insert(0, "start").unwrap();
+    /// list.insert_after(a, "b", AnchorFallback::Error).unwrap();
+    /// assert_eq!(list.get_value().to_json_value(), serde_json::json!(["start", "a", "b", "c"]));
+    ///
+    /// list.delete(1, 1).unwrap();
+    /// assert!(list.insert_after(a, "x", AnchorFallback::Error).is_err());
+    /// list.insert_after(a, "a2", AnchorFallback::Nearest).unwrap();
+    /// assert_eq!(list.get_value().to_json_value(), serde_json::json!(["start", "a2", "b", "c"]));
+    /// ```
+    #[inline]
+    pub fn insert_after(
+        &self,
+        elem: ID,
+        v: impl Into<LoroValue>,
+        fallback: AnchorFallback,
+    ) -> LoroResult<()> {
+        self.handler.insert_after(elem, v, fallback)
+    }
+
+    /// Insert a value right before the element with the given ID.
+    ///
+    /// If the element has been deleted, the position is decided by `fallback`.
+    /// See [`LoroList::insert_after`].
+    #[inline]
+    pub fn insert_before(
+        &self,
+        elem: ID,
+        v: impl Into<LoroValue>,
+        fallback: AnchorFallback,
+    ) -> LoroResult<()> {
+        self.handler.insert_before(elem, v, fallback)
+    }
 }
 
 impl Default for LoroList {
@@ -1539,6 +3954,26 @@ impl LoroMap {
     pub fn get_last_editor(&self, key: &str) -> Option<PeerID> {
         self.handler.get_last_editor(key)
     }
+
+    /// Get the peer, the lamport and the timestamp of the op that set the current value of
+    /// the given entry. A deleted entry returns the deletion.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// doc.set_peer_id(1).unwrap();
+    /// let map = doc.get_map("map");
+    /// map.insert("a", 1).unwrap();
+    /// map.insert("a", 2).unwrap();
+    /// let m = map.last_modified("a").unwrap();
+    /// assert_eq!((m.peer, m.lamport), (1, 1));
+    /// assert!(map.last_modified("b").is_none());
+    /// ```
+    pub fn last_modified(&self, key: &str) -> Option<LastModified> {
+        self.handler.last_modified(key)
+    }
 }
 
 impl Default for LoroMap {
@@ -1645,11 +4080,71 @@ impl LoroText {
         self.handler.delete_utf8(pos, len)
     }
 
+    /// Delete the word before or after the Unicode index `pos`, like ctrl+backspace and
+    /// ctrl+delete. Returns the deleted Unicode range.
+    ///
+    /// The word boundaries follow the Unicode text segmentation (UAX #29).
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, TextDirection};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello world").unwrap();
+    /// assert_eq!(text.delete_word(11, TextDirection::Backward).unwrap(), 6..11);
+    /// assert_eq!(text.to_string(), "Hello ");
+    /// ```
+    #[inline]
+    pub fn delete_word(&self, pos: usize, direction: TextDirection) -> LoroResult<Range<usize>> {
+        self.handler.delete_word(pos, direction)
+    }
+
+    /// Expand the Unicode `range` to the nearest grapheme, word or sentence boundaries around it.
+    ///
+    /// An empty range is expanded to the segment containing it, e.g. the word under the cursor
+    /// of a double click.
+    #[inline]
+    pub fn expand_to_boundary(
+        &self,
+        range: Range<usize>,
+        kind: TextBoundary,
+    ) -> LoroResult<Range<usize>> {
+        self.handler.expand_to_boundary(range, kind)
+    }
+
     /// Get a string slice at the given Unicode range
     pub fn slice(&self, start_index: usize, end_index: usize) -> LoroResult<String> {
         self.handler.slice(start_index, end_index)
     }
 
+    /// Get a string slice at the given UTF-16 range, e.g. the offsets of the DOM.
+    ///
+    /// It returns an error if a boundary is in the middle of a code point.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "😀Hello").unwrap();
+    /// assert_eq!(text.slice_utf16(2..4).unwrap(), "He");
+    /// assert!(text.slice_utf16(1..4).is_err());
+    /// ```
+    #[inline]
+    pub fn slice_utf16(&self, range: Range<usize>) -> LoroResult<String> {
+        self.handler.slice_utf16(range)
+    }
+
+    /// Get a string slice at the given UTF-8 range.
+    ///
+    /// It returns an error if a boundary is in the middle of a code point.
+    #[inline]
+    pub fn slice_utf8(&self, range: Range<usize>) -> LoroResult<String> {
+        self.handler.slice_utf8(range)
+    }
+
     /// Get the characters at given unicode position.
     pub fn char_at(&self, pos: usize) -> LoroResult<char> {
         self.handler.char_at(pos)
@@ -1714,6 +4209,76 @@ impl LoroText {
         self.handler.update_by_line(text, options)
     }
 
+    /// Update the current text to `text` with the diff computed on the units of `mode`.
+    ///
+    /// It's meant for the apps that only have the new full string, e.g. form fields. The
+    /// unchanged words, sentences or lines are kept, so the concurrent edits inside of them
+    /// still merge. A coarser mode is faster, but an edit inside a unit replaces the whole unit.
+    ///
+    /// # Example
+    /// ```rust
+    /// use loro::{DiffMode, LoroDoc};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "The quick fox").unwrap();
+    /// text.update_with_mode("The quick brown fox", DiffMode::Word, Default::default())
+    ///     .unwrap();
+    /// assert_eq!(text.to_string(), "The quick brown fox");
+    /// ```
+    pub fn update_with_mode(
+        &self,
+        text: &str,
+        mode: DiffMode,
+        options: UpdateOptions,
+    ) -> Result<(), UpdateTimeoutError> {
+        self.handler.update_with_mode(text, mode, options)
+    }
+
+    /// Attach the ruby annotation `text`, e.g. furigana, to the base text in the Unicode
+    /// `range`.
+    ///
+    /// The annotation is stored as the style [`RUBY_STYLE_KEY`], so it shows up in the
+    /// attributes of [`LoroText::to_delta`].
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, RubyMarkup};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "漢字").unwrap();
+    /// text.set_ruby(0..2, "かんじ").unwrap();
+    /// assert_eq!(text.rubies()[0].text, "かんじ");
+    /// assert_eq!(
+    ///     text.to_ruby_markup(RubyMarkup::Html),
+    ///     "<ruby>漢字<rt>かんじ</rt></ruby>"
+    /// );
+    /// ```
+    #[inline]
+    pub fn set_ruby(&self, range: Range<usize>, text: &str) -> LoroResult<()> {
+        self.handler.set_ruby(range, text)
+    }
+
+    /// Remove the ruby annotations in the Unicode `range`.
+    #[inline]
+    pub fn remove_ruby(&self, range: Range<usize>) -> LoroResult<()> {
+        self.handler.remove_ruby(range)
+    }
+
+    /// Get the ruby annotations with their base text ranges in document order.
+    #[inline]
+    pub fn rubies(&self) -> Vec<RubyAnnotation> {
+        self.handler.rubies()
+    }
+
+    /// Render the text with its ruby annotations as HTML or Markdown, dropping the other
+    /// styles.
+    #[inline]
+    pub fn to_ruby_markup(&self, markup: RubyMarkup) -> String {
+        self.handler.to_ruby_markup(markup)
+    }
+
     /// Apply a [delta](https://quilljs.com/docs/delta/) to the text container.
     pub fn apply_delta(&self, delta: &[TextDelta]) -> LoroResult<()> {
         self.handler.apply_delta(delta)
@@ -1852,6 +4417,54 @@ impl LoroText {
         self.handler.to_string()
     }
 
+    /// Get the ids of the paragraphs in document order.
+    ///
+    /// The i-th id belongs to the i-th line of the text split by `'\n'`. Returns `None`
+    /// if the text is detached.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, ParagraphId};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello\nWorld").unwrap();
+    /// let ids = text.paragraph_ids().unwrap();
+    /// assert_eq!(ids.len(), 2);
+    /// assert_eq!(ids[0], ParagraphId::First);
+    /// // Editing the paragraphs doesn't change their ids
+    /// text.insert(0, "Oh, ").unwrap();
+    /// text.insert(text.len_unicode(), "!").unwrap();
+    /// assert_eq!(text.paragraph_ids().unwrap(), ids);
+    /// ```
+    pub fn paragraph_ids(&self) -> Option<Vec<ParagraphId>> {
+        self.handler.paragraph_ids()
+    }
+
+    /// Get the lines of the text split by `'\n'`, with the ids of their paragraphs.
+    ///
+    /// The ids are kept across the edits, so a line-based view, e.g. a diff viewer or the
+    /// comments on the lines, can be keyed by them and only update the lines reported by
+    /// the events, see [`LoroDoc::set_text_paragraph_ids`]. Returns `None` if the text is
+    /// detached.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, ParagraphId};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello\nWorld").unwrap();
+    /// let lines = text.lines().unwrap();
+    /// assert_eq!(lines[0], (ParagraphId::First, "Hello".to_string()));
+    /// assert_eq!(lines[1].1, "World");
+    /// text.insert(0, "Oh, ").unwrap();
+    /// assert_eq!(text.lines().unwrap()[1], lines[1]);
+    /// ```
+    pub fn lines(&self) -> Option<Vec<(ParagraphId, String)>> {
+        self.handler.lines()
+    }
+
     /// Get the cursor at the given position in the given Unicode position.
     ///
     /// Using "index" to denote cursor positions can be unstable, as positions may
@@ -1886,6 +4499,38 @@ impl LoroText {
         self.handler.get_cursor(pos, side)
     }
 
+    /// Get the text removed by the changes that are in `to` but not in `from`, in causal order.
+    ///
+    /// Each delete op makes one [`DeletedSpan`] with the peer that removed the text and the
+    /// timestamp of its change, so that the deletions can be shown or restored without replaying
+    /// the history. The text concurrently deleted by several peers shows up once per deletion.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello world").unwrap();
+    /// doc.commit();
+    /// let from = doc.state_frontiers();
+    /// text.delete(5, 6).unwrap();
+    /// doc.commit();
+    /// let deleted = text
+    ///     .deleted_content_between(&from, &doc.state_frontiers())
+    ///     .unwrap();
+    /// assert_eq!(deleted[0].text, " world");
+    /// assert_eq!(deleted[0].deleted_by, doc.peer_id());
+    /// ```
+    #[inline]
+    pub fn deleted_content_between(
+        &self,
+        from: &Frontiers,
+        to: &Frontiers,
+    ) -> LoroResult<Vec<DeletedSpan>> {
+        self.handler.deleted_content_between(from, to)
+    }
+
     /// Whether the text container is deleted.
     pub fn is_deleted(&self) -> bool {
         self.handler.is_deleted()
@@ -2174,6 +4819,81 @@ impl LoroTree {
             .map(|h| LoroMap { handler: h })
     }
 
+    /// Make the tree match a flat parent-pointer description in one transaction.
+    ///
+    /// It's designed for syncing trees from external systems like file systems or databases.
+    /// The external id of each node is stored in its meta map under `id_key`, and it's used
+    /// to match the existing nodes on later imports, so that only the differences are turned
+    /// into ops: missing nodes are created, changed nodes are moved, meta entries are updated,
+    /// and the nodes that are not in the description are deleted. Siblings are ordered by `index`.
+    ///
+    /// It returns the [`TreeID`] of each node by its external id.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use loro::{FlatTreeNode, LoroDoc, TreeParentId};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let tree = doc.get_tree("fs");
+    /// let node = |id: &str, parent: Option<&str>, index| FlatTreeNode {
+    ///     id: id.to_string(),
+    ///     parent: parent.map(|p| p.to_string()),
+    ///     index,
+    ///     meta: Default::default(),
+    /// };
+    /// let ids = tree
+    ///     .import_flat(&[node("/", None, 0), node("/a", Some("/"), 0)], "path")
+    ///     .unwrap();
+    /// assert_eq!(tree.children(TreeParentId::Node(ids["/"])), Some(vec![ids["/a"]]));
+    ///
+    /// // Move `/a` to the root
+    /// let new_ids = tree
+    ///     .import_flat(&[node("/", None, 0), node("/a", None, 1)], "path")
+    ///     .unwrap();
+    /// assert_eq!(new_ids, ids);
+    /// assert_eq!(tree.roots(), vec![ids["/"], ids["/a"]]);
+    /// ```
+    #[inline]
+    pub fn import_flat(
+        &self,
+        nodes: &[FlatTreeNode],
+        id_key: &str,
+    ) -> LoroResult<FxHashMap<String, TreeID>> {
+        self.handler.import_flat(nodes, id_key)
+    }
+
+    /// Create a [`TreeOverlay`] to attach per-session flags to the nodes of this tree,
+    /// e.g. whether a node is expanded in the UI.
+    ///
+    /// The flags are not recorded in the history of the doc. The queries of the overlay
+    /// follow the current structure of the tree, so the flags of the deleted nodes are
+    /// ignored. The flags that are not updated within `timeout` milliseconds are removed by
+    /// [`TreeOverlay::remove_outdated`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use loro::LoroDoc;
+    /// let doc = LoroDoc::new();
+    /// let tree = doc.get_tree("tree");
+    /// let root = tree.create(None).unwrap();
+    /// let child = tree.create(root).unwrap();
+    /// let mut overlay = tree.overlay(i64::MAX);
+    /// overlay.set_expanded(root, true).unwrap();
+    /// assert_eq!(overlay.visible_nodes(), vec![root, child]);
+    ///
+    /// tree.delete(root).unwrap();
+    /// assert!(!overlay.is_expanded(root));
+    /// assert_eq!(overlay.prune(), vec![root]);
+    /// // Nothing is recorded in the history
+    /// doc.commit();
+    /// assert_eq!(doc.oplog_vv().get(&doc.peer_id()), Some(&3));
+    /// ```
+    pub fn overlay(&self, timeout: i64) -> TreeOverlay {
+        TreeOverlay::new(self.handler.clone(), timeout)
+    }
+
     /// Return the parent of target node.
     ///
     /// - If the target node does not exist, return `None`.
@@ -2460,6 +5180,50 @@ impl LoroMovableList {
         self.handler.mov(from, to)
     }
 
+    /// Sort the list with the comparator `cmp` on the shallow values, in a single commit.
+    ///
+    /// The sort is stable. Instead of moving every element, only the elements outside of a
+    /// longest increasing subsequence of the sorted order are moved, which is the minimal
+    /// number of moves and leaves less room for conflicts with the concurrent moves. Returns
+    /// the number of the moves.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let list = doc.get_movable_list("list");
+    /// for v in [1, 2, 3, 0] {
+    ///     list.push(v).unwrap();
+    /// }
+    /// let moves = list
+    ///     .sort_by(|a, b| a.as_i64().cmp(&b.as_i64()))
+    ///     .unwrap();
+    /// assert_eq!(moves, 1);
+    /// assert_eq!(list.get_value(), vec![0, 1, 2, 3].into());
+    /// ```
+    pub fn sort_by(
+        &self,
+        cmp: impl FnMut(&LoroValue, &LoroValue) -> std::cmp::Ordering,
+    ) -> LoroResult<usize> {
+        self.handler.sort_by(cmp)
+    }
+
+    /// Update the list to `target` with a minimal diff, matching the elements by the keys
+    /// returned by `key_fn`.
+    ///
+    /// The unmatched elements are deleted or inserted, the matched elements are moved into
+    /// the target order with as few moves as possible, and the ones whose values changed are
+    /// set. The ops are applied in one transaction.
+    #[inline]
+    pub fn sync_to<K: std::hash::Hash + Eq>(
+        &self,
+        target: &[LoroValue],
+        key_fn: impl FnMut(&LoroValue) -> K,
+    ) -> LoroResult<()> {
+        self.handler.sync_to(target, key_fn)
+    }
+
     /// Insert a container at the given position.
     pub fn insert_container<C: ContainerTrait>(&self, pos: usize, child: C) -> LoroResult<C> {
         Ok(C::from_handler(
@@ -2579,6 +5343,12 @@ impl LoroMovableList {
     pub fn get_last_editor_at(&self, pos: usize) -> Option<PeerID> {
         self.handler.get_last_editor_at(pos)
     }
+
+    /// Get the peer, the lamport and the timestamp of the latest op that set or moved the
+    /// list item at the given position.
+    pub fn last_modified_at(&self, pos: usize) -> Option<LastModified> {
+        self.handler.last_modified_at(pos)
+    }
 }
 
 impl Default for LoroMovableList {
@@ -2806,6 +5576,45 @@ impl Container {
             Self::Unknown(x) => x.handler.id(),
         }
     }
+
+    /// Copy the current content of the container into `doc` at `path`, in a single commit,
+    /// e.g. to paste it into another document. Returns the copy.
+    ///
+    /// The copy has new container ids, and the texts keep their styles. `path` is interpreted
+    /// like in [`LoroDoc::merge_in`]. If `record_source` is true and the container is
+    /// attached, the commit records its id and the version of its doc in the metadata, under
+    /// [`COPIED_FROM_METADATA_KEY`] and [`COPIED_VERSION_METADATA_KEY`].
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{Container, Index, LoroDoc, COPIED_FROM_METADATA_KEY};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello").unwrap();
+    /// text.mark(0..5, "bold", true).unwrap();
+    ///
+    /// let other = LoroDoc::new();
+    /// let notes = other.get_map("notes");
+    /// let path = [Index::Key("notes".into()), Index::Key("text".into())];
+    /// let copy = Container::Text(text.clone())
+    ///     .copy_into(&other, &path, true)
+    ///     .unwrap();
+    /// assert_eq!(notes.get("text").unwrap().into_container().unwrap().id(), copy.id());
+    /// assert_eq!(copy.into_text().unwrap().get_richtext_value(), text.get_richtext_value());
+    /// let change = other.get_change(other.oplog_frontiers().as_single().unwrap()).unwrap();
+    /// assert_eq!(change.metadata()[COPIED_FROM_METADATA_KEY], text.id().to_string());
+    /// ```
+    pub fn copy_into(
+        &self,
+        doc: &LoroDoc,
+        path: &[Index],
+        record_source: bool,
+    ) -> LoroResult<Container> {
+        self.to_handler()
+            .copy_into(&doc.doc, path, record_source)
+            .map(Container::from_handler)
+    }
 }
 
 impl From<InnerHandler> for Container {
@@ -2902,6 +5711,13 @@ impl UndoManager {
         self.0.add_exclude_origin_prefix(prefix)
     }
 
+    /// The changes of the container and its descendants will not be undone or redone, e.g.
+    /// the view settings of an editor. A commit that only changes the excluded containers is
+    /// not recorded in the undo stack.
+    pub fn add_exclude_container(&mut self, id: ContainerID) {
+        self.0.add_exclude_container(id)
+    }
+
     /// Set the maximum number of undo steps. The default value is 100.
     pub fn set_max_undo_steps(&mut self, size: usize) {
         self.0.set_max_undo_steps(size)
@@ -0,0 +1,157 @@
+use loro_common::{ContainerID, PeerID};
+use loro_internal::{
+    cursor::{CannotFindRelativePosition, Cursor},
+    handler::TextDelta,
+};
+
+use crate::{
+    event::{Diff, DiffEvent},
+    LoroDoc,
+};
+
+/// The selection of a remote peer in a text, e.g. shared by [`crate::awareness::Awareness`].
+///
+/// The positions are Unicode indexes. Editors keep them as the remote selections to render,
+/// and move them through the local and imported edits with [`LoroDoc::remap_remote_cursors`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteCursor {
+    pub peer: PeerID,
+    /// The text of the selection
+    pub container: ContainerID,
+    /// Where the selection starts
+    pub anchor: usize,
+    /// Where the selection ends, i.e. the caret. It equals `anchor` if nothing is selected.
+    pub head: usize,
+}
+
+impl RemoteCursor {
+    /// Resolve the cursors shared by the remote peer to the positions in the doc.
+    pub fn resolve(
+        doc: &LoroDoc,
+        peer: PeerID,
+        anchor: &Cursor,
+        head: &Cursor,
+    ) -> Result<Self, CannotFindRelativePosition> {
+        Ok(Self {
+            peer,
+            container: anchor.container.clone(),
+            anchor: doc.get_cursor_pos(anchor)?.current.pos,
+            head: doc.get_cursor_pos(head)?.current.pos,
+        })
+    }
+
+    /// Whether the selection is collapsed to a caret
+    pub fn is_collapsed(&self) -> bool {
+        self.anchor == self.head
+    }
+}
+
+impl LoroDoc {
+    /// Move the remote cursors through the text diffs of the event.
+    ///
+    /// The positions in a deleted range move to the start of the deletion. The text inserted
+    /// at a caret goes before it, as the remote peer is likely typing there, and the text
+    /// inserted at the boundaries of a selection goes outside of it. All the cursors on a
+    /// text are moved in one pass over each diff.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::{LoroDoc, RemoteCursor};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Hello world").unwrap();
+    /// doc.commit();
+    /// let cursors = Arc::new(Mutex::new(vec![RemoteCursor {
+    ///     peer: 2,
+    ///     container: text.id(),
+    ///     anchor: 6,
+    ///     head: 11,
+    /// }]));
+    /// let cursors_clone = cursors.clone();
+    /// let doc_clone = doc.clone();
+    /// let _sub = doc.subscribe_root(Arc::new(move |e| {
+    ///     doc_clone.remap_remote_cursors(&mut cursors_clone.lock().unwrap(), &e);
+    /// }));
+    /// text.insert(0, "Oh, ").unwrap();
+    /// doc.commit();
+    /// assert_eq!(cursors.lock().unwrap()[0].anchor, 10);
+    /// assert_eq!(cursors.lock().unwrap()[0].head, 15);
+    /// ```
+    pub fn remap_remote_cursors(&self, cursors: &mut [RemoteCursor], event: &DiffEvent) {
+        for diff in event.events.iter() {
+            let Diff::Text(delta) = &diff.diff else {
+                continue;
+            };
+
+            // (position, whether it moves after the text inserted at it, cursor index,
+            // whether it's the head)
+            let mut points = Vec::new();
+            for (i, c) in cursors.iter().enumerate() {
+                if &c.container != diff.target {
+                    continue;
+                }
+
+                let start = c.anchor.min(c.head);
+                points.push((c.anchor, c.anchor == start, i, false));
+                points.push((c.head, c.head == start, i, true));
+            }
+
+            if points.is_empty() {
+                continue;
+            }
+
+            // The points that stay before the insertions come first at the same position
+            points.sort_unstable_by_key(|p| (p.0, p.1));
+            let positions: Vec<_> = points.iter().map(|p| (p.0, p.1)).collect();
+            for (new_pos, (_, _, i, is_head)) in
+                map_positions(delta, &positions).into_iter().zip(points)
+            {
+                if is_head {
+                    cursors[i].head = new_pos;
+                } else {
+                    cursors[i].anchor = new_pos;
+                }
+            }
+        }
+    }
+}
+
+/// Map the positions through the delta in one pass.
+///
+/// The positions are sorted, paired with whether they move after the text inserted at them.
+/// The ones that don't move come first at the same position.
+fn map_positions(delta: &[TextDelta], positions: &[(usize, bool)]) -> Vec<usize> {
+    let mut ans = Vec::with_capacity(positions.len());
+    let mut iter = positions.iter().peekable();
+    // The position in the old text and the new text that the delta has reached
+    let mut old = 0;
+    let mut new = 0;
+    for item in delta {
+        match item {
+            TextDelta::Retain { retain, .. } => {
+                while let Some((pos, _)) = iter.next_if(|(pos, _)| *pos < old + retain) {
+                    ans.push(new + pos - old);
+                }
+                old += retain;
+                new += retain;
+            }
+            TextDelta::Delete { delete } => {
+                while iter.next_if(|(pos, _)| *pos < old + delete).is_some() {
+                    ans.push(new);
+                }
+                old += delete;
+            }
+            TextDelta::Insert { insert, .. } => {
+                while iter.next_if(|(pos, moves)| *pos == old && !moves).is_some() {
+                    ans.push(new);
+                }
+                new += insert.chars().count();
+            }
+        }
+    }
+
+    ans.extend(iter.map(|(pos, _)| new + pos - old));
+    ans
+}
@@ -0,0 +1,276 @@
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+use loro_internal::{
+    cursor::{Cursor, Side},
+    handler::TextDelta,
+    HandlerTrait, LoroError, LoroResult, LoroValue, Subscription,
+};
+
+use crate::{
+    event::{Diff, DiffEvent},
+    LoroText,
+};
+
+/// A view of a range of a [`LoroText`], created by [`LoroText::sub_view`].
+///
+/// The positions passed to its methods are relative to the start of the view, and the edits
+/// are clamped to the view. The range is tracked, so it moves and grows or shrinks with the
+/// edits on the text, including the concurrent ones. The text inserted right at either
+/// boundary of the view belongs to the view.
+#[derive(Debug, Clone)]
+pub struct TextView {
+    text: LoroText,
+    /// Anchored to the character before the view, `None` if the view starts at the
+    /// start of the text
+    start: Option<Cursor>,
+    /// Anchored to the character after the view
+    end: Cursor,
+}
+
+/// A callback that receives the deltas of the edits inside a [`TextView`],
+/// relative to the start of the view.
+pub type TextViewSubscriber = Arc<dyn Fn(Vec<TextDelta>) + Send + Sync>;
+
+impl LoroText {
+    /// Create a view of the range of the text, whose edits are kept inside the range.
+    ///
+    /// The range is in Unicode indexes. It's tracked by the cursors of its boundaries, so it
+    /// stays on the same characters while the text is edited. It fails if the text is
+    /// detached or the range is out of bound.
+    ///
+    /// # Example
+    /// ```
+    /// use loro::LoroDoc;
+    ///
+    /// let doc = LoroDoc::new();
+    /// let text = doc.get_text("text");
+    /// text.insert(0, "Title: Hello!").unwrap();
+    /// let view = text.sub_view(7..12).unwrap();
+    /// text.insert(0, "# ").unwrap();
+    /// view.insert(5, " world").unwrap();
+    /// view.delete(0, 100).unwrap();
+    /// view.insert(0, "Hi").unwrap();
+    /// assert_eq!(text.to_string(), "# Title: Hi!");
+    /// assert_eq!(view.range(), 9..11);
+    /// ```
+    pub fn sub_view(&self, range: Range<usize>) -> LoroResult<TextView> {
+        if !self.is_attached() {
+            return Err(LoroError::MisuseDetachedContainer { method: "sub_view" });
+        }
+
+        let len = self.len_unicode();
+        if range.start > range.end || range.end > len {
+            return Err(LoroError::OutOfBound {
+                pos: range.end,
+                len,
+                info: format!("Position: {}:{}", file!(), line!()).into_boxed_str(),
+            });
+        }
+
+        let start = match range.start {
+            0 => None,
+            start => Some(self.get_cursor(start - 1, Side::Right).unwrap()),
+        };
+        let end = self.get_cursor(range.end, Side::Left).unwrap();
+        Ok(TextView {
+            text: self.clone(),
+            start,
+            end,
+        })
+    }
+}
+
+impl TextView {
+    /// The text that the view is on
+    pub fn text(&self) -> &LoroText {
+        &self.text
+    }
+
+    /// The current range of the view in the text, in Unicode indexes.
+    pub fn range(&self) -> Range<usize> {
+        let doc = self.text.handler.doc().unwrap();
+        let start = match &self.start {
+            None => 0,
+            Some(cursor) => match doc.query_pos(cursor) {
+                // The anchor is alive, the view starts after it
+                Ok(pos) if pos.update.is_none() => pos.current.pos + 1,
+                Ok(pos) => pos.current.pos,
+                Err(_) => 0,
+            },
+        };
+        let end = doc
+            .query_pos(&self.end)
+            .map(|pos| pos.current.pos)
+            .unwrap_or_else(|_| self.text.len_unicode());
+        start..end.max(start)
+    }
+
+    /// The length of the view in Unicode characters
+    pub fn len_unicode(&self) -> usize {
+        self.range().len()
+    }
+
+    /// Whether the view is empty
+    pub fn is_empty(&self) -> bool {
+        self.range().is_empty()
+    }
+
+    /// Insert a string at the position in the view. The position is clamped to the view.
+    pub fn insert(&self, pos: usize, s: &str) -> LoroResult<()> {
+        let range = self.range();
+        self.text.insert(range.start + pos.min(range.len()), s)
+    }
+
+    /// Delete the characters in the view. The range is clamped to the view.
+    pub fn delete(&self, pos: usize, len: usize) -> LoroResult<()> {
+        let range = self.clamp(pos..pos.saturating_add(len));
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        self.text.delete(range.start, range.len())
+    }
+
+    /// Mark the range of the view with the style. The range is clamped to the view.
+    ///
+    /// See [`LoroText::mark`].
+    pub fn mark(
+        &self,
+        range: Range<usize>,
+        key: &str,
+        value: impl Into<LoroValue>,
+    ) -> LoroResult<()> {
+        let range = self.clamp(range);
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        self.text.mark(range, key, value)
+    }
+
+    /// Unmark the style in the range of the view. The range is clamped to the view.
+    ///
+    /// See [`LoroText::unmark`].
+    pub fn unmark(&self, range: Range<usize>, key: &str) -> LoroResult<()> {
+        let range = self.clamp(range);
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        self.text.unmark(range, key)
+    }
+
+    /// Subscribe to the edits inside the view.
+    ///
+    /// The callback receives the part of each text event that is inside the view, with the
+    /// positions relative to the start of the view before the event.
+    pub fn subscribe(&self, callback: TextViewSubscriber) -> Subscription {
+        let doc = self.text.handler.doc().unwrap();
+        let id = self.text.id();
+        let target = id.clone();
+        let window = Arc::new(Mutex::new(self.range()));
+        doc.subscribe(
+            &id,
+            Arc::new(move |e| {
+                for diff in DiffEvent::from(e).events {
+                    if diff.target != &target {
+                        continue;
+                    }
+
+                    let Diff::Text(delta) = diff.diff else {
+                        continue;
+                    };
+
+                    let mut window = window.lock().unwrap();
+                    let (delta, new_window) = clip_delta(&delta, window.clone());
+                    *window = new_window;
+                    drop(window);
+                    if !delta.is_empty() {
+                        callback(delta);
+                    }
+                }
+            }),
+        )
+    }
+
+    /// Convert the range in the view to the range in the text
+    fn clamp(&self, range: Range<usize>) -> Range<usize> {
+        let view = self.range();
+        let start = view.start + range.start.min(view.len());
+        let end = view.start + range.end.min(view.len());
+        start..end.max(start)
+    }
+}
+
+/// Keep the part of the text delta inside the window, relative to the start of the window.
+///
+/// Returns the clipped delta and the window after the delta. The insertions at the
+/// boundaries of the window are inside it.
+fn clip_delta(delta: &[TextDelta], window: Range<usize>) -> (Vec<TextDelta>, Range<usize>) {
+    let mut ans = Vec::new();
+    // The position in the text before the delta
+    let mut pos = 0;
+    // The position in the window before the delta that `ans` has reached
+    let mut out_pos = 0;
+    let mut start = window.start;
+    let mut len = window.len();
+    let overlap = |from: usize, to: usize| from.max(window.start)..to.min(window.end);
+    let seek = |ans: &mut Vec<TextDelta>, out_pos: &mut usize, target: usize| {
+        if target > *out_pos {
+            ans.push(TextDelta::Retain {
+                retain: target - *out_pos,
+                attributes: None,
+            });
+            *out_pos = target;
+        }
+    };
+    for item in delta {
+        match item {
+            TextDelta::Retain { retain, attributes } => {
+                let o = overlap(pos, pos + retain);
+                if !o.is_empty() && attributes.is_some() {
+                    seek(&mut ans, &mut out_pos, o.start - window.start);
+                    ans.push(TextDelta::Retain {
+                        retain: o.len(),
+                        attributes: attributes.clone(),
+                    });
+                    out_pos += o.len();
+                }
+                pos += retain;
+            }
+            TextDelta::Delete { delete } => {
+                let o = overlap(pos, pos + delete);
+                if o.is_empty() {
+                    if pos < window.start {
+                        start -= (*delete).min(window.start - pos);
+                    }
+                } else {
+                    start -= o.start - pos.min(o.start);
+                    seek(&mut ans, &mut out_pos, o.start - window.start);
+                    ans.push(TextDelta::Delete { delete: o.len() });
+                    out_pos += o.len();
+                    len -= o.len();
+                }
+                pos += delete;
+            }
+            TextDelta::Insert { insert, attributes } => {
+                let n = insert.chars().count();
+                if pos < window.start {
+                    start += n;
+                } else if pos <= window.end {
+                    seek(&mut ans, &mut out_pos, pos - window.start);
+                    ans.push(TextDelta::Insert {
+                        insert: insert.clone(),
+                        attributes: attributes.clone(),
+                    });
+                    len += n;
+                }
+            }
+        }
+    }
+
+    (ans, start..start + len)
+}
@@ -0,0 +1,291 @@
+//! Replay the handler traces recorded by `startTrace` / `stopTrace` in loro-wasm.
+//!
+//! Every `*.json` file in `tests/traces` is replayed on [`LoroDoc`], and the final states of
+//! the docs are compared with the checkpoints of the trace. To turn a bug reported from JS
+//! into a regression test, record the trace of the reproduction and drop it into the folder.
+use std::path::Path;
+
+use loro::{
+    handler_trace::{HandlerTrace, TraceAction},
+    Container, ContainerType, ExpandType, Frontiers, LoroDoc, LoroResult, LoroText, StyleConfig,
+    StyleConfigMap, ToJson,
+};
+
+/// Convert the UTF-16 index passed by JS to the Unicode index used by the Rust API
+fn unicode_pos(text: &LoroText, utf16_pos: usize) -> usize {
+    let s = text.to_string();
+    let mut utf16 = 0;
+    for (i, c) in s.chars().enumerate() {
+        if utf16 >= utf16_pos {
+            return i;
+        }
+        utf16 += c.len_utf16();
+    }
+
+    // Out of bound positions stay out of bound, so that a diverged replay fails instead of
+    // editing another position
+    s.chars().count() + utf16_pos.saturating_sub(utf16)
+}
+
+fn apply(doc: &LoroDoc, action: &TraceAction) -> LoroResult<()> {
+    match action {
+        TraceAction::NewDoc { .. } => unreachable!(),
+        TraceAction::SetPeerId { peer } => doc.set_peer_id(*peer)?,
+        TraceAction::Commit => doc.commit(),
+        TraceAction::Import { bytes } => {
+            doc.import(bytes)?;
+        }
+        TraceAction::Checkout { frontiers } => {
+            doc.checkout(&frontiers.iter().copied().collect::<Frontiers>())?
+        }
+        TraceAction::CheckoutToLatest => doc.checkout_to_latest(),
+        TraceAction::ConfigTextStyle { styles } => {
+            let mut config = StyleConfigMap::new();
+            for (key, expand) in styles {
+                config.insert(
+                    key.as_str().into(),
+                    StyleConfig {
+                        expand: ExpandType::try_from_str(expand).unwrap(),
+                        value_type: None,
+                    },
+                );
+            }
+            doc.config_text_style(config);
+        }
+        TraceAction::TextInsert {
+            container,
+            pos,
+            text: s,
+        } => {
+            let text = doc.get_text(container);
+            text.insert(unicode_pos(&text, *pos), s)?;
+        }
+        TraceAction::TextDelete {
+            container,
+            pos,
+            len,
+        } => {
+            let text = doc.get_text(container);
+            let start = unicode_pos(&text, *pos);
+            let end = unicode_pos(&text, pos + len);
+            text.delete(start, end - start)?;
+        }
+        TraceAction::TextMark {
+            container,
+            start,
+            end,
+            key,
+            value,
+        } => {
+            let text = doc.get_text(container);
+            let range = unicode_pos(&text, *start)..unicode_pos(&text, *end);
+            text.mark(range, key, value.clone())?;
+        }
+        TraceAction::TextUnmark {
+            container,
+            start,
+            end,
+            key,
+        } => {
+            let text = doc.get_text(container);
+            let range = unicode_pos(&text, *start)..unicode_pos(&text, *end);
+            text.unmark(range, key)?;
+        }
+        TraceAction::MapSet {
+            container,
+            key,
+            value,
+        } => doc.get_map(container).insert(key, value.clone())?,
+        TraceAction::MapDelete { container, key } => doc.get_map(container).delete(key)?,
+        TraceAction::MapSetContainer {
+            container,
+            key,
+            kind,
+        } => {
+            doc.get_map(container)
+                .insert_container(key, Container::new(*kind))?;
+        }
+        TraceAction::ListInsert {
+            container,
+            pos,
+            value,
+        } => match container.container_type() {
+            ContainerType::MovableList => doc
+                .get_movable_list(container)
+                .insert(*pos, value.clone())?,
+            _ => doc.get_list(container).insert(*pos, value.clone())?,
+        },
+        TraceAction::ListDelete {
+            container,
+            pos,
+            len,
+        } => match container.container_type() {
+            ContainerType::MovableList => doc.get_movable_list(container).delete(*pos, *len)?,
+            _ => doc.get_list(container).delete(*pos, *len)?,
+        },
+        TraceAction::ListInsertContainer {
+            container,
+            pos,
+            kind,
+        } => {
+            let child = Container::new(*kind);
+            match container.container_type() {
+                ContainerType::MovableList => {
+                    doc.get_movable_list(container)
+                        .insert_container(*pos, child)?;
+                }
+                _ => {
+                    doc.get_list(container).insert_container(*pos, child)?;
+                }
+            }
+        }
+        TraceAction::MovableListSet {
+            container,
+            pos,
+            value,
+        } => doc.get_movable_list(container).set(*pos, value.clone())?,
+        TraceAction::MovableListSetContainer {
+            container,
+            pos,
+            kind,
+        } => {
+            doc.get_movable_list(container)
+                .set_container(*pos, Container::new(*kind))?;
+        }
+        TraceAction::MovableListMove {
+            container,
+            from,
+            to,
+        } => doc.get_movable_list(container).mov(*from, *to)?,
+        TraceAction::TreeCreate {
+            container,
+            parent,
+            index,
+        } => {
+            let tree = doc.get_tree(container);
+            match index {
+                Some(index) => tree.create_at(*parent, *index)?,
+                None => tree.create(*parent)?,
+            };
+        }
+        TraceAction::TreeMove {
+            container,
+            target,
+            parent,
+            index,
+        } => {
+            let tree = doc.get_tree(container);
+            match index {
+                Some(index) => tree.mov_to(*target, *parent, *index)?,
+                None => tree.mov(*target, *parent)?,
+            }
+        }
+        TraceAction::TreeMoveAfter {
+            container,
+            target,
+            other,
+        } => doc.get_tree(container).mov_after(*target, *other)?,
+        TraceAction::TreeMoveBefore {
+            container,
+            target,
+            other,
+        } => doc.get_tree(container).mov_before(*target, *other)?,
+        TraceAction::TreeDelete { container, target } => doc.get_tree(container).delete(*target)?,
+        #[cfg(feature = "counter")]
+        TraceAction::CounterIncrement { container, value } => {
+            doc.get_counter(container).increment(*value)?
+        }
+        #[cfg(not(feature = "counter"))]
+        TraceAction::CounterIncrement { .. } => {
+            unreachable!("the traces with counters are skipped without the `counter` feature")
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the trace can only be replayed with the `counter` feature
+fn uses_counters(trace: &HandlerTrace) -> bool {
+    trace
+        .steps
+        .iter()
+        .any(|step| matches!(step.action, TraceAction::CounterIncrement { .. }))
+}
+
+/// Replay the trace and check the states of the docs against its checkpoints.
+fn replay(trace: &HandlerTrace) -> Vec<LoroDoc> {
+    let mut docs: Vec<LoroDoc> = Vec::new();
+    for (i, step) in trace.steps.iter().enumerate() {
+        if let TraceAction::NewDoc { peer, snapshot } = &step.action {
+            assert_eq!(step.doc, docs.len());
+            let doc = LoroDoc::new();
+            if let Some(snapshot) = snapshot {
+                doc.import(snapshot).unwrap();
+            }
+            doc.set_peer_id(*peer).unwrap();
+            docs.push(doc);
+            continue;
+        }
+
+        // Only the calls that succeeded in JS are recorded
+        if let Err(e) = apply(&docs[step.doc], &step.action) {
+            panic!("step {} of doc {} failed: {}", i, step.doc, e);
+        }
+    }
+
+    for checkpoint in &trace.checkpoints {
+        let doc = &docs[checkpoint.doc];
+        assert_eq!(
+            serde_json::to_value(doc.get_deep_value()).unwrap(),
+            checkpoint.value,
+            "the state of doc {} diverged",
+            checkpoint.doc
+        );
+        assert_eq!(
+            doc.oplog_vv(),
+            checkpoint.oplog_vv,
+            "the version of doc {} diverged",
+            checkpoint.doc
+        );
+    }
+
+    docs
+}
+
+#[test]
+fn replay_recorded_traces() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/traces");
+    let mut replayed = 0;
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+
+        let trace = HandlerTrace::from_json(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        if !cfg!(feature = "counter") && uses_counters(&trace) {
+            continue;
+        }
+
+        replay(&trace);
+        replayed += 1;
+    }
+
+    assert!(replayed > 0);
+}
+
+#[test]
+fn replay_handles_text_positions_in_utf16() {
+    let trace =
+        HandlerTrace::from_json(include_str!("traces/text_with_surrogate_pairs.json")).unwrap();
+    let docs = replay(&trace);
+    let text = docs[0].get_text("text");
+    assert_eq!(text.to_string(), "Hello 😀World");
+    assert_eq!(
+        text.get_richtext_value().to_json_value(),
+        serde_json::json!([
+            { "insert": "Hello", "attributes": { "bold": true } },
+            { "insert": " 😀World" },
+        ])
+    );
+}
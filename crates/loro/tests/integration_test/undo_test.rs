@@ -627,6 +627,7 @@ fn undo_richtext_conflict_set_style() -> LoroResult<()> {
         "color".into(),
         StyleConfig {
             expand: loro::ExpandType::After,
+            value_type: None,
         },
     );
     doc_a.config_text_style(config.clone());
@@ -1448,6 +1449,36 @@ fn undo_collab_list_move() -> LoroResult<()> {
     Ok(())
 }
 
+#[test]
+fn exclude_containers_from_undo() -> anyhow::Result<()> {
+    let doc = LoroDoc::new();
+    let mut undo = UndoManager::new(&doc);
+    let settings = doc.get_map("settings");
+    undo.add_exclude_container(settings.id());
+    let text = doc.get_text("text");
+    text.insert(0, "hello")?;
+    settings.insert("theme", "dark")?;
+    doc.commit();
+    // Only changes the excluded containers, so it's not an undo step
+    let zoom = settings.insert_container("zoom", LoroMap::new())?;
+    zoom.insert("level", 2)?;
+    doc.commit();
+    text.insert(5, " world")?;
+    doc.commit();
+
+    let settings_value = json!({"theme": "dark", "zoom": {"level": 2}});
+    undo.undo()?;
+    assert_eq!(text.to_string(), "hello");
+    undo.undo()?;
+    assert_eq!(text.to_string(), "");
+    assert_eq!(settings.get_deep_value().to_json_value(), settings_value);
+    assert!(!undo.can_undo());
+    undo.redo()?;
+    assert_eq!(text.to_string(), "hello");
+    assert_eq!(settings.get_deep_value().to_json_value(), settings_value);
+    Ok(())
+}
+
 #[test]
 fn exclude_certain_local_ops_from_undo() -> anyhow::Result<()> {
     let doc = LoroDoc::new();
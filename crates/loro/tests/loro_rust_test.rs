@@ -7,7 +7,7 @@ use std::{
     collections::HashSet,
     ops::ControlFlow,
     sync::{
-        atomic::{AtomicBool, AtomicU64},
+        atomic::{AtomicBool, AtomicU64, AtomicUsize},
         Arc,
     },
 };
@@ -15,9 +15,9 @@ use std::{
 use loro::{
     awareness::Awareness,
     event::{Diff, DiffBatch, ListDiffItem},
-    loro_value, CommitOptions, ContainerID, ContainerTrait, ContainerType, ExportMode, Frontiers,
-    FrontiersNotIncluded, IdSpan, Index, LoroDoc, LoroError, LoroList, LoroMap, LoroStringValue,
-    LoroText, LoroValue, ToJson, TreeParentId,
+    loro_value, AnchorFallback, CommitOptions, ContainerID, ContainerTrait, ContainerType,
+    ExportMode, Frontiers, FrontiersNotIncluded, IdLp, IdSpan, IgnoredOp, Index, LoroDoc,
+    LoroError, LoroList, LoroMap, LoroStringValue, LoroText, LoroValue, ToJson, TreeParentId,
 };
 use loro_internal::{
     encoding::EncodedBlobMode, fx_map, handler::TextDelta, id::ID, version_range, vv, LoroResult,
@@ -3042,6 +3042,36 @@ fn test_update_long_text() {
     assert_eq!(doc.get_text("text").to_string(), text);
 }
 
+#[test]
+fn update_text_by_words_keeps_concurrent_edits() {
+    use loro::DiffMode;
+
+    let doc = LoroDoc::new();
+    let text = doc.get_text("text");
+    text.insert(0, "Hello world. Bye now.").unwrap();
+    doc.commit();
+    let other = doc.fork();
+    other.get_text("text").insert(6, "big ").unwrap();
+    other.commit();
+
+    text.update_with_mode(
+        "Hello world. Goodbye now.",
+        DiffMode::Word,
+        Default::default(),
+    )
+    .unwrap();
+    doc.commit();
+    doc.import(&other.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    assert_eq!(text.to_string(), "Hello big world. Goodbye now.");
+
+    for mode in [DiffMode::Char, DiffMode::Sentence, DiffMode::Line] {
+        text.update_with_mode("First.\nSecond one. Third.", mode, Default::default())
+            .unwrap();
+        assert_eq!(text.to_string(), "First.\nSecond one. Third.");
+    }
+}
+
 #[test]
 fn test_loro_tree_move() {
     let doc = LoroDoc::new();
@@ -3306,3 +3336,3056 @@ fn test_apply_delta_on_the_end() {
         ]
     );
 }
+
+#[test]
+fn checkout_containers() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let a = doc.get_text("a");
+    let b = doc.get_list("b");
+    a.insert(0, "Hello").unwrap();
+    b.push(1).unwrap();
+    doc.commit();
+    let v = doc.oplog_frontiers();
+    a.insert(5, " World").unwrap();
+    b.push(2).unwrap();
+    doc.commit();
+
+    doc.checkout_containers(&v, &[a.id()]).unwrap();
+    assert!(doc.is_detached());
+    assert!(doc.is_partially_checked_out());
+    assert_eq!(a.to_string(), "Hello");
+    assert_eq!(b.get_value().to_json_value(), json!([1, 2]));
+    assert_eq!(doc.get_container_version(&a.id()), v);
+    assert_eq!(doc.get_container_version(&b.id()), doc.oplog_frontiers());
+    assert!(a.insert(0, "x").is_err());
+
+    doc.checkout_to_latest();
+    assert!(!doc.is_partially_checked_out());
+    assert_eq!(a.to_string(), "Hello World");
+    assert_eq!(b.get_value().to_json_value(), json!([1, 2]));
+}
+
+#[test]
+fn subscribe_version() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let versions = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let versions_clone = versions.clone();
+    let _sub = doc.subscribe_version(Box::new(move |f, vv| {
+        versions_clone.lock().unwrap().push((f.clone(), vv.clone()));
+        true
+    }));
+    doc.get_text("text").insert(0, "Hello").unwrap();
+    doc.commit();
+    // Empty commits don't advance the version
+    doc.commit();
+    assert_eq!(versions.lock().unwrap().len(), 1);
+    assert_eq!(versions.lock().unwrap()[0].0, doc.oplog_frontiers());
+
+    let doc2 = LoroDoc::new();
+    doc2.set_peer_id(2).unwrap();
+    doc2.get_map("map").insert("key", 1).unwrap();
+    doc.import(&doc2.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    let versions = versions.lock().unwrap();
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[1].1, doc.oplog_vv());
+}
+
+#[test]
+fn import_event_coalescing_window() {
+    let doc = LoroDoc::new();
+    doc.set_import_event_coalescing_window(3);
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let events_clone = events.clone();
+    let _sub = doc.subscribe_root(Arc::new(move |e| {
+        for e in e.events {
+            events_clone
+                .lock()
+                .unwrap()
+                .push(e.diff.as_text().unwrap().clone());
+        }
+    }));
+
+    let remote = LoroDoc::new();
+    let text = remote.get_text("text");
+    let mut updates = Vec::new();
+    for (i, s) in ["a", "b", "c", "d"].iter().enumerate() {
+        let vv = remote.oplog_vv();
+        text.insert(i, s).unwrap();
+        updates.push(remote.export(ExportMode::updates(&vv)).unwrap());
+    }
+
+    doc.import(&updates[0]).unwrap();
+    doc.import(&updates[1]).unwrap();
+    assert!(events.lock().unwrap().is_empty());
+    doc.import(&updates[2]).unwrap();
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![vec![TextDelta::Insert {
+            insert: "abc".into(),
+            attributes: None,
+        }]]
+    );
+
+    doc.import(&updates[3]).unwrap();
+    assert_eq!(events.lock().unwrap().len(), 1);
+    doc.flush_pending_events();
+    assert_eq!(events.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn durable_subscription() {
+    let doc = LoroDoc::new();
+    let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sub = {
+        let received = received.clone();
+        doc.subscribe_durable(
+            8,
+            Arc::new(move |seq, e| {
+                let delta = e.events[0].diff.as_text().unwrap().clone();
+                received.lock().unwrap().push((seq, delta));
+            }),
+        )
+    };
+
+    let text = doc.get_text("text");
+    text.insert(0, "a").unwrap();
+    doc.commit();
+    // The consumer persists the version of the doc with the batches it applied
+    let persisted = doc.state_frontiers();
+    text.insert(1, "b").unwrap();
+    doc.commit();
+    text.insert(2, "c").unwrap();
+    doc.commit();
+    assert_eq!(sub.last_seq(), 3);
+    assert_eq!(sub.retained_len(), 3);
+
+    // The consumer failed to apply the batches after the first one
+    received.lock().unwrap().clear();
+    assert_eq!(sub.resume(1), Ok(2));
+    let seqs: Vec<u64> = received.lock().unwrap().iter().map(|(s, _)| *s).collect();
+    assert_eq!(seqs, vec![2, 3]);
+    assert_eq!(
+        received.lock().unwrap()[0].1,
+        vec![
+            TextDelta::Retain {
+                retain: 1,
+                attributes: None
+            },
+            TextDelta::Insert {
+                insert: "b".into(),
+                attributes: None
+            }
+        ]
+    );
+    sub.ack(3);
+    assert_eq!(sub.retained_len(), 0);
+    assert_eq!(sub.acked_seq(), 3);
+
+    // The retained batches are lost with the process. A new subscription starts from 1, and
+    // the mirror catches up from the version it persisted with a diff
+    drop(sub);
+    let mirror = doc.fork_at(&persisted);
+    mirror
+        .apply_diff(doc.diff(&persisted, &doc.state_frontiers()).unwrap())
+        .unwrap();
+    assert_eq!(mirror.get_text("text").to_string(), "abc");
+    received.lock().unwrap().clear();
+    let sub = {
+        let received = received.clone();
+        doc.subscribe_durable(
+            8,
+            Arc::new(move |seq, e| {
+                let delta = e.events[0].diff.as_text().unwrap().clone();
+                received.lock().unwrap().push((seq, delta));
+            }),
+        )
+    };
+    text.insert(3, "d").unwrap();
+    doc.commit();
+    assert_eq!(sub.last_seq(), 1);
+    assert_eq!(received.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn value_interning() {
+    let doc = LoroDoc::new();
+    doc.set_value_interning(true);
+    assert!(doc.is_value_interning_enabled());
+    let list = doc.get_list("logs");
+    let map = doc.get_map("meta");
+    for i in 0..10 {
+        list.push(if i % 2 == 0 { "info" } else { "warn" }).unwrap();
+        map.insert(&i.to_string(), "info").unwrap();
+    }
+    doc.commit();
+    let ptr = |v: LoroValue| v.as_string().unwrap().as_ptr();
+    let info = ptr(list.get(0).unwrap().into_value().unwrap());
+    assert_eq!(ptr(list.get(2).unwrap().into_value().unwrap()), info);
+    assert_eq!(ptr(map.get("3").unwrap().into_value().unwrap()), info);
+    assert_ne!(ptr(list.get(1).unwrap().into_value().unwrap()), info);
+
+    let doc2 = LoroDoc::new();
+    doc2.set_value_interning(true);
+    doc2.import(&doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    assert_eq!(doc.get_deep_value(), doc2.get_deep_value());
+    let list2 = doc2.get_list("logs");
+    assert_eq!(
+        list2.get(1).unwrap().into_value().unwrap(),
+        LoroValue::from("warn")
+    );
+    assert_eq!(
+        ptr(list2.get(1).unwrap().into_value().unwrap()),
+        ptr(list2.get(3).unwrap().into_value().unwrap())
+    );
+    assert_eq!(
+        ptr(doc2.get_map("meta").get("1").unwrap().into_value().unwrap()),
+        ptr(list2.get(0).unwrap().into_value().unwrap())
+    );
+
+    doc.set_value_interning(false);
+    assert!(!doc.is_value_interning_enabled());
+    list.push("info").unwrap();
+    assert_eq!(list.len(), 11);
+}
+
+#[test]
+fn infer_schema() {
+    let doc = LoroDoc::new();
+    let list = doc.get_list("logs");
+    let a = list.insert_container(0, LoroMap::new()).unwrap();
+    a.insert("level", "info").unwrap();
+    a.insert("ts", 1).unwrap();
+    let b = list.insert_container(1, LoroMap::new()).unwrap();
+    b.insert("level", "warn").unwrap();
+    b.insert("ts", 2.5).unwrap();
+    b.insert("tags", vec!["a", "b"]).unwrap();
+    let tree = doc.get_tree("tree");
+    let node = tree.create(None).unwrap();
+    tree.get_meta(node)
+        .unwrap()
+        .insert("title", "root")
+        .unwrap();
+    let child = tree.create(node).unwrap();
+    tree.get_meta(child).unwrap().insert("title", 1).unwrap();
+    doc.get_text("text").insert(0, "hi").unwrap();
+
+    let schema = doc.infer_schema().to_json_value();
+    assert_eq!(
+        schema["$schema"],
+        json!("https://json-schema.org/draft/2020-12/schema")
+    );
+    let logs = &schema["properties"]["logs"];
+    assert_eq!(logs["type"], json!("array"));
+    assert_eq!(logs["x-loro-container"], json!("List"));
+    let record = &logs["items"];
+    assert_eq!(record["x-loro-container"], json!("Map"));
+    assert_eq!(record["properties"]["level"], json!({"type": "string"}));
+    assert_eq!(
+        record["properties"]["ts"],
+        json!({"anyOf": [{"type": "integer"}, {"type": "number"}]})
+    );
+    assert_eq!(
+        record["properties"]["tags"],
+        json!({"type": "array", "items": {"type": "string"}})
+    );
+    let tree = &schema["properties"]["tree"];
+    assert_eq!(tree["x-loro-container"], json!("Tree"));
+    // The nodes refer to their own schema in `children`
+    assert_eq!(tree["items"], json!({"$ref": "#/$defs/treeNode0"}));
+    let node = &schema["$defs"]["treeNode0"];
+    assert_eq!(
+        node["properties"]["meta"]["properties"]["title"],
+        json!({"anyOf": [{"type": "string"}, {"type": "integer"}]})
+    );
+    assert_eq!(
+        node["properties"]["children"],
+        json!({"type": "array", "items": {"$ref": "#/$defs/treeNode0"}})
+    );
+    assert_eq!(
+        schema["properties"]["text"],
+        json!({"type": "string", "x-loro-container": "Text"})
+    );
+}
+
+#[test]
+fn replay_history_in_rounds() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let text = doc.get_text("text");
+    text.insert(0, "ab").unwrap();
+    doc.commit();
+    let doc2 = doc.fork();
+    doc2.set_peer_id(2).unwrap();
+    doc2.get_list("list").push("x").unwrap();
+    doc2.commit();
+    text.insert(2, "c").unwrap();
+    doc.commit();
+    doc.import(&doc2.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+
+    let mut visited = Vec::new();
+    let mut checkpoint = loro::VersionVector::default();
+    loop {
+        let mut n = 0;
+        let next = doc.replay(&checkpoint, &mut |op| {
+            visited.push(op);
+            n += 1;
+            if n == 2 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        if next == checkpoint {
+            break;
+        }
+        checkpoint = next;
+    }
+
+    assert_eq!(checkpoint, doc.oplog_vv());
+    assert_eq!(visited.len(), 3);
+    assert!(visited.windows(2).all(|w| w[0].lamport <= w[1].lamport));
+    assert_eq!(visited[0].id, ID::new(1, 0));
+    assert!(matches!(
+        &visited[0].content,
+        loro::JsonOpContent::Text(loro::JsonTextOp::Insert { text, .. }) if text == "ab"
+    ));
+
+    // Resume after new edits
+    text.insert(0, "z").unwrap();
+    let mut new_ops = Vec::new();
+    doc.replay(&checkpoint, &mut |op| {
+        new_ops.push(op);
+        ControlFlow::Continue(())
+    });
+    assert_eq!(new_ops.len(), 1);
+    assert_eq!(new_ops[0].container, text.id());
+}
+
+#[test]
+fn tree_import_flat() {
+    let doc = LoroDoc::new();
+    let tree = doc.get_tree("fs");
+    let node = |id: &str, parent: Option<&str>, index: usize, size: i64| loro::FlatTreeNode {
+        id: id.to_string(),
+        parent: parent.map(|p| p.to_string()),
+        index,
+        meta: [("size".to_string(), LoroValue::from(size))]
+            .into_iter()
+            .collect(),
+    };
+    let ids = tree
+        .import_flat(
+            &[
+                node("/b", Some("/"), 1, 2),
+                node("/", None, 0, 0),
+                node("/a", Some("/"), 0, 1),
+                node("/a/x", Some("/a"), 0, 3),
+            ],
+            "path",
+        )
+        .unwrap();
+    doc.commit();
+    assert_eq!(
+        tree.children(TreeParentId::Node(ids["/"])).unwrap(),
+        vec![ids["/a"], ids["/b"]]
+    );
+    assert_eq!(
+        tree.get_meta(ids["/a/x"]).unwrap().get_deep_value(),
+        loro_value!({"path": "/a/x", "size": 3})
+    );
+
+    // Drop `/a` with its child, reorder `/b` and add `/b/x`
+    let vv = doc.oplog_vv();
+    let new_ids = tree
+        .import_flat(
+            &[
+                node("/", None, 0, 0),
+                node("/b", Some("/"), 0, 2),
+                node("/b/x", Some("/b"), 0, 4),
+            ],
+            "path",
+        )
+        .unwrap();
+    doc.commit();
+    assert_eq!(new_ids["/"], ids["/"]);
+    assert_eq!(new_ids["/b"], ids["/b"]);
+    assert!(tree.is_node_deleted(&ids["/a"]).unwrap());
+    assert_eq!(
+        tree.children(TreeParentId::Node(ids["/"])).unwrap(),
+        vec![ids["/b"]]
+    );
+    assert_eq!(
+        tree.get_meta(new_ids["/b/x"]).unwrap().get_deep_value(),
+        loro_value!({"path": "/b/x", "size": 4})
+    );
+
+    // Importing the same description again produces no ops
+    let vv2 = doc.oplog_vv();
+    assert_ne!(vv, vv2);
+    tree.import_flat(
+        &[
+            node("/", None, 0, 0),
+            node("/b", Some("/"), 0, 2),
+            node("/b/x", Some("/b"), 0, 4),
+        ],
+        "path",
+    )
+    .unwrap();
+    doc.commit();
+    assert_eq!(doc.oplog_vv(), vv2);
+
+    // Dropping a node before its siblings doesn't move them
+    tree.import_flat(
+        &[
+            node("/", None, 0, 0),
+            node("/c", Some("/"), 0, 5),
+            node("/b", Some("/"), 1, 2),
+            node("/b/x", Some("/b"), 0, 4),
+        ],
+        "path",
+    )
+    .unwrap();
+    doc.commit();
+    let vv3 = doc.oplog_vv();
+    tree.import_flat(
+        &[
+            node("/", None, 0, 0),
+            node("/b", Some("/"), 0, 2),
+            node("/b/x", Some("/b"), 0, 4),
+        ],
+        "path",
+    )
+    .unwrap();
+    doc.commit();
+    let peer = doc.peer_id();
+    assert_eq!(
+        doc.oplog_vv().get(&peer).copied().unwrap_or(0) - vv3.get(&peer).copied().unwrap_or(0),
+        1
+    );
+
+    // Invalid descriptions
+    assert!(tree
+        .import_flat(&[node("/a", Some("/missing"), 0, 0)], "path")
+        .is_err());
+    assert!(tree
+        .import_flat(
+            &[node("/a", Some("/b"), 0, 0), node("/b", Some("/a"), 0, 0)],
+            "path"
+        )
+        .is_err());
+}
+
+#[test]
+fn container_history_size() {
+    let doc = LoroDoc::new();
+    let list = doc.get_list("logs");
+    let map = doc.get_map("settings");
+    for i in 0..200 {
+        list.push(format!("a long log entry number {}", i)).unwrap();
+    }
+    map.insert("theme", "dark").unwrap();
+    doc.commit();
+    let list_size = doc.container_history_size(&list.id());
+    let map_size = doc.container_history_size(&map.id());
+    assert!(list_size > 200 * 20);
+    assert!(map_size > 0 && map_size < 100);
+    assert_eq!(doc.container_history_size(&doc.get_text("empty").id()), 0);
+
+    // The sizes are recalculated after importing a snapshot and updated by new changes
+    let new_doc = LoroDoc::new();
+    new_doc
+        .import(&doc.export(ExportMode::Snapshot).unwrap())
+        .unwrap();
+    assert_eq!(new_doc.container_history_size(&list.id()), list_size);
+    new_doc.get_map("settings").insert("font", "mono").unwrap();
+    new_doc.commit();
+    assert!(new_doc.container_history_size(&map.id()) > map_size);
+    let sizes = new_doc.container_history_sizes();
+    assert_eq!(sizes.get(&list.id()), Some(&list_size));
+
+    doc.import(&new_doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    assert_eq!(
+        doc.container_history_size(&map.id()),
+        new_doc.container_history_size(&map.id())
+    );
+}
+
+#[test]
+fn export_squashed_updates() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    doc.set_change_merge_interval(-1);
+    let text = doc.get_text("text");
+    for (i, ts) in [100, 101, 102, 200, 201].into_iter().enumerate() {
+        text.insert(i, "a").unwrap();
+        doc.set_next_commit_timestamp(ts);
+        doc.commit();
+    }
+
+    let other = LoroDoc::new();
+    other.set_peer_id(2).unwrap();
+    other
+        .import(&doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    other.get_text("text").insert(0, "b").unwrap();
+    other.commit();
+    doc.import(&other.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    // It depends on the change of peer 2, so it's not squashed into the previous change
+    text.insert(0, "c").unwrap();
+    doc.set_next_commit_timestamp(202);
+    doc.commit();
+    assert_eq!(doc.len_changes(), 7);
+
+    let squashed = doc
+        .export(ExportMode::updates_squashed(&Default::default(), 10))
+        .unwrap();
+    assert!(squashed.len() < doc.export(ExportMode::all_updates()).unwrap().len());
+    let follower = LoroDoc::new();
+    follower.import(&squashed).unwrap();
+    assert_eq!(follower.len_changes(), 4);
+    assert_eq!(follower.get_deep_value(), doc.get_deep_value());
+    assert_eq!(follower.oplog_vv(), doc.oplog_vv());
+
+    // The follower can still receive the following updates
+    let vv = follower.oplog_vv();
+    text.insert(0, "d").unwrap();
+    doc.set_next_commit_timestamp(300);
+    doc.commit();
+    follower
+        .import(&doc.export(ExportMode::updates(&vv)).unwrap())
+        .unwrap();
+    assert_eq!(follower.get_deep_value(), doc.get_deep_value());
+}
+
+#[test]
+fn fork_at_shallow() {
+    let doc = LoroDoc::new();
+    doc.set_change_merge_interval(42);
+    let map = doc.get_map("map");
+    map.insert("title", "template").unwrap();
+    map.insert("count", 1).unwrap();
+    doc.commit();
+    let template = doc.state_frontiers();
+    map.insert("title", "edited").unwrap();
+    doc.commit();
+
+    let forked = doc.fork_at_shallow(&template).unwrap();
+    assert!(forked.is_shallow());
+    assert_ne!(forked.peer_id(), doc.peer_id());
+    assert_eq!(forked.config().merge_interval(), 42);
+    assert_eq!(forked.state_frontiers(), template);
+    assert_eq!(
+        forked.get_deep_value().to_json_value(),
+        json!({"map": {"title": "template", "count": 1}})
+    );
+
+    // The forked doc is editable and can still sync with the original doc
+    forked.get_map("map").insert("count", 2).unwrap();
+    forked.commit();
+    forked
+        .import(&doc.export(ExportMode::updates(&forked.oplog_vv())).unwrap())
+        .unwrap();
+    doc.import(&forked.export(ExportMode::updates(&doc.oplog_vv())).unwrap())
+        .unwrap();
+    assert_eq!(forked.get_deep_value(), doc.get_deep_value());
+
+    assert!(doc.fork_at_shallow(&ID::new(123, 0).into()).is_err());
+}
+
+#[test]
+fn map_position_across_versions() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let list = doc.get_list("list");
+    for i in 0..5 {
+        list.push(i).unwrap();
+    }
+    doc.commit();
+    let v1 = doc.state_frontiers();
+
+    let other = doc.fork();
+    other.get_list("list").insert(0, "a").unwrap();
+    other.commit();
+    list.delete(1, 2).unwrap();
+    doc.commit();
+    doc.import(&other.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    let v2 = doc.state_frontiers();
+    assert_eq!(list.get_value().to_json_value(), json!(["a", 0, 3, 4]));
+
+    // The element 3 moves from 3 to 2
+    assert_eq!(doc.map_position(&list.id(), 3, &v1, &v2), Some(2));
+    // The deleted element is mapped to where the deletion happened
+    assert_eq!(doc.map_position(&list.id(), 2, &v1, &v2), Some(2));
+    assert_eq!(doc.map_position(&list.id(), 0, &v1, &v2), Some(1));
+    assert_eq!(doc.map_position(&list.id(), 2, &v2, &v1), Some(3));
+    // The same version
+    assert_eq!(doc.map_position(&list.id(), 4, &v2, &v2), Some(4));
+
+    // A moved element is mapped to the position it was removed from
+    let movable = doc.get_movable_list("movable");
+    for i in 0..4 {
+        movable.push(i).unwrap();
+    }
+    doc.commit();
+    let v3 = doc.state_frontiers();
+    movable.mov(0, 3).unwrap();
+    doc.commit();
+    let v4 = doc.state_frontiers();
+    assert_eq!(movable.get_value().to_json_value(), json!([1, 2, 3, 0]));
+    assert_eq!(doc.map_position(&movable.id(), 0, &v3, &v4), Some(0));
+    assert_eq!(doc.map_position(&movable.id(), 2, &v3, &v4), Some(1));
+    // Only the movable list is changed, so the list positions stay the same
+    assert_eq!(doc.map_position(&list.id(), 1, &v2, &v4), Some(1));
+
+    assert_eq!(
+        doc.map_position(&doc.get_map("map").id(), 0, &v1, &v2),
+        None
+    );
+    assert_eq!(
+        doc.map_position(&list.id(), 0, &ID::new(100, 0).into(), &v2),
+        None
+    );
+    // The doc is not left detached
+    assert!(!doc.is_detached());
+}
+
+#[test]
+fn batch_commits_once_and_rolls_back_on_error() {
+    let doc = LoroDoc::new();
+    // Don't merge the local changes, so that the commits can be counted
+    doc.set_change_merge_interval(-1);
+    let map = doc.get_map("map");
+    let text = doc.get_text("text");
+    map.insert("title", "draft").unwrap();
+    let count = Arc::new(AtomicUsize::new(0));
+    let count_clone = count.clone();
+    let _sub = doc.subscribe_root(Arc::new(move |_| {
+        count_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }));
+
+    doc.batch(|| {
+        for i in 0..5 {
+            text.insert(text.len_unicode(), &i.to_string())?;
+            doc.commit();
+        }
+        map.insert("count", 5)?;
+        Ok(())
+    })
+    .unwrap();
+    // One event batch for the pending changes before the batch, one for the batch
+    assert_eq!(count.load(std::sync::atomic::Ordering::SeqCst), 2);
+    assert_eq!(doc.len_changes(), 2);
+    assert_eq!(text.to_string(), "01234");
+
+    let vv = doc.oplog_vv();
+    let ans = doc.batch(|| {
+        text.delete(0, 3)?;
+        map.insert("title", "final")?;
+        map.insert_container("meta", LoroMap::new())?
+            .insert("a", 1)?;
+        doc.get_list("list").push(1)?;
+        text.insert(100, "out of bound")?;
+        Ok(())
+    });
+    assert!(ans.is_err());
+    assert_eq!(count.load(std::sync::atomic::Ordering::SeqCst), 2);
+    assert_eq!(doc.oplog_vv(), vv);
+    assert_eq!(
+        doc.get_deep_value().to_json_value(),
+        json!({"map": {"title": "draft", "count": 5}, "text": "01234", "list": []})
+    );
+
+    // The doc is still editable and consistent after the rollback
+    text.insert(0, "x").unwrap();
+    doc.commit();
+    assert_eq!(text.to_string(), "x01234");
+    let new_doc = LoroDoc::new();
+    new_doc
+        .import(&doc.export(ExportMode::Snapshot).unwrap())
+        .unwrap();
+    assert_eq!(new_doc.get_deep_value(), doc.get_deep_value());
+    doc.check_state_correctness_slow();
+}
+
+#[test]
+fn tree_overlay_follows_structure() {
+    let doc = LoroDoc::new();
+    let tree = doc.get_tree("tree");
+    let a = tree.create(None).unwrap();
+    let b = tree.create(a).unwrap();
+    let c = tree.create(b).unwrap();
+    let d = tree.create(None).unwrap();
+    doc.commit();
+    let vv = doc.oplog_vv();
+
+    let mut overlay = tree.overlay(i64::MAX);
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let events_clone = events.clone();
+    let _sub = overlay.subscribe(Box::new(move |e| {
+        events_clone
+            .lock()
+            .unwrap()
+            .extend(e.updated.iter().cloned());
+        true
+    }));
+    assert_eq!(overlay.visible_nodes(), vec![a, d]);
+    overlay.set_expanded(a, true).unwrap();
+    overlay.set_expanded(b, true).unwrap();
+    overlay.set(c, "selected", true).unwrap();
+    assert_eq!(overlay.visible_nodes(), vec![a, b, c, d]);
+    assert_eq!(overlay.nodes_with_flag("expanded"), {
+        let mut v = vec![a, b];
+        v.sort();
+        v
+    });
+    assert_eq!(overlay.flags(c).get("selected"), Some(&true.into()));
+    assert_eq!(events.lock().unwrap().len(), 3);
+    overlay.set_expanded(a, false).unwrap();
+    assert_eq!(overlay.visible_nodes(), vec![a, d]);
+    overlay.set_expanded(a, true).unwrap();
+
+    // The overlay follows the structural changes
+    tree.mov(c, d).unwrap();
+    assert_eq!(overlay.visible_nodes(), vec![a, b, d]);
+    overlay.set_expanded(d, true).unwrap();
+    assert_eq!(overlay.visible_nodes(), vec![a, b, d, c]);
+    tree.delete(a).unwrap();
+    assert!(!overlay.is_expanded(b));
+    assert!(overlay.set_expanded(b, true).is_err());
+    assert_eq!(overlay.visible_nodes(), vec![d, c]);
+    let mut pruned = overlay.prune();
+    pruned.sort();
+    let mut expected = vec![a, b];
+    expected.sort();
+    assert_eq!(pruned, expected);
+    assert_eq!(overlay.get(c, "selected"), Some(true.into()));
+
+    // The flags can be shared with another session
+    let mut other = tree.overlay(i64::MAX);
+    other.apply(&overlay.encode_all());
+    assert!(other.is_expanded(d));
+    doc.commit();
+    assert_eq!(
+        doc.oplog_vv().get(&doc.peer_id()).copied(),
+        vv.get(&doc.peer_id()).map(|x| x + 2)
+    );
+}
+
+#[test]
+fn list_containers_with_metadata() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    doc.set_record_timestamp(true);
+    let map = doc.get_map("map");
+    let text = map.insert_container("text", LoroText::new()).unwrap();
+    let list = map.insert_container("list", LoroList::new()).unwrap();
+    let nested = list.insert_container(0, LoroMap::new()).unwrap();
+    doc.commit();
+    map.delete("list").unwrap();
+    doc.commit();
+
+    let doc_b = LoroDoc::new();
+    doc_b
+        .import(&doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    let infos = doc_b.list_containers(&Default::default());
+    assert_eq!(infos.len(), 4);
+    let get = |id: &ContainerID| infos.iter().find(|c| &c.id == id).unwrap();
+
+    let root = get(&map.id());
+    assert!(root.alive);
+    assert_eq!(root.parent, None);
+    assert_eq!(root.created_by, None);
+    assert_eq!(root.created_at, None);
+
+    let text_info = get(&text.id());
+    assert!(text_info.alive);
+    assert_eq!(text_info.container_type, ContainerType::Text);
+    assert_eq!(text_info.parent, Some(map.id()));
+    assert_eq!(text_info.created_by, Some(ID::new(1, 0)));
+    assert!(text_info.created_at.unwrap() > 0);
+    assert_eq!(text_info.path, doc_b.get_path_to_container(&text.id()));
+
+    // The deleted containers are still listed
+    let nested_info = get(&nested.id());
+    assert!(!nested_info.alive);
+    assert_eq!(nested_info.path, None);
+    assert_eq!(nested_info.parent, Some(list.id()));
+    assert!(!get(&list.id()).alive);
+
+    let alive_maps = doc_b.list_containers(&loro::ContainerFilter {
+        container_type: Some(ContainerType::Map),
+        alive_only: true,
+    });
+    assert_eq!(alive_maps.len(), 1);
+    assert_eq!(alive_maps[0].id, map.id());
+}
+
+#[test]
+fn user_maps_that_look_like_tags_keep_their_type() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    let map = doc.get_map("map");
+    let tagged = LoroValue::from(json!({ "🦜ref": "cid:root-a:Map" }));
+    assert!(matches!(tagged, LoroValue::Map(_)));
+    map.insert("tagged", tagged.clone())?;
+    map.insert("escape", LoroValue::from(json!({ "🦜map": 1 })))?;
+    doc.commit();
+
+    // They are escaped in JSON, so they are not parsed as the extended value types
+    let value = doc.get_deep_value();
+    assert_eq!(
+        value.to_json_value()["map"]["tagged"],
+        json!({ "🦜map": { "🦜ref": "cid:root-a:Map" } })
+    );
+    let parsed: LoroValue = serde_json::from_str(&serde_json::to_string(&value).unwrap()).unwrap();
+    assert_eq!(parsed.parse_tags(), value);
+    let from_json = LoroDoc::new();
+    from_json.import_json_updates(doc.export_json_updates(&Default::default(), &doc.oplog_vv()))?;
+    assert_eq!(from_json.get_deep_value(), value);
+
+    // The local ops only parse them after opting in
+    doc.set_extended_value_types(true);
+    map.insert("ref", tagged)?;
+    assert_eq!(
+        map.get_value().get_by_key("ref"),
+        Some(&LoroValue::ContainerRef(
+            ContainerID::try_from("cid:root-a:Map").unwrap()
+        ))
+    );
+    Ok(())
+}
+
+#[test]
+fn container_ref_does_not_own_its_target() {
+    let doc = LoroDoc::new();
+    let a = doc.get_list("a");
+    let b = doc.get_list("b");
+    let item = a.insert_container(0, LoroMap::new()).unwrap();
+    item.insert("name", "x").unwrap();
+    // The older versions can't decode the references, so they must be enabled explicitly
+    assert!(matches!(
+        b.push(LoroValue::ContainerRef(item.id())),
+        Err(LoroError::ExtendedValueTypeDisabled(_))
+    ));
+    doc.set_extended_value_types(true);
+    b.push(LoroValue::ContainerRef(item.id())).unwrap();
+    let map = doc.get_map("map");
+    map.insert(
+        "nested",
+        loro_value!({ "refs": [LoroValue::ContainerRef(a.id())] }),
+    )
+    .unwrap();
+    doc.commit();
+
+    // The reference is not a child, so the target keeps its parent and path
+    assert_eq!(doc.get_path_to_container(&item.id()).unwrap()[0].0, a.id());
+    assert_eq!(
+        b.get_value(),
+        LoroValue::from(vec![LoroValue::ContainerRef(item.id())])
+    );
+    assert_eq!(
+        doc.get_deep_value().to_json_value()["b"][0],
+        json!({ "🦜ref": item.id().to_string() })
+    );
+    // The tags are only parsed on request
+    let json = serde_json::to_string(&doc.get_deep_value()).unwrap();
+    let parsed: LoroValue = serde_json::from_str(&json).unwrap();
+    assert_ne!(parsed, doc.get_deep_value());
+    assert_eq!(parsed.parse_tags(), doc.get_deep_value());
+    // A plain string never turns into a reference
+    let s = LoroValue::from(format!("🦜ref:{}", item.id()));
+    let parsed: LoroValue = serde_json::from_str(&serde_json::to_string(&s).unwrap()).unwrap();
+    assert_eq!(parsed, s);
+
+    // It survives the encodings
+    let snapshot = LoroDoc::from_snapshot(&doc.export(ExportMode::Snapshot).unwrap()).unwrap();
+    assert_eq!(snapshot.get_deep_value(), doc.get_deep_value());
+    let updates = LoroDoc::new();
+    updates
+        .import(&doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    assert_eq!(updates.get_deep_value(), doc.get_deep_value());
+    let from_json = LoroDoc::new();
+    let json_updates = doc.export_json_updates(&Default::default(), &doc.oplog_vv());
+    assert!(matches!(
+        from_json.import_json_updates(json_updates.clone()),
+        Err(LoroError::ExtendedValueTypeDisabled(_))
+    ));
+    assert!(from_json.oplog_vv().is_empty());
+    from_json.set_extended_value_types(true);
+    from_json.import_json_updates(json_updates).unwrap();
+    assert_eq!(from_json.get_deep_value(), doc.get_deep_value());
+
+    // Deleting the target leaves a dangling reference
+    let resolved = updates.resolve_container_ref(&item.id()).unwrap();
+    assert_eq!(resolved.id(), item.id());
+    updates.get_list("a").delete(0, 1).unwrap();
+    updates.commit();
+    assert!(updates.resolve_container_ref(&item.id()).is_none());
+    assert_eq!(
+        updates.dangling_container_refs(),
+        vec![loro::DanglingContainerRef {
+            container: b.id(),
+            target: item.id(),
+        }]
+    );
+    // Deleting the holder drops the dangling reference
+    updates.get_list("b").delete(0, 1).unwrap();
+    assert!(updates.dangling_container_refs().is_empty());
+}
+
+#[test]
+fn import_container_type_conflict() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let list = doc
+        .get_map("m")
+        .insert_container("c", LoroList::new())
+        .unwrap();
+    list.push(1).unwrap();
+    doc.commit();
+    let before = doc.get_deep_value();
+
+    // Another doc with the same peer creates a text container with the same id
+    let other = LoroDoc::new();
+    other.set_peer_id(1).unwrap();
+    let text = other
+        .get_map("m")
+        .insert_container("c", LoroText::new())
+        .unwrap();
+    other.commit();
+    let remote = LoroDoc::from_snapshot(&other.export(ExportMode::Snapshot).unwrap()).unwrap();
+    remote.set_peer_id(2).unwrap();
+    remote.get_text(text.id()).insert(0, "conflict").unwrap();
+    remote.commit();
+    let updates = remote
+        .export(ExportMode::updates(&other.oplog_vv()))
+        .unwrap();
+
+    // Rejected by default
+    assert!(matches!(
+        doc.import(&updates),
+        Err(LoroError::ContainerTypeConflict { .. })
+    ));
+    assert_eq!(doc.get_deep_value(), before);
+    assert_eq!(doc.oplog_vv().get(&2), None);
+    assert!(doc.container_type_conflicts().is_empty());
+
+    doc.set_container_type_conflict_policy(loro::ContainerTypeConflictPolicy::Quarantine);
+    doc.import(&updates).unwrap();
+    assert_eq!(doc.oplog_vv().get(&2), Some(&8));
+    assert_eq!(doc.get_deep_value(), before);
+    assert_eq!(
+        doc.container_type_conflicts(),
+        vec![loro::ContainerTypeConflict {
+            existing: list.id(),
+            incoming: text.id(),
+            change: ID::new(2, 0),
+        }]
+    );
+    // The quarantined container is never reachable
+    assert!(doc.get_path_to_container(&text.id()).is_none());
+
+    // The conflicting containers in the state of a snapshot are checked too
+    let snapshot = doc.export(ExportMode::Snapshot).unwrap();
+    assert!(matches!(
+        LoroDoc::from_snapshot(&snapshot),
+        Err(LoroError::ContainerTypeConflict { .. })
+    ));
+    let new_doc = LoroDoc::new();
+    new_doc.set_container_type_conflict_policy(loro::ContainerTypeConflictPolicy::Quarantine);
+    new_doc.import(&snapshot).unwrap();
+    assert_eq!(new_doc.container_type_conflicts().len(), 1);
+}
+
+#[test]
+fn import_status_stats() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    doc.set_record_timestamp(true);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    doc.get_text("text").insert(0, "abc").unwrap();
+    doc.set_next_commit_timestamp(now - 7200);
+    doc.commit();
+    let update1 = doc.export(ExportMode::all_updates()).unwrap();
+    let vv = doc.oplog_vv();
+    doc.get_text("text").insert(3, "d").unwrap();
+    doc.commit();
+    let update2 = doc.export(ExportMode::updates(&vv)).unwrap();
+
+    let other = LoroDoc::new();
+    let status = other.import(&update2).unwrap();
+    assert_eq!(status.stats.changes, 1);
+    assert_eq!(status.stats.ops, 1);
+    assert_eq!(
+        status.stats.peers.iter().copied().collect::<Vec<_>>(),
+        vec![1]
+    );
+    let age = status.stats.op_age.unwrap();
+    assert_eq!(age.ops, 1);
+    assert!(age.max < 60);
+    assert_eq!(status.stats.causal_gaps.get(&1), Some(&3));
+
+    let status = other.import(&update1).unwrap();
+    assert_eq!(status.stats.ops, 3);
+    let age = status.stats.op_age.unwrap();
+    assert!(age.min >= 7200);
+    assert_eq!(age.buckets[4], 3);
+    assert!(status.stats.causal_gaps.is_empty());
+    assert_eq!(other.get_text("text").to_string(), "abcd");
+}
+
+#[test]
+fn text_paragraph_ids_in_events() {
+    use loro::ParagraphId;
+
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    doc.set_text_paragraph_ids(true);
+    let text = doc.get_text("text");
+    text.insert(0, "a\nb\nc").unwrap();
+    doc.commit();
+    assert_eq!(
+        text.paragraph_ids().unwrap(),
+        vec![
+            ParagraphId::First,
+            ParagraphId::Newline(ID::new(1, 1)),
+            ParagraphId::Newline(ID::new(1, 3)),
+        ]
+    );
+
+    let changed = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let changed_clone = changed.clone();
+    let _sub = doc.subscribe_root(Arc::new(move |e| {
+        for diff in e.events {
+            changed_clone
+                .lock()
+                .unwrap()
+                .push(diff.paragraph_changed.to_vec());
+        }
+    }));
+    let take = || std::mem::take(&mut *changed.lock().unwrap());
+
+    // Edit inside the second paragraph
+    text.insert(2, "x").unwrap();
+    doc.commit();
+    assert_eq!(take(), vec![vec![ParagraphId::Newline(ID::new(1, 1))]]);
+
+    // Split the second paragraph
+    text.insert(3, "\n").unwrap();
+    doc.commit();
+    assert_eq!(
+        take(),
+        vec![vec![
+            ParagraphId::Newline(ID::new(1, 1)),
+            ParagraphId::Newline(ID::new(1, 6)),
+        ]]
+    );
+
+    // Merge the second paragraph into the first one
+    text.delete(1, 1).unwrap();
+    doc.commit();
+    assert_eq!(take(), vec![vec![ParagraphId::First]]);
+    assert_eq!(
+        text.paragraph_ids().unwrap(),
+        vec![
+            ParagraphId::First,
+            ParagraphId::Newline(ID::new(1, 6)),
+            ParagraphId::Newline(ID::new(1, 3)),
+        ]
+    );
+
+    // Style the last paragraph
+    text.mark(5..6, "bold", true).unwrap();
+    doc.commit();
+    assert_eq!(take(), vec![vec![ParagraphId::Newline(ID::new(1, 3))]]);
+}
+
+#[test]
+fn text_lines_keep_their_ids() {
+    use loro::ParagraphId;
+
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let text = doc.get_text("text");
+    text.insert(0, "ab\n\n😀c\n").unwrap();
+    doc.commit();
+    let lines = text.lines().unwrap();
+    assert_eq!(
+        lines,
+        vec![
+            (ParagraphId::First, "ab".to_string()),
+            (ParagraphId::Newline(ID::new(1, 2)), "".to_string()),
+            (ParagraphId::Newline(ID::new(1, 3)), "😀c".to_string()),
+            (ParagraphId::Newline(ID::new(1, 6)), "".to_string()),
+        ]
+    );
+    assert_eq!(
+        lines.iter().map(|x| x.0).collect::<Vec<_>>(),
+        text.paragraph_ids().unwrap()
+    );
+
+    // The lines are split across the chunks of the text
+    let other = LoroDoc::new();
+    other.set_peer_id(2).unwrap();
+    other
+        .import(&doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    other.get_text("text").insert(5, "x\ny").unwrap();
+    other.commit();
+    doc.import(&other.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    text.insert(1, "-").unwrap();
+    doc.commit();
+    assert_eq!(
+        text.lines().unwrap(),
+        vec![
+            (ParagraphId::First, "a-b".to_string()),
+            (ParagraphId::Newline(ID::new(1, 2)), "".to_string()),
+            (ParagraphId::Newline(ID::new(1, 3)), "😀x".to_string()),
+            (ParagraphId::Newline(ID::new(2, 1)), "yc".to_string()),
+            (ParagraphId::Newline(ID::new(1, 6)), "".to_string()),
+        ]
+    );
+    assert_eq!(
+        text.lines().unwrap().len(),
+        text.to_string().split('\n').count()
+    );
+}
+
+#[test]
+fn seeded_randomness_is_deterministic() {
+    let run = |seed: u64| {
+        let doc = LoroDoc::new();
+        doc.set_peer_id(1).unwrap();
+        doc.set_random_seed(Some(seed));
+        let tree = doc.get_tree("tree");
+        tree.enable_fractional_index(8);
+        let root = tree.create(None).unwrap();
+        let a = tree.create(root).unwrap();
+        let b = tree.create(root).unwrap();
+        tree.create_at(root, 1).unwrap();
+        tree.mov_to(b, root, 0).unwrap();
+        doc.commit();
+        let positions: Vec<_> = [a, b]
+            .iter()
+            .map(|&id| tree.fractional_index(id).unwrap())
+            .collect();
+        let forked = doc.fork();
+        let detached = doc.fork();
+        detached.set_detached_editing(true);
+        detached.checkout(&Frontiers::default()).unwrap();
+        (
+            doc.export(ExportMode::Snapshot).unwrap(),
+            positions,
+            forked.peer_id(),
+            detached.peer_id(),
+        )
+    };
+
+    assert_eq!(run(7), run(7));
+    assert_ne!(run(7).2, run(8).2);
+}
+
+#[test]
+fn upgrade_list_to_movable_list() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let root = doc.get_list("root");
+    root.push(1).unwrap();
+    root.push(2).unwrap();
+    let map = doc.get_map("map");
+    let list = map.insert_container("list", LoroList::new()).unwrap();
+    list.push("a").unwrap();
+    let text = list.push_container(LoroText::new()).unwrap();
+    text.insert(0, "hello").unwrap();
+    doc.commit();
+    let expected = doc.get_deep_value().to_json_value();
+
+    let new_root = doc
+        .upgrade_container(&root.id(), ContainerType::MovableList)
+        .unwrap();
+    let new_list = doc
+        .upgrade_container(&list.id(), ContainerType::MovableList)
+        .unwrap();
+    assert_eq!(doc.get_deep_value().to_json_value(), expected);
+    assert!(root.is_empty());
+    assert!(list.is_empty());
+    assert_eq!(
+        doc.get_upgraded_container(&root.id()),
+        Some(new_root.clone())
+    );
+    assert_eq!(
+        doc.get_upgraded_container(&list.id()),
+        Some(new_list.clone())
+    );
+    let new_text = doc.get_upgraded_container(&text.id()).unwrap();
+    assert_eq!(doc.get_text(new_text).to_string(), "hello");
+    assert_eq!(doc.get_upgraded_container(&map.id()), None);
+
+    let movable = doc.get_movable_list(new_list.clone());
+    movable.mov(0, 1).unwrap();
+    doc.commit();
+    assert_eq!(
+        doc.get_deep_value().to_json_value(),
+        json!({"root": [1, 2], "map": {"list": ["hello", "a"]}})
+    );
+
+    // The peers without the knowledge of upgrades see the same state
+    let doc2 = LoroDoc::new();
+    doc2.import(&doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    assert_eq!(doc2.get_deep_value(), doc.get_deep_value());
+    assert_eq!(doc2.get_upgraded_container(&list.id()), Some(new_list));
+
+    assert!(matches!(
+        doc.upgrade_container(&map.id(), ContainerType::MovableList),
+        Err(LoroError::ArgErr(_))
+    ));
+}
+
+#[test]
+fn rotate_peer_id() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let text = doc.get_text("text");
+    text.insert(0, "a").unwrap();
+    let rotated = doc.rotate_peer_id().unwrap();
+    // Rotating again before any change links the new peer to the original one
+    let peer = doc.rotate_peer_id().unwrap();
+    assert_ne!(rotated, peer);
+    text.insert(1, "b").unwrap();
+    doc.commit();
+    assert_eq!(doc.peer_id(), peer);
+    assert_eq!(doc.oplog_vv().get(&peer), Some(&1));
+    assert_eq!(doc.oplog_vv().get(&rotated), None);
+    assert_eq!(doc.get_peer_rotations(), vec![(1, peer)]);
+
+    let doc2 = LoroDoc::new();
+    doc2.import(&doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    assert_eq!(doc2.get_text("text").to_string(), "ab");
+    assert_eq!(doc2.get_peer_rotations(), vec![(1, peer)]);
+
+    doc.batch(|| {
+        assert!(doc.rotate_peer_id().is_err());
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn convergence_digest() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    doc.get_text("text").insert(0, "Hello").unwrap();
+    doc.commit();
+    let v1 = doc.oplog_frontiers();
+    doc.get_map("map").insert("a", 1).unwrap();
+    doc.get_map("map").insert("b", 2).unwrap();
+    doc.commit();
+
+    let replica = LoroDoc::new();
+    replica.set_peer_id(2).unwrap();
+    // Accessing an empty root container doesn't change the digest
+    replica.get_list("list");
+    replica
+        .import(&doc.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    let digest = doc.convergence_digest(&doc.oplog_frontiers()).unwrap();
+    assert_eq!(
+        replica
+            .convergence_digest(&replica.oplog_frontiers())
+            .unwrap(),
+        digest
+    );
+    assert!(replica.verify_convergence(&digest).unwrap().is_none());
+    let old = doc.convergence_digest(&v1).unwrap();
+    assert_ne!(old.hash, digest.hash);
+    assert!(replica.verify_convergence(&old).unwrap().is_none());
+
+    let mut forged = digest.clone();
+    forged.hash[0] ^= 1;
+    let bundle = replica.verify_convergence(&forged).unwrap().unwrap();
+    assert_eq!(bundle.actual, digest.hash);
+    assert_eq!(bundle.value, replica.get_deep_value());
+    let reproduced = LoroDoc::new();
+    reproduced.import(&bundle.updates).unwrap();
+    assert_eq!(reproduced.oplog_vv(), bundle.oplog_vv);
+
+    replica.get_text("text").insert(0, "!").unwrap();
+    replica.commit();
+    assert!(doc.convergence_digest(&replica.oplog_frontiers()).is_err());
+}
+
+#[test]
+fn list_insert_by_neighbor_id() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    let list = doc.get_list("list");
+    list.push("a").unwrap();
+    list.push("b").unwrap();
+    doc.commit();
+    let b = list.get_id_at(1).unwrap();
+
+    // A concurrent edit shifts the index of the anchor
+    let doc2 = doc.fork();
+    doc2.get_list("list").insert(0, "x").unwrap();
+    doc2.commit();
+    doc.import(&doc2.export(ExportMode::all_updates()).unwrap())
+        .unwrap();
+    list.insert_before(b, "before b", AnchorFallback::Error)
+        .unwrap();
+    list.insert_after(b, "after b", AnchorFallback::Error)
+        .unwrap();
+    assert_eq!(
+        list.get_value().to_json_value(),
+        json!(["x", "a", "before b", "b", "after b"])
+    );
+
+    list.delete(3, 1).unwrap();
+    assert!(matches!(
+        list.insert_after(b, 0, AnchorFallback::Error),
+        Err(LoroError::NotFoundError(_))
+    ));
+    list.insert_after(b, "start", AnchorFallback::Start)
+        .unwrap();
+    list.insert_after(b, "end", AnchorFallback::End).unwrap();
+    assert_eq!(
+        list.get_value().to_json_value(),
+        json!(["start", "x", "a", "before b", "after b", "end"])
+    );
+}
+
+#[test]
+fn apply_log_records_ignored_ops() {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1).unwrap();
+    doc.set_record_apply_log(true);
+    let map = doc.get_map("map");
+    map.insert("key", "a").unwrap();
+    doc.commit();
+
+    // A concurrent edit with a smaller peer id loses
+    let doc0 = LoroDoc::new();
+    doc0.set_peer_id(0).unwrap();
+    doc0.get_map("map").insert("key", "b").unwrap();
+    doc0.commit();
+    let updates = doc0.export(ExportMode::all_updates()).unwrap();
+    doc.import(&updates).unwrap();
+    doc.import(&updates).unwrap();
+    assert_eq!(map.get("key").unwrap().into_value().unwrap(), "a".into());
+    assert_eq!(
+        doc.take_apply_log(),
+        vec![
+            IgnoredOp::OverriddenMapValue {
+                id: ID::new(0, 0),
+                container: map.id(),
+                key: "key".into(),
+                winner: IdLp::new(1, 0),
+            },
+            IgnoredOp::Duplicate {
+                span: IdSpan::new(0, 0, 1),
+            },
+        ]
+    );
+
+    // The change depends on the missing ops of peer 0
+    doc0.get_map("map").insert("x", 1).unwrap();
+    doc0.commit();
+    doc0.get_map("map").insert("y", 1).unwrap();
+    doc0.commit();
+    doc.import(
+        &doc0
+            .export(ExportMode::updates_in_range(vec![IdSpan::new(0, 2, 3)]))
+            .unwrap(),
+    )
+    .unwrap();
+    let text = doc.get_text("text");
+    text.insert(0, "hello").unwrap();
+    text.mark(0..5, "bold", true).unwrap();
+    text.mark(1..3, "bold", true).unwrap();
+    assert_eq!(
+        doc.take_apply_log(),
+        vec![
+            IgnoredOp::Pending {
+                span: IdSpan::new(0, 2, 3),
+            },
+            IgnoredOp::StyleNoop {
+                container: text.id(),
+                key: "bold".into(),
+                start: 1,
+                end: 3,
+            },
+        ]
+    );
+
+    doc.set_record_apply_log(false);
+    doc.import(&updates).unwrap();
+    assert!(doc.take_apply_log().is_empty());
+}
+
+#[test]
+fn text_sub_view() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    let text = doc.get_text("text");
+    text.insert(0, "Title: Hello!")?;
+    let view = text.sub_view(7..12)?;
+    assert_eq!(view.range(), 7..12);
+    assert!(text.sub_view(3..20).is_err());
+    assert!(LoroText::new().sub_view(0..0).is_err());
+
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let events_clone = events.clone();
+    let _sub = view.subscribe(Arc::new(move |delta| {
+        events_clone.lock().unwrap().push(delta);
+    }));
+
+    // Edits outside the view shift it and don't emit view events
+    text.insert(0, "# ")?;
+    text.insert(text.len_unicode(), "?")?;
+    doc.commit();
+    assert_eq!(view.range(), 9..14);
+    assert!(events.lock().unwrap().is_empty());
+
+    // Edits are clamped to the view, and the insertions at its boundaries are inside it
+    view.insert(100, " world")?;
+    view.insert(0, ">")?;
+    doc.commit();
+    assert_eq!(text.to_string(), "# Title: >Hello world!?");
+    assert_eq!(view.range(), 9..21);
+    view.delete(6, 100)?;
+    doc.commit();
+    view.mark(0..100, "bold", true)?;
+    doc.commit();
+    assert_eq!(text.to_string(), "# Title: >Hello!?");
+    assert_eq!(view.range(), 9..15);
+    assert_eq!(
+        text.get_richtext_value().to_json_value(),
+        json!([
+            {"insert": "# Title: "},
+            {"insert": ">Hello", "attributes": {"bold": true}},
+            {"insert": "!?"},
+        ])
+    );
+
+    let events = events.lock().unwrap();
+    assert_eq!(
+        *events,
+        vec![
+            vec![
+                TextDelta::Insert {
+                    insert: ">".into(),
+                    attributes: None,
+                },
+                TextDelta::Retain {
+                    retain: 5,
+                    attributes: None,
+                },
+                TextDelta::Insert {
+                    insert: " world".into(),
+                    attributes: None,
+                },
+            ],
+            vec![
+                TextDelta::Retain {
+                    retain: 6,
+                    attributes: None,
+                },
+                TextDelta::Delete { delete: 6 },
+            ],
+            vec![TextDelta::Retain {
+                retain: 6,
+                attributes: Some(fx_map! { "bold".into() => LoroValue::Bool(true) }),
+            }],
+        ]
+    );
+    drop(events);
+
+    // Deleting the whole view keeps it at the same place
+    view.delete(0, view.len_unicode())?;
+    view.insert(0, "Hi")?;
+    assert_eq!(text.to_string(), "# Title: Hi!?");
+    assert_eq!(view.range(), 9..11);
+    Ok(())
+}
+
+#[test]
+fn import_snapshot_into_non_empty_doc() -> LoroResult<()> {
+    use loro::SnapshotImportMode;
+
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1)?;
+    doc_a.get_text("text").insert(0, "hello")?;
+    doc_a.commit();
+
+    let doc_b = LoroDoc::new();
+    doc_b.set_peer_id(2)?;
+    let status = doc_b.import(&doc_a.export(ExportMode::Snapshot).unwrap())?;
+    assert_eq!(status.snapshot, Some(SnapshotImportMode::Reset));
+
+    // Both sides edit concurrently, then exchange snapshots
+    doc_a.get_text("text").insert(5, " world")?;
+    doc_a.commit();
+    doc_b.get_map("map").insert("key", 1)?;
+    doc_b.commit();
+    let status = doc_b.import(&doc_a.export(ExportMode::Snapshot).unwrap())?;
+    assert_eq!(status.snapshot, Some(SnapshotImportMode::Merged));
+    assert_eq!(status.success, version_range!(1 => (5, 11)));
+    assert!(status.pending.is_none());
+    let status = doc_a.import(&doc_b.export(ExportMode::Snapshot).unwrap())?;
+    assert_eq!(status.snapshot, Some(SnapshotImportMode::Merged));
+    assert_eq!(status.success, version_range!(2 => (0, 1)));
+    assert_eq!(doc_a.get_deep_value(), doc_b.get_deep_value());
+    assert_eq!(
+        doc_a.get_deep_value().to_json_value(),
+        json!({"text": "hello world", "map": {"key": 1}})
+    );
+
+    // Importing a snapshot that is already included changes nothing
+    let status = doc_a.import(&doc_b.export(ExportMode::Snapshot).unwrap())?;
+    assert_eq!(status.snapshot, Some(SnapshotImportMode::Merged));
+    assert!(status.success.is_empty());
+
+    // Updates are not snapshots
+    let status = doc_a.import(&doc_b.export(ExportMode::all_updates()).unwrap())?;
+    assert_eq!(status.snapshot, None);
+    Ok(())
+}
+
+#[test]
+fn deleted_content_between_versions() -> LoroResult<()> {
+    let doc_a = LoroDoc::new();
+    doc_a.set_peer_id(1)?;
+    doc_a.set_record_timestamp(true);
+    let text = doc_a.get_text("text");
+    text.insert(0, "Hello brave new world")?;
+    let list = doc_a.get_list("list");
+    list.push("a")?;
+    list.push_container(LoroMap::new())?;
+    list.push("c")?;
+    doc_a.commit();
+    let from = doc_a.oplog_frontiers();
+
+    let doc_b = doc_a.fork();
+    doc_b.set_peer_id(2)?;
+    doc_b.get_text("text").delete(5, 6)?;
+    doc_b.commit();
+    text.delete(11, 4)?;
+    text.insert(0, "Oh, ")?;
+    text.delete(0, 4)?;
+    list.delete(0, 2)?;
+    doc_a.commit();
+    doc_a.import(&doc_b.export(ExportMode::all_updates()).unwrap())?;
+    assert_eq!(text.to_string(), "Hello world");
+
+    let deleted = text.deleted_content_between(&from, &doc_a.oplog_frontiers())?;
+    let mut deleted: Vec<_> = deleted
+        .into_iter()
+        .map(|d| {
+            assert!(d.at > 0);
+            (d.text, d.deleted_by)
+        })
+        .collect();
+    deleted.sort();
+    assert_eq!(
+        deleted,
+        vec![
+            (" brave".to_string(), 2),
+            (" new".to_string(), 1),
+            ("Oh, ".to_string(), 1)
+        ]
+    );
+
+    let deleted = list.deleted_content_between(&from, &doc_a.oplog_frontiers())?;
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0].deleted_by, 1);
+    assert_eq!(deleted[0].values[0], LoroValue::from("a"));
+    assert!(deleted[0].values[1].is_container());
+
+    // Nothing is deleted in the other direction or in an empty range
+    assert!(text
+        .deleted_content_between(&doc_a.oplog_frontiers(), &from)?
+        .is_empty());
+    assert!(text.deleted_content_between(&from, &from)?.is_empty());
+    assert!(matches!(
+        text.deleted_content_between(&from, &ID::new(3, 0).into()),
+        Err(LoroError::FrontiersNotFound(_))
+    ));
+    assert!(LoroText::new()
+        .deleted_content_between(&from, &from)
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn snapshot_state_is_isolated_from_later_edits() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    let map = doc.get_map("map");
+    map.insert("a", 1)?;
+    let list = map.insert_container("list", LoroList::new())?;
+    list.push("x")?;
+    doc.get_text("text").insert(0, "Hello")?;
+    doc.commit();
+
+    // Load the doc from a snapshot so that the states start encoded
+    let doc = LoroDoc::from_snapshot(&doc.export(ExportMode::Snapshot).unwrap())?;
+    doc.get_text("text").insert(5, "!")?;
+    let snapshot = doc.snapshot_state();
+    let expected = doc.get_deep_value();
+    assert_eq!(snapshot.frontiers(), &doc.state_frontiers());
+
+    let list = doc
+        .get_map("map")
+        .get("list")
+        .unwrap()
+        .into_container()
+        .unwrap()
+        .into_list()
+        .unwrap();
+    doc.get_text("text").delete(0, 2)?;
+    doc.get_map("map").insert("b", 2)?;
+    list.push("y")?;
+    doc.get_list("new_root").push(1)?;
+    doc.commit();
+
+    let list_id = list.id();
+    let (value, list_value, snapshot) = std::thread::spawn(move || {
+        let list_value = snapshot.get_container_deep_value(&list_id);
+        (snapshot.get_deep_value(), list_value, snapshot)
+    })
+    .join()
+    .unwrap();
+    assert_eq!(value, expected);
+    assert_eq!(list_value, Some(loro_value!(["x"])));
+    assert_eq!(
+        value.to_json_value(),
+        json!({"map": {"a": 1, "list": ["x"]}, "text": "Hello!"})
+    );
+    assert_eq!(
+        snapshot.get_container_deep_value(&ContainerID::new_root("text", ContainerType::Text)),
+        Some("Hello!".into())
+    );
+    assert_eq!(
+        snapshot.get_container_deep_value(&ContainerID::new_root("new_root", ContainerType::List)),
+        None
+    );
+    Ok(())
+}
+
+#[test]
+fn checkout_to_returns_a_view_without_detaching() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    doc.get_text("a").insert(0, "one")?;
+    doc.get_map("map").insert("k", 1)?;
+    doc.commit();
+    let v1 = doc.state_frontiers();
+    doc.get_text("a").insert(3, " two")?;
+    doc.get_text("b").insert(0, "b")?;
+    doc.get_map("map").insert("k", 2)?;
+    doc.commit();
+    let v2 = doc.state_frontiers();
+
+    let view = doc.checkout_to(&v1)?;
+    assert!(!doc.is_detached());
+    assert_eq!(view.frontiers(), &v1);
+    assert_eq!(view.get_text("a").as_deref(), Some("one"));
+    assert_eq!(
+        view.get_deep_value().to_json_value(),
+        json!({"a": "one", "map": {"k": 1}, "b": ""})
+    );
+    let value = view.get_value().into_map().unwrap();
+    assert_eq!(value.len(), 3);
+    assert_eq!(
+        value.get("map"),
+        Some(&LoroValue::Container(ContainerID::new_root(
+            "map",
+            ContainerType::Map
+        )))
+    );
+
+    // The doc is still editable, and the view doesn't change
+    doc.get_text("a").insert(0, ">")?;
+    doc.commit();
+    assert_eq!(doc.get_text("a").to_string(), ">one two");
+    assert_eq!(view.get_text("a").as_deref(), Some("one"));
+
+    // The partially checked out containers are moved from the version of the state
+    doc.checkout(&v2)?;
+    doc.checkout_containers(&v1, &[doc.get_text("b").id()])?;
+    let view = doc.checkout_to(&v2)?;
+    assert_eq!(view.get_text("b").as_deref(), Some("b"));
+    assert_eq!(view.get_text("a").as_deref(), Some("one two"));
+    assert!(doc.checkout_to(&Frontiers::from(ID::new(2, 0))).is_err());
+    Ok(())
+}
+
+#[test]
+fn typed_path_in_lookups_and_events() -> LoroResult<()> {
+    use loro::Path;
+
+    let doc = LoroDoc::new();
+    let map = doc.get_map("map");
+    let list = map.insert_container("a/b", LoroList::new())?;
+    let text = list.insert_container(0, LoroText::new())?;
+    doc.commit();
+
+    let path: Path = r"map/a\/b/0".parse()?;
+    assert_eq!(path, Path::new().key("map").key("a/b").seq(0));
+    assert_eq!(
+        doc.get_by_str_path(r"map/a\/b/0")
+            .unwrap()
+            .into_container()
+            .unwrap()
+            .id(),
+        text.id()
+    );
+    assert!(doc.get_by_str_path("map/a/b/0").is_none());
+
+    let paths = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let paths_clone = paths.clone();
+    let _sub = doc.subscribe_path(
+        &path,
+        Arc::new(move |e| {
+            for diff in e.events {
+                paths_clone.lock().unwrap().push(diff.index_path());
+            }
+        }),
+    )?;
+    text.insert(0, "hi")?;
+    doc.commit();
+    assert_eq!(*paths.lock().unwrap(), vec![path.clone()]);
+    assert_eq!(
+        doc.get_by_path(&paths.lock().unwrap()[0])
+            .unwrap()
+            .into_container()
+            .unwrap()
+            .id(),
+        text.id()
+    );
+
+    assert!(doc
+        .subscribe_path(&Path::new().key("map").key("missing"), Arc::new(|_| {}))
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn run_maintenance_in_slices() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    for peer in 0..20 {
+        doc.set_peer_id(peer)?;
+        doc.get_list("list").push(peer as i64)?;
+        doc.get_map(format!("map{peer}").as_str())
+            .insert("k", peer as i64)?;
+        doc.commit();
+    }
+
+    let pending = doc.pending_maintenance();
+    assert_eq!(pending.change_blocks, 20);
+    assert_eq!(pending.container_states, 21);
+    assert!(!pending.caches);
+
+    let report = doc.run_maintenance(std::time::Duration::ZERO);
+    assert_eq!(report.compressed_change_blocks, 0);
+    assert_eq!(report.pending, pending);
+
+    let report = doc.run_maintenance(std::time::Duration::from_secs(10));
+    assert_eq!(report.compressed_change_blocks, 20);
+    assert_eq!(report.encoded_container_states, 21);
+    assert!(report.pending.is_empty());
+
+    // The maintained doc keeps working
+    doc.get_list("list").push(100)?;
+    doc.commit();
+    assert_eq!(doc.pending_maintenance().change_blocks, 1);
+    let f = doc.state_frontiers();
+    doc.checkout(&Frontiers::from(ID::new(0, 0)))?;
+    assert!(doc.has_history_cache());
+    assert!(!doc.pending_maintenance().caches);
+    doc.checkout(&f)?;
+    doc.attach();
+    assert!(doc.pending_maintenance().caches);
+    let report = doc.run_maintenance(std::time::Duration::from_secs(10));
+    assert!(report.freed_caches);
+    assert!(!doc.has_history_cache());
+
+    let new_doc = LoroDoc::new();
+    new_doc.import(&doc.export(ExportMode::snapshot()).unwrap())?;
+    assert_eq!(new_doc.get_deep_value(), doc.get_deep_value());
+    Ok(())
+}
+
+#[test]
+fn invariants_are_checked_after_imports() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let list = doc.get_list("items");
+    let id = list.id();
+    let invariant = doc.add_invariant(
+        "at-most-two",
+        Arc::new(move |view| {
+            let len = view
+                .get_container_deep_value(&id)
+                .map_or(0, |v| v.into_list().unwrap().len());
+            if len > 2 {
+                return Err(loro::Violation::new(format!("{len} items")).with_container(id.clone()));
+            }
+            Ok(())
+        }),
+    );
+    let violations = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let violations_clone = violations.clone();
+    let _sub = doc.subscribe_invariant_violation(Box::new(move |v| {
+        violations_clone.lock().unwrap().push(v.clone());
+        true
+    }));
+
+    // Local edits are not checked
+    list.push(1)?;
+    list.push(2)?;
+    list.push(3)?;
+    doc.commit();
+    assert!(violations.lock().unwrap().is_empty());
+
+    let other = LoroDoc::new();
+    other.set_peer_id(2)?;
+    other.get_list("items").push(4)?;
+    doc.import(&other.export(ExportMode::all_updates()).unwrap())?;
+    {
+        let violations = violations.lock().unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].invariant, "at-most-two");
+        assert_eq!(violations[0].violation.message, "4 items");
+        assert_eq!(violations[0].violation.containers, vec![list.id()]);
+        assert_eq!(violations[0].frontiers, doc.state_frontiers());
+    }
+
+    // Importing nothing new doesn't check again
+    doc.import(&other.export(ExportMode::all_updates()).unwrap())?;
+    assert_eq!(violations.lock().unwrap().len(), 1);
+
+    // Detached docs are not checked, and the removed invariants are not checked
+    other.get_list("items").push(5)?;
+    doc.detach();
+    doc.import(&other.export(ExportMode::all_updates()).unwrap())?;
+    assert_eq!(violations.lock().unwrap().len(), 1);
+    doc.attach();
+    drop(invariant);
+    other.get_list("items").push(6)?;
+    doc.import(&other.export(ExportMode::all_updates()).unwrap())?;
+    assert_eq!(violations.lock().unwrap().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn get_handler_as_any_handler() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    let map = doc.get_map("map");
+    map.insert("a", 1)?;
+    let text = map.insert_container("text", LoroText::new())?;
+    text.insert(0, "hi")?;
+    let tree = doc.get_tree("tree");
+    tree.create(None)?;
+    doc.get_movable_list("movable").push(true)?;
+    doc.commit();
+
+    let ids = [
+        map.id(),
+        text.id(),
+        tree.id(),
+        doc.get_movable_list("movable").id(),
+    ];
+    let handlers: Vec<loro::AnyHandler> =
+        ids.iter().map(|id| doc.get_handler(id).unwrap()).collect();
+    for (handler, id) in handlers.iter().zip(ids.iter()) {
+        assert_eq!(&handler.id(), id);
+        assert_eq!(handler.get_type(), id.container_type());
+        assert_eq!(handler.as_container().id(), *id);
+    }
+
+    assert_eq!(
+        handlers[0].get_deep_value().to_json_value(),
+        json!({"a": 1, "text": "hi"})
+    );
+    assert!(handlers[0].is::<LoroMap>());
+    assert!(handlers[1].downcast_ref::<LoroMap>().is_none());
+    assert_eq!(
+        handlers[1].downcast_ref::<LoroText>().unwrap().to_string(),
+        "hi"
+    );
+    assert!(handlers[2].downcast_ref::<loro::LoroTree>().is_some());
+    assert_eq!(handlers[3].get_value().to_json_value(), json!([true]));
+
+    let missing = ContainerID::new_normal(ID::new(100, 0), ContainerType::List);
+    assert!(doc.get_handler(&missing).is_none());
+    Ok(())
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn open_snapshot_by_mmap() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.get_text("text").insert(0, "Hello")?;
+    doc.get_map("map").insert("a", 1)?;
+    doc.commit();
+    let path = std::env::temp_dir().join(format!("loro-mmap-{}.snapshot", std::process::id()));
+    std::fs::write(&path, doc.export(ExportMode::Snapshot).unwrap()).unwrap();
+
+    // SAFETY: the file is only removed after the doc is dropped
+    let opened = unsafe { LoroDoc::open_mmap(&path)? };
+    assert_eq!(opened.get_deep_value(), doc.get_deep_value());
+    assert_eq!(opened.oplog_vv(), doc.oplog_vv());
+    opened.get_text("text").insert(5, " world")?;
+    opened.commit();
+    doc.import(&opened.export(ExportMode::updates(&doc.oplog_vv())).unwrap())?;
+    assert_eq!(doc.get_text("text").to_string(), "Hello world");
+    drop(opened);
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(unsafe { LoroDoc::open_mmap(&path) }.is_err());
+    Ok(())
+}
+
+#[test]
+fn remap_remote_cursors_through_edits() -> LoroResult<()> {
+    use loro::RemoteCursor;
+
+    let doc = LoroDoc::new();
+    let text = doc.get_text("text");
+    text.insert(0, "0123456789")?;
+    doc.commit();
+    let cursor = |peer, anchor, head| RemoteCursor {
+        peer,
+        container: text.id(),
+        anchor,
+        head,
+    };
+    let cursors = Arc::new(std::sync::Mutex::new(vec![
+        cursor(1, 2, 2),
+        cursor(2, 4, 6),
+        cursor(3, 9, 7),
+        RemoteCursor {
+            peer: 4,
+            container: doc.get_text("other").id(),
+            anchor: 3,
+            head: 3,
+        },
+    ]));
+    let cursors_clone = cursors.clone();
+    let doc_clone = doc.clone();
+    let _sub = doc.subscribe_root(Arc::new(move |e| {
+        doc_clone.remap_remote_cursors(&mut cursors_clone.lock().unwrap(), &e);
+    }));
+    let positions = || {
+        cursors
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|c| (c.anchor, c.head))
+            .collect::<Vec<_>>()
+    };
+
+    // The text inserted at a caret goes before it, and the text inserted at the
+    // boundaries of a selection goes outside of it
+    text.insert(2, "a")?;
+    text.insert(5, "b")?;
+    text.insert(8, "c")?;
+    doc.commit();
+    assert_eq!(text.to_string(), "01a23b45c6789");
+    assert_eq!(positions(), vec![(3, 3), (6, 8), (12, 10), (3, 3)]);
+
+    // The positions in a deleted range move to the start of the deletion
+    text.delete(1, 6)?;
+    doc.commit();
+    assert_eq!(text.to_string(), "05c6789");
+    assert_eq!(positions(), vec![(1, 1), (1, 2), (6, 4), (3, 3)]);
+
+    // Imported edits
+    let other = doc.fork();
+    other.get_text("text").insert(0, "xyz")?;
+    other.commit();
+    doc.import(&other.export(ExportMode::updates(&doc.oplog_vv())).unwrap())?;
+    assert_eq!(text.to_string(), "xyz05c6789");
+    assert_eq!(positions(), vec![(4, 4), (4, 5), (9, 7), (3, 3)]);
+
+    // The cursors can be resolved from the shared stable cursors
+    let a = text.get_cursor(1, Default::default()).unwrap();
+    let b = text.get_cursor(4, Default::default()).unwrap();
+    let resolved = RemoteCursor::resolve(&doc, 7, &a, &b).unwrap();
+    assert_eq!(resolved, cursor(7, 1, 4));
+    assert!(!resolved.is_collapsed());
+    Ok(())
+}
+
+#[test]
+fn strip_update_payloads_keeps_structure() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let text = doc.get_text("text");
+    text.insert(0, "Hi 😀 there")?;
+    text.mark(0..2, "bold", true)?;
+    let map = doc.get_map("map");
+    map.insert("secret", "password")?;
+    map.insert("n", 42)?;
+    let list = doc.get_list("list");
+    list.push(1.5)?;
+    let child = list.insert_container(1, LoroMap::new())?;
+    child.insert("k", vec![1, 2, 3])?;
+    doc.set_next_commit_message("private message");
+    doc.commit();
+    text.delete(0, 3)?;
+    doc.commit();
+
+    let updates = doc.export(ExportMode::all_updates()).unwrap();
+    let stripped = loro::strip_update_payloads(&updates)?;
+    let redacted = LoroDoc::new();
+    redacted.import(&stripped)?;
+    assert_eq!(redacted.oplog_vv(), doc.oplog_vv());
+    assert_eq!(redacted.oplog_frontiers(), doc.oplog_frontiers());
+    assert_eq!(redacted.get_change(ID::new(1, 0)).unwrap().message(), "");
+
+    let redacted_text = redacted.get_text("text");
+    assert_eq!(redacted_text.len_unicode(), text.len_unicode());
+    assert_eq!(redacted_text.len_utf16(), text.len_utf16());
+    assert_eq!(
+        redacted_text.to_string(),
+        "\u{10FFFD}".to_string() + &"\u{FFFD}".repeat(6)
+    );
+    assert_eq!(
+        redacted.get_map("map").get_deep_value().to_json_value(),
+        json!({"secret": "\u{FFFD}".repeat(8), "n": 0})
+    );
+    assert_eq!(
+        redacted.get_list("list").get_deep_value().to_json_value(),
+        json!([0.0, {"k": [0, 0, 0]}])
+    );
+    assert_eq!(
+        redacted.get_list("list").get_value(),
+        LoroValue::from(vec![
+            LoroValue::Double(0.),
+            LoroValue::Container(child.id())
+        ])
+    );
+
+    // Only the updates in the current encoding are supported
+    assert!(loro::strip_update_payloads(&doc.export(ExportMode::Snapshot).unwrap()).is_err());
+    Ok(())
+}
+
+#[test]
+fn numeric_list_bulk_ops() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let list = doc.get_list("points");
+    let values: Vec<f64> = (0..1000).map(|i| i as f64 / 2.).collect();
+    list.push_slice(&values)?;
+    list.set_range(998, &[7., 8., 9.])?;
+    doc.commit();
+    assert_eq!(list.len(), 1001);
+    assert_eq!(
+        list.get(999).unwrap().into_value().unwrap(),
+        LoroValue::Double(8.)
+    );
+    assert_eq!(list.to_f64_vec().unwrap()[997..], [498.5, 7., 8., 9.]);
+    assert_eq!(list.to_i64_vec(), None);
+
+    let counts = doc.get_list("counts");
+    counts.push_slice(&[1i64, 2, 3])?;
+    counts.insert(1, "x")?;
+    assert_eq!(counts.to_i64_vec(), None);
+    counts.delete(1, 1)?;
+    assert_eq!(counts.to_i64_vec(), Some(vec![1, 2, 3]));
+    assert!(counts.set_range(4, &[1i64]).is_err());
+
+    // The dense runs survive the snapshots and the imports
+    let snapshot = LoroDoc::new();
+    snapshot.import(&doc.export(ExportMode::Snapshot).unwrap())?;
+    assert_eq!(snapshot.get_deep_value(), doc.get_deep_value());
+    let updated = LoroDoc::new();
+    updated.import(&doc.export(ExportMode::all_updates()).unwrap())?;
+    assert_eq!(updated.get_list("points").to_f64_vec(), list.to_f64_vec());
+    updated.get_list("points").delete(10, 500)?;
+    assert_eq!(updated.get_list("points").len(), 501);
+    assert_eq!(
+        updated
+            .get_list("points")
+            .get(10)
+            .unwrap()
+            .into_value()
+            .unwrap(),
+        LoroValue::Double(255.)
+    );
+    Ok(())
+}
+
+#[test]
+fn snapshot_with_many_peers_keeps_vv() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    let text = doc.get_text("text");
+    for peer in 0..300u64 {
+        doc.set_peer_id(peer.wrapping_mul(0x9E3779B97F4A7C15))?;
+        text.insert(0, "ab")?;
+        doc.commit();
+    }
+
+    let vv = doc.oplog_vv();
+    assert_eq!(vv.len(), 300);
+    let compact = loro::CompactVersionVector::from_vv(&vv);
+    assert_eq!(compact.len(), 300);
+    assert_eq!(compact.to_vv(), vv);
+
+    let snapshot = doc.export(ExportMode::Snapshot).unwrap();
+    let new_doc = LoroDoc::new();
+    new_doc.import(&snapshot)?;
+    assert_eq!(new_doc.oplog_vv(), vv);
+    assert_eq!(new_doc.get_text("text").len_unicode(), 600);
+    Ok(())
+}
+
+#[test]
+fn frontiers_set_ops() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let text = doc.get_text("text");
+    text.insert(0, "base")?;
+    doc.commit();
+    let base = doc.oplog_frontiers();
+
+    text.insert(0, "a")?;
+    doc.commit();
+    let a = doc.oplog_frontiers();
+    let fork = LoroDoc::new();
+    fork.set_peer_id(2)?;
+    let base_vv = doc.frontiers_to_vv(&base).unwrap();
+    fork.import(&doc.export(ExportMode::updates_till(&base_vv)).unwrap())?;
+    fork.get_text("text").insert(0, "b")?;
+    fork.commit();
+    let b = fork.oplog_frontiers();
+    doc.import(&fork.export(ExportMode::all_updates()).unwrap())?;
+
+    assert_eq!(doc.cmp_frontiers(&a, &b), Ok(None));
+    assert_eq!(doc.cmp_frontiers(&base, &a), Ok(Some(Ordering::Less)));
+    assert_eq!(doc.find_common_ancestor(&a, &b).unwrap(), base);
+    assert_eq!(doc.frontiers_intersection(&a, &b).unwrap(), base);
+    assert_eq!(doc.frontiers_union(&a, &b).unwrap(), doc.oplog_frontiers());
+    assert_eq!(doc.frontiers_union(&base, &a).unwrap(), a);
+    assert_eq!(doc.frontiers_intersection(&base, &a).unwrap(), base);
+
+    let missing = Frontiers::from_id(ID::new(3, 0));
+    assert_eq!(
+        doc.find_common_ancestor(&a, &missing),
+        Err(FrontiersNotIncluded)
+    );
+    assert!(doc.frontiers_intersection(&missing, &a).is_err());
+    Ok(())
+}
+
+#[test]
+fn text_style_validation() -> LoroResult<()> {
+    use loro::{
+        ExpandType, InvalidStyleReason, StyleConfig, StyleConfigMap, StyleImportPolicy,
+        StyleValueType,
+    };
+
+    let mut config = StyleConfigMap::new();
+    config.insert(
+        "bold".into(),
+        StyleConfig::new()
+            .expand(ExpandType::After)
+            .value_type(StyleValueType::Bool),
+    );
+    config.insert("color".into(), StyleConfig::new());
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    doc.config_text_style(config);
+    let text = doc.get_text("text");
+    text.insert(0, "hello world")?;
+    assert_eq!(
+        text.mark(0..5, "blod", true),
+        Err(LoroError::StyleConfigMissing("blod".into()))
+    );
+    assert!(matches!(
+        text.mark(0..5, "bold", "yes"),
+        Err(LoroError::InvalidStyleValue { .. })
+    ));
+    text.mark(0..5, "bold", true)?;
+    text.mark(6..11, "color", "red")?;
+    text.unmark(0..2, "bold")?;
+    doc.commit();
+    let updates = doc.export(ExportMode::all_updates()).unwrap();
+
+    let mut only_bold = StyleConfigMap::new();
+    only_bold.insert("bold".into(), StyleConfig::new().expand(ExpandType::After));
+    only_bold.set_import_policy(StyleImportPolicy::Drop);
+    let dropped = LoroDoc::new();
+    dropped.config_text_style(only_bold.clone());
+    dropped.import(&updates)?;
+    assert_eq!(
+        dropped
+            .get_text("text")
+            .get_richtext_value()
+            .to_json_value(),
+        json!([
+            {"insert": "he"},
+            {"insert": "llo", "attributes": {"bold": true}},
+            {"insert": " world"},
+        ])
+    );
+    assert!(dropped.take_flagged_styles().is_empty());
+
+    only_bold.set_import_policy(StyleImportPolicy::Flag);
+    let flagged = LoroDoc::new();
+    flagged.config_text_style(only_bold);
+    flagged.import(&updates)?;
+    assert_eq!(
+        flagged.get_text("text").get_richtext_value(),
+        doc.get_text("text").get_richtext_value()
+    );
+    let styles = flagged.take_flagged_styles();
+    assert_eq!(styles.len(), 1);
+    assert_eq!(styles[0].key.as_str(), "color");
+    assert_eq!(styles[0].reason, InvalidStyleReason::UnknownKey);
+    assert!(flagged.take_flagged_styles().is_empty());
+    Ok(())
+}
+
+#[test]
+fn list_sync_to_target() -> LoroResult<()> {
+    let item = |id: i64, name: &str| loro_value!({"id": id, "name": name});
+    let key = |v: &LoroValue| v.as_map().unwrap().get("id").cloned();
+    let current = vec![item(1, "a"), item(2, "b"), item(3, "c"), item(4, "d")];
+    let target = vec![
+        item(2, "b"),
+        item(3, "C"),
+        item(4, "d"),
+        item(1, "a"),
+        item(5, "e"),
+    ];
+
+    let doc = LoroDoc::new();
+    let list = doc.get_movable_list("movable");
+    for v in current.iter() {
+        list.push(v.clone())?;
+    }
+    doc.commit();
+    let ops_before = doc.len_ops();
+    list.sync_to(&target, key)?;
+    doc.commit();
+    assert_eq!(list.get_value(), LoroValue::from(target.clone()));
+    // One move, one insert and one set
+    assert_eq!(doc.len_ops() - ops_before, 3);
+
+    let plain = doc.get_list("list");
+    for v in current.iter() {
+        plain.push(v.clone())?;
+    }
+    plain.sync_to(&target, key)?;
+    doc.commit();
+    assert_eq!(plain.get_value(), LoroValue::from(target));
+    plain.sync_to(&[], key)?;
+    assert!(plain.is_empty());
+
+    let detached = loro::LoroMovableList::new();
+    detached.sync_to(&current, key)?;
+    assert_eq!(detached.get_value(), LoroValue::from(current));
+    Ok(())
+}
+
+#[test]
+fn export_updates_stream_matches_export() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let text = doc.get_text("text");
+    for i in 0..2000 {
+        text.insert(0, &format!("{i} "))?;
+        if i % 100 == 0 {
+            doc.commit();
+        }
+    }
+    doc.get_list("list").push_slice(&[1, 2, 3])?;
+    doc.commit();
+    let half_vv = doc.oplog_vv();
+    doc.set_peer_id(2)?;
+    doc.get_map("map").insert("key", "value")?;
+    text.delete(0, 100)?;
+    doc.commit();
+
+    for from in [loro::VersionVector::default(), half_vv] {
+        let mut stream = Vec::new();
+        doc.export_updates_stream(&from, &mut stream).unwrap();
+        let expected = doc.export(ExportMode::updates(&from)).unwrap();
+        let a = LoroDoc::new();
+        let b = LoroDoc::new();
+        if !from.is_empty() {
+            let base = doc.export(ExportMode::updates_till(&from)).unwrap();
+            a.import(&base)?;
+            b.import(&base)?;
+        }
+
+        a.import(&stream)?;
+        b.import(&expected)?;
+        assert_eq!(a.get_deep_value(), doc.get_deep_value());
+        assert_eq!(a.oplog_vv(), b.oplog_vv());
+    }
+
+    // The stream of an empty range is still a valid update
+    let mut stream = Vec::new();
+    doc.export_updates_stream(&doc.oplog_vv(), &mut stream)
+        .unwrap();
+    LoroDoc::new().import(&stream)?;
+    Ok(())
+}
+
+#[test]
+fn import_snapshot_with_progress() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let text = doc.get_text("text");
+    for i in 0..100 {
+        text.insert(0, &i.to_string())?;
+        doc.commit();
+    }
+    let list = doc.get_list("list");
+    for i in 0..1000 {
+        list.push(i)?;
+    }
+    doc.commit();
+    let snapshot = doc.export(ExportMode::Snapshot).unwrap();
+    let total = doc.len_ops();
+
+    let new_doc = LoroDoc::new();
+    let mut calls = Vec::new();
+    new_doc.import_snapshot_with_progress(snapshot.as_slice(), &mut |loaded, all| {
+        calls.push((loaded, all));
+    })?;
+    assert!(calls.iter().all(|(_, all)| *all == total));
+    assert!(calls.windows(2).all(|w| w[0].0 <= w[1].0));
+    assert_eq!(calls.last(), Some(&(total, total)));
+    assert_eq!(new_doc.get_deep_value(), doc.get_deep_value());
+    assert_eq!(new_doc.oplog_frontiers(), doc.oplog_frontiers());
+
+    // Invalid data is rejected
+    assert!(LoroDoc::new()
+        .import_snapshot_with_progress(&snapshot[..10], &mut |_, _| {})
+        .is_err());
+    Ok(())
+}
+
+#[cfg(feature = "counter")]
+#[test]
+fn counter_decimal_precision() -> LoroResult<()> {
+    use loro::{CounterConfig, CounterOverflowPolicy};
+
+    let config = Some(CounterConfig::new(Some(2), CounterOverflowPolicy::Reject));
+    let a = LoroDoc::new();
+    a.set_peer_id(1)?;
+    a.config_counter(config);
+    let b = LoroDoc::new();
+    b.set_peer_id(2)?;
+    b.config_counter(config);
+    for _ in 0..10 {
+        a.get_counter("c").increment(0.1)?;
+        a.commit();
+    }
+    b.get_counter("c").increment(0.004)?;
+    b.get_counter("c").increment(0.7)?;
+    b.commit();
+    a.import(&b.export(ExportMode::all_updates()).unwrap())?;
+    b.import(&a.export(ExportMode::all_updates()).unwrap())?;
+    assert_eq!(a.get_counter("c").get_value(), 1.7);
+    assert_eq!(b.get_counter("c").get_value(), 1.7);
+
+    // The overflowing local increments are rejected
+    let max = ((1u64 << 53) - 1) as f64 / 100.;
+    assert!(matches!(
+        a.get_counter("c").increment(max),
+        Err(LoroError::CounterOverflow)
+    ));
+    assert_eq!(a.get_counter("c").get_value(), 1.7);
+
+    // The imported increments saturate
+    b.config_counter(Some(CounterConfig::new(
+        None,
+        CounterOverflowPolicy::Saturate,
+    )));
+    b.get_counter("c").increment(max)?;
+    b.commit();
+    a.import(&b.export(ExportMode::all_updates()).unwrap())?;
+    assert_eq!(a.get_counter("c").get_value(), max);
+    Ok(())
+}
+
+#[test]
+fn instantiate_container_template() -> LoroResult<()> {
+    use loro::ContainerTemplate;
+
+    let doc = LoroDoc::new();
+    let template = ContainerTemplate::Map(
+        [
+            (
+                "title".to_string(),
+                ContainerTemplate::Text("Untitled".to_string()),
+            ),
+            (
+                "subtasks".to_string(),
+                ContainerTemplate::MovableList(vec![ContainerTemplate::Value(1.into())]),
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    doc.register_template("task", template)?;
+    let tasks = doc.get_movable_list("tasks");
+    let before = doc.len_changes();
+    for i in 0..10 {
+        doc.instantiate_template("task", &tasks.id(), i)?;
+    }
+    assert_eq!(doc.len_changes(), before + 10);
+    assert_eq!(tasks.len(), 10);
+    let first = tasks.get(0).unwrap().into_container().unwrap();
+    let last = tasks.get(9).unwrap().into_container().unwrap();
+    assert_ne!(first.id(), last.id());
+    assert_eq!(
+        first.get_deep_value().to_json_value(),
+        json!({"title": "Untitled", "subtasks": [1]})
+    );
+
+    // The instances are independent
+    first
+        .into_map()
+        .unwrap()
+        .get_or_create_container("title", LoroText::new())?
+        .insert(0, "Buy ")?;
+    assert_eq!(
+        last.get_deep_value().to_json_value(),
+        json!({"title": "Untitled", "subtasks": [1]})
+    );
+
+    assert!(doc.instantiate_template("missing", &tasks.id(), 0).is_err());
+    assert!(doc.unregister_template("task"));
+    assert!(doc.instantiate_template("task", &tasks.id(), 0).is_err());
+    Ok(())
+}
+
+#[test]
+fn timestamp_and_decimal_values() -> LoroResult<()> {
+    use loro::LoroDecimal;
+
+    let price = LoroDecimal::parse("+0019.9900").unwrap();
+    assert_eq!(price.as_str(), "19.99");
+    assert_eq!(price, LoroDecimal::new(1999, 2));
+    assert_eq!(LoroDecimal::new(-5, 3).as_str(), "-0.005");
+    assert_eq!(LoroDecimal::parse("-0.000").unwrap().as_str(), "0");
+    assert!(LoroDecimal::parse("1e5").is_none());
+    assert!(LoroDecimal::parse("-").is_none());
+
+    let doc = LoroDoc::new();
+    let map = doc.get_map("order");
+    assert!(matches!(
+        map.insert("price", price.clone()),
+        Err(LoroError::ExtendedValueTypeDisabled(_))
+    ));
+    doc.set_extended_value_types(true);
+    map.insert("created", LoroValue::Timestamp(1_700_000_000_123))?;
+    map.insert("price", price.clone())?;
+    doc.get_list("history")
+        .push(LoroValue::List(vec![LoroValue::Timestamp(-1)].into()))?;
+    doc.commit();
+
+    let new_doc = LoroDoc::new();
+    new_doc.import(&doc.export(ExportMode::Snapshot).unwrap())?;
+    new_doc.import(&doc.export(ExportMode::all_updates()).unwrap())?;
+    let value = new_doc.get_deep_value();
+    assert_eq!(value, doc.get_deep_value());
+    assert_eq!(
+        value.get_by_key("order").unwrap().get_by_key("price"),
+        Some(&LoroValue::Decimal(price))
+    );
+
+    // The types survive a JSON round trip, but only when the tags are parsed
+    let json = serde_json::to_string(&value).unwrap();
+    let parsed: LoroValue = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        parsed.get_by_key("order").unwrap().get_by_key("created"),
+        Some(&LoroValue::from(json!({"🦜ts": 1700000000123i64})))
+    );
+    assert_eq!(parsed.parse_tags(), value);
+    assert_eq!(
+        value.get_by_key("order").unwrap().to_json_value(),
+        json!({"created": {"🦜ts": 1700000000123i64}, "price": {"🦜dec": "19.99"}})
+    );
+    // The strings that look like the old tagged strings stay strings
+    let s = LoroValue::from("🦜dec:19.99");
+    let parsed: LoroValue = serde_json::from_str(&serde_json::to_string(&s).unwrap()).unwrap();
+    assert_eq!(parsed, s);
+    Ok(())
+}
+
+#[test]
+fn text_word_and_grapheme_boundaries() -> LoroResult<()> {
+    use loro::{TextBoundary, TextDirection};
+
+    let doc = LoroDoc::new();
+    let text = doc.get_text("text");
+    text.insert(0, "Caf\u{e9} 👨‍👩‍👧 party")?;
+    assert_eq!(text.expand_to_boundary(2..2, TextBoundary::Word)?, 0..4);
+    // The family emoji is one grapheme of five Unicode code points
+    assert_eq!(
+        text.expand_to_boundary(6..6, TextBoundary::Grapheme)?,
+        5..10
+    );
+    assert_eq!(text.delete_word(16, TextDirection::Backward)?, 11..16);
+    assert_eq!(text.delete_word(10, TextDirection::Backward)?, 5..10);
+    assert_eq!(text.to_string(), "Caf\u{e9}  ");
+    Ok(())
+}
+
+#[test]
+fn text_slice_in_all_index_spaces() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    let text = doc.get_text("text");
+    text.insert(0, "a😀bc")?;
+    text.mark(0..3, "bold", true)?;
+    text.insert(4, "ü")?;
+    assert_eq!(text.to_string(), "a😀bcü");
+    assert_eq!(text.slice(1, 3)?, "😀b");
+    assert_eq!(text.slice_utf16(1..4)?, "😀b");
+    assert_eq!(text.slice_utf8(1..6)?, "😀b");
+    assert_eq!(text.slice_utf8(7..9)?, "ü");
+    assert_eq!(text.slice_utf16(0..0)?, "");
+    assert!(matches!(
+        text.slice_utf16(2..4),
+        Err(LoroError::UTF16InUnicodeCodePoint { pos: 2 })
+    ));
+    assert!(matches!(
+        text.slice_utf8(0..8),
+        Err(LoroError::UTF8InUnicodeCodePoint { pos: 8 })
+    ));
+    assert!(text.slice_utf16(4..3).is_err());
+    assert!(text.slice_utf16(0..8).is_err());
+    Ok(())
+}
+
+#[test]
+fn extract_subtree_and_merge_in() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    let page = doc
+        .get_map("workspace")
+        .insert_container("a", LoroMap::new())?;
+    let body = page.insert_container("body", LoroText::new())?;
+    body.insert(0, "Hello world")?;
+    body.mark(0..5, "bold", true)?;
+    page.insert_container("tags", loro::LoroMovableList::new())?
+        .push("draft")?;
+    doc.commit();
+
+    let page_doc = doc.extract_subtree(&page.id())?;
+    let copy = page_doc.get_map(loro::SUBTREE_ROOT_NAME);
+    assert_eq!(copy.get_deep_value(), page.get_deep_value());
+
+    let other = LoroDoc::new();
+    other.get_map("workspace");
+    let merged = other.merge_in(
+        &page_doc,
+        &[
+            loro::Index::Key("workspace".into()),
+            loro::Index::Key("b".into()),
+        ],
+    )?;
+    assert_eq!(
+        other.get_deep_value().to_json_value(),
+        json!({"workspace": {"b": {"body": "Hello world", "tags": ["draft"]}}})
+    );
+    let merged_body = merged
+        .into_map()
+        .unwrap()
+        .get("body")
+        .unwrap()
+        .into_container()
+        .unwrap()
+        .into_text()
+        .unwrap();
+    assert_eq!(merged_body.to_delta(), body.to_delta());
+    Ok(())
+}
+
+#[test]
+fn reject_updates_exceeding_decode_limits() -> LoroResult<()> {
+    use loro::{DecodeLimit, DecodeLimits};
+
+    let doc = LoroDoc::new();
+    let list = doc.get_list("list");
+    list.push(vec![0u8; 64])?;
+    doc.commit();
+    let updates = doc.export(loro::ExportMode::all_updates()).unwrap();
+
+    let server = LoroDoc::new();
+    server.set_decode_limits(DecodeLimits {
+        max_binary_len: 16,
+        ..Default::default()
+    });
+    assert_eq!(
+        server.import(&updates).unwrap_err(),
+        LoroError::DecodeLimitExceeded {
+            limit: DecodeLimit::BinaryLength,
+            max: 16,
+            actual: 64
+        }
+    );
+    assert_eq!(server.get_deep_value().to_json_value(), json!({}));
+
+    server.set_decode_limits(DecodeLimits::default());
+    server.import(&updates)?;
+    assert_eq!(server.get_deep_value(), doc.get_deep_value());
+    Ok(())
+}
+
+#[test]
+fn explain_concurrent_list_inserts() -> LoroResult<()> {
+    let a = LoroDoc::new();
+    a.set_peer_id(1)?;
+    let b = LoroDoc::new();
+    b.set_peer_id(2)?;
+    a.get_list("list").insert(0, "a")?;
+    b.get_list("list").insert(0, "b")?;
+    a.import(&b.export(ExportMode::all_updates()).unwrap())?;
+
+    let explanation = a.explain(loro::ID::new(2, 0)).unwrap();
+    assert_eq!(explanation.container, a.get_list("list").id());
+    assert_eq!(explanation.concurrent, vec![IdSpan::new(1, 0, 1)]);
+    let pos = a
+        .get_list("list")
+        .get_deep_value()
+        .into_list()
+        .unwrap()
+        .iter()
+        .position(|v| v == &LoroValue::from("b"))
+        .unwrap();
+    assert_eq!(
+        explanation.outcome,
+        loro::OpOutcome::Inserted {
+            pos,
+            deleted: false
+        }
+    );
+    assert!(explanation
+        .to_string()
+        .contains("concurrent with: [0..1@1]"));
+    Ok(())
+}
+
+#[test]
+fn iter_changes_causal_across_peers() -> LoroResult<()> {
+    let a = LoroDoc::new();
+    a.set_peer_id(1)?;
+    let b = LoroDoc::new();
+    b.set_peer_id(2)?;
+    a.get_text("text").insert(0, "Hello")?;
+    a.commit_with(CommitOptions::new().commit_msg("a1"));
+    b.import(&a.export(ExportMode::all_updates()).unwrap())?;
+    let base = b.oplog_frontiers();
+    b.get_text("text").insert(5, " world")?;
+    b.commit_with(CommitOptions::new().commit_msg("b1"));
+    a.get_text("text").insert(0, "> ")?;
+    a.commit_with(CommitOptions::new().commit_msg("a2"));
+    a.import(&b.export(ExportMode::all_updates()).unwrap())?;
+
+    let changes: Vec<_> = a
+        .iter_changes_causal(&Frontiers::default(), &a.oplog_frontiers())
+        .unwrap()
+        .collect();
+    let messages: Vec<_> = changes
+        .iter()
+        .map(|c| c.message.as_deref().unwrap())
+        .collect();
+    assert_eq!(messages, vec!["a1", "a2", "b1"]);
+    assert_eq!(changes[2].deps, base);
+
+    // The change of "Hello" is trimmed to the ops after "Hel"
+    let changes: Vec<_> = a
+        .iter_changes_causal(&ID::new(1, 2).into(), &base)
+        .unwrap()
+        .collect();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].id, ID::new(1, 3));
+    assert_eq!(changes[0].lamport, 3);
+    assert_eq!(changes[0].len, 2);
+    assert_eq!(changes[0].deps, Frontiers::from(ID::new(1, 2)));
+
+    assert!(a
+        .iter_changes_causal(&Frontiers::default(), &ID::new(3, 0).into())
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn values_at_multiple_versions() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let map = doc.get_map("map");
+    let mut versions = vec![doc.oplog_frontiers()];
+    for i in 0..3 {
+        map.insert(&i.to_string(), i)?;
+        doc.commit();
+        versions.push(doc.oplog_frontiers());
+    }
+    map.delete("0")?;
+    doc.commit();
+    versions.push(doc.oplog_frontiers());
+
+    let called = Arc::new(AtomicBool::new(false));
+    let called_clone = called.clone();
+    let _sub = doc.subscribe_root(Arc::new(move |_| {
+        called_clone.store(true, std::sync::atomic::Ordering::Relaxed);
+    }));
+    let values: Vec<_> = doc
+        .values_at(&versions, &map.id())?
+        .iter()
+        .map(|v| v.to_json_value())
+        .collect();
+    assert_eq!(
+        values,
+        vec![
+            json!({}),
+            json!({"0": 0}),
+            json!({"0": 0, "1": 1}),
+            json!({"0": 0, "1": 1, "2": 2}),
+            json!({"1": 1, "2": 2}),
+        ]
+    );
+    // Out of order versions
+    let values: Vec<_> = doc
+        .values_at(&[versions[2].clone(), versions[1].clone()], &map.id())?
+        .iter()
+        .map(|v| v.to_json_value())
+        .collect();
+    assert_eq!(values, vec![json!({"0": 0, "1": 1}), json!({"0": 0})]);
+    assert!(!called.load(std::sync::atomic::Ordering::Relaxed));
+    assert!(!doc.is_detached());
+    assert_eq!(doc.state_frontiers(), doc.oplog_frontiers());
+    map.insert("3", 3)?;
+    assert!(doc.values_at(&[ID::new(2, 0).into()], &map.id()).is_err());
+    Ok(())
+}
+
+#[test]
+fn delete_merge_config() -> LoroResult<()> {
+    fn text_deletions(doc: &LoroDoc) -> Vec<(i32, i32)> {
+        let json = doc.export_json_updates(&Default::default(), &doc.oplog_vv());
+        json.changes
+            .iter()
+            .flat_map(|c| c.ops.iter())
+            .filter_map(|op| match &op.content {
+                loro::JsonOpContent::Text(loro::JsonTextOp::Delete { pos, len, .. }) => {
+                    Some((*pos, *len))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    doc.set_delete_merge_config(loro::DeleteMergeConfig {
+        backward: true,
+        forward: false,
+        max_len: 2,
+    });
+    let text = doc.get_text("text");
+    text.insert(0, "abcdef")?;
+    doc.commit();
+    // Backspace
+    for i in (3..6).rev() {
+        text.delete(i, 1)?;
+        doc.commit();
+    }
+    // The delete key
+    for _ in 0..2 {
+        text.delete(0, 1)?;
+    }
+    doc.commit();
+    assert_eq!(text.to_string(), "c");
+    let deletions = vec![(5, -2), (3, 1), (0, 1), (0, 1)];
+    assert_eq!(text_deletions(&doc), deletions);
+
+    // The granularity is kept by the other replicas
+    let other = LoroDoc::new();
+    other.import(&doc.export(ExportMode::all_updates()).unwrap())?;
+    assert_eq!(text_deletions(&other), deletions);
+    let other = LoroDoc::new();
+    other.import(&doc.export(ExportMode::Snapshot).unwrap())?;
+    assert_eq!(text_deletions(&other), deletions);
+
+    let merged = LoroDoc::new();
+    merged.set_peer_id(2)?;
+    let text = merged.get_text("text");
+    text.insert(0, "abcdef")?;
+    for i in (3..6).rev() {
+        text.delete(i, 1)?;
+    }
+    merged.commit();
+    assert_eq!(text_deletions(&merged), vec![(5, -3)]);
+    Ok(())
+}
+
+#[test]
+fn decode_import_blob_meta_containers() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let page = doc.get_map("pages").insert_container("a", LoroMap::new())?;
+    let title = page.insert_container("title", LoroText::new())?;
+    title.insert(0, "Hi")?;
+    let tree = doc.get_tree("tree");
+    let node = tree.create(TreeParentId::Root)?;
+    doc.commit();
+
+    let expected = vec![
+        (page.id(), doc.get_map("pages").id()),
+        (title.id(), page.id()),
+        (node.associated_meta_container(), tree.id()),
+    ];
+    for mode in [ExportMode::all_updates(), ExportMode::Snapshot] {
+        let meta = LoroDoc::decode_import_blob_meta(&doc.export(mode).unwrap(), false)?;
+        assert_eq!(
+            meta.root_containers,
+            vec![doc.get_map("pages").id(), tree.id()]
+        );
+        assert_eq!(meta.created_containers, expected);
+    }
+
+    // Only the containers created by the ops in the blob are reported
+    let v = doc.oplog_vv();
+    title.insert(0, "Oh ")?;
+    doc.commit();
+    let meta =
+        LoroDoc::decode_import_blob_meta(&doc.export(ExportMode::updates(&v)).unwrap(), false)?;
+    assert!(meta.root_containers.is_empty());
+    assert!(meta.created_containers.is_empty());
+    Ok(())
+}
+
+#[test]
+fn commit_metadata() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let text = doc.get_text("text");
+    text.insert(0, "a")?;
+    doc.set_next_commit_message("first");
+    doc.set_next_commit_metadata("author", "alice");
+    doc.set_next_commit_metadata("reason", "typo");
+    doc.commit();
+    // Different metadata stops the changes from merging
+    text.insert(1, "b")?;
+    doc.set_next_commit_metadata("author", "bob");
+    doc.commit();
+    assert_eq!(doc.len_changes(), 2);
+
+    let other = LoroDoc::new();
+    other.import(&doc.export(ExportMode::all_updates()).unwrap())?;
+    let json = doc.export_json_updates(&Default::default(), &doc.oplog_vv());
+    let from_json = LoroDoc::new();
+    from_json.import_json_updates(json)?;
+    for d in [&doc, &other, &from_json] {
+        let first = d.get_change(ID::new(1, 0)).unwrap();
+        assert_eq!(first.message_text(), "first");
+        assert_eq!(
+            first.metadata(),
+            loro::CommitMetadata::from([
+                ("author".to_string(), "alice".to_string()),
+                ("reason".to_string(), "typo".to_string()),
+            ])
+        );
+        let second = d.get_change(ID::new(1, 1)).unwrap();
+        assert_eq!(second.message_text(), "");
+        assert_eq!(second.metadata()["author"], "bob");
+    }
+    Ok(())
+}
+
+#[test]
+fn import_and_checkout_in_slices() -> LoroResult<()> {
+    let a = LoroDoc::new();
+    a.set_peer_id(1)?;
+    let list = a.get_list("list");
+    for i in 0..100 {
+        list.push(i)?;
+    }
+    a.commit();
+    let v = a.oplog_frontiers();
+    a.get_map("map").insert("k", 1)?;
+    a.commit();
+
+    let b = LoroDoc::new();
+    b.import_in_slices(&a.export(ExportMode::all_updates()).unwrap())?;
+    assert!(b.has_pending_work());
+    assert!(b.get_list("list").is_empty());
+    let mut lens = Vec::new();
+    while b.poll_pending_work(40)? == loro::PollStatus::Pending {
+        lens.push(b.get_list("list").len());
+    }
+    assert_eq!(lens, vec![40, 80]);
+    assert!(!b.is_detached());
+    assert_eq!(b.get_deep_value(), a.get_deep_value());
+
+    b.checkout_in_slices(&v)?;
+    while b.poll_pending_work(40)? == loro::PollStatus::Pending {}
+    assert_eq!(b.state_frontiers(), v);
+    assert!(b.is_detached());
+    assert_eq!(b.get_list("list").len(), 100);
+    Ok(())
+}
+
+#[test]
+fn traverse_the_dag() -> LoroResult<()> {
+    let a = LoroDoc::new();
+    a.set_peer_id(1)?;
+    a.get_text("text").insert(0, "ab")?;
+    a.commit();
+    let b = a.fork();
+    b.set_peer_id(2)?;
+    b.get_text("text").insert(0, "c")?;
+    b.commit();
+    a.get_text("text").insert(0, "d")?;
+    a.commit();
+    a.import(&b.export(ExportMode::all_updates()).unwrap())?;
+    a.get_text("text").insert(0, "e")?;
+    a.commit();
+
+    let latest = a.oplog_frontiers();
+    a.with_oplog(|oplog| {
+        let dag = oplog.dag();
+        assert_eq!(dag.children_of(ID::new(1, 0)), vec![ID::new(1, 1)]);
+        assert_eq!(
+            dag.children_of(ID::new(1, 1)),
+            vec![ID::new(1, 2), ID::new(2, 0)]
+        );
+        assert!(dag.children_of(ID::new(1, 3)).is_empty());
+        assert_eq!(
+            dag.parents_of(ID::new(1, 3)),
+            Some(Frontiers::from(vec![ID::new(1, 2), ID::new(2, 0)]))
+        );
+        assert_eq!(dag.parents_of(ID::new(1, 0)), Some(Frontiers::default()));
+        assert_eq!(dag.parents_of(ID::new(3, 0)), None);
+
+        let nodes: Vec<_> = dag
+            .iter_dag_nodes_between(&ID::new(1, 0).into(), &latest)
+            .unwrap()
+            .map(|n| (n.id, n.lamport, n.len))
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                (ID::new(1, 1), 1, 1),
+                (ID::new(1, 2), 2, 1),
+                (ID::new(2, 0), 2, 1),
+                (ID::new(1, 3), 3, 1),
+            ]
+        );
+    });
+    Ok(())
+}
+
+#[test]
+fn exports_are_identical_for_identical_oplogs() {
+    // The same map with different hash map layouts, which iterate in different orders
+    let map_value = |capacity: usize| {
+        let mut map = fxhash::FxHashMap::with_capacity_and_hasher(capacity, Default::default());
+        for i in 0..20 {
+            map.insert(format!("key{i}"), LoroValue::from(i));
+        }
+        LoroValue::Map(loro::LoroMapValue::from(map))
+    };
+    let new_doc = |capacity: usize| {
+        let doc = LoroDoc::new();
+        doc.set_peer_id(1).unwrap();
+        doc.get_map("map")
+            .insert("value", map_value(capacity))
+            .unwrap();
+        doc.get_list("list").push(map_value(capacity)).unwrap();
+        doc.commit();
+        doc
+    };
+    let a = new_doc(0);
+    let b = new_doc(4096);
+    assert_eq!(
+        a.export(ExportMode::Snapshot).unwrap(),
+        b.export(ExportMode::Snapshot).unwrap()
+    );
+    assert_eq!(
+        a.export(ExportMode::all_updates()).unwrap(),
+        b.export(ExportMode::all_updates()).unwrap()
+    );
+
+    // The same changes imported in different orders
+    let peers: Vec<LoroDoc> = (0..20)
+        .map(|i| {
+            let doc = LoroDoc::new();
+            doc.set_peer_id(100 + i).unwrap();
+            doc.get_text("text").insert(0, "x").unwrap();
+            doc.commit();
+            doc
+        })
+        .collect();
+    let c = LoroDoc::new();
+    let d = LoroDoc::new();
+    for peer in peers.iter() {
+        c.import(&peer.export(ExportMode::all_updates()).unwrap())
+            .unwrap();
+    }
+    for peer in peers.iter().rev() {
+        d.import(&peer.export(ExportMode::all_updates()).unwrap())
+            .unwrap();
+    }
+    assert_eq!(
+        c.export(ExportMode::all_updates()).unwrap(),
+        d.export(ExportMode::all_updates()).unwrap()
+    );
+    assert_eq!(c.oplog_vv().encode(), d.oplog_vv().encode());
+    let json = |doc: &LoroDoc| {
+        serde_json::to_string(&doc.export_json_updates(&Default::default(), &doc.oplog_vv()))
+            .unwrap()
+    };
+    assert_eq!(json(&c), json(&d));
+}
+
+#[test]
+fn numeric_list_delete_across_runs() -> LoroResult<()> {
+    let doc = LoroDoc::new();
+    doc.set_peer_id(1)?;
+    let list = doc.get_list("values");
+    list.push_slice(&[0.5, 1.5, 2.5])?;
+    list.push_slice(&[3i64, 4, 5])?;
+    doc.commit();
+    let vv = doc.oplog_vv();
+    // The deleted range spans the end of the f64 run and the start of the i64 run
+    list.delete(1, 4)?;
+    doc.commit();
+    assert_eq!(list.get_value().to_json_value(), json!([0.5, 5]));
+    let json = doc.export_json_updates(&vv, &doc.oplog_vv());
+    assert_eq!(json.changes.len(), 1);
+    assert_eq!(json.changes[0].ops.len(), 1);
+
+    list.set_range(1, &[6i64, 7])?;
+    doc.commit();
+    assert_eq!(list.to_i64_vec(), None);
+    list.delete(0, 1)?;
+    assert_eq!(list.to_i64_vec(), Some(vec![6, 7]));
+
+    let snapshot = LoroDoc::new();
+    snapshot.import(&doc.export(ExportMode::Snapshot).unwrap())?;
+    assert_eq!(snapshot.get_deep_value(), doc.get_deep_value());
+    Ok(())
+}